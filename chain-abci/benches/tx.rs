@@ -11,7 +11,7 @@ use chain_core::init::config::InitNetworkParameters;
 use chain_core::init::config::{InitialValidator, ValidatorKeyType};
 use chain_core::init::{address::RedeemAddress, coin::Coin, config::InitConfig};
 use chain_core::state::account::*;
-use chain_core::tx::fee::{LinearFee, Milli};
+use chain_core::tx::fee::{FeePolicy, LinearFee, Milli};
 use chain_core::tx::witness::EcdsaSignature;
 use chain_core::tx::witness::TxInWitness;
 use chain_core::tx::TransactionId;
@@ -86,11 +86,12 @@ fn init_chain_for(addresses: &Vec<RedeemAddress>) -> ChainNodeApp {
     );
 
     let params = InitNetworkParameters {
-        initial_fee_policy: LinearFee::new(
+        initial_fee_policy: FeePolicy::Linear(LinearFee::new(
             Milli::try_new(1, 1).unwrap(),
             Milli::try_new(1, 1).unwrap(),
-        ),
+        )),
         required_council_node_stake: remaining,
+        required_community_node_stake: remaining,
         unbonding_period: 1,
     };
     let c = InitConfig::new(
@@ -13,6 +13,7 @@ use chain_core::init::coin::Coin;
 use chain_core::init::config::InitConfig;
 use chain_core::init::config::NetworkParameters;
 use chain_core::state::tendermint::{TendermintValidatorPubKey, TendermintVotePower};
+use chain_core::state::ChainState;
 use chain_storage::{Storage, NUM_COLUMNS};
 use kvdb::KeyValueDB;
 use kvdb_memorydb::create;
@@ -50,6 +51,7 @@ const TEST_GENESIS: &str = "{
         \"coefficient\": 1001
       },
       \"required_council_node_stake\": \"1\",
+      \"required_community_node_stake\": \"1\",
       \"jailing_config\": {
         \"block_signing_window\": 100,
         \"missed_block_threshold\": 50
@@ -169,7 +171,12 @@ fuzz_target!(|data: &[u8]| {
                                     req.validators.clone().into_vec(),
                                     &c.distribution,
                                 );
-                                if r.is_err() {
+                                let cp = check_consensus_params(
+                                    req.get_consensus_params(),
+                                    &state.validators,
+                                    &network_params,
+                                );
+                                if r.is_err() || cp.is_err() {
                                     defaultinit
                                 } else {
                                     let tx_tree = MerkleTree::empty();
@@ -178,9 +185,12 @@ fuzz_target!(|data: &[u8]| {
 
                                     let genesis_app_hash = compute_app_hash(
                                         &tx_tree,
-                                        &new_account_root,
-                                        &state.rewards_pool,
-                                        &network_params,
+                                        &ChainState::genesis(
+                                            new_account_root,
+                                            state.rewards_pool.clone(),
+                                            network_params.clone(),
+                                            state.app_version,
+                                        ),
                                     );
                                     if req.chain_id.len() > 3 {
                                         if let Ok(netid) =
@@ -1,17 +1,24 @@
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryInto;
 use std::mem;
+use std::thread::JoinHandle;
 
 use abci::*;
-use log::{info, warn};
+use log::info;
 use parity_scale_codec::{Decode, Encode};
 use protobuf::Message;
 use serde::{Deserialize, Serialize};
 
+use crate::app::migration::storage_migrations;
+use crate::app::upgrade::check_upgrade_halt;
 #[cfg(all(not(feature = "mock-enclave"), target_os = "linux"))]
 use crate::enclave_bridge::real::start_zmq;
 use crate::enclave_bridge::EnclaveProxy;
+use crate::governance::ProposalTable;
+use crate::peer_filter::PeerFilter;
+use crate::snapshot::{SnapshotRestore, SnapshotStore};
 use crate::staking::StakingTable;
+use crate::tdbe::TdbeGroupState;
 use chain_core::common::MerkleTree;
 use chain_core::common::Timespec;
 use chain_core::common::{H256, HASH_SIZE_256};
@@ -22,14 +29,19 @@ use chain_core::init::config::InitConfig;
 use chain_core::init::config::NetworkParameters;
 use chain_core::state::account::StakedStateDestination;
 use chain_core::state::account::{CouncilNodeMeta, StakedStateAddress};
+use chain_core::state::governance::UpgradePlan;
 use chain_core::state::tendermint::{BlockHeight, TendermintVotePower};
-use chain_core::state::{ChainState, RewardsPoolState};
+use chain_core::state::ChainState;
+use chain_core::tx::fee::FeeInput;
 use chain_core::tx::TxAux;
 use chain_core::ChainInfo;
 use chain_storage::buffer::{
-    flush_storage, GetStaking, KVBuffer, StakingBuffer, StoreKV, StoreStaking,
+    flush_storage, GetKV, GetStaking, KVBuffer, StakingBuffer, StoreKV, StoreStaking,
 };
-use chain_storage::jellyfish::{compute_staking_root, sum_staking_coins, StakingGetter, Version};
+use chain_storage::jellyfish::{
+    compute_staking_root, restore_spent_outputs, sum_staking_coins, StakingGetter, Version,
+};
+use chain_storage::pruning::PruningMode;
 use chain_storage::{Storage, StoredChainState};
 
 /// ABCI app state snapshot
@@ -57,9 +69,19 @@ pub struct ChainNodeState {
     /// Record the biggest enclave ISVSVN (Security Version Number of the Enclave) we've seen in
     /// keypackage so far
     pub enclave_isv_svn: u16,
+    /// Public view of the TDBE MLS group, updated by the MLS handshake txs
+    #[serde(skip)]
+    pub tdbe_group: Option<TdbeGroupState>,
+    /// Network parameter change proposals waiting for their activation height
+    #[serde(skip)]
+    pub proposals: ProposalTable,
 
     /// The parts of states which involved in computing app_hash
+    /// (and the app version active on the network, which is bumped by the scheduled upgrades)
     pub top_level: ChainState,
+
+    /// Network upgrade scheduled from genesis or by an approved proposal
+    pub upgrade_plan: Option<UpgradePlan>,
 }
 
 impl StoredChainState for ChainNodeState {
@@ -81,16 +103,14 @@ impl StoredChainState for ChainNodeState {
 }
 
 impl ChainNodeState {
-    #[allow(clippy::too_many_arguments)]
     pub fn genesis(
         genesis_apphash: H256,
         genesis_time: Timespec,
         max_evidence_age: Timespec,
-        account_root: H256,
-        rewards_pool: RewardsPoolState,
-        network_params: NetworkParameters,
+        top_level: ChainState,
         staking_table: StakingTable,
         enclave_isv_svn: u16,
+        upgrade_plan: Option<UpgradePlan>,
     ) -> Self {
         ChainNodeState {
             last_block_height: BlockHeight::genesis(),
@@ -103,17 +123,26 @@ impl ChainNodeState {
             staking_version: 0,
             utxo_coins: Coin::zero(),
             enclave_isv_svn,
-            top_level: ChainState {
-                account_root,
-                rewards_pool,
-                network_params,
-            },
+            tdbe_group: None,
+            proposals: ProposalTable::default(),
+            top_level,
+            upgrade_plan,
         }
     }
 
     pub fn get_unbonding_period(&self) -> Timespec {
         self.max_evidence_age
     }
+
+    /// decodes the stored state, which must take up all the data
+    /// (otherwise it was written in a different layout)
+    pub fn decode_stored(mut data: &[u8]) -> Result<Self, parity_scale_codec::Error> {
+        let state = Self::decode(&mut data)?;
+        if !data.is_empty() {
+            return Err("Unexpected trailing data after the node state".into());
+        }
+        Ok(state)
+    }
 }
 
 /// Two types of storage buffer
@@ -142,6 +171,16 @@ pub struct ChainNodeApp<T: EnclaveProxy> {
     pub rewards_pool_updated: bool,
     /// address of tx query enclave to supply to clients (if any)
     pub tx_query_address: Option<String>,
+    /// how much of the staking trie history is kept
+    pub pruning: PruningMode,
+    /// where (and how often) the state snapshots are taken (if enabled)
+    pub snapshots: Option<SnapshotStore>,
+    /// the state snapshot being restored (if any)
+    pub snapshot_restore: Option<SnapshotRestore>,
+    /// the state snapshot being taken in the background (if any)
+    pub snapshot_worker: Option<JoinHandle<()>>,
+    /// which peers Tendermint may connect to
+    pub peer_filter: PeerFilter,
 
     /// consensus buffer of staking merkle trie storage
     pub staking_buffer: StakingBuffer,
@@ -161,30 +200,113 @@ pub fn get_validator_key(node: &CouncilNodeMeta) -> PubKey {
     pk
 }
 
-fn check_and_store_consensus_params(
-    init_consensus_params: Option<&ConsensusParams>,
-    _validators: &[(StakedStateAddress, CouncilNodeMeta)],
-    _network_params: &NetworkParameters,
-    storage: &mut Storage,
-) {
-    match init_consensus_params {
-        Some(cp) => {
-            // TODO: check validators only used allowed key types
-            // TODO: check unbonding period == cp.evidence.max_age
-            // NOTE: cp.evidence.max_age is currently in the number of blocks
-            // but it should be migrated to "time", in which case this check will make sense
-            // (as unbonding time is in seconds, not blocks)
-            warn!("consensus parameters not checked (TODO)");
-            storage.store_consensus_params(
-                &(cp as &dyn Message)
-                    .write_to_bytes()
-                    .expect("consensus params"),
-            );
+/// The validator key type allowed by Tendermint if the consensus parameters don't specify any
+const DEFAULT_PUB_KEY_TYPE: &str = "ed25519";
+
+/// The consensus parameters accepted in InitChain (returned by the "state" query)
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct GenesisConsensusParams {
+    /// max block size in bytes (0 if not specified)
+    pub block_max_bytes: i64,
+    /// max gas per block (-1 or 0 if not limited)
+    pub block_max_gas: i64,
+    /// max age of evidence (and the unbonding period) in seconds
+    pub max_evidence_age: Timespec,
+    /// max age of evidence in blocks
+    pub max_evidence_age_blocks: i64,
+    /// the key types the validators can use
+    pub validator_pub_key_types: Vec<String>,
+}
+
+impl From<&ConsensusParams> for GenesisConsensusParams {
+    fn from(cp: &ConsensusParams) -> Self {
+        let evidence = cp.get_evidence();
+        let pub_key_types = cp.get_validator().get_pub_key_types();
+        GenesisConsensusParams {
+            block_max_bytes: cp.get_block().get_max_bytes(),
+            block_max_gas: cp.get_block().get_max_gas(),
+            max_evidence_age: evidence
+                .get_max_age_duration()
+                .get_seconds()
+                .try_into()
+                .unwrap_or(0),
+            max_evidence_age_blocks: evidence.get_max_age_num_blocks(),
+            validator_pub_key_types: if pub_key_types.is_empty() {
+                vec![DEFAULT_PUB_KEY_TYPE.to_owned()]
+            } else {
+                pub_key_types.to_vec()
+            },
         }
-        None => {
-            info!("consensus params not in the initchain request");
+    }
+}
+
+/// the max evidence age in blocks in the consensus parameters accepted in InitChain
+/// (0 before InitChain)
+pub fn get_max_evidence_age_blocks(storage: &impl GetKV) -> u64 {
+    chain_storage::get_consensus_params(storage)
+        .and_then(|value| protobuf::parse_from_bytes::<ConsensusParams>(&value).ok())
+        .and_then(|cp| cp.get_evidence().get_max_age_num_blocks().try_into().ok())
+        .unwrap_or(0)
+}
+
+/// checks InitChain's consensus parameters are consistent with the genesis configuration:
+/// * evidence needs to be accepted for the whole unbonding period
+///   (which is the max evidence age in whole seconds)
+/// * evidence needs to be accepted for at least the jailing block signing window
+///   (so that the faults within the window can still be punished)
+/// * the genesis validators' keys and the keys in the node join transactions (ed25519) are allowed
+pub fn check_consensus_params(
+    cp: &ConsensusParams,
+    validators: &[(StakedStateAddress, CouncilNodeMeta)],
+    network_params: &NetworkParameters,
+) -> Result<GenesisConsensusParams, String> {
+    let duration = cp.get_evidence().get_max_age_duration();
+    if !cp.get_evidence().has_max_age_duration() || duration.get_seconds() <= 0 {
+        return Err("evidence max age duration (the unbonding period) must be positive".to_owned());
+    }
+    if duration.get_nanos() != 0 {
+        return Err(
+            "evidence max age duration must be in whole seconds (as the unbonding period)"
+                .to_owned(),
+        );
+    }
+    if cp.get_evidence().get_max_age_num_blocks() < 0 {
+        return Err("evidence max age in blocks can't be negative".to_owned());
+    }
+    let block_signing_window = network_params.get_block_signing_window();
+    if cp.get_evidence().get_max_age_num_blocks() < i64::from(block_signing_window) {
+        return Err(format!(
+            "evidence max age in blocks ({}) is shorter than the jailing block signing window ({})",
+            cp.get_evidence().get_max_age_num_blocks(),
+            block_signing_window
+        ));
+    }
+    if cp.has_block() && cp.get_block().get_max_bytes() <= 0 {
+        return Err("block max bytes must be positive".to_owned());
+    }
+    let params = GenesisConsensusParams::from(cp);
+    let is_allowed = |keytype: &str| {
+        params
+            .validator_pub_key_types
+            .iter()
+            .any(|allowed| allowed == keytype)
+    };
+    if !is_allowed(DEFAULT_PUB_KEY_TYPE) {
+        return Err(format!(
+            "validator key types {:?} don't include {} (used by the council nodes)",
+            params.validator_pub_key_types, DEFAULT_PUB_KEY_TYPE
+        ));
+    }
+    for (address, node) in validators.iter() {
+        let keytype = get_validator_key(node).field_type;
+        if !is_allowed(&keytype) {
+            return Err(format!(
+                "the key type of the validator {} ({}) isn't allowed in the consensus params: {:?}",
+                address, keytype, params.validator_pub_key_types
+            ));
         }
     }
+    Ok(params)
 }
 
 /// checks InitChain's req.validators is consistent with InitChain's app_state's council nodes
@@ -234,9 +356,12 @@ pub fn init_app_hash(conf: &InitConfig, genesis_time: Timespec) -> H256 {
 
     compute_app_hash(
         &MerkleTree::empty(),
-        &compute_staking_root(&state.accounts),
-        &state.rewards_pool,
-        &NetworkParameters::Genesis(conf.network_params.clone()),
+        &ChainState::genesis(
+            compute_staking_root(&state.accounts),
+            state.rewards_pool,
+            NetworkParameters::Genesis(conf.network_params.clone()),
+            state.app_version,
+        ),
     )
 }
 
@@ -278,6 +403,11 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
             tx_validator,
             rewards_pool_updated: false,
             tx_query_address,
+            pruning: PruningMode::default(),
+            snapshots: None,
+            snapshot_restore: None,
+            snapshot_worker: None,
+            peer_filter: PeerFilter::default(),
 
             staking_buffer: HashMap::new(),
             mempool_staking_buffer: HashMap::new(),
@@ -311,6 +441,12 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
         let chain_hex_id = hex::decode(&chain_id[chain_id.len() - 2..])
             .expect("failed to decode two last hex digits in chain ID")[0];
 
+        // upgrade the storage written by the previous versions before anything is read from it
+        let schema_version = storage_migrations()
+            .run(&mut storage)
+            .unwrap_or_else(|e| panic!("storage schema check failed: {}", e));
+        info!("storage schema version: {}", schema_version);
+
         if let (Some(_), Some(_conn_str)) = (tx_query_address.as_ref(), enclave_server.as_ref()) {
             #[cfg(all(not(feature = "mock-enclave"), target_os = "linux"))]
             let _ = start_zmq(_conn_str, chain_hex_id, storage.get_read_only());
@@ -318,8 +454,9 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
 
         if let Some(data) = storage.get_last_app_state() {
             info!("last app state stored");
+            check_upgrade_halt(&storage);
             let mut last_state =
-                ChainNodeState::decode(&mut data.as_slice()).expect("deserialize app state");
+                ChainNodeState::decode_stored(&data).expect("deserialize app state");
 
             // if tx-query address wasn't provided first time,
             // then it shouldn't be provided on another run, and vice versa
@@ -349,12 +486,14 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
             }
 
             // populate the indexing structures in staking table.
+            let network_params = &last_state.top_level.network_params;
+            let minimal_required_staking = network_params.get_required_council_node_stake();
+            let minimal_required_community_staking =
+                network_params.get_required_community_node_stake();
             last_state.staking_table.initialize(
                 &StakingGetter::new(&storage, last_state.staking_version),
-                last_state
-                    .top_level
-                    .network_params
-                    .get_required_council_node_stake(),
+                minimal_required_staking,
+                minimal_required_community_staking,
             );
             ChainNodeApp::restore_from_storage(
                 tx_validator,
@@ -387,6 +526,11 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
                 tx_validator,
                 rewards_pool_updated: false,
                 tx_query_address,
+                pruning: PruningMode::default(),
+                snapshots: None,
+                snapshot_restore: None,
+                snapshot_worker: None,
+                peer_filter: PeerFilter::default(),
 
                 staking_buffer: HashMap::new(),
                 mempool_staking_buffer: HashMap::new(),
@@ -400,18 +544,6 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
     /// should validate initial genesis distribution, initialize everything in the key-value DB and check it matches the expected values
     /// provided as arguments.
     pub fn init_chain_handler(&mut self, req: &RequestInitChain) -> ResponseInitChain {
-        let max_evidence_age = req
-            .consensus_params
-            .as_ref()
-            .and_then(|params| {
-                params.evidence.as_ref().and_then(|evidence| {
-                    evidence
-                        .max_age_duration
-                        .as_ref()
-                        .and_then(|duration| duration.seconds.try_into().ok())
-                })
-            })
-            .expect("No valid max_evidence_age");
         let conf: InitConfig =
             serde_json::from_slice(&req.app_state_bytes).expect("failed to parse initial config");
 
@@ -437,22 +569,29 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
 
         let network_params = NetworkParameters::Genesis(conf.network_params);
         let new_account_root = self.storage.put_stakings(0, &state.accounts);
-        let genesis_app_hash = compute_app_hash(
-            &MerkleTree::empty(),
-            &new_account_root,
-            &state.rewards_pool,
-            &network_params,
+        let top_level = ChainState::genesis(
+            new_account_root,
+            state.rewards_pool,
+            network_params,
+            state.app_version,
         );
+        let genesis_app_hash = compute_app_hash(&MerkleTree::empty(), &top_level);
 
         if self.genesis_app_hash != genesis_app_hash {
             panic!("initchain resulting genesis app hash: {} does not match the expected genesis app hash: {}", hex::encode(genesis_app_hash), hex::encode(self.genesis_app_hash));
         }
 
-        check_and_store_consensus_params(
-            req.consensus_params.as_ref(),
-            &state.validators,
-            &network_params,
-            &mut self.storage,
+        let cp = req
+            .consensus_params
+            .as_ref()
+            .expect("consensus params not in the initchain request");
+        let consensus_params = check_consensus_params(cp, &state.validators, &network_params)
+            .unwrap_or_else(|e| panic!("invalid consensus params: {}", e));
+        info!("accepted consensus params: {:?}", consensus_params);
+        self.storage.store_consensus_params(
+            &(cp as &dyn Message)
+                .write_to_bytes()
+                .expect("consensus params"),
         );
 
         check_validators(
@@ -469,26 +608,41 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
             .collect::<Vec<_>>();
         let staking_table = StakingTable::from_genesis(
             &staking_getter!(self, 0),
-            network_params.get_required_council_node_stake(),
-            network_params.get_max_validators(),
+            top_level.network_params.get_required_council_node_stake(),
+            top_level.network_params.get_required_community_node_stake(),
+            top_level.network_params.get_max_validators(),
             &val_addresses,
         );
 
-        let genesis_state = ChainNodeState::genesis(
+        let mut genesis_state = ChainNodeState::genesis(
             genesis_app_hash,
             genesis_time,
-            max_evidence_age,
-            new_account_root,
-            state.rewards_pool,
-            network_params,
+            consensus_params.max_evidence_age,
+            top_level,
             staking_table,
             state.isv_svn,
+            state.upgrade_plan,
+        );
+        info!(
+            "genesis app version: {}",
+            genesis_state.top_level.app_version
         );
+        if let Some(plan) = genesis_state.upgrade_plan.as_ref() {
+            info!("upgrade scheduled in genesis: {:?}", plan);
+        }
+        genesis_state.tdbe_group = TdbeGroupState::genesis(&state.validators);
         chain_storage::store_genesis_state(
             &mut kv_store!(self),
             &genesis_state,
             self.tx_query_address.is_some(),
         );
+        // nothing is spent in genesis, the following blocks build on the empty trie
+        restore_spent_outputs(
+            &mut kv_store!(self),
+            BlockHeight::genesis(),
+            std::iter::empty(),
+        )
+        .expect("merkle trie io error");
         flush_storage(&mut self.storage, mem::take(&mut self.kv_buffer)).expect("storage io error");
 
         self.last_state = Some(genesis_state);
@@ -528,12 +682,12 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
         kv_store!(self, buffer_type)
     }
 
-    pub fn tx_extra_info(&self, tx_len: usize) -> ChainInfo {
+    pub fn tx_extra_info(&self, fee_input: &FeeInput) -> ChainInfo {
         let state = self.last_state.as_ref().expect("the app state is expected");
         let min_fee = state
             .top_level
             .network_params
-            .calculate_fee(tx_len)
+            .calculate_fee(fee_input, state.top_level.block_fullness)
             .expect("invalid fee policy");
         ChainInfo {
             min_fee_computed: min_fee,
@@ -541,13 +695,15 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
             block_time: state.block_time,
             block_height: state.block_height,
             max_evidence_age: state.max_evidence_age,
+            max_evidence_age_blocks: get_max_evidence_age_blocks(&self.storage),
+            app_version: state.top_level.app_version,
         }
     }
 
     /// Double check the circulating coins
     ///
     /// - utxo_coins = withdraw - deposit - transfer tx fee
-    /// - utxo_coins + staking + reward_pool = init_dist + minted
+    /// - utxo_coins + staking + reward_pool + burned fees = init_dist + minted
     /// - init_dist = Coin::max() - expansion_cap  -- checked at init chain
     pub fn check_circulating_coins(&self) -> Coin {
         let state = self.last_state.as_ref().expect("expect last_state");
@@ -559,6 +715,7 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
         let total1 = ((state.utxo_coins + staking).unwrap()
             + state.top_level.rewards_pool.period_bonus)
             .unwrap();
+        let total1 = (total1 + state.top_level.rewards_pool.fees_burned).unwrap();
 
         let init_dist = (Coin::max()
             - state
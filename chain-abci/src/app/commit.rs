@@ -1,3 +1,4 @@
+use std::collections::BTreeSet;
 use std::mem;
 
 use super::ChainNodeApp;
@@ -8,41 +9,56 @@ use chain_core::compute_app_hash;
 use chain_core::tx::data::input::{TxoPointer, TxoSize};
 use chain_core::tx::data::TxId;
 use chain_core::tx::{TxAux, TxEnclaveAux, TxPublicAux};
+use chain_core::TxCreation;
 use chain_storage::buffer::{flush_storage, StoreKV};
-use chain_storage::jellyfish::flush_stakings;
+use chain_storage::jellyfish::{flush_stakings, put_spent_outputs};
 use parity_scale_codec::Encode;
 
 /// Given a db and a DB transaction, it will go through TX inputs and mark them as spent
-/// in the TX_META storage and it will create a new entry for TX in TX_META with all outputs marked as unspent.
+/// (in the block being committed) in the TX_META storage
+/// and it will create a new entry for TX in TX_META with all outputs marked as unspent.
 fn update_utxos_commit(
     inputs: &[TxoPointer],
     no_of_outputs: TxoSize,
     txid: TxId,
+    created: &TxCreation,
     db: &mut impl StoreKV,
 ) {
     chain_storage::spend_utxos(db, &inputs);
-    chain_storage::create_utxo(db, no_of_outputs, &txid);
+    chain_storage::store_spent_height(db, &inputs, created.block_height);
+    chain_storage::create_utxo(db, no_of_outputs, &txid, created);
 }
 
-fn process_txs(delivered_txs: &[TxAux], db: &mut impl StoreKV) {
+/// the outputs spent by the transaction
+fn spent_inputs(txaux: &TxAux) -> &[TxoPointer] {
+    match txaux {
+        TxAux::EnclaveTx(TxEnclaveAux::TransferTx { inputs, .. }) => &inputs[..],
+        TxAux::EnclaveTx(TxEnclaveAux::DepositStakeTx { tx, .. }) => &tx.inputs[..],
+        _ => &[],
+    }
+}
+
+fn process_txs(delivered_txs: &[TxAux], created: &TxCreation, db: &mut impl StoreKV) {
     for txaux in delivered_txs.iter() {
         let txid: TxId = txaux.tx_id();
         match &txaux {
-            TxAux::MLSHandshake(_) => {
-                // FIXME
+            TxAux::MLSHandshake(tx) => {
+                chain_storage::store_tx_body(db, &txid, &tx.encode());
+                // group state updated in deliver_tx (and persisted with the chain node state)
             }
             TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
                 inputs,
                 no_of_outputs,
                 ..
             }) => {
-                update_utxos_commit(&inputs, *no_of_outputs, txid, db);
+                update_utxos_commit(&inputs, *no_of_outputs, txid, created, db);
             }
             TxAux::EnclaveTx(TxEnclaveAux::DepositStakeTx { tx, .. }) => {
                 chain_storage::store_tx_body(db, &txid, &tx.encode());
                 // witness is obfuscated -- TODO: could be stored on the enclave side or thrown away?
                 // this is not necessary (as they are spent in deliver_tx) and more of a sanity check (as update_utxos_commit does it)
                 chain_storage::spend_utxos(db, &tx.inputs);
+                chain_storage::store_spent_height(db, &tx.inputs, created.block_height);
                 // account should be already updated in deliver_tx
             }
             TxAux::PublicTx(TxPublicAux::UnbondStakeTx(tx, witness)) => {
@@ -57,7 +73,7 @@ fn process_txs(delivered_txs: &[TxAux], db: &mut impl StoreKV) {
             }) => {
                 chain_storage::store_tx_witness(db, &txid, &witness.encode());
                 // account should be already updated in deliver_tx
-                chain_storage::create_utxo(db, *no_of_outputs, &txid);
+                chain_storage::create_utxo(db, *no_of_outputs, &txid, created);
             }
             TxAux::PublicTx(TxPublicAux::UnjailTx(tx, witness)) => {
                 chain_storage::store_tx_body(db, &txid, &tx.encode());
@@ -70,6 +86,38 @@ fn process_txs(delivered_txs: &[TxAux], db: &mut impl StoreKV) {
                 // staked state updated in deliver_tx
                 // validator state updated in end_block
             }
+            TxAux::PublicTx(TxPublicAux::ParamChangeProposalTx(tx, witness)) => {
+                chain_storage::store_tx_body(db, &txid, &tx.encode());
+                chain_storage::store_tx_witness(db, &txid, &witness.encode());
+                // proposal recorded in deliver_tx, tallied in end_block
+            }
+            TxAux::PublicTx(TxPublicAux::ParamChangeVoteTx(tx, witness)) => {
+                chain_storage::store_tx_body(db, &txid, &tx.encode());
+                chain_storage::store_tx_witness(db, &txid, &witness.encode());
+                // vote recorded in deliver_tx, tallied in end_block
+            }
+            TxAux::PublicTx(TxPublicAux::DelegateTx(tx, witness)) => {
+                chain_storage::store_tx_body(db, &txid, &tx.encode());
+                chain_storage::store_tx_witness(db, &txid, &witness.encode());
+                // staked states (delegator and council node) updated in deliver_tx
+            }
+            TxAux::PublicTx(TxPublicAux::UndelegateTx(tx, witness)) => {
+                chain_storage::store_tx_body(db, &txid, &tx.encode());
+                chain_storage::store_tx_witness(db, &txid, &witness.encode());
+                // staked states (delegator and council node) updated in deliver_tx
+            }
+            TxAux::PublicTx(TxPublicAux::NodeMetadataUpdateTx(tx, witness)) => {
+                chain_storage::store_tx_body(db, &txid, &tx.encode());
+                chain_storage::store_tx_witness(db, &txid, &witness.encode());
+                // staked state updated in deliver_tx
+                // validator key rotation handled in end_block
+            }
+            TxAux::PublicTx(TxPublicAux::NodeLeaveTx(tx, witness)) => {
+                chain_storage::store_tx_body(db, &txid, &tx.encode());
+                chain_storage::store_tx_witness(db, &txid, &witness.encode());
+                // staked state updated in deliver_tx
+                // validator removed in end_block, node record cleaned up after unbonding period
+            }
         }
     }
 }
@@ -89,15 +137,38 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
         let tree = MerkleTree::new(ids);
 
         if !self.delivered_txs.is_empty() {
-            process_txs(&self.delivered_txs, &mut kv_store!(self));
+            let created = TxCreation {
+                block_height: new_state.block_height,
+                block_time: new_state.block_time,
+            };
+            process_txs(&self.delivered_txs, &created, &mut kv_store!(self));
         }
+        // every block has a version of the spent outputs trie (even if nothing was spent)
+        let spent = self
+            .delivered_txs
+            .iter()
+            .flat_map(spent_inputs)
+            .collect::<BTreeSet<_>>();
+        top_level.spent_outputs_root = put_spent_outputs(
+            &mut kv_store!(self),
+            new_state.last_block_height,
+            spent.into_iter().cloned(),
+        )
+        .expect("merkle trie io error");
+        let block_size: usize = self
+            .delivered_txs
+            .iter()
+            .map(|txaux| txaux.encode().len())
+            .sum();
+        top_level.block_fullness.update(block_size as u64);
         if self.rewards_pool_updated {
             top_level.rewards_pool.last_block_height = new_state.last_block_height;
             self.rewards_pool_updated = false;
         }
 
         // flush staking storage
-        if !self.staking_buffer.is_empty() {
+        let staking_updated = !self.staking_buffer.is_empty();
+        if staking_updated {
             new_state.staking_version = new_state
                 .staking_version
                 .checked_add(1)
@@ -110,12 +181,9 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
             .expect("merkle trie io error");
         }
 
-        let app_hash = compute_app_hash(
-            &tree,
-            &top_level.account_root,
-            &top_level.rewards_pool,
-            &top_level.network_params,
-        );
+        top_level.proposals_hash = new_state.proposals.hash();
+
+        let app_hash = compute_app_hash(&tree, top_level);
         new_state.last_apphash = app_hash;
 
         chain_storage::store_txs_merkle_tree(&mut kv_store!(self), &app_hash, &tree.encode());
@@ -127,8 +195,15 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
         );
 
         // flush key-value storage
+        self.wait_for_snapshot();
         flush_storage(&mut self.storage, mem::take(&mut self.kv_buffer))
             .expect("kv storage io error");
+        // pruning only depends on the staking version
+        if staking_updated {
+            self.storage
+                .prune_staking_versions(self.pruning, new_state.staking_version)
+                .expect("kv storage io error");
+        }
 
         resp.data = new_state.last_apphash.to_vec();
 
@@ -136,6 +211,7 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
         self.delivered_txs.clear();
         self.mempool_kv_buffer.clear();
         self.mempool_staking_buffer.clear();
+        self.spawn_scheduled_snapshot();
         resp
     }
 }
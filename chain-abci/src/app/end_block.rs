@@ -1,9 +1,12 @@
 use std::convert::TryInto;
 
 use crate::app::app_init::ChainNodeApp;
+use crate::app::upgrade::schedule_upgrade;
 use crate::enclave_bridge::EnclaveProxy;
+use crate::governance::activate_changes;
 use abci::{Event, Pair as KVPair, PubKey, RequestEndBlock, ResponseEndBlock, ValidatorUpdate};
 use chain_core::common::TendermintEventType;
+use chain_core::state::governance::NetworkParameterUpdate;
 use chain_tx_filter::BlockFilter;
 use enclave_protocol::{IntraEnclaveRequest, IntraEnclaveResponseOk};
 
@@ -34,6 +37,40 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
         }
         // TODO: skipchain-based validator changes?
         let state = self.last_state.as_mut().expect("executing end block, but no app state stored (i.e. no initchain or recovery was executed)");
+        // network parameter changes take effect before the validator set is computed
+        let (outcomes, changes) = state.proposals.tally(
+            &staking_getter!(self, state.staking_version),
+            &state.staking_table,
+            state.block_height,
+        );
+        for outcome in outcomes.iter() {
+            log::info!(
+                "proposal {} tallied (yes: {}, no: {}), approved: {}",
+                hex::encode(&outcome.proposal_id),
+                outcome.yes,
+                outcome.no,
+                outcome.approved
+            );
+        }
+        // the scheduled upgrades are kept in the node state (they don't change the parameters)
+        let (upgrades, changes): (Vec<_>, Vec<_>) = changes
+            .into_iter()
+            .partition(|change| matches!(change, NetworkParameterUpdate::ScheduleUpgrade(_)));
+        for upgrade in upgrades.into_iter() {
+            if let NetworkParameterUpdate::ScheduleUpgrade(plan) = upgrade {
+                schedule_upgrade(state, plan);
+            }
+        }
+        if !changes.is_empty() {
+            state.top_level.network_params = activate_changes(
+                &mut staking_store!(self, state.staking_version),
+                &mut state.staking_table,
+                &state.top_level.network_params,
+                &changes,
+                state.block_time,
+                state.block_height,
+            );
+        }
         let val_updates = state.staking_table.end_block(
             &staking_getter!(self, state.staking_version),
             state.top_level.network_params.get_max_validators(),
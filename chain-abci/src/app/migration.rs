@@ -0,0 +1,500 @@
+//! The storage migration steps (one for each increment of
+//! `chain_storage::migration::CURRENT_SCHEMA_VERSION`) and the layouts of the previous schema versions:
+//!
+//! * version 1: the first recorded layout
+//! * version 2: the TDBE group and the open proposals hashes in the top level state
+//! * version 3: the app version in the top level state and the scheduled upgrade in the node state
+//! * version 4: the commission rate in the validator (instead of the council node metadata),
+//!   the p2p node ID in the node metadata and no community node jailing in the staked states
+//! * version 5: the staked states without the fields added after 0.5 (delegations, commission rate,
+//!   p2p node ID and community node inactivity) in the 0.5 layout
+//! * version 6: the merkle trie of spent outputs and its root in the top level state
+use std::collections::BTreeMap;
+
+use parity_scale_codec::{Decode, Encode, Error, Input};
+
+use super::app_init::ChainNodeState;
+use crate::governance::ProposalTable;
+use crate::staking::StakingTable;
+use crate::tdbe::TdbeGroupState;
+use chain_core::common::{Timespec, H256};
+use chain_core::init::coin::Coin;
+use chain_core::init::config::NetworkParameters;
+use chain_core::state::account::{
+    CommunityNode, ConfidentialInit, CouncilNodeMeta, NodeCommonInfo, NodeState, Nonce,
+    SlashRecord, StakedState, StakedStateAddress, Validator,
+};
+use chain_core::state::governance::UpgradePlan;
+use chain_core::state::tendermint::{BlockHeight, TendermintValidatorPubKey};
+use chain_core::state::{ChainState, RewardsPoolState};
+use chain_core::tx::fee::{BlockFullness, Milli};
+use chain_storage::buffer::{BufferStore, KVBuffer};
+use chain_storage::jellyfish::{
+    has_staking_version, iter_staking_blobs, put_staking_blobs, restore_spent_outputs, Version,
+};
+use chain_storage::migration::Migrations;
+use chain_storage::{
+    spent_outputs_in_bitset, Storage, COL_APP_STATES, COL_EXTRA, COL_NODE_INFO,
+    COL_STAKING_VERSIONS, COL_TX_META, LAST_STATE_KEY, PRUNED_HEIGHT_KEY,
+};
+
+/// The storage migration steps
+pub fn storage_migrations() -> Migrations {
+    let mut migrations = Migrations::default();
+    migrations
+        .register(
+            1,
+            "add the TDBE group and the proposals hashes to the top level states",
+            add_node_state_hashes,
+        )
+        .register(
+            2,
+            "add the app version to the top level states and the upgrade plan to the node state",
+            add_app_version_to_state,
+        )
+        .register(
+            3,
+            "re-encode the staked states in the merkle trie",
+            |storage, buffer| {
+                reencode_staked_states::<StakedStateV3, StakedStateV4>(storage, buffer, "version 3")
+            },
+        )
+        .register(
+            4,
+            "encode the staked states without the later fields in the 0.5 layout",
+            |storage, buffer| {
+                reencode_staked_states::<StakedStateV4, StakedState>(storage, buffer, "version 4")
+            },
+        )
+        .register(
+            5,
+            "add the merkle trie of spent outputs and its root to the top level state",
+            add_spent_outputs_root,
+        );
+    migrations
+}
+
+/// The fields of the node state before the top level state (the same in all schema versions)
+#[derive(Encode, Decode)]
+struct NodeStateHead {
+    last_block_height: BlockHeight,
+    last_apphash: H256,
+    block_time: Timespec,
+    block_height: BlockHeight,
+    staking_table: StakingTable,
+    genesis_time: Timespec,
+    max_evidence_age: Timespec,
+    staking_version: Version,
+    utxo_coins: Coin,
+    enclave_isv_svn: u16,
+    tdbe_group: Option<TdbeGroupState>,
+    proposals: ProposalTable,
+}
+
+/// The top level state in the schema version 1
+#[derive(Encode, Decode)]
+struct ChainStateV1 {
+    account_root: H256,
+    rewards_pool: RewardsPoolState,
+    network_params: NetworkParameters,
+    block_fullness: BlockFullness,
+}
+
+/// The top level state in the schema version 2
+#[derive(Encode, Decode)]
+struct ChainStateV2 {
+    v1: ChainStateV1,
+    tdbe_group_hash: H256,
+    proposals_hash: H256,
+}
+
+impl ChainStateV2 {
+    fn with_app_version(self, app_version: u64) -> ChainStateV5 {
+        ChainStateV5 {
+            v2: self,
+            app_version,
+        }
+    }
+}
+
+/// The top level state in the schema versions 3-5
+#[derive(Encode, Decode)]
+struct ChainStateV5 {
+    v2: ChainStateV2,
+    app_version: u64,
+}
+
+impl ChainStateV5 {
+    fn with_spent_outputs_root(self, spent_outputs_root: H256) -> ChainState {
+        ChainState {
+            account_root: self.v2.v1.account_root,
+            rewards_pool: self.v2.v1.rewards_pool,
+            network_params: self.v2.v1.network_params,
+            block_fullness: self.v2.v1.block_fullness,
+            tdbe_group_hash: self.v2.tdbe_group_hash,
+            proposals_hash: self.v2.proposals_hash,
+            app_version: self.app_version,
+            spent_outputs_root,
+        }
+    }
+}
+
+/// The node state in the schema versions 3-5
+#[derive(Encode, Decode)]
+struct NodeStateV5 {
+    head: NodeStateHead,
+    top_level: ChainStateV5,
+    upgrade_plan: Option<UpgradePlan>,
+}
+
+/// decodes the data in the layout of a previous schema version, which must take up all the data
+fn decode_all<T: Decode>(mut data: &[u8], what: &str) -> Result<T, String> {
+    let value = T::decode(&mut data).map_err(|e| format!("invalid {}: {}", what, e))?;
+    if !data.is_empty() {
+        return Err(format!("unexpected trailing data after the {}", what));
+    }
+    Ok(value)
+}
+
+/// re-encodes the historical top level states (stored if the tx query is enabled)
+fn migrate_historical_states<T: Decode, U: Encode>(
+    storage: &Storage,
+    buffer: &mut KVBuffer,
+    what: &str,
+    migrate: impl Fn(T) -> U,
+) -> Result<(), String> {
+    for (key, value) in storage.iter_column(COL_APP_STATES) {
+        let state = decode_all(&value, what)?;
+        buffer.insert(
+            (COL_APP_STATES, key.to_vec()),
+            Some(migrate(state).encode()),
+        );
+    }
+    Ok(())
+}
+
+/// Version 1 -> 2: the hashes of the TDBE group state and the open proposals
+/// (zero for the genesis group and if there are no proposals).
+/// The groups and proposals of the historical heights aren't stored, so their states get zero hashes
+/// (the node state hash isn't a part of the app hash in app version 1, which produced them).
+fn add_node_state_hashes(storage: &Storage, buffer: &mut KVBuffer) -> Result<(), String> {
+    if let Some(data) = storage.get_last_app_state() {
+        let (head, v1): (NodeStateHead, ChainStateV1) = decode_all(&data, "version 1 node state")?;
+        let tdbe_group_hash = match &head.tdbe_group {
+            Some(group) if group.epoch() > 0 => group.hash(),
+            _ => H256::default(),
+        };
+        let top_level = ChainStateV2 {
+            v1,
+            tdbe_group_hash,
+            proposals_hash: head.proposals.hash(),
+        };
+        buffer.insert(
+            (COL_NODE_INFO, LAST_STATE_KEY.to_vec()),
+            Some((head, top_level).encode()),
+        );
+    }
+    migrate_historical_states(storage, buffer, "version 1 historical state", |v1| {
+        ChainStateV2 {
+            v1,
+            tdbe_group_hash: H256::default(),
+            proposals_hash: H256::default(),
+        }
+    })
+}
+
+/// Version 2 -> 3: the states of the previous versions were produced by app version 1
+/// and had no scheduled upgrade.
+fn add_app_version_to_state(storage: &Storage, buffer: &mut KVBuffer) -> Result<(), String> {
+    if let Some(data) = storage.get_last_app_state() {
+        let (head, v2): (NodeStateHead, ChainStateV2) = decode_all(&data, "version 2 node state")?;
+        let state = NodeStateV5 {
+            head,
+            top_level: v2.with_app_version(1),
+            upgrade_plan: None,
+        };
+        buffer.insert(
+            (COL_NODE_INFO, LAST_STATE_KEY.to_vec()),
+            Some(state.encode()),
+        );
+    }
+    migrate_historical_states(
+        storage,
+        buffer,
+        "version 2 historical state",
+        |v2: ChainStateV2| v2.with_app_version(1),
+    )
+}
+
+/// Version 3 -> 4 and 4 -> 5: every staked state of the last staking version is re-encoded
+/// (from the layout `T` into the layout `U`).
+/// They are put into a new staking version, so the staking root in the last state changes
+/// (all the nodes need to migrate before the next block, e.g. at a scheduled upgrade).
+/// The staking versions of the committed heights can't be decoded anymore,
+/// so they are dropped and these heights are reported as pruned.
+fn reencode_staked_states<T, U>(
+    storage: &Storage,
+    buffer: &mut KVBuffer,
+    schema_version: &str,
+) -> Result<(), String>
+where
+    T: Decode + Into<StakedState>,
+    U: Encode + From<StakedState>,
+{
+    let data = match storage.get_last_app_state() {
+        Some(data) => data,
+        None => return Ok(()),
+    };
+    let mut state: NodeStateV5 = decode_all(&data, &format!("{} node state", schema_version))?;
+    if !has_staking_version(storage, state.head.staking_version) {
+        return Ok(());
+    }
+    let what = format!("{} staked state", schema_version);
+    let blobs = iter_staking_blobs(storage, state.head.staking_version)
+        .map(|(key, blob)| {
+            let staking: StakedState = decode_all::<T>(&blob, &what)?.into();
+            if staking.key() != key {
+                return Err(format!("staked state {} in a wrong leaf", staking.address));
+            }
+            Ok((key, U::from(staking).encode()))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let version = state
+        .head
+        .staking_version
+        .checked_add(1)
+        .ok_or("staking version overflow")?;
+    state.top_level.v2.v1.account_root = put_staking_blobs(
+        &mut BufferStore::new(storage, buffer),
+        version,
+        blobs.into_iter(),
+    )
+    .map_err(|e| e.to_string())?;
+    state.head.staking_version = version;
+
+    for (key, _) in storage.iter_column(COL_STAKING_VERSIONS) {
+        buffer.insert((COL_STAKING_VERSIONS, key.to_vec()), None);
+    }
+    buffer.insert(
+        (COL_EXTRA, PRUNED_HEIGHT_KEY.to_vec()),
+        Some(state.head.last_block_height.saturating_add(1).encode()),
+    );
+    buffer.insert(
+        (COL_NODE_INFO, LAST_STATE_KEY.to_vec()),
+        Some(state.encode()),
+    );
+    Ok(())
+}
+
+/// Version 5 -> 6: the outputs marked as spent in the UTXO bitsets are put into the merkle trie
+/// of spent outputs at the last height, and its root is added to the last state
+/// (it's only a part of the app hash since app version 2, so the networks already at app version 2
+/// need to migrate before the next block, e.g. at a scheduled upgrade).
+/// The spent outputs of the historical heights aren't stored, so their states get zero roots
+/// and their output statuses can't be proven.
+fn add_spent_outputs_root(storage: &Storage, buffer: &mut KVBuffer) -> Result<(), String> {
+    if let Some(data) = storage.get_last_app_state() {
+        let v5: NodeStateV5 = decode_all(&data, "version 5 node state")?;
+        let spent = storage
+            .iter_column(COL_TX_META)
+            .flat_map(|(key, value)| spent_outputs_in_bitset(&key, &value));
+        let spent_outputs_root = restore_spent_outputs(
+            &mut BufferStore::new(storage, buffer),
+            v5.head.last_block_height,
+            spent,
+        )
+        .map_err(|e| e.to_string())?;
+        let head = v5.head;
+        let state = ChainNodeState {
+            last_block_height: head.last_block_height,
+            last_apphash: head.last_apphash,
+            block_time: head.block_time,
+            block_height: head.block_height,
+            staking_table: head.staking_table,
+            genesis_time: head.genesis_time,
+            max_evidence_age: head.max_evidence_age,
+            staking_version: head.staking_version,
+            utxo_coins: head.utxo_coins,
+            enclave_isv_svn: head.enclave_isv_svn,
+            tdbe_group: head.tdbe_group,
+            proposals: head.proposals,
+            top_level: v5.top_level.with_spent_outputs_root(spent_outputs_root),
+            upgrade_plan: v5.upgrade_plan,
+        };
+        buffer.insert(
+            (COL_NODE_INFO, LAST_STATE_KEY.to_vec()),
+            Some(state.encode()),
+        );
+    }
+    migrate_historical_states(
+        storage,
+        buffer,
+        "version 5 historical state",
+        |v5: ChainStateV5| v5.with_spent_outputs_root(H256::default()),
+    )
+}
+
+/// The staked state in the schema versions 1-3: the commission rate was a part of the council node
+/// metadata, the node metadata had no p2p node ID and the community nodes had a jailing time
+/// (which was never set).
+struct StakedStateV3(StakedState);
+
+impl From<StakedStateV3> for StakedState {
+    fn from(staking: StakedStateV3) -> Self {
+        staking.0
+    }
+}
+
+impl Decode for StakedStateV3 {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let nonce = Nonce::decode(input)?;
+        let bonded = Coin::decode(input)?;
+        let unbonded = Coin::decode(input)?;
+        let unbonded_from = Timespec::decode(input)?;
+        let address = StakedStateAddress::decode(input)?;
+        let node_meta = match input.read_byte()? {
+            0 => None,
+            1 => Some(decode_node_state_v3(input)?),
+            _ => return Err("Invalid Option<NodeState> variant".into()),
+        };
+        let last_slash = Option::decode(input)?;
+        let delegated_to = Option::decode(input)?;
+        let delegators = BTreeMap::decode(input)?;
+        Ok(StakedStateV3(StakedState {
+            nonce,
+            bonded,
+            unbonded,
+            unbonded_from,
+            address,
+            node_meta,
+            last_slash,
+            delegated_to,
+            delegators,
+        }))
+    }
+}
+
+fn decode_node_state_v3<I: Input>(input: &mut I) -> Result<NodeState, Error> {
+    let tag = input.read_byte()?;
+    let name = String::decode(input)?;
+    let security_contact = Option::<String>::decode(input)?;
+    match tag {
+        0 => {
+            // the consensus key is before the keypackage (as in the 0.5 TX format)
+            let consensus_pubkey = TendermintValidatorPubKey::decode(input)?;
+            let keypackage = Vec::decode(input)?;
+            let commission_rate = Milli::decode(input)?;
+            let jailed_until = Option::decode(input)?;
+            let inactive_time = Option::decode(input)?;
+            let inactive_block = Option::decode(input)?;
+            let used_validator_addresses = Vec::decode(input)?;
+            Ok(NodeState::CouncilNode(Validator {
+                council_node: CouncilNodeMeta::new_with_details(
+                    name,
+                    security_contact,
+                    consensus_pubkey,
+                    ConfidentialInit { keypackage },
+                ),
+                jailed_until,
+                inactive_time,
+                inactive_block,
+                used_validator_addresses,
+                commission_rate,
+            }))
+        }
+        1 => {
+            let keypackage = Vec::decode(input)?;
+            let _jailed_until = Option::<Timespec>::decode(input)?;
+            let inactive_time = Option::decode(input)?;
+            let inactive_block = Option::decode(input)?;
+            Ok(NodeState::CommunityNode(CommunityNode {
+                node_info: NodeCommonInfo {
+                    name,
+                    security_contact,
+                    confidential_init: ConfidentialInit { keypackage },
+                    p2p_node_id: None,
+                },
+                jailed_until: None,
+                inactive_time,
+                inactive_block,
+            }))
+        }
+        _ => Err("Invalid NodeState variant".into()),
+    }
+}
+
+/// The staked state in the schema version 4: the delegations and the node states
+/// were always encoded with the fields added after 0.5
+#[derive(Encode, Decode)]
+struct StakedStateV4 {
+    nonce: Nonce,
+    bonded: Coin,
+    unbonded: Coin,
+    unbonded_from: Timespec,
+    address: StakedStateAddress,
+    node_meta: Option<NodeStateV4>,
+    last_slash: Option<SlashRecord>,
+    delegated_to: Option<StakedStateAddress>,
+    delegators: BTreeMap<StakedStateAddress, Coin>,
+}
+
+#[derive(Encode, Decode)]
+enum NodeStateV4 {
+    CouncilNode(Validator),
+    CommunityNode(CommunityNodeV4),
+}
+
+/// The community node in the schema version 4 (without the jailing time)
+#[derive(Encode, Decode)]
+struct CommunityNodeV4 {
+    node_info: NodeCommonInfo,
+    inactive_time: Option<Timespec>,
+    inactive_block: Option<BlockHeight>,
+}
+
+impl From<StakedState> for StakedStateV4 {
+    fn from(staking: StakedState) -> Self {
+        StakedStateV4 {
+            nonce: staking.nonce,
+            bonded: staking.bonded,
+            unbonded: staking.unbonded,
+            unbonded_from: staking.unbonded_from,
+            address: staking.address,
+            node_meta: staking.node_meta.map(|node_state| match node_state {
+                NodeState::CouncilNode(validator) => NodeStateV4::CouncilNode(validator),
+                NodeState::CommunityNode(node) => NodeStateV4::CommunityNode(CommunityNodeV4 {
+                    node_info: node.node_info,
+                    inactive_time: node.inactive_time,
+                    inactive_block: node.inactive_block,
+                }),
+            }),
+            last_slash: staking.last_slash,
+            delegated_to: staking.delegated_to,
+            delegators: staking.delegators,
+        }
+    }
+}
+
+impl From<StakedStateV4> for StakedState {
+    fn from(staking: StakedStateV4) -> Self {
+        StakedState {
+            nonce: staking.nonce,
+            bonded: staking.bonded,
+            unbonded: staking.unbonded,
+            unbonded_from: staking.unbonded_from,
+            address: staking.address,
+            node_meta: staking.node_meta.map(|node_state| match node_state {
+                NodeStateV4::CouncilNode(validator) => NodeState::CouncilNode(validator),
+                NodeStateV4::CommunityNode(node) => NodeState::CommunityNode(CommunityNode {
+                    node_info: node.node_info,
+                    jailed_until: None,
+                    inactive_time: node.inactive_time,
+                    inactive_block: node.inactive_block,
+                }),
+            }),
+            last_slash: staking.last_slash,
+            delegated_to: staking.delegated_to,
+            delegators: staking.delegators,
+        }
+    }
+}
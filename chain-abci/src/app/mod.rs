@@ -4,9 +4,12 @@ mod macros;
 mod app_init;
 mod commit;
 mod end_block;
+mod migration;
 mod query;
 mod rewards;
+mod snapshot;
 mod staking_event;
+mod upgrade;
 pub mod validate_tx;
 
 use abci::Pair as KVPair;
@@ -18,8 +21,10 @@ use std::env;
 #[cfg(fuzzing)]
 pub use self::app_init::check_validators;
 pub use self::app_init::{
-    get_validator_key, init_app_hash, BufferType, ChainNodeApp, ChainNodeState,
+    check_consensus_params, get_max_evidence_age_blocks, get_validator_key, init_app_hash,
+    BufferType, ChainNodeApp, ChainNodeState, GenesisConsensusParams,
 };
+pub use self::migration::storage_migrations;
 use crate::app::staking_event::StakingEvent;
 use crate::app::validate_tx::ResponseWithCodeAndLog;
 use crate::enclave_bridge::EnclaveProxy;
@@ -28,9 +33,10 @@ use crate::storage::{TxAction, TxEnclaveAction, TxPublicAction};
 use chain_core::common::{TendermintEventKey, TendermintEventType, Timespec};
 use chain_core::init::coin::Coin;
 use chain_core::init::config::NetworkParameters;
-use chain_core::state::account::PunishmentKind;
+use chain_core::state::account::{PunishmentKind, StakingHistoryEntry};
 use chain_core::state::tendermint::{BlockHeight, TendermintValidatorAddress, TendermintVotePower};
 use chain_core::tx::TxAux;
+use chain_storage::buffer::StoreKV;
 
 fn get_version() -> String {
     format!(
@@ -66,8 +72,12 @@ impl<T: EnclaveProxy> abci::Application for ChainNodeApp<T> {
     fn info(&mut self, _req: &RequestInfo) -> ResponseInfo {
         info!("received info request");
         let mut resp = ResponseInfo::new();
-        resp.app_version = chain_core::APP_VERSION;
         resp.version = get_version();
+        resp.app_version = self
+            .last_state
+            .as_ref()
+            .map(|state| state.top_level.app_version)
+            .unwrap_or(chain_core::APP_VERSION);
         if let Some(app_state) = &self.last_state {
             resp.last_block_app_hash = app_state.last_apphash.to_vec();
             resp.last_block_height = app_state.last_block_height.value().try_into().unwrap();
@@ -144,6 +154,9 @@ impl<T: EnclaveProxy> abci::Application for ChainNodeApp<T> {
             vec![]
         };
 
+        // halts here if this binary doesn't support the upgrade scheduled at this height
+        self.apply_scheduled_upgrade(block_height);
+
         let last_state = self
             .last_state
             .as_mut()
@@ -186,7 +199,7 @@ impl<T: EnclaveProxy> abci::Application for ChainNodeApp<T> {
 
         let rewards_pool = &mut last_state.top_level.rewards_pool;
         for punishment_outcome in punishment_outcomes.iter() {
-            // slashed_amount <= bonded + unbonded <= max supply
+            // slashed_amount <= bonded + unbonded + delegated <= max supply
             let slashed_amount = punishment_outcome
                 .slashed_coin
                 .sum()
@@ -202,7 +215,27 @@ impl<T: EnclaveProxy> abci::Application for ChainNodeApp<T> {
                 punishment_outcome.slashed_coin.unbonded,
                 punishment_outcome.punishment_kind,
             );
+            record_staking_history(
+                &mut kv_store!(self),
+                &event,
+                last_state.block_height,
+                last_state.block_time,
+            );
             response.events.push(event.into());
+            for (delegator, amount) in punishment_outcome.slashed_coin.delegated.iter() {
+                let event = StakingEvent::SlashDelegated(
+                    delegator,
+                    *amount,
+                    punishment_outcome.punishment_kind,
+                );
+                record_staking_history(
+                    &mut kv_store!(self),
+                    &event,
+                    last_state.block_height,
+                    last_state.block_time,
+                );
+                response.events.push(event.into());
+            }
 
             if punishment_outcome.punishment_kind == PunishmentKind::ByzantineFault {
                 let jailed_until = punishment_outcome
@@ -213,6 +246,12 @@ impl<T: EnclaveProxy> abci::Application for ChainNodeApp<T> {
                     jailed_until,
                     punishment_outcome.punishment_kind,
                 );
+                record_staking_history(
+                    &mut kv_store!(self),
+                    &event,
+                    last_state.block_height,
+                    last_state.block_time,
+                );
                 response.events.push(event.into());
             }
         }
@@ -234,6 +273,16 @@ impl<T: EnclaveProxy> abci::Application for ChainNodeApp<T> {
         }
 
         if let Some((distributed, minted)) = self.rewards_try_distribute() {
+            let last_state = self.last_state.as_ref().expect("app state");
+            for reward in distributed.iter() {
+                let event = StakingEvent::Reward(&reward.0, reward.1);
+                record_staking_history(
+                    &mut kv_store!(self),
+                    &event,
+                    last_state.block_height,
+                    last_state.block_time,
+                );
+            }
             let events = generate_reward_events(distributed, minted);
             for event in events.iter() {
                 response.events.push(event.to_owned());
@@ -252,7 +301,11 @@ impl<T: EnclaveProxy> abci::Application for ChainNodeApp<T> {
         match result {
             Ok((txaux, tx_action)) => {
                 let fee_amount = tx_action.fee().to_coin();
-                let tx_events = generate_tx_events(&txaux, tx_action);
+                let last_state = self.last_state.as_ref().expect("app state");
+                let (block_height, block_time) = (last_state.block_height, last_state.block_time);
+                let tx_events = generate_tx_events(&txaux, tx_action, |event| {
+                    record_staking_history(&mut kv_store!(self), event, block_height, block_time)
+                });
 
                 resp.set_code(0);
 
@@ -263,10 +316,32 @@ impl<T: EnclaveProxy> abci::Application for ChainNodeApp<T> {
                 self.delivered_txs.push(txaux);
 
                 if fee_amount > Coin::zero() {
-                    let rewards_pool =
-                        &mut self.last_state.as_mut().unwrap().top_level.rewards_pool;
-                    rewards_pool.period_bonus = (rewards_pool.period_bonus + fee_amount)
+                    let state = self.last_state.as_mut().unwrap();
+                    let network_params = &state.top_level.network_params;
+                    let split = network_params.split_fee(fee_amount);
+                    let treasury_address = network_params.get_rewards_treasury_address();
+                    let rewards_pool = &mut state.top_level.rewards_pool;
+                    rewards_pool.period_bonus = (rewards_pool.period_bonus + split.rewards_pool)
                         .expect("rewards pool + fee greater than max coin?");
+                    rewards_pool.fees_to_rewards_pool = (rewards_pool.fees_to_rewards_pool
+                        + split.rewards_pool)
+                        .expect("total fees greater than max coin?");
+                    rewards_pool.fees_burned = (rewards_pool.fees_burned + split.burned)
+                        .expect("total fees greater than max coin?");
+                    rewards_pool.fees_to_treasury = (rewards_pool.fees_to_treasury
+                        + split.treasury)
+                        .expect("total fees greater than max coin?");
+                    if let Some(address) =
+                        treasury_address.filter(|_| split.treasury > Coin::zero())
+                    {
+                        state.staking_table.treasury_deposit(
+                            &mut staking_store!(self, state.staking_version),
+                            &address,
+                            split.treasury,
+                        );
+                        resp.events
+                            .push(StakingEvent::Treasury(&address, split.treasury).into());
+                    }
                     self.rewards_pool_updated = true;
                 }
             }
@@ -298,6 +373,24 @@ impl<T: EnclaveProxy> abci::Application for ChainNodeApp<T> {
     }
 }
 
+/// records the punishment, unjailing or reward event (if it's one)
+/// in the staking history of the address
+fn record_staking_history(
+    kv_store: &mut impl StoreKV,
+    event: &StakingEvent,
+    block_height: BlockHeight,
+    block_time: Timespec,
+) {
+    if let Some((address, event)) = event.history_event() {
+        let entry = StakingHistoryEntry {
+            block_height,
+            block_time,
+            event,
+        };
+        chain_storage::append_staking_history(kv_store, address, &entry);
+    }
+}
+
 fn iter_votes(last_commit_info: &LastCommitInfo) -> impl Iterator<Item = &VoteInfo> {
     last_commit_info.votes.iter()
 }
@@ -359,7 +452,12 @@ fn generate_reward_events(distribution: RewardsDistribution, minted: Coin) -> Ve
     events
 }
 
-fn generate_tx_events(txaux: &TxAux, tx_action: TxAction) -> Vec<abci::Event> {
+/// `record_history` is called with the staking events of the transaction (if any)
+fn generate_tx_events(
+    txaux: &TxAux,
+    tx_action: TxAction,
+    mut record_history: impl FnMut(&StakingEvent),
+) -> Vec<abci::Event> {
     let mut events = Vec::new();
 
     let mut valid_txs_event = Event::new();
@@ -378,7 +476,16 @@ fn generate_tx_events(txaux: &TxAux, tx_action: TxAction) -> Vec<abci::Event> {
 
     events.push(valid_txs_event);
 
-    let maybe_tx_staking_event = generate_tx_staking_change_event(tx_action);
+    // the community nodes evicted from the TDBE group by the handshake
+    if let TxAction::MLSHandshake { jailed, .. } = &tx_action {
+        for (address, jailed_until) in jailed.iter() {
+            let event = StakingEvent::Jail(address, *jailed_until, PunishmentKind::TdbeEviction);
+            record_history(&event);
+            events.push(event.into());
+        }
+    }
+
+    let maybe_tx_staking_event = generate_tx_staking_change_event(tx_action, record_history);
     if let Some(tx_staking_event) = maybe_tx_staking_event {
         events.push(tx_staking_event);
     }
@@ -386,15 +493,22 @@ fn generate_tx_events(txaux: &TxAux, tx_action: TxAction) -> Vec<abci::Event> {
     events
 }
 
-fn generate_tx_staking_change_event(tx_action: TxAction) -> Option<abci::Event> {
+fn generate_tx_staking_change_event(
+    tx_action: TxAction,
+    record_history: impl FnOnce(&StakingEvent),
+) -> Option<abci::Event> {
+    let into_event = |event: StakingEvent| -> abci::Event {
+        record_history(&event);
+        event.into()
+    };
     match tx_action {
         TxAction::Enclave(tx_enclave_action) => match tx_enclave_action {
             TxEnclaveAction::Transfer { .. } => None,
             TxEnclaveAction::Deposit { deposit, .. } => {
-                Some(StakingEvent::Deposit(&deposit.0, deposit.1).into())
+                Some(into_event(StakingEvent::Deposit(&deposit.0, deposit.1)))
             }
             TxEnclaveAction::Withdraw { withdraw, .. } => {
-                Some(StakingEvent::Withdraw(&withdraw.0, withdraw.1).into())
+                Some(into_event(StakingEvent::Withdraw(&withdraw.0, withdraw.1)))
             }
         },
         TxAction::Public(tx_public_action) => match tx_public_action {
@@ -403,16 +517,52 @@ fn generate_tx_staking_change_event(tx_action: TxAction) -> Option<abci::Event>
                 unbonded_from,
                 fee,
                 ..
-            } => Some(StakingEvent::Unbond(&unbond.0, unbond.1, unbonded_from, fee).into()),
+            } => Some(into_event(StakingEvent::Unbond(
+                &unbond.0,
+                unbond.1,
+                unbonded_from,
+                fee,
+            ))),
             TxPublicAction::NodeJoin {
-                address,
-                council_node,
-                ..
-            } => Some(StakingEvent::NodeJoin(&address, council_node).into()),
+                address, node_meta, ..
+            } => Some(into_event(StakingEvent::NodeJoin(&address, node_meta))),
             TxPublicAction::Unjail(staking_address) => {
-                Some(StakingEvent::Unjail(&staking_address).into())
+                Some(into_event(StakingEvent::Unjail(&staking_address)))
             }
+            // only the nonce is changed
+            TxPublicAction::ParamChangeProposal(..) | TxPublicAction::ParamChangeVote(..) => None,
+            TxPublicAction::Delegate {
+                delegator,
+                council_node,
+                amount,
+            } => Some(into_event(StakingEvent::Delegate(
+                &delegator,
+                &council_node,
+                amount,
+            ))),
+            TxPublicAction::Undelegate {
+                delegator,
+                amount,
+                unbonded_from,
+                ..
+            } => Some(into_event(StakingEvent::Undelegate(
+                &delegator,
+                amount,
+                unbonded_from,
+            ))),
+            TxPublicAction::NodeMetadataUpdate {
+                address,
+                council_node,
+            } => Some(into_event(StakingEvent::NodeMetadataUpdate(
+                &address,
+                council_node,
+            ))),
+            TxPublicAction::NodeLeave {
+                address,
+                inactive_time,
+            } => Some(into_event(StakingEvent::NodeLeave(&address, inactive_time))),
         },
+        TxAction::MLSHandshake { .. } => None,
     }
 }
 
@@ -1,17 +1,36 @@
 use std::convert::{TryFrom, TryInto};
 
-use super::ChainNodeApp;
+use super::{ChainNodeApp, GenesisConsensusParams};
 use crate::enclave_bridge::EnclaveProxy;
+use crate::peer_filter::Peer;
 use abci::*;
 use chain_core::common::{MerkleTree, Proof as MerkleProof, H256, HASH_SIZE_256};
+use chain_core::init::address::REDEEM_ADDRESS_BYTES;
 use chain_core::state::account::StakedStateAddress;
 use chain_core::state::tendermint::BlockHeight;
 use chain_core::state::ChainState;
+use chain_core::tx::data::input::{TxoPointer, TxoSize};
 use chain_core::tx::data::{txid_hash, TXID_HASH_ID};
-use chain_storage::jellyfish::get_with_proof;
+use chain_core::tx::inclusion::{OutputStatus, TxInclusionProof};
+use chain_core::AppHashParts;
+use chain_storage::jellyfish::{
+    get_spent_output_with_proof, get_with_proof, OutputStatusProof, SparseMerkleProof,
+};
 use chain_storage::LookupItem;
+use log::info;
 use parity_scale_codec::{Decode, Encode};
 
+/// response code of the historical queries for the heights which were pruned
+const PRUNED_HEIGHT_CODE: u32 = 5;
+/// response code of the p2p filter queries for the rejected peers
+const PEER_REJECTED_CODE: u32 = 6;
+/// data of the "state" query for the consensus params accepted in InitChain
+const CONSENSUS_PARAMS_QUERY: &[u8] = b"consensus_params";
+/// the number of staking history events returned if the limit isn't specified
+const DEFAULT_STAKING_HISTORY_LIMIT: u16 = 100;
+/// the maximal number of staking history events returned in one query
+const MAX_STAKING_HISTORY_LIMIT: u16 = 1000;
+
 /// Generate generic ABCI ProofOp for the witness
 fn get_witness_proof_op(witness: &[u8]) -> ProofOp {
     let mut op = ProofOp::new();
@@ -53,6 +72,31 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
         }
     }
 
+    /// if the p2p node ID is recorded in the metadata of an active council or community node
+    fn is_staked_node_id(&self, id: &str) -> bool {
+        match &self.last_state {
+            Some(state) => state
+                .staking_table
+                .list_p2p_node_ids(&self.staking_getter_committed())
+                .iter()
+                .any(|node_id| node_id.to_string() == id),
+            None => false,
+        }
+    }
+
+    /// Helper to check if the historical data of a height not found in KV DB was pruned
+    /// (and log an error in the response)
+    fn check_pruned(&self, resp: &mut ResponseQuery, height: BlockHeight) -> bool {
+        match chain_storage::get_pruned_height(&self.storage) {
+            Some(pruned_height) if height < pruned_height => {
+                resp.log += &format!("historical state at height {} was pruned", height);
+                resp.code = PRUNED_HEIGHT_CODE;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Helper to find a key under a column in KV DB, or log an error (both stored in the response).
     fn lookup(
         &self,
@@ -70,17 +114,254 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
         None
     }
 
+    /// the committed top-level state after the block at the height
+    /// (historical states are only available when tx query address is set)
+    fn committed_top_level(&self, height: BlockHeight) -> Option<ChainState> {
+        match &self.last_state {
+            Some(state) if state.last_block_height == height => Some(state.top_level.clone()),
+            _ => {
+                let value = self.storage.get_historical_state(height)?;
+                ChainState::decode(&mut value.as_slice()).ok()
+            }
+        }
+    }
+
+    /// Helper to get the committed top-level state, or log an error in the response
+    fn top_level_state(&self, resp: &mut ResponseQuery, height: BlockHeight) -> Option<ChainState> {
+        let state = self.committed_top_level(height);
+        if state.is_none() && !self.check_pruned(resp, height) {
+            resp.log += "historical state not found (tx query address not set?)";
+            resp.code = 2;
+        }
+        state
+    }
+
+    /// the parts of the committed app hash after the block at the height
+    /// (for heights before the latest one, it needs the historical states)
+    fn app_hash_parts(&self, height: BlockHeight) -> Option<AppHashParts> {
+        let state = self.committed_top_level(height)?;
+        let app_hash = match &self.last_state {
+            Some(last_state) if last_state.last_block_height == height => last_state.last_apphash,
+            _ => self.storage.get_historical_app_hash(height)?,
+        };
+        let valid_tx_root = match self.storage.lookup_item(LookupItem::TxsMerkle, &app_hash) {
+            Some(tree) => MerkleTree::<H256>::decode(&mut tree.as_slice())
+                .ok()?
+                .root_hash(),
+            // no transactions at genesis
+            None if height == BlockHeight::genesis() => MerkleTree::<H256>::empty().root_hash(),
+            None => return None,
+        };
+        Some(state.app_hash_parts(valid_tx_root))
+    }
+
+    /// the membership / non-membership proof of the staking state
+    /// and (if the state at the height is available) the parts of the app hash committing to it
+    fn staking_proof(
+        &self,
+        address: &StakedStateAddress,
+        proof: &SparseMerkleProof,
+        height: BlockHeight,
+    ) -> Proof {
+        let mut ops = vec![ProofOp {
+            field_type: "staking".to_owned(),
+            key: address.encode(),
+            data: proof.encode(),
+            ..Default::default()
+        }];
+        if let Some(parts) = self.app_hash_parts(height) {
+            ops.push(ProofOp {
+                field_type: "app_hash".to_owned(),
+                key: height.encode(),
+                data: parts.encode(),
+                ..Default::default()
+            });
+        }
+        Proof {
+            ops: ops.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Helper to return a page of the staking history of an address:
+    /// the data is the address, optionally followed by the encoded start index and limit (u64, u16)
+    fn staking_history(&self, resp: &mut ResponseQuery, data: &[u8]) {
+        let (address, start, limit) = if data.len() > REDEEM_ADDRESS_BYTES {
+            let (address, mut range) = data.split_at(REDEEM_ADDRESS_BYTES);
+            match <(u64, u16)>::decode(&mut range) {
+                Ok((start, limit)) if range.is_empty() => (address, start, limit),
+                _ => {
+                    resp.log += "invalid staking history range";
+                    resp.code = 4;
+                    return;
+                }
+            }
+        } else {
+            (data, 0, DEFAULT_STAKING_HISTORY_LIMIT)
+        };
+        match StakedStateAddress::try_from(address) {
+            Ok(address) => {
+                let limit = limit.min(MAX_STAKING_HISTORY_LIMIT);
+                let page = chain_storage::get_staking_history(
+                    &self.storage,
+                    &address,
+                    start,
+                    limit.into(),
+                );
+                resp.value = page.encode();
+            }
+            Err(_) => {
+                resp.log += "invalid staking address";
+                resp.code = 3;
+            }
+        }
+    }
+
+    /// Helper to build the inclusion proof of a committed transaction
+    /// (data: TxId, optionally followed by the encoded output index
+    /// whose status is proven as of the requested height).
+    /// The output status is proven by the output's membership (or absence)
+    /// in the merkle trie of spent outputs, whose root is a part of the app hash since app version 2.
+    fn inclusion_proof(&self, resp: &mut ResponseQuery, data: &[u8], req_height: i64) {
+        let (txid, index) = if data.len() > HASH_SIZE_256 {
+            let (txid, mut index) = data.split_at(HASH_SIZE_256);
+            match TxoSize::decode(&mut index) {
+                Ok(i) if index.is_empty() => (txid, Some(i)),
+                _ => {
+                    resp.log += "invalid output index";
+                    resp.code = 4;
+                    return;
+                }
+            }
+        } else {
+            (data, None)
+        };
+        let txid = match get_key(resp, txid) {
+            Some(txid) => txid,
+            None => return,
+        };
+        let created = match chain_storage::get_tx_creation(&self.storage, &txid) {
+            Some(created) => created,
+            None => {
+                resp.log += "tx not found (or committed before its block was recorded)";
+                resp.code = 1;
+                return;
+            }
+        };
+        let tree_proof = self
+            .storage
+            .get_historical_app_hash(created.block_height)
+            .and_then(|app_hash| self.storage.lookup_item(LookupItem::TxsMerkle, &app_hash))
+            .and_then(|data| MerkleTree::<H256>::decode(&mut data.as_slice()).ok())
+            .and_then(|tree| {
+                tree.generate_proof(txid)
+                    .map(|proof| (tree.root_hash(), proof))
+            });
+        let (valid_tx_root, merkle_proof) = match tree_proof {
+            Some(tree_proof) => tree_proof,
+            None => {
+                resp.log += "proof error: tx not found in the block's merkle tree";
+                resp.code = 2;
+                return;
+            }
+        };
+        let state = match self.top_level_state(resp, created.block_height) {
+            Some(state) => state,
+            None => return,
+        };
+
+        let last_height = self
+            .last_state
+            .as_ref()
+            .map_or(BlockHeight::genesis(), |x| x.last_block_height);
+        // Negative / zero height defaults to the last height
+        let req_height: Option<BlockHeight> = req_height.try_into().ok();
+        let status_height = match req_height {
+            Some(height) if height != BlockHeight::genesis() && height <= last_height => height,
+            _ => last_height,
+        };
+        let status_proof = match index {
+            Some(index) => {
+                let txin = TxoPointer::new(txid, index as usize);
+                if chain_storage::lookup_input(&self.storage, &txin).is_none() {
+                    resp.log += "output not found";
+                    resp.code = 1;
+                    return;
+                }
+                match self.output_status_proof(resp, &txin, status_height) {
+                    Some(status_proof) => Some(status_proof),
+                    None => return,
+                }
+            }
+            None => None,
+        };
+        let proof = TxInclusionProof {
+            block_height: created.block_height,
+            merkle_proof,
+            app_hash_parts: state.app_hash_parts(valid_tx_root),
+        };
+        resp.value = (proof, status_proof).encode();
+    }
+
+    /// Helper to prove the output status as of the block at the height
+    /// against the spent outputs root in its app hash
+    fn output_status_proof(
+        &self,
+        resp: &mut ResponseQuery,
+        txin: &TxoPointer,
+        height: BlockHeight,
+    ) -> Option<OutputStatusProof> {
+        let app_hash_parts = match self.app_hash_parts(height) {
+            Some(parts) => parts,
+            None => {
+                if !self.check_pruned(resp, height) {
+                    resp.log += "historical state not found (tx query address not set?)";
+                    resp.code = 2;
+                }
+                return None;
+            }
+        };
+        if app_hash_parts.spent_outputs_root.is_none() {
+            resp.log += "the spent outputs are only committed since app version 2";
+            resp.code = 2;
+            return None;
+        }
+        match get_spent_output_with_proof(&self.storage, height, txin) {
+            Some((spent, proof)) => Some(OutputStatusProof {
+                block_height: height,
+                app_hash_parts,
+                status: if spent {
+                    OutputStatus::Spent
+                } else {
+                    OutputStatus::Unspent
+                },
+                proof,
+            }),
+            None => {
+                resp.log += "output status not available at the height (pruned or migrated)";
+                resp.code = 2;
+                None
+            }
+        }
+    }
+
     /// Responds to query requests -- note that path is hex-encoded in the original request on the client side
     /// e.g. "store" == 0x73746f7265.
     pub fn query_handler(&self, _req: &RequestQuery) -> ResponseQuery {
         let mut resp = ResponseQuery::new();
 
-        // "When Tendermint connects to a peer, it sends two queries to the ABCI application using the following paths, with no additional data:
-        // * /p2p/filter/addr/<IP:PORT>, where <IP:PORT> denote the IP address and the port of the connection
-        // * p2p/filter/id/<ID>, where <ID> is the peer node ID (ie. the pubkey.Address() for the peer's PubKey)
-        // If either of these queries return a non-zero ABCI code, Tendermint will refuse to connect to the peer."
+        // peer filtering queries from Tendermint (see `crate::peer_filter`)
         if _req.path.starts_with("/p2p") || _req.path.starts_with("p2p") {
-            // TODO: peer filtering
+            if let Some(peer) = Peer::from_query_path(&_req.path) {
+                if let Err(reason) = self
+                    .peer_filter
+                    .check(&peer, |id| self.is_staked_node_id(id))
+                {
+                    info!("rejected peer: {}", reason);
+                    resp.log += &reason;
+                    resp.code = PEER_REJECTED_CODE;
+                }
+            }
             return resp;
         }
 
@@ -146,6 +427,12 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
                     }
                 }
             }
+            "inclusion" => {
+                self.inclusion_proof(&mut resp, &_req.data[..], _req.height);
+            }
+            "staking-history" => {
+                self.staking_history(&mut resp, &_req.data[..]);
+            }
             "meta" => {
                 self.lookup(
                     &mut resp,
@@ -173,12 +460,19 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
             "account" => {
                 let account_address = StakedStateAddress::try_from(_req.data.as_slice());
                 if let (Some(state), Ok(address)) = (&self.last_state, account_address) {
-                    let (account, _proof) =
+                    let (account, proof) =
                         get_with_proof(&self.storage, state.staking_version, &address);
+                    if _req.prove {
+                        // the non-membership proof is included if the account doesn't exist
+                        resp.set_proof(self.staking_proof(
+                            &address,
+                            &proof,
+                            state.last_block_height,
+                        ));
+                    }
                     match account {
                         Some(a) => {
                             resp.value = a.encode();
-                            // TODO: inclusion proof
                         }
                         None => {
                             resp.log += "account lookup failed: account not exists";
@@ -191,40 +485,56 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
                 }
             }
             "staking" => {
-                let mversion = if let Ok(height) = _req.height.try_into() {
+                let mheight: Option<BlockHeight> = _req.height.try_into().ok();
+                let mversion = if let Some(height) = mheight {
                     self.storage.get_historical_staking_version(height)
                 } else {
                     self.last_state.as_ref().map(|state| state.staking_version)
                 };
                 let account_address = StakedStateAddress::try_from(_req.data.as_slice());
+                if let (None, Some(height)) = (mversion, mheight) {
+                    if self.check_pruned(&mut resp, height) {
+                        return resp;
+                    }
+                }
                 if let (Some(version), Ok(address)) = (mversion, account_address) {
                     let (mstaking, proof) = get_with_proof(&self.storage, version, &address);
                     resp.value = mstaking.encode();
                     if _req.prove {
-                        resp.set_proof(Proof {
-                            ops: vec![ProofOp {
-                                field_type: "staking".to_owned(),
-                                key: address.encode(),
-                                data: proof.encode(),
-                                ..Default::default()
-                            }]
-                            .into(),
-                            ..Default::default()
+                        let height = mheight.unwrap_or_else(|| {
+                            self.last_state
+                                .as_ref()
+                                .map_or(BlockHeight::genesis(), |x| x.last_block_height)
                         });
+                        resp.set_proof(self.staking_proof(&address, &proof, height));
                     }
                 } else {
                     resp.log += "account lookup failed (either invalid address or node not correctly restored / initialized)";
                     resp.code = 3;
                 }
             }
+            "state" if _req.data == CONSENSUS_PARAMS_QUERY => {
+                let params = chain_storage::get_consensus_params(&self.storage)
+                    .and_then(|value| protobuf::parse_from_bytes::<ConsensusParams>(&value).ok());
+                match params {
+                    Some(cp) => {
+                        resp.value = serde_json::to_string(&GenesisConsensusParams::from(&cp))
+                            .expect("Unable to serialize consensus params into json")
+                            .into_bytes();
+                    }
+                    None => {
+                        resp.log += "consensus params not found";
+                        resp.code = 2;
+                    }
+                }
+            }
             "state" => {
                 if self.tx_query_address.is_none() {
                     resp.code = 1;
                     resp.log += "tx query address not set / state is not persisted";
                 } else {
-                    let value = self.storage.get_historical_state(
-                        _req.height.try_into().expect("Invalid block height"),
-                    );
+                    let height = _req.height.try_into().expect("Invalid block height");
+                    let value = self.storage.get_historical_state(height);
                     match value {
                         Some(value) => {
                             if let Ok(state) = ChainState::decode(&mut value.to_vec().as_slice()) {
@@ -235,8 +545,10 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
                             }
                         }
                         _ => {
-                            resp.log += "state not found";
-                            resp.code = 2;
+                            if !self.check_pruned(&mut resp, height) {
+                                resp.log += "state not found";
+                                resp.code = 2;
+                            }
                         }
                     }
                 }
@@ -253,6 +565,18 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
                     .expect("Unable to serialize validator metadata into json")
                     .into_bytes();
             }
+            "community-nodes" => {
+                let community_nodes = &self
+                    .last_state
+                    .as_ref()
+                    .expect("Missing last_state: init chain was not called")
+                    .staking_table
+                    .list_community_nodes(&self.staking_getter_committed());
+
+                resp.value = serde_json::to_string(&community_nodes)
+                    .expect("Unable to serialize community node metadata into json")
+                    .into_bytes();
+            }
             "sealed" => {
                 self.lookup(
                     &mut resp,
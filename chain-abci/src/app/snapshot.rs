@@ -0,0 +1,324 @@
+//! Taking, listing and restoring the state snapshots.
+//!
+//! NOTE: the handlers mirror Tendermint's state sync calls (ListSnapshots, LoadSnapshotChunk,
+//! OfferSnapshot, ApplySnapshotChunk), but Tendermint 0.33 (and the `abci::Application` trait
+//! of the abci crate for it) has no state sync, so they aren't reachable over ABCI
+//! and the snapshots can't be fetched from the other nodes by Tendermint yet.
+//! Until then, a snapshot directory (e.g. copied from another node) is restored through the same
+//! calls by `restore_snapshot_from_dir` (the `--restore_snapshot` option of chain-abci).
+//! Wiring them into ABCI needs the upgrade to Tendermint 0.34 and an `abci` release for it,
+//! after which the `abci::Application` implementation (`app/mod.rs`) only forwards
+//! the requests to `list_snapshots`, `load_snapshot_chunk`, `offer_snapshot`
+//! and `apply_snapshot_chunk`.
+use std::path::Path;
+use std::thread;
+
+use log::{info, warn};
+use parity_scale_codec::Decode;
+
+use super::{ChainNodeApp, ChainNodeState};
+use crate::enclave_bridge::EnclaveProxy;
+use crate::snapshot::{
+    read_chunk, read_manifest, ApplySnapshotChunkResult, OfferSnapshotResult, Snapshot,
+    SnapshotRestore, SnapshotStore,
+};
+use chain_core::common::{MerkleTree, H256};
+use chain_core::compute_app_hash;
+use chain_core::state::tendermint::BlockHeight;
+use chain_storage::buffer::MemStore;
+use chain_storage::jellyfish::{
+    compute_staking_root, iter_stakings, restore_spent_outputs, StakingGetter,
+};
+use chain_storage::snapshot::{
+    SnapshotContent, SnapshotError, SNAPSHOT_CHUNK_SIZE, SNAPSHOT_FORMAT,
+};
+use chain_storage::{
+    spent_outputs_in_bitset, LookupItem, ReadOnlyStorage, CHAIN_ID_KEY, COL_EXTRA, COL_NODE_INFO,
+    COL_TX_META, GENESIS_APP_HASH_KEY,
+};
+
+/// Takes a snapshot of the committed block of `state` and stores it
+/// (a failed snapshot doesn't affect the consensus state, so the node can continue)
+fn create_snapshot(storage: &ReadOnlyStorage, store: &SnapshotStore, state: &ChainNodeState) {
+    let content = match storage.create_snapshot(state, state.last_block_height) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!(
+                "failed to read the snapshot at height {}: {}",
+                state.last_block_height, e
+            );
+            return;
+        }
+    };
+    let (manifest, chunks) = content.into_chunks(state.last_apphash, SNAPSHOT_CHUNK_SIZE);
+    match store.save(&manifest, &chunks) {
+        Ok(()) => info!(
+            "snapshot at height {} created ({} chunks)",
+            manifest.height,
+            manifest.chunks()
+        ),
+        Err(e) => warn!(
+            "failed to store the snapshot at height {}: {}",
+            manifest.height, e
+        ),
+    }
+}
+
+impl<T: EnclaveProxy> ChainNodeApp<T> {
+    /// Starts taking a snapshot of the last committed block in a background thread,
+    /// if it's scheduled at its height
+    pub fn spawn_scheduled_snapshot(&mut self) {
+        let (store, state) = match (&self.snapshots, &self.last_state) {
+            (Some(store), Some(state)) if store.is_scheduled(state.last_block_height) => {
+                (store.clone(), state.clone())
+            }
+            _ => return,
+        };
+        let storage = self.storage.get_read_only();
+        self.snapshot_worker = Some(thread::spawn(move || {
+            create_snapshot(&storage, &store, &state)
+        }));
+    }
+
+    /// Waits until the snapshot being taken in the background (if any) is finished.
+    /// It reads the committed storage, so it must finish before the next block is flushed
+    /// (which only blocks if taking the snapshot is slower than a block).
+    pub fn wait_for_snapshot(&mut self) {
+        if let Some(worker) = self.snapshot_worker.take() {
+            if worker.join().is_err() {
+                warn!("the snapshot thread panicked");
+            }
+        }
+    }
+
+    /// ListSnapshots: the snapshots this node can offer to the others
+    pub fn list_snapshots(&self) -> Vec<Snapshot> {
+        match &self.snapshots {
+            Some(store) => store
+                .list()
+                .unwrap_or_else(|e| {
+                    warn!("failed to list the snapshots: {}", e);
+                    vec![]
+                })
+                .iter()
+                .map(Snapshot::from)
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// LoadSnapshotChunk: a chunk of a snapshot this node offered (empty if not found)
+    pub fn load_snapshot_chunk(&self, height: u64, format: u32, chunk: u32) -> Vec<u8> {
+        match &self.snapshots {
+            Some(store) if format == SNAPSHOT_FORMAT => store
+                .load_chunk(BlockHeight::new(height), chunk)
+                .unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+
+    /// OfferSnapshot: a snapshot discovered by Tendermint, with the app hash
+    /// of its height verified by the light client
+    pub fn offer_snapshot(&mut self, snapshot: &Snapshot, app_hash: &[u8]) -> OfferSnapshotResult {
+        if self.last_state.is_some() {
+            warn!("snapshot offered, but the node already has a state");
+            return OfferSnapshotResult::Abort;
+        }
+        if snapshot.format != SNAPSHOT_FORMAT {
+            return OfferSnapshotResult::RejectFormat;
+        }
+        match snapshot.manifest() {
+            Some(manifest) if manifest.app_hash[..] == app_hash[..] => {
+                info!(
+                    "accepted snapshot at height {} ({} chunks)",
+                    manifest.height,
+                    manifest.chunks()
+                );
+                self.snapshot_restore = Some(SnapshotRestore::new(manifest));
+                OfferSnapshotResult::Accept
+            }
+            _ => OfferSnapshotResult::Reject,
+        }
+    }
+
+    /// ApplySnapshotChunk: checks the chunk and restores the snapshot once all chunks are received.
+    /// Returns the result and the chunks to be fetched again.
+    pub fn apply_snapshot_chunk(
+        &mut self,
+        index: u32,
+        chunk: Vec<u8>,
+    ) -> (ApplySnapshotChunkResult, Vec<u32>) {
+        let restore = match self.snapshot_restore.as_mut() {
+            Some(restore) => restore,
+            None => return (ApplySnapshotChunkResult::Abort, vec![]),
+        };
+        match restore.apply_chunk(index, chunk) {
+            Ok(()) => {}
+            Err(SnapshotError::ChunkHashMismatch(index)) => {
+                return (ApplySnapshotChunkResult::Retry, vec![index]);
+            }
+            Err(e) => {
+                warn!("invalid snapshot chunk: {}", e);
+                return (ApplySnapshotChunkResult::RejectSnapshot, vec![]);
+            }
+        }
+        let chunks = match restore.complete_chunks() {
+            Some(chunks) => chunks,
+            None => return (ApplySnapshotChunkResult::Accept, vec![]),
+        };
+        let restore = self
+            .snapshot_restore
+            .take()
+            .expect("snapshot restore in progress");
+        let restored = SnapshotContent::from_chunks(&restore.manifest, &chunks)
+            .map_err(|e| e.to_string())
+            .and_then(|content| self.restore_snapshot(&content, &restore.manifest.app_hash));
+        match restored {
+            Ok(()) => {
+                info!("snapshot at height {} restored", restore.manifest.height);
+                (ApplySnapshotChunkResult::Accept, vec![])
+            }
+            Err(e) => {
+                warn!("failed to restore the snapshot: {}", e);
+                (ApplySnapshotChunkResult::RejectSnapshot, vec![])
+            }
+        }
+    }
+
+    /// verifies the snapshot content against the trusted app hash (and the node's genesis),
+    /// then writes it to the storage
+    fn restore_snapshot(
+        &mut self,
+        content: &SnapshotContent,
+        app_hash: &H256,
+    ) -> Result<(), String> {
+        let entry = |col: u32, key: &[u8]| {
+            content
+                .entries
+                .iter()
+                .find(|(c, k, _)| *c == col && k.as_slice() == key)
+                .map(|(_, _, value)| value.as_slice())
+        };
+        if entry(COL_NODE_INFO, GENESIS_APP_HASH_KEY) != Some(&self.genesis_app_hash[..]) {
+            return Err("genesis app hash mismatch".to_owned());
+        }
+        if entry(COL_EXTRA, CHAIN_ID_KEY) != Some(&self.storage.get_stored_chain_id()[..]) {
+            return Err("chain id mismatch".to_owned());
+        }
+
+        let state = content.last_state().ok_or("no node state")?;
+        let mut state = ChainNodeState::decode_stored(state).map_err(|e| e.to_string())?;
+        if state.last_apphash != *app_hash
+            || state.last_block_height != content.height
+            || state.staking_version != content.staking_version
+        {
+            return Err("node state doesn't match the snapshot".to_owned());
+        }
+        if state.top_level.app_version > chain_core::APP_VERSION {
+            return Err(format!(
+                "the snapshot needs app version {}, but this binary only supports {}",
+                state.top_level.app_version,
+                chain_core::APP_VERSION
+            ));
+        }
+        let staking_root = content.staking_root().map_err(|e| e.to_string())?;
+        if staking_root != state.top_level.account_root {
+            return Err("staking states don't match the staking root".to_owned());
+        }
+        let spent_outputs_root = content.spent_outputs_root().map_err(|e| e.to_string())?;
+        if spent_outputs_root != state.top_level.spent_outputs_root {
+            return Err("spent outputs don't match the spent outputs root".to_owned());
+        }
+        let mut tree = content
+            .txs_merkle_tree(app_hash)
+            .ok_or("no transaction merkle tree")?;
+        let tree = MerkleTree::<H256>::decode(&mut tree).map_err(|e| e.to_string())?;
+        let computed_app_hash = compute_app_hash(&tree, &state.top_level);
+        if computed_app_hash != *app_hash {
+            return Err("app hash mismatch".to_owned());
+        }
+
+        self.storage
+            .restore_snapshot(content)
+            .map_err(|e| e.to_string())?;
+        // populate the indexing structures in staking table
+        let network_params = &state.top_level.network_params;
+        state.staking_table.initialize(
+            &StakingGetter::new(&self.storage, state.staking_version),
+            network_params.get_required_council_node_stake(),
+            network_params.get_required_community_node_stake(),
+        );
+        self.last_state = Some(state.clone());
+        self.mempool_state = Some(state);
+        Ok(())
+    }
+
+    /// Restores the snapshot stored in `dir` (as taken by `SnapshotStore`) into the empty storage
+    /// of this node: it's offered with the trusted app hash and its chunks are applied as in state sync,
+    /// then the app hash is recomputed from the restored storage.
+    pub fn restore_snapshot_from_dir(
+        &mut self,
+        dir: &Path,
+        app_hash: &H256,
+    ) -> Result<BlockHeight, String> {
+        let manifest = read_manifest(dir).map_err(|e| format!("invalid manifest: {}", e))?;
+        match self.offer_snapshot(&Snapshot::from(&manifest), &app_hash[..]) {
+            OfferSnapshotResult::Accept => {}
+            result => return Err(format!("snapshot not accepted: {:?}", result)),
+        }
+        for index in 0..manifest.chunks() {
+            let chunk = read_chunk(dir, index).map_err(|e| {
+                self.snapshot_restore = None;
+                format!("failed to read chunk {}: {}", index, e)
+            })?;
+            match self.apply_snapshot_chunk(index, chunk) {
+                (ApplySnapshotChunkResult::Accept, _) => {}
+                (result, _) => {
+                    self.snapshot_restore = None;
+                    return Err(format!("chunk {} not applied: {:?}", index, result));
+                }
+            }
+        }
+        if self.last_state.is_none() {
+            return Err("snapshot not restored".to_owned());
+        }
+        let restored_app_hash = self.restored_app_hash()?;
+        if restored_app_hash != *app_hash {
+            return Err(format!(
+                "restored app hash mismatch: {}",
+                hex::encode(restored_app_hash)
+            ));
+        }
+        Ok(manifest.height)
+    }
+
+    /// recomputes the app hash of the last state in the storage
+    /// (and checks its staking and spent outputs roots)
+    fn restored_app_hash(&self) -> Result<H256, String> {
+        let raw = self
+            .storage
+            .get_last_app_state()
+            .ok_or("no node state in the storage")?;
+        let state = ChainNodeState::decode_stored(&raw).map_err(|e| e.to_string())?;
+        let stakings = iter_stakings(&self.storage, state.staking_version).collect::<Vec<_>>();
+        if compute_staking_root(&stakings) != state.top_level.account_root {
+            return Err("restored staking states don't match the staking root".to_owned());
+        }
+        let spent = self
+            .storage
+            .iter_column(COL_TX_META)
+            .flat_map(|(key, value)| spent_outputs_in_bitset(&key, &value));
+        let spent_outputs_root =
+            restore_spent_outputs(&mut MemStore::new(), state.last_block_height, spent)
+                .map_err(|e| e.to_string())?;
+        if spent_outputs_root != state.top_level.spent_outputs_root {
+            return Err("restored spent outputs don't match the spent outputs root".to_owned());
+        }
+        let tree = self
+            .storage
+            .lookup_item(LookupItem::TxsMerkle, &state.last_apphash)
+            .ok_or("no transaction merkle tree in the storage")?;
+        let tree = MerkleTree::<H256>::decode(&mut tree.as_slice()).map_err(|e| e.to_string())?;
+        Ok(compute_app_hash(&tree, &state.top_level))
+    }
+}
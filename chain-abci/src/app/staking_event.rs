@@ -6,18 +6,57 @@ use abci::Pair as KVPair;
 use abci::*;
 use chain_core::common::{TendermintEventKey, TendermintEventType, Timespec};
 use chain_core::init::coin::Coin;
-use chain_core::state::account::{CouncilNodeMeta, PunishmentKind, StakedStateAddress};
+use chain_core::state::account::{
+    CouncilNodeMeta, NodeMetadata, PunishmentKind, StakedStateAddress, StakingHistoryEvent,
+};
 use chain_core::tx::fee::Fee;
 
 pub(crate) enum StakingEvent<'a> {
     Deposit(&'a StakedStateAddress, Coin),
     Unbond(&'a StakedStateAddress, Coin, Timespec, Fee),
     Withdraw(&'a StakedStateAddress, Coin),
-    NodeJoin(&'a StakedStateAddress, CouncilNodeMeta),
+    NodeJoin(&'a StakedStateAddress, NodeMetadata),
+    NodeMetadataUpdate(&'a StakedStateAddress, CouncilNodeMeta),
+    NodeLeave(&'a StakedStateAddress, Timespec),
     Reward(&'a StakedStateAddress, Coin),
+    Treasury(&'a StakedStateAddress, Coin),
     Jail(&'a StakedStateAddress, Timespec, PunishmentKind),
     Slash(&'a StakedStateAddress, Coin, Coin, PunishmentKind),
+    SlashDelegated(&'a StakedStateAddress, Coin, PunishmentKind),
     Unjail(&'a StakedStateAddress),
+    Delegate(&'a StakedStateAddress, &'a StakedStateAddress, Coin),
+    Undelegate(&'a StakedStateAddress, Coin, Timespec),
+}
+
+impl<'a> StakingEvent<'a> {
+    /// the punishments and rewards (recorded in the staking history of the address)
+    pub(crate) fn history_event(&self) -> Option<(&'a StakedStateAddress, StakingHistoryEvent)> {
+        match *self {
+            StakingEvent::Jail(staking_address, jailed_until, kind) => Some((
+                staking_address,
+                StakingHistoryEvent::Jail { jailed_until, kind },
+            )),
+            StakingEvent::Unjail(staking_address) => {
+                Some((staking_address, StakingHistoryEvent::Unjail))
+            }
+            StakingEvent::Slash(staking_address, bonded, unbonded, kind) => Some((
+                staking_address,
+                StakingHistoryEvent::Slash {
+                    bonded,
+                    unbonded,
+                    kind,
+                },
+            )),
+            StakingEvent::SlashDelegated(staking_address, amount, kind) => Some((
+                staking_address,
+                StakingHistoryEvent::SlashDelegated { amount, kind },
+            )),
+            StakingEvent::Reward(staking_address, amount) => {
+                Some((staking_address, StakingHistoryEvent::Reward { amount }))
+            }
+            _ => None,
+        }
+    }
 }
 
 impl<'a> From<StakingEvent<'a>> for Event {
@@ -34,12 +73,21 @@ impl<'a> From<StakingEvent<'a>> for Event {
             StakingEvent::Withdraw(staking_address, withdraw_amount) => {
                 builder.withdraw(staking_address, withdraw_amount)
             }
-            StakingEvent::NodeJoin(staking_address, council_node) => {
-                builder.node_join(staking_address, council_node)
+            StakingEvent::NodeJoin(staking_address, node_meta) => {
+                builder.node_join(staking_address, node_meta)
+            }
+            StakingEvent::NodeMetadataUpdate(staking_address, council_node) => {
+                builder.node_metadata_update(staking_address, council_node)
+            }
+            StakingEvent::NodeLeave(staking_address, inactive_time) => {
+                builder.node_leave(staking_address, inactive_time)
             }
             StakingEvent::Reward(staking_address, reward_amount) => {
                 builder.reward(staking_address, reward_amount)
             }
+            StakingEvent::Treasury(staking_address, fee_amount) => {
+                builder.treasury(staking_address, fee_amount)
+            }
             StakingEvent::Jail(staking_address, timespec, punishment_kind) => {
                 builder.jail(staking_address, timespec, punishment_kind)
             }
@@ -54,7 +102,18 @@ impl<'a> From<StakingEvent<'a>> for Event {
                 unbonded_slash_amount,
                 punishment_kind,
             ),
+            StakingEvent::SlashDelegated(
+                staking_address,
+                delegated_slash_amount,
+                punishment_kind,
+            ) => builder.slash_delegated(staking_address, delegated_slash_amount, punishment_kind),
             StakingEvent::Unjail(staking_address) => builder.unjail(staking_address),
+            StakingEvent::Delegate(staking_address, council_node, delegate_amount) => {
+                builder.delegate(staking_address, council_node, delegate_amount)
+            }
+            StakingEvent::Undelegate(staking_address, undelegate_amount, unbonded_from) => {
+                builder.undelegate(staking_address, undelegate_amount, unbonded_from)
+            }
         }
 
         builder.to_event()
@@ -119,7 +178,7 @@ impl StakingEventBuilder {
         );
     }
 
-    fn node_join(&mut self, staking_address: &StakedStateAddress, node: CouncilNodeMeta) {
+    fn node_join(&mut self, staking_address: &StakedStateAddress, node: NodeMetadata) {
         self.attributes
             .push(staking_address_attribute(staking_address));
         self.attributes.push(StakingEventOpType::NodeJoin.into());
@@ -127,6 +186,32 @@ impl StakingEventBuilder {
             .push(StakingDiffField(vec![StakingDiff::NodeJoin(node)]).into());
     }
 
+    fn node_metadata_update(
+        &mut self,
+        staking_address: &StakedStateAddress,
+        council_node: CouncilNodeMeta,
+    ) {
+        self.attributes
+            .push(staking_address_attribute(staking_address));
+        self.attributes
+            .push(StakingEventOpType::NodeMetadataUpdate.into());
+
+        self.attributes.push(
+            StakingDiffField(vec![StakingDiff::NodeJoin(NodeMetadata::CouncilNode(
+                council_node,
+            ))])
+            .into(),
+        );
+    }
+
+    fn node_leave(&mut self, staking_address: &StakedStateAddress, inactive_time: Timespec) {
+        self.attributes
+            .push(staking_address_attribute(staking_address));
+        self.attributes.push(StakingEventOpType::NodeLeave.into());
+        self.attributes
+            .push(StakingDiffField(vec![StakingDiff::InactiveTime(inactive_time)]).into());
+    }
+
     fn reward(&mut self, staking_address: &StakedStateAddress, reward_amount: Coin) {
         self.attributes
             .push(staking_address_attribute(staking_address));
@@ -140,6 +225,19 @@ impl StakingEventBuilder {
         );
     }
 
+    fn treasury(&mut self, staking_address: &StakedStateAddress, fee_amount: Coin) {
+        self.attributes
+            .push(staking_address_attribute(staking_address));
+        self.attributes.push(StakingEventOpType::Treasury.into());
+        self.attributes.push(
+            StakingDiffField(vec![StakingDiff::Unbonded(
+                StakingCoinChange::Increase,
+                fee_amount,
+            )])
+            .into(),
+        );
+    }
+
     fn jail(
         &mut self,
         staking_address: &StakedStateAddress,
@@ -182,12 +280,76 @@ impl StakingEventBuilder {
         self.attributes.push(reason_kv_pair)
     }
 
+    fn slash_delegated(
+        &mut self,
+        staking_address: &StakedStateAddress,
+        delegated_slash_amount: Coin,
+        punishment_kind: PunishmentKind,
+    ) {
+        self.attributes
+            .push(staking_address_attribute(staking_address));
+        self.attributes.push(StakingEventOpType::Slash.into());
+        self.attributes.push(
+            StakingDiffField(vec![StakingDiff::Delegated(
+                StakingCoinChange::Decrease,
+                delegated_slash_amount,
+            )])
+            .into(),
+        );
+
+        let mut reason_kv_pair = KVPair::new();
+        reason_kv_pair.key = TendermintEventKey::StakingOpReason.into();
+        reason_kv_pair.value = punishment_reason(punishment_kind).into_bytes();
+        self.attributes.push(reason_kv_pair)
+    }
+
     fn unjail(&mut self, staking_address: &StakedStateAddress) {
         self.attributes
             .push(staking_address_attribute(staking_address));
         self.attributes.push(StakingEventOpType::Unjail.into());
     }
 
+    fn delegate(
+        &mut self,
+        staking_address: &StakedStateAddress,
+        council_node: &StakedStateAddress,
+        delegate_amount: Coin,
+    ) {
+        self.attributes
+            .push(staking_address_attribute(staking_address));
+        self.attributes.push(StakingEventOpType::Delegate.into());
+
+        self.attributes.push(
+            StakingDiffField(vec![
+                StakingDiff::Bonded(StakingCoinChange::Decrease, delegate_amount),
+                StakingDiff::Delegated(StakingCoinChange::Increase, delegate_amount),
+                StakingDiff::DelegatedTo(Some(*council_node)),
+            ])
+            .into(),
+        );
+    }
+
+    fn undelegate(
+        &mut self,
+        staking_address: &StakedStateAddress,
+        undelegate_amount: Coin,
+        unbonded_from: Timespec,
+    ) {
+        self.attributes
+            .push(staking_address_attribute(staking_address));
+        self.attributes.push(StakingEventOpType::Undelegate.into());
+
+        self.attributes.push(
+            StakingDiffField(vec![
+                StakingDiff::Delegated(StakingCoinChange::Decrease, undelegate_amount),
+                StakingDiff::Unbonded(StakingCoinChange::Increase, undelegate_amount),
+                StakingDiff::UnbondedFrom(unbonded_from),
+                StakingDiff::DelegatedTo(None),
+            ])
+            .into(),
+        );
+    }
+
     fn to_event(&self) -> Event {
         let mut event = Event::new();
         event.field_type = TendermintEventType::StakingChange.to_string();
@@ -213,6 +375,7 @@ fn punishment_reason(punishment_kind: PunishmentKind) -> String {
     match punishment_kind {
         PunishmentKind::ByzantineFault => String::from("ByzantineFault"),
         PunishmentKind::NonLive => String::from("NonLive"),
+        PunishmentKind::TdbeEviction => String::from("TdbeEviction"),
     }
 }
 
@@ -221,10 +384,15 @@ enum StakingEventOpType {
     Unbond,
     Withdraw,
     NodeJoin,
+    NodeMetadataUpdate,
+    NodeLeave,
     Reward,
+    Treasury,
     Jail,
     Slash,
     Unjail,
+    Delegate,
+    Undelegate,
 }
 
 impl fmt::Display for StakingEventOpType {
@@ -234,10 +402,15 @@ impl fmt::Display for StakingEventOpType {
             StakingEventOpType::Unbond => write!(f, "unbond"),
             StakingEventOpType::Withdraw => write!(f, "withdraw"),
             StakingEventOpType::NodeJoin => write!(f, "nodejoin"),
+            StakingEventOpType::NodeMetadataUpdate => write!(f, "nodemetadataupdate"),
+            StakingEventOpType::NodeLeave => write!(f, "nodeleave"),
             StakingEventOpType::Reward => write!(f, "reward"),
+            StakingEventOpType::Treasury => write!(f, "treasury"),
             StakingEventOpType::Jail => write!(f, "jail"),
             StakingEventOpType::Slash => write!(f, "slash"),
             StakingEventOpType::Unjail => write!(f, "unjail"),
+            StakingEventOpType::Delegate => write!(f, "delegate"),
+            StakingEventOpType::Undelegate => write!(f, "undelegate"),
         }
     }
 }
@@ -280,8 +453,13 @@ enum StakingDiff {
     Bonded(StakingCoinChange, Coin),
     Unbonded(StakingCoinChange, Coin),
     UnbondedFrom(Timespec),
-    NodeJoin(CouncilNodeMeta),
+    NodeJoin(NodeMetadata),
     JailedUntil(Timespec),
+    /// the node record is removed after the unbonding period since this time
+    InactiveTime(Timespec),
+    /// the amount delegated to a council node
+    Delegated(StakingCoinChange, Coin),
+    DelegatedTo(Option<StakedStateAddress>),
 }
 
 impl Serialize for StakingDiff {
@@ -314,18 +492,48 @@ impl Serialize for StakingDiff {
                 state.serialize_field("value", &unbonded_from)?;
                 state.end()
             }
-            StakingDiff::NodeJoin(node) => {
+            StakingDiff::NodeJoin(NodeMetadata::CouncilNode(node)) => {
                 let mut state = serializer.serialize_struct("NodeJoin", 2)?;
                 state.serialize_field("key", "CouncilNode")?;
                 state.serialize_field("value", node)?;
                 state.end()
             }
+            StakingDiff::NodeJoin(NodeMetadata::CommunityNode(node)) => {
+                let mut state = serializer.serialize_struct("NodeJoin", 2)?;
+                state.serialize_field("key", "CommunityNode")?;
+                state.serialize_field("value", node)?;
+                state.end()
+            }
             StakingDiff::JailedUntil(jailed_until) => {
                 let mut state = serializer.serialize_struct("JailedUntil", 2)?;
                 state.serialize_field("key", "JailedUntil")?;
                 state.serialize_field("value", &jailed_until)?;
                 state.end()
             }
+            StakingDiff::InactiveTime(inactive_time) => {
+                let mut state = serializer.serialize_struct("InactiveTime", 2)?;
+                state.serialize_field("key", "InactiveTime")?;
+                state.serialize_field("value", &inactive_time)?;
+                state.end()
+            }
+            StakingDiff::Delegated(change, coin) => {
+                let mut state = serializer.serialize_struct("Delegated", 2)?;
+                state.serialize_field("key", "Delegated")?;
+                state.serialize_field(
+                    "value",
+                    format!("{}{}", change, u64::from(coin.to_owned())).as_str(),
+                )?;
+                state.end()
+            }
+            StakingDiff::DelegatedTo(council_node) => {
+                let mut state = serializer.serialize_struct("DelegatedTo", 2)?;
+                state.serialize_field("key", "DelegatedTo")?;
+                state.serialize_field(
+                    "value",
+                    &council_node.as_ref().map(|address| address.to_string()),
+                )?;
+                state.end()
+            }
         }
     }
 }
@@ -357,7 +565,7 @@ impl fmt::Display for StakingCoinChange {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chain_core::state::account::ConfidentialInit;
+    use chain_core::state::account::{ConfidentialInit, CouncilNodeMeta};
     use chain_core::state::tendermint::TendermintValidatorPubKey;
     use chain_core::tx::fee::Fee;
     use std::str::FromStr;
@@ -433,7 +641,8 @@ mod tests {
             #[test]
             fn to_string_should_serialize_to_json() {
                 let any_council_node = any_council_node();
-                let staking_diff = StakingDiff::NodeJoin(any_council_node);
+                let staking_diff =
+                    StakingDiff::NodeJoin(NodeMetadata::CouncilNode(any_council_node));
 
                 assert_eq!(
                     staking_diff.to_string(),
@@ -441,6 +650,18 @@ mod tests {
                 );
             }
 
+            #[test]
+            fn community_node_to_string_should_serialize_to_json() {
+                let any_community_node = any_council_node().node_info;
+                let staking_diff =
+                    StakingDiff::NodeJoin(NodeMetadata::CommunityNode(any_community_node));
+
+                assert_eq!(
+                    staking_diff.to_string(),
+                    "{\"key\":\"CommunityNode\",\"value\":{\"name\":\"Council Node\",\"security_contact\":\"security@crypto.com\",\"confidential_init\":{\"keypackage\":\"AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=\"}}}",
+                );
+            }
+
             fn any_council_node() -> CouncilNodeMeta {
                 let any_name = String::from("Council Node");
                 let any_security_contact = Some(String::from("security@crypto.com"));
@@ -472,6 +693,42 @@ mod tests {
                 );
             }
         }
+
+        mod inactive_time {
+            use super::*;
+
+            #[test]
+            fn to_string_should_serialize_to_json() {
+                let any_inactive_time: Timespec = 1587071014;
+                let staking_diff = StakingDiff::InactiveTime(any_inactive_time);
+
+                assert_eq!(
+                    staking_diff.to_string(),
+                    "{\"key\":\"InactiveTime\",\"value\":1587071014}",
+                );
+            }
+        }
+
+        mod delegated_to {
+            use super::*;
+
+            #[test]
+            fn to_string_should_serialize_to_json() {
+                let staking_diff = StakingDiff::DelegatedTo(Some(any_staking_address()));
+
+                assert_eq!(
+                    staking_diff.to_string(),
+                    "{\"key\":\"DelegatedTo\",\"value\":\"0x83fe11feb0887183eb62c30994bdd9e303497e3d\"}",
+                );
+
+                let staking_diff = StakingDiff::DelegatedTo(None);
+
+                assert_eq!(
+                    staking_diff.to_string(),
+                    "{\"key\":\"DelegatedTo\",\"value\":null}",
+                );
+            }
+        }
     }
 
     mod staking_event {
@@ -535,8 +792,11 @@ mod tests {
                 let any_staking_address = any_staking_address();
                 let any_council_node = any_council_node();
 
-                let event: Event =
-                    StakingEvent::NodeJoin(&any_staking_address, any_council_node.clone()).into();
+                let event: Event = StakingEvent::NodeJoin(
+                    &any_staking_address,
+                    NodeMetadata::CouncilNode(any_council_node.clone()),
+                )
+                .into();
 
                 assert_node_join_event(event, &any_staking_address, any_council_node)
             }
@@ -572,6 +832,38 @@ mod tests {
             }
         }
 
+        mod treasury {
+            use super::*;
+
+            #[test]
+            fn should_create_treasury_event() {
+                let any_staking_address = any_staking_address();
+                let any_amount = Coin::unit();
+
+                let event: Event = StakingEvent::Treasury(&any_staking_address, any_amount).into();
+
+                assert_eq!(
+                    event.field_type,
+                    TendermintEventType::StakingChange.to_string()
+                );
+                assert_eq!(event.attributes.len(), 3);
+                assert_kv_pair(
+                    event.attributes.get(1).unwrap(),
+                    TendermintEventKey::StakingOpType.to_string(),
+                    StakingEventOpType::Treasury.to_string(),
+                );
+                let expected_value = format!(
+                    "[{{\"key\":\"Unbonded\",\"value\":\"{}\"}}]",
+                    u64::from(any_amount)
+                );
+                assert_kv_pair(
+                    event.attributes.get(2).unwrap(),
+                    TendermintEventKey::StakingDiff.to_string(),
+                    expected_value,
+                );
+            }
+        }
+
         mod jail {
             use super::*;
 
@@ -629,6 +921,78 @@ mod tests {
             }
         }
 
+        mod node_leave {
+            use super::*;
+
+            #[test]
+            fn should_create_node_leave_event() {
+                let any_staking_address = any_staking_address();
+                let any_time: Timespec = 1587071014;
+
+                let event: Event = StakingEvent::NodeLeave(&any_staking_address, any_time).into();
+
+                assert_eq!(
+                    event.field_type,
+                    TendermintEventType::StakingChange.to_string()
+                );
+                assert_eq!(event.attributes.len(), 3);
+                assert_kv_pair(
+                    event.attributes.first().unwrap(),
+                    TendermintEventKey::StakingAddress.to_string(),
+                    any_staking_address.to_string(),
+                );
+                assert_kv_pair(
+                    event.attributes.get(1).unwrap(),
+                    TendermintEventKey::StakingOpType.to_string(),
+                    StakingEventOpType::NodeLeave.to_string(),
+                );
+                assert_kv_pair(
+                    event.attributes.get(2).unwrap(),
+                    TendermintEventKey::StakingDiff.to_string(),
+                    format!("[{{\"key\":\"InactiveTime\",\"value\":{}}}]", any_time),
+                );
+            }
+        }
+
+        mod delegate {
+            use super::*;
+
+            #[test]
+            fn should_create_delegate_event() {
+                let any_staking_address = any_staking_address();
+                let any_council_node =
+                    StakedStateAddress::from_str("0x0e7c045110b8dbf29765047380898919c5cb56f4")
+                        .unwrap();
+                let any_amount = Coin::unit();
+
+                let event: Event =
+                    StakingEvent::Delegate(&any_staking_address, &any_council_node, any_amount)
+                        .into();
+
+                assert_eq!(
+                    event.field_type,
+                    TendermintEventType::StakingChange.to_string()
+                );
+                assert_eq!(event.attributes.len(), 3);
+                assert_kv_pair(
+                    event.attributes.get(1).unwrap(),
+                    TendermintEventKey::StakingOpType.to_string(),
+                    StakingEventOpType::Delegate.to_string(),
+                );
+                let expected_value = format!(
+                    "[{{\"key\":\"Bonded\",\"value\":\"-{}\"}},{{\"key\":\"Delegated\",\"value\":\"{}\"}},{{\"key\":\"DelegatedTo\",\"value\":\"{}\"}}]",
+                    u64::from(any_amount),
+                    u64::from(any_amount),
+                    any_council_node
+                );
+                assert_kv_pair(
+                    event.attributes.get(2).unwrap(),
+                    TendermintEventKey::StakingDiff.to_string(),
+                    expected_value,
+                );
+            }
+        }
+
         fn assert_deposit_event(
             event: Event,
             staking_address: StakedStateAddress,
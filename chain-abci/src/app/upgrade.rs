@@ -0,0 +1,81 @@
+//! Coordinated network upgrades:
+//! a plan (from genesis or an approved proposal) is kept in the node state until its height.
+//! At the upgrade height, a binary which doesn't support the new app version records a halt
+//! marker and stops before processing the block; the upgraded binary replays the block
+//! with the new app version.
+//! The genesis app version comes from the genesis config (1 if not specified).
+//! The transactions are decoded in the wire format of the active app version
+//! (see `TxAux::decode_versioned`).
+use log::{info, warn};
+
+use crate::app::app_init::{ChainNodeApp, ChainNodeState};
+use crate::enclave_bridge::EnclaveProxy;
+use chain_core::state::governance::UpgradePlan;
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::APP_VERSION;
+use chain_storage::buffer::{flush_storage, BufferStore, KVBuffer};
+use chain_storage::Storage;
+
+/// replaces the scheduled upgrade with the approved one (if it bumps the app version)
+pub(crate) fn schedule_upgrade(state: &mut ChainNodeState, plan: UpgradePlan) {
+    if plan.app_version <= state.top_level.app_version || plan.height <= state.block_height {
+        warn!("ignoring the approved upgrade (already passed): {:?}", plan);
+        return;
+    }
+    if let Some(previous) = state.upgrade_plan.as_ref() {
+        info!("upgrade {:?} replaced", previous);
+    }
+    info!("upgrade scheduled: {:?}", plan);
+    state.upgrade_plan = Some(plan);
+}
+
+/// panics if the node halted for an upgrade which this binary doesn't support
+pub(crate) fn check_upgrade_halt(storage: &Storage) {
+    if let Some(plan) = chain_storage::get_upgrade_halt(storage) {
+        if plan.app_version > APP_VERSION {
+            panic!(
+                "the node halted at height {} for the upgrade \"{}\" to app version {}, but this binary only supports app version {}",
+                plan.height, plan.name, plan.app_version, APP_VERSION
+            );
+        }
+        info!(
+            "resuming at the upgrade \"{}\" (app version {}) from height {}",
+            plan.name, plan.app_version, plan.height
+        );
+    }
+}
+
+impl<T: EnclaveProxy> ChainNodeApp<T> {
+    /// Activates the scheduled upgrade at its height.
+    /// If this binary doesn't support the new app version,
+    /// the halt marker is persisted and the node stops without processing the block.
+    pub(crate) fn apply_scheduled_upgrade(&mut self, block_height: BlockHeight) {
+        let last_state = self
+            .last_state
+            .as_mut()
+            .expect("executing begin block, but no app state stored (i.e. no initchain or recovery was executed)");
+        let plan = match last_state.upgrade_plan.as_ref() {
+            Some(plan) if plan.height <= block_height => plan.clone(),
+            _ => return,
+        };
+        if plan.app_version > APP_VERSION {
+            let mut buffer = KVBuffer::new();
+            chain_storage::store_upgrade_halt(
+                &mut BufferStore::new(&self.storage, &mut buffer),
+                &plan,
+            );
+            flush_storage(&mut self.storage, buffer).expect("storage io error");
+            panic!(
+                "UPGRADE NEEDED: upgrade \"{}\" to app version {} is scheduled at height {}, but this binary only supports app version {}",
+                plan.name, plan.app_version, plan.height, APP_VERSION
+            );
+        }
+        info!(
+            "upgrade \"{}\" activated: app version {}",
+            plan.name, plan.app_version
+        );
+        last_state.top_level.app_version = plan.app_version;
+        last_state.upgrade_plan = None;
+        chain_storage::clear_upgrade_halt(&mut kv_store!(self));
+    }
+}
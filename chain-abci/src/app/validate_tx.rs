@@ -3,12 +3,13 @@ use crate::enclave_bridge::EnclaveProxy;
 use crate::storage::{
     process_public_tx, verify_enclave_tx, TxAction, TxEnclaveAction, TxPublicAction,
 };
-use crate::tx_error::TxError;
+use crate::tx_error::{MLSHandshakeError, TxError};
 use abci::*;
 use chain_core::tx::data::TxId;
+use chain_core::tx::fee::FeeInput;
 use chain_core::tx::TxAux;
 use chain_storage::buffer::{StoreKV, StoreStaking};
-use parity_scale_codec::Decode;
+use ra_client::ENCLAVE_CERT_VERIFIER;
 
 /// Wrapper to abstract over CheckTx and DeliverTx requests
 pub trait RequestWithTx {
@@ -66,15 +67,70 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
         req: &impl RequestWithTx,
         buffer_type: BufferType,
     ) -> Result<(TxAux, TxAction), TxError> {
-        let extra_info = self.tx_extra_info(req.tx().len());
+        let app_version = match buffer_type {
+            BufferType::Consensus => self.last_state.as_ref().expect("expect last_state"),
+            BufferType::Mempool => self.mempool_state.as_ref().expect("expect mempool_state"),
+        }
+        .top_level
+        .app_version;
+        let txaux = TxAux::decode_versioned(app_version, &mut req.tx())?;
+        let extra_info = self.tx_extra_info(&FeeInput::with_tx_size(&txaux, req.tx().len()));
         let state = match buffer_type {
             BufferType::Consensus => self.last_state.as_mut().expect("expect last_state"),
             BufferType::Mempool => self.mempool_state.as_mut().expect("expect mempool_state"),
         };
-        let txaux = TxAux::decode(&mut req.tx())?;
         let txid = txaux.tx_id();
         let tx_action = match &txaux {
-            TxAux::MLSHandshake(_) => return Err(TxError::WIPMLSData),
+            TxAux::MLSHandshake(tx) => {
+                let staking_getter = staking_getter!(self, state.staking_version, buffer_type);
+                let mut node_keypackages = state
+                    .staking_table
+                    .list_council_keypackages(&staking_getter);
+                node_keypackages.extend(
+                    state
+                        .staking_table
+                        .list_community_keypackages(&staking_getter),
+                );
+                let group = state
+                    .tdbe_group
+                    .as_mut()
+                    .ok_or(MLSHandshakeError::GroupNotInitialized)?;
+                let changes = group.process_handshake(
+                    tx,
+                    state.block_time,
+                    &node_keypackages,
+                    &*ENCLAVE_CERT_VERIFIER,
+                )?;
+                state.top_level.tdbe_group_hash = group.hash();
+                let epoch = group.epoch();
+
+                let mut staking_store = staking_store!(self, state.staking_version, buffer_type);
+                // only the council nodes can evict the community nodes
+                let by_council = !state
+                    .staking_table
+                    .is_community_keypackage(&staking_store, &changes.committer);
+                for (keypackage, updated) in changes.updated {
+                    state.staking_table.update_community_keypackage(
+                        &mut staking_store,
+                        &keypackage,
+                        updated,
+                    );
+                }
+                let mut jailed = Vec::new();
+                if by_council {
+                    let jail_duration = state.get_unbonding_period();
+                    for keypackage in changes.removed.iter() {
+                        jailed.extend(state.staking_table.jail_community_node(
+                            &mut staking_store,
+                            keypackage,
+                            state.block_time,
+                            state.block_height,
+                            jail_duration,
+                        ));
+                    }
+                }
+                TxAction::MLSHandshake { epoch, jailed }
+            }
             TxAux::EnclaveTx(tx) => {
                 let action = verify_enclave_tx(
                     &mut self.tx_validator,
@@ -121,6 +177,7 @@ impl<T: EnclaveProxy> ChainNodeApp<T> {
                 let action = process_public_tx(
                     &mut staking_store!(self, state.staking_version, buffer_type),
                     &mut state.staking_table,
+                    &mut state.proposals,
                     state.enclave_isv_svn,
                     &extra_info,
                     &tx,
@@ -69,7 +69,8 @@ impl EnclaveProxy for MockClient {
                         payload,
                         inputs
                             .iter()
-                            .map(|log| unseal(&log))
+                            .zip(request.inputs_created.iter())
+                            .map(|(log, created)| unseal(&log).map(|tx| (tx, *created)))
                             .collect::<Result<_, _>>()?,
                     ),
                     (
@@ -83,7 +84,8 @@ impl EnclaveProxy for MockClient {
                         payload,
                         inputs
                             .iter()
-                            .map(|log| unseal(&log))
+                            .zip(request.inputs_created.iter())
+                            .map(|(log, created)| unseal(&log).map(|tx| (tx, *created)))
                             .collect::<Result<_, _>>()?,
                     ),
                     (TxEnclaveAux::WithdrawUnbondedStakeTx { payload, .. }, _) => (payload, vec![]),
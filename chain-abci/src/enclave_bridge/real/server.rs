@@ -1,13 +1,15 @@
-use crate::app::ChainNodeState;
+use crate::app::{get_max_evidence_age_blocks, ChainNodeState};
 use crate::enclave_bridge::real::enclave_u::encrypt_tx;
 use chain_core::state::account::StakedState;
 use chain_core::state::account::StakedStateOpWitness;
+use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::TxId;
+use chain_core::tx::fee::FeeInput;
 use chain_storage::buffer::Get;
 use chain_storage::jellyfish::StakingGetter;
 use chain_storage::ReadOnlyStorage;
 use chain_tx_validation::witness::verify_tx_recover_address;
-use chain_tx_validation::ChainInfo;
+use chain_tx_validation::{ChainInfo, TxCreation};
 use enclave_protocol::IntraEnclaveRequest;
 use enclave_protocol::{EnclaveRequest, EnclaveResponse, IntraEncryptRequest, FLAGS};
 use parity_scale_codec::{Decode, Encode};
@@ -59,6 +61,15 @@ impl TxValidationServer {
         Some(result)
     }
 
+    fn lookup_creation(&self, inputs: &[TxoPointer]) -> Vec<TxCreation> {
+        inputs
+            .iter()
+            .map(|input| {
+                chain_storage::get_tx_creation(&self.storage, &input.id).unwrap_or_default()
+            })
+            .collect()
+    }
+
     fn lookup_state(
         &self,
         txid: &TxId,
@@ -83,13 +94,21 @@ impl TxValidationServer {
                     }
                     Ok(EnclaveRequest::EncryptTx(req)) => {
                         let result = {
-                            let tx_inputs = match req.tx_inputs {
-                                Some(inputs) => self.lookup_txids(inputs.iter().map(|x| x.id)),
-                                _ => None,
+                            let fee_input = FeeInput {
+                                tx_size: req.tx_size as usize,
+                                no_of_inputs: req.tx_inputs.as_ref().map_or(0, Vec::len),
+                                no_of_outputs: req.no_of_outputs as usize,
+                            };
+                            let (tx_inputs, inputs_created) = match req.tx_inputs {
+                                Some(inputs) => (
+                                    self.lookup_txids(inputs.iter().map(|x| x.id)),
+                                    self.lookup_creation(&inputs),
+                                ),
+                                _ => (None, Vec::new()),
                             };
                             match self.storage.get_last_app_state() {
                                 Some(state) => {
-                                    let last_state = ChainNodeState::decode(&mut state.as_slice())
+                                    let last_state = ChainNodeState::decode_stored(&state)
                                         .expect("deserialize app state");
                                     let account = match req.op_sig {
                                         Some(sig) => self.lookup_state(
@@ -104,7 +123,10 @@ impl TxValidationServer {
                                     let min_fee = last_state
                                         .top_level
                                         .network_params
-                                        .calculate_fee(req.tx_size as usize)
+                                        .calculate_fee(
+                                            &fee_input,
+                                            last_state.top_level.block_fullness,
+                                        )
                                         .expect("valid fee");
                                     let info = ChainInfo {
                                         min_fee_computed: min_fee,
@@ -112,11 +134,16 @@ impl TxValidationServer {
                                         block_time: last_state.block_time,
                                         block_height: last_state.block_height,
                                         max_evidence_age: last_state.max_evidence_age,
+                                        max_evidence_age_blocks: get_max_evidence_age_blocks(
+                                            &self.storage,
+                                        ),
+                                        app_version: last_state.top_level.app_version,
                                     };
                                     let request = IntraEncryptRequest {
                                         txid: req.txid,
                                         sealed_enc_request: req.sealed_enc_request,
                                         tx_inputs,
+                                        inputs_created,
                                         info,
                                         account,
                                     };
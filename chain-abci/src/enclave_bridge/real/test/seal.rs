@@ -24,7 +24,7 @@ use chain_core::tx::{
     witness::TxInWitness,
     TxEnclaveAux,
 };
-use chain_core::ChainInfo;
+use chain_core::{ChainInfo, TxCreation};
 use chain_tx_validation::Error;
 use enclave_protocol::{
     EncryptionRequest, IntraEnclaveRequest, IntraEnclaveResponseOk, VerifyTxRequest,
@@ -161,6 +161,8 @@ pub fn test_sealing() {
         block_time: 1,
         block_height: BlockHeight::genesis(),
         max_evidence_age: 0,
+        max_evidence_age_blocks: 0,
+        app_version: chain_core::APP_VERSION,
     };
 
     let request0 = IntraEnclaveRequest::ValidateTx {
@@ -168,6 +170,7 @@ pub fn test_sealing() {
             tx: withdrawtx,
             account: Some(account),
             info,
+            inputs_created: vec![],
         }),
         tx_inputs: None,
     };
@@ -221,6 +224,7 @@ pub fn test_sealing() {
             tx: transfertx,
             account: None,
             info,
+            inputs_created: vec![TxCreation::default()],
         }),
         tx_inputs: Some(vec![sealedtx.clone()]),
     };
@@ -257,6 +261,7 @@ pub fn test_sealing() {
             tx: transfertx2,
             account: None,
             info,
+            inputs_created: vec![TxCreation::default()],
         }),
         tx_inputs: Some(vec![sealedtx]),
     };
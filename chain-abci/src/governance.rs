@@ -0,0 +1,482 @@
+//! Network parameter change proposals and their votes,
+//! the votes are weighted by the voting stake (bonded + delegated coins)
+//! of the voters at the activation height.
+use std::collections::BTreeMap;
+
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::common::{Timespec, H256};
+use chain_core::init::coin::Coin;
+use chain_core::init::config::NetworkParameters;
+use chain_core::state::account::{NodeState, StakedStateAddress};
+use chain_core::state::governance::{
+    NetworkParameterUpdate, ParamChangeProposalTx, ParamChangeVoteTx, ProposalId,
+};
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::data::txid_hash;
+use chain_core::tx::TransactionId;
+use chain_storage::buffer::{GetStaking, StoreStaking};
+
+use crate::staking::{set_staking, StakingTable};
+use crate::tx_error::{GovernanceError, PublicTxError};
+
+/// A proposal waiting for its activation height
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct Proposal {
+    pub proposer: StakedStateAddress,
+    pub changes: Vec<NetworkParameterUpdate>,
+    pub activation_height: BlockHeight,
+    /// the latest vote of each staked state (true if in favor)
+    pub votes: BTreeMap<StakedStateAddress, bool>,
+}
+
+/// The outcome of a tallied proposal
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TallyOutcome {
+    pub proposal_id: ProposalId,
+    pub yes: Coin,
+    pub no: Coin,
+    pub approved: bool,
+}
+
+/// Open proposals (persisted with the chain node state)
+#[derive(Clone, Debug, Default, Encode, Decode)]
+pub struct ProposalTable {
+    proposals: BTreeMap<ProposalId, Proposal>,
+}
+
+impl ProposalTable {
+    /// hash of the open proposals (committed in the app hash):
+    /// blake3(scale bytes(proposal table)), or zero if there are none
+    pub fn hash(&self) -> H256 {
+        if self.proposals.is_empty() {
+            H256::default()
+        } else {
+            txid_hash(&self.encode())
+        }
+    }
+
+    /// Handle `ParamChangeProposalTx`
+    pub fn propose(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        staking_table: &StakingTable,
+        block_height: BlockHeight,
+        tx: &ParamChangeProposalTx,
+    ) -> Result<ProposalId, PublicTxError> {
+        let mut staking = staking_table.get_or_default(heap, &tx.address);
+        if tx.nonce != staking.nonce {
+            return Err(PublicTxError::IncorrectNonce);
+        }
+        match staking.node_meta.as_ref() {
+            Some(NodeState::CouncilNode(val)) if val.is_active() => {}
+            _ => {
+                return Err(GovernanceError::NotActiveCouncilNode.into());
+            }
+        }
+        if tx.changes.is_empty() {
+            return Err(GovernanceError::EmptyProposal.into());
+        }
+        for change in tx.changes.iter() {
+            change
+                .validate()
+                .map_err(GovernanceError::InvalidParameter)?;
+        }
+        if tx.activation_height <= block_height {
+            return Err(GovernanceError::ActivationHeightPassed.into());
+        }
+        let proposal_id = tx.id();
+        if self.proposals.contains_key(&proposal_id) {
+            return Err(GovernanceError::DuplicateProposal.into());
+        }
+
+        self.proposals.insert(
+            proposal_id,
+            Proposal {
+                proposer: tx.address,
+                changes: tx.changes.clone(),
+                activation_height: tx.activation_height,
+                votes: BTreeMap::new(),
+            },
+        );
+        staking.inc_nonce();
+        set_staking(heap, staking, staking_table.minimal_required_staking);
+        Ok(proposal_id)
+    }
+
+    /// Handle `ParamChangeVoteTx`
+    pub fn vote(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        staking_table: &StakingTable,
+        block_height: BlockHeight,
+        tx: &ParamChangeVoteTx,
+    ) -> Result<(), PublicTxError> {
+        let mut staking = staking_table.get_or_default(heap, &tx.address);
+        if tx.nonce != staking.nonce {
+            return Err(PublicTxError::IncorrectNonce);
+        }
+        if staking.is_jailed() {
+            return Err(GovernanceError::IsJailed.into());
+        }
+        if staking.voting_stake() == Coin::zero() {
+            return Err(GovernanceError::NoBondedCoins.into());
+        }
+        let proposal = self
+            .proposals
+            .get_mut(&tx.proposal_id)
+            .ok_or(GovernanceError::ProposalNotFound)?;
+        // votes delivered in the activation block are still counted (tallied in end_block)
+        if proposal.activation_height < block_height {
+            return Err(GovernanceError::ActivationHeightPassed.into());
+        }
+
+        proposal.votes.insert(tx.address, tx.approve);
+        staking.inc_nonce();
+        set_staking(heap, staking, staking_table.minimal_required_staking);
+        Ok(())
+    }
+
+    /// Tally the proposals which activation height is reached and remove them.
+    /// A proposal is approved if:
+    /// - more than 2/3 of the voted stake is in favor
+    /// - the voted stake is at least 1/2 of the stake of the current validators
+    ///
+    /// Returns the tally outcomes and the changes of the approved proposals (in proposal order).
+    pub fn tally(
+        &mut self,
+        heap: &impl GetStaking,
+        staking_table: &StakingTable,
+        block_height: BlockHeight,
+    ) -> (Vec<TallyOutcome>, Vec<NetworkParameterUpdate>) {
+        let due = self
+            .proposals
+            .iter()
+            .filter(|(_, proposal)| proposal.activation_height <= block_height)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        if due.is_empty() {
+            return (vec![], vec![]);
+        }
+
+        let validator_stake = u64::from(staking_table.reward_total_staking(heap)) as u128;
+        let mut outcomes = Vec::with_capacity(due.len());
+        let mut changes = Vec::new();
+        for proposal_id in due.into_iter() {
+            // no panic: collected from the keys above
+            let proposal = self.proposals.remove(&proposal_id).unwrap();
+            let (mut yes, mut no) = (0u128, 0u128);
+            for (addr, approve) in proposal.votes.iter() {
+                if let Some(staking) = heap.get(addr) {
+                    if staking.is_jailed() {
+                        continue;
+                    }
+                    let weight = u64::from(staking.voting_stake()) as u128;
+                    if *approve {
+                        yes += weight;
+                    } else {
+                        no += weight;
+                    }
+                }
+            }
+            let voted = yes + no;
+            let approved = yes * 3 > voted * 2 && voted * 2 >= validator_stake;
+            if approved {
+                changes.extend(proposal.changes.into_iter());
+            }
+            outcomes.push(TallyOutcome {
+                proposal_id,
+                // no panic: sum of voting stakes doesn't exceed the max supply
+                yes: Coin::new(yes as u64).unwrap(),
+                no: Coin::new(no as u64).unwrap(),
+                approved,
+            });
+        }
+        (outcomes, changes)
+    }
+
+    /// Query open proposal by id
+    pub fn get_proposal(&self, proposal_id: &ProposalId) -> Option<&Proposal> {
+        self.proposals.get(proposal_id)
+    }
+}
+
+/// Activate the approved changes:
+/// returns the new network parameters, the staking table is updated
+/// with the new minimal stakes.
+pub fn activate_changes(
+    heap: &mut impl StoreStaking,
+    staking_table: &mut StakingTable,
+    network_params: &NetworkParameters,
+    changes: &[NetworkParameterUpdate],
+    block_time: Timespec,
+    block_height: BlockHeight,
+) -> NetworkParameters {
+    let new_params = network_params.apply_updates(changes, block_height);
+    staking_table.update_minimal_required_staking(
+        heap,
+        new_params.get_required_council_node_stake(),
+        new_params.get_required_community_node_stake(),
+        block_time,
+        block_height,
+    );
+    new_params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::init::address::RedeemAddress;
+    use chain_core::state::account::{StakedState, StakedStateOpAttributes, Validator};
+    use chain_core::state::tendermint::TendermintValidatorPubKey;
+    use chain_storage::buffer::{Get, MemStore};
+    use secp256k1::{
+        key::{PublicKey, SecretKey},
+        Secp256k1,
+    };
+    use test_common::chain_env::{get_init_network_params, mock_council_node_meta};
+
+    type StakingMemStore = MemStore<StakedStateAddress, StakedState>;
+
+    fn staking_address(seed: &[u8; 32]) -> StakedStateAddress {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(seed).expect("32 bytes, within curve order");
+        let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+        StakedStateAddress::BasicRedeem(RedeemAddress::from(&public_key))
+    }
+
+    fn new_validator(seed: &[u8; 32], bonded: Coin) -> StakedState {
+        let mut staking = StakedState::default(staking_address(seed));
+        staking.bonded = bonded;
+        staking.node_meta = Some(NodeState::CouncilNode(Validator::new(
+            mock_council_node_meta(TendermintValidatorPubKey::Ed25519(*seed)),
+        )));
+        staking
+    }
+
+    fn init() -> (StakingTable, StakingMemStore, Coin) {
+        let minimal = Coin::new(10_0000_0000).unwrap();
+        let mut store = StakingMemStore::new();
+        let validators = vec![
+            new_validator(&[0xcc; 32], minimal),
+            new_validator(&[0xcd; 32], (minimal + minimal).unwrap()),
+        ];
+        for staking in validators.iter() {
+            store.set_staking(staking.clone());
+        }
+        let table = StakingTable::from_genesis(
+            &store,
+            minimal,
+            minimal,
+            2,
+            &validators
+                .iter()
+                .map(|staking| staking.address)
+                .collect::<Vec<_>>(),
+        );
+        (table, store, minimal)
+    }
+
+    fn proposal(
+        store: &StakingMemStore,
+        seed: &[u8; 32],
+        changes: Vec<NetworkParameterUpdate>,
+        activation_height: BlockHeight,
+    ) -> ParamChangeProposalTx {
+        let address = staking_address(seed);
+        ParamChangeProposalTx::new(
+            store.get_or_default(&address).nonce,
+            address,
+            StakedStateOpAttributes::new(0),
+            changes,
+            activation_height,
+        )
+    }
+
+    fn vote(
+        store: &StakingMemStore,
+        seed: &[u8; 32],
+        proposal_id: ProposalId,
+        approve: bool,
+    ) -> ParamChangeVoteTx {
+        let address = staking_address(seed);
+        ParamChangeVoteTx::new(
+            store.get_or_default(&address).nonce,
+            address,
+            StakedStateOpAttributes::new(0),
+            proposal_id,
+            approve,
+        )
+    }
+
+    #[test]
+    fn check_proposal_validation() {
+        let (table, mut store, _) = init();
+        let mut proposals = ProposalTable::default();
+        let height = BlockHeight::new(10);
+
+        // not a council node
+        let tx = proposal(
+            &store,
+            &[0xce; 32],
+            vec![NetworkParameterUpdate::MaxValidators(3)],
+            BlockHeight::new(20),
+        );
+        assert!(matches!(
+            proposals.propose(&mut store, &table, height, &tx),
+            Err(PublicTxError::Governance(
+                GovernanceError::NotActiveCouncilNode
+            ))
+        ));
+
+        let tx = proposal(&store, &[0xcc; 32], vec![], BlockHeight::new(20));
+        assert!(matches!(
+            proposals.propose(&mut store, &table, height, &tx),
+            Err(PublicTxError::Governance(GovernanceError::EmptyProposal))
+        ));
+
+        let tx = proposal(
+            &store,
+            &[0xcc; 32],
+            vec![NetworkParameterUpdate::MaxValidators(0)],
+            BlockHeight::new(20),
+        );
+        assert!(matches!(
+            proposals.propose(&mut store, &table, height, &tx),
+            Err(PublicTxError::Governance(
+                GovernanceError::InvalidParameter(_)
+            ))
+        ));
+
+        let tx = proposal(
+            &store,
+            &[0xcc; 32],
+            vec![NetworkParameterUpdate::MaxValidators(3)],
+            height,
+        );
+        assert!(matches!(
+            proposals.propose(&mut store, &table, height, &tx),
+            Err(PublicTxError::Governance(
+                GovernanceError::ActivationHeightPassed
+            ))
+        ));
+
+        let tx = proposal(
+            &store,
+            &[0xcc; 32],
+            vec![NetworkParameterUpdate::MaxValidators(3)],
+            BlockHeight::new(20),
+        );
+        let proposal_id = proposals.propose(&mut store, &table, height, &tx).unwrap();
+        assert_eq!(proposal_id, tx.id());
+        assert_eq!(store.get(&tx.address).unwrap().nonce, 1);
+        // replay
+        assert!(matches!(
+            proposals.propose(&mut store, &table, height, &tx),
+            Err(PublicTxError::IncorrectNonce)
+        ));
+
+        // voter without bonded coins
+        let tx = vote(&store, &[0xce; 32], proposal_id, true);
+        assert!(matches!(
+            proposals.vote(&mut store, &table, height, &tx),
+            Err(PublicTxError::Governance(GovernanceError::NoBondedCoins))
+        ));
+        let tx = vote(&store, &[0xcd; 32], [0; 32], true);
+        assert!(matches!(
+            proposals.vote(&mut store, &table, height, &tx),
+            Err(PublicTxError::Governance(GovernanceError::ProposalNotFound))
+        ));
+        let tx = vote(&store, &[0xcd; 32], proposal_id, true);
+        assert!(matches!(
+            proposals.vote(&mut store, &table, BlockHeight::new(21), &tx),
+            Err(PublicTxError::Governance(
+                GovernanceError::ActivationHeightPassed
+            ))
+        ));
+    }
+
+    #[test]
+    fn check_tally_and_activation() {
+        let (mut table, mut store, minimal) = init();
+        let mut proposals = ProposalTable::default();
+        let params = NetworkParameters::Genesis(get_init_network_params(Coin::zero()));
+        let height = BlockHeight::new(10);
+        let activation_height = BlockHeight::new(20);
+        let new_minimal = (minimal + Coin::unit()).unwrap();
+
+        let tx = proposal(
+            &store,
+            &[0xcc; 32],
+            vec![NetworkParameterUpdate::RequiredCouncilNodeStake(
+                new_minimal,
+            )],
+            activation_height,
+        );
+        let approved_id = proposals.propose(&mut store, &table, height, &tx).unwrap();
+        let tx = proposal(
+            &store,
+            &[0xcc; 32],
+            vec![NetworkParameterUpdate::MaxValidators(1)],
+            activation_height,
+        );
+        let rejected_id = proposals.propose(&mut store, &table, height, &tx).unwrap();
+
+        // the later vote replaces the earlier one
+        let tx = vote(&store, &[0xcd; 32], approved_id, false);
+        proposals.vote(&mut store, &table, height, &tx).unwrap();
+        let voted_hash = proposals.hash();
+        let tx = vote(&store, &[0xcd; 32], approved_id, true);
+        proposals.vote(&mut store, &table, height, &tx).unwrap();
+        // the votes are committed in the hash
+        assert_ne!(proposals.hash(), voted_hash);
+        // 2/3 of the voted stake isn't enough
+        let tx = vote(&store, &[0xcd; 32], rejected_id, true);
+        proposals.vote(&mut store, &table, height, &tx).unwrap();
+        let tx = vote(&store, &[0xcc; 32], rejected_id, false);
+        proposals.vote(&mut store, &table, height, &tx).unwrap();
+
+        // not due yet
+        let (outcomes, _) = proposals.tally(&store, &table, height);
+        assert!(outcomes.is_empty());
+
+        let (outcomes, changes) = proposals.tally(&store, &table, activation_height);
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes
+            .iter()
+            .any(|outcome| outcome.proposal_id == approved_id && outcome.approved));
+        assert!(outcomes
+            .iter()
+            .any(|outcome| outcome.proposal_id == rejected_id && !outcome.approved));
+        assert!(proposals.get_proposal(&approved_id).is_none());
+        assert!(proposals.get_proposal(&rejected_id).is_none());
+        assert_eq!(proposals.hash(), ProposalTable::default().hash());
+
+        let new_params = activate_changes(
+            &mut store,
+            &mut table,
+            &params,
+            &changes,
+            0,
+            activation_height,
+        );
+        assert_eq!(new_params.get_required_council_node_stake(), new_minimal);
+        assert_eq!(new_params.get_max_validators(), params.get_max_validators());
+        assert_ne!(
+            new_params.hash(chain_core::APP_VERSION),
+            params.hash(chain_core::APP_VERSION)
+        );
+
+        // the validator with exactly the old minimal stake is inactivated
+        let staking = store.get(&staking_address(&[0xcc; 32])).unwrap();
+        match staking.node_meta {
+            Some(NodeState::CouncilNode(val)) => assert!(!val.is_active()),
+            _ => unreachable!(),
+        }
+        let staking = store.get(&staking_address(&[0xcd; 32])).unwrap();
+        match staking.node_meta {
+            Some(NodeState::CouncilNode(val)) => assert!(val.is_active()),
+            _ => unreachable!(),
+        }
+    }
+}
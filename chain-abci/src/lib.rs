@@ -1,6 +1,10 @@
 pub mod app;
 pub mod enclave_bridge;
+pub mod governance;
 pub mod liveness;
+pub mod peer_filter;
+pub mod snapshot;
 pub mod staking;
 pub mod storage;
+pub mod tdbe;
 pub mod tx_error;
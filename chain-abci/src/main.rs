@@ -8,13 +8,20 @@ use chain_abci::app::{sanity_check_enabled, ChainNodeApp};
 use chain_abci::enclave_bridge::mock::MockClient;
 #[cfg(all(not(feature = "mock-enclave"), target_os = "linux"))]
 use chain_abci::enclave_bridge::real::TxValidationApp;
+use chain_abci::peer_filter::PeerFilter;
+use chain_abci::snapshot::SnapshotStore;
+use chain_core::common::{H256, HASH_SIZE_256};
 use chain_core::init::network::{get_network, get_network_id, init_chain_id};
+use chain_storage::pruning::PruningMode;
 use chain_storage::{Storage, StorageConfig, StorageType};
 use log::warn;
 use serde::Deserialize;
 use std::io::BufReader;
 use structopt::StructOpt;
 
+/// the number of the most recent snapshots kept if not configured
+const DEFAULT_SNAPSHOT_KEEP_RECENT: usize = 2;
+
 #[derive(Deserialize, Debug)]
 pub struct Config {
     port: u16,
@@ -23,6 +30,11 @@ pub struct Config {
     chain_id: Option<String>,
     enclave_server: Option<String>,
     tx_query: Option<String>,
+    pruning: Option<String>,
+    snapshot_interval: Option<u64>,
+    snapshot_keep_recent: Option<usize>,
+    #[serde(default)]
+    p2p_filter: PeerFilter,
 }
 
 impl Default for Config {
@@ -34,6 +46,10 @@ impl Default for Config {
             chain_id: None,
             enclave_server: None,
             tx_query: None,
+            pruning: None,
+            snapshot_interval: None,
+            snapshot_keep_recent: None,
+            p2p_filter: PeerFilter::default(),
         }
     }
 }
@@ -64,6 +80,15 @@ impl Config {
         if opt.tx_query.is_some() {
             self.tx_query = opt.tx_query.clone();
         }
+        if opt.pruning.is_some() {
+            self.pruning = opt.pruning.clone();
+        }
+        if opt.snapshot_interval.is_some() {
+            self.snapshot_interval = opt.snapshot_interval;
+        }
+        if opt.snapshot_keep_recent.is_some() {
+            self.snapshot_keep_recent = opt.snapshot_keep_recent;
+        }
     }
     pub fn is_valid(&self) -> bool {
         let mut valid = true;
@@ -79,8 +104,21 @@ impl Config {
             log::error!("enclave_server should be set");
             valid = false
         }
+        if let Err(e) = self.pruning_mode() {
+            log::error!("{}", e);
+            valid = false
+        }
+        if let Err(e) = self.p2p_filter.validate() {
+            log::error!("{}", e);
+            valid = false
+        }
         valid
     }
+    pub fn pruning_mode(&self) -> Result<PruningMode, String> {
+        self.pruning
+            .as_ref()
+            .map_or(Ok(PruningMode::default()), |mode| mode.parse())
+    }
 }
 
 #[derive(Debug, StructOpt)]
@@ -126,6 +164,33 @@ pub struct AbciOpt {
         help = "Optional transaction query support for clients (tx query enclave listening address, e.g. mydomain.com:4444)"
     )]
     tx_query: Option<String>,
+    #[structopt(
+        long = "pruning",
+        help = "How much of the staking state history is kept: archive (default), keep-recent:N (the last N versions) or keep-every:K (every Kth version)"
+    )]
+    pruning: Option<String>,
+    #[structopt(
+        long = "snapshot_interval",
+        help = "Optional state snapshots taken at every N blocks (stored in the snapshots directory under the data directory)"
+    )]
+    snapshot_interval: Option<u64>,
+    #[structopt(
+        long = "snapshot_keep_recent",
+        help = "The number of the most recent state snapshots to keep (default: 2)"
+    )]
+    snapshot_keep_recent: Option<usize>,
+    #[structopt(
+        long = "restore_snapshot",
+        requires = "restore_app_hash",
+        help = "Restores the state snapshot in the directory (e.g. <data>/snapshots/<height> of another node) into the empty storage before starting"
+    )]
+    restore_snapshot: Option<PathBuf>,
+    #[structopt(
+        long = "restore_app_hash",
+        requires = "restore_snapshot",
+        help = "The trusted app hash (hex) of the block of the restored snapshot"
+    )]
+    restore_app_hash: Option<String>,
 }
 
 /// normal
@@ -179,16 +244,51 @@ fn main() {
     let host = config.host.parse().expect("invalid host");
     let addr = SocketAddr::new(host, config.port);
     let storage = Storage::new(&StorageConfig::new(&opt.data, StorageType::Node));
-    info!("starting up");
-    abci::run(
-        addr,
-        ChainNodeApp::new_with_storage(
-            tx_validator,
-            &config.genesis_app_hash.unwrap(),
-            &config.chain_id.unwrap(),
-            storage,
-            config.tx_query,
-            config.enclave_server,
-        ),
+    let pruning = config.pruning_mode().expect("invalid pruning mode");
+    info!("starting up (pruning={})", pruning);
+    let mut app = ChainNodeApp::new_with_storage(
+        tx_validator,
+        &config.genesis_app_hash.unwrap(),
+        &config.chain_id.unwrap(),
+        storage,
+        config.tx_query,
+        config.enclave_server,
     );
+    app.pruning = pruning;
+    if config.p2p_filter.is_enabled() {
+        info!("p2p peer filtering enabled: {:?}", config.p2p_filter);
+    }
+    app.peer_filter = config.p2p_filter;
+    if let Some(interval) = config.snapshot_interval.filter(|interval| *interval > 0) {
+        let snapshots_dir = Path::new(&opt.data).join("snapshots");
+        info!("state snapshots at every {} blocks", interval);
+        app.snapshots = Some(SnapshotStore::new(
+            snapshots_dir,
+            interval,
+            config
+                .snapshot_keep_recent
+                .unwrap_or(DEFAULT_SNAPSHOT_KEEP_RECENT),
+        ));
+    }
+    if let (Some(dir), Some(app_hash)) = (&opt.restore_snapshot, &opt.restore_app_hash) {
+        let app_hash = match hex::decode(app_hash) {
+            Ok(app_hash) if app_hash.len() == HASH_SIZE_256 => {
+                let mut hash = H256::default();
+                hash.copy_from_slice(&app_hash);
+                hash
+            }
+            _ => {
+                log::error!("restore_app_hash should be a 32-byte hex string");
+                return;
+            }
+        };
+        match app.restore_snapshot_from_dir(dir, &app_hash) {
+            Ok(height) => info!("restored the snapshot at height {}", height),
+            Err(e) => {
+                log::error!("failed to restore the snapshot {:?}: {}", dir, e);
+                return;
+            }
+        }
+    }
+    abci::run(addr, app);
 }
@@ -0,0 +1,211 @@
+//! Filtering of the peers Tendermint connects to
+//!
+//! "When Tendermint connects to a peer, it sends two queries to the ABCI application using the following paths, with no additional data:
+//! * /p2p/filter/addr/<IP:PORT>, where <IP:PORT> denote the IP address and the port of the connection
+//! * p2p/filter/id/<ID>, where <ID> is the peer node ID (ie. the pubkey.Address() for the peer's PubKey)
+//! If either of these queries return a non-zero ABCI code, Tendermint will refuse to connect to the peer."
+//!
+//! The filter is configured in the abci config file, e.g.:
+//! ```yaml
+//! p2p_filter:
+//!   allow: ["0e7c045110b8dbf29765047380898919c5cb56f4"]
+//!   deny: ["10.0.0.5", "10.0.0.6:26656", "fe7c045110b8dbf29765047380898919c5cb56f9"]
+//!   staked_nodes_only: true
+//! ```
+use std::collections::BTreeSet;
+use std::net::{IpAddr, SocketAddr};
+
+use serde::Deserialize;
+
+/// Tendermint node ID: hex-encoded 20 bytes
+const NODE_ID_LEN: usize = 40;
+const ADDR_PREFIX: &str = "p2p/filter/addr/";
+const ID_PREFIX: &str = "p2p/filter/id/";
+
+/// The peer in a p2p filter query
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Peer {
+    /// the address of the connection ("IP:PORT")
+    Addr(String),
+    /// the peer's node ID (lowercase hex)
+    Id(String),
+}
+
+impl Peer {
+    /// parses the query path (with or without the leading slash)
+    pub fn from_query_path(path: &str) -> Option<Self> {
+        let path = path.trim_start_matches('/');
+        if let Some(addr) = path.strip_prefix(ADDR_PREFIX) {
+            Some(Peer::Addr(addr.to_owned()))
+        } else if let Some(id) = path.strip_prefix(ID_PREFIX) {
+            Some(Peer::Id(id.to_ascii_lowercase()))
+        } else {
+            None
+        }
+    }
+}
+
+fn is_node_id(entry: &str) -> bool {
+    entry.len() == NODE_ID_LEN && entry.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Static allow / deny lists and the staking-based filtering of peers
+/// (by default, all peers are accepted)
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct PeerFilter {
+    /// node IDs which are always accepted (e.g. the node's own sentries)
+    pub allow: BTreeSet<String>,
+    /// node IDs, IP addresses or "IP:PORT" addresses which are always rejected
+    pub deny: BTreeSet<String>,
+    /// only accept the active council and community nodes and the allowed node IDs.
+    /// A node is recognized by the p2p node ID recorded in its metadata
+    /// (in the node join or the metadata update transaction),
+    /// so the nodes without a recorded ID need to be in the allow list
+    pub staked_nodes_only: bool,
+}
+
+impl PeerFilter {
+    /// checks the format of the configured entries
+    pub fn validate(&self) -> Result<(), String> {
+        for id in self.allow.iter() {
+            if !is_node_id(id) {
+                return Err(format!("invalid node ID in the p2p allow list: {}", id));
+            }
+        }
+        for entry in self.deny.iter() {
+            if !is_node_id(entry)
+                && entry.parse::<IpAddr>().is_err()
+                && entry.parse::<SocketAddr>().is_err()
+            {
+                return Err(format!(
+                    "invalid node ID or address in the p2p deny list: {}",
+                    entry
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// if any peers may be rejected
+    pub fn is_enabled(&self) -> bool {
+        !self.deny.is_empty() || self.staked_nodes_only
+    }
+
+    fn is_denied(&self, entry: &str) -> bool {
+        self.deny
+            .iter()
+            .any(|denied| denied.eq_ignore_ascii_case(entry))
+    }
+
+    fn is_allowed(&self, id: &str) -> bool {
+        self.allow
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(id))
+    }
+
+    /// Checks the peer (`is_staked_node` tells if the node ID belongs to an active council
+    /// or community node). Returns the reason if rejected.
+    pub fn check(
+        &self,
+        peer: &Peer,
+        is_staked_node: impl FnOnce(&str) -> bool,
+    ) -> Result<(), String> {
+        match peer {
+            Peer::Addr(addr) => {
+                let ip = addr
+                    .parse::<SocketAddr>()
+                    .map(|addr| addr.ip().to_string())
+                    .unwrap_or_else(|_| addr.clone());
+                if self.is_denied(addr) || self.is_denied(&ip) {
+                    Err(format!("peer address {} is denied", addr))
+                } else {
+                    Ok(())
+                }
+            }
+            Peer::Id(id) => {
+                if self.is_denied(id) {
+                    Err(format!("peer {} is denied", id))
+                } else if self.is_allowed(id) || !self.staked_nodes_only || is_staked_node(id) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "peer {} is not an active council or community node",
+                        id
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAKED_NODE: &str = "0e7c045110b8dbf29765047380898919c5cb56f4";
+    const SENTRY: &str = "1e7c045110b8dbf29765047380898919c5cb56f4";
+    const OTHER: &str = "2e7c045110b8dbf29765047380898919c5cb56f4";
+
+    fn check(filter: &PeerFilter, path: &str) -> Result<(), String> {
+        let peer = Peer::from_query_path(path).expect("p2p filter path");
+        filter.check(&peer, |id| id == STAKED_NODE)
+    }
+
+    #[test]
+    fn check_query_path() {
+        assert_eq!(
+            Peer::from_query_path("/p2p/filter/addr/10.0.0.1:26656"),
+            Some(Peer::Addr("10.0.0.1:26656".to_owned()))
+        );
+        assert_eq!(
+            Peer::from_query_path("p2p/filter/id/0E7C045110B8DBF29765047380898919C5CB56F4"),
+            Some(Peer::Id(STAKED_NODE.to_owned()))
+        );
+        assert_eq!(Peer::from_query_path("/p2p/other"), None);
+    }
+
+    #[test]
+    fn check_default_accepts_all() {
+        let filter = PeerFilter::default();
+        assert!(!filter.is_enabled());
+        assert!(check(&filter, "/p2p/filter/addr/10.0.0.1:26656").is_ok());
+        assert!(check(&filter, &format!("/p2p/filter/id/{}", OTHER)).is_ok());
+    }
+
+    #[test]
+    fn check_lists_and_staked_nodes() {
+        let mut filter = PeerFilter {
+            allow: vec![SENTRY.to_uppercase()].into_iter().collect(),
+            deny: vec!["10.0.0.5".to_owned(), "10.0.0.6:26656".to_owned()]
+                .into_iter()
+                .collect(),
+            staked_nodes_only: true,
+        };
+        filter.validate().unwrap();
+        assert!(filter.is_enabled());
+        assert!(check(&filter, "/p2p/filter/addr/10.0.0.5:26656").is_err());
+        assert!(check(&filter, "/p2p/filter/addr/10.0.0.6:26656").is_err());
+        assert!(check(&filter, "/p2p/filter/addr/10.0.0.6:26657").is_ok());
+        assert!(check(&filter, &format!("/p2p/filter/id/{}", STAKED_NODE)).is_ok());
+        assert!(check(&filter, &format!("/p2p/filter/id/{}", SENTRY)).is_ok());
+        assert!(check(&filter, &format!("/p2p/filter/id/{}", OTHER)).is_err());
+
+        filter.deny.insert(STAKED_NODE.to_owned());
+        assert!(check(&filter, &format!("/p2p/filter/id/{}", STAKED_NODE)).is_err());
+    }
+
+    #[test]
+    fn check_invalid_entries() {
+        let filter = PeerFilter {
+            allow: vec!["10.0.0.5".to_owned()].into_iter().collect(),
+            ..Default::default()
+        };
+        assert!(filter.validate().is_err());
+        let filter = PeerFilter {
+            deny: vec!["not-an-address".to_owned()].into_iter().collect(),
+            ..Default::default()
+        };
+        assert!(filter.validate().is_err());
+    }
+}
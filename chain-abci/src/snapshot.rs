@@ -0,0 +1,204 @@
+//! State snapshots of the committed blocks, in the shape of Tendermint's state sync snapshots
+//! (Tendermint 0.33 can't offer them to the other nodes yet, see `app/snapshot.rs`)
+//!
+//! The snapshots are taken at every `interval`th height (in the background after commit) and stored
+//! in the local filesystem, one directory per height:
+//! `<dir>/<height>/manifest` (encoded `SnapshotManifest`) and `<dir>/<height>/<chunk index>`.
+//! The manifest is written last, so directories without it are incomplete and ignored.
+//! A snapshot directory can be restored into an empty node with `chain-abci --restore_snapshot <dir>`.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::state::tendermint::BlockHeight;
+use chain_storage::snapshot::{SnapshotError, SnapshotManifest};
+
+const MANIFEST_FILE: &str = "manifest";
+
+/// the manifest of a snapshot directory
+pub fn read_manifest(dir: &Path) -> io::Result<SnapshotManifest> {
+    let raw = fs::read(dir.join(MANIFEST_FILE))?;
+    SnapshotManifest::decode(&mut raw.as_slice())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// a chunk of a snapshot directory
+pub fn read_chunk(dir: &Path, index: u32) -> io::Result<Vec<u8>> {
+    fs::read(dir.join(index.to_string()))
+}
+
+/// Snapshot description (as in Tendermint's state sync ABCI calls)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Snapshot {
+    /// the height at which the snapshot was taken
+    pub height: u64,
+    /// the application-specific snapshot format
+    pub format: u32,
+    /// the number of chunks
+    pub chunks: u32,
+    /// the hash of the encoded manifest
+    pub hash: Vec<u8>,
+    /// the encoded manifest
+    pub metadata: Vec<u8>,
+}
+
+impl From<&SnapshotManifest> for Snapshot {
+    fn from(manifest: &SnapshotManifest) -> Self {
+        Snapshot {
+            height: manifest.height.value(),
+            format: manifest.format,
+            chunks: manifest.chunks(),
+            hash: manifest.hash().to_vec(),
+            metadata: manifest.encode(),
+        }
+    }
+}
+
+impl Snapshot {
+    /// decodes the manifest and checks it's consistent with the snapshot description
+    pub fn manifest(&self) -> Option<SnapshotManifest> {
+        let mut input = self.metadata.as_slice();
+        let manifest = SnapshotManifest::decode(&mut input).ok()?;
+        if input.is_empty()
+            && manifest.hash()[..] == self.hash[..]
+            && manifest.height.value() == self.height
+            && manifest.format == self.format
+            && manifest.chunks() == self.chunks
+        {
+            Some(manifest)
+        } else {
+            None
+        }
+    }
+}
+
+/// The result of OfferSnapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OfferSnapshotResult {
+    /// the snapshot is accepted, the chunks can be applied
+    Accept,
+    /// the state sync should be aborted (e.g. the node already has some state)
+    Abort,
+    /// the snapshot is rejected (invalid or not matching the trusted app hash)
+    Reject,
+    /// all the snapshots of this format are rejected
+    RejectFormat,
+}
+
+/// The result of ApplySnapshotChunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplySnapshotChunkResult {
+    /// the chunk was applied
+    Accept,
+    /// no snapshot is being restored
+    Abort,
+    /// the chunk (in `refetch_chunks`) should be fetched again and reapplied
+    Retry,
+    /// the snapshot is rejected and another one should be offered
+    RejectSnapshot,
+}
+
+/// The snapshot being restored
+pub struct SnapshotRestore {
+    /// the accepted manifest
+    pub manifest: SnapshotManifest,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl SnapshotRestore {
+    pub fn new(manifest: SnapshotManifest) -> Self {
+        let chunks = vec![None; manifest.chunk_hashes.len()];
+        SnapshotRestore { manifest, chunks }
+    }
+
+    /// checks the chunk and keeps it (if valid)
+    pub fn apply_chunk(&mut self, index: u32, chunk: Vec<u8>) -> Result<(), SnapshotError> {
+        self.manifest.verify_chunk(index, &chunk)?;
+        self.chunks[index as usize] = Some(chunk);
+        Ok(())
+    }
+
+    /// all the chunks (if all were received)
+    pub fn complete_chunks(&self) -> Option<Vec<Vec<u8>>> {
+        self.chunks.iter().cloned().collect()
+    }
+}
+
+/// Snapshots taken by this node (stored in a local directory)
+#[derive(Clone)]
+pub struct SnapshotStore {
+    dir: PathBuf,
+    /// snapshots are taken at the heights divisible by the interval
+    pub interval: u64,
+    /// the number of the most recent snapshots to keep
+    pub keep_recent: usize,
+}
+
+impl SnapshotStore {
+    pub fn new(dir: impl Into<PathBuf>, interval: u64, keep_recent: usize) -> Self {
+        SnapshotStore {
+            dir: dir.into(),
+            interval,
+            keep_recent,
+        }
+    }
+
+    /// if a snapshot should be taken after committing the block at this height
+    pub fn is_scheduled(&self, height: BlockHeight) -> bool {
+        self.interval > 0 && height.value() > 0 && height.value() % self.interval == 0
+    }
+
+    fn snapshot_dir(&self, height: BlockHeight) -> PathBuf {
+        self.dir.join(height.value().to_string())
+    }
+
+    /// stores a new snapshot (and removes the old ones beyond `keep_recent`)
+    pub fn save(&self, manifest: &SnapshotManifest, chunks: &[Vec<u8>]) -> io::Result<()> {
+        let dir = self.snapshot_dir(manifest.height);
+        fs::create_dir_all(&dir)?;
+        for (index, chunk) in chunks.iter().enumerate() {
+            fs::write(dir.join(index.to_string()), chunk)?;
+        }
+        fs::write(dir.join(MANIFEST_FILE), manifest.encode())?;
+        self.prune()
+    }
+
+    /// the manifests of the complete snapshots (sorted by height)
+    pub fn list(&self) -> io::Result<Vec<SnapshotManifest>> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut manifests = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let dir = entry?.path();
+            if !dir.join(MANIFEST_FILE).exists() {
+                continue;
+            }
+            match read_manifest(&dir) {
+                Ok(manifest) => manifests.push(manifest),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    log::warn!("invalid snapshot manifest in {:?}: {}", dir, e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        manifests.sort_by_key(|manifest| manifest.height);
+        Ok(manifests)
+    }
+
+    /// a chunk of a stored snapshot
+    pub fn load_chunk(&self, height: BlockHeight, index: u32) -> io::Result<Vec<u8>> {
+        read_chunk(&self.snapshot_dir(height), index)
+    }
+
+    fn prune(&self) -> io::Result<()> {
+        let manifests = self.list()?;
+        let to_remove = manifests.len().saturating_sub(self.keep_recent);
+        for manifest in manifests.iter().take(to_remove) {
+            fs::remove_dir_all(self.snapshot_dir(manifest.height))?;
+        }
+        Ok(())
+    }
+}
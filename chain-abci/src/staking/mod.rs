@@ -1,6 +1,7 @@
 mod table;
 mod tx;
 
+pub(crate) use table::set_staking;
 pub use table::{RewardsDistribution, StakingTable};
 
 #[cfg(test)]
@@ -16,21 +17,26 @@ mod tests {
     use chain_core::init::config::SlashRatio;
     use chain_core::init::params::NetworkParameters;
     use chain_core::state::account::{
-        NodeState, PunishmentKind, StakedState, StakedStateAddress, UnbondTx, UnjailTx, Validator,
+        DelegateTx, NodeState, PunishmentKind, StakedState, StakedStateAddress, UnbondTx,
+        UndelegateTx, UnjailTx, Validator,
     };
-    use chain_core::state::tendermint::{BlockHeight, TendermintValidatorPubKey};
-    use chain_core::state::validator::NodeJoinRequestTx;
-    use chain_core::tx::fee::Fee;
+    use chain_core::state::tendermint::{
+        BlockHeight, TendermintValidatorAddress, TendermintValidatorPubKey,
+    };
+    use chain_core::state::validator::{NodeJoinRequestTx, NodeLeaveTx, NodeMetadataUpdateTx};
+    use chain_core::tx::fee::{Fee, Milli};
     use chain_storage::buffer::{Get, GetStaking, MemStore, StoreStaking};
     use test_common::chain_env::{
-        get_init_network_params, mock_council_node, mock_council_node_meta, DEFAULT_GENESIS_TIME,
+        get_init_network_params, mock_community_node, mock_council_node, mock_council_node_meta,
+        DEFAULT_GENESIS_TIME,
     };
 
     use super::*;
     use crate::app::BeginBlockInfo;
     use crate::staking::table::{PunishmentOutcome, SlashedCoin};
     use crate::tx_error::{
-        DepositError, NodeJoinError, PublicTxError, UnbondError, UnjailError, WithdrawError,
+        DelegateError, DepositError, NodeJoinError, NodeLeaveError, NodeUpdateError, PublicTxError,
+        UnbondError, UnjailError, WithdrawError,
     };
 
     macro_rules! matches {
@@ -91,6 +97,7 @@ mod tests {
             StakingTable::from_genesis(
                 &store,
                 minimal,
+                minimal,
                 3,
                 &genesis_accounts
                     .iter()
@@ -119,6 +126,7 @@ mod tests {
             address: addr4,
             attributes: Default::default(),
             node_meta: mock_council_node(val_pk4.clone()),
+            commission_rate: None,
         };
         table
             .node_join(&mut store, DEFAULT_GENESIS_TIME + 10, 0, 0, &node_join)
@@ -219,6 +227,7 @@ mod tests {
             slashed_coin: SlashedCoin {
                 bonded: bonded_slashed,
                 unbonded: unbonded_slashed,
+                delegated: vec![],
             },
             punishment_kind: PunishmentKind::ByzantineFault,
             jailed_until: Some(block_time.saturating_add(info.get_unbonding_period())),
@@ -272,6 +281,7 @@ mod tests {
             address: addr1,
             attributes: Default::default(),
             node_meta: mock_council_node(val_pk_new),
+            commission_rate: None,
         };
         assert!(matches!(
             table.node_join(&mut store, DEFAULT_GENESIS_TIME + 3, 0, 0, &node_join),
@@ -338,6 +348,7 @@ mod tests {
             address: addr,
             attributes: Default::default(),
             node_meta: mock_council_node(val_pk_new.clone()),
+            commission_rate: None,
         };
         // change to new validator key
         let result = table.node_join(store, DEFAULT_GENESIS_TIME + 1, 1, 0, &node_join);
@@ -351,6 +362,216 @@ mod tests {
         result.map(|_| ())
     }
 
+    #[test]
+    fn check_community_node() {
+        let (mut table, mut store) = init_staking_table();
+        let addr = staking_address(&[0xcf; 32]);
+        let val_pk = validator_pubkey(&[0xcf; 32]);
+        let minimal = Coin::new(10_0000_0000).unwrap();
+
+        // not enough bonded
+        table
+            .deposit(&mut store, &addr, Coin::new(1_0000_0000).unwrap())
+            .unwrap();
+        let node_join = NodeJoinRequestTx {
+            nonce: 0,
+            address: addr,
+            attributes: Default::default(),
+            node_meta: mock_community_node(),
+            commission_rate: None,
+        };
+        assert!(matches!(
+            table.node_join(&mut store, DEFAULT_GENESIS_TIME, 0, 0, &node_join),
+            Err(PublicTxError::NodeJoin(NodeJoinError::BondedNotEnough))
+        ));
+
+        table.deposit(&mut store, &addr, minimal).unwrap();
+        table
+            .node_join(&mut store, DEFAULT_GENESIS_TIME, 0, 0, &node_join)
+            .unwrap();
+        let staking = store.get(&addr).unwrap();
+        assert!(staking.has_community_node_meta());
+        assert_eq!(staking.nonce, 1);
+        assert_eq!(table.list_community_nodes(&store).len(), 1);
+        assert_eq!(table.list_community_nodes(&store)[0].staking_address, addr);
+        // community nodes don't become validators
+        assert_eq!(table.end_block(&store, 4), vec![]);
+        assert_eq!(table.list_council_nodes(&store).len(), 3);
+
+        // can't join again, nor as a council node
+        let node_join = NodeJoinRequestTx {
+            nonce: 1,
+            ..node_join
+        };
+        assert!(matches!(
+            table.node_join(&mut store, DEFAULT_GENESIS_TIME, 0, 0, &node_join),
+            Err(PublicTxError::NodeJoin(NodeJoinError::AlreadyJoined))
+        ));
+        let council_join = NodeJoinRequestTx {
+            node_meta: mock_council_node(val_pk),
+            ..node_join.clone()
+        };
+        assert!(matches!(
+            table.node_join(&mut store, DEFAULT_GENESIS_TIME, 0, 0, &council_join),
+            Err(PublicTxError::NodeJoin(NodeJoinError::NodeTypeMismatch))
+        ));
+
+        // not jailed
+        let unjail = UnjailTx {
+            nonce: 1,
+            address: addr,
+            attributes: Default::default(),
+        };
+        assert!(matches!(
+            table.unjail(&mut store, DEFAULT_GENESIS_TIME, &unjail),
+            Err(PublicTxError::Unjail(UnjailError::NotJailed))
+        ));
+
+        // leave: unbond below the minimal stake inactivates the node
+        let unbond = UnbondTx {
+            from_staked_account: addr,
+            nonce: 1,
+            value: Coin::new(2_0000_0000).unwrap(),
+            attributes: Default::default(),
+        };
+        table
+            .unbond(
+                &mut store,
+                10,
+                DEFAULT_GENESIS_TIME + 20,
+                3.into(),
+                &unbond,
+                Fee::zero(),
+            )
+            .unwrap();
+        assert!(table.list_community_nodes(&store).is_empty());
+
+        // the record is removed after unbonding period
+        let mut init_params = get_init_network_params(Coin::zero());
+        init_params.required_community_node_stake = minimal;
+        let params = NetworkParameters::Genesis(init_params);
+        table.begin_block(
+            &mut store,
+            &BeginBlockInfo {
+                params: &params,
+                max_evidence_age: 10,
+                block_time: DEFAULT_GENESIS_TIME + 31,
+                block_height: 4.into(),
+                voters: &[],
+                evidences: &[],
+            },
+        );
+        assert!(store.get(&addr).unwrap().node_meta.is_none());
+        assert!(table.community_nodes.is_empty());
+    }
+
+    #[test]
+    fn check_community_node_eviction() {
+        let (mut table, mut store) = init_staking_table();
+        let addr = staking_address(&[0xcf; 32]);
+        let minimal = Coin::new(10_0000_0000).unwrap();
+
+        table.deposit(&mut store, &addr, minimal).unwrap();
+        let node_join = NodeJoinRequestTx {
+            nonce: 0,
+            address: addr,
+            attributes: Default::default(),
+            node_meta: mock_community_node(),
+            commission_rate: None,
+        };
+        table
+            .node_join(&mut store, DEFAULT_GENESIS_TIME, 0, 0, &node_join)
+            .unwrap();
+        let keypackage = match store.get(&addr).unwrap().node_meta {
+            Some(NodeState::CommunityNode(node)) => node.node_info.confidential_init.keypackage,
+            _ => unreachable!(),
+        };
+        assert!(table.is_community_keypackage(&store, &keypackage));
+
+        // the member updated its keypackage in the TDBE group
+        let updated = vec![0xab; 10];
+        table.update_community_keypackage(&mut store, &keypackage, updated.clone());
+        assert!(!table.is_community_keypackage(&store, &keypackage));
+        assert_eq!(
+            table.list_community_keypackages(&store),
+            vec![updated.clone()]
+        );
+
+        // evicted from the group
+        assert_eq!(
+            table.jail_community_node(&mut store, &updated, DEFAULT_GENESIS_TIME + 1, 1.into(), 10),
+            Some((addr, DEFAULT_GENESIS_TIME + 11))
+        );
+        let staking = store.get(&addr).unwrap();
+        assert!(staking.is_jailed());
+        assert!(table.list_community_nodes(&store).is_empty());
+        assert!(table.list_community_keypackages(&store).is_empty());
+        // jailed only once
+        assert_eq!(
+            table.jail_community_node(&mut store, &updated, DEFAULT_GENESIS_TIME + 2, 2.into(), 10),
+            None
+        );
+
+        // transaction denied after jailed
+        assert!(matches!(
+            table.deposit(&mut store, &addr, minimal),
+            Err(DepositError::IsJailed)
+        ));
+        let node_join = NodeJoinRequestTx {
+            nonce: 1,
+            ..node_join
+        };
+        assert!(matches!(
+            table.node_join(&mut store, DEFAULT_GENESIS_TIME + 2, 0, 0, &node_join),
+            Err(PublicTxError::NodeJoin(NodeJoinError::IsJailed))
+        ));
+
+        // the jailed record isn't removed after unbonding period
+        let mut init_params = get_init_network_params(Coin::zero());
+        init_params.required_community_node_stake = minimal;
+        let params = NetworkParameters::Genesis(init_params);
+        table.begin_block(
+            &mut store,
+            &BeginBlockInfo {
+                params: &params,
+                max_evidence_age: 10,
+                block_time: DEFAULT_GENESIS_TIME + 31,
+                block_height: 4.into(),
+                voters: &[],
+                evidences: &[],
+            },
+        );
+        assert!(store.get(&addr).unwrap().is_jailed());
+        assert!(table.community_nodes.contains(&addr));
+
+        // unjail
+        let unjail = UnjailTx {
+            nonce: 1,
+            address: addr,
+            attributes: Default::default(),
+        };
+        assert!(matches!(
+            table.unjail(&mut store, DEFAULT_GENESIS_TIME + 2, &unjail),
+            Err(PublicTxError::Unjail(UnjailError::JailTimeNotExpired))
+        ));
+        table
+            .unjail(&mut store, DEFAULT_GENESIS_TIME + 31, &unjail)
+            .unwrap();
+        let staking = store.get(&addr).unwrap();
+        assert_eq!(staking.nonce, 2);
+        assert!(!staking.is_jailed());
+
+        // re-join
+        let node_join = NodeJoinRequestTx {
+            nonce: 2,
+            ..node_join
+        };
+        table
+            .node_join(&mut store, DEFAULT_GENESIS_TIME + 31, 0, 0, &node_join)
+            .unwrap();
+        assert_eq!(table.list_community_nodes(&store).len(), 1);
+    }
+
     #[test]
     fn check_used_validator_key() {
         let (mut table, mut store) = init_staking_table();
@@ -393,6 +614,7 @@ mod tests {
             address: addr_new,
             attributes: Default::default(),
             node_meta: mock_council_node(val_pk1),
+            commission_rate: None,
         };
         // can't join with used key
         assert!(matches!(
@@ -470,6 +692,7 @@ mod tests {
             address: addr1,
             attributes: Default::default(),
             node_meta: mock_council_node(val_pk1.clone()),
+            commission_rate: None,
         };
 
         let mut init_params = get_init_network_params(Coin::zero());
@@ -753,6 +976,7 @@ mod tests {
                 slashed_coin: SlashedCoin {
                     bonded: bonded_slashed,
                     unbonded: unbonded_slashed,
+                    delegated: vec![],
                 },
                 punishment_kind: PunishmentKind::ByzantineFault,
                 jailed_until: Some(expected_jailed_until),
@@ -791,15 +1015,30 @@ mod tests {
         table
             .deposit(&mut store, &addr2, Coin::new(12_0000_0000).unwrap())
             .unwrap();
-        let tx = NodeJoinRequestTx {
+        let mut tx = NodeJoinRequestTx {
             nonce: 1,
             address: addr2,
             attributes: Default::default(),
             node_meta: mock_council_node(val_pk_new.clone()),
+            commission_rate: Some(Milli::try_new(1, 1).unwrap()),
         };
+        // the commission rate can't be over 1.0
+        assert!(matches!(
+            table.node_join(&mut store, DEFAULT_GENESIS_TIME + 2, 0, 0, &tx),
+            Err(PublicTxError::NodeJoin(
+                NodeJoinError::InvalidCommissionRate
+            ))
+        ));
+        tx.commission_rate = Some(Milli::try_new(0, 50).unwrap());
         table
             .node_join(&mut store, DEFAULT_GENESIS_TIME + 2, 0, 0, &tx)
             .unwrap();
+        match store.get(&addr2).unwrap().node_meta {
+            Some(NodeState::CouncilNode(val)) => {
+                assert_eq!(val.commission_rate, Milli::try_new(0, 50).unwrap())
+            }
+            _ => panic!("council node record expected"),
+        }
         assert_eq!(
             table.end_block(&mut store, 3),
             vec![(val_pk_new.clone(), Coin::new(12_0000_0000).unwrap().into())]
@@ -829,6 +1068,7 @@ mod tests {
                 slashed_coin: SlashedCoin {
                     bonded: bonded_slashed,
                     unbonded: unbonded_slashed,
+                    delegated: vec![],
                 },
                 punishment_kind: PunishmentKind::ByzantineFault,
                 jailed_until: Some(expected_jailed_until),
@@ -841,4 +1081,334 @@ mod tests {
         );
         assert!(staking.is_jailed());
     }
+
+    /// Test council node metadata update:
+    /// - the old consensus key is replaced by the new one in the validator updates
+    /// - the old validator address is kept in the used list
+    #[test]
+    fn check_node_metadata_update() {
+        let (mut table, mut store) = init_staking_table();
+        let addr1 = staking_address(&[0xcc; 32]);
+        let val_pk1 = validator_pubkey(&[0xcc; 32]);
+        let addr2 = staking_address(&[0xcd; 32]);
+        let val_pk_new = validator_pubkey(&[0xdd; 32]);
+
+        let update_tx = |nonce, address, consensus_pubkey| NodeMetadataUpdateTx {
+            nonce,
+            address,
+            attributes: Default::default(),
+            name: "new name".to_owned(),
+            security_contact: Some("security@crypto.com".to_owned()),
+            consensus_pubkey,
+            commission_rate: Milli::try_new(0, 100).unwrap(),
+            p2p_node_id: None,
+        };
+        assert!(matches!(
+            table.update_node_metadata(
+                &mut store,
+                DEFAULT_GENESIS_TIME,
+                10,
+                &update_tx(0, staking_address(&[0xd0; 32]), val_pk_new.clone()),
+            ),
+            Err(PublicTxError::NodeUpdate(NodeUpdateError::NotCouncilNode))
+        ));
+        let council_node = table
+            .update_node_metadata(
+                &mut store,
+                DEFAULT_GENESIS_TIME,
+                10,
+                &update_tx(0, addr1, val_pk_new.clone()),
+            )
+            .unwrap();
+        assert_eq!(council_node.node_info.name, "new name");
+        assert_eq!(council_node.consensus_pubkey, val_pk_new);
+
+        // the commission rate can't be over 1.0
+        let mut invalid_tx = update_tx(1, addr1, val_pk_new.clone());
+        invalid_tx.commission_rate = Milli::try_new(1, 1).unwrap();
+        assert!(matches!(
+            table.update_node_metadata(&mut store, DEFAULT_GENESIS_TIME, 10, &invalid_tx),
+            Err(PublicTxError::NodeUpdate(
+                NodeUpdateError::InvalidCommissionRate
+            ))
+        ));
+
+        // neither the new key nor the old one can be used by others
+        for pk in [val_pk_new.clone(), val_pk1.clone()].iter() {
+            assert!(matches!(
+                table.update_node_metadata(
+                    &mut store,
+                    DEFAULT_GENESIS_TIME,
+                    10,
+                    &update_tx(0, addr2, pk.clone()),
+                ),
+                Err(PublicTxError::NodeUpdate(
+                    NodeUpdateError::DuplicateValidatorAddress
+                ))
+            ));
+        }
+
+        let staking = store.get(&addr1).unwrap();
+        assert_eq!(staking.nonce, 1);
+        if let Some(NodeState::CouncilNode(val)) = staking.node_meta.as_ref() {
+            assert_eq!(val.commission_rate, Milli::try_new(0, 100).unwrap());
+            assert_eq!(val.used_validator_addresses.len(), 1);
+            assert_eq!(
+                val.used_validator_addresses[0].0,
+                TendermintValidatorAddress::from(&val_pk1)
+            );
+        } else {
+            panic!("council node record expected");
+        }
+
+        assert_eq!(
+            table.end_block(&store, 3),
+            vec![
+                (val_pk1, Coin::zero().into()),
+                (val_pk_new, Coin::new(11_0000_0000).unwrap().into())
+            ]
+        );
+        assert_eq!(table.end_block(&store, 3), vec![]);
+    }
+
+    /// Test node leave:
+    /// - the council node is removed from the validator set in the next end block without slashing
+    /// - the node record is cleaned up after the unbonding period, the bonded amount is kept
+    #[test]
+    fn check_node_leave() {
+        let params = NetworkParameters::Genesis(get_init_network_params(Coin::zero()));
+        let info = BeginBlockInfo {
+            params: &params,
+            max_evidence_age: 10,
+            block_time: DEFAULT_GENESIS_TIME,
+            block_height: 1.into(),
+            voters: &[],
+            evidences: &[],
+        };
+        let (mut table, mut store) = init_staking_table();
+        let addr1 = staking_address(&[0xcc; 32]);
+        let val_pk1 = validator_pubkey(&[0xcc; 32]);
+
+        let leave_tx = |nonce, address| NodeLeaveTx {
+            nonce,
+            address,
+            attributes: Default::default(),
+        };
+        assert!(matches!(
+            table.node_leave(
+                &mut store,
+                DEFAULT_GENESIS_TIME,
+                1.into(),
+                &leave_tx(0, staking_address(&[0xd0; 32])),
+            ),
+            Err(PublicTxError::NodeLeave(NodeLeaveError::NotNode))
+        ));
+        table
+            .node_leave(
+                &mut store,
+                DEFAULT_GENESIS_TIME,
+                1.into(),
+                &leave_tx(0, addr1),
+            )
+            .unwrap();
+        assert!(matches!(
+            table.node_leave(
+                &mut store,
+                DEFAULT_GENESIS_TIME,
+                1.into(),
+                &leave_tx(1, addr1)
+            ),
+            Err(PublicTxError::NodeLeave(NodeLeaveError::AlreadyInactive))
+        ));
+
+        assert_eq!(
+            table.end_block(&store, 3),
+            vec![(val_pk1, Coin::zero().into())]
+        );
+        let bonded = Coin::new(11_0000_0000).unwrap();
+        let staking = store.get(&addr1).unwrap();
+        assert_eq!(staking.bonded, bonded);
+        assert!(staking.last_slash.is_none());
+
+        // still in unbonding period
+        table.begin_block(&mut store, &info);
+        assert!(store.get(&addr1).unwrap().node_meta.is_some());
+
+        table.begin_block(
+            &mut store,
+            &BeginBlockInfo {
+                block_time: DEFAULT_GENESIS_TIME + info.get_unbonding_period() + 1,
+                block_height: 2.into(),
+                ..info
+            },
+        );
+        let staking = store.get(&addr1).unwrap();
+        assert!(staking.node_meta.is_none());
+        assert_eq!(staking.bonded, bonded);
+    }
+
+    /// Test delegation:
+    /// - delegated amount counts toward the voting power of the council node
+    /// - only one council node can be delegated to at a time
+    /// - undelegate moves the whole delegated amount into unbonded
+    #[test]
+    fn check_delegation() {
+        let (mut table, mut store) = init_staking_table();
+        let addr1 = staking_address(&[0xcc; 32]);
+        let val_pk1 = validator_pubkey(&[0xcc; 32]);
+        let addr2 = staking_address(&[0xcd; 32]);
+        let delegator = staking_address(&[0xd0; 32]);
+        table
+            .deposit(&mut store, &delegator, Coin::new(5_0000_0000).unwrap())
+            .unwrap();
+
+        let delegate_tx = |nonce, to_council_node, value| DelegateTx {
+            from_staked_account: delegator,
+            to_council_node,
+            nonce,
+            value,
+            attributes: Default::default(),
+        };
+        let amount = Coin::new(2_0000_0000).unwrap();
+        assert!(matches!(
+            table.delegate(&mut store, &delegate_tx(0, addr1, Coin::zero())),
+            Err(PublicTxError::Delegate(DelegateError::ZeroValue))
+        ));
+        assert!(matches!(
+            table.delegate(&mut store, &delegate_tx(0, delegator, amount)),
+            Err(PublicTxError::Delegate(DelegateError::NotActiveCouncilNode))
+        ));
+        table
+            .delegate(&mut store, &delegate_tx(0, addr1, amount))
+            .unwrap();
+        assert!(matches!(
+            table.delegate(&mut store, &delegate_tx(1, addr2, amount)),
+            Err(PublicTxError::Delegate(DelegateError::AlreadyDelegated))
+        ));
+        // council nodes can't delegate
+        let tx = DelegateTx {
+            from_staked_account: addr2,
+            ..delegate_tx(0, addr1, amount)
+        };
+        assert!(matches!(
+            table.delegate(&mut store, &tx),
+            Err(PublicTxError::Delegate(DelegateError::IsNode))
+        ));
+
+        let staking = store.get(&delegator).unwrap();
+        assert_eq!(staking.bonded, Coin::new(3_0000_0000).unwrap());
+        assert_eq!(staking.delegated_to, Some(addr1));
+        assert_eq!(staking.nonce, 1);
+        assert_eq!(store.get(&addr1).unwrap().delegated(), amount);
+        assert_eq!(
+            table.end_block(&store, 3),
+            vec![(val_pk1.clone(), Coin::new(13_0000_0000).unwrap().into())]
+        );
+
+        let undelegate_tx = |nonce| UndelegateTx {
+            from_staked_account: delegator,
+            nonce,
+            attributes: Default::default(),
+        };
+        let (council_node, undelegated, unbonded_from) = table
+            .undelegate(&mut store, 10, DEFAULT_GENESIS_TIME, &undelegate_tx(1))
+            .unwrap();
+        assert_eq!(council_node, addr1);
+        assert_eq!(undelegated, amount);
+        assert_eq!(unbonded_from, DEFAULT_GENESIS_TIME + 10);
+        let staking = store.get(&delegator).unwrap();
+        assert_eq!(staking.unbonded, amount);
+        assert_eq!(staking.delegated_to, None);
+        assert_eq!(store.get(&addr1).unwrap().delegated(), Coin::zero());
+        assert_eq!(
+            table.end_block(&store, 3),
+            vec![(val_pk1, Coin::new(11_0000_0000).unwrap().into())]
+        );
+        assert!(matches!(
+            table.undelegate(&mut store, 10, DEFAULT_GENESIS_TIME, &undelegate_tx(2)),
+            Err(PublicTxError::Delegate(DelegateError::NotDelegated))
+        ));
+    }
+
+    /// Test the delegators' rewards (minus the commission) and slashing
+    #[test]
+    fn check_delegation_rewards_and_slashing() {
+        let (mut table, mut store) = init_staking_table();
+        let addr1 = staking_address(&[0xcc; 32]);
+        let val_pk1 = validator_pubkey(&[0xcc; 32]);
+        let delegator = staking_address(&[0xd0; 32]);
+
+        // 10% commission rate
+        let mut staking = store.get(&addr1).unwrap();
+        if let Some(NodeState::CouncilNode(val)) = &mut staking.node_meta {
+            val.commission_rate = Milli::try_new(0, 100).unwrap();
+        }
+        store.set_staking(staking);
+
+        let amount = Coin::new(11_0000_0000).unwrap();
+        table.deposit(&mut store, &delegator, amount).unwrap();
+        let tx = DelegateTx {
+            from_staked_account: delegator,
+            to_council_node: addr1,
+            nonce: 0,
+            value: amount,
+            attributes: Default::default(),
+        };
+        table.delegate(&mut store, &tx).unwrap();
+
+        // the rewards are split by the bonded and delegated amounts (11:11)
+        assert!(table.reward_record(
+            &store,
+            &TendermintValidatorAddress::from(&val_pk1),
+            Coin::new(22_0000_0000).unwrap().into(),
+        ));
+        let (remainder, distributed) =
+            table.reward_distribute(&mut store, Coin::new(1000).unwrap());
+        assert_eq!(remainder, Coin::zero());
+        assert_eq!(
+            distributed,
+            vec![
+                (delegator, Coin::new(450).unwrap()),
+                (addr1, Coin::new(550).unwrap())
+            ]
+        );
+        assert_eq!(
+            store.get(&delegator).unwrap().bonded,
+            Coin::new(450).unwrap()
+        );
+
+        let mut init_params = get_init_network_params(Coin::zero());
+        init_params.slashing_config.byzantine_slash_percent = "0.1".parse().unwrap();
+        let params = NetworkParameters::Genesis(init_params);
+        let punishment_outcomes = table.begin_block(
+            &mut store,
+            &BeginBlockInfo {
+                params: &params,
+                max_evidence_age: 10,
+                block_time: DEFAULT_GENESIS_TIME + 1,
+                block_height: 1.into(),
+                voters: &[],
+                evidences: &[(val_pk1.into(), 1.into(), DEFAULT_GENESIS_TIME)],
+            },
+        );
+        assert_eq!(punishment_outcomes.len(), 1);
+        let slashed_delegated = Coin::new(1_1000_0000).unwrap();
+        assert_eq!(
+            punishment_outcomes[0].slashed_coin.delegated,
+            vec![(delegator, slashed_delegated)]
+        );
+        let remaining = (amount - slashed_delegated).unwrap();
+        assert_eq!(store.get(&addr1).unwrap().delegated(), remaining);
+
+        // the delegation is still recorded after the council node is jailed
+        let tx = UndelegateTx {
+            from_staked_account: delegator,
+            nonce: 1,
+            attributes: Default::default(),
+        };
+        let (_, undelegated, _) = table
+            .undelegate(&mut store, 10, DEFAULT_GENESIS_TIME + 1, &tx)
+            .unwrap();
+        assert_eq!(undelegated, remaining);
+    }
 }
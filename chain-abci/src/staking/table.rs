@@ -13,10 +13,11 @@ use chain_core::init::coin::{sum_coins, Coin, CoinError, CoinResult};
 use chain_core::init::config::SlashRatio;
 use chain_core::state::account::{
     NodeName, NodeSecurityContact, NodeState, PunishmentKind, SlashRecord, StakedState,
-    StakedStateAddress,
+    StakedStateAddress, MAX_COMMISSION_RATE_MILLIS,
 };
 use chain_core::state::tendermint::{
-    BlockHeight, TendermintValidatorAddress, TendermintValidatorPubKey, TendermintVotePower,
+    BlockHeight, TendermintNodeId, TendermintValidatorAddress, TendermintValidatorPubKey,
+    TendermintVotePower,
 };
 use chain_storage::buffer::{GetStaking, StoreStaking};
 
@@ -40,16 +41,29 @@ pub struct CouncilNodeMetadata {
     pub tendermint_pubkey: TendermintValidatorPubKey,
 }
 
-/// order by bonded desc, staking_address
+#[derive(Debug, Clone, Serialize)]
+/// Metadata of a community node
+pub struct CommunityNodeMetadata {
+    /// Name of the node
+    pub name: NodeName,
+    /// Bonded amount of the staking account
+    pub bonded: Coin,
+    /// Address of staking account of the node
+    pub staking_address: StakedStateAddress,
+    /// Optional security email address of the node
+    pub security_contact: NodeSecurityContact,
+}
+
+/// order by voting stake (bonded + delegated) desc, staking_address
 #[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
 pub struct ValidatorSortKey {
-    pub bonded: Coin,
+    pub voting_stake: Coin,
     pub address: StakedStateAddress,
 }
 
 impl Ord for ValidatorSortKey {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self.bonded.cmp(&other.bonded) {
+        match self.voting_stake.cmp(&other.voting_stake) {
             Ordering::Equal => self.address.cmp(&other.address),
             ordering => ordering.reverse(),
         }
@@ -61,20 +75,23 @@ impl PartialOrd for ValidatorSortKey {
     }
 }
 impl ValidatorSortKey {
-    pub fn new(bonded: Coin, address: StakedStateAddress) -> Self {
-        Self { bonded, address }
+    pub fn new(voting_stake: Coin, address: StakedStateAddress) -> Self {
+        Self {
+            voting_stake,
+            address,
+        }
     }
 }
 
 impl Into<ValidatorSortKey> for &StakedState {
     fn into(self) -> ValidatorSortKey {
-        ValidatorSortKey::new(self.bonded, self.address)
+        ValidatorSortKey::new(self.voting_stake(), self.address)
     }
 }
 
 impl Into<ValidatorSortKey> for &mut StakedState {
     fn into(self) -> ValidatorSortKey {
-        ValidatorSortKey::new(self.bonded, self.address)
+        ValidatorSortKey::new(self.voting_stake(), self.address)
     }
 }
 
@@ -90,11 +107,14 @@ pub struct PunishmentOutcome {
 pub struct SlashedCoin {
     pub bonded: Coin,
     pub unbonded: Coin,
+    /// slashed from the amount delegated by each delegator
+    pub delegated: Vec<(StakedStateAddress, Coin)>,
 }
 
 impl SlashedCoin {
     pub fn sum(&self) -> CoinResult {
-        self.bonded + self.unbonded
+        (self.bonded + self.unbonded)?
+            + sum_coins(self.delegated.iter().map(|(_, amount)| *amount))?
     }
 }
 
@@ -120,21 +140,33 @@ impl SlashedCoin {
 /// Invariant 2.4:
 ///   idx_* only contains CouncilNode not CommunityNode
 ///   Proof: checked during insertion
+///
+/// Invariant 2.5:
+///   `community_nodes` contains exactly the addresses which have community node record on heap.
+///   Proof: always update it when community node record created or removed.
 #[derive(Clone, Debug, Default, Encode, Decode)]
 pub struct StakingTable {
     // Selected validator voting powers of last executed end block
     chosen_validators: BTreeMap<StakedStateAddress, TendermintVotePower>,
     liveness: BTreeMap<StakedStateAddress, LivenessTracker>,
     participator_stats: BTreeMap<StakedStateAddress, u64>,
+    // Community nodes don't participate in consensus, so they are tracked apart from the validators.
+    pub(crate) community_nodes: BTreeSet<StakedStateAddress>,
 
     // Call `initialize` to populate the indexes after deserialized.
     // Keep the recent value of minimal_required_staking to do sanity check on validator states.
     #[codec(skip)]
     pub(crate) minimal_required_staking: Coin,
     #[codec(skip)]
+    pub(crate) minimal_required_community_staking: Coin,
+    #[codec(skip)]
     pub(crate) idx_validator_address: BTreeMap<TendermintValidatorAddress, StakedStateAddress>,
     #[codec(skip)]
     idx_sort: BTreeSet<ValidatorSortKey>,
+    // The consensus keys (before the first rotation) of validators which rotated their keys
+    // in the current block, consumed in end block.
+    #[codec(skip)]
+    pub(crate) rotated_validator_keys: BTreeMap<StakedStateAddress, TendermintValidatorPubKey>,
 }
 
 /// Returned if the caller did not do the necessary validations
@@ -157,11 +189,13 @@ impl StakingTable {
     pub fn from_genesis(
         heap: &impl GetStaking,
         minimal_required_staking: Coin,
+        minimal_required_community_staking: Coin,
         max_validators: usize,
         addresses: &[StakedStateAddress],
     ) -> Self {
         let mut tbl = Self::default();
         tbl.minimal_required_staking = minimal_required_staking;
+        tbl.minimal_required_community_staking = minimal_required_community_staking;
         for addr in addresses.iter() {
             tbl.insert_validator(&heap.get(addr).unwrap())
                 .expect("only validator");
@@ -173,10 +207,16 @@ impl StakingTable {
     }
 
     /// After restored from storage, call initialize to populate the indexes
-    pub fn initialize(&mut self, heap: &impl GetStaking, minimal_required_staking: Coin) {
+    pub fn initialize(
+        &mut self,
+        heap: &impl GetStaking,
+        minimal_required_staking: Coin,
+        minimal_required_community_staking: Coin,
+    ) {
         assert!(self.idx_sort.is_empty());
         assert!(self.idx_validator_address.is_empty());
         self.minimal_required_staking = minimal_required_staking;
+        self.minimal_required_community_staking = minimal_required_community_staking;
 
         for (addr, _) in self.liveness.iter() {
             // no panic: Invariant 2.3 + 2.2 + 2.1
@@ -255,7 +295,7 @@ impl StakingTable {
         sum_coins(
             self.chosen_validators
                 .keys()
-                .map(|addr| heap.get(addr).unwrap().bonded),
+                .map(|addr| heap.get(addr).unwrap().voting_stake()),
         )
         .unwrap()
     }
@@ -285,8 +325,18 @@ impl StakingTable {
             )
             .expect("Overflow while distributing rewards");
             remainder = (remainder - amount).unwrap();
-            distributed.push((addr, amount));
-            self.add_bonded(amount, &mut staking).unwrap();
+
+            // the delegators' shares (minus the commission) are proportional to the delegated amounts
+            let mut own_amount = amount;
+            for (delegator_addr, reward) in delegators_rewards(&staking, amount).into_iter() {
+                let mut delegator = self.get_or_default(heap, &delegator_addr);
+                own_amount = (own_amount - reward).unwrap();
+                distributed.push((delegator_addr, reward));
+                self.add_bonded(reward, &mut delegator).unwrap();
+                set_staking(heap, delegator, self.minimal_required_staking);
+            }
+            distributed.push((addr, own_amount));
+            self.add_bonded(own_amount, &mut staking).unwrap();
             set_staking(heap, staking, self.minimal_required_staking);
         }
         #[cfg(debug_assertions)]
@@ -294,6 +344,20 @@ impl StakingTable {
         (remainder, distributed)
     }
 
+    /// Credit the treasury's portion of the transaction fees (as unbonded)
+    pub fn treasury_deposit(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        addr: &StakedStateAddress,
+        amount: Coin,
+    ) {
+        let mut staking = self.get_or_default(heap, addr);
+        staking.unbonded = (staking.unbonded + amount).expect("treasury unbonded overflow");
+        set_staking(heap, staking, self.minimal_required_staking);
+        #[cfg(debug_assertions)]
+        self.check_invariants(heap);
+    }
+
     /// list council nodes for abci_query
     pub fn list_council_nodes(&self, heap: &impl GetStaking) -> Vec<CouncilNodeMetadata> {
         self.idx_sort
@@ -304,7 +368,7 @@ impl StakingTable {
                     if val.is_active() {
                         Some(CouncilNodeMetadata {
                             name: val.council_node.node_info.name.clone(),
-                            voting_power: staking.bonded.into(),
+                            voting_power: staking.voting_stake().into(),
                             staking_address: key.address,
                             security_contact: val.council_node.node_info.security_contact.clone(),
                             tendermint_pubkey: val.council_node.consensus_pubkey.clone(),
@@ -320,6 +384,193 @@ impl StakingTable {
             .collect()
     }
 
+    /// Keypackages (as submitted in node join or genesis) of the active council nodes
+    pub fn list_council_keypackages(&self, heap: &impl GetStaking) -> Vec<Vec<u8>> {
+        self.idx_sort
+            .iter()
+            .filter_map(|key| {
+                let staking = heap.get(&key.address).unwrap();
+                match staking.node_meta {
+                    Some(NodeState::CouncilNode(val)) if val.is_active() => {
+                        Some(val.council_node.node_info.confidential_init.keypackage)
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// list community nodes for abci_query
+    pub fn list_community_nodes(&self, heap: &impl GetStaking) -> Vec<CommunityNodeMetadata> {
+        self.community_nodes
+            .iter()
+            .filter_map(|addr| {
+                // no panic: Invariant 2.5
+                let staking = heap.get(addr).unwrap();
+                match staking.node_meta {
+                    Some(NodeState::CommunityNode(node)) if node.is_active() => {
+                        Some(CommunityNodeMetadata {
+                            name: node.node_info.name,
+                            bonded: staking.bonded,
+                            staking_address: staking.address,
+                            security_contact: node.node_info.security_contact,
+                        })
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Keypackages (as submitted in node join) of the active community nodes
+    pub fn list_community_keypackages(&self, heap: &impl GetStaking) -> Vec<Vec<u8>> {
+        self.community_nodes
+            .iter()
+            .filter_map(|addr| {
+                let staking = heap.get(addr).unwrap();
+                match staking.node_meta {
+                    Some(NodeState::CommunityNode(node)) if node.is_active() => {
+                        Some(node.node_info.confidential_init.keypackage)
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// P2P node IDs (as recorded in the node metadata) of the active council and community nodes
+    pub fn list_p2p_node_ids(&self, heap: &impl GetStaking) -> Vec<TendermintNodeId> {
+        self.idx_sort
+            .iter()
+            .map(|key| &key.address)
+            .chain(self.community_nodes.iter())
+            .filter_map(|addr| {
+                let staking = heap.get(addr).unwrap();
+                match staking.node_meta {
+                    Some(NodeState::CouncilNode(val)) if val.is_active() => {
+                        val.council_node.node_info.p2p_node_id
+                    }
+                    Some(NodeState::CommunityNode(node)) if node.is_active() => {
+                        node.node_info.p2p_node_id
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// the community node record with the keypackage (of its TDBE group member)
+    fn find_community_node(
+        &self,
+        heap: &impl GetStaking,
+        keypackage: &[u8],
+    ) -> Option<StakedState> {
+        self.community_nodes.iter().find_map(|addr| {
+            // no panic: Invariant 2.5
+            let staking = heap.get(addr).unwrap();
+            match &staking.node_meta {
+                Some(NodeState::CommunityNode(node))
+                    if node.node_info.confidential_init.keypackage == keypackage =>
+                {
+                    Some(staking)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// checks if the keypackage (of a TDBE group member) is of a community node
+    pub fn is_community_keypackage(&self, heap: &impl GetStaking, keypackage: &[u8]) -> bool {
+        self.find_community_node(heap, keypackage).is_some()
+    }
+
+    /// Replace the keypackage of the community node updated in the TDBE group,
+    /// so that its group member can still be found by it
+    pub fn update_community_keypackage(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        keypackage: &[u8],
+        updated: Vec<u8>,
+    ) {
+        if let Some(mut staking) = self.find_community_node(heap, keypackage) {
+            if let Some(NodeState::CommunityNode(node)) = staking.node_meta.as_mut() {
+                node.node_info.confidential_init.keypackage = updated;
+            }
+            set_staking(heap, staking, self.minimal_required_staking);
+        }
+    }
+
+    /// Jail the active community node with the keypackage (evicted from the TDBE group),
+    /// returns its address and the jailed until time, or `None` if there's no such node.
+    pub fn jail_community_node(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        keypackage: &[u8],
+        block_time: Timespec,
+        block_height: BlockHeight,
+        jail_duration: Timespec,
+    ) -> Option<(StakedStateAddress, Timespec)> {
+        let mut staking = self.find_community_node(heap, keypackage)?;
+        let jailed_until = match staking.node_meta.as_mut() {
+            Some(NodeState::CommunityNode(node)) if node.is_active() => {
+                node.jail(block_time, block_height, jail_duration)
+            }
+            _ => return None,
+        };
+        let address = staking.address;
+        set_staking(heap, staking, self.minimal_required_staking);
+
+        #[cfg(debug_assertions)]
+        self.check_invariants(heap);
+        Some((address, jailed_until))
+    }
+
+    /// Set the new minimal stakes (e.g. changed by governance proposal),
+    /// the active nodes which don't have enough bonded coins anymore are inactivated.
+    pub fn update_minimal_required_staking(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        minimal_required_staking: Coin,
+        minimal_required_community_staking: Coin,
+        block_time: Timespec,
+        block_height: BlockHeight,
+    ) {
+        self.minimal_required_staking = minimal_required_staking;
+        self.minimal_required_community_staking = minimal_required_community_staking;
+
+        let addresses = self
+            .liveness
+            .keys()
+            .chain(self.community_nodes.iter())
+            .copied()
+            .collect::<Vec<_>>();
+        for addr in addresses.iter() {
+            // no panic: Invariant 2.1 + 2.5
+            let mut staking = heap.get(addr).unwrap();
+            let inactivated = match staking.node_meta.as_mut() {
+                Some(NodeState::CouncilNode(val))
+                    if val.is_active() && staking.bonded < minimal_required_staking =>
+                {
+                    val.inactivate(block_time, block_height);
+                    true
+                }
+                Some(NodeState::CommunityNode(node))
+                    if node.is_active() && staking.bonded < minimal_required_community_staking =>
+                {
+                    node.inactivate(block_time, block_height);
+                    true
+                }
+                _ => false,
+            };
+            if inactivated {
+                set_staking(heap, staking, self.minimal_required_staking);
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        self.check_invariants(heap);
+    }
+
     /// Query staking address by validator address
     pub fn lookup_address(
         &self,
@@ -385,14 +636,62 @@ impl StakingTable {
             assert!(self.idx_sort.insert(staking.into()));
         }
 
-        if let Some(NodeState::CouncilNode(val)) = staking.node_meta.as_mut() {
-            if val.is_active() && staking.bonded < self.minimal_required_staking {
-                val.inactivate(block_time, block_height);
+        match staking.node_meta.as_mut() {
+            Some(NodeState::CouncilNode(val)) => {
+                if val.is_active() && staking.bonded < self.minimal_required_staking {
+                    val.inactivate(block_time, block_height);
+                }
+            }
+            Some(NodeState::CommunityNode(node)) => {
+                if node.is_active() && staking.bonded < self.minimal_required_community_staking {
+                    node.inactivate(block_time, block_height);
+                }
             }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Record the amount delegated to the council node, and update related index
+    pub(crate) fn add_delegated(
+        &mut self,
+        staking: &mut StakedState,
+        delegator: StakedStateAddress,
+        amount: Coin,
+    ) -> Result<(), CoinError> {
+        let delegated = (staking
+            .delegators
+            .get(&delegator)
+            .copied()
+            .unwrap_or_default()
+            + amount)?;
+        if staking.has_council_node_meta() {
+            assert!(self.idx_sort.remove(&staking.into()));
+        }
+        staking.delegators.insert(delegator, delegated);
+        if staking.has_council_node_meta() {
+            assert!(self.idx_sort.insert(staking.into()));
         }
         Ok(())
     }
 
+    /// Remove the delegation record, and update related index,
+    /// returns the (possibly slashed) delegated amount
+    pub(crate) fn remove_delegated(
+        &mut self,
+        staking: &mut StakedState,
+        delegator: &StakedStateAddress,
+    ) -> Coin {
+        if staking.has_council_node_meta() {
+            assert!(self.idx_sort.remove(&staking.into()));
+        }
+        let amount = staking.delegators.remove(delegator).unwrap_or_default();
+        if staking.has_council_node_meta() {
+            assert!(self.idx_sort.insert(staking.into()));
+        }
+        amount
+    }
+
     /// Change bonded, and related index
     pub(crate) fn add_bonded(
         &mut self,
@@ -426,10 +725,33 @@ impl StakingTable {
             .unwrap();
         // no panic: SlashRatio invariant(<= 1.0)
         staking.unbonded = (staking.unbonded - unbonded_slashed).unwrap();
+
+        if staking.has_council_node_meta() {
+            assert!(self.idx_sort.remove(&staking.into()));
+        }
+        let delegated_slashed = staking
+            .delegators
+            .iter_mut()
+            .filter_map(|(delegator, amount)| {
+                let slashed = *amount * ratio;
+                // no panic: SlashRatio invariant(<= 1.0)
+                *amount = (*amount - slashed).unwrap();
+                if slashed > Coin::zero() {
+                    Some((*delegator, slashed))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        if staking.has_council_node_meta() {
+            assert!(self.idx_sort.insert(staking.into()));
+        }
+
         // no panic: Invariant: 4.1 + SlashRatio invariant
         SlashedCoin {
             bonded: bonded_slashed,
             unbonded: unbonded_slashed,
+            delegated: delegated_slashed,
         }
     }
 
@@ -446,7 +768,7 @@ impl StakingTable {
                 // no panic: Invariant 2.2
                 if let Some(NodeState::CouncilNode(val)) = staking.node_meta.as_ref() {
                     if val.is_active() {
-                        Some((staking.address, staking.bonded.into()))
+                        Some((staking.address, staking.voting_stake().into()))
                     } else {
                         None
                     }
@@ -492,6 +814,37 @@ impl StakingTable {
             log::info!("cleanup validators: {}", to_delete.len());
         }
 
+        let community_to_delete = self
+            .community_nodes
+            .iter()
+            .filter_map(|addr| {
+                let staking = heap.get(addr).unwrap();
+                if let Some(NodeState::CommunityNode(node)) = &staking.node_meta {
+                    if node.is_jailed() {
+                        return None;
+                    }
+                    if let Some(inactive_time) = node.inactive_time {
+                        if block_time > inactive_time.saturating_add(unbonding_period) {
+                            return Some(*addr);
+                        }
+                    }
+                }
+                None
+            })
+            .collect::<Vec<_>>();
+
+        if !community_to_delete.is_empty() {
+            log::info!("cleanup community nodes: {}", community_to_delete.len());
+        }
+
+        // only place that removes the community node records
+        for addr in community_to_delete.iter() {
+            let mut staking = heap.get(addr).unwrap();
+            assert!(self.community_nodes.remove(addr));
+            staking.node_meta = None;
+            set_staking(heap, staking, self.minimal_required_staking);
+        }
+
         // only place that removes the validator records
         for addr in to_delete.iter() {
             // no panic: Already checked above, no concurrency.
@@ -617,6 +970,9 @@ impl StakingTable {
                     match kind {
                         PunishmentKind::NonLive => info.params.get_liveness_slash_percent(),
                         PunishmentKind::ByzantineFault => info.params.get_byzantine_slash_percent(),
+                        PunishmentKind::TdbeEviction => {
+                            unreachable!("only the council nodes are slashed")
+                        }
                     },
                 );
 
@@ -653,12 +1009,24 @@ impl StakingTable {
         max_validators: usize,
     ) -> Vec<(TendermintValidatorPubKey, TendermintVotePower)> {
         let new = self.choose_validators(heap, max_validators);
-        let updates = diff_validators(&self.chosen_validators, &new);
-        self.chosen_validators = new;
-        updates
+        let rotated = std::mem::take(&mut self.rotated_validator_keys);
+        let mut updates = diff_validators(&self.chosen_validators, &new)
             .into_iter()
+            .filter(|(addr, _)| !rotated.contains_key(addr))
             .map(|(addr, power)| (self.get_validator_pubkey(heap, &addr), power))
-            .collect()
+            .collect::<Vec<_>>();
+        // the old key is removed, and the new key is added with the current voting power
+        // (the old key can't be reused before it's out of date, so they are always different)
+        for (addr, old_pubkey) in rotated.into_iter() {
+            if self.chosen_validators.contains_key(&addr) {
+                updates.push((old_pubkey, TendermintVotePower::zero()));
+            }
+            if let Some(power) = new.get(&addr) {
+                updates.push((self.get_validator_pubkey(heap, &addr), *power));
+            }
+        }
+        self.chosen_validators = new;
+        updates
     }
 
     #[cfg(debug_assertions)]
@@ -666,6 +1034,7 @@ impl StakingTable {
         self.check_invariant2_1(heap);
         self.check_invariant2_2(heap);
         self.check_invariant2_3();
+        self.check_invariant2_5(heap);
 
         self.check_validator_invariant(heap);
     }
@@ -707,7 +1076,7 @@ impl StakingTable {
             let staking = heap
                 .get(&key.address)
                 .expect("idx_validator_address doesn't match heap");
-            assert_eq!(key.bonded, staking.bonded);
+            assert_eq!(key.voting_stake, staking.voting_stake());
         }
     }
 
@@ -740,6 +1109,17 @@ impl StakingTable {
         );
     }
 
+    #[cfg(debug_assertions)]
+    fn check_invariant2_5(&mut self, heap: &impl GetStaking) {
+        for addr in self.community_nodes.iter() {
+            let staking = heap.get(addr).expect("community_nodes doesn't match heap");
+            assert!(staking.has_community_node_meta());
+        }
+        for key in self.idx_sort.iter() {
+            assert!(!self.community_nodes.contains(&key.address));
+        }
+    }
+
     #[cfg(debug_assertions)]
     fn check_validator_invariant(&mut self, heap: &impl GetStaking) {
         for addr in self.idx_validator_address.values() {
@@ -785,6 +1165,36 @@ impl StakingTable {
     }
 }
 
+/// The rewards of each delegator:
+/// the share of `amount` proportional to the delegated amount (of the voting stake),
+/// minus the commission kept by the council node.
+fn delegators_rewards(staking: &StakedState, amount: Coin) -> Vec<(StakedStateAddress, Coin)> {
+    let commission_rate = match staking.node_meta.as_ref() {
+        Some(NodeState::CouncilNode(val)) => val.commission_rate.as_millis(),
+        _ => MAX_COMMISSION_RATE_MILLIS,
+    };
+    let voting_stake = u64::from(staking.voting_stake()) as u128;
+    if voting_stake == 0 {
+        return vec![];
+    }
+    staking
+        .delegators
+        .iter()
+        .filter_map(|(delegator, delegated)| {
+            let share =
+                (u64::from(amount) as u128) * (u64::from(*delegated) as u128) / voting_stake;
+            let reward = share * MAX_COMMISSION_RATE_MILLIS.saturating_sub(commission_rate) as u128
+                / MAX_COMMISSION_RATE_MILLIS as u128;
+            if reward > 0 {
+                // no panic: reward <= share <= amount
+                Some((*delegator, Coin::new(reward as u64).unwrap()))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
 pub(crate) fn set_staking(
     heap: &mut impl StoreStaking,
     staking: StakedState,
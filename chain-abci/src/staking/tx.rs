@@ -3,10 +3,11 @@ use log::warn;
 use chain_core::common::Timespec;
 use chain_core::init::coin::Coin;
 use chain_core::state::account::{
-    NodeMetadata, NodeState, StakedStateAddress, UnbondTx, UnjailTx, Validator,
+    CommunityNode, CouncilNodeMeta, DelegateTx, NodeCommonInfo, NodeMetadata, NodeState,
+    StakedState, StakedStateAddress, UnbondTx, UndelegateTx, UnjailTx, Validator,
 };
 use chain_core::state::tendermint::{BlockHeight, TendermintValidatorAddress};
-use chain_core::state::validator::NodeJoinRequestTx;
+use chain_core::state::validator::{NodeJoinRequestTx, NodeLeaveTx, NodeMetadataUpdateTx};
 use chain_core::tx::fee::Fee;
 use chain_storage::buffer::StoreStaking;
 use mls::{Codec, KeyPackage};
@@ -14,7 +15,8 @@ use ra_client::ENCLAVE_CERT_VERIFIER;
 
 use super::table::{set_staking, StakingTable};
 use crate::tx_error::{
-    DepositError, NodeJoinError, PublicTxError, UnbondError, UnjailError, WithdrawError,
+    DelegateError, DepositError, NodeJoinError, NodeLeaveError, NodeUpdateError, PublicTxError,
+    UnbondError, UnjailError, WithdrawError,
 };
 
 const MAX_USED_VALIDATOR_ADDR: usize = 10;
@@ -33,9 +35,19 @@ impl StakingTable {
         if tx.nonce != staking.nonce {
             return Err(PublicTxError::IncorrectNonce);
         }
-        if staking.bonded < self.minimal_required_staking {
+        let minimal_required_staking = match &tx.node_meta {
+            NodeMetadata::CouncilNode(_) => self.minimal_required_staking,
+            NodeMetadata::CommunityNode(_) => self.minimal_required_community_staking,
+        };
+        if staking.bonded < minimal_required_staking {
             return Err(NodeJoinError::BondedNotEnough.into());
         }
+        if staking.delegated_to.is_some() {
+            return Err(NodeJoinError::IsDelegator.into());
+        }
+        if !tx.is_valid_commission_rate() {
+            return Err(NodeJoinError::InvalidCommissionRate.into());
+        }
 
         let isv_svn = if cfg!(feature = "mock-enclave") {
             0
@@ -57,11 +69,18 @@ impl StakingTable {
         };
 
         let val_addr = match &tx.node_meta {
-            NodeMetadata::CouncilNode(cm) => {
-                Ok(TendermintValidatorAddress::from(&cm.consensus_pubkey))
+            NodeMetadata::CouncilNode(cm) => TendermintValidatorAddress::from(&cm.consensus_pubkey),
+            NodeMetadata::CommunityNode(node_info) => {
+                self.community_node_join(&mut staking, node_info)?;
+                staking.inc_nonce();
+                set_staking(heap, staking, self.minimal_required_staking);
+
+                #[cfg(debug_assertions)]
+                self.check_invariants(heap);
+
+                return Ok(new_isv_svn);
             }
-            _ => Err(NodeJoinError::WIPNotValidator),
-        }?;
+        };
         if let Some(NodeState::CouncilNode(val)) = &mut staking.node_meta {
             if val.is_jailed() {
                 return Err(NodeJoinError::IsJailed.into());
@@ -98,11 +117,14 @@ impl StakingTable {
                     NodeMetadata::CouncilNode(cm) => cm.clone(),
                     _ => unreachable!("FIXME"),
                 };
+                val.commission_rate = tx.commission_rate.unwrap_or_default();
                 val.inactive_time = None;
                 val.inactive_block = None;
             } else {
                 return Err(NodeJoinError::AlreadyJoined.into());
             }
+        } else if staking.has_community_node_meta() {
+            return Err(NodeJoinError::NodeTypeMismatch.into());
         } else {
             if self.idx_validator_address.contains_key(&val_addr) {
                 return Err(NodeJoinError::DuplicateValidatorAddress.into());
@@ -111,7 +133,9 @@ impl StakingTable {
             // insert
             staking.node_meta = match &tx.node_meta {
                 NodeMetadata::CouncilNode(cm) => {
-                    Some(NodeState::CouncilNode(Validator::new(cm.clone())))
+                    let mut val = Validator::new(cm.clone());
+                    val.commission_rate = tx.commission_rate.unwrap_or_default();
+                    Some(NodeState::CouncilNode(val))
                 }
                 _ => {
                     // FIXME
@@ -129,6 +153,147 @@ impl StakingTable {
         Ok(new_isv_svn)
     }
 
+    /// Handle `NodeMetadataUpdateTx`, returns the updated council node metadata
+    pub fn update_node_metadata(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        block_time: Timespec,
+        max_evidence_age: Timespec,
+        tx: &NodeMetadataUpdateTx,
+    ) -> Result<CouncilNodeMeta, PublicTxError> {
+        let mut staking = self.get_or_default(heap, &tx.address);
+        if tx.nonce != staking.nonce {
+            return Err(PublicTxError::IncorrectNonce);
+        }
+        let val = match &mut staking.node_meta {
+            Some(NodeState::CouncilNode(val)) => val,
+            _ => return Err(NodeUpdateError::NotCouncilNode.into()),
+        };
+        if val.is_jailed() {
+            return Err(NodeUpdateError::IsJailed.into());
+        }
+        if !tx.is_valid_commission_rate() {
+            return Err(NodeUpdateError::InvalidCommissionRate.into());
+        }
+
+        let old_val_addr = val.validator_address();
+        let val_addr = TendermintValidatorAddress::from(&tx.consensus_pubkey);
+        if old_val_addr != val_addr {
+            if self.idx_validator_address.contains_key(&val_addr) {
+                return Err(NodeUpdateError::DuplicateValidatorAddress.into());
+            }
+
+            // Add the old one to the used list, so the evidences of it can still be handled.
+            let out_of_date = add_old_val_addr(
+                &mut val.used_validator_addresses,
+                block_time,
+                &old_val_addr,
+                MAX_USED_VALIDATOR_ADDR,
+                max_evidence_age,
+            )
+            .ok_or(NodeUpdateError::UsedValidatorAddrFull)?;
+
+            for used_addr in out_of_date.into_iter() {
+                assert_eq!(
+                    self.idx_validator_address.remove(&used_addr),
+                    Some(tx.address)
+                );
+            }
+            self.idx_validator_address.insert(val_addr, tx.address);
+            self.rotated_validator_keys
+                .entry(tx.address)
+                .or_insert_with(|| val.council_node.consensus_pubkey.clone());
+        }
+        val.council_node.node_info.name = tx.name.clone();
+        val.council_node.node_info.security_contact = tx.security_contact.clone();
+        val.council_node.consensus_pubkey = tx.consensus_pubkey.clone();
+        val.commission_rate = tx.commission_rate;
+        val.council_node.node_info.p2p_node_id = tx.p2p_node_id.clone();
+        let council_node = val.council_node.clone();
+
+        staking.inc_nonce();
+        set_staking(heap, staking, self.minimal_required_staking);
+
+        #[cfg(debug_assertions)]
+        self.check_invariants(heap);
+
+        Ok(council_node)
+    }
+
+    /// Handle `NodeLeaveTx`
+    /// The node is inactivated without punishment, so a council node is removed from the
+    /// validator set in the next end block, and the record is cleaned up after the unbonding period.
+    pub fn node_leave(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        block_time: Timespec,
+        block_height: BlockHeight,
+        tx: &NodeLeaveTx,
+    ) -> Result<(), PublicTxError> {
+        let mut staking = self.get_or_default(heap, &tx.address);
+        if tx.nonce != staking.nonce {
+            return Err(PublicTxError::IncorrectNonce);
+        }
+        match staking.node_meta.as_mut() {
+            Some(NodeState::CouncilNode(val)) => {
+                if val.is_jailed() {
+                    return Err(NodeLeaveError::IsJailed.into());
+                }
+                if !val.is_active() {
+                    return Err(NodeLeaveError::AlreadyInactive.into());
+                }
+                val.inactivate(block_time, block_height);
+            }
+            Some(NodeState::CommunityNode(node)) => {
+                if node.is_jailed() {
+                    return Err(NodeLeaveError::IsJailed.into());
+                }
+                if !node.is_active() {
+                    return Err(NodeLeaveError::AlreadyInactive.into());
+                }
+                node.inactivate(block_time, block_height);
+            }
+            None => return Err(NodeLeaveError::NotNode.into()),
+        }
+        staking.inc_nonce();
+        set_staking(heap, staking, self.minimal_required_staking);
+
+        #[cfg(debug_assertions)]
+        self.check_invariants(heap);
+        Ok(())
+    }
+
+    /// Insert or re-activate the community node record
+    fn community_node_join(
+        &mut self,
+        staking: &mut StakedState,
+        node_info: &NodeCommonInfo,
+    ) -> Result<(), NodeJoinError> {
+        match &mut staking.node_meta {
+            Some(NodeState::CommunityNode(node)) => {
+                if node.is_jailed() {
+                    return Err(NodeJoinError::IsJailed);
+                }
+                if node.is_active() {
+                    return Err(NodeJoinError::AlreadyJoined);
+                }
+                node.node_info = node_info.clone();
+                node.inactive_time = None;
+                node.inactive_block = None;
+            }
+            Some(NodeState::CouncilNode(_)) => {
+                return Err(NodeJoinError::NodeTypeMismatch);
+            }
+            None => {
+                staking.node_meta = Some(NodeState::CommunityNode(CommunityNode::new(
+                    node_info.clone(),
+                )));
+                assert!(self.community_nodes.insert(staking.address));
+            }
+        }
+        Ok(())
+    }
+
     /// Handle `UnjailTx`
     pub fn unjail(
         &mut self,
@@ -141,21 +306,26 @@ impl StakingTable {
             return Err(PublicTxError::IncorrectNonce);
         }
 
-        if let Some(NodeState::CouncilNode(val)) = staking.node_meta.as_mut() {
-            if let Some(jailed_until) = val.jailed_until {
-                if block_time >= jailed_until {
-                    val.unjail();
-                    staking.inc_nonce();
-                    set_staking(heap, staking, self.minimal_required_staking);
-
-                    #[cfg(debug_assertions)]
-                    self.check_invariants(heap);
-                    Ok(())
-                } else {
-                    Err(UnjailError::JailTimeNotExpired.into())
+        let jailed_until = match staking.node_meta.as_ref() {
+            Some(NodeState::CouncilNode(val)) => val.jailed_until,
+            Some(NodeState::CommunityNode(node)) => node.jailed_until,
+            None => None,
+        };
+        if let Some(jailed_until) = jailed_until {
+            if block_time >= jailed_until {
+                match staking.node_meta.as_mut() {
+                    Some(NodeState::CouncilNode(val)) => val.unjail(),
+                    Some(NodeState::CommunityNode(node)) => node.unjail(),
+                    None => unreachable!("jailed staking has node metadata"),
                 }
+                staking.inc_nonce();
+                set_staking(heap, staking, self.minimal_required_staking);
+
+                #[cfg(debug_assertions)]
+                self.check_invariants(heap);
+                Ok(())
             } else {
-                Err(UnjailError::NotJailed.into())
+                Err(UnjailError::JailTimeNotExpired.into())
             }
         } else {
             Err(UnjailError::NotJailed.into())
@@ -223,6 +393,80 @@ impl StakingTable {
         Ok(unbonded_from)
     }
 
+    /// Handle delegate tx
+    pub fn delegate(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        tx: &DelegateTx,
+    ) -> Result<(), PublicTxError> {
+        let mut staking = self.get_or_default(heap, &tx.from_staked_account);
+        if tx.nonce != staking.nonce {
+            return Err(PublicTxError::IncorrectNonce);
+        }
+        if tx.value == Coin::zero() {
+            return Err(DelegateError::ZeroValue.into());
+        }
+        if staking.node_meta.is_some() {
+            return Err(DelegateError::IsNode.into());
+        }
+        match staking.delegated_to {
+            Some(addr) if addr != tx.to_council_node => {
+                return Err(DelegateError::AlreadyDelegated.into());
+            }
+            _ => {}
+        }
+        if tx.to_council_node == tx.from_staked_account {
+            return Err(DelegateError::NotActiveCouncilNode.into());
+        }
+        let mut council_node = self.get_or_default(heap, &tx.to_council_node);
+        match council_node.node_meta.as_ref() {
+            Some(NodeState::CouncilNode(val)) if val.is_active() => {}
+            _ => {
+                return Err(DelegateError::NotActiveCouncilNode.into());
+            }
+        }
+        staking.bonded = (staking.bonded - tx.value).map_err(DelegateError::CoinError)?;
+        self.add_delegated(&mut council_node, tx.from_staked_account, tx.value)
+            .map_err(DelegateError::CoinError)?;
+        staking.delegated_to = Some(tx.to_council_node);
+        staking.inc_nonce();
+        set_staking(heap, staking, self.minimal_required_staking);
+        set_staking(heap, council_node, self.minimal_required_staking);
+        #[cfg(debug_assertions)]
+        self.check_invariants(heap);
+        Ok(())
+    }
+
+    /// Handle undelegate tx,
+    /// returns the council node undelegated from, the amount moved to unbonded and unbonded_from
+    pub fn undelegate(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        unbonding_period: Timespec,
+        block_time: Timespec,
+        tx: &UndelegateTx,
+    ) -> Result<(StakedStateAddress, Coin, Timespec), PublicTxError> {
+        let mut staking = self.get_or_default(heap, &tx.from_staked_account);
+        if tx.nonce != staking.nonce {
+            return Err(PublicTxError::IncorrectNonce);
+        }
+        let council_address = staking.delegated_to.ok_or(DelegateError::NotDelegated)?;
+        // no panic: Invariant 4.4, the delegated staked state exists
+        let mut council_node = heap.get(&council_address).unwrap();
+        let amount = self.remove_delegated(&mut council_node, &tx.from_staked_account);
+        staking.unbonded = (staking.unbonded + amount).map_err(DelegateError::CoinError)?;
+
+        let unbonded_from = block_time.saturating_add(unbonding_period);
+        staking.unbonded_from = unbonded_from;
+        staking.delegated_to = None;
+        staking.inc_nonce();
+        set_staking(heap, staking, self.minimal_required_staking);
+        set_staking(heap, council_node, self.minimal_required_staking);
+        #[cfg(debug_assertions)]
+        self.check_invariants(heap);
+        Ok((council_address, amount, unbonded_from))
+    }
+
     /// Handle withdraw tx
     /// Enclave validation is done in enclave, only incomplete check here.
     pub fn withdraw(
@@ -1,21 +1,31 @@
 use crate::enclave_bridge::EnclaveProxy;
+use crate::governance::ProposalTable;
 use crate::staking::StakingTable;
-use crate::tx_error::PublicTxError;
+use crate::tx_error::{GovernanceError, PublicTxError};
 use chain_core::common::Timespec;
 use chain_core::init::coin::Coin;
 use chain_core::state::account::{
     CouncilNodeMeta, NodeMetadata, StakedStateAddress, StakedStateOpAttributes,
 };
+use chain_core::state::governance::{NetworkParameterUpdate, ProposalId};
 use chain_core::tx::data::input::{TxoPointer, TxoSize};
 use chain_core::tx::fee::Fee;
 use chain_core::tx::{TransactionId, TxEnclaveAux, TxObfuscated, TxPublicAux};
 use chain_storage::buffer::{GetKV, GetStaking, StoreStaking};
-use chain_tx_validation::{verify_unjailed, witness::verify_tx_recover_address, ChainInfo, Error};
+use chain_tx_validation::{
+    verify_unjailed, witness::verify_tx_recover_address, ChainInfo, Error, TxCreation,
+};
 use enclave_protocol::{IntraEnclaveRequest, IntraEnclaveResponseOk, SealedLog};
 
 pub enum TxAction {
     Enclave(TxEnclaveAction),
     Public(TxPublicAction),
+    /// MLS group state moved to the new epoch (nack doesn't change it)
+    MLSHandshake {
+        epoch: u64,
+        /// the community nodes evicted from the group by a council node (and their jailed until times)
+        jailed: Vec<(StakedStateAddress, Timespec)>,
+    },
 }
 
 impl TxAction {
@@ -23,6 +33,7 @@ impl TxAction {
         match self {
             Self::Enclave(action) => action.fee(),
             Self::Public(action) => action.fee(),
+            Self::MLSHandshake { .. } => Fee::new(Coin::zero()),
         }
     }
 
@@ -30,6 +41,7 @@ impl TxAction {
         match self {
             Self::Enclave(action) => action.staking_address(),
             Self::Public(action) => action.staking_address(),
+            Self::MLSHandshake { .. } => None,
         }
     }
 }
@@ -120,11 +132,32 @@ pub enum TxPublicAction {
     },
     NodeJoin {
         address: StakedStateAddress,
-        council_node: CouncilNodeMeta,
+        node_meta: NodeMetadata,
         // most recent isv_svn
         isv_svn: u16,
     },
     Unjail(StakedStateAddress),
+    ParamChangeProposal(StakedStateAddress, ProposalId),
+    ParamChangeVote(StakedStateAddress, ProposalId),
+    Delegate {
+        delegator: StakedStateAddress,
+        council_node: StakedStateAddress,
+        amount: Coin,
+    },
+    Undelegate {
+        delegator: StakedStateAddress,
+        council_node: StakedStateAddress,
+        amount: Coin,
+        unbonded_from: Timespec,
+    },
+    NodeMetadataUpdate {
+        address: StakedStateAddress,
+        council_node: CouncilNodeMeta,
+    },
+    NodeLeave {
+        address: StakedStateAddress,
+        inactive_time: Timespec,
+    },
 }
 
 impl TxPublicAction {
@@ -135,10 +168,10 @@ impl TxPublicAction {
             unbonded_from,
         }
     }
-    fn node_join(address: StakedStateAddress, council_node: CouncilNodeMeta, isv_svn: u16) -> Self {
+    fn node_join(address: StakedStateAddress, node_meta: NodeMetadata, isv_svn: u16) -> Self {
         Self::NodeJoin {
             address,
-            council_node,
+            node_meta,
             isv_svn,
         }
     }
@@ -151,6 +184,12 @@ impl TxPublicAction {
             Self::Unbond { fee, .. } => *fee,
             Self::NodeJoin { .. } => Fee::new(Coin::zero()),
             Self::Unjail(_) => Fee::new(Coin::zero()),
+            Self::ParamChangeProposal(..) => Fee::new(Coin::zero()),
+            Self::ParamChangeVote(..) => Fee::new(Coin::zero()),
+            Self::Delegate { .. } => Fee::new(Coin::zero()),
+            Self::Undelegate { .. } => Fee::new(Coin::zero()),
+            Self::NodeMetadataUpdate { .. } => Fee::new(Coin::zero()),
+            Self::NodeLeave { .. } => Fee::new(Coin::zero()),
         }
     }
 
@@ -159,19 +198,27 @@ impl TxPublicAction {
             Self::Unbond { unbond, .. } => Some(unbond.0),
             Self::NodeJoin { address, .. } => Some(*address),
             Self::Unjail(staking_address) => Some(*staking_address),
+            Self::ParamChangeProposal(staking_address, _) => Some(*staking_address),
+            Self::ParamChangeVote(staking_address, _) => Some(*staking_address),
+            Self::Delegate { delegator, .. } => Some(*delegator),
+            Self::Undelegate { delegator, .. } => Some(*delegator),
+            Self::NodeMetadataUpdate { address, .. } => Some(*address),
+            Self::NodeLeave { address, .. } => Some(*address),
         }
     }
 }
 
+/// returns the sealed transactions of inputs and the blocks in which they were created
 fn check_spent_input_lookup(
     kvdb: &impl GetKV,
     inputs: &[TxoPointer],
-) -> Result<Vec<SealedLog>, Error> {
+) -> Result<(Vec<SealedLog>, Vec<TxCreation>), Error> {
     // check that there are inputs
     if inputs.is_empty() {
         return Err(Error::NoInputs);
     }
     let mut result = Vec::with_capacity(inputs.len());
+    let mut created = Vec::with_capacity(inputs.len());
     for txin in inputs.iter() {
         let spent = chain_storage::lookup_input(kvdb, txin).ok_or(Error::InvalidInput)?;
         if spent {
@@ -181,9 +228,11 @@ fn check_spent_input_lookup(
                 chain_storage::get_sealed_log(kvdb, &txin.id)
                     .expect("valid unspent tx output should be stored"),
             );
+            // transactions committed before the creation info was stored can't have relative timelocks
+            created.push(chain_storage::get_tx_creation(kvdb, &txin.id).unwrap_or_default());
         }
     }
-    Ok(result)
+    Ok((result, created))
 }
 
 /// Checks TX against the current DB, passes to the enclave and returns an `Error` if something fails.
@@ -201,10 +250,14 @@ pub fn verify_enclave_tx<T: EnclaveProxy>(
             no_of_outputs,
             ..
         } => {
-            let tx_inputs = check_spent_input_lookup(kvdb, &inputs)?;
-            let response = tx_validator.process_request(
-                IntraEnclaveRequest::new_validate_transfer(txaux.clone(), *extra_info, tx_inputs),
-            );
+            let (tx_inputs, inputs_created) = check_spent_input_lookup(kvdb, &inputs)?;
+            let response =
+                tx_validator.process_request(IntraEnclaveRequest::new_validate_transfer(
+                    txaux.clone(),
+                    *extra_info,
+                    tx_inputs,
+                    inputs_created,
+                ));
             match response {
                 Ok(IntraEnclaveResponseOk::TxWithOutputs {
                     paid_fee,
@@ -225,13 +278,14 @@ pub fn verify_enclave_tx<T: EnclaveProxy>(
                 verify_unjailed(account)?;
             }
 
-            let tx_inputs = check_spent_input_lookup(kvdb, &tx.inputs)?;
+            let (tx_inputs, inputs_created) = check_spent_input_lookup(kvdb, &tx.inputs)?;
 
             let response = tx_validator.process_request(IntraEnclaveRequest::new_validate_deposit(
                 txaux.clone(),
                 *extra_info,
                 account,
                 tx_inputs,
+                inputs_created,
             ));
             match response {
                 Ok(IntraEnclaveResponseOk::DepositStakeTx { input_coins }) => {
@@ -279,14 +333,14 @@ pub fn verify_enclave_tx<T: EnclaveProxy>(
 
 fn check_staking_attributes(
     attrs: &StakedStateOpAttributes,
-    chain_hex_id: u8,
+    chain_info: &ChainInfo,
 ) -> Result<(), PublicTxError> {
     // check that chain IDs match
-    if chain_hex_id != attrs.chain_hex_id {
+    if chain_info.chain_hex_id != attrs.chain_hex_id {
         return Err(PublicTxError::WrongChainHexId);
     }
-    // check that version number is <= current one
-    if chain_core::APP_VERSION < attrs.app_version {
+    // check that version number is <= the one active on the network
+    if chain_info.app_version < attrs.app_version {
         return Err(PublicTxError::UnsupportedVersion);
     }
     Ok(())
@@ -297,11 +351,12 @@ fn check_staking_attributes(
 pub fn process_public_tx(
     staking_store: &mut impl StoreStaking,
     staking_table: &mut StakingTable,
+    proposals: &mut ProposalTable,
     enclave_isv_svn: u16,
     chain_info: &ChainInfo,
     txaux: &TxPublicAux,
 ) -> Result<TxPublicAction, PublicTxError> {
-    check_staking_attributes(txaux.attributes(), chain_info.chain_hex_id)?;
+    check_staking_attributes(txaux.attributes(), chain_info)?;
     match txaux {
         // TODO: delay checking witness, as address is contained in Tx?
         TxPublicAux::UnbondStakeTx(maintx, witness) => {
@@ -351,12 +406,129 @@ pub fn process_public_tx(
 
             Ok(TxPublicAction::node_join(
                 address,
-                match &maintx.node_meta {
-                    NodeMetadata::CouncilNode(cm) => cm.clone(),
-                    _ => unreachable!("FIXME?"),
-                },
+                maintx.node_meta.clone(),
                 isv_svn,
             ))
         }
+        TxPublicAux::ParamChangeProposalTx(maintx, witness) => {
+            let address = verify_tx_recover_address(&witness, &maintx.id())?;
+            if address != maintx.address {
+                return Err(PublicTxError::StakingWitnessNotMatch);
+            }
+            for change in maintx.changes.iter() {
+                match change {
+                    NetworkParameterUpdate::ScheduleUpgrade(plan) => {
+                        plan.check(maintx.activation_height, chain_info.app_version)
+                            .map_err(GovernanceError::InvalidUpgrade)?;
+                    }
+                    // the parameters are committed in the 0.5 layout before app version 2
+                    _ if chain_info.app_version < 2 => {
+                        return Err(GovernanceError::InvalidParameter(
+                            "only network upgrades can be proposed before app version 2",
+                        )
+                        .into());
+                    }
+                    // the faults within the window need to be punishable (as checked at genesis)
+                    NetworkParameterUpdate::JailingConfig(config)
+                        if u64::from(config.block_signing_window)
+                            > chain_info.max_evidence_age_blocks =>
+                    {
+                        return Err(GovernanceError::InvalidParameter(
+                            "block signing window can't exceed the evidence max age in blocks",
+                        )
+                        .into());
+                    }
+                    _ => {}
+                }
+            }
+            let proposal_id = proposals.propose(
+                staking_store,
+                staking_table,
+                chain_info.block_height,
+                maintx,
+            )?;
+
+            Ok(TxPublicAction::ParamChangeProposal(address, proposal_id))
+        }
+        TxPublicAux::ParamChangeVoteTx(maintx, witness) => {
+            let address = verify_tx_recover_address(&witness, &maintx.id())?;
+            if address != maintx.address {
+                return Err(PublicTxError::StakingWitnessNotMatch);
+            }
+            proposals.vote(
+                staking_store,
+                staking_table,
+                chain_info.block_height,
+                maintx,
+            )?;
+
+            Ok(TxPublicAction::ParamChangeVote(address, maintx.proposal_id))
+        }
+        TxPublicAux::DelegateTx(maintx, witness) => {
+            let address = verify_tx_recover_address(&witness, &maintx.id())?;
+            if address != maintx.from_staked_account {
+                return Err(PublicTxError::StakingWitnessNotMatch);
+            }
+            staking_table.delegate(staking_store, maintx)?;
+
+            Ok(TxPublicAction::Delegate {
+                delegator: address,
+                council_node: maintx.to_council_node,
+                amount: maintx.value,
+            })
+        }
+        TxPublicAux::UndelegateTx(maintx, witness) => {
+            let address = verify_tx_recover_address(&witness, &maintx.id())?;
+            if address != maintx.from_staked_account {
+                return Err(PublicTxError::StakingWitnessNotMatch);
+            }
+            let (council_node, amount, unbonded_from) = staking_table.undelegate(
+                staking_store,
+                chain_info.get_unbonding_period(),
+                chain_info.block_time,
+                maintx,
+            )?;
+
+            Ok(TxPublicAction::Undelegate {
+                delegator: address,
+                council_node,
+                amount,
+                unbonded_from,
+            })
+        }
+        TxPublicAux::NodeMetadataUpdateTx(maintx, witness) => {
+            let address = verify_tx_recover_address(&witness, &maintx.id())?;
+            if address != maintx.address {
+                return Err(PublicTxError::StakingWitnessNotMatch);
+            }
+            let council_node = staking_table.update_node_metadata(
+                staking_store,
+                chain_info.block_time,
+                chain_info.max_evidence_age,
+                maintx,
+            )?;
+
+            Ok(TxPublicAction::NodeMetadataUpdate {
+                address,
+                council_node,
+            })
+        }
+        TxPublicAux::NodeLeaveTx(maintx, witness) => {
+            let address = verify_tx_recover_address(&witness, &maintx.id())?;
+            if address != maintx.address {
+                return Err(PublicTxError::StakingWitnessNotMatch);
+            }
+            staking_table.node_leave(
+                staking_store,
+                chain_info.block_time,
+                chain_info.block_height,
+                maintx,
+            )?;
+
+            Ok(TxPublicAction::NodeLeave {
+                address,
+                inactive_time: chain_info.block_time,
+            })
+        }
     }
 }
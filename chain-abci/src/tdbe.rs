@@ -0,0 +1,433 @@
+//! The MLS group of TDBE (Transaction Data Bootstrapping Enclave) as tracked by the chain
+//! ref: https://github.com/crypto-com/chain-docs/blob/master/docs/modules/tdbe.md
+use parity_scale_codec::{Decode, Encode, Error, Input, Output};
+
+use chain_core::common::{Timespec, H256};
+use chain_core::mls::MLSHandshakeAux;
+use chain_core::state::account::{CouncilNodeMeta, StakedStateAddress};
+use chain_core::tx::data::txid_hash;
+use mls::group::GroupPublicView;
+use mls::message::{ContentType, MLSPlaintext, Proposal, Welcome};
+use mls::tree_math::LeafSize;
+use mls::{Codec, KeyPackage};
+use ra_client::AttestedCertVerifier;
+
+use crate::tx_error::MLSHandshakeError;
+
+/// The members changed by a commit (identified by their encoded keypackages before the commit)
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MemberChanges {
+    /// the member who sent the commit
+    pub committer: Vec<u8>,
+    /// the members whose keypackages were replaced (by their update proposals or by the committer's path),
+    /// with their new keypackages
+    pub updated: Vec<(Vec<u8>, Vec<u8>)>,
+    /// the removed members
+    pub removed: Vec<Vec<u8>>,
+}
+
+/// Public view (without any secrets) of the TDBE MLS group:
+/// chain-abci isn't a group member, so it can only check the signatures,
+/// epochs and the membership of the handshake messages
+/// (the path secrets and confirmations are checked by the enclaves).
+#[derive(Clone)]
+pub struct TdbeGroupState(GroupPublicView);
+
+impl Encode for TdbeGroupState {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.0.get_encoding().encode_to(dest);
+    }
+
+    fn size_hint(&self) -> usize {
+        self.0.get_encoding().size_hint()
+    }
+}
+
+impl Decode for TdbeGroupState {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let payload: Vec<u8> = Vec::decode(input)?;
+        GroupPublicView::read_bytes(&payload)
+            .map(TdbeGroupState)
+            .ok_or_else(|| "Invalid MLS group state".into())
+    }
+}
+
+impl TdbeGroupState {
+    /// The group is created by the first genesis council node (ordered by staking address),
+    /// the other genesis council nodes are added by its first commit.
+    pub fn genesis(validators: &[(StakedStateAddress, CouncilNodeMeta)]) -> Option<Self> {
+        let (_, creator) = validators.iter().min_by_key(|(address, _)| *address)?;
+        let keypackage = KeyPackage::read_bytes(&creator.node_info.confidential_init.keypackage)?;
+        Some(TdbeGroupState(GroupPublicView::init(keypackage)))
+    }
+
+    /// the current epoch of the group
+    pub fn epoch(&self) -> u64 {
+        self.0.context.epoch
+    }
+
+    /// hash of the group state (committed in the app hash): blake3(scale bytes(group state))
+    pub fn hash(&self) -> H256 {
+        txid_hash(&self.encode())
+    }
+
+    /// Verify the handshake message and apply it to the group state,
+    /// returns the members changed by its commit
+    ///
+    /// `node_keypackages`: keypackages of the active council and community nodes,
+    /// only those can be added to the group
+    pub fn process_handshake(
+        &mut self,
+        tx: &MLSHandshakeAux,
+        block_time: Timespec,
+        node_keypackages: &[Vec<u8>],
+        ra_verifier: &impl AttestedCertVerifier,
+    ) -> Result<MemberChanges, MLSHandshakeError> {
+        match tx {
+            MLSHandshakeAux::CommitProposal { messages, welcome } => {
+                let mut messages = messages
+                    .iter()
+                    .map(|msg| decode_plaintext(msg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let commit = messages.pop().ok_or(MLSHandshakeError::MissingCommit)?;
+                let proposals = messages;
+                let mut adds = 0;
+                for proposal in proposals.iter() {
+                    match &proposal.content.content {
+                        ContentType::Proposal(Proposal::Add(add)) => {
+                            let keypackage = add.key_package.get_encoding();
+                            if !node_keypackages.contains(&keypackage) {
+                                return Err(MLSHandshakeError::AddedNotActiveNode);
+                            }
+                            adds += 1;
+                        }
+                        ContentType::Proposal(Proposal::Remove(_)) => {}
+                        _ => {
+                            return Err(MLSHandshakeError::InvalidContentType);
+                        }
+                    }
+                }
+                match welcome {
+                    None if adds == 0 => {}
+                    Some(welcome) if adds > 0 => {
+                        let welcome = Welcome::read_bytes(welcome)
+                            .ok_or(MLSHandshakeError::InvalidWelcome)?;
+                        if welcome.secrets.len() != adds {
+                            return Err(MLSHandshakeError::InvalidWelcome);
+                        }
+                    }
+                    _ => {
+                        return Err(MLSHandshakeError::InvalidWelcome);
+                    }
+                }
+                self.process_commit(&commit, &proposals, block_time, ra_verifier)
+            }
+            MLSHandshakeAux::SelfUpdateProposal { proposal, commit } => {
+                let proposal = decode_plaintext(proposal)?;
+                let commit = decode_plaintext(commit)?;
+                if !matches!(
+                    proposal.content.content,
+                    ContentType::Proposal(Proposal::Update(_))
+                ) {
+                    return Err(MLSHandshakeError::InvalidContentType);
+                }
+                if proposal.content.sender.sender != commit.content.sender.sender {
+                    return Err(MLSHandshakeError::InvalidSelfUpdate);
+                }
+                self.process_commit(&commit, &[proposal], block_time, ra_verifier)
+            }
+            // the nack content (the DLEQ proof) isn't specified yet,
+            // so it can't be verified and is rejected
+            MLSHandshakeAux::MsgNack(_) => Err(MLSHandshakeError::NackNotSupported),
+        }
+    }
+
+    fn process_commit(
+        &mut self,
+        commit: &MLSPlaintext,
+        proposals: &[MLSPlaintext],
+        block_time: Timespec,
+        ra_verifier: &impl AttestedCertVerifier,
+    ) -> Result<MemberChanges, MLSHandshakeError> {
+        if !matches!(commit.content.content, ContentType::Commit { .. }) {
+            return Err(MLSHandshakeError::InvalidContentType);
+        }
+        // the state is only updated if everything is valid
+        let mut group = self.0.clone();
+        let content = group.process_commit(commit, proposals, ra_verifier, block_time)?;
+        let committed = content.additions.len() + content.updates.len() + content.removes.len();
+        if committed != proposals.len() {
+            return Err(MLSHandshakeError::ProposalNotCommitted);
+        }
+        // the members are looked up in the state before the commit (where they were verified)
+        let member = |leaf: u32| {
+            self.0
+                .get_member(LeafSize(leaf))
+                .map(Codec::get_encoding)
+                .expect("verified group member")
+        };
+        let sender = commit.content.sender.sender;
+        let committer = member(sender);
+        // the committer's leaf ends up with the keypackage of its path (if any)
+        let path = content.commit.path.as_ref();
+        let mut updated = content
+            .updates
+            .iter()
+            .filter(|(leaf, _)| leaf.0 != sender || path.is_none())
+            .map(|(leaf, update)| (member(leaf.0), update.key_package.get_encoding()))
+            .collect::<Vec<_>>();
+        if let Some(path) = path {
+            updated.push((committer.clone(), path.leaf_key_package.get_encoding()));
+        }
+        let removed = content
+            .removes
+            .iter()
+            .map(|remove| member(remove.removed))
+            .collect();
+        self.0 = group;
+        Ok(MemberChanges {
+            committer,
+            updated,
+            removed,
+        })
+    }
+}
+
+fn decode_plaintext(msg: &[u8]) -> Result<MLSPlaintext, MLSHandshakeError> {
+    MLSPlaintext::read_bytes(msg).ok_or(MLSHandshakeError::MsgDecodeError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use mls::credential::Credential;
+    use mls::extensions::{self as ext, MLSExtension};
+    use mls::group::GroupAux;
+    use mls::key::{HPKEPrivateKey, IdentityPrivateKey};
+    use mls::keypackage::{
+        KeyPackagePayload, MLS10_128_DHKEMP256_AES128GCM_SHA256_P256, PROTOCOL_VERSION_MLS10,
+    };
+    use mls::KeyPackageSecret;
+    use ra_client::{CertVerifyResult, EnclaveCertVerifierError, ENCLAVE_CERT_VERIFIER};
+
+    /// accepts the fake keypackages (their credential is the raw public key)
+    struct MockVerifier;
+
+    impl AttestedCertVerifier for MockVerifier {
+        fn verify_attested_cert(
+            &self,
+            certificate: &[u8],
+            _now: DateTime<Utc>,
+        ) -> Result<CertVerifyResult, EnclaveCertVerifierError> {
+            static VECTOR: &[u8] =
+                include_bytes!("../../chain-tx-enclave-next/mls/tests/test_vectors/keypackage.bin");
+            let kp = KeyPackage::read_bytes(VECTOR).expect("decode");
+            let quote = kp
+                .verify(&*ENCLAVE_CERT_VERIFIER, 1590490084)
+                .unwrap()
+                .quote;
+            Ok(CertVerifyResult {
+                public_key: certificate.to_vec(),
+                quote,
+            })
+        }
+    }
+
+    fn fake_keypackage() -> (KeyPackage, KeyPackageSecret) {
+        let keypair = ring::signature::EcdsaKeyPair::generate_pkcs8(
+            &ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+            &ring::rand::SystemRandom::new(),
+        )
+        .unwrap();
+        let private_key =
+            IdentityPrivateKey::from_pkcs8(keypair.as_ref()).expect("invalid private key");
+        let (init_private_key, init_key) = HPKEPrivateKey::generate();
+        let payload = KeyPackagePayload {
+            version: PROTOCOL_VERSION_MLS10,
+            cipher_suite: MLS10_128_DHKEMP256_AES128GCM_SHA256_P256,
+            init_key,
+            credential: Credential::X509(private_key.public_key_raw().to_vec()),
+            extensions: vec![
+                ext::SupportedVersionsExt(vec![PROTOCOL_VERSION_MLS10]).entry(),
+                ext::SupportedCipherSuitesExt(vec![MLS10_128_DHKEMP256_AES128GCM_SHA256_P256])
+                    .entry(),
+                ext::LifeTimeExt::new(0, 100).entry(),
+            ],
+        };
+        let signature = private_key.sign(&payload.get_encoding());
+        (
+            KeyPackage { payload, signature },
+            KeyPackageSecret {
+                credential_private_key: private_key,
+                init_private_key,
+            },
+        )
+    }
+
+    fn commit_proposal(
+        proposals: &[MLSPlaintext],
+        commit: &MLSPlaintext,
+        welcome: Option<&Welcome>,
+    ) -> MLSHandshakeAux {
+        let mut messages = proposals
+            .iter()
+            .map(Codec::get_encoding)
+            .collect::<Vec<_>>();
+        messages.push(commit.get_encoding());
+        MLSHandshakeAux::CommitProposal {
+            messages,
+            welcome: welcome.map(Codec::get_encoding),
+        }
+    }
+
+    #[test]
+    fn check_add_commit_and_self_update() {
+        let (creator, creator_secret) = fake_keypackage();
+        let (member, member_secret) = fake_keypackage();
+        let mut state = TdbeGroupState(GroupPublicView::init(creator.clone()));
+        let genesis_hash = state.hash();
+
+        // genesis commit adding the other node
+        let (mut creator_group, adds, commit, welcome) =
+            GroupAux::init_group(creator, creator_secret, &[member.clone()], &MockVerifier, 0)
+                .expect("group init");
+        let tx = commit_proposal(&adds, &commit, Some(&welcome));
+        let node_keypackages = vec![member.get_encoding()];
+
+        // only active nodes can be added
+        assert!(matches!(
+            state.clone().process_handshake(&tx, 0, &[], &MockVerifier),
+            Err(MLSHandshakeError::AddedNotActiveNode)
+        ));
+        // the welcome is required for the added node
+        let no_welcome = commit_proposal(&adds, &commit, None);
+        assert!(matches!(
+            state
+                .clone()
+                .process_handshake(&no_welcome, 0, &node_keypackages, &MockVerifier),
+            Err(MLSHandshakeError::InvalidWelcome)
+        ));
+
+        state
+            .process_handshake(&tx, 0, &node_keypackages, &MockVerifier)
+            .expect("add commit");
+        assert_eq!(state.epoch(), 1);
+        assert_eq!(state.0.find_member(&member), Some(LeafSize(1)));
+        assert_ne!(state.hash(), genesis_hash);
+
+        // replayed commit is rejected (and the state isn't changed)
+        let hash = state.hash();
+        assert!(state
+            .process_handshake(&tx, 0, &node_keypackages, &MockVerifier)
+            .is_err());
+        assert_eq!(state.hash(), hash);
+
+        creator_group
+            .process_commit(commit, &adds, &MockVerifier, 0)
+            .expect("commit ok");
+        let mut member_group =
+            GroupAux::init_group_from_welcome(member, member_secret, welcome, &MockVerifier, 0)
+                .expect("group init from welcome");
+        assert_eq!(state.0.context, member_group.context);
+
+        // self update of the added node
+        let (updated, updated_secret) = fake_keypackage();
+        let proposal = member_group.get_signed_self_update(updated.clone(), updated_secret);
+        let (commit, _welcome) = member_group.commit_proposals(&[proposal.clone()]);
+        let tx = MLSHandshakeAux::SelfUpdateProposal {
+            proposal: proposal.get_encoding(),
+            commit: commit.get_encoding(),
+        };
+        let changes = state
+            .process_handshake(&tx, 0, &node_keypackages, &MockVerifier)
+            .expect("self update commit");
+        assert_eq!(changes.committer, member.get_encoding());
+        let current = state.0.get_member(LeafSize(1)).unwrap().get_encoding();
+        assert_eq!(changes.updated, vec![(member.get_encoding(), current)]);
+        assert!(changes.removed.is_empty());
+        member_group
+            .process_commit(commit, &[proposal], &MockVerifier, 0)
+            .expect("commit ok");
+        assert_eq!(state.epoch(), 2);
+        assert_eq!(state.0.context, member_group.context);
+
+        // the state survives serialization
+        let decoded = TdbeGroupState::decode(&mut state.encode().as_slice()).expect("decode");
+        assert_eq!(decoded.hash(), state.hash());
+    }
+
+    #[test]
+    fn check_remove_commit_reports_removed_member() {
+        let (creator, creator_secret) = fake_keypackage();
+        let (member, _) = fake_keypackage();
+        let mut state = TdbeGroupState(GroupPublicView::init(creator.clone()));
+        let (mut creator_group, adds, commit, welcome) =
+            GroupAux::init_group(creator, creator_secret, &[member.clone()], &MockVerifier, 0)
+                .expect("group init");
+        let tx = commit_proposal(&adds, &commit, Some(&welcome));
+        state
+            .process_handshake(&tx, 0, &[member.get_encoding()], &MockVerifier)
+            .expect("add commit");
+        creator_group
+            .process_commit(commit, &adds, &MockVerifier, 0)
+            .expect("commit ok");
+
+        // the creator removes the member
+        let committer = state.0.get_member(LeafSize(0)).unwrap().get_encoding();
+        let remove = creator_group.get_signed_remove(LeafSize(1));
+        let (commit, _welcome) = creator_group.commit_proposals(&[remove.clone()]);
+        let tx = commit_proposal(&[remove], &commit, None);
+        let changes = state
+            .process_handshake(&tx, 0, &[], &MockVerifier)
+            .expect("remove commit");
+        assert_eq!(changes.committer, committer);
+        assert_eq!(changes.removed, vec![member.get_encoding()]);
+        assert_eq!(state.0.find_member(&member), None);
+        assert_eq!(state.epoch(), 2);
+    }
+
+    #[test]
+    fn check_self_update_by_other_member_is_rejected() {
+        let (creator, creator_secret) = fake_keypackage();
+        let (member, _) = fake_keypackage();
+        let mut state = TdbeGroupState(GroupPublicView::init(creator.clone()));
+        let (mut creator_group, adds, commit, welcome) =
+            GroupAux::init_group(creator, creator_secret, &[member.clone()], &MockVerifier, 0)
+                .expect("group init");
+        let tx = commit_proposal(&adds, &commit, Some(&welcome));
+        state
+            .process_handshake(&tx, 0, &[member.get_encoding()], &MockVerifier)
+            .expect("add commit");
+        creator_group
+            .process_commit(commit, &adds, &MockVerifier, 0)
+            .expect("commit ok");
+
+        // the creator commits an update of the member's leaf
+        let (updated, updated_secret) = fake_keypackage();
+        let mut proposal = creator_group.get_signed_self_update(updated, updated_secret);
+        let (commit, _welcome) = creator_group.commit_proposals(&[proposal.clone()]);
+        proposal.content.sender.sender = 1;
+        let tx = MLSHandshakeAux::SelfUpdateProposal {
+            proposal: proposal.get_encoding(),
+            commit: commit.get_encoding(),
+        };
+        assert!(matches!(
+            state.process_handshake(&tx, 0, &[], &MockVerifier),
+            Err(MLSHandshakeError::InvalidSelfUpdate)
+        ));
+        assert_eq!(state.epoch(), 1);
+    }
+
+    #[test]
+    fn check_nack_is_rejected() {
+        let (creator, _) = fake_keypackage();
+        let mut state = TdbeGroupState(GroupPublicView::init(creator));
+        let hash = state.hash();
+        assert!(matches!(
+            state.process_handshake(&MLSHandshakeAux::MsgNack(vec![]), 0, &[], &MockVerifier),
+            Err(MLSHandshakeError::NackNotSupported)
+        ));
+        assert_eq!(state.hash(), hash);
+    }
+}
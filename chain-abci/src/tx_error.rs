@@ -1,4 +1,5 @@
 use chain_core::init::coin::{Coin, CoinError};
+use mls::group::ProcessCommitError;
 use mls::keypackage;
 
 #[derive(thiserror::Error, Debug)]
@@ -9,8 +10,8 @@ pub enum TxError {
     Enclave(#[from] chain_tx_validation::Error),
     #[error("public tx process failed: {0}")]
     Public(#[from] PublicTxError),
-    #[error("FIXME/WIP payload for MLS handshake (not yet supported)")]
-    WIPMLSData,
+    #[error("MLS handshake process failed: {0}")]
+    MLSHandshake(#[from] MLSHandshakeError),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -31,6 +32,14 @@ pub enum PublicTxError {
     NodeJoin(#[from] NodeJoinError),
     #[error("unbond tx process failed: {0}")]
     Unbond(#[from] UnbondError),
+    #[error("governance tx process failed: {0}")]
+    Governance(#[from] GovernanceError),
+    #[error("delegation tx process failed: {0}")]
+    Delegate(#[from] DelegateError),
+    #[error("node metadata update tx process failed: {0}")]
+    NodeUpdate(#[from] NodeUpdateError),
+    #[error("node leave tx process failed: {0}")]
+    NodeLeave(#[from] NodeLeaveError),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -43,7 +52,7 @@ pub enum UnjailError {
 
 #[derive(thiserror::Error, Debug)]
 pub enum NodeJoinError {
-    #[error("bonded coins not enough to become validator")]
+    #[error("bonded coins not enough to join as the requested node type")]
     BondedNotEnough,
     #[error("validator address already exists")]
     DuplicateValidatorAddress,
@@ -57,8 +66,36 @@ pub enum NodeJoinError {
     KeyPackageDecodeError,
     #[error("invalid key package: {0}")]
     KeyPackageVerifyError(#[from] keypackage::Error),
-    #[error("FIXME: WIP -- community node not yet supported")]
-    WIPNotValidator,
+    #[error("the staking address is already joined as a different node type")]
+    NodeTypeMismatch,
+    #[error("the staking address delegates to a council node")]
+    IsDelegator,
+    #[error("the commission rate is over 1.0")]
+    InvalidCommissionRate,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum NodeUpdateError {
+    #[error("the staking address is not a council node")]
+    NotCouncilNode,
+    #[error("the staking address is jailed")]
+    IsJailed,
+    #[error("validator address already exists")]
+    DuplicateValidatorAddress,
+    #[error("the used_validator_addresses queue is full")]
+    UsedValidatorAddrFull,
+    #[error("the commission rate is over 1.0")]
+    InvalidCommissionRate,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum NodeLeaveError {
+    #[error("the staking address is not a node")]
+    NotNode,
+    #[error("the staking address is jailed")]
+    IsJailed,
+    #[error("the node is already inactive")]
+    AlreadyInactive,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -71,6 +108,22 @@ pub enum WithdrawError {
     IsJailed,
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum DelegateError {
+    #[error("coin error in delegation tx: {0}")]
+    CoinError(#[from] CoinError),
+    #[error("the value of tx is zero")]
+    ZeroValue,
+    #[error("the staking address is a node, which can't delegate")]
+    IsNode,
+    #[error("the staking address already delegates to another council node")]
+    AlreadyDelegated,
+    #[error("the target staking address is not an active council node")]
+    NotActiveCouncilNode,
+    #[error("the staking address doesn't delegate to any council node")]
+    NotDelegated,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum UnbondError {
     #[error("nonce value don't match")]
@@ -90,3 +143,49 @@ pub enum DepositError {
     #[error("the staking address is jailed")]
     IsJailed,
 }
+
+#[derive(thiserror::Error, Debug)]
+pub enum GovernanceError {
+    #[error("the proposer is not an active council node")]
+    NotActiveCouncilNode,
+    #[error("the proposal doesn't contain any change")]
+    EmptyProposal,
+    #[error("invalid network parameter: {0}")]
+    InvalidParameter(&'static str),
+    #[error("invalid upgrade plan: {0}")]
+    InvalidUpgrade(&'static str),
+    #[error("the activation height is already reached")]
+    ActivationHeightPassed,
+    #[error("the proposal already exists")]
+    DuplicateProposal,
+    #[error("the proposal doesn't exist")]
+    ProposalNotFound,
+    #[error("the staking address is jailed")]
+    IsJailed,
+    #[error("the staking address doesn't have any bonded coins")]
+    NoBondedCoins,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MLSHandshakeError {
+    #[error("MLS group state is not initialized")]
+    GroupNotInitialized,
+    #[error("MLSPlaintext decode failed")]
+    MsgDecodeError,
+    #[error("commit message is missing")]
+    MissingCommit,
+    #[error("unexpected MLSPlaintext content type")]
+    InvalidContentType,
+    #[error("welcome is missing or doesn't match the add proposals")]
+    InvalidWelcome,
+    #[error("proposal is not covered by the commit")]
+    ProposalNotCommitted,
+    #[error("added keypackage doesn't belong to any active council or community node")]
+    AddedNotActiveNode,
+    #[error("update proposal is not sent by the committer")]
+    InvalidSelfUpdate,
+    #[error("NACK is not supported until its DLEQ proof can be verified")]
+    NackNotSupported,
+    #[error("process commit failed: {0}")]
+    ProcessCommit(#[from] ProcessCommitError),
+}
@@ -2,8 +2,11 @@ use abci::*;
 use bit_vec::BitVec;
 use chain_abci::app::*;
 use chain_abci::enclave_bridge::mock::MockClient;
+use chain_abci::governance::ProposalTable;
+use chain_abci::peer_filter::PeerFilter;
+use chain_abci::snapshot::{ApplySnapshotChunkResult, OfferSnapshotResult, SnapshotStore};
 use chain_abci::staking::StakingTable;
-use chain_core::common::{MerkleTree, Proof, H256, HASH_SIZE_256};
+use chain_core::common::{MerkleTree, Proof, Timespec, H256, HASH_SIZE_256};
 use chain_core::compute_app_hash;
 use chain_core::init::address::RedeemAddress;
 use chain_core::init::coin::Coin;
@@ -13,16 +16,21 @@ use chain_core::init::config::NetworkParameters;
 use chain_core::init::config::{
     JailingParameters, RewardsParameters, SlashRatio, SlashingParameters,
 };
+use chain_core::mls::MLSHandshakeAux;
 use chain_core::state::account::{
-    DepositBondTx, NodeState, StakedState, StakedStateAddress, StakedStateDestination,
-    StakedStateOpAttributes, StakedStateOpWitness, UnbondTx, WithdrawUnbondedTx,
+    CommunityNode, CouncilNodeMeta, DepositBondTx, NodeCommonInfo, NodeMetadata, NodeState,
+    StakedState, StakedStateAddress, StakedStateDestination, StakedStateOpAttributes,
+    StakedStateOpWitness, UnbondTx, Validator, WithdrawUnbondedTx,
 };
+use chain_core::state::governance::UpgradePlan;
 use chain_core::state::tendermint::{
-    BlockHeight, TendermintValidatorAddress, TendermintValidatorPubKey, TendermintVotePower,
+    BlockHeight, TendermintNodeId, TendermintValidatorAddress, TendermintValidatorPubKey,
+    TendermintVotePower,
 };
 use chain_core::state::validator::NodeJoinRequestTx;
 use chain_core::state::{ChainState, RewardsPoolState};
-use chain_core::tx::fee::{LinearFee, Milli};
+use chain_core::tx::fee::{BlockFullness, FeePolicy, LinearFee, Milli};
+use chain_core::tx::inclusion::{OutputStatus, TxInclusionProof};
 use chain_core::tx::witness::tree::RawXOnlyPubkey;
 use chain_core::tx::witness::EcdsaSignature;
 use chain_core::tx::{
@@ -37,11 +45,18 @@ use chain_core::tx::{
     witness::{TxInWitness, TxWitness},
     PlainTxAux, TransactionId, TxAux, TxEnclaveAux, TxPublicAux,
 };
-use chain_storage::buffer::Get;
-use chain_storage::jellyfish::SparseMerkleProof;
+use chain_core::AppHashParts;
+use chain_storage::buffer::{flush_storage, BufferStore, Get, KVBuffer};
+use chain_storage::jellyfish::{
+    compute_staking_root, get_spent_output_with_proof, put_staking_blobs, OutputStatusProof,
+    SparseMerkleProof,
+};
+use chain_storage::migration::{CURRENT_SCHEMA_VERSION, INITIAL_SCHEMA_VERSION};
 use chain_storage::{
-    LookupItem, Storage, CHAIN_ID_KEY, COL_EXTRA, COL_NODE_INFO, GENESIS_APP_HASH_KEY,
-    LAST_STATE_KEY, NUM_COLUMNS,
+    get_pruned_height, get_schema_version, get_upgrade_halt, LookupItem, Storage, CHAIN_ID_KEY,
+    COL_APP_STATES, COL_EXTRA, COL_NODE_INFO, COL_STAKING_VERSIONS, COL_TX_META,
+    GENESIS_APP_HASH_KEY, LAST_STATE_KEY, NUM_COLUMNS, PRUNED_HEIGHT_KEY, SCHEMA_VERSION_KEY,
+    UPGRADE_HALT_KEY,
 };
 use chain_tx_filter::BlockFilter;
 use hex::decode;
@@ -93,6 +108,11 @@ fn create_db_with_state_history() -> Arc<dyn KeyValueDB> {
         &get_dummy_app_state(genesis_app_hash).encode(),
     );
     inittx.put(COL_EXTRA, CHAIN_ID_KEY, TEST_CHAIN_ID.as_bytes());
+    inittx.put(
+        COL_EXTRA,
+        SCHEMA_VERSION_KEY,
+        &CURRENT_SCHEMA_VERSION.encode(),
+    );
 
     db.write(inittx).unwrap();
     db
@@ -137,6 +157,322 @@ fn proper_last_state_should_be_restored() {
     assert_eq!(chain_id, TEST_CHAIN_ID.as_bytes());
 }
 
+#[test]
+fn schema_version_should_be_stored() {
+    let db = create_db_with_state_history();
+    let app = ChainNodeApp::new_with_storage(
+        get_enclave_bridge_mock(),
+        EXAMPLE_HASH,
+        TEST_CHAIN_ID,
+        Storage::new_db(db.clone()),
+        None,
+        None,
+    );
+    assert_eq!(
+        get_schema_version(&app.storage),
+        Some(CURRENT_SCHEMA_VERSION)
+    );
+}
+
+#[test]
+#[should_panic(expected = "resync is needed")]
+fn baseline_storage_should_be_rejected() {
+    let db = create_db();
+    let genesis_app_hash = decode(EXAMPLE_HASH).unwrap();
+    let mut inittx = db.transaction();
+    inittx.put(COL_NODE_INFO, GENESIS_APP_HASH_KEY, &genesis_app_hash);
+    inittx.put(
+        COL_NODE_INFO,
+        LAST_STATE_KEY,
+        &get_baseline_app_state([0u8; 32]),
+    );
+    inittx.put(COL_EXTRA, CHAIN_ID_KEY, TEST_CHAIN_ID.as_bytes());
+    db.write(inittx).unwrap();
+    let _app = ChainNodeApp::new_with_storage(
+        get_enclave_bridge_mock(),
+        EXAMPLE_HASH,
+        TEST_CHAIN_ID,
+        Storage::new_db(db.clone()),
+        None,
+        None,
+    );
+}
+
+/// the staked states of a council node with a commission rate and a community node
+/// and their encoding in the layout of the schema versions 1-3
+/// (the commission rate in the council node metadata, no p2p node ID, community node jailing)
+fn get_schema_v1_stakings() -> Vec<(StakedState, Vec<u8>)> {
+    let council_meta = CouncilNodeMeta::new_with_details(
+        "council".to_owned(),
+        Some("security@example.com".to_owned()),
+        TendermintValidatorPubKey::Ed25519([0xcd; 32]),
+        mock_confidential_init(),
+    );
+    let council = StakedState {
+        bonded: Coin::unit(),
+        node_meta: Some(NodeState::CouncilNode(Validator {
+            commission_rate: Milli::try_new(0, 100).unwrap(),
+            ..Validator::new(council_meta.clone())
+        })),
+        ..StakedState::default(StakedStateAddress::BasicRedeem(RedeemAddress::from(
+            [0x01; 20],
+        )))
+    };
+    let mut council_v1 = (
+        council.nonce,
+        council.bonded,
+        council.unbonded,
+        council.unbonded_from,
+        council.address,
+    )
+        .encode();
+    // Some(NodeState::CouncilNode)
+    council_v1.extend(&[1, 0]);
+    council_v1.extend(
+        (
+            &council_meta.node_info.name,
+            &council_meta.node_info.security_contact,
+            &council_meta.consensus_pubkey,
+            &council_meta.node_info.confidential_init.keypackage,
+            Milli::try_new(0, 100).unwrap(),
+        )
+            .encode(),
+    );
+    // jailed until, inactive time, inactive block, used validator addresses
+    council_v1.extend(
+        (
+            Option::<Timespec>::None,
+            Option::<Timespec>::None,
+            Option::<BlockHeight>::None,
+            Vec::<(TendermintValidatorAddress, Timespec)>::new(),
+        )
+            .encode(),
+    );
+    council_v1.extend(
+        (
+            &council.last_slash,
+            council.delegated_to,
+            &council.delegators,
+        )
+            .encode(),
+    );
+
+    let community_info = NodeCommonInfo {
+        name: "community".to_owned(),
+        security_contact: None,
+        confidential_init: mock_confidential_init(),
+        p2p_node_id: None,
+    };
+    let community = StakedState {
+        bonded: Coin::unit(),
+        node_meta: Some(NodeState::CommunityNode(CommunityNode::new(
+            community_info.clone(),
+        ))),
+        ..StakedState::default(StakedStateAddress::BasicRedeem(RedeemAddress::from(
+            [0x02; 20],
+        )))
+    };
+    let mut community_v1 = (
+        community.nonce,
+        community.bonded,
+        community.unbonded,
+        community.unbonded_from,
+        community.address,
+    )
+        .encode();
+    // Some(NodeState::CommunityNode)
+    community_v1.extend(&[1, 1]);
+    community_v1.extend(
+        (
+            &community_info.name,
+            &community_info.security_contact,
+            &community_info.confidential_init.keypackage,
+        )
+            .encode(),
+    );
+    // jailed until, inactive time, inactive block
+    community_v1.extend(
+        (
+            Option::<Timespec>::None,
+            Option::<Timespec>::None,
+            Option::<BlockHeight>::None,
+        )
+            .encode(),
+    );
+    community_v1.extend(
+        (
+            &community.last_slash,
+            community.delegated_to,
+            &community.delegators,
+        )
+            .encode(),
+    );
+    vec![(council, council_v1), (community, community_v1)]
+}
+
+#[test]
+fn schema_v1_storage_should_be_migrated() {
+    let db = create_db_with_state_history();
+    let mut storage = Storage::new_db(db.clone());
+    let stakings = get_schema_v1_stakings();
+    let mut buffer = KVBuffer::new();
+    let v1_account_root = put_staking_blobs(
+        &mut BufferStore::new(&storage, &mut buffer),
+        0,
+        stakings
+            .iter()
+            .map(|(staking, blob)| (staking.key(), blob.clone())),
+    )
+    .unwrap();
+    flush_storage(&mut storage, buffer).unwrap();
+
+    let historical_height = BlockHeight::new(1);
+    let mut state = get_dummy_app_state([0u8; 32]);
+    state.last_block_height = historical_height;
+    state.block_height = historical_height;
+    state.top_level.account_root = v1_account_root;
+    // the top level state had no TDBE group and proposals hashes (2 * 32 bytes),
+    // no app version (u64) and no spent outputs root (32 bytes)
+    // and the node state had no upgrade plan (1 byte for None)
+    let mut v1_state = state.encode();
+    v1_state.truncate(v1_state.len() - 105);
+    let mut v1_historical_state = state.top_level.encode();
+    v1_historical_state.truncate(v1_historical_state.len() - 104);
+    // the second output of a transaction was spent
+    let spent_txin = TxoPointer::new([9u8; 32], 1);
+    let mut inittx = db.transaction();
+    inittx.put(COL_NODE_INFO, LAST_STATE_KEY, &v1_state);
+    inittx.put(
+        COL_APP_STATES,
+        &historical_height.encode(),
+        &v1_historical_state,
+    );
+    inittx.put(
+        COL_STAKING_VERSIONS,
+        &historical_height.encode(),
+        &0u64.encode(),
+    );
+    inittx.put(COL_TX_META, &spent_txin.id, &[0b0100_0000]);
+    inittx.put(
+        COL_EXTRA,
+        SCHEMA_VERSION_KEY,
+        &INITIAL_SCHEMA_VERSION.encode(),
+    );
+    db.write(inittx).unwrap();
+
+    let app = ChainNodeApp::new_with_storage(
+        get_enclave_bridge_mock(),
+        EXAMPLE_HASH,
+        TEST_CHAIN_ID,
+        Storage::new_db(db.clone()),
+        Some("localhost:3443".to_owned()),
+        None,
+    );
+    assert_eq!(
+        get_schema_version(&app.storage),
+        Some(CURRENT_SCHEMA_VERSION)
+    );
+    let state = app.last_state.as_ref().unwrap();
+    assert_eq!(state.top_level.app_version, 1);
+    assert_eq!(state.upgrade_plan, None);
+    assert_eq!(state.top_level.tdbe_group_hash, [0u8; 32]);
+    assert_eq!(
+        state.top_level.proposals_hash,
+        ProposalTable::default().hash()
+    );
+
+    // the staked states are re-encoded into a new staking version (by the steps from 3 and 4)
+    let expected = stakings
+        .into_iter()
+        .map(|(staking, _)| staking)
+        .collect::<Vec<_>>();
+    assert_eq!(state.staking_version, 2);
+    assert_ne!(state.top_level.account_root, v1_account_root);
+    assert_eq!(
+        state.top_level.account_root,
+        compute_staking_root(&expected)
+    );
+    for staking in expected.iter() {
+        assert_eq!(
+            app.staking_getter_committed().get(&staking.address),
+            Some(staking.clone())
+        );
+    }
+
+    // the spent outputs are put into the merkle trie at the last height (by the step from 5)
+    assert_ne!(state.top_level.spent_outputs_root, [0u8; 32]);
+    let (spent, proof) =
+        get_spent_output_with_proof(&app.storage, historical_height, &spent_txin).unwrap();
+    assert!(spent);
+    assert!(proof
+        .verify_spent_output(state.top_level.spent_outputs_root, &spent_txin, true)
+        .is_ok());
+
+    // the historical states are migrated, their staking versions are dropped
+    let historical_state = ChainState::decode(
+        &mut app
+            .storage
+            .get_historical_state(historical_height)
+            .unwrap()
+            .as_slice(),
+    )
+    .unwrap();
+    assert_eq!(historical_state.app_version, 1);
+    assert_eq!(historical_state.account_root, v1_account_root);
+    assert_eq!(historical_state.spent_outputs_root, [0u8; 32]);
+    assert_eq!(
+        app.storage
+            .get_historical_staking_version(historical_height),
+        None
+    );
+    assert_eq!(get_pruned_height(&app.storage), Some(BlockHeight::new(2)));
+}
+
+#[test]
+#[should_panic]
+fn schema_v1_state_in_other_layout_should_panic() {
+    let db = create_db_with_state_history();
+    // already has the app version and the upgrade plan
+    let state = get_dummy_app_state([0u8; 32]).encode();
+    let mut inittx = db.transaction();
+    inittx.put(COL_NODE_INFO, LAST_STATE_KEY, &state);
+    inittx.put(
+        COL_EXTRA,
+        SCHEMA_VERSION_KEY,
+        &INITIAL_SCHEMA_VERSION.encode(),
+    );
+    db.write(inittx).unwrap();
+    let _app = ChainNodeApp::new_with_storage(
+        get_enclave_bridge_mock(),
+        EXAMPLE_HASH,
+        TEST_CHAIN_ID,
+        Storage::new_db(db.clone()),
+        None,
+        None,
+    );
+}
+
+#[test]
+#[should_panic]
+fn newer_schema_version_should_panic() {
+    let db = create_db_with_state_history();
+    let mut inittx = db.transaction();
+    inittx.put(
+        COL_EXTRA,
+        SCHEMA_VERSION_KEY,
+        &(CURRENT_SCHEMA_VERSION + 1).encode(),
+    );
+    db.write(inittx).unwrap();
+    let _app = ChainNodeApp::new_with_storage(
+        get_enclave_bridge_mock(),
+        EXAMPLE_HASH,
+        TEST_CHAIN_ID,
+        Storage::new_db(db.clone()),
+        None,
+        None,
+    );
+}
+
 #[test]
 #[should_panic]
 fn too_long_hash_should_panic() {
@@ -184,11 +520,12 @@ fn nonhex_hash_should_panic() {
 
 fn get_dummy_network_params() -> NetworkParameters {
     NetworkParameters::Genesis(InitNetworkParameters {
-        initial_fee_policy: LinearFee::new(
+        initial_fee_policy: FeePolicy::Linear(LinearFee::new(
             Milli::try_new(1, 1).unwrap(),
             Milli::try_new(1, 1).unwrap(),
-        ),
+        )),
         required_council_node_stake: Coin::unit(),
+        required_community_node_stake: Coin::unit(),
         jailing_config: JailingParameters {
             block_signing_window: 100,
             missed_block_threshold: 50,
@@ -203,6 +540,9 @@ fn get_dummy_network_params() -> NetworkParameters {
             monetary_expansion_r0: "0.5".parse().unwrap(),
             monetary_expansion_tau: 166_666_600,
             monetary_expansion_decay: 999_860,
+            fee_burn_ratio: Milli::default(),
+            fee_treasury_ratio: Milli::default(),
+            treasury_address: None,
         },
         max_validators: 2,
     })
@@ -221,14 +561,93 @@ fn get_dummy_app_state(app_hash: H256) -> ChainNodeState {
         staking_version: 0,
         utxo_coins: Coin::zero(),
         enclave_isv_svn: 0,
+        tdbe_group: None,
+        proposals: ProposalTable::default(),
         top_level: ChainState {
             account_root: [0u8; 32],
             rewards_pool: RewardsPoolState::new(0, params.get_rewards_monetary_expansion_tau()),
             network_params: params,
+            block_fullness: BlockFullness::default(),
+            tdbe_group_hash: [0u8; 32],
+            proposals_hash: [0u8; 32],
+            app_version: chain_core::APP_VERSION,
+            spent_outputs_root: [0u8; 32],
         },
+        upgrade_plan: None,
     }
 }
 
+/// the node state in the layout of the storages created before the schema version was recorded:
+/// no TDBE group, proposals, app version and upgrade plan in the node state,
+/// no fee counters in the rewards pool, no block fullness or node state hashes in the top level,
+/// and only the linear fee, no community node stake and no fee split in the network parameters
+fn get_baseline_app_state(app_hash: H256) -> Vec<u8> {
+    let state = get_dummy_app_state(app_hash);
+    let params = match &state.top_level.network_params {
+        NetworkParameters::Genesis(params) => params.clone(),
+        _ => unreachable!("genesis parameters"),
+    };
+    let fee = match &params.initial_fee_policy {
+        FeePolicy::Linear(fee) => fee.clone(),
+        _ => unreachable!("linear fee"),
+    };
+    let pool = &state.top_level.rewards_pool;
+    let rewards = &params.rewards_config;
+    let mut encoded = (
+        state.last_block_height,
+        state.last_apphash,
+        state.block_time,
+        state.block_height,
+    )
+        .encode();
+    // staking table: chosen validators, liveness trackers and participation stats (all empty)
+    encoded.extend(&[0u8; 3]);
+    encoded.extend(
+        (
+            state.genesis_time,
+            state.max_evidence_age,
+            state.staking_version,
+            state.utxo_coins,
+            state.enclave_isv_svn,
+        )
+            .encode(),
+    );
+    encoded.extend(
+        (
+            state.top_level.account_root,
+            pool.period_bonus,
+            pool.last_block_height,
+            pool.last_distribution_time,
+            pool.minted,
+            pool.tau,
+        )
+            .encode(),
+    );
+    // NetworkParameters::Genesis
+    encoded.push(0);
+    encoded.extend(
+        (
+            &fee,
+            &params.required_council_node_stake,
+            &params.jailing_config,
+            &params.slashing_config,
+        )
+            .encode(),
+    );
+    encoded.extend(
+        (
+            rewards.monetary_expansion_cap,
+            rewards.reward_period_seconds,
+            rewards.monetary_expansion_r0,
+            rewards.monetary_expansion_tau,
+            rewards.monetary_expansion_decay,
+            params.max_validators,
+        )
+            .encode(),
+    );
+    encoded
+}
+
 #[test]
 #[should_panic]
 fn previously_stored_hash_should_match() {
@@ -245,6 +664,14 @@ fn previously_stored_hash_should_match() {
 }
 
 fn init_chain_for(address: RedeemAddress) -> ChainNodeApp<MockClient> {
+    init_chain_with_version(address, chain_core::APP_VERSION, None)
+}
+
+fn init_chain_with_version(
+    address: RedeemAddress,
+    app_version: u64,
+    upgrade_plan: Option<UpgradePlan>,
+) -> ChainNodeApp<MockClient> {
     let db = create_db();
     let total = (Coin::max() - Coin::unit()).unwrap();
     let validator_addr = "0x0e7c045110b8dbf29765047380898919c5cb56f4"
@@ -264,7 +691,10 @@ fn init_chain_for(address: RedeemAddress) -> ChainNodeApp<MockClient> {
     .iter()
     .cloned()
     .collect();
-    let NetworkParameters::Genesis(params) = get_dummy_network_params();
+    let params = match get_dummy_network_params() {
+        NetworkParameters::Genesis(params) => params,
+        _ => unreachable!("dummy network parameters are genesis ones"),
+    };
     let mut nodes = BTreeMap::new();
     let pub_key =
         TendermintValidatorPubKey::from_base64(b"MDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDA=")
@@ -286,7 +716,9 @@ fn init_chain_for(address: RedeemAddress) -> ChainNodeApp<MockClient> {
         ..Default::default()
     };
     nodes.insert(validator_addr, node_pubkey);
-    let c = InitConfig::new(distribution, params, nodes);
+    let mut c = InitConfig::new(distribution, params, nodes);
+    c.app_version = app_version;
+    c.upgrade_plan = upgrade_plan;
     let t = ::protobuf::well_known_types::Timestamp {
         seconds: DEFAULT_GENESIS_TIME as i64,
         ..Default::default()
@@ -298,9 +730,12 @@ fn init_chain_for(address: RedeemAddress) -> ChainNodeApp<MockClient> {
         let new_account_root = storage.put_stakings(0, &genesis_state.accounts);
         let genesis_app_hash = compute_app_hash(
             &tx_tree,
-            &new_account_root,
-            &genesis_state.rewards_pool,
-            &get_dummy_network_params(),
+            &ChainState::genesis(
+                new_account_root,
+                genesis_state.rewards_pool,
+                get_dummy_network_params(),
+                genesis_state.app_version,
+            ),
         );
 
         let example_hash = hex::encode_upper(genesis_app_hash);
@@ -367,11 +802,12 @@ fn init_chain_panics_with_different_app_hash() {
     .cloned()
     .collect();
     let params = InitNetworkParameters {
-        initial_fee_policy: LinearFee::new(
+        initial_fee_policy: FeePolicy::Linear(LinearFee::new(
             Milli::try_new(1, 1).unwrap(),
             Milli::try_new(1, 1).unwrap(),
-        ),
+        )),
         required_council_node_stake: Coin::unit(),
+        required_community_node_stake: Coin::unit(),
         jailing_config: JailingParameters {
             block_signing_window: 100,
             missed_block_threshold: 50,
@@ -386,6 +822,9 @@ fn init_chain_panics_with_different_app_hash() {
             monetary_expansion_r0: "0.5".parse().unwrap(),
             monetary_expansion_tau: 166_666_600,
             monetary_expansion_decay: 999_860,
+            fee_burn_ratio: Milli::default(),
+            fee_treasury_ratio: Milli::default(),
+            treasury_address: None,
         },
         max_validators: 1,
     };
@@ -407,6 +846,80 @@ fn init_chain_panics_with_different_app_hash() {
     app.init_chain(&req);
 }
 
+fn consensus_params_with_max_age(seconds: i64, nanos: i32) -> ConsensusParams {
+    ConsensusParams {
+        evidence: Some(EvidenceParams {
+            max_age_num_blocks: 100_000,
+            max_age_duration: Some(::protobuf::well_known_types::Duration {
+                seconds,
+                nanos,
+                ..Default::default()
+            })
+            .into(),
+            ..Default::default()
+        })
+        .into(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn consensus_params_should_be_checked() {
+    let params = get_dummy_network_params();
+    let cp = consensus_params_with_max_age(172_800, 0);
+    let accepted = check_consensus_params(&cp, &[], &params).unwrap();
+    assert_eq!(accepted.max_evidence_age, 172_800);
+    assert_eq!(accepted.max_evidence_age_blocks, 100_000);
+    assert_eq!(accepted.validator_pub_key_types, vec!["ed25519".to_owned()]);
+
+    assert!(check_consensus_params(&ConsensusParams::default(), &[], &params).is_err());
+    assert!(check_consensus_params(&consensus_params_with_max_age(0, 0), &[], &params).is_err());
+    assert!(
+        check_consensus_params(&consensus_params_with_max_age(172_800, 5), &[], &params).is_err()
+    );
+
+    let mut cp = consensus_params_with_max_age(172_800, 0);
+    cp.set_validator(ValidatorParams {
+        pub_key_types: vec!["secp256k1".to_owned()].into(),
+        ..Default::default()
+    });
+    assert!(check_consensus_params(&cp, &[], &params).is_err());
+    cp.mut_validator()
+        .mut_pub_key_types()
+        .push("ed25519".to_owned());
+    assert!(check_consensus_params(&cp, &[], &params).is_ok());
+
+    // evidence expires before the end of the block signing window (100 blocks)
+    let mut short_evidence = cp.clone();
+    short_evidence.mut_evidence().set_max_age_num_blocks(99);
+    assert!(check_consensus_params(&short_evidence, &[], &params).is_err());
+    short_evidence.mut_evidence().set_max_age_num_blocks(100);
+    assert!(check_consensus_params(&short_evidence, &[], &params).is_ok());
+
+    cp.set_block(BlockParams {
+        max_bytes: 0,
+        ..Default::default()
+    });
+    assert!(check_consensus_params(&cp, &[], &params).is_err());
+}
+
+#[test]
+fn state_query_should_return_consensus_params() {
+    let addr = "fe7c045110b8dbf29765047380898919c5cb56f9";
+    let mut app = init_chain_for(addr.parse().unwrap());
+    let mut qreq = RequestQuery::new();
+    qreq.path = "state".into();
+    qreq.data = b"consensus_params".to_vec();
+    let qresp = app.query(&qreq);
+    assert_eq!(qresp.code, 0);
+    let params: GenesisConsensusParams = serde_json::from_slice(&qresp.value).unwrap();
+    assert_eq!(params.max_evidence_age, 172_800);
+    assert_eq!(
+        params.max_evidence_age,
+        app.last_state.as_ref().unwrap().get_unbonding_period()
+    );
+}
+
 #[test]
 #[should_panic]
 fn init_chain_panics_with_empty_app_bytes() {
@@ -450,6 +963,48 @@ fn check_tx_should_reject_invalid_tx() {
     assert_ne!(0, cresp.code);
 }
 
+#[test]
+fn check_tx_should_reject_invalid_mls_handshake() {
+    let mut app = init_chain_for(
+        "0xfe7c045110b8dbf29765047380898919c5cb56f9"
+            .parse()
+            .unwrap(),
+    );
+    let group = app
+        .last_state
+        .as_ref()
+        .unwrap()
+        .tdbe_group
+        .as_ref()
+        .expect("MLS group should be initialized in genesis");
+    assert_eq!(0, group.epoch());
+
+    let mut creq = RequestCheckTx::default();
+    let tx = TxAux::MLSHandshake(MLSHandshakeAux::CommitProposal {
+        messages: vec![vec![0; 10]],
+        welcome: None,
+    });
+    creq.set_tx(tx.encode());
+    let cresp = app.check_tx(&creq);
+    assert_ne!(0, cresp.code);
+    assert!(cresp.log.contains("MLSPlaintext decode failed"));
+
+    let tx = TxAux::MLSHandshake(MLSHandshakeAux::CommitProposal {
+        messages: vec![],
+        welcome: None,
+    });
+    creq.set_tx(tx.encode());
+    let cresp = app.check_tx(&creq);
+    assert_ne!(0, cresp.code);
+    assert!(cresp.log.contains("commit message is missing"));
+
+    let tx = TxAux::MLSHandshake(MLSHandshakeAux::MsgNack(vec![0; 10]));
+    creq.set_tx(tx.encode());
+    let cresp = app.check_tx(&creq);
+    assert_ne!(0, cresp.code);
+    assert!(cresp.log.contains("NACK is not supported"));
+}
+
 fn prepare_app_valid_tx() -> (ChainNodeApp<MockClient>, TxAux, WithdrawUnbondedTx) {
     let secp = Secp256k1::new();
     let secret_key = SecretKey::from_slice(&[0xcd; 32]).expect("32 bytes, within curve order");
@@ -533,8 +1088,13 @@ fn get_block_proposer(app: &ChainNodeApp<MockClient>) -> TendermintValidatorAddr
 }
 
 fn begin_block(app: &mut ChainNodeApp<MockClient>) {
+    begin_block_at(app, 0);
+}
+
+fn begin_block_at(app: &mut ChainNodeApp<MockClient>, height: i64) {
     let mut bbreq = RequestBeginBlock::default();
     let mut header = Header::default();
+    header.set_height(height);
     header.set_time(::protobuf::well_known_types::Timestamp {
         seconds: DEFAULT_GENESIS_TIME as i64,
         ..Default::default()
@@ -544,6 +1104,145 @@ fn begin_block(app: &mut ChainNodeApp<MockClient>) {
     app.begin_block(&bbreq);
 }
 
+#[test]
+fn scheduled_upgrade_should_halt_unsupported_binary() {
+    let mut app = init_chain_for(
+        "0xfe7c045110b8dbf29765047380898919c5cb56f9"
+            .parse()
+            .unwrap(),
+    );
+    let plan = UpgradePlan {
+        name: "next".to_owned(),
+        height: BlockHeight::new(2),
+        app_version: chain_core::APP_VERSION + 1,
+    };
+    app.last_state.as_mut().unwrap().upgrade_plan = Some(plan.clone());
+    let halted = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        begin_block_at(&mut app, 2);
+    }));
+    assert!(halted.is_err());
+    assert_eq!(get_upgrade_halt(&app.storage), Some(plan));
+}
+
+#[test]
+#[should_panic]
+fn restart_after_upgrade_halt_should_panic() {
+    let db = create_db_with_state_history();
+    let plan = UpgradePlan {
+        name: "next".to_owned(),
+        height: BlockHeight::new(2),
+        app_version: chain_core::APP_VERSION + 1,
+    };
+    let mut inittx = db.transaction();
+    inittx.put(COL_EXTRA, UPGRADE_HALT_KEY, &plan.encode());
+    db.write(inittx).unwrap();
+    let _app = ChainNodeApp::new_with_storage(
+        get_enclave_bridge_mock(),
+        EXAMPLE_HASH,
+        TEST_CHAIN_ID,
+        Storage::new_db(db.clone()),
+        None,
+        None,
+    );
+}
+
+#[test]
+fn scheduled_upgrade_should_bump_app_version() {
+    let mut app = init_chain_for(
+        "0xfe7c045110b8dbf29765047380898919c5cb56f9"
+            .parse()
+            .unwrap(),
+    );
+    {
+        let state = app.last_state.as_mut().unwrap();
+        state.top_level.app_version = chain_core::APP_VERSION - 1;
+        state.upgrade_plan = Some(UpgradePlan {
+            name: "current".to_owned(),
+            height: BlockHeight::new(1),
+            app_version: chain_core::APP_VERSION,
+        });
+    }
+    begin_block_at(&mut app, 1);
+    let state = app.last_state.as_ref().unwrap();
+    assert_eq!(state.top_level.app_version, chain_core::APP_VERSION);
+    assert_eq!(state.upgrade_plan, None);
+    assert_eq!(
+        app.info(&RequestInfo::default()).app_version,
+        chain_core::APP_VERSION
+    );
+}
+
+#[test]
+fn genesis_app_version_should_come_from_config() {
+    let address = "0xfe7c045110b8dbf29765047380898919c5cb56f9"
+        .parse()
+        .unwrap();
+    // an upgraded binary replays the blocks before the upgrade height with the genesis version
+    let mut app = init_chain_with_version(
+        address,
+        1,
+        Some(UpgradePlan {
+            name: "next".to_owned(),
+            height: BlockHeight::new(2),
+            app_version: chain_core::APP_VERSION,
+        }),
+    );
+    assert_eq!(app.last_state.as_ref().unwrap().top_level.app_version, 1);
+    begin_block_at(&mut app, 1);
+    assert_eq!(app.last_state.as_ref().unwrap().top_level.app_version, 1);
+    begin_block_at(&mut app, 2);
+    assert_eq!(
+        app.last_state.as_ref().unwrap().top_level.app_version,
+        chain_core::APP_VERSION
+    );
+}
+
+#[test]
+fn app_hash_should_have_node_state_part_since_app_version_2() {
+    let address = "0xfe7c045110b8dbf29765047380898919c5cb56f9"
+        .parse()
+        .unwrap();
+    let mut app = init_chain_with_version(
+        address,
+        1,
+        Some(UpgradePlan {
+            name: "next".to_owned(),
+            height: BlockHeight::new(1),
+            app_version: 2,
+        }),
+    );
+    let empty_root = MerkleTree::<H256>::empty().root_hash();
+    let state = app.last_state.as_ref().unwrap();
+    // the blocks before the upgrade keep the four-part app hash
+    let parts = state.top_level.app_hash_parts(empty_root);
+    assert_eq!(parts.node_state_hash, None);
+    assert_eq!(parts.spent_outputs_root, None);
+    assert_eq!(parts.app_hash(), state.last_apphash);
+    assert_eq!(
+        parts.app_hash(),
+        chain_core::compute_app_hash_from_parts(
+            &empty_root,
+            &state.top_level.account_root,
+            &state.top_level.rewards_pool.hash(1),
+            &state.top_level.network_params.hash(1),
+            None,
+            None,
+        )
+    );
+
+    begin_block_at(&mut app, 1);
+    let top_level = &app.last_state.as_ref().unwrap().top_level;
+    assert_eq!(
+        top_level.app_hash_parts(empty_root).node_state_hash,
+        top_level.node_state_hash()
+    );
+    assert!(top_level.node_state_hash().is_some());
+    assert_eq!(
+        top_level.app_hash_parts(empty_root).spent_outputs_root,
+        Some(top_level.spent_outputs_root)
+    );
+}
+
 #[test]
 fn deliver_tx_should_reject_empty_tx() {
     let mut app = init_chain_for(
@@ -808,6 +1507,52 @@ fn no_delivered_tx_commit_should_keep_apphash() {
     assert_eq!(&old_app_hash[..], &cresp.data[..]);
 }
 
+#[test]
+fn tdbe_group_hash_should_be_in_apphash() {
+    let mut app = init_chain_for(
+        "0xfe7c045110b8dbf29765047380898919c5cb56f9"
+            .parse()
+            .unwrap(),
+    );
+    let old_app_hash = app.genesis_app_hash;
+    // as if the group was changed by a handshake in the block
+    app.last_state.as_mut().unwrap().top_level.tdbe_group_hash = [1u8; 32];
+    begin_block(&mut app);
+    app.end_block(&RequestEndBlock::default());
+    let cresp = app.commit(&RequestCommit::default());
+    let top_level = &app.last_state.as_ref().unwrap().top_level;
+    assert_ne!(&old_app_hash[..], &cresp.data[..]);
+    assert_eq!(
+        &compute_app_hash(&MerkleTree::empty(), top_level)[..],
+        &cresp.data[..]
+    );
+}
+
+#[test]
+fn block_fullness_should_be_in_apphash() {
+    let mut app = init_chain_for(
+        "0xfe7c045110b8dbf29765047380898919c5cb56f9"
+            .parse()
+            .unwrap(),
+    );
+    let old_app_hash = app.genesis_app_hash;
+    // as if the previous blocks were full
+    let mut fullness = BlockFullness::default();
+    fullness.update(1_000_000);
+    app.last_state.as_mut().unwrap().top_level.block_fullness = fullness;
+    begin_block(&mut app);
+    app.end_block(&RequestEndBlock::default());
+    let cresp = app.commit(&RequestCommit::default());
+    let top_level = &app.last_state.as_ref().unwrap().top_level;
+    // the empty block decreases the average block size, which changes the congestion fee
+    assert!(top_level.block_fullness.average_block_size() < fullness.average_block_size());
+    assert_ne!(&old_app_hash[..], &cresp.data[..]);
+    assert_eq!(
+        &compute_app_hash(&MerkleTree::empty(), top_level)[..],
+        &cresp.data[..]
+    );
+}
+
 #[test]
 fn query_should_return_an_account() {
     let addr = "fe7c045110b8dbf29765047380898919c5cb56f9";
@@ -838,6 +1583,207 @@ fn staking_query_should_return_an_account() {
     );
 }
 
+#[test]
+fn staking_query_should_prove_a_missing_account() {
+    let addr = "fe7c045110b8dbf29765047380898919c5cb56f9";
+    let mut app = init_chain_for(addr.parse().unwrap());
+    let unknown =
+        StakedStateAddress::from_str("0x0e7c045110b8dbf29765047380898919c5cb56f5").unwrap();
+    let mut qreq = RequestQuery::new();
+    qreq.data = unknown.as_ref().to_vec();
+    qreq.path = "staking".into();
+    qreq.prove = true;
+    let qresp = app.query(&qreq);
+    let mstaking = <Option<StakedState>>::decode(&mut qresp.value.as_slice()).unwrap();
+    assert!(mstaking.is_none());
+    let ops = &qresp.proof.get_ref().ops;
+    assert_eq!(ops.len(), 2);
+    let proof = SparseMerkleProof::decode(&mut ops[0].data.as_slice()).unwrap();
+    let parts = AppHashParts::decode(&mut ops[1].data.as_slice()).unwrap();
+    let last_state = app.last_state.as_ref().unwrap();
+    assert_eq!(parts.app_hash(), last_state.last_apphash);
+    proof.verify(parts.account_root, &unknown, None).unwrap();
+    let fake = StakedState::default(unknown);
+    assert!(proof
+        .verify(parts.account_root, &unknown, Some(&fake))
+        .is_err());
+
+    qreq.path = "account".into();
+    let qresp = app.query(&qreq);
+    assert_eq!(qresp.code, 1);
+    let ops = &qresp.proof.get_ref().ops;
+    let proof = SparseMerkleProof::decode(&mut ops[0].data.as_slice()).unwrap();
+    proof.verify(parts.account_root, &unknown, None).unwrap();
+}
+
+#[test]
+fn staking_query_should_fail_for_pruned_height() {
+    let addr = "fe7c045110b8dbf29765047380898919c5cb56f9";
+    let mut app = init_chain_for(addr.parse().unwrap());
+    let mut buffer = KVBuffer::new();
+    buffer.insert(
+        (COL_EXTRA, PRUNED_HEIGHT_KEY.to_vec()),
+        Some(BlockHeight::new(10).encode()),
+    );
+    flush_storage(&mut app.storage, buffer).unwrap();
+    let mut qreq = RequestQuery::new();
+    qreq.data = hex::decode(&addr).unwrap();
+    qreq.path = "staking".into();
+    qreq.height = 5;
+    let qresp = app.query(&qreq);
+    assert_eq!(qresp.code, 5);
+    assert!(qresp.log.contains("pruned"));
+    qreq.height = 20;
+    let qresp = app.query(&qreq);
+    assert_ne!(qresp.code, 5);
+}
+
+#[test]
+fn p2p_filter_should_accept_only_staked_nodes() {
+    let addr = "fe7c045110b8dbf29765047380898919c5cb56f9";
+    let mut app = init_chain_for(addr.parse().unwrap());
+    let allowed_node_id = "0e7c045110b8dbf29765047380898919c5cb56f4";
+    app.peer_filter = PeerFilter {
+        allow: vec![allowed_node_id.to_owned()].into_iter().collect(),
+        deny: vec!["10.0.0.5".to_owned()].into_iter().collect(),
+        staked_nodes_only: true,
+    };
+    // the p2p node ID isn't derived from the consensus key
+    // (and the genesis council node has no p2p node ID recorded)
+    let pub_key =
+        TendermintValidatorPubKey::from_base64(b"MDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDA=")
+            .unwrap();
+    let consensus_key_address = TendermintValidatorAddress::from(&pub_key).to_string();
+    let mut qreq = RequestQuery::new();
+    qreq.path = format!("/p2p/filter/id/{}", consensus_key_address);
+    let qresp = app.query(&qreq);
+    assert_eq!(qresp.code, 6);
+    assert!(qresp
+        .log
+        .contains("not an active council or community node"));
+    qreq.path = format!("/p2p/filter/id/{}", allowed_node_id);
+    assert_eq!(app.query(&qreq).code, 0);
+    qreq.path = "/p2p/filter/addr/10.0.0.5:26656".into();
+    assert_eq!(app.query(&qreq).code, 6);
+    qreq.path = "/p2p/filter/addr/10.0.0.6:26656".into();
+    assert_eq!(app.query(&qreq).code, 0);
+}
+
+#[test]
+fn snapshot_should_restore_a_fresh_node() {
+    let addr = "fe7c045110b8dbf29765047380898919c5cb56f9";
+    let mut app = init_chain_for(addr.parse().unwrap());
+    let snapshots_dir =
+        std::env::temp_dir().join(format!("chain-abci-snapshots-{}", std::process::id()));
+    app.snapshots = Some(SnapshotStore::new(&snapshots_dir, 1, 2));
+    begin_block(&mut app);
+    let mut breq = RequestEndBlock::default();
+    breq.set_height(1);
+    app.end_block(&breq);
+    let cresp = app.commit(&RequestCommit::default());
+    app.wait_for_snapshot();
+
+    let snapshots = app.list_snapshots();
+    assert_eq!(snapshots.len(), 1);
+    let snapshot = &snapshots[0];
+    assert_eq!(snapshot.height, 1);
+    let chunks = (0..snapshot.chunks)
+        .map(|index| app.load_snapshot_chunk(snapshot.height, snapshot.format, index))
+        .collect::<Vec<_>>();
+    assert!(chunks.iter().all(|chunk| !chunk.is_empty()));
+
+    let mut fresh = ChainNodeApp::new_with_storage(
+        get_enclave_bridge_mock(),
+        &hex::encode(app.genesis_app_hash),
+        TEST_CHAIN_ID,
+        Storage::new_db(create_db()),
+        None,
+        None,
+    );
+    assert_eq!(
+        fresh.offer_snapshot(snapshot, &[0u8; 32]),
+        OfferSnapshotResult::Reject
+    );
+    assert_eq!(
+        fresh.offer_snapshot(snapshot, &cresp.data),
+        OfferSnapshotResult::Accept
+    );
+    let mut tampered = chunks[0].clone();
+    tampered[0] ^= 1;
+    assert_eq!(
+        fresh.apply_snapshot_chunk(0, tampered),
+        (ApplySnapshotChunkResult::Retry, vec![0])
+    );
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let (result, _) = fresh.apply_snapshot_chunk(index as u32, chunk);
+        assert_eq!(result, ApplySnapshotChunkResult::Accept);
+    }
+    std::fs::remove_dir_all(&snapshots_dir).unwrap();
+
+    let state = fresh.last_state.as_ref().expect("restored state");
+    assert_eq!(&state.last_apphash[..], &cresp.data[..]);
+    assert_eq!(state.last_block_height, BlockHeight::new(1));
+    assert_eq!(
+        fresh.info(&RequestInfo::default()).last_block_app_hash,
+        cresp.data
+    );
+    let address = StakedStateAddress::from_str(addr).unwrap();
+    assert_eq!(
+        fresh.staking_getter(BufferType::Consensus).get(&address),
+        app.staking_getter(BufferType::Consensus).get(&address)
+    );
+}
+
+#[test]
+fn snapshot_should_restore_from_a_directory() {
+    let addr = "fe7c045110b8dbf29765047380898919c5cb56f9";
+    let mut app = init_chain_for(addr.parse().unwrap());
+    let snapshots_dir =
+        std::env::temp_dir().join(format!("chain-abci-restore-{}", std::process::id()));
+    app.snapshots = Some(SnapshotStore::new(&snapshots_dir, 1, 2));
+    begin_block(&mut app);
+    let mut breq = RequestEndBlock::default();
+    breq.set_height(1);
+    app.end_block(&breq);
+    let cresp = app.commit(&RequestCommit::default());
+    app.wait_for_snapshot();
+    let mut app_hash = H256::default();
+    app_hash.copy_from_slice(&cresp.data);
+
+    let fresh_node = || {
+        ChainNodeApp::new_with_storage(
+            get_enclave_bridge_mock(),
+            &hex::encode(app.genesis_app_hash),
+            TEST_CHAIN_ID,
+            Storage::new_db(create_db()),
+            None,
+            None,
+        )
+    };
+    let mut untrusted = fresh_node();
+    assert!(untrusted
+        .restore_snapshot_from_dir(&snapshots_dir.join("1"), &[0u8; 32])
+        .is_err());
+    assert!(untrusted.last_state.is_none());
+    let mut fresh = fresh_node();
+    let restored = fresh.restore_snapshot_from_dir(&snapshots_dir.join("1"), &app_hash);
+    // the node's storage isn't empty anymore
+    let restored_again = fresh.restore_snapshot_from_dir(&snapshots_dir.join("1"), &app_hash);
+    std::fs::remove_dir_all(&snapshots_dir).unwrap();
+    assert_eq!(restored, Ok(BlockHeight::new(1)));
+    assert!(restored_again.is_err());
+
+    assert_eq!(
+        fresh.info(&RequestInfo::default()).last_block_app_hash,
+        cresp.data
+    );
+    let address = StakedStateAddress::from_str(addr).unwrap();
+    assert_eq!(
+        fresh.staking_getter(BufferType::Consensus).get(&address),
+        app.staking_getter(BufferType::Consensus).get(&address)
+    );
+}
+
 fn block_commit(app: &mut ChainNodeApp<MockClient>, tx: TxAux, block_height: i64) {
     let mut creq = RequestCheckTx::default();
     creq.set_tx(tx.encode());
@@ -1029,11 +1975,17 @@ fn all_valid_tx_types_should_commit() {
         assert_eq!(account.nonce, 0);
     }
 
+    let p2p_node_id = TendermintNodeId::try_from(&[3u8; 20][..]).unwrap();
+    let mut node_meta = mock_council_node(TendermintValidatorPubKey::Ed25519([2u8; 32]));
+    if let NodeMetadata::CouncilNode(council_node) = &mut node_meta {
+        council_node.node_info.p2p_node_id = Some(p2p_node_id.clone());
+    }
     let tx = NodeJoinRequestTx::new(
         1,
         addr.into(),
         StakedStateOpAttributes::new(0),
-        mock_council_node(TendermintValidatorPubKey::Ed25519([2u8; 32])),
+        node_meta,
+        Milli::default(),
     );
     let secp = Secp256k1::new();
     let witness = StakedStateOpWitness::new(get_ecdsa_witness(&secp, &tx.id(), &secret_key));
@@ -1052,8 +2004,17 @@ fn all_valid_tx_types_should_commit() {
         );
         assert_eq!(account.nonce, 1);
     }
+    app.peer_filter = PeerFilter {
+        staked_nodes_only: true,
+        ..Default::default()
+    };
+    let mut qreq = RequestQuery::new();
+    qreq.path = format!("/p2p/filter/id/{}", p2p_node_id);
+    assert_eq!(app.query(&qreq).code, 6);
     block_commit(&mut app, nodejointx, 5);
     {
+        // the joined council node is accepted by its recorded p2p node ID
+        assert_eq!(app.query(&qreq).code, 0);
         let account = get_account(&addr, &app).expect("account not exist");
         assert!(account.node_meta.is_some());
         assert_eq!(
@@ -1146,13 +2107,7 @@ fn query_should_return_proof_for_committed_tx() {
     assert_eq!(merkle.root_hash(), transaction_root_hash);
     let last_state = app.last_state.clone().unwrap();
     assert_eq!(
-        compute_app_hash(
-            &merkle,
-            &last_state.top_level.account_root,
-            &last_state.top_level.rewards_pool,
-            &last_state.top_level.network_params
-        )
-        .to_vec(),
+        compute_app_hash(&merkle, &last_state.top_level).to_vec(),
         cresp.data
     );
     let mut qreq2 = RequestQuery::new();
@@ -1168,6 +2123,121 @@ fn query_should_return_proof_for_committed_tx() {
     assert_eq!(proof.ops[1].data, txid_hash(&qresp.value));
 }
 
+#[test]
+fn query_should_return_inclusion_proof_with_output_status() {
+    let secp = Secp256k1::new();
+    let secret_key = SecretKey::from_slice(&[0xcd; 32]).expect("32 bytes, within curve order");
+    let x_public_key = XOnlyPublicKey::from_secret_key(&secp, &secret_key);
+    let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+    let addr = RedeemAddress::from(&public_key);
+    let mut app = init_chain_for(addr);
+
+    let merkle_tree = MerkleTree::new(vec![RawXOnlyPubkey::from(x_public_key.serialize())]);
+    let eaddr = ExtendedAddr::OrTree(merkle_tree.root_hash());
+    let tx0 = WithdrawUnbondedTx::new(
+        0,
+        vec![
+            TxOut::new_with_timelock(eaddr.clone(), Coin::one(), DEFAULT_GENESIS_TIME),
+            TxOut::new_with_timelock(
+                eaddr.clone(),
+                Coin::new(9999999999899999602).unwrap(),
+                DEFAULT_GENESIS_TIME,
+            ),
+        ],
+        TxAttributes::new_with_access(0, vec![TxAccessPolicy::new(public_key, TxAccess::AllData)]),
+    );
+    let txid = tx0.id();
+    let witness0 = StakedStateOpWitness::new(get_ecdsa_witness(&secp, &txid, &secret_key));
+    let withdrawtx = TxAux::EnclaveTx(TxEnclaveAux::WithdrawUnbondedStakeTx {
+        no_of_outputs: tx0.outputs.len() as TxoSize,
+        witness: witness0,
+        payload: encrypt(&PlainTxAux::WithdrawUnbondedStakeTx(tx0.clone()), txid),
+    });
+    block_commit(&mut app, withdrawtx, 1);
+    let app_hash1 = app.last_state.as_ref().unwrap().last_apphash;
+    // historical states are persisted when tx query address is set
+    let mut buffer = KVBuffer::new();
+    buffer.insert(
+        (COL_APP_STATES, BlockHeight::new(1).encode()),
+        Some(app.last_state.as_ref().unwrap().top_level.encode()),
+    );
+    flush_storage(&mut app.storage, buffer).unwrap();
+
+    let mut tx1 = Tx::new();
+    tx1.add_input(TxoPointer::new(txid, 0));
+    tx1.add_output(TxOut::new(eaddr, Coin::from(99999700u32)));
+    let txid1 = tx1.id();
+    let witness1 = vec![TxInWitness::TreeSig(
+        schnorr_sign(
+            &secp,
+            &Message::from_slice(&txid1).unwrap(),
+            &secret_key,
+            &mut rand::thread_rng(),
+        ),
+        merkle_tree
+            .generate_proof(RawXOnlyPubkey::from(x_public_key.serialize()))
+            .unwrap(),
+    )]
+    .into();
+    let transfertx = TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+        inputs: tx1.inputs.clone(),
+        no_of_outputs: tx1.outputs.len() as TxoSize,
+        payload: encrypt(&PlainTxAux::TransferTx(tx1.clone(), witness1), txid1),
+    });
+    block_commit(&mut app, transfertx, 2);
+
+    let query = |txid: &TxId, index: Option<TxoSize>, height: i64| {
+        let mut qreq = RequestQuery::new();
+        qreq.data = txid.to_vec();
+        if let Some(index) = index {
+            qreq.data.extend(index.encode());
+        }
+        qreq.path = "inclusion".into();
+        qreq.height = height;
+        app.query_handler(&qreq)
+    };
+    let decode_proof = |qresp: &ResponseQuery| {
+        <(TxInclusionProof, Option<OutputStatusProof>)>::decode(&mut qresp.value.as_slice())
+            .unwrap()
+    };
+    let app_hash2 = app.last_state.as_ref().unwrap().last_apphash;
+    let txin0 = TxoPointer::new(txid, 0);
+    let txin1 = TxoPointer::new(txid, 1);
+
+    let qresp = query(&txid, Some(0), 0);
+    assert_eq!(qresp.code, 0);
+    let (proof, status_proof) = decode_proof(&qresp);
+    assert_eq!(proof.block_height, BlockHeight::new(1));
+    assert!(proof.verify(&txid, &app_hash1));
+    assert!(!proof.verify(&txid1, &app_hash1));
+    let status_proof = status_proof.unwrap();
+    assert_eq!(status_proof.block_height, BlockHeight::new(2));
+    assert_eq!(status_proof.status, OutputStatus::Spent);
+    assert!(status_proof.verify(&txin0, &app_hash2).is_ok());
+    assert!(status_proof.verify(&txin0, &app_hash1).is_err());
+    assert!(status_proof.verify(&txin1, &app_hash2).is_err());
+    let mut forged = status_proof.clone();
+    forged.status = OutputStatus::Unspent;
+    assert!(forged.verify(&txin0, &app_hash2).is_err());
+
+    let (_, status_proof) = decode_proof(&query(&txid, Some(0), 1));
+    let status_proof = status_proof.unwrap();
+    assert_eq!(status_proof.block_height, BlockHeight::new(1));
+    assert_eq!(status_proof.status, OutputStatus::Unspent);
+    assert!(status_proof.verify(&txin0, &app_hash1).is_ok());
+    let (_, status_proof) = decode_proof(&query(&txid, Some(1), 0));
+    let status_proof = status_proof.unwrap();
+    assert_eq!(status_proof.status, OutputStatus::Unspent);
+    assert!(status_proof.verify(&txin1, &app_hash2).is_ok());
+    let (proof, status_proof) = decode_proof(&query(&txid1, None, 0));
+    assert_eq!(proof.block_height, BlockHeight::new(2));
+    assert!(proof.verify(&txid1, &app_hash2));
+    assert!(status_proof.is_none());
+
+    assert_eq!(query(&txid, Some(2), 0).code, 1);
+    assert_eq!(query(&[0u8; 32], None, 0).code, 1);
+}
+
 #[test]
 #[should_panic]
 fn check_invalid_punishment_config() {
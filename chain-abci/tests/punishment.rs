@@ -1,7 +1,10 @@
 use abci::*;
 use chain_core::init::coin::Coin;
-use chain_core::state::account::NodeState;
-use parity_scale_codec::Encode;
+use chain_core::state::account::{
+    NodeState, PunishmentKind, StakingHistoryEvent, StakingHistoryPage,
+};
+use chain_core::state::tendermint::BlockHeight;
+use parity_scale_codec::{Decode, Encode};
 use protobuf::well_known_types::Timestamp;
 use test_common::chain_env::{get_account, ChainEnv, DEFAULT_GENESIS_TIME};
 
@@ -197,3 +200,89 @@ fn begin_block_should_slash_non_live_validators() {
     assert_eq!(1, response_end_block.validator_updates.to_vec().len());
     assert_eq!(0, response_end_block.validator_updates.to_vec()[0].power);
 }
+
+#[test]
+fn staking_history_should_record_punishments() {
+    // Init Chain
+    let (env, storage) = ChainEnv::new(Coin::max(), Coin::zero(), 1);
+    let mut app = env.chain_node(storage);
+    let _rsp_init_chain = app.init_chain(&env.req_init_chain());
+
+    // Begin Block
+    app.begin_block(&RequestBeginBlock {
+        byzantine_validators: vec![env.byzantine_evidence(0)].into(),
+        ..env.req_begin_block(1, 0)
+    });
+    app.end_block(&RequestEndBlock {
+        height: 1,
+        ..Default::default()
+    });
+    app.commit(&RequestCommit::new());
+
+    let address = env.accounts[0].staking_address();
+    let mut req = RequestQuery {
+        path: "staking-history".into(),
+        data: address.as_ref().to_vec(),
+        ..Default::default()
+    };
+    let rsp = app.query(&req);
+    assert_eq!(rsp.code, 0);
+    let page = StakingHistoryPage::decode(&mut rsp.value.as_slice()).unwrap();
+    assert_eq!(page.total, 2);
+    assert_eq!(page.entries.len(), 2);
+    assert_eq!(page.entries[0].block_height, BlockHeight::new(1));
+    let slash_amount = Coin::new((u64::from(env.dist_coin) / 10) * 2).unwrap();
+    match &page.entries[0].event {
+        StakingHistoryEvent::Slash {
+            bonded,
+            unbonded,
+            kind,
+        } => {
+            assert_eq!((*bonded + *unbonded).unwrap(), slash_amount);
+            assert_eq!(*kind, PunishmentKind::ByzantineFault);
+        }
+        event => panic!("unexpected event: {:?}", event),
+    }
+    assert!(matches!(
+        page.entries[1].event,
+        StakingHistoryEvent::Jail {
+            kind: PunishmentKind::ByzantineFault,
+            ..
+        }
+    ));
+
+    // the second page with one event per page
+    req.data.extend_from_slice(&(1u64, 1u16).encode());
+    let rsp = app.query(&req);
+    let page = StakingHistoryPage::decode(&mut rsp.value.as_slice()).unwrap();
+    assert_eq!((page.total, page.start), (2, 1));
+    assert_eq!(page.entries.len(), 1);
+    assert!(matches!(
+        page.entries[0].event,
+        StakingHistoryEvent::Jail { .. }
+    ));
+
+    // unjail after the jail duration
+    let jailed_until = match page.entries[0].event {
+        StakingHistoryEvent::Jail { jailed_until, .. } => jailed_until,
+        _ => unreachable!(),
+    };
+    app.begin_block(&env.req_begin_block_with_time(2, 0, jailed_until as i64));
+    let rsp_tx = app.deliver_tx(&RequestDeliverTx {
+        tx: env.unjail_tx(0, 0).encode(),
+        ..Default::default()
+    });
+    assert_eq!(rsp_tx.code, 0);
+    app.end_block(&RequestEndBlock {
+        height: 2,
+        ..Default::default()
+    });
+    app.commit(&RequestCommit::new());
+
+    req.data = address.as_ref().to_vec();
+    let rsp = app.query(&req);
+    let page = StakingHistoryPage::decode(&mut rsp.value.as_slice()).unwrap();
+    assert_eq!(page.total, 3);
+    assert_eq!(page.entries[2].block_height, BlockHeight::new(2));
+    assert!(matches!(page.entries[2].event, StakingHistoryEvent::Unjail));
+}
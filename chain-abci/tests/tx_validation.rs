@@ -4,14 +4,18 @@ use bit_vec::BitVec;
 /// FIXME: organize better / refactor (group by tx, less duplication or unneeded arguments)
 use chain_abci::enclave_bridge::mock::MockClient;
 use chain_abci::enclave_bridge::EnclaveProxy;
+use chain_abci::governance::ProposalTable;
 use chain_abci::staking::StakingTable;
 use chain_abci::storage::{
     process_public_tx, verify_enclave_tx as verify_enclave_tx_inner, TxEnclaveAction,
 };
-use chain_abci::tx_error::{NodeJoinError, PublicTxError, TxError, UnbondError, UnjailError};
+use chain_abci::tx_error::{
+    GovernanceError, NodeJoinError, PublicTxError, TxError, UnbondError, UnjailError,
+};
 use chain_core::common::{MerkleTree, Timespec};
 use chain_core::init::address::RedeemAddress;
 use chain_core::init::coin::{Coin, CoinError};
+use chain_core::init::config::JailingParameters;
 use chain_core::state::account::StakedState;
 use chain_core::state::account::StakedStateAddress;
 use chain_core::state::account::StakedStateOpAttributes;
@@ -19,14 +23,15 @@ use chain_core::state::account::{
     DepositBondTx, NodeMetadata, StakedStateOpWitness, UnbondTx, UnjailTx, Validator,
     WithdrawUnbondedTx,
 };
+use chain_core::state::governance::{NetworkParameterUpdate, ParamChangeProposalTx};
 use chain_core::state::tendermint::BlockHeight;
 use chain_core::state::tendermint::TendermintValidatorPubKey;
 use chain_core::state::validator::NodeJoinRequestTx;
 use chain_core::tx::data::{
-    address::ExtendedAddr,
+    address::{ExtendedAddr, HashTimeLock},
     attribute::TxAttributes,
     input::{TxoPointer, TxoSize},
-    output::TxOut,
+    output::{RelativeTimelock, TxOut},
 };
 use chain_core::tx::data::{Tx, TxId};
 use chain_core::tx::fee::FeeAlgorithm;
@@ -40,9 +45,10 @@ use chain_core::tx::{TxAux, TxEnclaveAux, TxPublicAux};
 use chain_storage::buffer::Get;
 use chain_storage::jellyfish::{StakingBufferStore, StakingGetter, Version};
 use chain_storage::{Storage, COL_ENCLAVE_TX, COL_TX_META, NUM_COLUMNS};
+use chain_tx_validation::witness::hash_preimage;
 use chain_tx_validation::{
     verify_bonded_deposit_core, verify_transfer, verify_unbonded_withdraw_core, ChainInfo, Error,
-    TxWithOutputs,
+    TxCreation, TxWithOutputs,
 };
 use kvdb::KeyValueDB;
 use kvdb_memorydb::create;
@@ -79,12 +85,24 @@ fn verify_public_tx(
     version: Version,
     storage: &Storage,
 ) -> Result<(Fee, Option<StakedState>), TxError> {
-    let mut tbl =
-        StakingTable::from_genesis(&StakingGetter::new(storage, version), info.0, 50, &info.1);
+    let mut tbl = StakingTable::from_genesis(
+        &StakingGetter::new(storage, version),
+        info.0,
+        info.0,
+        50,
+        &info.1,
+    );
     let mut buffer = HashMap::new();
 
     let mut store = StakingBufferStore::new(StakingGetter::new(storage, version), &mut buffer);
-    let tx_action = process_public_tx(&mut store, &mut tbl, 0, extra_info, txaux)?;
+    let tx_action = process_public_tx(
+        &mut store,
+        &mut tbl,
+        &mut ProposalTable::default(),
+        0,
+        extra_info,
+        txaux,
+    )?;
 
     let fee = tx_action.fee();
     let maddress = tx_action.staking_address();
@@ -166,6 +184,8 @@ fn get_chain_info(txaux: &TxAux) -> ChainInfo {
         block_time: DEFAULT_GENESIS_TIME,
         block_height: BlockHeight::genesis(),
         max_evidence_age: 1,
+        max_evidence_age_blocks: 1,
+        app_version: chain_core::APP_VERSION,
     }
 }
 
@@ -797,7 +817,7 @@ fn test_deposit_verify_fail() {
             &tx,
             &witness,
             &extra_info,
-            vec![TxWithOutputs::Transfer(input_tx)],
+            vec![(TxWithOutputs::Transfer(input_tx), TxCreation::default())],
         );
         expect_error(
             &result,
@@ -1086,7 +1106,7 @@ fn test_transfer_verify_fail() {
             &tx,
             &witness,
             &extra_info,
-            vec![TxWithOutputs::Transfer(input_tx)],
+            vec![(TxWithOutputs::Transfer(input_tx), TxCreation::default())],
         );
         expect_error(
             &result,
@@ -1128,7 +1148,7 @@ fn test_transfer_verify_fail() {
             &tx,
             &witness,
             &extra_info,
-            vec![TxWithOutputs::Transfer(input_tx)],
+            vec![(TxWithOutputs::Transfer(input_tx), TxCreation::default())],
         );
         expect_error(&result, Error::OutputInTimelock);
         let result = verify_enclave_tx(&mut mock_bridge, &txaux, &extra_info, 0, &storage);
@@ -1136,6 +1156,179 @@ fn test_transfer_verify_fail() {
     }
 }
 
+#[test]
+fn test_hash_time_lock_transfer() {
+    let secp = Secp256k1::new();
+    let recipient_key = SecretKey::from_slice(&[0xcd; 32]).expect("32 bytes, within curve order");
+    let refund_key = SecretKey::from_slice(&[0x11; 32]).expect("32 bytes, within curve order");
+    let (recipient, recipient_tree) = get_address(&secp, &recipient_key);
+    let (refund, refund_tree) = get_address(&secp, &refund_key);
+    let preimage = [0x42; 32];
+    let deadline = DEFAULT_GENESIS_TIME + 20;
+    let lock = HashTimeLock {
+        hash: hash_preimage(&preimage),
+        recipient: recipient_tree.root_hash(),
+        refund: refund_tree.root_hash(),
+        deadline,
+    };
+    let mut input_tx = Tx::new();
+    input_tx.add_output(TxOut::new(ExtendedAddr::HashTimeLock(lock), Coin::one()));
+    let input_txs = || {
+        vec![(
+            TxWithOutputs::Transfer(input_tx.clone()),
+            TxCreation::default(),
+        )]
+    };
+
+    let mut claim_tx = Tx::new();
+    claim_tx.add_input(TxoPointer::new(input_tx.id(), 0));
+    claim_tx.add_output(TxOut::new(recipient, Coin::one()));
+    let mut refund_tx = claim_tx.clone();
+    refund_tx.outputs[0].address = refund;
+    let claim_witness = |preimage| -> TxWitness {
+        match get_tx_witness(
+            secp.clone(),
+            &claim_tx.id(),
+            &recipient_key,
+            &recipient_tree,
+        ) {
+            TxInWitness::TreeSig(sig, proof) => {
+                vec![TxInWitness::HashLockClaim(preimage, sig, proof)].into()
+            }
+            _ => unreachable!(),
+        }
+    };
+    let refund_witness: TxWitness =
+        match get_tx_witness(secp.clone(), &refund_tx.id(), &refund_key, &refund_tree) {
+            TxInWitness::TreeSig(sig, proof) => {
+                vec![TxInWitness::TimeLockRefund(sig, proof)].into()
+            }
+            _ => unreachable!(),
+        };
+    let chain_info = |block_time| ChainInfo {
+        min_fee_computed: Fee::new(Coin::zero()),
+        chain_hex_id: DEFAULT_CHAIN_ID,
+        block_time,
+        block_height: BlockHeight::genesis(),
+        max_evidence_age: 1,
+        max_evidence_age_blocks: 1,
+        app_version: chain_core::APP_VERSION,
+    };
+    let before_deadline = chain_info(deadline - 1);
+    let after_deadline = chain_info(deadline);
+
+    // claimed with the preimage before the deadline
+    assert!(verify_transfer(
+        &claim_tx,
+        &claim_witness(preimage),
+        &before_deadline,
+        input_txs()
+    )
+    .is_ok());
+    let result = verify_transfer(
+        &claim_tx,
+        &claim_witness([0x24; 32]),
+        &before_deadline,
+        input_txs(),
+    );
+    expect_error(&result, Error::InvalidHashPreimage);
+    let result = verify_transfer(
+        &claim_tx,
+        &claim_witness(preimage),
+        &after_deadline,
+        input_txs(),
+    );
+    expect_error(&result, Error::HashLockExpired);
+    // refunded after the deadline
+    assert!(verify_transfer(&refund_tx, &refund_witness, &after_deadline, input_txs()).is_ok());
+    let result = verify_transfer(&refund_tx, &refund_witness, &before_deadline, input_txs());
+    expect_error(&result, Error::OutputInTimelock);
+    // the tree signature isn't enough
+    let witness: TxWitness = vec![get_tx_witness(
+        secp.clone(),
+        &refund_tx.id(),
+        &refund_key,
+        &refund_tree,
+    )]
+    .into();
+    let result = verify_transfer(&refund_tx, &witness, &after_deadline, input_txs());
+    expect_error(&result, Error::EcdsaCrypto);
+}
+
+#[test]
+fn test_relative_timelock_transfer() {
+    let secp = Secp256k1::new();
+    let secret_key = SecretKey::from_slice(&[0xcd; 32]).expect("32 bytes, within curve order");
+    let (addr, tree) = get_address(&secp, &secret_key);
+    let created = TxCreation {
+        block_height: BlockHeight::new(10),
+        block_time: DEFAULT_GENESIS_TIME,
+    };
+    let mut input_tx = Tx::new();
+    input_tx.add_output(TxOut::new_with_relative_lock(
+        addr.clone(),
+        Coin::one(),
+        RelativeTimelock::Blocks(5),
+    ));
+    input_tx.add_output(TxOut::new_with_relative_lock(
+        addr.clone(),
+        Coin::one(),
+        RelativeTimelock::Seconds(100),
+    ));
+    let input_txs = || vec![(TxWithOutputs::Transfer(input_tx.clone()), created)];
+    let spend = |index| {
+        let mut tx = Tx::new();
+        tx.add_input(TxoPointer::new(input_tx.id(), index));
+        tx.add_output(TxOut::new(addr.clone(), Coin::one()));
+        let witness: TxWitness =
+            vec![get_tx_witness(secp.clone(), &tx.id(), &secret_key, &tree)].into();
+        (tx, witness)
+    };
+    let chain_info = |block_height, block_time| ChainInfo {
+        min_fee_computed: Fee::new(Coin::zero()),
+        chain_hex_id: DEFAULT_CHAIN_ID,
+        block_time,
+        block_height: BlockHeight::new(block_height),
+        max_evidence_age: 1,
+        max_evidence_age_blocks: 1,
+        app_version: chain_core::APP_VERSION,
+    };
+
+    // N blocks after the creation
+    let (tx, witness) = spend(0);
+    let result = verify_transfer(
+        &tx,
+        &witness,
+        &chain_info(14, DEFAULT_GENESIS_TIME + 1000),
+        input_txs(),
+    );
+    expect_error(&result, Error::OutputInRelativeTimelock);
+    assert!(verify_transfer(
+        &tx,
+        &witness,
+        &chain_info(15, DEFAULT_GENESIS_TIME),
+        input_txs()
+    )
+    .is_ok());
+
+    // N seconds after the creation
+    let (tx, witness) = spend(1);
+    let result = verify_transfer(
+        &tx,
+        &witness,
+        &chain_info(100, DEFAULT_GENESIS_TIME + 99),
+        input_txs(),
+    );
+    expect_error(&result, Error::OutputInRelativeTimelock);
+    assert!(verify_transfer(
+        &tx,
+        &witness,
+        &chain_info(11, DEFAULT_GENESIS_TIME + 100),
+        input_txs()
+    )
+    .is_ok());
+}
+
 fn prepare_jailed_accounts() -> (
     Storage,
     SecretKey,
@@ -1163,6 +1356,7 @@ fn prepare_jailed_accounts() -> (
             inactive_time: Some(0),
             inactive_block: Some(BlockHeight::genesis()),
             used_validator_addresses: vec![],
+            commission_rate: Milli::default(),
         }),
     );
 
@@ -1323,6 +1517,8 @@ fn check_unjail_transaction() {
         block_time: DEFAULT_GENESIS_TIME + 101,
         block_height: BlockHeight::genesis(),
         max_evidence_age: 0,
+        max_evidence_age_blocks: 0,
+        app_version: chain_core::APP_VERSION,
     };
 
     let (fee, new_account) =
@@ -1349,6 +1545,7 @@ fn prepare_nodejoin_transaction(
             TendermintValidatorPubKey::Ed25519([1u8; 32]),
             mock_confidential_init(),
         ),
+        commission_rate: Some(Milli::default()),
     };
     let witness = get_account_op_witness(secp, &tx.id(), &secret_key);
 
@@ -1471,3 +1668,73 @@ fn test_nodejoin_fail() {
         expect_error_joinnode(&result, NodeJoinError::AlreadyJoined);
     }
 }
+
+fn prepare_jailing_proposal(
+    secret_key: &SecretKey,
+    block_signing_window: u16,
+    app_version: u64,
+) -> TxPublicAux {
+    let secp = Secp256k1::new();
+    let address = StakedStateAddress::BasicRedeem(RedeemAddress::from(
+        &PublicKey::from_secret_key(&secp, secret_key),
+    ));
+    let mut tx = ParamChangeProposalTx::new(
+        0,
+        address,
+        StakedStateOpAttributes::new(DEFAULT_CHAIN_ID),
+        vec![NetworkParameterUpdate::JailingConfig(JailingParameters {
+            block_signing_window,
+            missed_block_threshold: 1,
+        })],
+        BlockHeight::new(10),
+    );
+    tx.attributes.app_version = app_version;
+    let witness = get_account_op_witness(secp, &tx.id(), secret_key);
+    TxPublicAux::ParamChangeProposalTx(tx, witness)
+}
+
+#[test]
+fn check_jailing_proposal_against_evidence_max_age() {
+    let secret_key = SecretKey::from_slice(&[0xcc; 32]).unwrap();
+    let storage = create_storage();
+    let too_long = prepare_jailing_proposal(&secret_key, 101, chain_core::APP_VERSION);
+    let mut chain_info = get_chain_info_pub(&too_long);
+    chain_info.max_evidence_age_blocks = 100;
+
+    let result = verify_public_tx(&too_long, &chain_info, NodeInfoWrap::default(), 0, &storage);
+    assert!(matches!(
+        result,
+        Err(TxError::Public(PublicTxError::Governance(
+            GovernanceError::InvalidParameter(_)
+        )))
+    ));
+    // the window is accepted (the proposer isn't a council node though)
+    let result = verify_public_tx(
+        &prepare_jailing_proposal(&secret_key, 100, chain_core::APP_VERSION),
+        &chain_info,
+        NodeInfoWrap::default(),
+        0,
+        &storage,
+    );
+    assert!(matches!(
+        result,
+        Err(TxError::Public(PublicTxError::Governance(
+            GovernanceError::NotActiveCouncilNode
+        )))
+    ));
+    // only the upgrades can be proposed in app version 1
+    chain_info.app_version = 1;
+    let result = verify_public_tx(
+        &prepare_jailing_proposal(&secret_key, 100, 1),
+        &chain_info,
+        NodeInfoWrap::default(),
+        0,
+        &storage,
+    );
+    assert!(matches!(
+        result,
+        Err(TxError::Public(PublicTxError::Governance(
+            GovernanceError::InvalidParameter(_)
+        )))
+    ));
+}
@@ -6,7 +6,8 @@ use crate::state::account::{
     ConfidentialInit, CouncilNodeMeta, NodeName, NodeSecurityContact, StakedState,
     StakedStateAddress, StakedStateDestination,
 };
-use crate::state::tendermint::TendermintValidatorPubKey;
+use crate::state::governance::UpgradePlan;
+use crate::state::tendermint::{BlockHeight, TendermintValidatorPubKey};
 use crate::state::RewardsPoolState;
 use mls::{keypackage, Codec, KeyPackage};
 use ra_client::ENCLAVE_CERT_VERIFIER;
@@ -48,6 +49,9 @@ pub enum DistributionError {
     /// TODO: embed the error type?
     #[error("Invalid rewards parameters: {0}")]
     InvalidRewardsParamter(&'static str),
+    /// problems with fee policy configuration
+    #[error("Invalid fee policy: {0}")]
+    InvalidFeePolicy(&'static str),
     /// Invalid punishment configuration parameter
     #[error("Invalid punishment parameters")]
     InvalidPunishmentParamter,
@@ -57,6 +61,15 @@ pub enum DistributionError {
     /// keypackage verify error
     #[error("invalid key package: {0}")]
     KeyPackageVerifyError(#[from] keypackage::Error),
+    /// problems with the scheduled upgrade
+    #[error("Invalid upgrade plan: {0}")]
+    InvalidUpgradePlan(&'static str),
+    /// the binary doesn't support the genesis app version
+    #[error("Unsupported app version: {0}")]
+    UnsupportedAppVersion(u64),
+    /// the network parameters can't be committed in the layout of the genesis app version
+    #[error("Network parameters not supported in the genesis app version: {0}")]
+    UnsupportedNetworkParameters(&'static str),
 }
 
 /// Initial configuration ("app_state" in genesis.json of Tendermint config)
@@ -79,6 +92,21 @@ pub struct InitConfig {
             ConfidentialInit,
         ),
     >,
+    /// network upgrade scheduled from genesis (if any)
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub upgrade_plan: Option<UpgradePlan>,
+    /// app version active from genesis (1 if not specified, as in the genesis files before it);
+    /// later versions are only activated by the scheduled upgrades
+    #[cfg_attr(
+        not(feature = "mesalock_sgx"),
+        serde(default = "default_genesis_app_version")
+    )]
+    pub app_version: u64,
+}
+
+#[cfg(not(feature = "mesalock_sgx"))]
+fn default_genesis_app_version() -> u64 {
+    1
 }
 
 /// the initial state at genesis
@@ -91,6 +119,10 @@ pub struct GenesisState {
     pub validators: Vec<(StakedStateAddress, CouncilNodeMeta)>,
     /// enclave ISVSVN in genesis keypackage
     pub isv_svn: u16,
+    /// app version active from genesis
+    pub app_version: u64,
+    /// scheduled network upgrade
+    pub upgrade_plan: Option<UpgradePlan>,
 }
 
 impl InitConfig {
@@ -112,6 +144,8 @@ impl InitConfig {
             distribution: owners,
             network_params,
             council_nodes,
+            upgrade_plan: None,
+            app_version: crate::APP_VERSION,
         }
     }
 
@@ -176,9 +210,25 @@ impl InitConfig {
             .rewards_config
             .validate()
             .map_err(DistributionError::InvalidRewardsParamter)?;
+        self.network_params
+            .initial_fee_policy
+            .validate()
+            .map_err(DistributionError::InvalidFeePolicy)?;
         if self.council_nodes.is_empty() {
             return Err(DistributionError::NoValidators);
         }
+        if self.app_version == 0 || self.app_version > crate::APP_VERSION {
+            return Err(DistributionError::UnsupportedAppVersion(self.app_version));
+        }
+        if self.app_version < 2 {
+            self.network_params
+                .check_v1_layout()
+                .map_err(DistributionError::UnsupportedNetworkParameters)?;
+        }
+        if let Some(plan) = &self.upgrade_plan {
+            plan.check(BlockHeight::genesis(), self.app_version)
+                .map_err(DistributionError::InvalidUpgradePlan)?;
+        }
         // check validator pubkey is duplicated or not
         let pub_keys: HashSet<_> = self
             .council_nodes
@@ -233,6 +283,8 @@ impl InitConfig {
             rewards_pool,
             validators,
             isv_svn,
+            upgrade_plan: self.upgrade_plan.clone(),
+            app_version: self.app_version,
         })
     }
 }
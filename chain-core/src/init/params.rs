@@ -1,13 +1,17 @@
 use crate::common::H256;
 use crate::init::coin::{Coin, CoinError};
-use crate::tx::fee::{Fee, FeeAlgorithm};
-use crate::tx::fee::{LinearFee, Milli, MilliError};
+use crate::state::account::StakedStateAddress;
+use crate::state::governance::NetworkParameterUpdate;
+use crate::state::tendermint::BlockHeight;
+use crate::tx::fee::{BlockFullness, Fee, FeeInput, FeePolicy};
+use crate::tx::fee::{Milli, MilliError};
 use parity_scale_codec::{Decode, Encode};
 #[cfg(not(feature = "mesalock_sgx"))]
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::convert::TryFrom;
 use std::fmt;
 use std::ops::Mul;
+use std::prelude::v1::Vec;
 use std::str::FromStr;
 
 const MAX_SLASH_RATIO: Milli = Milli::new(1, 0); // 1.0
@@ -16,13 +20,17 @@ const MAX_SLASH_RATIO: Milli = Milli::new(1, 0); // 1.0
 /// ref: https://crypto-com.github.io/getting-started/network-parameters.html
 #[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
 #[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+#[cfg_attr(
+    not(feature = "mesalock_sgx"),
+    serde(from = "InitNetworkParametersConfig")
+)]
 pub struct InitNetworkParameters {
-    /// Initial fee setting
-    /// -- TODO: perhaps change to be against T: FeeAlgorithm
-    /// TBD here, the intention would be to "freeze" the genesis config, so not sure generic field is worth it
-    pub initial_fee_policy: LinearFee,
+    /// Initial fee setting (linear, weighted by inputs/outputs or with a congestion multiplier)
+    pub initial_fee_policy: FeePolicy,
     /// minimal? council node stake
     pub required_council_node_stake: Coin,
+    /// minimal community node stake
+    pub required_community_node_stake: Coin,
     /// Jailing configuration
     pub jailing_config: JailingParameters,
     /// Slashing configuration
@@ -33,116 +41,291 @@ pub struct InitNetworkParameters {
     pub max_validators: u16,
 }
 
-/// so far only one specified at genesis
+/// network parameters as written in the genesis configurations:
+/// the ones created before the community nodes were added don't have
+/// the community node stake, so the council node stake is required from the community nodes
+#[cfg(not(feature = "mesalock_sgx"))]
+#[derive(Deserialize)]
+struct InitNetworkParametersConfig {
+    initial_fee_policy: FeePolicy,
+    required_council_node_stake: Coin,
+    #[serde(default)]
+    required_community_node_stake: Option<Coin>,
+    jailing_config: JailingParameters,
+    slashing_config: SlashingParameters,
+    rewards_config: RewardsParameters,
+    max_validators: u16,
+}
+
+impl InitNetworkParameters {
+    /// checks if the parameters can be encoded in the app version 1 (0.5) layout:
+    /// it has only a linear fee policy, no fee split and no separate community node stake
+    pub fn check_v1_layout(&self) -> Result<(), &'static str> {
+        if !matches!(self.initial_fee_policy, FeePolicy::Linear(_)) {
+            return Err("only the linear fee policy is supported");
+        }
+        if self.required_community_node_stake != self.required_council_node_stake {
+            return Err("community node stake needs to be the council node stake");
+        }
+        let rewards = &self.rewards_config;
+        if rewards.fee_burn_ratio != Milli::default()
+            || rewards.fee_treasury_ratio != Milli::default()
+            || rewards.treasury_address.is_some()
+        {
+            return Err("fee burning and treasury are not supported");
+        }
+        Ok(())
+    }
+
+    /// encodes the parameters in the app version 1 (0.5) layout
+    /// (None if they can't be encoded in it)
+    pub fn encode_v1(&self) -> Option<Vec<u8>> {
+        let fee = match self.initial_fee_policy {
+            FeePolicy::Linear(fee) if self.check_v1_layout().is_ok() => fee,
+            _ => return None,
+        };
+        let rewards = &self.rewards_config;
+        Some(
+            (
+                fee,
+                self.required_council_node_stake,
+                self.jailing_config,
+                self.slashing_config,
+                (
+                    rewards.monetary_expansion_cap,
+                    rewards.reward_period_seconds,
+                    rewards.monetary_expansion_r0,
+                    rewards.monetary_expansion_tau,
+                    rewards.monetary_expansion_decay,
+                ),
+                self.max_validators,
+            )
+                .encode(),
+        )
+    }
+}
+
+#[cfg(not(feature = "mesalock_sgx"))]
+impl From<InitNetworkParametersConfig> for InitNetworkParameters {
+    fn from(config: InitNetworkParametersConfig) -> Self {
+        InitNetworkParameters {
+            initial_fee_policy: config.initial_fee_policy,
+            required_council_node_stake: config.required_council_node_stake,
+            required_community_node_stake: config
+                .required_community_node_stake
+                .unwrap_or(config.required_council_node_stake),
+            jailing_config: config.jailing_config,
+            slashing_config: config.slashing_config,
+            rewards_config: config.rewards_config,
+            max_validators: config.max_validators,
+        }
+    }
+}
+
+/// specified at genesis, then possibly changed by governance proposals
 #[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
 #[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
 pub enum NetworkParameters {
     /// parameters specified at genesis time
     Genesis(InitNetworkParameters),
+    /// parameters after an approved change proposal was activated
+    Updated {
+        /// the current parameters
+        params: InitNetworkParameters,
+        /// the block height at which the changes were activated
+        activation_height: BlockHeight,
+    },
 }
 
 /// TODO: extract these to a trait?
 impl NetworkParameters {
-    /// retrieves the hash of the current state (currently blake3(scale_code_bytes(network params)))
-    pub fn hash(&self) -> H256 {
+    /// applies the approved changes (in order) to the current parameters
+    pub fn apply_updates(
+        &self,
+        changes: &[NetworkParameterUpdate],
+        activation_height: BlockHeight,
+    ) -> NetworkParameters {
+        let mut params = match self {
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.clone()
+            }
+        };
+        for change in changes.iter() {
+            change.apply(&mut params);
+        }
+        NetworkParameters::Updated {
+            params,
+            activation_height,
+        }
+    }
+
+    /// retrieves the hash of the current state (currently blake3(scale_code_bytes(network params)));
+    /// before app version 2, the genesis parameters are hashed in the 0.5 layout
+    /// (if they can be encoded in it, as checked at genesis, see `InitNetworkParameters::encode_v1`)
+    pub fn hash(&self, app_version: u64) -> H256 {
+        match self {
+            NetworkParameters::Genesis(params) if app_version < 2 => {
+                if let Some(params_v1) = params.encode_v1() {
+                    // the genesis variant was the only one in 0.5
+                    let mut data = vec![0u8];
+                    data.extend(params_v1);
+                    return blake3::hash(&data).into();
+                }
+            }
+            _ => {}
+        }
         blake3::hash(&self.encode()).into()
     }
 
     /// cap on validators in tendermint
     pub fn get_max_validators(&self) -> usize {
         match self {
-            NetworkParameters::Genesis(params) => params.max_validators as usize,
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.max_validators as usize
+            }
         }
     }
 
     /// minimal stake required for node joining (to be a validator)
     pub fn get_required_council_node_stake(&self) -> Coin {
         match self {
-            NetworkParameters::Genesis(params) => params.required_council_node_stake,
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.required_council_node_stake
+            }
+        }
+    }
+
+    /// minimal stake required for node joining as a community node (non-validator TDBE participant)
+    pub fn get_required_community_node_stake(&self) -> Coin {
+        match self {
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.required_community_node_stake
+            }
         }
     }
 
     /// infraction configuration for byzantine fault
     pub fn get_byzantine_slash_percent(&self) -> SlashRatio {
         match self {
-            NetworkParameters::Genesis(params) => params.slashing_config.byzantine_slash_percent,
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.slashing_config.byzantine_slash_percent
+            }
         }
     }
 
     /// infraction configuration for liveness fault
     pub fn get_liveness_slash_percent(&self) -> SlashRatio {
         match self {
-            NetworkParameters::Genesis(params) => params.slashing_config.liveness_slash_percent,
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.slashing_config.liveness_slash_percent
+            }
         }
     }
 
     /// infraction configuration for liveness fault
     pub fn get_missed_block_threshold(&self) -> u16 {
         match self {
-            NetworkParameters::Genesis(params) => params.jailing_config.missed_block_threshold,
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.jailing_config.missed_block_threshold
+            }
         }
     }
 
     /// infraction configuration for liveness fault
     pub fn get_block_signing_window(&self) -> u16 {
         match self {
-            NetworkParameters::Genesis(params) => params.jailing_config.block_signing_window,
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.jailing_config.block_signing_window
+            }
         }
     }
 
     /// The period of reward being distributed
     pub fn get_rewards_reward_period_seconds(&self) -> u64 {
         match self {
-            NetworkParameters::Genesis(params) => params.rewards_config.reward_period_seconds,
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.rewards_config.reward_period_seconds
+            }
         }
     }
 
     /// The upper bound for the reward rate per annum
     pub fn get_rewards_monetary_expansion_r0(&self) -> Milli {
         match self {
-            NetworkParameters::Genesis(params) => params.rewards_config.monetary_expansion_r0,
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.rewards_config.monetary_expansion_r0
+            }
         }
     }
 
     /// Initial value of tau in the reward function
     pub fn get_rewards_monetary_expansion_tau(&self) -> u64 {
         match self {
-            NetworkParameters::Genesis(params) => params.rewards_config.monetary_expansion_tau,
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.rewards_config.monetary_expansion_tau
+            }
         }
     }
 
     /// The decay rate of tau.
     pub fn get_rewards_monetary_expansion_decay(&self) -> u64 {
         match self {
-            NetworkParameters::Genesis(params) => params.rewards_config.monetary_expansion_decay,
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.rewards_config.monetary_expansion_decay
+            }
         }
     }
 
     /// The total amount of tokens reserved for validator's reward in the basic unit
     pub fn get_rewards_monetary_expansion_cap(&self) -> Coin {
         match self {
-            NetworkParameters::Genesis(params) => params.rewards_config.monetary_expansion_cap,
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.rewards_config.monetary_expansion_cap
+            }
         }
     }
 
-    /// constant fee -- TODO: will it be necessary? (used in the tx-query fee?)
-    pub fn get_min_const_fee(&self) -> Result<Fee, CoinError> {
+    /// The staked state which receives the treasury portion of the fees
+    pub fn get_rewards_treasury_address(&self) -> Option<StakedStateAddress> {
         match self {
-            NetworkParameters::Genesis(params) => {
-                let coin = Coin::new(params.initial_fee_policy.coefficient.to_integral())?;
-                Ok(Fee::new(coin))
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.rewards_config.treasury_address
             }
         }
     }
 
-    /// calculates the fee based on the specified policy
-    pub fn calculate_fee(&self, num_bytes: usize) -> Result<Fee, CoinError> {
+    /// splits the transaction fee between burning, the treasury and the rewards pool
+    pub fn split_fee(&self, fee: Coin) -> FeeDistribution {
         match self {
-            NetworkParameters::Genesis(params) => {
-                params.initial_fee_policy.calculate_fee(num_bytes)
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.rewards_config.split_fee(fee)
             }
         }
     }
+
+    /// the current fee policy
+    pub fn get_fee_policy(&self) -> FeePolicy {
+        match self {
+            NetworkParameters::Genesis(params) | NetworkParameters::Updated { params, .. } => {
+                params.initial_fee_policy
+            }
+        }
+    }
+
+    /// the fee of an empty transaction under the current policy and no congestion
+    pub fn get_min_const_fee(&self) -> Result<Fee, CoinError> {
+        self.get_fee_policy()
+            .estimate(&FeeInput::default(), BlockFullness::default())
+    }
+
+    /// calculates the fee based on the specified policy and the recent block fullness
+    pub fn calculate_fee(
+        &self,
+        input: &FeeInput,
+        fullness: BlockFullness,
+    ) -> Result<Fee, CoinError> {
+        self.get_fee_policy().estimate(input, fullness)
+    }
 }
 
 /// infraction parameters for jailing
@@ -167,6 +350,19 @@ pub struct SlashingParameters {
     pub byzantine_slash_percent: SlashRatio,
 }
 
+impl SlashingParameters {
+    /// check if the slash ratios are at most 1.0
+    /// (they aren't checked when decoded, e.g. in a parameter change proposal)
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.liveness_slash_percent.0 > MAX_SLASH_RATIO
+            || self.byzantine_slash_percent.0 > MAX_SLASH_RATIO
+        {
+            return Err("slash ratio can't > 1");
+        }
+        Ok(())
+    }
+}
+
 /// reward parameters
 /// ref: https://crypto-com.github.io/getting-started/reward-and-punishments.html#validator-rewards
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Encode, Decode)]
@@ -182,6 +378,27 @@ pub struct RewardsParameters {
     pub monetary_expansion_tau: u64,
     /// Monetary expansion formula parameter
     pub monetary_expansion_decay: u64,
+    /// Portion of the transaction fees which is burned
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub fee_burn_ratio: Milli,
+    /// Portion of the transaction fees which goes to the treasury
+    /// (the rest goes to the rewards pool)
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub fee_treasury_ratio: Milli,
+    /// Staked state which receives the treasury portion of the fees (as unbonded)
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub treasury_address: Option<StakedStateAddress>,
+}
+
+/// How a transaction fee is split
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FeeDistribution {
+    /// removed from the total supply
+    pub burned: Coin,
+    /// credited to the treasury staked state
+    pub treasury: Coin,
+    /// added to the rewards pool
+    pub rewards_pool: Coin,
 }
 
 impl RewardsParameters {
@@ -200,8 +417,39 @@ impl RewardsParameters {
         if self.monetary_expansion_decay > 1_000_000 {
             return Err("decay can't > 1_000_000");
         }
+        let fee_ratios = self
+            .fee_burn_ratio
+            .as_millis()
+            .saturating_add(self.fee_treasury_ratio.as_millis());
+        if fee_ratios > Milli::integral(1).unwrap().as_millis() {
+            return Err("fee burn ratio + fee treasury ratio can't > 1");
+        }
+        if self.fee_treasury_ratio > Milli::default() && self.treasury_address.is_none() {
+            return Err("treasury address is required if fee treasury ratio > 0");
+        }
         Ok(())
     }
+
+    /// splits the fee according to the burn and treasury ratios
+    /// (rounding down; the remainder goes to the rewards pool)
+    pub fn split_fee(&self, fee: Coin) -> FeeDistribution {
+        let portion = |ratio: Milli| {
+            let amount = u128::from(u64::from(fee)) * u128::from(ratio.as_millis()) / 1000;
+            Coin::new(amount as u64).expect("fee portion exceeds the fee")
+        };
+        let burned = portion(self.fee_burn_ratio);
+        let treasury = if self.treasury_address.is_some() {
+            portion(self.fee_treasury_ratio)
+        } else {
+            Coin::zero()
+        };
+        let rewards_pool = (fee - burned - treasury).expect("fee portions exceed the fee");
+        FeeDistribution {
+            burned,
+            treasury,
+            rewards_pool,
+        }
+    }
 }
 
 /// how much to slash from bonded+unbonded
@@ -25,46 +25,98 @@ pub mod tx;
 pub mod fixed;
 
 use common::{MerkleTree, Timespec, H256};
-use init::params::NetworkParameters;
 use parity_scale_codec::{Decode, Encode};
 use state::tendermint::BlockHeight;
-use state::RewardsPoolState;
+use state::ChainState;
 use tx::fee::Fee;
 
 /// The app version returned in Tendermint "Info" response,
 /// included in every header + transaction metadata.
 /// It denotes both binary schema and semantics (state machine rules)
 /// ref: https://github.com/tendermint/tendermint/blob/master/docs/architecture/adr-016-protocol-versions.md#appversion
-/// TODO: upgrades/new version signalling
+///
+/// This is the highest version the binary supports; the version active on the network
+/// is kept in the node state and is bumped by a scheduled upgrade (`UpgradePlan`):
+/// the old binary halts at the upgrade height and the new one continues from there.
 ///
 /// version 0 -- 0.4.0 release
 /// version 1 -- 0.5.0 release (wire format didn't change, but unbond tx semantics changed: https://github.com/crypto-com/chain/pull/1516)
 /// version 2 -- 0.6.0 (not yet released --> transaction data bootstrapping, new TX types, genesis changes..);
-/// FIXME: bump to 2 with https://github.com/crypto-com/chain/issues/1715#issuecomment-650845116
-pub const APP_VERSION: u64 = 1;
+/// the TX formats of the lower versions are kept in `TxAux::decode_versioned`
+pub const APP_VERSION: u64 = 2;
 
 /// computes the "global" application hash (used by Tendermint to check consistency + block replaying)
 /// currently: app_hash = blake3(root of valid TX merkle tree
-/// || root of account/staked state trie || blake3(scale bytes(rewards pool state)) || blake3(scale bytes(network params)))
+/// || root of account/staked state trie || blake3(scale bytes(rewards pool state)) || blake3(scale bytes(network params))
+/// || hash of the other node state since app version 2 (see `ChainState::node_state_hash`)
+/// || root of the spent outputs trie since app version 2);
+/// before app version 2, the staked states, rewards pool state and network params are hashed
+/// in their 0.5 layout (so that the app hashes of the app version 1 networks don't change)
 /// TODO: cache (as many parts remain static)
 /// MUST/TODO: include node whitelists
-pub fn compute_app_hash(
-    valid_tx_id_tree: &MerkleTree<H256>,
+pub fn compute_app_hash(valid_tx_id_tree: &MerkleTree<H256>, state: &ChainState) -> H256 {
+    state
+        .app_hash_parts(valid_tx_id_tree.root_hash())
+        .app_hash()
+}
+
+/// computes the "global" application hash from the hashes of its parts
+/// (e.g. when the parts are received in an inclusion proof);
+/// the node state hash and the spent outputs root are only present since app version 2
+pub fn compute_app_hash_from_parts(
+    valid_tx_root: &H256,
     account_state_root: &H256,
-    reward_pool: &RewardsPoolState,
-    params: &NetworkParameters,
+    rewards_pool_hash: &H256,
+    network_params_hash: &H256,
+    node_state_hash: Option<&H256>,
+    spent_outputs_root: Option<&H256>,
 ) -> H256 {
-    let valid_tx_part = valid_tx_id_tree.root_hash();
-    let rewards_pool_part = reward_pool.hash();
-    let network_params_part = params.hash();
     let mut hasher = blake3::Hasher::new();
-    hasher.update(&valid_tx_part);
+    hasher.update(&valid_tx_root[..]);
     hasher.update(&account_state_root[..]);
-    hasher.update(&rewards_pool_part);
-    hasher.update(&network_params_part);
+    hasher.update(&rewards_pool_hash[..]);
+    hasher.update(&network_params_hash[..]);
+    if let Some(node_state_hash) = node_state_hash {
+        hasher.update(&node_state_hash[..]);
+    }
+    if let Some(spent_outputs_root) = spent_outputs_root {
+        hasher.update(&spent_outputs_root[..]);
+    }
     hasher.finalize().into()
 }
 
+/// The hashes the application hash is computed from
+/// (e.g. to check a staking state proof against the app hash in a trusted header)
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Encode, Decode)]
+pub struct AppHashParts {
+    /// root of valid TX merkle tree
+    pub valid_tx_root: H256,
+    /// root of account/staked state trie
+    pub account_root: H256,
+    /// hash of the rewards pool state
+    pub rewards_pool_hash: H256,
+    /// hash of the network parameters
+    pub network_params_hash: H256,
+    /// hash of the other node state (since app version 2)
+    pub node_state_hash: Option<H256>,
+    /// root of the spent outputs trie (since app version 2)
+    pub spent_outputs_root: Option<H256>,
+}
+
+impl AppHashParts {
+    /// computes the application hash from the parts
+    pub fn app_hash(&self) -> H256 {
+        compute_app_hash_from_parts(
+            &self.valid_tx_root,
+            &self.account_root,
+            &self.rewards_pool_hash,
+            &self.network_params_hash,
+            self.node_state_hash.as_ref(),
+            self.spent_outputs_root.as_ref(),
+        )
+    }
+}
+
 /// External information needed for TX validation
 #[derive(Clone, Copy, Encode, Decode)]
 pub struct ChainInfo {
@@ -78,6 +130,11 @@ pub struct ChainInfo {
     pub block_height: BlockHeight,
     /// max evidence age in tendermint consensus parameter
     pub max_evidence_age: Timespec,
+    /// max evidence age in blocks in tendermint consensus parameter
+    /// (the jailing block signing window can't be longer)
+    pub max_evidence_age_blocks: u64,
+    /// the app version active on the network
+    pub app_version: u64,
 }
 
 impl ChainInfo {
@@ -86,3 +143,13 @@ impl ChainInfo {
         self.max_evidence_age
     }
 }
+
+/// The block in which a transaction was committed
+/// (its outputs may be timelocked relative to it)
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Encode, Decode)]
+pub struct TxCreation {
+    /// height of the block
+    pub block_height: BlockHeight,
+    /// time of the block
+    pub block_time: Timespec,
+}
@@ -1,13 +1,17 @@
 mod address;
+mod history;
 mod op;
 use crate::common::{Timespec, HASH_SIZE_256};
-use crate::init::coin::Coin;
+use crate::init::coin::{sum_coins, Coin};
 use crate::state::tendermint::{
-    BlockHeight, TendermintValidatorAddress, TendermintValidatorPubKey,
+    BlockHeight, TendermintNodeId, TendermintValidatorAddress, TendermintValidatorPubKey,
 };
 pub use crate::state::validator::UnjailTx;
+use crate::tx::fee::Milli;
 pub use address::StakedStateAddress;
+pub use history::{StakingHistoryEntry, StakingHistoryEvent, StakingHistoryPage};
 pub use op::data::attribute::StakedStateOpAttributes;
+pub use op::data::delegate::{DelegateTx, UndelegateTx};
 pub use op::data::deposit::DepositBondTx;
 pub use op::data::unbond::UnbondTx;
 pub use op::data::withdraw::WithdrawUnbondedTx;
@@ -15,6 +19,7 @@ pub use op::witness::StakedStateOpWitness;
 use parity_scale_codec::{Decode, Encode, Error, Input, Output};
 #[cfg(not(feature = "mesalock_sgx"))]
 use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
 use std::convert::From;
 #[cfg(not(feature = "mesalock_sgx"))]
 use std::fmt;
@@ -76,6 +81,13 @@ pub struct NodeCommonInfo {
     /// serialized keypackage for MLS (https://tools.ietf.org/html/draft-ietf-mls-protocol-10)
     /// (expected that attestation payload will be a part of the cert extension, as done in TLS)
     pub confidential_init: ConfidentialInit,
+    /// p2p node ID (derived from the key in Tendermint's `node_key.json`)
+    /// the connecting peers are matched against when filtering peers
+    #[cfg_attr(
+        not(feature = "mesalock_sgx"),
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub p2p_node_id: Option<TendermintNodeId>,
 }
 
 #[cfg(not(feature = "mesalock_sgx"))]
@@ -85,9 +97,9 @@ impl fmt::Display for NodeCommonInfo {
     }
 }
 
-// TODO: size hint
-impl Encode for NodeCommonInfo {
-    fn encode_to<W: Output>(&self, dest: &mut W) {
+impl NodeCommonInfo {
+    /// encodes the fields of the 0.5 TX format (without the p2p node ID)
+    fn encode_v05<W: Output>(&self, dest: &mut W) {
         self.name.encode_to(dest);
         match &self.security_contact {
             None => dest.push_byte(0),
@@ -98,9 +110,29 @@ impl Encode for NodeCommonInfo {
         };
         self.confidential_init.keypackage.encode_to(dest);
     }
+
+    /// decodes the fields of the 0.5 TX format (without the p2p node ID)
+    fn decode_v05<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let (name, security_contact) = decode_name_security_contact(input)?;
+        let keypackage: Vec<u8> = Vec::decode(input)?;
+        Ok(NodeCommonInfo {
+            name,
+            security_contact,
+            confidential_init: ConfidentialInit { keypackage },
+            p2p_node_id: None,
+        })
+    }
 }
 
-fn decode_name_security_contact<I: Input>(
+// TODO: size hint
+impl Encode for NodeCommonInfo {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.encode_v05(dest);
+        self.p2p_node_id.encode_to(dest);
+    }
+}
+
+pub(crate) fn decode_name_security_contact<I: Input>(
     input: &mut I,
 ) -> Result<(NodeName, NodeSecurityContact), Error> {
     let name_raw: Vec<u8> = Vec::decode(input)?;
@@ -125,13 +157,9 @@ const MAX_STRING_LEN: usize = 255;
 
 impl Decode for NodeCommonInfo {
     fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
-        let (name, security_contact) = decode_name_security_contact(input)?;
-        let keypackage: Vec<u8> = Vec::decode(input)?;
-        Ok(NodeCommonInfo {
-            name,
-            security_contact,
-            confidential_init: ConfidentialInit { keypackage },
-        })
+        let mut node_info = NodeCommonInfo::decode_v05(input)?;
+        node_info.p2p_node_id = Option::decode(input)?;
+        Ok(node_info)
     }
 }
 
@@ -146,8 +174,12 @@ pub struct CouncilNodeMeta {
     pub consensus_pubkey: TendermintValidatorPubKey,
 }
 
-impl Encode for CouncilNodeMeta {
-    fn encode_to<W: Output>(&self, dest: &mut W) {
+/// the maximal commission rate (1.0) in millis
+pub const MAX_COMMISSION_RATE_MILLIS: u64 = 1000;
+
+impl CouncilNodeMeta {
+    /// encodes the fields of the 0.5 TX format (without the p2p node ID)
+    fn encode_v05<W: Output>(&self, dest: &mut W) {
         // NOTE/WARN: the order of node_info + consensus pubkey
         // is swapped in order not to break 0.5 TX format
         // where it was like this
@@ -162,10 +194,9 @@ impl Encode for CouncilNodeMeta {
         self.consensus_pubkey.encode_to(dest);
         self.node_info.confidential_init.keypackage.encode_to(dest);
     }
-}
 
-impl Decode for CouncilNodeMeta {
-    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+    /// decodes the fields of the 0.5 TX format (without the p2p node ID)
+    fn decode_v05<I: Input>(input: &mut I) -> Result<Self, Error> {
         // NOTE/WARN: the order of node_info + consensus pubkey
         // is swapped in order not to break 0.5 TX format
         // where it was like this
@@ -181,6 +212,21 @@ impl Decode for CouncilNodeMeta {
     }
 }
 
+impl Encode for CouncilNodeMeta {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.encode_v05(dest);
+        self.node_info.p2p_node_id.encode_to(dest);
+    }
+}
+
+impl Decode for CouncilNodeMeta {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let mut council_node = CouncilNodeMeta::decode_v05(input)?;
+        council_node.node_info.p2p_node_id = Option::decode(input)?;
+        Ok(council_node)
+    }
+}
+
 /// info about a node
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 #[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
@@ -202,15 +248,25 @@ impl fmt::Display for NodeMetadata {
 }
 
 // TODO: size hint once MLS payloads are there
+// NOTE: the metadata without a p2p node ID is encoded in the 0.5 TX format (tags 0 and 1),
+// the tags 2 and 3 are for the metadata with a p2p node ID
 impl Encode for NodeMetadata {
     fn encode_to<W: Output>(&self, dest: &mut W) {
         match self {
-            NodeMetadata::CouncilNode(cm) => {
+            NodeMetadata::CouncilNode(cm) if cm.node_info.p2p_node_id.is_none() => {
                 dest.push_byte(0);
+                cm.encode_v05(dest);
+            }
+            NodeMetadata::CommunityNode(cm) if cm.p2p_node_id.is_none() => {
+                dest.push_byte(1);
+                cm.encode_v05(dest);
+            }
+            NodeMetadata::CouncilNode(cm) => {
+                dest.push_byte(2);
                 cm.encode_to(dest);
             }
             NodeMetadata::CommunityNode(cm) => {
-                dest.push_byte(1);
+                dest.push_byte(3);
                 cm.encode_to(dest);
             }
         }
@@ -220,21 +276,43 @@ impl Encode for NodeMetadata {
 impl Decode for NodeMetadata {
     fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
         let tag = input.read_byte()?;
-        match tag {
-            0 => {
-                let node_info = CouncilNodeMeta::decode(input)?;
-                Ok(NodeMetadata::CouncilNode(node_info))
-            }
-            1 => {
-                let node_info = NodeCommonInfo::decode(input)?;
-                Ok(NodeMetadata::CommunityNode(node_info))
-            }
-            _ => Err(Error::from("Unsupported Node variant")),
+        let node_meta = match tag {
+            0 => NodeMetadata::CouncilNode(CouncilNodeMeta::decode_v05(input)?),
+            1 => NodeMetadata::CommunityNode(NodeCommonInfo::decode_v05(input)?),
+            2 => NodeMetadata::CouncilNode(CouncilNodeMeta::decode(input)?),
+            3 => NodeMetadata::CommunityNode(NodeCommonInfo::decode(input)?),
+            _ => return Err(Error::from("Unsupported Node variant")),
+        };
+        // only one encoding of the same metadata
+        if tag > 1 && node_meta.node_info().p2p_node_id.is_none() {
+            return Err(Error::from("Missing p2p node ID"));
         }
+        Ok(node_meta)
     }
 }
 
 impl NodeMetadata {
+    /// decodes the metadata in the app version 1 (0.5) TX format (without the p2p node ID)
+    pub fn decode_v1<I: Input>(input: &mut I) -> Result<Self, Error> {
+        match input.read_byte()? {
+            0 => Ok(NodeMetadata::CouncilNode(CouncilNodeMeta::decode_v05(
+                input,
+            )?)),
+            1 => Ok(NodeMetadata::CommunityNode(NodeCommonInfo::decode_v05(
+                input,
+            )?)),
+            _ => Err(Error::from("Unsupported Node variant")),
+        }
+    }
+
+    /// name, security contact, keypackage and p2p node ID
+    pub fn node_info(&self) -> &NodeCommonInfo {
+        match self {
+            NodeMetadata::CouncilNode(cm) => &cm.node_info,
+            NodeMetadata::CommunityNode(cm) => cm,
+        }
+    }
+
     /// create an empty council node (in testing etc.)
     pub fn new_council_node(
         consensus_pubkey: TendermintValidatorPubKey,
@@ -270,6 +348,7 @@ impl CouncilNodeMeta {
                 name: "no-name".to_string(),
                 security_contact: None,
                 confidential_init,
+                p2p_node_id: None,
             },
             consensus_pubkey,
         }
@@ -287,6 +366,7 @@ impl CouncilNodeMeta {
                 name,
                 security_contact,
                 confidential_init,
+                p2p_node_id: None,
             },
             consensus_pubkey,
         }
@@ -301,6 +381,8 @@ pub enum PunishmentKind {
     NonLive,
     /// byzantine fault (double vote signing initially)
     ByzantineFault,
+    /// evicted from the TDBE group by a council node (community nodes are only jailed for it)
+    TdbeEviction,
 }
 
 #[cfg(not(feature = "mesalock_sgx"))]
@@ -309,6 +391,7 @@ impl fmt::Display for PunishmentKind {
         match self {
             PunishmentKind::NonLive => write!(f, "Non-live"),
             PunishmentKind::ByzantineFault => write!(f, "Byzantine fault"),
+            PunishmentKind::TdbeEviction => write!(f, "TDBE eviction"),
         }
     }
 }
@@ -358,6 +441,11 @@ pub struct Validator {
     /// last N (10?) used consensus pubkeys/addresses
     #[cfg_attr(not(feature = "mesalock_sgx"), serde(skip))]
     pub used_validator_addresses: Vec<(TendermintValidatorAddress, Timespec)>,
+
+    /// share of the delegators' rewards kept by the council node (at most 1.0),
+    /// set at join (`NodeJoinRequestTx`) and by `NodeMetadataUpdateTx`
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub commission_rate: Milli,
 }
 
 impl Validator {
@@ -369,6 +457,7 @@ impl Validator {
             inactive_time: None,
             inactive_block: None,
             used_validator_addresses: Vec::new(),
+            commission_rate: Milli::default(),
         }
     }
 
@@ -431,21 +520,214 @@ impl Validator {
         assert!(self.is_jailed());
         self.jailed_until = None;
     }
+
+    /// checks if it has none of the fields added after 0.5 (commission rate and p2p node ID)
+    fn has_v05_layout(&self) -> bool {
+        self.commission_rate == Milli::default()
+            && self.council_node.node_info.p2p_node_id.is_none()
+    }
+
+    /// encodes the fields of the 0.5 layout
+    fn encode_v05<W: Output>(&self, dest: &mut W) {
+        self.council_node.encode_v05(dest);
+        self.jailed_until.encode_to(dest);
+        self.inactive_time.encode_to(dest);
+        self.inactive_block.encode_to(dest);
+        self.used_validator_addresses.encode_to(dest);
+    }
+
+    /// decodes the fields of the 0.5 layout
+    fn decode_v05<I: Input>(input: &mut I) -> Result<Self, Error> {
+        Ok(Validator {
+            council_node: CouncilNodeMeta::decode_v05(input)?,
+            jailed_until: Option::decode(input)?,
+            inactive_time: Option::decode(input)?,
+            inactive_block: Option::decode(input)?,
+            used_validator_addresses: Vec::decode(input)?,
+            commission_rate: Milli::default(),
+        })
+    }
 }
 
-/// represents node state metadata
+/// Community node meta
+///
+/// Community nodes don't take part in consensus, so there's no evidence or liveness fault
+/// to punish them for (and they aren't slashed); they are jailed if a council node
+/// evicts them from the TDBE group.
+///
+/// Invariant 1.3:
+///   ```plain
+///   (inactive_time.is_none() && inactive_block.is_none()) ||
+///   (inactive_time.is_some() && inactive_block.is_some())
+///   ```
+///
+/// Invariant 1.4:
+///   `! (is_jailed() && is_active())`
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Encode, Decode)]
 #[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct CommunityNode {
+    /// node metadata (name, contact, keypackage)
+    pub node_info: NodeCommonInfo,
+    /// if jailed, it's specified until what block time
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub jailed_until: Option<Timespec>,
+
+    /// when it became inactive (from block time)
+    pub inactive_time: Option<Timespec>,
+    /// which block it became inactive
+    pub inactive_block: Option<BlockHeight>,
+}
+
+impl CommunityNode {
+    /// creates an active community node
+    pub fn new(node_info: NodeCommonInfo) -> Self {
+        Self {
+            node_info,
+            jailed_until: None,
+            inactive_time: None,
+            inactive_block: None,
+        }
+    }
+
+    /// checks if jailed
+    pub fn is_jailed(&self) -> bool {
+        self.jailed_until.is_some()
+    }
+
+    /// checks if active
+    pub fn is_active(&self) -> bool {
+        self.inactive_time.is_none()
+    }
+
+    /// extra dynamic assertions for fuzzer etc.
+    #[cfg(debug_assertions)]
+    pub fn check_invariants(&self) {
+        // check: Invariant 1.3
+        assert!(
+            (self.inactive_time.is_none() && self.inactive_block.is_none())
+                || (self.inactive_time.is_some() && self.inactive_block.is_some())
+        );
+
+        // check: Invariant 1.4
+        assert!(!(self.is_jailed() && self.is_active()));
+    }
+
+    /// updates this state to be "jailed" (and "inactive" if it's active),
+    /// returns the jailed until time
+    pub fn jail(
+        &mut self,
+        block_time: Timespec,
+        block_height: BlockHeight,
+        jail_duration: Timespec,
+    ) -> Timespec {
+        assert!(!self.is_jailed());
+        let jailed_until = block_time.saturating_add(jail_duration);
+
+        self.jailed_until = Some(jailed_until);
+        if self.is_active() {
+            self.inactivate(block_time, block_height);
+        }
+
+        jailed_until
+    }
+
+    /// updates this state to be "inactive"
+    pub fn inactivate(&mut self, block_time: Timespec, block_height: BlockHeight) {
+        assert!(self.is_active());
+        self.inactive_time = Some(block_time);
+        self.inactive_block = Some(block_height);
+    }
+
+    /// updates this state to be unjailed
+    pub fn unjail(&mut self) {
+        assert!(self.is_jailed());
+        self.jailed_until = None;
+    }
+
+    /// checks if it has none of the fields added after 0.5 (inactivity and p2p node ID)
+    fn has_v05_layout(&self) -> bool {
+        self.is_active() && self.node_info.p2p_node_id.is_none()
+    }
+}
+
+/// represents node state metadata
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
 pub enum NodeState {
     /// information related to council nodes (validator metadata + keypackage from TDBE)
     CouncilNode(Validator),
     /// information related to community nodes (keypackage from TDBE)
-    CommunityNode(NodeCommonInfo),
+    CommunityNode(CommunityNode),
+}
+
+// NOTE: the node states without the fields added after 0.5 are encoded in the 0.5 layout (tags 0 and 1),
+// so that the staking root of the app version 1 networks doesn't change;
+// the tags 2 and 3 are for the node states with these fields.
+// The jailed community nodes have the tag 4 (the community nodes of the tag 3 have no jailing time,
+// so that the staked states stored before it was added don't change)
+impl Encode for NodeState {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        match self {
+            NodeState::CouncilNode(validator) if validator.has_v05_layout() => {
+                dest.push_byte(0);
+                validator.encode_v05(dest);
+            }
+            NodeState::CommunityNode(node) if node.has_v05_layout() => {
+                dest.push_byte(1);
+                node.node_info.encode_v05(dest);
+            }
+            NodeState::CouncilNode(validator) => {
+                dest.push_byte(2);
+                validator.encode_to(dest);
+            }
+            NodeState::CommunityNode(node) if node.is_jailed() => {
+                dest.push_byte(4);
+                node.encode_to(dest);
+            }
+            NodeState::CommunityNode(node) => {
+                dest.push_byte(3);
+                node.node_info.encode_to(dest);
+                node.inactive_time.encode_to(dest);
+                node.inactive_block.encode_to(dest);
+            }
+        }
+    }
+}
+
+impl Decode for NodeState {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let tag = input.read_byte()?;
+        let node_state = match tag {
+            0 => NodeState::CouncilNode(Validator::decode_v05(input)?),
+            1 => NodeState::CommunityNode(CommunityNode::new(NodeCommonInfo::decode_v05(input)?)),
+            2 => NodeState::CouncilNode(Validator::decode(input)?),
+            3 => NodeState::CommunityNode(CommunityNode {
+                node_info: NodeCommonInfo::decode(input)?,
+                jailed_until: None,
+                inactive_time: Option::decode(input)?,
+                inactive_block: Option::decode(input)?,
+            }),
+            4 => match CommunityNode::decode(input)? {
+                node if node.is_jailed() => NodeState::CommunityNode(node),
+                _ => return Err(Error::from("Community node not jailed")),
+            },
+            _ => return Err(Error::from("Invalid NodeState variant")),
+        };
+        // only one encoding of the same node state
+        let v05_layout = match &node_state {
+            NodeState::CouncilNode(validator) => validator.has_v05_layout(),
+            NodeState::CommunityNode(node) => node.has_v05_layout(),
+        };
+        if tag > 1 && v05_layout {
+            return Err(Error::from("Node state not in the 0.5 layout"));
+        }
+        Ok(node_state)
+    }
 }
 
 /// represents the StakedState (account involved in staking)
 /// Invariant 4.1:
-///   - bonded + unbonded <= max supply
+///   - bonded + unbonded + sum(delegators) <= max supply
 ///
 /// Invariant 4.2:
 ///   ```plain
@@ -455,7 +737,23 @@ pub enum NodeState {
 ///       }
 ///   }
 ///   ```
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Encode, Decode)]
+///
+/// Invariant 4.3:
+///   ```plain
+///   if let Some(node) = community_node {
+///       node satisfies Invariants 1.3 and 1.4
+///   }
+///   ```
+///
+/// Invariant 4.4:
+///   ```plain
+///   if let Some(addr) = delegated_to {
+///       node_meta.is_none() && addr != address
+///   }
+///   ```
+///   (and the address is one of the `delegators` of the staked state at `addr`,
+///   which is checked by the staking table)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
 pub struct StakedState {
     /// "from" operations counter
@@ -472,6 +770,75 @@ pub struct StakedState {
     pub node_meta: Option<NodeState>,
     /// record the last slash only for query
     pub last_slash: Option<SlashRecord>,
+    /// the council node this staked state delegates to (the amount is recorded there)
+    pub delegated_to: Option<StakedStateAddress>,
+    /// amounts delegated to this staked state by other staked states
+    pub delegators: BTreeMap<StakedStateAddress, Coin>,
+}
+
+// NOTE: the staked states without delegations are encoded in the 0.5 layout
+// (so that the staking root of the app version 1 networks doesn't change);
+// the tags 2 and 3 of the last slash mark that the delegations follow it
+impl Encode for StakedState {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.nonce.encode_to(dest);
+        self.bonded.encode_to(dest);
+        self.unbonded.encode_to(dest);
+        self.unbonded_from.encode_to(dest);
+        self.address.encode_to(dest);
+        self.node_meta.encode_to(dest);
+        let delegations = self.has_delegations();
+        match (&self.last_slash, delegations) {
+            (None, false) => dest.push_byte(0),
+            (Some(slash), false) => {
+                dest.push_byte(1);
+                slash.encode_to(dest);
+            }
+            (None, true) => dest.push_byte(2),
+            (Some(slash), true) => {
+                dest.push_byte(3);
+                slash.encode_to(dest);
+            }
+        }
+        if delegations {
+            self.delegated_to.encode_to(dest);
+            self.delegators.encode_to(dest);
+        }
+    }
+}
+
+impl Decode for StakedState {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let mut staking = StakedState {
+            nonce: Nonce::decode(input)?,
+            bonded: Coin::decode(input)?,
+            unbonded: Coin::decode(input)?,
+            unbonded_from: Timespec::decode(input)?,
+            address: StakedStateAddress::decode(input)?,
+            node_meta: Option::decode(input)?,
+            last_slash: None,
+            delegated_to: None,
+            delegators: BTreeMap::new(),
+        };
+        let tag = input.read_byte()?;
+        if tag > 3 {
+            return Err(Error::from("Invalid Option<SlashRecord> variant"));
+        }
+        if tag % 2 == 1 {
+            staking.last_slash = Some(SlashRecord::decode(input)?);
+        }
+        if tag > 1 {
+            staking.delegated_to = Option::decode(input)?;
+            staking.delegators = BTreeMap::decode(input)?;
+            // only one encoding of the same staked state
+            if !staking.has_delegations() {
+                return Err(Error::from(
+                    "Staked state without delegations not in the 0.5 layout",
+                ));
+            }
+        }
+        Ok(staking)
+    }
 }
 
 /// the tree used in StakedState storage db has a hardcoded 32-byte keys,
@@ -491,6 +858,16 @@ impl StakedState {
         matches!(&self.node_meta, Some(NodeState::CouncilNode(_x)))
     }
 
+    /// checks if it contains community node metadata
+    pub fn has_community_node_meta(&self) -> bool {
+        matches!(&self.node_meta, Some(NodeState::CommunityNode(_x)))
+    }
+
+    /// checks if it delegates or has delegators
+    pub fn has_delegations(&self) -> bool {
+        self.delegated_to.is_some() || !self.delegators.is_empty()
+    }
+
     /// creates a new StakedState with given parameters
     pub fn new(
         nonce: Nonce,
@@ -508,6 +885,8 @@ impl StakedState {
             address,
             node_meta: validator.map(NodeState::CouncilNode),
             last_slash: None,
+            delegated_to: None,
+            delegators: BTreeMap::new(),
         }
     }
 
@@ -521,6 +900,8 @@ impl StakedState {
             unbonded_from: 0,
             node_meta: None,
             last_slash: None,
+            delegated_to: None,
+            delegators: BTreeMap::new(),
         }
     }
 
@@ -551,17 +932,29 @@ impl StakedState {
         staking
     }
 
+    /// the sum of the amounts delegated to this staked state
+    pub fn delegated(&self) -> Coin {
+        // no panic: Invariant 4.1
+        sum_coins(self.delegators.values().copied()).unwrap()
+    }
+
+    /// the bonded amount plus the delegated amount (determines the voting power of council nodes)
+    pub fn voting_stake(&self) -> Coin {
+        // no panic: Invariant 4.1
+        (self.bonded + self.delegated()).unwrap()
+    }
+
     /// Key of merkle storage
     pub fn key(&self) -> [u8; HASH_SIZE_256] {
         to_stake_key(&self.address)
     }
 
-    /// Return is jailed, non validator default to false.
+    /// Return is jailed, account without node metadata default to false.
     pub fn is_jailed(&self) -> bool {
-        if let Some(NodeState::CouncilNode(v)) = &self.node_meta {
-            v.is_jailed()
-        } else {
-            false
+        match &self.node_meta {
+            Some(NodeState::CouncilNode(v)) => v.is_jailed(),
+            Some(NodeState::CommunityNode(n)) => n.is_jailed(),
+            None => false,
         }
     }
 
@@ -569,7 +962,7 @@ impl StakedState {
     #[cfg(debug_assertions)]
     pub fn check_invariants(&self, minimal_required_staking: Coin) {
         // check: Invariant 4.1
-        (self.bonded + self.unbonded).unwrap();
+        ((self.bonded + self.unbonded).unwrap() + self.delegated()).unwrap();
 
         // check: Invariant 4.2
         if let Some(NodeState::CouncilNode(val)) = &self.node_meta {
@@ -577,6 +970,16 @@ impl StakedState {
                 assert!(self.bonded >= minimal_required_staking && !val.is_jailed());
             }
         }
+
+        // check: Invariant 4.3
+        if let Some(NodeState::CommunityNode(node)) = &self.node_meta {
+            node.check_invariants();
+        }
+
+        // check: Invariant 4.4
+        if let Some(addr) = &self.delegated_to {
+            assert!(self.node_meta.is_none() && addr != &self.address);
+        }
     }
 
     /// Increment nonce by 1
@@ -604,6 +1007,7 @@ mod test {
     use quickcheck::quickcheck;
     use quickcheck::Arbitrary;
     use quickcheck::Gen;
+    use std::convert::TryFrom;
 
     impl Arbitrary for CouncilNodeMeta {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
@@ -618,12 +1022,19 @@ mod test {
             };
             // TODO: generate well-formed keypackage
             let keypackage: Vec<u8> = Vec::arbitrary(g);
-            CouncilNodeMeta::new_with_details(
+            let mut council_node = CouncilNodeMeta::new_with_details(
                 name,
                 security_contact,
                 TendermintValidatorPubKey::Ed25519(raw_pubkey),
                 ConfidentialInit { keypackage },
-            )
+            );
+            if bool::arbitrary(g) {
+                let mut raw_node_id = [0u8; 20];
+                g.fill_bytes(&mut raw_node_id);
+                council_node.node_info.p2p_node_id =
+                    Some(TendermintNodeId::try_from(&raw_node_id[..]).unwrap());
+            }
+            council_node
         }
     }
 
@@ -649,5 +1060,140 @@ mod test {
                 CouncilNodeMeta::decode(&mut encoded.as_ref()).is_err()
             }
         }
+
+        // tests if the node metadata without a p2p node ID keeps the 0.5 TX format
+        fn prop_node_metadata_format(council_node: CouncilNodeMeta) -> bool {
+            let node_meta = NodeMetadata::CouncilNode(council_node.clone());
+            let encoded = node_meta.encode();
+            let decoded = NodeMetadata::decode(&mut encoded.as_ref());
+            if !has_valid_len(&council_node) {
+                decoded.is_err()
+            } else if council_node.node_info.p2p_node_id.is_none() {
+                let mut encoded_v05 = vec![0u8];
+                council_node.encode_v05(&mut encoded_v05);
+                encoded == encoded_v05 && decoded.expect("decode node metadata") == node_meta
+            } else {
+                encoded[0] == 2 && decoded.expect("decode node metadata") == node_meta
+            }
+        }
+    }
+
+    #[test]
+    fn check_node_metadata_without_p2p_node_id_in_new_format() {
+        let mut encoded = vec![3u8];
+        NodeCommonInfo {
+            name: "community".to_owned(),
+            security_contact: None,
+            confidential_init: ConfidentialInit { keypackage: vec![] },
+            p2p_node_id: None,
+        }
+        .encode_to(&mut encoded);
+        assert!(NodeMetadata::decode(&mut encoded.as_ref()).is_err());
+    }
+
+    #[test]
+    fn check_community_node_state_layout() {
+        let mut node = CommunityNode::new(NodeCommonInfo {
+            name: "community".to_owned(),
+            security_contact: None,
+            confidential_init: ConfidentialInit { keypackage: vec![] },
+            p2p_node_id: None,
+        });
+        node.inactivate(10, BlockHeight::new(2));
+
+        // the community nodes which aren't jailed have no jailing time
+        let mut encoded = vec![3u8];
+        node.node_info.encode_to(&mut encoded);
+        (Some(10u64), Some(BlockHeight::new(2))).encode_to(&mut encoded);
+        let node_state = NodeState::CommunityNode(node.clone());
+        assert_eq!(node_state.encode(), encoded);
+        assert_eq!(
+            NodeState::decode(&mut encoded.as_slice()).unwrap(),
+            node_state
+        );
+
+        let mut jailed = CommunityNode::new(node.node_info.clone());
+        assert_eq!(jailed.jail(10, BlockHeight::new(2), 5), 15);
+        assert!(!jailed.is_active());
+        let node_state = NodeState::CommunityNode(jailed.clone());
+        let encoded = node_state.encode();
+        assert_eq!(encoded[0], 4);
+        assert_eq!(
+            NodeState::decode(&mut encoded.as_slice()).unwrap(),
+            node_state
+        );
+
+        // only one encoding of the same node state
+        let mut encoded = vec![4u8];
+        node.encode_to(&mut encoded);
+        assert!(NodeState::decode(&mut encoded.as_slice()).is_err());
+    }
+
+    #[test]
+    fn check_staked_state_layout() {
+        let address = StakedStateAddress::BasicRedeem([0x01; 20].into());
+        let council_node = CouncilNodeMeta::new_with_details(
+            "council".to_owned(),
+            None,
+            TendermintValidatorPubKey::Ed25519([0x02; 32]),
+            ConfidentialInit { keypackage: vec![] },
+        );
+        let slash = SlashRecord {
+            kind: PunishmentKind::NonLive,
+            time: 10,
+            amount: Coin::one(),
+        };
+        let mut staking = StakedState::new(
+            1,
+            Coin::one(),
+            Coin::zero(),
+            0,
+            address,
+            Some(Validator::new(council_node.clone())),
+        );
+        staking.last_slash = Some(slash.clone());
+
+        // the staked states a 0.5 network can have keep its layout
+        let mut encoded_v05 = (1u64, Coin::one(), Coin::zero(), 0u64, address).encode();
+        encoded_v05.extend_from_slice(&[1, 0]);
+        council_node.encode_v05(&mut encoded_v05);
+        (
+            Option::<Timespec>::None,
+            Option::<Timespec>::None,
+            Option::<BlockHeight>::None,
+            Vec::<(TendermintValidatorAddress, Timespec)>::new(),
+        )
+            .encode_to(&mut encoded_v05);
+        encoded_v05.push(1);
+        slash.encode_to(&mut encoded_v05);
+        assert_eq!(staking.encode(), encoded_v05);
+        assert_eq!(
+            StakedState::decode(&mut encoded_v05.as_slice()).unwrap(),
+            staking
+        );
+
+        // the fields added later
+        if let Some(NodeState::CouncilNode(validator)) = staking.node_meta.as_mut() {
+            validator.commission_rate = Milli::new(0, 100);
+        }
+        staking.delegators.insert(
+            StakedStateAddress::BasicRedeem([0x03; 20].into()),
+            Coin::one(),
+        );
+        let encoded = staking.encode();
+        assert_eq!(
+            StakedState::decode(&mut encoded.as_slice()).unwrap(),
+            staking
+        );
+
+        // only one encoding of the same staked state
+        let mut encoded = StakedState::default(address).encode();
+        *encoded.last_mut().unwrap() = 2;
+        (
+            Option::<StakedStateAddress>::None,
+            BTreeMap::<StakedStateAddress, Coin>::new(),
+        )
+            .encode_to(&mut encoded);
+        assert!(StakedState::decode(&mut encoded.as_slice()).is_err());
     }
 }
@@ -0,0 +1,69 @@
+use crate::common::Timespec;
+use crate::init::coin::Coin;
+use crate::state::account::PunishmentKind;
+use crate::state::tendermint::BlockHeight;
+use parity_scale_codec::{Decode, Encode};
+#[cfg(not(feature = "mesalock_sgx"))]
+use serde::{Deserialize, Serialize};
+use std::prelude::v1::Vec;
+
+/// Punishment or reward of a staked state
+/// (the other changes are in the transactions)
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub enum StakingHistoryEvent {
+    /// the council node was jailed
+    Jail {
+        /// the node can be unjailed after this time
+        jailed_until: Timespec,
+        /// why
+        kind: PunishmentKind,
+    },
+    /// the council node was unjailed
+    Unjail,
+    /// the punished council node's bonded and unbonded coins were slashed
+    Slash {
+        /// slashed from the bonded amount
+        bonded: Coin,
+        /// slashed from the unbonded amount
+        unbonded: Coin,
+        /// why
+        kind: PunishmentKind,
+    },
+    /// the coins delegated to a punished council node were slashed
+    SlashDelegated {
+        /// slashed from the delegated amount
+        amount: Coin,
+        /// why
+        kind: PunishmentKind,
+    },
+    /// rewards of the period were distributed (added to the bonded amount)
+    Reward {
+        /// how much
+        amount: Coin,
+    },
+}
+
+/// Staking history event in a block
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct StakingHistoryEntry {
+    /// height of the block
+    pub block_height: BlockHeight,
+    /// time of the block
+    pub block_time: Timespec,
+    /// what happened
+    pub event: StakingHistoryEvent,
+}
+
+/// A range of the staking history of an address (oldest first)
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, Default)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct StakingHistoryPage {
+    /// number of all the recorded events of the address
+    pub total: u64,
+    /// index of the first returned event
+    pub start: u64,
+    /// the returned events
+    pub entries: Vec<StakingHistoryEntry>,
+}
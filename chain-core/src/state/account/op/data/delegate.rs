@@ -0,0 +1,165 @@
+use crate::init::coin::Coin;
+use crate::state::account::address::StakedStateAddress;
+use crate::state::account::op::data::attribute::StakedStateOpAttributes;
+use crate::state::account::Nonce;
+use crate::tx::TransactionId;
+use parity_scale_codec::{Decode, Encode, Error, Input, Output};
+#[cfg(not(feature = "mesalock_sgx"))]
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::fmt;
+
+/// updates the StakedState by moving some of the bonded amount to the council node,
+/// where it counts toward the voting power (and rewards are shared back minus the commission)
+/// -- only one council node can be delegated to at a time
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct DelegateTx {
+    /// which (staking) state to delegate from
+    pub from_staked_account: StakedStateAddress,
+    /// the (staking) state of the council node to delegate to
+    pub to_council_node: StakedStateAddress,
+    /// expected counter to check against
+    pub nonce: Nonce,
+    /// amount to delegate
+    pub value: Coin,
+    /// versioning info etc.
+    pub attributes: StakedStateOpAttributes,
+}
+
+impl Decode for DelegateTx {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let from_staked_account = StakedStateAddress::decode(input)?;
+        let to_council_node = StakedStateAddress::decode(input)?;
+        let nonce = Nonce::decode(input)?;
+        let value = Coin::decode(input)?;
+        let attributes = StakedStateOpAttributes::decode(input)?;
+
+        Ok(DelegateTx {
+            from_staked_account,
+            to_council_node,
+            nonce,
+            value,
+            attributes,
+        })
+    }
+}
+
+impl Encode for DelegateTx {
+    fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
+        dest.push(&self.from_staked_account);
+        dest.push(&self.to_council_node);
+        dest.push(&self.nonce);
+        dest.push(&self.value);
+        dest.push(&self.attributes);
+    }
+
+    fn size_hint(&self) -> usize {
+        self.from_staked_account.size_hint()
+            + self.to_council_node.size_hint()
+            + self.nonce.size_hint()
+            + self.value.size_hint()
+            + self.attributes.size_hint()
+    }
+}
+
+impl TransactionId for DelegateTx {}
+
+impl DelegateTx {
+    /// creates a new tx to delegate certain amount
+    pub fn new(
+        from_staked_account: StakedStateAddress,
+        to_council_node: StakedStateAddress,
+        nonce: Nonce,
+        value: Coin,
+        attributes: StakedStateOpAttributes,
+    ) -> Self {
+        DelegateTx {
+            from_staked_account,
+            to_council_node,
+            nonce,
+            value,
+            attributes,
+        }
+    }
+}
+
+#[cfg(not(feature = "mesalock_sgx"))]
+impl fmt::Display for DelegateTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} delegated: {} to {} (nonce: {})",
+            self.from_staked_account, self.value, self.to_council_node, self.nonce
+        )?;
+        write!(f, "")
+    }
+}
+
+/// updates the StakedState by taking back the whole delegated amount into unbonded,
+/// and setting the unbonded_from to last_block_time+min_unbonding_time (network parameter)
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct UndelegateTx {
+    /// which (staking) state to undelegate from
+    pub from_staked_account: StakedStateAddress,
+    /// expected counter to check against
+    pub nonce: Nonce,
+    /// versioning info etc.
+    pub attributes: StakedStateOpAttributes,
+}
+
+impl Decode for UndelegateTx {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let from_staked_account = StakedStateAddress::decode(input)?;
+        let nonce = Nonce::decode(input)?;
+        let attributes = StakedStateOpAttributes::decode(input)?;
+
+        Ok(UndelegateTx {
+            from_staked_account,
+            nonce,
+            attributes,
+        })
+    }
+}
+
+impl Encode for UndelegateTx {
+    fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
+        dest.push(&self.from_staked_account);
+        dest.push(&self.nonce);
+        dest.push(&self.attributes);
+    }
+
+    fn size_hint(&self) -> usize {
+        self.from_staked_account.size_hint() + self.nonce.size_hint() + self.attributes.size_hint()
+    }
+}
+
+impl TransactionId for UndelegateTx {}
+
+impl UndelegateTx {
+    /// creates a new tx to take back the delegated amount
+    pub fn new(
+        from_staked_account: StakedStateAddress,
+        nonce: Nonce,
+        attributes: StakedStateOpAttributes,
+    ) -> Self {
+        UndelegateTx {
+            from_staked_account,
+            nonce,
+            attributes,
+        }
+    }
+}
+
+#[cfg(not(feature = "mesalock_sgx"))]
+impl fmt::Display for UndelegateTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} undelegated (nonce: {})",
+            self.from_staked_account, self.nonce
+        )?;
+        write!(f, "")
+    }
+}
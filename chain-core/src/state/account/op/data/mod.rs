@@ -1,5 +1,7 @@
 /// versioning info etc.
 pub mod attribute;
+/// delegate and undelegate stake transactions
+pub mod delegate;
 /// deposit transaction
 pub mod deposit;
 /// unbond stake transaction
@@ -0,0 +1,443 @@
+use crate::init::coin::Coin;
+use crate::init::params::{
+    InitNetworkParameters, JailingParameters, RewardsParameters, SlashingParameters,
+};
+use crate::state::account::{Nonce, StakedStateAddress, StakedStateOpAttributes};
+use crate::state::tendermint::BlockHeight;
+use crate::tx::data::TxId;
+use crate::tx::fee::FeePolicy;
+use crate::tx::TransactionId;
+use parity_scale_codec::{Decode, Encode, Error, Input, Output};
+#[cfg(not(feature = "mesalock_sgx"))]
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::fmt;
+use std::prelude::v1::{String, Vec};
+
+/// Identifier of a network parameter change proposal (the proposal tx id)
+pub type ProposalId = TxId;
+
+/// A coordinated network upgrade: the binaries which don't support `app_version`
+/// stop at `height`, and the ones which do continue with it from there
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct UpgradePlan {
+    /// human-readable name of the upgrade (e.g. the release)
+    pub name: String,
+    /// the first block processed with the new app version
+    pub height: BlockHeight,
+    /// the new app version
+    pub app_version: u64,
+}
+
+impl UpgradePlan {
+    /// checks if the plan can be scheduled:
+    /// the upgrade can't happen before the plan is approved
+    /// and it needs to bump the currently active app version
+    pub fn check(
+        &self,
+        approval_height: BlockHeight,
+        current_app_version: u64,
+    ) -> Result<(), &'static str> {
+        if self.name.is_empty() {
+            return Err("upgrade name can't be empty");
+        }
+        if self.height <= approval_height {
+            return Err("upgrade height needs to be after the approval");
+        }
+        if self.app_version <= current_app_version {
+            return Err("upgrade needs to increase the app version");
+        }
+        Ok(())
+    }
+}
+
+/// A change of one of the network parameters
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub enum NetworkParameterUpdate {
+    /// new fee policy
+    FeePolicy(FeePolicy),
+    /// new minimal council node stake
+    RequiredCouncilNodeStake(Coin),
+    /// new minimal community node stake
+    RequiredCommunityNodeStake(Coin),
+    /// new jailing configuration
+    JailingConfig(JailingParameters),
+    /// new slashing configuration
+    SlashingConfig(SlashingParameters),
+    /// new rewards configuration
+    RewardsConfig(RewardsParameters),
+    /// new cap on validators in tendermint
+    MaxValidators(u16),
+    /// scheduled network upgrade (it replaces any previously scheduled one)
+    ScheduleUpgrade(UpgradePlan),
+}
+
+impl NetworkParameterUpdate {
+    /// checks if the new value is acceptable
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self {
+            NetworkParameterUpdate::RequiredCouncilNodeStake(stake)
+            | NetworkParameterUpdate::RequiredCommunityNodeStake(stake) => {
+                if *stake == Coin::zero() {
+                    return Err("required node stake can't be zero");
+                }
+            }
+            NetworkParameterUpdate::JailingConfig(config) => {
+                if config.block_signing_window == 0 {
+                    return Err("block signing window can't be zero");
+                }
+                if config.missed_block_threshold > config.block_signing_window {
+                    return Err("missed block threshold can't exceed block signing window");
+                }
+            }
+            NetworkParameterUpdate::RewardsConfig(config) => {
+                config.validate()?;
+            }
+            NetworkParameterUpdate::MaxValidators(max_validators) => {
+                if *max_validators == 0 {
+                    return Err("max validators can't be zero");
+                }
+            }
+            NetworkParameterUpdate::FeePolicy(fee_policy) => {
+                fee_policy.validate()?;
+            }
+            NetworkParameterUpdate::ScheduleUpgrade(plan) => {
+                if plan.name.is_empty() {
+                    return Err("upgrade name can't be empty");
+                }
+            }
+            NetworkParameterUpdate::SlashingConfig(config) => {
+                config.validate()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// replaces the corresponding value in the parameters
+    pub fn apply(&self, params: &mut InitNetworkParameters) {
+        match self {
+            NetworkParameterUpdate::FeePolicy(fee_policy) => {
+                params.initial_fee_policy = *fee_policy;
+            }
+            NetworkParameterUpdate::RequiredCouncilNodeStake(stake) => {
+                params.required_council_node_stake = *stake;
+            }
+            NetworkParameterUpdate::RequiredCommunityNodeStake(stake) => {
+                params.required_community_node_stake = *stake;
+            }
+            NetworkParameterUpdate::JailingConfig(config) => {
+                params.jailing_config = *config;
+            }
+            NetworkParameterUpdate::SlashingConfig(config) => {
+                params.slashing_config = *config;
+            }
+            NetworkParameterUpdate::RewardsConfig(config) => {
+                params.rewards_config = *config;
+            }
+            NetworkParameterUpdate::MaxValidators(max_validators) => {
+                params.max_validators = *max_validators;
+            }
+            // not a parameter: the plan is kept in the node state until the upgrade height
+            NetworkParameterUpdate::ScheduleUpgrade(_) => {}
+        }
+    }
+}
+
+/// Submits a proposal to change the network parameters:
+/// if it's approved by the bonded stake at the activation height,
+/// the changes take effect from the next block.
+///
+/// tx-validation should check that:
+/// - the proposer is an active council node
+/// - the changes are not empty and valid
+/// - the activation height is in the future
+/// - the witness is correct
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct ParamChangeProposalTx {
+    /// the expected nonce on the corresponding state
+    pub nonce: Nonce,
+    /// the expected address on the corresponding state (proposer)
+    pub address: StakedStateAddress,
+    /// the versioning and network identifier
+    pub attributes: StakedStateOpAttributes,
+    /// the proposed changes (applied in order)
+    pub changes: Vec<NetworkParameterUpdate>,
+    /// the height at which votes are tallied (and the changes are activated if approved)
+    pub activation_height: BlockHeight,
+}
+
+impl Decode for ParamChangeProposalTx {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let nonce = Nonce::decode(input)?;
+        let address = StakedStateAddress::decode(input)?;
+        let attributes = StakedStateOpAttributes::decode(input)?;
+        let changes = Vec::decode(input)?;
+        let activation_height = BlockHeight::decode(input)?;
+
+        Ok(ParamChangeProposalTx {
+            nonce,
+            address,
+            attributes,
+            changes,
+            activation_height,
+        })
+    }
+}
+
+impl Encode for ParamChangeProposalTx {
+    fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
+        dest.push(&self.nonce);
+        dest.push(&self.address);
+        dest.push(&self.attributes);
+        dest.push(&self.changes);
+        dest.push(&self.activation_height);
+    }
+
+    fn size_hint(&self) -> usize {
+        self.nonce.size_hint()
+            + self.address.size_hint()
+            + self.attributes.size_hint()
+            + self.changes.size_hint()
+            + self.activation_height.size_hint()
+    }
+}
+
+impl TransactionId for ParamChangeProposalTx {}
+
+impl ParamChangeProposalTx {
+    /// constructs a new proposal transaction from the provided components
+    #[inline]
+    pub fn new(
+        nonce: Nonce,
+        address: StakedStateAddress,
+        attributes: StakedStateOpAttributes,
+        changes: Vec<NetworkParameterUpdate>,
+        activation_height: BlockHeight,
+    ) -> Self {
+        Self {
+            nonce,
+            address,
+            attributes,
+            changes,
+            activation_height,
+        }
+    }
+}
+
+#[cfg(not(feature = "mesalock_sgx"))]
+impl fmt::Display for ParamChangeProposalTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "proposed by: {} (nonce: {})", self.address, self.nonce)?;
+        writeln!(f, "activation height: {}", self.activation_height)?;
+        for change in self.changes.iter() {
+            writeln!(f, "-> {:?}", change)?;
+        }
+        write!(f, "")
+    }
+}
+
+/// Votes on a network parameter change proposal
+/// (weighted by the bonded + delegated amount of the staked state at the activation height;
+/// a later vote from the same staked state replaces the earlier one)
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct ParamChangeVoteTx {
+    /// the expected nonce on the corresponding state
+    pub nonce: Nonce,
+    /// the expected address on the corresponding state (voter)
+    pub address: StakedStateAddress,
+    /// the versioning and network identifier
+    pub attributes: StakedStateOpAttributes,
+    /// the proposal being voted on
+    pub proposal_id: ProposalId,
+    /// true if in favor of the proposal
+    pub approve: bool,
+}
+
+impl Decode for ParamChangeVoteTx {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let nonce = Nonce::decode(input)?;
+        let address = StakedStateAddress::decode(input)?;
+        let attributes = StakedStateOpAttributes::decode(input)?;
+        let proposal_id = ProposalId::decode(input)?;
+        let approve = bool::decode(input)?;
+
+        Ok(ParamChangeVoteTx {
+            nonce,
+            address,
+            attributes,
+            proposal_id,
+            approve,
+        })
+    }
+}
+
+impl Encode for ParamChangeVoteTx {
+    fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
+        dest.push(&self.nonce);
+        dest.push(&self.address);
+        dest.push(&self.attributes);
+        dest.push(&self.proposal_id);
+        dest.push(&self.approve);
+    }
+
+    fn size_hint(&self) -> usize {
+        self.nonce.size_hint()
+            + self.address.size_hint()
+            + self.attributes.size_hint()
+            + self.proposal_id.size_hint()
+            + self.approve.size_hint()
+    }
+}
+
+impl TransactionId for ParamChangeVoteTx {}
+
+impl ParamChangeVoteTx {
+    /// constructs a new vote transaction from the provided components
+    #[inline]
+    pub fn new(
+        nonce: Nonce,
+        address: StakedStateAddress,
+        attributes: StakedStateOpAttributes,
+        proposal_id: ProposalId,
+        approve: bool,
+    ) -> Self {
+        Self {
+            nonce,
+            address,
+            attributes,
+            proposal_id,
+            approve,
+        }
+    }
+}
+
+#[cfg(not(feature = "mesalock_sgx"))]
+impl fmt::Display for ParamChangeVoteTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} voted {} on proposal {} (nonce: {})",
+            self.address,
+            if self.approve { "yes" } else { "no" },
+            hex::encode(&self.proposal_id),
+            self.nonce
+        )?;
+        write!(f, "")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::init::address::RedeemAddress;
+    use crate::init::params::SlashRatio;
+    use crate::tx::fee::{CongestionFee, LinearFee, Milli};
+    use std::str::FromStr;
+
+    #[test]
+    fn proposal_encode_decode() {
+        let address = StakedStateAddress::BasicRedeem(
+            RedeemAddress::from_str("0x0e7c045110b8dbf29765047380898919c5cb56f4").unwrap(),
+        );
+        let tx = ParamChangeProposalTx::new(
+            1,
+            address,
+            StakedStateOpAttributes::new(0xab),
+            vec![
+                NetworkParameterUpdate::MaxValidators(10),
+                NetworkParameterUpdate::RequiredCouncilNodeStake(Coin::unit()),
+            ],
+            BlockHeight::new(100),
+        );
+        let encoded = tx.encode();
+        assert_eq!(
+            ParamChangeProposalTx::decode(&mut encoded.as_slice()).unwrap(),
+            tx
+        );
+
+        let vote = ParamChangeVoteTx::new(
+            2,
+            address,
+            StakedStateOpAttributes::new(0xab),
+            tx.id(),
+            true,
+        );
+        let encoded = vote.encode();
+        assert_eq!(
+            ParamChangeVoteTx::decode(&mut encoded.as_slice()).unwrap(),
+            vote
+        );
+    }
+
+    #[test]
+    fn validate_parameter_updates() {
+        assert!(NetworkParameterUpdate::MaxValidators(0).validate().is_err());
+        assert!(NetworkParameterUpdate::MaxValidators(1).validate().is_ok());
+        assert!(
+            NetworkParameterUpdate::RequiredCommunityNodeStake(Coin::zero())
+                .validate()
+                .is_err()
+        );
+        assert!(NetworkParameterUpdate::JailingConfig(JailingParameters {
+            block_signing_window: 10,
+            missed_block_threshold: 11,
+        })
+        .validate()
+        .is_err());
+        assert!(
+            NetworkParameterUpdate::FeePolicy(FeePolicy::Congestion(CongestionFee {
+                base: LinearFee::new(Milli::new(1, 0), Milli::new(1, 0)),
+                minimal_fee: Milli::new(1, 0),
+                target_block_size: 0,
+                max_multiplier: Milli::new(2, 0),
+            }))
+            .validate()
+            .is_err()
+        );
+        // not checked when decoded
+        let over_one = SlashRatio::decode(&mut Milli::new(1, 1).encode().as_slice()).unwrap();
+        let slashing = SlashingParameters {
+            liveness_slash_percent: SlashRatio::from_str("0.1").unwrap(),
+            byzantine_slash_percent: SlashRatio::from_str("1.0").unwrap(),
+        };
+        assert!(NetworkParameterUpdate::SlashingConfig(slashing)
+            .validate()
+            .is_ok());
+        assert!(NetworkParameterUpdate::SlashingConfig(SlashingParameters {
+            byzantine_slash_percent: over_one,
+            ..slashing
+        })
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn check_upgrade_plan() {
+        let plan = UpgradePlan {
+            name: "v0.6".to_owned(),
+            height: BlockHeight::new(100),
+            app_version: crate::APP_VERSION + 1,
+        };
+        assert!(plan.check(BlockHeight::new(99), crate::APP_VERSION).is_ok());
+        assert!(plan
+            .check(BlockHeight::new(100), crate::APP_VERSION)
+            .is_err());
+        assert!(plan
+            .check(BlockHeight::new(99), crate::APP_VERSION + 1)
+            .is_err());
+        let unnamed = UpgradePlan {
+            name: String::new(),
+            ..plan
+        };
+        assert!(unnamed
+            .check(BlockHeight::new(99), crate::APP_VERSION)
+            .is_err());
+        assert!(NetworkParameterUpdate::ScheduleUpgrade(unnamed)
+            .validate()
+            .is_err());
+    }
+}
@@ -1,5 +1,7 @@
 /// data types related to staked state operations
 pub mod account;
+/// data types related to on-chain governance (network parameter change proposals and votes)
+pub mod governance;
 /// data types related to working with Tendermint
 pub mod tendermint;
 /// data types related to council node operations in staked state (nodejoin and unjail)
@@ -12,10 +14,11 @@ use std::prelude::v1::Vec;
 
 use self::tendermint::BlockHeight;
 use crate::common::{MerkleTree, Timespec, H256};
-use crate::compute_app_hash;
 use crate::init::coin::Coin;
 use crate::init::params::NetworkParameters;
 use crate::tx::data::TxId;
+use crate::tx::fee::BlockFullness;
+use crate::{compute_app_hash, AppHashParts};
 
 /// ABCI chain state
 #[derive(PartialEq, Debug, Clone, Encode, Decode)]
@@ -27,18 +30,93 @@ pub struct ChainState {
     pub rewards_pool: RewardsPoolState,
     /// network parameters (fee policy, staking configuration etc.)
     pub network_params: NetworkParameters,
+    /// recent block sizes (for the congestion fee policy)
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub block_fullness: BlockFullness,
+    /// hash of the TDBE MLS group state after the last MLS handshake
+    /// (zero for the genesis group, which is derived from the genesis council nodes in the staking trie)
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub tdbe_group_hash: H256,
+    /// hash of the open network parameter change proposals and their votes
+    /// (zero if there are none, as in genesis)
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub proposals_hash: H256,
+    /// app version active on the network (bumped by the scheduled upgrades),
+    /// it determines which parts the app hash is computed from
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default = "default_app_version"))]
+    pub app_version: u64,
+    /// root of the sparse merkle trie of the spent transaction outputs
+    /// (zero if none were spent, as in genesis)
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub spent_outputs_root: H256,
+}
+
+/// the states before the app version was recorded in them were produced by app version 1
+#[cfg(not(feature = "mesalock_sgx"))]
+fn default_app_version() -> u64 {
+    1
 }
 
 impl ChainState {
+    /// the state in genesis
+    pub fn genesis(
+        account_root: H256,
+        rewards_pool: RewardsPoolState,
+        network_params: NetworkParameters,
+        app_version: u64,
+    ) -> Self {
+        ChainState {
+            account_root,
+            rewards_pool,
+            network_params,
+            block_fullness: BlockFullness::default(),
+            tdbe_group_hash: H256::default(),
+            proposals_hash: H256::default(),
+            app_version,
+            spent_outputs_root: H256::default(),
+        }
+    }
+
+    /// hash of the consensus state which isn't committed by the other parts of the app hash
+    /// (currently: blake3(scale bytes(TDBE group hash, proposals hash, block fullness)));
+    /// it's only a part of the app hash since app version 2
+    pub fn node_state_hash(&self) -> Option<H256> {
+        if self.app_version < 2 {
+            return None;
+        }
+        Some(
+            blake3::hash(
+                &(
+                    self.tdbe_group_hash,
+                    self.proposals_hash,
+                    self.block_fullness,
+                )
+                    .encode(),
+            )
+            .into(),
+        )
+    }
+
+    /// the parts of the app hash given the root of the valid TX merkle tree
+    pub fn app_hash_parts(&self, valid_tx_root: H256) -> AppHashParts {
+        AppHashParts {
+            valid_tx_root,
+            account_root: self.account_root,
+            rewards_pool_hash: self.rewards_pool.hash(self.app_version),
+            network_params_hash: self.network_params.hash(self.app_version),
+            node_state_hash: self.node_state_hash(),
+            spent_outputs_root: if self.app_version < 2 {
+                None
+            } else {
+                Some(self.spent_outputs_root)
+            },
+        }
+    }
+
     /// computes the app hash based on the internal parameters
     /// identifiers of valid transactions in a given block
     pub fn compute_app_hash(&self, txids: Vec<TxId>) -> H256 {
-        compute_app_hash(
-            &MerkleTree::new(txids),
-            &self.account_root,
-            &self.rewards_pool,
-            &self.network_params,
-        )
+        compute_app_hash(&MerkleTree::new(txids), self)
     }
 }
 
@@ -56,11 +134,34 @@ pub struct RewardsPoolState {
     pub minted: Coin,
     /// Parameter in monetary expansion formula, decayed for each rewards distribution
     pub tau: u64,
+    /// Total amount of the burned transaction fees
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub fees_burned: Coin,
+    /// Total amount of the transaction fees credited to the treasury
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub fees_to_treasury: Coin,
+    /// Total amount of the transaction fees added to the rewards pool
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub fees_to_rewards_pool: Coin,
 }
 
 impl RewardsPoolState {
-    /// retrieves the hash of the current state (currently blake3(scale_code_bytes(rewards_pool_state)))
-    pub fn hash(&self) -> H256 {
+    /// retrieves the hash of the current state (currently blake3(scale_code_bytes(rewards_pool_state)));
+    /// before app version 2, the fee totals (added in it) aren't hashed, as in the 0.5 layout
+    pub fn hash(&self, app_version: u64) -> H256 {
+        if app_version < 2 {
+            return blake3::hash(
+                &(
+                    self.period_bonus,
+                    self.last_block_height,
+                    self.last_distribution_time,
+                    self.minted,
+                    self.tau,
+                )
+                    .encode(),
+            )
+            .into();
+        }
         blake3::hash(&self.encode()).into()
     }
 
@@ -73,6 +174,9 @@ impl RewardsPoolState {
             last_distribution_time: genesis_time,
             minted: Coin::zero(),
             tau,
+            fees_burned: Coin::zero(),
+            fees_to_treasury: Coin::zero(),
+            fees_to_rewards_pool: Coin::zero(),
         }
     }
 }
@@ -15,7 +15,7 @@ use std::prelude::v1::{String, ToString, Vec};
 use thiserror::Error;
 
 /// Tendermint block height
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Default)]
 #[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
 #[cfg_attr(not(feature = "mesalock_sgx"), serde(transparent))]
 pub struct BlockHeight(u64);
@@ -403,6 +403,10 @@ impl TryFrom<&[u8]> for TendermintValidatorAddress {
     }
 }
 
+/// Tendermint p2p node ID: the address of the node key (in `node_key.json`),
+/// in the same format as the validator address
+pub type TendermintNodeId = TendermintValidatorAddress;
+
 /// "Note that the maximum total power of the validator set is bounded by MaxTotalVotingPower = MaxInt64 / 1000.
 /// 1000 is chosen because we want to be able to do fixed point arithmetic operations on `TendermintVotePower` using `Milli`.
 /// Applications are responsible for ensuring they do not make changes to the validator set that cause it to exceed this limit."
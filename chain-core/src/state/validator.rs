@@ -1,5 +1,9 @@
+mod leave;
 mod nodejoin;
 mod unjail;
+mod update;
 
+pub use leave::NodeLeaveTx;
 pub use nodejoin::NodeJoinRequestTx;
 pub use unjail::UnjailTx;
+pub use update::NodeMetadataUpdateTx;
@@ -0,0 +1,77 @@
+use crate::state::account::{Nonce, StakedStateAddress, StakedStateOpAttributes};
+use crate::tx::TransactionId;
+use parity_scale_codec::{Decode, Encode, Error, Input, Output};
+#[cfg(not(feature = "mesalock_sgx"))]
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::fmt;
+
+/// Voluntarily stops an active node (community or council node):
+/// the node is inactivated without punishment,
+/// and its record is cleaned up after the unbonding period (the bonded amount is kept).
+///
+/// tx-validation should check that:
+/// - the associated staked state has an active node record and is not jailed
+/// - the witness is correct
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct NodeLeaveTx {
+    /// the expected nonce on the corresponding state
+    pub nonce: Nonce,
+    /// the expected address on the corresponding state
+    pub address: StakedStateAddress,
+    /// the versioning and network identifier
+    pub attributes: StakedStateOpAttributes,
+}
+
+impl Decode for NodeLeaveTx {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let nonce = Nonce::decode(input)?;
+        let address = StakedStateAddress::decode(input)?;
+        let attributes = StakedStateOpAttributes::decode(input)?;
+
+        Ok(NodeLeaveTx {
+            nonce,
+            address,
+            attributes,
+        })
+    }
+}
+
+impl Encode for NodeLeaveTx {
+    fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
+        dest.push(&self.nonce);
+        dest.push(&self.address);
+        dest.push(&self.attributes);
+    }
+
+    fn size_hint(&self) -> usize {
+        self.nonce.size_hint() + self.address.size_hint() + self.attributes.size_hint()
+    }
+}
+
+impl TransactionId for NodeLeaveTx {}
+
+impl NodeLeaveTx {
+    /// constructs a new node leave transaction from the provided components
+    #[inline]
+    pub fn new(
+        nonce: Nonce,
+        address: StakedStateAddress,
+        attributes: StakedStateOpAttributes,
+    ) -> Self {
+        Self {
+            nonce,
+            address,
+            attributes,
+        }
+    }
+}
+
+#[cfg(not(feature = "mesalock_sgx"))]
+impl fmt::Display for NodeLeaveTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "node left: {} (nonce: {})", self.address, self.nonce)?;
+        write!(f, "")
+    }
+}
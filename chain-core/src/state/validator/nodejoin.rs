@@ -1,4 +1,8 @@
-use crate::state::account::{NodeMetadata, Nonce, StakedStateAddress, StakedStateOpAttributes};
+use crate::state::account::{
+    NodeMetadata, Nonce, StakedStateAddress, StakedStateOpAttributes, MAX_COMMISSION_RATE_MILLIS,
+};
+use crate::tx::data::{txid_hash, TxId};
+use crate::tx::fee::Milli;
 use crate::tx::TransactionId;
 use parity_scale_codec::{Decode, Encode, Error, Input, Output};
 #[cfg(not(feature = "mesalock_sgx"))]
@@ -17,10 +21,17 @@ use std::fmt;
 /// - the address and the consensus_pubkey are not used
 /// - the associated staked state is ok (not jailed etc.)
 /// - the bonded amount in the stake state is more than the minimal required one
+/// - the commission rate is not over 1.0
 /// - the witness is correct
 ///
 /// # community node:
-/// FIXME
+/// the node is tracked (and its keypackage can be added to the TDBE group),
+/// but it doesn't become a validator.
+///
+/// tx-validation should check that:
+/// - the associated staked state is ok (not jailed, not already a council node etc.)
+/// - the bonded amount in the stake state is more than the minimal required one for community nodes
+/// - the witness is correct
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
 pub struct NodeJoinRequestTx {
@@ -33,6 +44,11 @@ pub struct NodeJoinRequestTx {
     /// node information, both consensus critical (validator pubkey...)
     /// as well as informational (security contact...)
     pub node_meta: NodeMetadata,
+    /// share of the delegators' rewards kept by the council node
+    /// (not used by the community nodes, which have no delegators);
+    /// it's only missing in the requests of app version 1 (0.5), which join with no commission
+    #[cfg_attr(not(feature = "mesalock_sgx"), serde(default))]
+    pub commission_rate: Option<Milli>,
 }
 
 impl Decode for NodeJoinRequestTx {
@@ -41,19 +57,37 @@ impl Decode for NodeJoinRequestTx {
         let address = StakedStateAddress::decode(input)?;
         let attributes = StakedStateOpAttributes::decode(input)?;
         let node_meta = NodeMetadata::decode(input)?;
+        let commission_rate = Option::decode(input)?;
 
         Ok(NodeJoinRequestTx {
             nonce,
             address,
             attributes,
             node_meta,
+            commission_rate,
         })
     }
 }
 
-// TODO: size hint as node_meta needs more info
-impl Encode for NodeJoinRequestTx {
-    fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
+impl NodeJoinRequestTx {
+    /// decodes the request in the app version 1 (0.5) TX format
+    pub fn decode_v1<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let nonce = Nonce::decode(input)?;
+        let address = StakedStateAddress::decode(input)?;
+        let attributes = StakedStateOpAttributes::decode(input)?;
+        let node_meta = NodeMetadata::decode_v1(input)?;
+
+        Ok(NodeJoinRequestTx {
+            nonce,
+            address,
+            attributes,
+            node_meta,
+            commission_rate: None,
+        })
+    }
+
+    /// encodes the fields of the app version 1 (0.5) TX format (without the commission rate)
+    pub(crate) fn encode_v1<EncOut: Output>(&self, dest: &mut EncOut) {
         dest.push(&self.nonce);
         dest.push(&self.address);
         dest.push(&self.attributes);
@@ -61,7 +95,27 @@ impl Encode for NodeJoinRequestTx {
     }
 }
 
-impl TransactionId for NodeJoinRequestTx {}
+// TODO: size hint as node_meta needs more info
+impl Encode for NodeJoinRequestTx {
+    fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
+        self.encode_v1(dest);
+        dest.push(&self.commission_rate);
+    }
+}
+
+// NOTE: the requests without the commission rate keep the TX ID of the 0.5 TX format
+// (so that the witnesses of the app version 1 requests stay valid)
+impl TransactionId for NodeJoinRequestTx {
+    fn id(&self) -> TxId {
+        if self.commission_rate.is_some() {
+            txid_hash(&self.encode())
+        } else {
+            let mut data = Vec::new();
+            self.encode_v1(&mut data);
+            txid_hash(&data)
+        }
+    }
+}
 
 impl NodeJoinRequestTx {
     /// returns the keypackage
@@ -79,14 +133,22 @@ impl NodeJoinRequestTx {
         address: StakedStateAddress,
         attributes: StakedStateOpAttributes,
         node_meta: NodeMetadata,
+        commission_rate: Milli,
     ) -> Self {
         Self {
             nonce,
             address,
             attributes,
             node_meta,
+            commission_rate: Some(commission_rate),
         }
     }
+
+    /// checks if the commission rate is not over 1.0
+    pub fn is_valid_commission_rate(&self) -> bool {
+        self.commission_rate
+            .map_or(true, |rate| rate.as_millis() <= MAX_COMMISSION_RATE_MILLIS)
+    }
 }
 
 #[cfg(not(feature = "mesalock_sgx"))]
@@ -97,6 +159,9 @@ impl fmt::Display for NodeJoinRequestTx {
             "address: {} (nonce: {}) to add {}",
             self.address, self.nonce, self.node_meta
         )?;
+        if let Some(commission_rate) = &self.commission_rate {
+            writeln!(f, "commission rate: {}", commission_rate)?;
+        }
         write!(f, "")
     }
 }
@@ -0,0 +1,174 @@
+use crate::state::account::{
+    decode_name_security_contact, NodeName, NodeSecurityContact, Nonce, StakedStateAddress,
+    StakedStateOpAttributes, MAX_COMMISSION_RATE_MILLIS,
+};
+use crate::state::tendermint::{TendermintNodeId, TendermintValidatorPubKey};
+use crate::tx::fee::Milli;
+use crate::tx::TransactionId;
+use parity_scale_codec::{Decode, Encode, Error, Input, Output};
+#[cfg(not(feature = "mesalock_sgx"))]
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::fmt;
+
+/// Updates the metadata of a council node that already joined:
+/// the name and security contact are informational,
+/// the consensus pubkey rotation replaces the validator key in Tendermint,
+/// the commission rate applies to the delegators' rewards from the next distribution,
+/// the p2p node ID is used to filter the peers.
+///
+/// tx-validation should check that:
+/// - the associated staked state has a council node record and is not jailed
+/// - the new consensus_pubkey is not used (unless it's the current one)
+/// - the commission rate is not over 1.0
+/// - the witness is correct
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct NodeMetadataUpdateTx {
+    /// the expected nonce on the corresponding state
+    pub nonce: Nonce,
+    /// the expected address on the corresponding state
+    pub address: StakedStateAddress,
+    /// the versioning and network identifier
+    pub attributes: StakedStateOpAttributes,
+    /// new name of the node
+    pub name: NodeName,
+    /// new security contact of the node
+    pub security_contact: NodeSecurityContact,
+    /// new Tendermint consensus validator-associated public key
+    pub consensus_pubkey: TendermintValidatorPubKey,
+    /// new share of the delegators' rewards kept by the council node
+    pub commission_rate: Milli,
+    /// new p2p node ID of the node
+    pub p2p_node_id: Option<TendermintNodeId>,
+}
+
+impl Decode for NodeMetadataUpdateTx {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let nonce = Nonce::decode(input)?;
+        let address = StakedStateAddress::decode(input)?;
+        let attributes = StakedStateOpAttributes::decode(input)?;
+        let (name, security_contact) = decode_name_security_contact(input)?;
+        let consensus_pubkey = TendermintValidatorPubKey::decode(input)?;
+        let commission_rate = Milli::decode(input)?;
+        let p2p_node_id = Option::decode(input)?;
+
+        Ok(NodeMetadataUpdateTx {
+            nonce,
+            address,
+            attributes,
+            name,
+            security_contact,
+            consensus_pubkey,
+            commission_rate,
+            p2p_node_id,
+        })
+    }
+}
+
+impl Encode for NodeMetadataUpdateTx {
+    fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
+        dest.push(&self.nonce);
+        dest.push(&self.address);
+        dest.push(&self.attributes);
+        dest.push(&self.name);
+        dest.push(&self.security_contact);
+        dest.push(&self.consensus_pubkey);
+        dest.push(&self.commission_rate);
+        dest.push(&self.p2p_node_id);
+    }
+
+    fn size_hint(&self) -> usize {
+        self.nonce.size_hint()
+            + self.address.size_hint()
+            + self.attributes.size_hint()
+            + self.name.size_hint()
+            + self.security_contact.size_hint()
+            + self.consensus_pubkey.size_hint()
+            + self.commission_rate.size_hint()
+            + self.p2p_node_id.size_hint()
+    }
+}
+
+impl TransactionId for NodeMetadataUpdateTx {}
+
+impl NodeMetadataUpdateTx {
+    /// constructs a new node metadata update transaction from the provided components
+    #[inline]
+    pub fn new(
+        nonce: Nonce,
+        address: StakedStateAddress,
+        attributes: StakedStateOpAttributes,
+        name: NodeName,
+        security_contact: NodeSecurityContact,
+        consensus_pubkey: TendermintValidatorPubKey,
+        commission_rate: Milli,
+        p2p_node_id: Option<TendermintNodeId>,
+    ) -> Self {
+        Self {
+            nonce,
+            address,
+            attributes,
+            name,
+            security_contact,
+            consensus_pubkey,
+            commission_rate,
+            p2p_node_id,
+        }
+    }
+
+    /// checks if the commission rate is not over 1.0
+    pub fn is_valid_commission_rate(&self) -> bool {
+        self.commission_rate.as_millis() <= MAX_COMMISSION_RATE_MILLIS
+    }
+}
+
+#[cfg(not(feature = "mesalock_sgx"))]
+impl fmt::Display for NodeMetadataUpdateTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "address: {} (nonce: {}) updated node metadata",
+            self.address, self.nonce
+        )?;
+        writeln!(f, "name: {}", self.name)?;
+        if let Some(security_contact) = &self.security_contact {
+            writeln!(f, "security contact: {}", security_contact)?;
+        }
+        writeln!(f, "consensus pubkey: {}", self.consensus_pubkey)?;
+        writeln!(f, "commission rate: {}", self.commission_rate)?;
+        if let Some(p2p_node_id) = &self.p2p_node_id {
+            writeln!(f, "p2p node ID: {}", p2p_node_id)?;
+        }
+        write!(f, "")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::init::address::RedeemAddress;
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+
+    #[test]
+    fn node_metadata_update_encode_decode() {
+        let tx = NodeMetadataUpdateTx::new(
+            3,
+            StakedStateAddress::BasicRedeem(
+                RedeemAddress::from_str("0x0e7c045110b8dbf29765047380898919c5cb56f4").unwrap(),
+            ),
+            StakedStateOpAttributes::new(0xab),
+            "new name".to_string(),
+            Some("security@crypto.com".to_string()),
+            TendermintValidatorPubKey::Ed25519([0xab; 32]),
+            Milli::try_new(0, 50).unwrap(),
+            Some(TendermintNodeId::try_from(&[0xcd; 20][..]).unwrap()),
+        );
+        let encoded = tx.encode();
+        assert_eq!(
+            NodeMetadataUpdateTx::decode(&mut encoded.as_slice()).unwrap(),
+            tx
+        );
+    }
+}
@@ -6,7 +6,7 @@ use std::fmt;
 #[cfg(not(feature = "mesalock_sgx"))]
 use std::str::FromStr;
 
-use crate::common::H256;
+use crate::common::{Timespec, H256};
 #[cfg(not(feature = "mesalock_sgx"))]
 use crate::init::address::{CroAddress, CroAddressError};
 #[cfg(not(feature = "mesalock_sgx"))]
@@ -17,6 +17,27 @@ use crate::init::network::{get_bech32_human_part_from_network, get_network, Netw
 
 type TreeRoot = H256;
 
+/// the part after the network's human-readable prefix in the textual form of hash-timelocked addresses
+/// (they don't fit in the bech32 length limit, so they are hex-encoded)
+#[cfg(not(feature = "mesalock_sgx"))]
+const HASH_TIME_LOCK_PREFIX: &str = "htlc:";
+
+/// Hash-timelock contract: the output can be spent by the recipient
+/// with the preimage of the hash before the deadline,
+/// or by the sender (refund) after the deadline
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Encode, Decode)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct HashTimeLock {
+    /// SHA-256 hash of the (32-byte) secret preimage
+    pub hash: H256,
+    /// the address (root) which can claim the output with the preimage
+    pub recipient: TreeRoot,
+    /// the address (root) which can get the output back after the deadline
+    pub refund: TreeRoot,
+    /// the block time since which the output can only be refunded
+    pub deadline: Timespec,
+}
+
 /// MAST of Or operations (records the root).
 /// Root of a Merkle tree where leafs are X-only
 /// (potentially summed up / combined) pubkeys
@@ -27,6 +48,8 @@ pub enum ExtendedAddr {
     /// but each operation is "OR"
     /// (root of such tree)
     OrTree(TreeRoot),
+    /// hash-timelocked output (for cross-chain atomic swaps)
+    HashTimeLock(HashTimeLock),
 }
 
 impl Encode for ExtendedAddr {
@@ -36,12 +59,17 @@ impl Encode for ExtendedAddr {
                 dest.push_byte(0);
                 dest.push(aa);
             }
+            ExtendedAddr::HashTimeLock(ref lock) => {
+                dest.push_byte(1);
+                dest.push(lock);
+            }
         }
     }
 
     fn size_hint(&self) -> usize {
         (match self {
             ExtendedAddr::OrTree(ref aa) => aa.size_hint(),
+            ExtendedAddr::HashTimeLock(ref lock) => lock.size_hint(),
         }) + 1
     }
 }
@@ -49,13 +77,14 @@ impl Encode for ExtendedAddr {
 impl Decode for ExtendedAddr {
     fn decode<DecIn: Input>(input: &mut DecIn) -> Result<Self, Error> {
         let tag = input.read_byte()?;
-        // NOTE: tag 1 may be used for other address types -- e.g. one to denote
-        // requiring a different witness type (leaf may be a combination of root + timelock)
+        // NOTE: tag 2+ may be used for other address types -- e.g. one to denote
+        // requiring a different witness type
         match tag {
             0 => Ok(ExtendedAddr::OrTree({
                 let address: TreeRoot = Decode::decode(input)?;
                 address
             })),
+            1 => Ok(ExtendedAddr::HashTimeLock(HashTimeLock::decode(input)?)),
             _ => Err("No such variant in enum ExtendedAddr".into()),
         }
     }
@@ -72,13 +101,32 @@ impl CroAddress<ExtendedAddr> for ExtendedAddr {
                         .expect("bech32 encoding error");
                 Ok(encoded)
             }
+            ExtendedAddr::HashTimeLock(lock) => Ok(format!(
+                "{}{}{}",
+                get_bech32_human_part_from_network(network),
+                HASH_TIME_LOCK_PREFIX,
+                hex::encode(lock.encode())
+            )),
         }
     }
 
     fn from_cro(encoded_addr: &str, network: Network) -> Result<Self, CroAddressError> {
-        if !encoded_addr.starts_with(get_bech32_human_part_from_network(network)) {
+        let human_part = get_bech32_human_part_from_network(network);
+        if !encoded_addr.starts_with(human_part) {
             return Err(CroAddressError::InvalidNetwork);
         }
+        if encoded_addr[human_part.len()..].starts_with(HASH_TIME_LOCK_PREFIX) {
+            let encoded_lock = &encoded_addr[human_part.len() + HASH_TIME_LOCK_PREFIX.len()..];
+            return hex::decode(encoded_lock)
+                .map_err(|_e| CroAddressError::ConvertError)
+                .and_then(|raw| {
+                    let mut input = raw.as_slice();
+                    match HashTimeLock::decode(&mut input) {
+                        Ok(lock) if input.is_empty() => Ok(ExtendedAddr::HashTimeLock(lock)),
+                        _ => Err(CroAddressError::ConvertError),
+                    }
+                });
+        }
 
         bech32::decode(encoded_addr)
             .map_err(|e| CroAddressError::Bech32Error(e.to_string()))
@@ -148,6 +196,28 @@ mod test {
         assert_eq!(extended_addr_from_hash, extended_addr_from_str);
     }
 
+    #[test]
+    fn should_be_correct_hash_time_lock_address() {
+        let network = Network::Devnet;
+        let extended_addr = ExtendedAddr::HashTimeLock(HashTimeLock {
+            hash: [1; 32],
+            recipient: [2; 32],
+            refund: [3; 32],
+            deadline: 1_600_000_000,
+        });
+        assert_eq!(
+            ExtendedAddr::decode(&mut extended_addr.encode().as_slice()).unwrap(),
+            extended_addr
+        );
+        assert_eq!(extended_addr.encode().len(), extended_addr.size_hint());
+
+        let textual_addr = extended_addr.to_cro(network).unwrap();
+        assert!(textual_addr.starts_with("dcrohtlc:"));
+        let restored_extended_addr = ExtendedAddr::from_cro(&textual_addr, network).unwrap();
+        assert_eq!(restored_extended_addr, extended_addr);
+        assert!(ExtendedAddr::from_cro(&textual_addr[..textual_addr.len() - 2], network).is_err());
+    }
+
     mod from_cro {
         use super::*;
 
@@ -11,7 +11,41 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::common::Timespec;
 use crate::init::coin::Coin;
+use crate::state::tendermint::BlockHeight;
 use crate::tx::data::address::ExtendedAddr;
+use crate::TxCreation;
+
+/// Timelock relative to the block in which the output was created
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Encode, Decode)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+#[cfg_attr(not(feature = "mesalock_sgx"), serde(rename_all = "snake_case"))]
+pub enum RelativeTimelock {
+    /// spendable the number of seconds after the creating transaction's block time
+    Seconds(Timespec),
+    /// spendable the number of blocks after the creating transaction's block
+    Blocks(u64),
+}
+
+impl RelativeTimelock {
+    /// checks if the output created in `created` can be spent in a block with the given height and time
+    pub fn is_unlocked(
+        &self,
+        created: &TxCreation,
+        block_height: BlockHeight,
+        block_time: Timespec,
+    ) -> bool {
+        match self {
+            RelativeTimelock::Seconds(seconds) => match created.block_time.checked_add(*seconds) {
+                Some(unlock_time) => block_time >= unlock_time,
+                None => false,
+            },
+            RelativeTimelock::Blocks(blocks) => match created.block_height.checked_add(*blocks) {
+                Some(unlock_height) => block_height >= unlock_height,
+                None => false,
+            },
+        }
+    }
+}
 
 /// Tx Output composed of an address and a coin value
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -31,17 +65,50 @@ pub struct TxOut {
     pub value: Coin,
     /// the optional timelock
     pub valid_from: Option<Timespec>,
+    /// the optional timelock relative to the output's creation
+    #[cfg_attr(
+        not(feature = "mesalock_sgx"),
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub relative_lock: Option<RelativeTimelock>,
 }
 
+// the timelocks are encoded with a common tag, so that the outputs without
+// the relative timelock are encoded the same as before it was introduced
+const TIMELOCK_NONE: u8 = 0;
+const TIMELOCK_ABSOLUTE: u8 = 1;
+const TIMELOCK_RELATIVE: u8 = 2;
+const TIMELOCK_BOTH: u8 = 3;
+
 impl Encode for TxOut {
     fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
         dest.push(&self.address);
         dest.push(&self.value);
-        dest.push(&self.valid_from);
+        match (&self.valid_from, &self.relative_lock) {
+            (None, None) => dest.push_byte(TIMELOCK_NONE),
+            (Some(valid_from), None) => {
+                dest.push_byte(TIMELOCK_ABSOLUTE);
+                dest.push(valid_from);
+            }
+            (None, Some(relative_lock)) => {
+                dest.push_byte(TIMELOCK_RELATIVE);
+                dest.push(relative_lock);
+            }
+            (Some(valid_from), Some(relative_lock)) => {
+                dest.push_byte(TIMELOCK_BOTH);
+                dest.push(valid_from);
+                dest.push(relative_lock);
+            }
+        }
     }
 
     fn size_hint(&self) -> usize {
-        self.address.size_hint() + self.value.size_hint() + self.valid_from.size_hint()
+        // the timelock tag + the payloads which are present
+        self.address.size_hint()
+            + self.value.size_hint()
+            + 1
+            + self.valid_from.map_or(0, |time| time.size_hint())
+            + self.relative_lock.map_or(0, |lock| lock.size_hint())
     }
 }
 
@@ -55,11 +122,21 @@ impl Decode for TxOut {
         // but one needs to careful that "None" isn't required to be encoded
         let address = ExtendedAddr::decode(input)?;
         let value = Coin::decode(input)?;
-        let valid_from: Option<Timespec> = Option::decode(input)?;
+        let (valid_from, relative_lock) = match input.read_byte()? {
+            TIMELOCK_NONE => (None, None),
+            TIMELOCK_ABSOLUTE => (Some(Timespec::decode(input)?), None),
+            TIMELOCK_RELATIVE => (None, Some(RelativeTimelock::decode(input)?)),
+            TIMELOCK_BOTH => (
+                Some(Timespec::decode(input)?),
+                Some(RelativeTimelock::decode(input)?),
+            ),
+            _ => return Err("No such variant in TxOut timelocks".into()),
+        };
         Ok(TxOut {
             address,
             value,
             valid_from,
+            relative_lock,
         })
     }
 }
@@ -115,6 +192,7 @@ impl TxOut {
             address,
             value,
             valid_from: None,
+            relative_lock: None,
         }
     }
 
@@ -124,6 +202,82 @@ impl TxOut {
             address,
             value,
             valid_from: Some(valid_from),
+            relative_lock: None,
         }
     }
+
+    /// creates a TX output with timelock relative to its creation
+    pub fn new_with_relative_lock(
+        address: ExtendedAddr,
+        value: Coin,
+        relative_lock: RelativeTimelock,
+    ) -> Self {
+        TxOut {
+            address,
+            value,
+            valid_from: None,
+            relative_lock: Some(relative_lock),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_timelocks_encoding() {
+        let address = ExtendedAddr::OrTree([0xbb; 32]);
+        let plain = TxOut::new(address.clone(), Coin::unit());
+        let absolute = TxOut::new_with_timelock(address.clone(), Coin::unit(), 10);
+        // the same encoding as `Option<Timespec>`
+        assert_eq!(
+            plain.encode(),
+            (&address, Coin::unit(), None::<Timespec>).encode()
+        );
+        assert_eq!(
+            absolute.encode(),
+            (&address, Coin::unit(), Some(10 as Timespec)).encode()
+        );
+
+        let relative = TxOut::new_with_relative_lock(
+            address.clone(),
+            Coin::unit(),
+            RelativeTimelock::Blocks(5),
+        );
+        let mut both = relative.clone();
+        both.valid_from = Some(10);
+        for output in [plain, absolute, relative, both].iter() {
+            let encoded = output.encode();
+            assert_eq!(&TxOut::decode(&mut encoded.as_slice()).unwrap(), output);
+            // the timelock tag and payloads are counted exactly
+            let timelock_size =
+                encoded.len() - address.encode().len() - Coin::unit().encode().len();
+            assert_eq!(
+                output.size_hint(),
+                address.size_hint() + Coin::unit().size_hint() + timelock_size
+            );
+        }
+    }
+
+    #[test]
+    fn check_relative_timelock() {
+        let created = TxCreation {
+            block_height: BlockHeight::new(10),
+            block_time: 1000,
+        };
+        let blocks = RelativeTimelock::Blocks(5);
+        assert!(!blocks.is_unlocked(&created, BlockHeight::new(14), 2000));
+        assert!(blocks.is_unlocked(&created, BlockHeight::new(15), 1000));
+        let seconds = RelativeTimelock::Seconds(100);
+        assert!(!seconds.is_unlocked(&created, BlockHeight::new(100), 1099));
+        assert!(seconds.is_unlocked(&created, BlockHeight::new(11), 1100));
+        assert!(
+            !RelativeTimelock::Seconds(Timespec::max_value()).is_unlocked(
+                &created,
+                BlockHeight::new(100),
+                Timespec::max_value()
+            )
+        );
+    }
 }
@@ -14,6 +14,11 @@ use std::prelude::v1::Vec;
 use std::str::FromStr;
 use std::{error, fmt};
 
+mod policy;
+pub use policy::{
+    BlockFullness, CongestionFee, CurrentFeePolicy, FeeInput, FeePolicy, WeightedFee,
+};
+
 /// A fee value that represent either a fee to pay, or a fee paid.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Encode, Decode)]
 #[cfg_attr(not(feature = "mesalock_sgx"), serde(transparent))]
@@ -41,7 +46,7 @@ impl Fee {
 /// TODO: overflow checks in Cargo?
 /// [profile.release]
 /// overflow-checks = true
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Encode, Decode)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Default, Encode, Decode)]
 #[cfg_attr(not(feature = "mesalock_sgx"), serde(transparent))]
 #[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
 pub struct Milli(u64);
@@ -207,6 +212,7 @@ impl Div for Milli {
 /// Linear fee using the basic affine formula `COEFFICIENT * scale_bytes(txaux).len() + CONSTANT`
 #[derive(PartialEq, Eq, PartialOrd, Debug, Clone, Copy, Encode, Decode)]
 #[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+#[cfg_attr(not(feature = "mesalock_sgx"), serde(deny_unknown_fields))]
 pub struct LinearFee {
     /// this is the minimal fee
     pub constant: Milli,
@@ -239,6 +245,11 @@ pub trait FeeAlgorithm: Send + Sync + Clone {
     /// calculates the fee based on the provided transaction
     /// (it serializes it and uses the serialized length for fee estimation)
     fn calculate_for_txaux(&self, txaux: &TxAux) -> Result<Fee, CoinError>;
+    /// the algorithm to use for building one transaction, whose fee may be calculated several times
+    /// (e.g. with the network fee policy fetched once for the whole build)
+    fn for_build(&self) -> Self {
+        self.clone()
+    }
 }
 
 impl FeeAlgorithm for LinearFee {
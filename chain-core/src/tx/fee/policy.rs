@@ -0,0 +1,330 @@
+use super::{Fee, FeeAlgorithm, LinearFee, Milli};
+use crate::init::coin::{Coin, CoinError};
+use crate::tx::{TxAux, TxEnclaveAux};
+use parity_scale_codec::{Decode, Encode};
+#[cfg(not(feature = "mesalock_sgx"))]
+use serde::{Deserialize, Serialize};
+use std::cmp;
+
+/// The number of recent blocks the average block size is (roughly) computed over
+const BLOCK_FULLNESS_WINDOW: u64 = 20;
+
+const MIN_CONGESTION_MULTIPLIER: Milli = Milli::new(1, 0); // 1.0
+
+/// The observable parts of a transaction the fee policies are based on
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct FeeInput {
+    /// size of the scale-encoded `TxAux`
+    pub tx_size: usize,
+    /// number of the spent transaction outputs
+    pub no_of_inputs: usize,
+    /// number of the created transaction outputs
+    pub no_of_outputs: usize,
+}
+
+impl FeeInput {
+    /// only based on the transaction size
+    pub fn from_size(tx_size: usize) -> Self {
+        FeeInput {
+            tx_size,
+            ..Default::default()
+        }
+    }
+
+    /// the size and the (public) number of inputs and outputs of the transaction
+    pub fn from_txaux(txaux: &TxAux) -> Self {
+        Self::with_tx_size(txaux, txaux.encode().len())
+    }
+
+    /// the same as `from_txaux`, but with the already known size of the encoded transaction
+    pub fn with_tx_size(txaux: &TxAux, tx_size: usize) -> Self {
+        let (no_of_inputs, no_of_outputs) = match txaux {
+            TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+                inputs,
+                no_of_outputs,
+                ..
+            }) => (inputs.len(), *no_of_outputs as usize),
+            TxAux::EnclaveTx(TxEnclaveAux::DepositStakeTx { tx, .. }) => (tx.inputs.len(), 0),
+            TxAux::EnclaveTx(TxEnclaveAux::WithdrawUnbondedStakeTx { no_of_outputs, .. }) => {
+                (0, *no_of_outputs as usize)
+            }
+            TxAux::PublicTx(_) | TxAux::MLSHandshake(_) => (0, 0),
+        };
+        FeeInput {
+            tx_size,
+            no_of_inputs,
+            no_of_outputs,
+        }
+    }
+}
+
+/// Fee weighted by the number of inputs and outputs (which are stored in the UTXO set)
+/// `CONSTANT + COEFFICIENT * size + PER_INPUT * inputs + PER_OUTPUT * outputs`
+#[derive(PartialEq, Eq, PartialOrd, Debug, Clone, Copy, Encode, Decode)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+#[cfg_attr(not(feature = "mesalock_sgx"), serde(deny_unknown_fields))]
+pub struct WeightedFee {
+    /// this is the minimal fee
+    pub constant: Milli,
+    /// the transaction's size coefficient fee
+    pub coefficient: Milli,
+    /// the fee for each spent input
+    pub per_input: Milli,
+    /// the fee for each created output
+    pub per_output: Milli,
+}
+
+impl WeightedFee {
+    /// calculates the fee based on the provided transaction properties
+    pub fn estimate(&self, input: &FeeInput) -> Result<Fee, CoinError> {
+        let msz = Milli::integral(input.tx_size as u64).map_err(|_| CoinError::Overflow)?;
+        let minputs =
+            Milli::integral(input.no_of_inputs as u64).map_err(|_| CoinError::Overflow)?;
+        let moutputs =
+            Milli::integral(input.no_of_outputs as u64).map_err(|_| CoinError::Overflow)?;
+        let fee = self.constant
+            + self.coefficient * msz
+            + self.per_input * minputs
+            + self.per_output * moutputs;
+        let coin = Coin::new(fee.to_integral())?;
+        Ok(Fee::new(coin))
+    }
+}
+
+/// Linear fee scaled by the congestion multiplier, but not lower than the minimal fee.
+/// The multiplier is `average block size / target block size`, bounded by `[1.0, max_multiplier]`.
+#[derive(PartialEq, Eq, PartialOrd, Debug, Clone, Copy, Encode, Decode)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+#[cfg_attr(not(feature = "mesalock_sgx"), serde(deny_unknown_fields))]
+pub struct CongestionFee {
+    /// the fee before the congestion multiplier is applied
+    pub base: LinearFee,
+    /// the fee floor
+    pub minimal_fee: Milli,
+    /// the average block size (in bytes) above which the fee goes up
+    pub target_block_size: u64,
+    /// the upper bound of the congestion multiplier
+    pub max_multiplier: Milli,
+}
+
+impl CongestionFee {
+    /// the congestion multiplier computed from the recent block fullness
+    pub fn multiplier(&self, fullness: BlockFullness) -> Milli {
+        if self.target_block_size == 0 {
+            return MIN_CONGESTION_MULTIPLIER;
+        }
+        let ratio = match (
+            Milli::integral(fullness.average_block_size()),
+            Milli::integral(self.target_block_size),
+        ) {
+            (Ok(average), Ok(target)) => average / target,
+            _ => self.max_multiplier,
+        };
+        cmp::max(
+            MIN_CONGESTION_MULTIPLIER,
+            cmp::min(ratio, self.max_multiplier),
+        )
+    }
+
+    /// calculates the fee based on the provided transaction size and the recent block fullness
+    pub fn estimate(&self, input: &FeeInput, fullness: BlockFullness) -> Result<Fee, CoinError> {
+        let msz = Milli::integral(input.tx_size as u64).map_err(|_| CoinError::Overflow)?;
+        let base = self.base.constant + self.base.coefficient * msz;
+        let fee = cmp::max(self.minimal_fee, base * self.multiplier(fullness));
+        let coin = Coin::new(fee.to_integral())?;
+        Ok(Fee::new(coin))
+    }
+}
+
+/// The fee policy selected in the genesis (or changed by the governance proposals)
+///
+/// NOTE: it's (de)serialized without a tag, so the variants should have different fields
+#[derive(PartialEq, Eq, PartialOrd, Debug, Clone, Copy, Encode, Decode)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+#[cfg_attr(not(feature = "mesalock_sgx"), serde(untagged))]
+pub enum FeePolicy {
+    /// `COEFFICIENT * size + CONSTANT`
+    Linear(LinearFee),
+    /// linear fee + fees for each input and output
+    Weighted(WeightedFee),
+    /// linear fee scaled by the recent block fullness, with a minimal fee
+    Congestion(CongestionFee),
+}
+
+impl FeePolicy {
+    /// calculates the fee based on the provided transaction properties and the recent block fullness
+    pub fn estimate(&self, input: &FeeInput, fullness: BlockFullness) -> Result<Fee, CoinError> {
+        match self {
+            FeePolicy::Linear(fee) => fee.estimate(input.tx_size),
+            FeePolicy::Weighted(fee) => fee.estimate(input),
+            FeePolicy::Congestion(fee) => fee.estimate(input, fullness),
+        }
+    }
+
+    /// check if the policy parameters are correct
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if let FeePolicy::Congestion(fee) = self {
+            if fee.target_block_size == 0 {
+                return Err("target block size can't be zero");
+            }
+            if fee.max_multiplier < MIN_CONGESTION_MULTIPLIER {
+                return Err("max congestion multiplier can't < 1");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<LinearFee> for FeePolicy {
+    fn from(fee: LinearFee) -> Self {
+        FeePolicy::Linear(fee)
+    }
+}
+
+/// The fee policy together with the block fullness the fees are estimated for
+/// (the last committed one, as in the chain state)
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct CurrentFeePolicy {
+    /// the fee policy in the network parameters
+    pub policy: FeePolicy,
+    /// the recent block fullness
+    pub fullness: BlockFullness,
+}
+
+impl CurrentFeePolicy {
+    /// creates the policy with the recent block fullness
+    pub fn new(policy: FeePolicy, fullness: BlockFullness) -> Self {
+        CurrentFeePolicy { policy, fullness }
+    }
+}
+
+impl FeeAlgorithm for CurrentFeePolicy {
+    fn calculate_fee(&self, num_bytes: usize) -> Result<Fee, CoinError> {
+        self.policy
+            .estimate(&FeeInput::from_size(num_bytes), self.fullness)
+    }
+
+    fn calculate_for_txaux(&self, txaux: &TxAux) -> Result<Fee, CoinError> {
+        self.policy
+            .estimate(&FeeInput::from_txaux(txaux), self.fullness)
+    }
+}
+
+/// Moving average of the recent block sizes (sum of the valid transaction sizes in bytes)
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Encode, Decode)]
+#[cfg_attr(not(feature = "mesalock_sgx"), serde(transparent))]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub struct BlockFullness(u64);
+
+impl BlockFullness {
+    /// the average size of the recent blocks
+    #[inline]
+    pub fn average_block_size(self) -> u64 {
+        self.0
+    }
+
+    /// updates the average with the size of the newly committed block
+    pub fn update(&mut self, block_size: u64) {
+        self.0 = (self.0.saturating_mul(BLOCK_FULLNESS_WINDOW - 1)).saturating_add(block_size)
+            / BLOCK_FULLNESS_WINDOW;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn congestion_fee() -> CongestionFee {
+        CongestionFee {
+            base: LinearFee::new(Milli::new(1, 0), Milli::new(0, 100)),
+            minimal_fee: Milli::new(5, 0),
+            target_block_size: 1000,
+            max_multiplier: Milli::new(3, 0),
+        }
+    }
+
+    #[test]
+    fn check_weighted_fee() {
+        let fee = WeightedFee {
+            constant: Milli::new(1, 0),
+            coefficient: Milli::new(0, 1),
+            per_input: Milli::new(2, 0),
+            per_output: Milli::new(3, 0),
+        };
+        let input = FeeInput {
+            tx_size: 1000,
+            no_of_inputs: 2,
+            no_of_outputs: 3,
+        };
+        // 1 + 1 + 2 * 2 + 3 * 3
+        assert_eq!(
+            fee.estimate(&input).unwrap(),
+            Fee::new(Coin::new(15).unwrap())
+        );
+    }
+
+    #[test]
+    fn check_congestion_fee() {
+        let fee = congestion_fee();
+        let input = FeeInput::from_size(100);
+        let mut fullness = BlockFullness::default();
+        // the minimal fee floor: 1 + 0.1 * 100 < 5 * 1.0
+        assert_eq!(
+            fee.estimate(&FeeInput::from_size(10), fullness).unwrap(),
+            Fee::new(Coin::new(5).unwrap())
+        );
+        assert_eq!(
+            fee.estimate(&input, fullness).unwrap(),
+            Fee::new(Coin::new(11).unwrap())
+        );
+
+        for _ in 0..1000 {
+            fullness.update(2000);
+        }
+        assert!(fullness.average_block_size() <= 2000);
+        assert!(fullness.average_block_size() > 1900);
+        assert!(fee.multiplier(fullness) > Milli::new(1, 900));
+        assert!(fee.estimate(&input, fullness).unwrap() > Fee::new(Coin::new(20).unwrap()));
+
+        // capped by the max multiplier
+        for _ in 0..1000 {
+            fullness.update(100_000);
+        }
+        assert_eq!(fee.multiplier(fullness), Milli::new(3, 0));
+        assert_eq!(
+            fee.estimate(&input, fullness).unwrap(),
+            Fee::new(Coin::new(33).unwrap())
+        );
+
+        // the current policy is estimated for the recent block fullness
+        let current = CurrentFeePolicy::new(FeePolicy::Congestion(fee), fullness);
+        assert_eq!(
+            current.calculate_fee(100).unwrap(),
+            Fee::new(Coin::new(33).unwrap())
+        );
+    }
+
+    #[test]
+    fn check_fee_policy_json() {
+        let linear: FeePolicy =
+            serde_json::from_str(r#"{"constant": 1100, "coefficient": 1250}"#).unwrap();
+        assert!(matches!(linear, FeePolicy::Linear(_)));
+        let weighted: FeePolicy = serde_json::from_str(
+            r#"{"constant": 1100, "coefficient": 1250, "per_input": 1000, "per_output": 1000}"#,
+        )
+        .unwrap();
+        assert!(matches!(weighted, FeePolicy::Weighted(_)));
+        let congestion = FeePolicy::Congestion(congestion_fee());
+        assert_eq!(
+            serde_json::from_str::<FeePolicy>(&serde_json::to_string(&congestion).unwrap())
+                .unwrap(),
+            congestion
+        );
+        assert!(congestion.validate().is_ok());
+        let invalid = FeePolicy::Congestion(CongestionFee {
+            max_multiplier: Milli::new(0, 500),
+            ..congestion_fee()
+        });
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use parity_scale_codec::{Decode, Encode};
+#[cfg(not(feature = "mesalock_sgx"))]
+use serde::{Deserialize, Serialize};
+
+use crate::common::{Proof, H256};
+use crate::state::tendermint::BlockHeight;
+use crate::tx::data::TxId;
+use crate::AppHashParts;
+
+/// Spending status of a transaction output as of some block height
+/// (proven by its membership or absence in the block's spent outputs trie, see `AppHashParts`)
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Encode, Decode)]
+#[cfg_attr(not(feature = "mesalock_sgx"), derive(Serialize, Deserialize))]
+pub enum OutputStatus {
+    /// not spent as of the height (it may have been spent in a later block)
+    Unspent,
+    /// spent in the block at the height or before
+    Spent,
+}
+
+/// Proof that a transaction was committed in a block:
+/// the transaction ID is included in the block's merkle tree of valid transactions,
+/// whose root (together with the other parts) gives the block's app hash.
+/// The app hash is in the header of the next block (which is verified by a light client).
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+pub struct TxInclusionProof {
+    /// height of the block in which the transaction was committed
+    pub block_height: BlockHeight,
+    /// the transaction ID in the block's merkle tree of valid transactions
+    pub merkle_proof: Proof<H256>,
+    /// the parts of the block's app hash (the valid transaction root and the state after the block)
+    pub app_hash_parts: AppHashParts,
+}
+
+impl TxInclusionProof {
+    /// the proven transaction ID
+    pub fn txid(&self) -> &TxId {
+        self.merkle_proof.value()
+    }
+
+    /// the app hash of the block computed from the proof
+    pub fn app_hash(&self) -> H256 {
+        self.app_hash_parts.app_hash()
+    }
+
+    /// checks the transaction was committed in the block with the (trusted) app hash
+    pub fn verify(&self, txid: &TxId, app_hash: &H256) -> bool {
+        self.txid() == txid
+            && self.merkle_proof.verify(&self.app_hash_parts.valid_tx_root)
+            && self.app_hash() == *app_hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::MerkleTree;
+
+    #[test]
+    fn check_inclusion_proof_verify() {
+        let txids: Vec<TxId> = (0u8..5).map(|i| [i; 32]).collect();
+        let tree = MerkleTree::new(txids.clone());
+        let app_hash_parts = AppHashParts {
+            valid_tx_root: tree.root_hash(),
+            account_root: [7u8; 32],
+            rewards_pool_hash: [1u8; 32],
+            network_params_hash: [2u8; 32],
+            node_state_hash: Some([3u8; 32]),
+            spent_outputs_root: Some([4u8; 32]),
+        };
+        let app_hash = app_hash_parts.app_hash();
+        let proof = TxInclusionProof {
+            block_height: BlockHeight::new(3),
+            merkle_proof: tree.generate_proof(txids[2]).unwrap(),
+            app_hash_parts,
+        };
+        assert!(proof.verify(&txids[2], &app_hash));
+        assert!(!proof.verify(&txids[1], &app_hash));
+        assert!(!proof.verify(&txids[2], &[0u8; 32]));
+
+        let decoded = TxInclusionProof::decode(&mut proof.encode().as_slice()).unwrap();
+        assert_eq!(decoded, proof);
+
+        let mut forged = proof.clone();
+        forged.app_hash_parts.account_root = [8u8; 32];
+        assert!(!forged.verify(&txids[2], &app_hash));
+
+        // the blocks before app version 2 have no node state and spent outputs parts
+        let mut forged = proof.clone();
+        forged.app_hash_parts.node_state_hash = None;
+        assert!(!forged.verify(&txids[2], &app_hash));
+        let mut forged = proof.clone();
+        forged.app_hash_parts.spent_outputs_root = None;
+        assert!(!forged.verify(&txids[2], &app_hash));
+
+        // a merkle proof from another tree doesn't match the tx root of the app hash
+        let other_tree = MerkleTree::new(vec![txids[2], [9u8; 32]]);
+        let mut forged = proof;
+        forged.merkle_proof = other_tree.generate_proof(txids[2]).unwrap();
+        assert!(!forged.verify(&txids[2], &app_hash));
+    }
+}
@@ -4,6 +4,8 @@ use std::prelude::v1::Vec;
 pub mod data;
 /// Transaction fee calculation
 pub mod fee;
+/// Proofs of transactions committed in blocks
+pub mod inclusion;
 /// Witness structures (e.g. signatures) for transactions
 pub mod witness;
 
@@ -16,11 +18,12 @@ use self::data::Tx;
 use self::witness::TxWitness;
 use crate::mls::MLSHandshakeAux;
 use crate::state::account::{
-    DepositBondTx, StakedStateOpAttributes, StakedStateOpWitness, UnbondTx, UnjailTx,
-    WithdrawUnbondedTx,
+    DelegateTx, DepositBondTx, StakedStateOpAttributes, StakedStateOpWitness, UnbondTx,
+    UndelegateTx, UnjailTx, WithdrawUnbondedTx,
 };
+use crate::state::governance::{ParamChangeProposalTx, ParamChangeVoteTx};
 use crate::state::tendermint::BlockHeight;
-use crate::state::validator::NodeJoinRequestTx;
+use crate::state::validator::{NodeJoinRequestTx, NodeLeaveTx, NodeMetadataUpdateTx};
 use crate::tx::data::{txid_hash, TxId};
 use aead::Payload;
 use data::input::{TxoPointer, TxoSize};
@@ -382,7 +385,7 @@ impl TxEnclaveAux {
 }
 
 /// Transactions that are directly processed in non-enclave execution environment (chain-abci)
-/// TODO/NOTE: other TX types expected -- update of council node metadata, bonus donation, ...
+/// TODO/NOTE: other TX types expected -- bonus donation, ...
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TxPublicAux {
     /// Tx that modifies staked state -- moves some bonded stake into unbonded (witness for staked state)
@@ -391,6 +394,18 @@ pub enum TxPublicAux {
     UnjailTx(UnjailTx, StakedStateOpWitness),
     /// Tx that updates a staked state with node (community or council node) details
     NodeJoinTx(NodeJoinRequestTx, StakedStateOpWitness),
+    /// Tx that proposes network parameter changes (witness for the proposer's staked state)
+    ParamChangeProposalTx(ParamChangeProposalTx, StakedStateOpWitness),
+    /// Tx that votes on a network parameter change proposal (witness for the voter's staked state)
+    ParamChangeVoteTx(ParamChangeVoteTx, StakedStateOpWitness),
+    /// Tx that delegates some bonded amount to a council node
+    DelegateTx(DelegateTx, StakedStateOpWitness),
+    /// Tx that takes back the delegated amount into unbonded
+    UndelegateTx(UndelegateTx, StakedStateOpWitness),
+    /// Tx that updates the council node details (name, security contact, consensus key)
+    NodeMetadataUpdateTx(NodeMetadataUpdateTx, StakedStateOpWitness),
+    /// Tx that voluntarily inactivates a node (community or council node)
+    NodeLeaveTx(NodeLeaveTx, StakedStateOpWitness),
 }
 
 impl Encode for TxPublicAux {
@@ -411,6 +426,36 @@ impl Encode for TxPublicAux {
                 dest.push(tx);
                 dest.push(witness);
             }
+            TxPublicAux::ParamChangeProposalTx(ref tx, ref witness) => {
+                dest.push_byte(3);
+                dest.push(tx);
+                dest.push(witness);
+            }
+            TxPublicAux::ParamChangeVoteTx(ref tx, ref witness) => {
+                dest.push_byte(4);
+                dest.push(tx);
+                dest.push(witness);
+            }
+            TxPublicAux::DelegateTx(ref tx, ref witness) => {
+                dest.push_byte(5);
+                dest.push(tx);
+                dest.push(witness);
+            }
+            TxPublicAux::UndelegateTx(ref tx, ref witness) => {
+                dest.push_byte(6);
+                dest.push(tx);
+                dest.push(witness);
+            }
+            TxPublicAux::NodeMetadataUpdateTx(ref tx, ref witness) => {
+                dest.push_byte(7);
+                dest.push(tx);
+                dest.push(witness);
+            }
+            TxPublicAux::NodeLeaveTx(ref tx, ref witness) => {
+                dest.push_byte(8);
+                dest.push(tx);
+                dest.push(witness);
+            }
         }
     }
 
@@ -419,6 +464,12 @@ impl Encode for TxPublicAux {
             TxPublicAux::UnbondStakeTx(tx, witness) => tx.size_hint() + witness.size_hint(),
             TxPublicAux::UnjailTx(tx, witness) => tx.size_hint() + witness.size_hint(),
             TxPublicAux::NodeJoinTx(tx, witness) => tx.size_hint() + witness.size_hint(),
+            TxPublicAux::ParamChangeProposalTx(tx, witness) => tx.size_hint() + witness.size_hint(),
+            TxPublicAux::ParamChangeVoteTx(tx, witness) => tx.size_hint() + witness.size_hint(),
+            TxPublicAux::DelegateTx(tx, witness) => tx.size_hint() + witness.size_hint(),
+            TxPublicAux::UndelegateTx(tx, witness) => tx.size_hint() + witness.size_hint(),
+            TxPublicAux::NodeMetadataUpdateTx(tx, witness) => tx.size_hint() + witness.size_hint(),
+            TxPublicAux::NodeLeaveTx(tx, witness) => tx.size_hint() + witness.size_hint(),
         }
     }
 }
@@ -426,7 +477,7 @@ impl Encode for TxPublicAux {
 impl Decode for TxPublicAux {
     fn decode<DecIn: Input>(input: &mut DecIn) -> Result<Self, Error> {
         let tag = input.read_byte()?;
-        // note: 3.. tags reserved for other tx types (node metadata update etc.)
+        // note: 9.. tags reserved for other tx types
         match tag {
             0 => {
                 let tx = UnbondTx::decode(input)?;
@@ -443,18 +494,89 @@ impl Decode for TxPublicAux {
                 let witness = StakedStateOpWitness::decode(input)?;
                 Ok(TxPublicAux::NodeJoinTx(tx, witness))
             }
+            3 => {
+                let tx = ParamChangeProposalTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::ParamChangeProposalTx(tx, witness))
+            }
+            4 => {
+                let tx = ParamChangeVoteTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::ParamChangeVoteTx(tx, witness))
+            }
+            5 => {
+                let tx = DelegateTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::DelegateTx(tx, witness))
+            }
+            6 => {
+                let tx = UndelegateTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::UndelegateTx(tx, witness))
+            }
+            7 => {
+                let tx = NodeMetadataUpdateTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::NodeMetadataUpdateTx(tx, witness))
+            }
+            8 => {
+                let tx = NodeLeaveTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::NodeLeaveTx(tx, witness))
+            }
             _ => Err("No such variant in enum TxPublicAux".into()),
         }
     }
 }
 
 impl TxPublicAux {
+    /// decodes the transaction in the app version 1 (0.5) TX format:
+    /// the unbond, unjail and node join transactions,
+    /// and the proposal and vote transactions (so that the app version 1 networks
+    /// can schedule the upgrade to the next version; no other change can be proposed in it)
+    pub fn decode_v1<DecIn: Input>(input: &mut DecIn) -> Result<Self, Error> {
+        match input.read_byte()? {
+            0 => {
+                let tx = UnbondTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::UnbondStakeTx(tx, witness))
+            }
+            1 => {
+                let tx = UnjailTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::UnjailTx(tx, witness))
+            }
+            2 => {
+                let tx = NodeJoinRequestTx::decode_v1(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::NodeJoinTx(tx, witness))
+            }
+            3 => {
+                let tx = ParamChangeProposalTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::ParamChangeProposalTx(tx, witness))
+            }
+            4 => {
+                let tx = ParamChangeVoteTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::ParamChangeVoteTx(tx, witness))
+            }
+            _ => Err("No such variant in enum TxPublicAux".into()),
+        }
+    }
+
     /// retrieves a TX ID (currently blake3(scale_codec_bytes(tx)))
     pub fn tx_id(&self) -> TxId {
         match self {
             TxPublicAux::UnbondStakeTx(tx, _) => tx.id(),
             TxPublicAux::UnjailTx(tx, _) => tx.id(),
             TxPublicAux::NodeJoinTx(tx, _) => tx.id(),
+            TxPublicAux::ParamChangeProposalTx(tx, _) => tx.id(),
+            TxPublicAux::ParamChangeVoteTx(tx, _) => tx.id(),
+            TxPublicAux::DelegateTx(tx, _) => tx.id(),
+            TxPublicAux::UndelegateTx(tx, _) => tx.id(),
+            TxPublicAux::NodeMetadataUpdateTx(tx, _) => tx.id(),
+            TxPublicAux::NodeLeaveTx(tx, _) => tx.id(),
         }
     }
 
@@ -464,6 +586,12 @@ impl TxPublicAux {
             TxPublicAux::UnbondStakeTx(tx, _) => &tx.attributes,
             TxPublicAux::UnjailTx(tx, _) => &tx.attributes,
             TxPublicAux::NodeJoinTx(tx, _) => &tx.attributes,
+            TxPublicAux::ParamChangeProposalTx(tx, _) => &tx.attributes,
+            TxPublicAux::ParamChangeVoteTx(tx, _) => &tx.attributes,
+            TxPublicAux::DelegateTx(tx, _) => &tx.attributes,
+            TxPublicAux::UndelegateTx(tx, _) => &tx.attributes,
+            TxPublicAux::NodeMetadataUpdateTx(tx, _) => &tx.attributes,
+            TxPublicAux::NodeLeaveTx(tx, _) => &tx.attributes,
         }
     }
 
@@ -561,6 +689,38 @@ impl TxAux {
             TxAux::MLSHandshake(tx) => tx.id(),
         }
     }
+
+    /// Decodes the transaction in the wire format of the app version active on the network.
+    /// When the format changes in a new app version, the previous decoding is kept here
+    /// for the lower versions, so that the blocks before the upgrade height are replayed as before:
+    /// - versions 0 and 1 (0.4 and 0.5): `decode_v1`
+    /// - version 2 (0.6): the current `Decode` implementation
+    pub fn decode_versioned<I: Input>(app_version: u64, input: &mut I) -> Result<Self, Error> {
+        match app_version {
+            0 | 1 => Self::decode_v1(input),
+            crate::APP_VERSION => Self::decode(input),
+            _ => Err("Unsupported app version".into()),
+        }
+    }
+
+    /// decodes the transaction in the app version 1 (0.5) TX format
+    /// (the public transactions and the node metadata were extended in version 2)
+    pub fn decode_v1<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let size = input
+            .remaining_len()?
+            .ok_or_else(|| "Unable to calculate size of input")?;
+
+        if size > TX_AUX_SIZE {
+            return Err("Input too large".into());
+        }
+
+        match input.read_byte()? {
+            0 => Ok(TxAux::EnclaveTx(TxEnclaveAux::decode(input)?)),
+            1 => Ok(TxAux::PublicTx(TxPublicAux::decode_v1(input)?)),
+            2 => Ok(TxAux::MLSHandshake(MLSHandshakeAux::decode(input)?)),
+            _ => Err("No such variant in enum TxAux".into()),
+        }
+    }
 }
 
 #[cfg(not(feature = "mesalock_sgx"))]
@@ -607,6 +767,24 @@ impl fmt::Display for TxAux {
             TxAux::PublicTx(TxPublicAux::NodeJoinTx(tx, witness)) => {
                 display_tx_witness(f, tx, witness)
             }
+            TxAux::PublicTx(TxPublicAux::ParamChangeProposalTx(tx, witness)) => {
+                display_tx_witness(f, tx, witness)
+            }
+            TxAux::PublicTx(TxPublicAux::ParamChangeVoteTx(tx, witness)) => {
+                display_tx_witness(f, tx, witness)
+            }
+            TxAux::PublicTx(TxPublicAux::DelegateTx(tx, witness)) => {
+                display_tx_witness(f, tx, witness)
+            }
+            TxAux::PublicTx(TxPublicAux::UndelegateTx(tx, witness)) => {
+                display_tx_witness(f, tx, witness)
+            }
+            TxAux::PublicTx(TxPublicAux::NodeMetadataUpdateTx(tx, witness)) => {
+                display_tx_witness(f, tx, witness)
+            }
+            TxAux::PublicTx(TxPublicAux::NodeLeaveTx(tx, witness)) => {
+                display_tx_witness(f, tx, witness)
+            }
             TxAux::MLSHandshake(_) => {
                 // FIXME
                 writeln!(f, "mls handshake")
@@ -621,7 +799,7 @@ pub mod tests {
     use crate::common::MerkleTree;
     use crate::init::coin::Coin;
     use crate::tx::data::access::{TxAccess, TxAccessPolicy};
-    use crate::tx::data::address::ExtendedAddr;
+    use crate::tx::data::address::{ExtendedAddr, HashTimeLock};
     use crate::tx::data::input::TxoPointer;
     use crate::tx::data::output::TxOut;
     use crate::tx::witness::tree::RawXOnlyPubkey;
@@ -638,6 +816,15 @@ pub mod tests {
         let mut tx = Tx::new();
         tx.add_input(TxoPointer::new([0x01; 32], 1));
         tx.add_output(TxOut::new(ExtendedAddr::OrTree([0xbb; 32]), Coin::unit()));
+        tx.add_output(TxOut::new(
+            ExtendedAddr::HashTimeLock(HashTimeLock {
+                hash: [0xdd; 32],
+                recipient: [0xbb; 32],
+                refund: [0xee; 32],
+                deadline: 1_000,
+            }),
+            Coin::unit(),
+        ));
         let secp = Secp256k1::new();
         let sk1 = SecretKey::from_slice(&[0xcc; 32][..]).expect("secret key");
         let pk1 = PublicKey::from_secret_key(&secp, &sk1);
@@ -653,14 +840,124 @@ pub mod tests {
 
         let merkle = MerkleTree::new(raw_public_keys.clone());
 
-        let w1 = TxInWitness::TreeSig(
-            schnorr_sign(&secp, &msg, &sk1, &mut rand::thread_rng()),
-            merkle.generate_proof(raw_public_keys[0].clone()).unwrap(),
-        );
-        let txa = PlainTxAux::TransferTx(tx, vec![w1].into());
+        let sign = || schnorr_sign(&secp, &msg, &sk1, &mut rand::thread_rng());
+        let proof = merkle.generate_proof(raw_public_keys[0].clone()).unwrap();
+        let w1 = TxInWitness::TreeSig(sign(), proof.clone());
+        let w2 = TxInWitness::HashLockClaim([0xff; 32], sign(), proof.clone());
+        let w3 = TxInWitness::TimeLockRefund(sign(), proof);
+        for w in [&w1, &w2, &w3].iter() {
+            assert_eq!(w.encode().len(), w.size_hint());
+        }
+        let txa = PlainTxAux::TransferTx(tx, vec![w1, w2, w3].into());
         let mut encoded: Vec<u8> = txa.encode();
         let mut data: &[u8] = encoded.as_mut();
         let decoded = PlainTxAux::decode(&mut data).expect("decode tx aux");
         assert_eq!(txa, decoded);
     }
+
+    #[test]
+    fn decode_versioned_should_use_format_of_app_version() {
+        use crate::init::address::RedeemAddress;
+        use crate::state::account::{
+            ConfidentialInit, CouncilNodeMeta, NodeMetadata, StakedStateAddress,
+        };
+        use crate::state::tendermint::{TendermintNodeId, TendermintValidatorPubKey};
+        use crate::tx::fee::Milli;
+        use std::convert::TryFrom;
+
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[0xcc; 32][..]).expect("secret key");
+        let address = StakedStateAddress::BasicRedeem(RedeemAddress::from(
+            &PublicKey::from_secret_key(&secp, &sk),
+        ));
+        let witness = StakedStateOpWitness::new(
+            secp.sign_recoverable(&Message::from_slice(&[0xdd; 32]).unwrap(), &sk),
+        );
+        let mut council_node = CouncilNodeMeta::new_with_details(
+            "node".to_owned(),
+            None,
+            TendermintValidatorPubKey::Ed25519([0xee; 32]),
+            ConfidentialInit {
+                keypackage: vec![0xff],
+            },
+        );
+        let join = |council_node: CouncilNodeMeta, commission_rate: Option<Milli>| {
+            TxAux::PublicTx(TxPublicAux::NodeJoinTx(
+                NodeJoinRequestTx {
+                    commission_rate,
+                    ..NodeJoinRequestTx::new(
+                        0,
+                        address,
+                        StakedStateOpAttributes::new(0),
+                        NodeMetadata::CouncilNode(council_node),
+                        Milli::default(),
+                    )
+                },
+                witness.clone(),
+            ))
+        };
+        // as decoded in version 1
+        let join_v1 = join(council_node.clone(), None);
+        // the commission rate at join was added in version 2
+        let join_commission = join(council_node.clone(), Some(Milli::new(0, 100)));
+        // the p2p node ID was added in version 2
+        council_node.node_info.p2p_node_id =
+            Some(TendermintNodeId::try_from(&[0x11; 20][..]).unwrap());
+        let join_v2 = join(council_node, Some(Milli::new(0, 100)));
+        let leave = TxAux::PublicTx(TxPublicAux::NodeLeaveTx(
+            NodeLeaveTx::new(0, address, StakedStateOpAttributes::new(0)),
+            witness.clone(),
+        ));
+
+        // in the 0.5 TX format (without the commission rate)
+        let mut join_v1_tx = Vec::new();
+        let mut join_v1_bytes = vec![1, 2];
+        match &join_v1 {
+            TxAux::PublicTx(TxPublicAux::NodeJoinTx(tx, witness)) => {
+                tx.encode_v1(&mut join_v1_tx);
+                join_v1_bytes.extend(&join_v1_tx);
+                witness.encode_to(&mut join_v1_bytes);
+            }
+            _ => unreachable!("node join tx"),
+        }
+        for app_version in 0..=1 {
+            assert_eq!(
+                TxAux::decode_versioned(app_version, &mut join_v1_bytes.as_slice()).unwrap(),
+                join_v1
+            );
+        }
+        // the request without the commission rate keeps its 0.5 TX ID
+        assert_eq!(join_v1.tx_id(), txid_hash(&join_v1_tx));
+        assert_eq!(
+            TxAux::decode_versioned(crate::APP_VERSION, &mut join_v1.encode().as_slice()).unwrap(),
+            join_v1
+        );
+        // the upgrades can be voted on in version 1
+        let vote = TxAux::PublicTx(TxPublicAux::ParamChangeVoteTx(
+            ParamChangeVoteTx::new(
+                0,
+                address,
+                StakedStateOpAttributes::new(0),
+                [0x22; 32],
+                true,
+            ),
+            witness.clone(),
+        ));
+        for app_version in [1, crate::APP_VERSION].iter() {
+            assert_eq!(
+                TxAux::decode_versioned(*app_version, &mut vote.encode().as_slice()).unwrap(),
+                vote
+            );
+        }
+        for tx in [join_commission, join_v2, leave].iter() {
+            assert!(TxAux::decode_versioned(1, &mut tx.encode().as_slice()).is_err());
+            assert_eq!(
+                &TxAux::decode_versioned(crate::APP_VERSION, &mut tx.encode().as_slice()).unwrap(),
+                tx
+            );
+        }
+        assert!(
+            TxAux::decode_versioned(crate::APP_VERSION + 1, &mut join_v1_bytes.as_slice()).is_err()
+        );
+    }
 }
@@ -7,7 +7,7 @@ use std::prelude::v1::Vec;
 // TODO: switch to normal signatures + explicit public key
 use secp256k1::{self, recovery::RecoverableSignature, schnorrsig::SchnorrSignature};
 
-use crate::common::Proof;
+use crate::common::{Proof, H256};
 use crate::tx::witness::tree::{RawSignature, RawXOnlyPubkey};
 
 /// ETH-style recoverable ECDSA
@@ -101,6 +101,12 @@ pub enum TxInWitness {
     /// BIP340-compatible Schnorr signature
     /// + Merkle proof from the pubkey leaf to the address root
     TreeSig(SchnorrSignature, Proof<RawXOnlyPubkey>),
+    /// claim of a hash-timelocked output by its recipient:
+    /// the hash preimage + the same as `TreeSig` (for the recipient address)
+    HashLockClaim(H256, SchnorrSignature, Proof<RawXOnlyPubkey>),
+    /// refund of an expired hash-timelocked output:
+    /// the same as `TreeSig` (for the refund address)
+    TimeLockRefund(SchnorrSignature, Proof<RawXOnlyPubkey>),
 }
 
 impl fmt::Display for TxInWitness {
@@ -117,12 +123,25 @@ impl Encode for TxInWitness {
                 schnorrsig.serialize_default().encode_to(dest);
                 proof.encode_to(dest);
             }
+            TxInWitness::HashLockClaim(ref preimage, ref schnorrsig, ref proof) => {
+                dest.push_byte(1);
+                preimage.encode_to(dest);
+                schnorrsig.serialize_default().encode_to(dest);
+                proof.encode_to(dest);
+            }
+            TxInWitness::TimeLockRefund(ref schnorrsig, ref proof) => {
+                dest.push_byte(2);
+                schnorrsig.serialize_default().encode_to(dest);
+                proof.encode_to(dest);
+            }
         }
     }
 
     fn size_hint(&self) -> usize {
         match self {
             TxInWitness::TreeSig(_, ref proof) => 65 + proof.size_hint(),
+            TxInWitness::HashLockClaim(_, _, ref proof) => 97 + proof.size_hint(),
+            TxInWitness::TimeLockRefund(_, ref proof) => 65 + proof.size_hint(),
         }
     }
 }
@@ -138,6 +157,21 @@ impl Decode for TxInWitness {
                 let proof = Proof::decode(input)?;
                 Ok(TxInWitness::TreeSig(schnorrsig, proof))
             }
+            1 => {
+                let preimage = H256::decode(input)?;
+                let raw_sig = RawSignature::decode(input)?;
+                let schnorrsig = SchnorrSignature::from_default(&raw_sig)
+                    .map_err(|_| Error::from("Unable to parse schnorr signature"))?;
+                let proof = Proof::decode(input)?;
+                Ok(TxInWitness::HashLockClaim(preimage, schnorrsig, proof))
+            }
+            2 => {
+                let raw_sig = RawSignature::decode(input)?;
+                let schnorrsig = SchnorrSignature::from_default(&raw_sig)
+                    .map_err(|_| Error::from("Unable to parse schnorr signature"))?;
+                let proof = Proof::decode(input)?;
+                Ok(TxInWitness::TimeLockRefund(schnorrsig, proof))
+            }
             _ => Err(Error::from("Invalid tag")),
         }
     }
@@ -1,12 +1,15 @@
+use chain_core::common::H256;
 use chain_core::init::address::RedeemAddress;
 use chain_core::init::coin::Coin;
 use chain_core::init::config::{
-    InitConfig, InitNetworkParameters, JailingParameters, RewardsParameters, SlashRatio,
-    SlashingParameters,
+    DistributionError, InitConfig, InitNetworkParameters, JailingParameters, NetworkParameters,
+    RewardsParameters, SlashRatio, SlashingParameters,
 };
-use chain_core::state::account::StakedStateDestination;
-use chain_core::state::tendermint::TendermintValidatorPubKey;
-use chain_core::tx::fee::{LinearFee, Milli};
+use chain_core::state::account::{StakedStateAddress, StakedStateDestination};
+use chain_core::state::tendermint::{BlockHeight, TendermintValidatorPubKey};
+use chain_core::state::RewardsPoolState;
+use chain_core::tx::fee::{FeePolicy, LinearFee, Milli};
+use parity_scale_codec::Encode;
 use serde::Deserialize;
 use std::collections::BTreeMap;
 use std::str::FromStr;
@@ -58,11 +61,12 @@ fn test_verify_test_example_snapshot() {
     }
     let constant_fee = Milli::try_new(1, 25).unwrap();
     let coefficient_fee = Milli::try_new(1, 1).unwrap();
-    let fee_policy = LinearFee::new(constant_fee, coefficient_fee);
+    let fee_policy = FeePolicy::Linear(LinearFee::new(constant_fee, coefficient_fee));
     let expansion_cap = Coin::new(951_6484_5705_9733_7034).unwrap();
     let mut params = InitNetworkParameters {
         initial_fee_policy: fee_policy,
         required_council_node_stake: Coin::new(5000_0000_0000_0000).unwrap(),
+        required_community_node_stake: Coin::new(5000_0000_0000_0000).unwrap(),
         jailing_config: JailingParameters {
             block_signing_window: 100,
             missed_block_threshold: 50,
@@ -77,6 +81,9 @@ fn test_verify_test_example_snapshot() {
             monetary_expansion_r0: "0.5".parse().unwrap(),
             monetary_expansion_tau: 166666600,
             monetary_expansion_decay: 999860,
+            fee_burn_ratio: Milli::default(),
+            fee_treasury_ratio: Milli::default(),
+            treasury_address: None,
         },
         max_validators: 1,
     };
@@ -93,3 +100,145 @@ fn test_verify_test_example_snapshot() {
         .validate_config_get_genesis(DEFAULT_GENESIS_TIME)
         .is_err());
 }
+
+#[test]
+fn test_fee_split() {
+    let treasury_address = StakedStateAddress::BasicRedeem(
+        "0x2440ad2533c66d91eb97807a339be13556d04990"
+            .parse::<RedeemAddress>()
+            .unwrap(),
+    );
+    let mut rewards_config = RewardsParameters {
+        monetary_expansion_cap: Coin::zero(),
+        reward_period_seconds: 24 * 60 * 60,
+        monetary_expansion_r0: "0.5".parse().unwrap(),
+        monetary_expansion_tau: 166666600,
+        monetary_expansion_decay: 999860,
+        fee_burn_ratio: "0.5".parse().unwrap(),
+        fee_treasury_ratio: "0.3".parse().unwrap(),
+        treasury_address: None,
+    };
+    // treasury address is required
+    assert!(rewards_config.validate().is_err());
+    rewards_config.treasury_address = Some(treasury_address);
+    assert!(rewards_config.validate().is_ok());
+
+    let split = rewards_config.split_fee(Coin::new(1001).unwrap());
+    assert_eq!(split.burned, Coin::new(500).unwrap());
+    assert_eq!(split.treasury, Coin::new(300).unwrap());
+    assert_eq!(split.rewards_pool, Coin::new(201).unwrap());
+
+    rewards_config.fee_treasury_ratio = "0.6".parse().unwrap();
+    assert!(rewards_config.validate().is_err());
+}
+
+fn get_test_params() -> InitNetworkParameters {
+    InitNetworkParameters {
+        initial_fee_policy: FeePolicy::Linear(LinearFee::new(Milli::default(), Milli::default())),
+        required_council_node_stake: Coin::new(5000_0000_0000_0000).unwrap(),
+        required_community_node_stake: Coin::new(5000_0000_0000_0000).unwrap(),
+        jailing_config: JailingParameters {
+            block_signing_window: 100,
+            missed_block_threshold: 50,
+        },
+        slashing_config: SlashingParameters {
+            liveness_slash_percent: SlashRatio::from_str("0.1").unwrap(),
+            byzantine_slash_percent: SlashRatio::from_str("0.2").unwrap(),
+        },
+        rewards_config: RewardsParameters {
+            monetary_expansion_cap: Coin::zero(),
+            reward_period_seconds: 24 * 60 * 60,
+            monetary_expansion_r0: "0.5".parse().unwrap(),
+            monetary_expansion_tau: 166666600,
+            monetary_expansion_decay: 999860,
+            fee_burn_ratio: Milli::default(),
+            fee_treasury_ratio: Milli::default(),
+            treasury_address: None,
+        },
+        max_validators: 1,
+    }
+}
+
+#[test]
+fn test_community_node_stake_defaults_to_council_node_stake() {
+    let mut params = get_test_params();
+    params.required_community_node_stake = Coin::unit();
+    let mut json = serde_json::to_value(&params).unwrap();
+    let parsed: InitNetworkParameters = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(parsed, params);
+
+    // genesis configs from before the community nodes
+    json.as_object_mut()
+        .unwrap()
+        .remove("required_community_node_stake");
+    let parsed: InitNetworkParameters = serde_json::from_value(json).unwrap();
+    assert_eq!(
+        parsed.required_community_node_stake,
+        params.required_council_node_stake
+    );
+}
+
+#[test]
+fn test_app_version_1_hashes_in_0_5_layout() {
+    let params = get_test_params();
+    let rewards = params.rewards_config;
+    // NetworkParameters::Genesis with the 0.5 fields
+    let mut params_v1 = vec![0u8];
+    params_v1.extend(
+        (
+            LinearFee::new(Milli::default(), Milli::default()),
+            params.required_council_node_stake,
+            params.jailing_config,
+            params.slashing_config,
+            rewards.monetary_expansion_cap,
+            rewards.reward_period_seconds,
+            rewards.monetary_expansion_r0,
+            rewards.monetary_expansion_tau,
+            rewards.monetary_expansion_decay,
+            params.max_validators,
+        )
+            .encode(),
+    );
+    let network_params = NetworkParameters::Genesis(params.clone());
+    assert_eq!(network_params.hash(1), H256::from(blake3::hash(&params_v1)));
+    assert_eq!(
+        network_params.hash(2),
+        H256::from(blake3::hash(&network_params.encode()))
+    );
+
+    let mut rewards_pool =
+        RewardsPoolState::new(DEFAULT_GENESIS_TIME, rewards.monetary_expansion_tau);
+    rewards_pool.fees_to_rewards_pool = Coin::one();
+    let rewards_pool_v1 = (
+        Coin::zero(),
+        BlockHeight::genesis(),
+        DEFAULT_GENESIS_TIME,
+        Coin::zero(),
+        rewards.monetary_expansion_tau,
+    )
+        .encode();
+    assert_eq!(
+        rewards_pool.hash(1),
+        H256::from(blake3::hash(&rewards_pool_v1))
+    );
+    assert_ne!(rewards_pool.hash(2), rewards_pool.hash(1));
+
+    // the parameters added later can't be committed in app version 1
+    let mut nodes = BTreeMap::new();
+    nodes.insert(
+        RedeemAddress::from([0x01; 20]),
+        (
+            "no-name".to_owned(),
+            None,
+            TendermintValidatorPubKey::Ed25519([0x02; 32]),
+            mock_confidential_init(),
+        ),
+    );
+    let mut config = InitConfig::new(BTreeMap::new(), params, nodes);
+    config.app_version = 1;
+    config.network_params.rewards_config.fee_burn_ratio = "0.5".parse().unwrap();
+    assert!(matches!(
+        config.validate_config_get_genesis(DEFAULT_GENESIS_TIME),
+        Err(DistributionError::UnsupportedNetworkParameters(_))
+    ));
+}
@@ -4,17 +4,23 @@ use bit_vec::BitVec;
 use parity_scale_codec::{Decode, Encode};
 
 use crate::jellyfish::Version;
-use chain_core::common::H256;
+use crate::migration::SchemaVersion;
+use chain_core::common::{H256, HASH_SIZE_256};
+use chain_core::state::account::{StakedStateAddress, StakingHistoryEntry, StakingHistoryPage};
+use chain_core::state::governance::UpgradePlan;
 use chain_core::state::tendermint::BlockHeight;
 use chain_core::tx::data::{
     input::{TxoPointer, TxoSize},
     TxId,
 };
+use chain_core::TxCreation;
 
 use super::buffer::{GetKV, StoreKV};
 use super::{
     LookupItem, StoredChainState, CHAIN_ID_KEY, COL_APP_HASHS, COL_APP_STATES, COL_EXTRA,
-    COL_NODE_INFO, COL_STAKING_VERSIONS, GENESIS_APP_HASH_KEY, LAST_STATE_KEY,
+    COL_NODE_INFO, COL_STAKING_VERSIONS, COL_TX_META, CONSENSUS_PARAMS_KEY, GENESIS_APP_HASH_KEY,
+    LAST_STATE_KEY, PRUNED_HEIGHT_KEY, SCHEMA_VERSION_KEY, STAKING_HISTORY_KEY_PREFIX,
+    TX_CREATION_KEY_SUFFIX, TX_SPENT_KEY_SUFFIX, UPGRADE_HALT_KEY,
 };
 
 pub fn get_last_app_state(db: &impl GetKV) -> Option<Vec<u8>> {
@@ -71,6 +77,39 @@ pub fn get_historical_staking_version(db: &impl GetKV, height: BlockHeight) -> O
     Version::decode(&mut sah.as_slice()).ok()
 }
 
+/// the historical states (and staking versions) below this height may have been pruned
+pub fn get_pruned_height(db: &impl GetKV) -> Option<BlockHeight> {
+    let value = db.get(&(COL_EXTRA, PRUNED_HEIGHT_KEY.to_vec()))?;
+    BlockHeight::decode(&mut value.as_slice()).ok()
+}
+
+/// the protobuf-serialized consensus parameters accepted in InitChain
+pub fn get_consensus_params(db: &impl GetKV) -> Option<Vec<u8>> {
+    db.get(&(COL_EXTRA, CONSENSUS_PARAMS_KEY.to_vec()))
+}
+
+/// the upgrade the node halted for (the binary didn't support its app version)
+pub fn get_upgrade_halt(db: &impl GetKV) -> Option<UpgradePlan> {
+    let value = db.get(&(COL_EXTRA, UPGRADE_HALT_KEY.to_vec()))?;
+    UpgradePlan::decode(&mut value.as_slice()).ok()
+}
+
+/// records that the node halted at the upgrade height
+pub fn store_upgrade_halt(db: &mut impl StoreKV, plan: &UpgradePlan) {
+    db.set((COL_EXTRA, UPGRADE_HALT_KEY.to_vec()), plan.encode());
+}
+
+/// removes the halt marker once the upgrade is applied
+pub fn clear_upgrade_halt(db: &mut impl StoreKV) {
+    db.delete((COL_EXTRA, UPGRADE_HALT_KEY.to_vec()));
+}
+
+/// the version of the storage layout (None if the storage is new or was created before it was recorded)
+pub fn get_schema_version(db: &impl GetKV) -> Option<SchemaVersion> {
+    let value = db.get(&(COL_EXTRA, SCHEMA_VERSION_KEY.to_vec()))?;
+    SchemaVersion::decode(&mut value.as_slice()).ok()
+}
+
 pub fn store_chain_state<T: StoredChainState>(
     db: &mut impl StoreKV,
     genesis_state: &T,
@@ -140,13 +179,110 @@ pub fn spend_utxos(db: &mut impl StoreKV, txins: &[TxoPointer]) {
     }
 }
 
-pub fn create_utxo(db: &mut impl StoreKV, no_of_outputs: TxoSize, txid: &TxId) {
+pub fn create_utxo(
+    db: &mut impl StoreKV,
+    no_of_outputs: TxoSize,
+    txid: &TxId,
+    created: &TxCreation,
+) {
     insert_item(
         db,
         LookupItem::TxMetaSpent,
         *txid,
         BitVec::from_elem(no_of_outputs as usize, false).to_bytes(),
     );
+    db.set((COL_TX_META, tx_creation_key(txid)), created.encode());
+}
+
+fn tx_creation_key(txid: &TxId) -> Vec<u8> {
+    let mut key = txid.to_vec();
+    key.extend_from_slice(TX_CREATION_KEY_SUFFIX);
+    key
+}
+
+/// the block in which the transaction was committed
+/// (None if the transaction isn't stored or was committed before it was recorded)
+pub fn get_tx_creation(db: &impl GetKV, txid: &TxId) -> Option<TxCreation> {
+    let created = db.get(&(COL_TX_META, tx_creation_key(txid)))?;
+    TxCreation::decode(&mut created.as_slice()).ok()
+}
+
+fn tx_spent_key(txin: &TxoPointer) -> Vec<u8> {
+    let mut key = txin.id.to_vec();
+    key.extend_from_slice(TX_SPENT_KEY_SUFFIX);
+    key.extend_from_slice(&txin.index.encode());
+    key
+}
+
+/// records the block in which the outputs were spent
+pub fn store_spent_height(db: &mut impl StoreKV, txins: &[TxoPointer], height: BlockHeight) {
+    for txin in txins.iter() {
+        db.set((COL_TX_META, tx_spent_key(txin)), height.encode());
+    }
+}
+
+/// the block in which the output was spent
+/// (None if the output is unspent or was spent before it was recorded)
+pub fn get_spent_height(db: &impl GetKV, txin: &TxoPointer) -> Option<BlockHeight> {
+    let height = db.get(&(COL_TX_META, tx_spent_key(txin)))?;
+    BlockHeight::decode(&mut height.as_slice()).ok()
+}
+
+/// the number of events is stored under the prefix + address,
+/// the events are under the prefix + address + index (big endian, so they're ordered)
+fn staking_history_key(address: &StakedStateAddress, index: Option<u64>) -> Vec<u8> {
+    let mut key = STAKING_HISTORY_KEY_PREFIX.to_vec();
+    key.extend_from_slice(address.as_ref());
+    if let Some(index) = index {
+        key.extend_from_slice(&index.to_be_bytes());
+    }
+    key
+}
+
+/// the number of the recorded staking history events of the address
+pub fn get_staking_history_len(db: &impl GetKV, address: &StakedStateAddress) -> u64 {
+    db.get(&(COL_EXTRA, staking_history_key(address, None)))
+        .and_then(|value| u64::decode(&mut value.as_slice()).ok())
+        .unwrap_or(0)
+}
+
+/// appends the event to the staking history of the address
+pub fn append_staking_history(
+    db: &mut impl StoreKV,
+    address: &StakedStateAddress,
+    entry: &StakingHistoryEntry,
+) {
+    let len = get_staking_history_len(&*db, address);
+    db.set(
+        (COL_EXTRA, staking_history_key(address, Some(len))),
+        entry.encode(),
+    );
+    db.set(
+        (COL_EXTRA, staking_history_key(address, None)),
+        (len + 1).encode(),
+    );
+}
+
+/// at most `limit` staking history events of the address from the `start` index
+pub fn get_staking_history(
+    db: &impl GetKV,
+    address: &StakedStateAddress,
+    start: u64,
+    limit: u64,
+) -> StakingHistoryPage {
+    let total = get_staking_history_len(db, address);
+    let end = start.saturating_add(limit).min(total);
+    let entries = (start..end)
+        .filter_map(|index| {
+            let value = db.get(&(COL_EXTRA, staking_history_key(address, Some(index))))?;
+            StakingHistoryEntry::decode(&mut value.as_slice()).ok()
+        })
+        .collect();
+    StakingHistoryPage {
+        total,
+        start,
+        entries,
+    }
 }
 
 pub fn store_sealed_log(db: &mut impl StoreKV, txid: &TxId, sealed_log: &[u8]) {
@@ -169,3 +305,19 @@ pub fn lookup_input(db: &impl GetKV, txin: &TxoPointer) -> Option<bool> {
     lookup_item(db, LookupItem::TxMetaSpent, &txin.id)
         .and_then(|v| BitVec::from_bytes(&v).get(txin.index as usize))
 }
+
+/// the outputs marked as spent in an entry of `COL_TX_META`
+/// (none if it's not a UTXO bitset, e.g. the creation or spending records)
+pub fn spent_outputs_in_bitset(key: &[u8], value: &[u8]) -> Vec<TxoPointer> {
+    if key.len() != HASH_SIZE_256 {
+        return vec![];
+    }
+    let mut txid = TxId::default();
+    txid.copy_from_slice(key);
+    BitVec::from_bytes(value)
+        .iter()
+        .enumerate()
+        .filter(|(_, spent)| *spent)
+        .map(|(index, _)| TxoPointer::new(txid, index))
+        .collect()
+}
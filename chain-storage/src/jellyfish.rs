@@ -2,11 +2,11 @@ use std::convert::TryInto;
 use std::mem;
 use std::sync::Arc;
 
-use anyhow::{ensure, Result};
+use anyhow::{anyhow, ensure, Result};
 use jellyfish_merkle::iterator::JellyfishMerkleIterator;
 use jellyfish_merkle::{
     node_type::{LeafNode, Node, NodeKey},
-    HashValue, JellyfishMerkleTree, StaleNodeIndex, TreeReader,
+    AccountStateBlob, HashValue, JellyfishMerkleTree, StaleNodeIndex, TreeReader,
 };
 use kvdb::KeyValueDB;
 use parity_scale_codec::{Decode, Encode, Error, Input, Output};
@@ -15,25 +15,43 @@ use chain_core::common::{H256, HASH_SIZE_256};
 use chain_core::init::coin::{sum_coins, Coin, CoinError};
 use chain_core::state::account::{to_stake_key, StakedState, StakedStateAddress};
 use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::data::input::TxoPointer;
+use chain_core::tx::inclusion::OutputStatus;
+use chain_core::AppHashParts;
 
-use super::{COL_TRIE_NODE, COL_TRIE_STALED};
+use super::{COL_SPENT_TRIE_NODE, COL_SPENT_TRIE_STALED, COL_TRIE_NODE, COL_TRIE_STALED};
 use crate::buffer::{
     BufferGetter, BufferSimpleStore, Get, GetKV, MemStore, StakingBuffer, StoreKV,
 };
 
 pub use jellyfish_merkle::Version;
 
-pub struct KVReader<'a, S: GetKV>(&'a S);
+pub struct KVReader<'a, S: GetKV> {
+    storage: &'a S,
+    column: u32,
+}
 impl<'a, S: GetKV> KVReader<'a, S> {
+    /// reads the staking merkle trie
     pub fn new(storage: &'a S) -> Self {
-        Self(storage)
+        Self {
+            storage,
+            column: COL_TRIE_NODE,
+        }
+    }
+
+    /// reads the merkle trie of spent outputs
+    pub fn spent_outputs(storage: &'a S) -> Self {
+        Self {
+            storage,
+            column: COL_SPENT_TRIE_NODE,
+        }
     }
 }
 
 impl<'a, S: GetKV> TreeReader for KVReader<'a, S> {
     fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
-        self.0
-            .get(&(COL_TRIE_NODE, node_key.encode()?))
+        self.storage
+            .get(&(self.column, node_key.encode()?))
             .map(|bytes| Node::decode(&bytes))
             .transpose()
     }
@@ -59,10 +77,9 @@ impl<'a, S: GetKV> Get for StakingGetter<'a, S> {
     type Value = StakedState;
     fn get(&self, key: &Self::Key) -> Option<Self::Value> {
         // treat non exist version as empty set.
-        self.storage.get(&(
-            COL_TRIE_NODE,
-            NodeKey::new_empty_path(self.version).encode().unwrap(),
-        ))?;
+        if !has_staking_version(self.storage, self.version) {
+            return None;
+        }
         JellyfishMerkleTree::new(&KVReader::new(self.storage))
             .get_with_proof(HashValue::new(to_stake_key(key)), self.version)
             .expect("merkle trie internal error")
@@ -73,6 +90,16 @@ impl<'a, S: GetKV> Get for StakingGetter<'a, S> {
     }
 }
 
+/// Check if the merkle tree has a root node at the version
+pub fn has_staking_version<S: GetKV>(storage: &S, version: Version) -> bool {
+    storage
+        .get(&(
+            COL_TRIE_NODE,
+            NodeKey::new_empty_path(version).encode().unwrap(),
+        ))
+        .is_some()
+}
+
 /// Specialized for staking
 pub type StakingBufferStore<'a, S, H> = BufferSimpleStore<'a, StakingGetter<'a, S>, H>;
 /// Specialized for staking
@@ -83,11 +110,25 @@ pub fn put_stakings<'a, S: StoreKV>(
     storage: &mut S,
     version: Version,
     stakings: impl Iterator<Item = &'a StakedState>,
+) -> Result<H256> {
+    put_staking_blobs(
+        storage,
+        version,
+        stakings.map(|staking| (staking.key(), staking.encode())),
+    )
+}
+
+/// Put the encoded stakings (keyed by `to_stake_key`) into the merkle tree
+/// (e.g. when they are re-encoded by a storage migration)
+pub fn put_staking_blobs<S: StoreKV>(
+    storage: &mut S,
+    version: Version,
+    blobs: impl Iterator<Item = (H256, Vec<u8>)>,
 ) -> Result<H256> {
     let reader = KVReader::new(storage);
     let tree = JellyfishMerkleTree::new(&reader);
-    let stakings = stakings
-        .map(|staking| (HashValue::new(staking.key()), staking.encode().into()))
+    let stakings = blobs
+        .map(|(key, blob)| (HashValue::new(key), blob.into()))
         .collect::<Vec<_>>();
     ensure!(!stakings.is_empty(), "can't put empty stakings");
     let (root_hashes, batch) = tree.put_blob_sets(vec![stakings], version)?;
@@ -101,6 +142,26 @@ pub fn put_stakings<'a, S: StoreKV>(
     Ok(*root_hashes[0].as_ref())
 }
 
+/// Put all the stakings of a version into an empty merkle tree (e.g. restored from a snapshot),
+/// so the following versions can be built on top of it.
+pub fn restore_stakings<'a, S: StoreKV>(
+    storage: &mut S,
+    version: Version,
+    stakings: impl Iterator<Item = &'a StakedState>,
+) -> Result<H256> {
+    if version > 0 {
+        // the tree is updated from the root of the previous version, which is empty here
+        storage.set(
+            (
+                COL_TRIE_NODE,
+                NodeKey::new_empty_path(version - 1).encode()?,
+            ),
+            Node::new_null().encode()?,
+        );
+    }
+    put_stakings(storage, version, stakings)
+}
+
 /// Flush buffer to merkle trie
 pub fn flush_stakings<S: StoreKV>(
     storage: &mut S,
@@ -170,6 +231,7 @@ impl Decode for SparseMerkleProof {
 }
 
 impl SparseMerkleProof {
+    /// Verifies the staking state (or its absence if `value` is None) against the root hash
     pub fn verify(
         &self,
         root_hash: H256,
@@ -182,9 +244,65 @@ impl SparseMerkleProof {
             value.map(|staking| staking.encode().into()).as_ref(),
         )
     }
+
+    /// Verifies the output is in the merkle trie of spent outputs with the root hash
+    /// (or its absence if not `spent`); the zero root is the empty set
+    pub fn verify_spent_output(
+        &self,
+        root_hash: H256,
+        txin: &TxoPointer,
+        spent: bool,
+    ) -> Result<()> {
+        if root_hash == H256::default() {
+            ensure!(!spent, "no outputs are spent in the empty set");
+            return Ok(());
+        }
+        let blob: Option<AccountStateBlob> = if spent {
+            Some(txin.encode().into())
+        } else {
+            None
+        };
+        self.0.verify(
+            HashValue::new(root_hash),
+            HashValue::new(to_spent_output_key(txin)),
+            blob.as_ref(),
+        )
+    }
+}
+
+/// Proof of the status of a transaction output as of a block:
+/// its membership (or absence) in the merkle trie of spent outputs,
+/// whose root is a part of the block's app hash since app version 2
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct OutputStatusProof {
+    /// the height of the block
+    pub block_height: BlockHeight,
+    /// the parts of the block's app hash
+    pub app_hash_parts: AppHashParts,
+    /// the output status as of the block
+    pub status: OutputStatus,
+    /// the proof of the output in the merkle trie of spent outputs
+    pub proof: SparseMerkleProof,
+}
+
+impl OutputStatusProof {
+    /// Verifies the output status against the block with the (trusted) app hash
+    pub fn verify(&self, txin: &TxoPointer, app_hash: &H256) -> Result<()> {
+        ensure!(
+            self.app_hash_parts.app_hash() == *app_hash,
+            "app hash parts don't match the app hash"
+        );
+        let root_hash = self
+            .app_hash_parts
+            .spent_outputs_root
+            .ok_or_else(|| anyhow!("no spent outputs root before app version 2"))?;
+        self.proof
+            .verify_spent_output(root_hash, txin, self.status == OutputStatus::Spent)
+    }
 }
 
 /// Get with proof from underlying storage.
+/// If the staking state doesn't exist, the proof is a non-membership proof of the address.
 pub fn get_with_proof<S: GetKV>(
     storage: &S,
     version: Version,
@@ -221,7 +339,7 @@ fn encode_stale_node_index(index: &StaleNodeIndex) -> Result<Vec<u8>> {
     Ok(encoded)
 }
 
-fn decode_stale_node_index(data: &[u8]) -> Result<StaleNodeIndex> {
+pub(crate) fn decode_stale_node_index(data: &[u8]) -> Result<StaleNodeIndex> {
     let version_size = mem::size_of::<u64>();
 
     let stale_since_version = u64::from_be_bytes(data[..version_size].try_into().unwrap());
@@ -238,6 +356,20 @@ pub fn iter_stakings<S: GetKV>(
     storage: &S,
     version: Version,
 ) -> impl Iterator<Item = StakedState> + '_ {
+    iter_staking_blobs(storage, version).map(|(key, blob)| {
+        let staking =
+            StakedState::decode(&mut blob.as_slice()).expect("jellyfish storage corrupted");
+        assert_eq!(key, staking.key());
+        staking
+    })
+}
+
+/// Iterate through all the encoded stakings (with their keys), without decoding them
+/// (e.g. when they were encoded in the layout of an older storage schema version)
+pub fn iter_staking_blobs<S: GetKV>(
+    storage: &S,
+    version: Version,
+) -> impl Iterator<Item = (H256, Vec<u8>)> + '_ {
     JellyfishMerkleIterator::new(
         Arc::new(KVReader::new(storage)),
         version,
@@ -246,9 +378,8 @@ pub fn iter_stakings<S: GetKV>(
     .expect("jellyfish storage internal error")
     .map(|mblob| {
         let (key, blob) = mblob.expect("jellyfish storage internal error");
-        let staking = StakedState::decode(&mut blob.as_ref()).expect("jellyfish storage corrupted");
-        assert_eq!(key, HashValue::new(staking.key()));
-        staking
+        let key: H256 = *key.as_ref();
+        (key, blob.as_ref().to_vec())
     })
 }
 
@@ -257,16 +388,148 @@ pub fn sum_staking_coins<S: GetKV>(
     storage: &S,
     version: Version,
 ) -> std::result::Result<Coin, CoinError> {
-    sum_coins(
-        iter_stakings(storage, version)
-            .flat_map(|staking| vec![staking.bonded, staking.unbonded].into_iter()),
-    )
+    sum_coins(iter_stakings(storage, version).flat_map(|staking| {
+        vec![staking.bonded, staking.unbonded, staking.delegated()].into_iter()
+    }))
+}
+
+/// The key of the output in the merkle trie of spent outputs
+pub fn to_spent_output_key(txin: &TxoPointer) -> H256 {
+    blake3::hash(&txin.encode()).into()
+}
+
+/// Check if the merkle trie of spent outputs has a root node at the block height
+pub fn has_spent_outputs_version<S: GetKV>(storage: &S, height: BlockHeight) -> bool {
+    storage
+        .get(&(
+            COL_SPENT_TRIE_NODE,
+            NodeKey::new_empty_path(height.value()).encode().unwrap(),
+        ))
+        .is_some()
+}
+
+/// the root hash of the merkle trie of spent outputs (zero for the empty set, as in genesis)
+fn spent_outputs_root(root: &Node) -> H256 {
+    match root {
+        Node::Null => H256::default(),
+        _ => *root.hash().as_ref(),
+    }
+}
+
+/// Put the outputs spent in the block at the height into the merkle trie of spent outputs.
+/// Every block has its own version (the previous root node is copied if nothing was spent),
+/// so the spent outputs can be proven as of any stored height.
+pub fn put_spent_outputs<S: StoreKV>(
+    storage: &mut S,
+    height: BlockHeight,
+    spent: impl Iterator<Item = TxoPointer>,
+) -> Result<H256> {
+    let version = height.value();
+    let blobs = spent
+        .map(|txin| {
+            (
+                HashValue::new(to_spent_output_key(&txin)),
+                txin.encode().into(),
+            )
+        })
+        .collect::<Vec<_>>();
+    if !blobs.is_empty() {
+        let reader = KVReader::spent_outputs(storage);
+        let tree = JellyfishMerkleTree::new(&reader);
+        let (root_hashes, batch) = tree.put_blob_sets(vec![blobs], version)?;
+        assert_eq!(root_hashes.len(), 1);
+        for (key, node) in batch.node_batch.iter() {
+            storage.set((COL_SPENT_TRIE_NODE, key.encode()?), node.encode()?);
+        }
+        for key in batch.stale_node_index_batch {
+            storage.set(
+                (COL_SPENT_TRIE_STALED, encode_stale_node_index(&key)?),
+                vec![],
+            );
+        }
+        return Ok(*root_hashes[0].as_ref());
+    }
+    ensure!(
+        version > 0,
+        "no merkle trie of spent outputs before genesis"
+    );
+    let previous = NodeKey::new_empty_path(version - 1);
+    let root = KVReader::spent_outputs(storage)
+        .get_node_option(&previous)?
+        .ok_or_else(|| anyhow!("no merkle trie of spent outputs at version {}", version - 1))?;
+    storage.set(
+        (
+            COL_SPENT_TRIE_NODE,
+            NodeKey::new_empty_path(version).encode()?,
+        ),
+        root.encode()?,
+    );
+    storage.set(
+        (
+            COL_SPENT_TRIE_STALED,
+            encode_stale_node_index(&StaleNodeIndex {
+                stale_since_version: version,
+                node_key: previous,
+            })?,
+        ),
+        vec![],
+    );
+    Ok(spent_outputs_root(&root))
+}
+
+/// Put all the outputs spent as of the height into an empty merkle trie of spent outputs
+/// (none in genesis, or the outputs marked in the UTXO bitsets of a snapshot or a migrated storage),
+/// so the following heights can be built on top of it.
+pub fn restore_spent_outputs<S: StoreKV>(
+    storage: &mut S,
+    height: BlockHeight,
+    spent: impl Iterator<Item = TxoPointer>,
+) -> Result<H256> {
+    let mut spent = spent.peekable();
+    let version = height.value();
+    if spent.peek().is_none() {
+        storage.set(
+            (
+                COL_SPENT_TRIE_NODE,
+                NodeKey::new_empty_path(version).encode()?,
+            ),
+            Node::new_null().encode()?,
+        );
+        return Ok(H256::default());
+    }
+    ensure!(version > 0, "no outputs can be spent in genesis");
+    // the trie is updated from the root of the previous version, which is empty here
+    storage.set(
+        (
+            COL_SPENT_TRIE_NODE,
+            NodeKey::new_empty_path(version - 1).encode()?,
+        ),
+        Node::new_null().encode()?,
+    );
+    put_spent_outputs(storage, height, spent)
+}
+
+/// If the output was spent as of the block height, with the proof of its membership
+/// (or absence) in the merkle trie of spent outputs; None if the trie of the height isn't stored
+/// (e.g. before a storage migration or a restored snapshot).
+pub fn get_spent_output_with_proof<S: GetKV>(
+    storage: &S,
+    height: BlockHeight,
+    txin: &TxoPointer,
+) -> Option<(bool, SparseMerkleProof)> {
+    if !has_spent_outputs_version(storage, height) {
+        return None;
+    }
+    let (blob, proof) = JellyfishMerkleTree::new(&KVReader::spent_outputs(storage))
+        .get_with_proof(HashValue::new(to_spent_output_key(txin)), height.value())
+        .expect("merkle trie internal error");
+    Some((blob.is_some(), SparseMerkleProof(proof)))
 }
 
 #[cfg(test)]
 mod tests {
     use jellyfish_merkle::node_type::Node;
-    use jellyfish_merkle::{AccountStateBlob, CryptoHash};
+    use jellyfish_merkle::CryptoHash;
     use kvdb_memorydb::{create as create_memorydb, InMemory};
 
     use super::*;
@@ -417,6 +680,111 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_non_existence_proof() {
+        let mut app = App::new();
+        let staking = StakedState {
+            bonded: Coin::one(),
+            ..StakedState::default(StakedStateAddress::BasicRedeem([0x01; 20].into()))
+        };
+        app.staking_store().set_staking(staking.clone());
+        app.commit();
+
+        let unknown = StakedStateAddress::BasicRedeem([0x02; 20].into());
+        let (mstaking, proof) = get_with_proof(&app.storage, 0, &unknown);
+        assert!(mstaking.is_none());
+        let proof = SparseMerkleProof::decode(&mut proof.encode().as_slice()).unwrap();
+        proof.verify(app.root_hash, &unknown, None).unwrap();
+        let fake = StakedState::default(unknown);
+        assert!(proof.verify(app.root_hash, &unknown, Some(&fake)).is_err());
+        // the proof of an existing staking can't prove its absence
+        let (mstaking, proof) = get_with_proof(&app.storage, 0, &staking.address);
+        assert_eq!(mstaking.as_ref(), Some(&staking));
+        proof
+            .verify(app.root_hash, &staking.address, Some(&staking))
+            .unwrap();
+        assert!(proof.verify(app.root_hash, &staking.address, None).is_err());
+    }
+
+    #[test]
+    fn check_spent_outputs_proof() {
+        let store = create_memorydb(NUM_COLUMNS);
+        let put = |height: u64, spent: Vec<TxoPointer>| {
+            let height = BlockHeight::new(height);
+            let mut buffer = KVBuffer::new();
+            let root = {
+                let mut kv_store = BufferStore::new(&store, &mut buffer);
+                if height == BlockHeight::genesis() {
+                    restore_spent_outputs(&mut kv_store, height, spent.into_iter())
+                } else {
+                    put_spent_outputs(&mut kv_store, height, spent.into_iter())
+                }
+            };
+            flush_kvdb(&store, buffer).unwrap();
+            root.unwrap()
+        };
+        let txin = TxoPointer::new([1; 32], 0);
+        let other = TxoPointer::new([1; 32], 1);
+        let empty_root = put(0, vec![]);
+        assert_eq!(empty_root, H256::default());
+        // the empty root is copied if nothing was spent
+        assert_eq!(put(1, vec![]), empty_root);
+        let root = put(2, vec![txin.clone()]);
+        assert_ne!(root, empty_root);
+        assert_eq!(put(3, vec![]), root);
+        assert!(!has_spent_outputs_version(&store, BlockHeight::new(4)));
+
+        let (spent, proof) =
+            get_spent_output_with_proof(&store, BlockHeight::new(1), &txin).unwrap();
+        assert!(!spent);
+        proof.verify_spent_output(empty_root, &txin, false).unwrap();
+        assert!(proof.verify_spent_output(empty_root, &txin, true).is_err());
+
+        for height in 2..4 {
+            let height = BlockHeight::new(height);
+            let (spent, proof) = get_spent_output_with_proof(&store, height, &txin).unwrap();
+            assert!(spent);
+            let proof = SparseMerkleProof::decode(&mut proof.encode().as_slice()).unwrap();
+            proof.verify_spent_output(root, &txin, true).unwrap();
+            assert!(proof.verify_spent_output(root, &txin, false).is_err());
+            assert!(proof.verify_spent_output(root, &other, true).is_err());
+
+            let (spent, proof) = get_spent_output_with_proof(&store, height, &other).unwrap();
+            assert!(!spent);
+            proof.verify_spent_output(root, &other, false).unwrap();
+            assert!(proof.verify_spent_output(root, &other, true).is_err());
+        }
+        assert!(get_spent_output_with_proof(&store, BlockHeight::new(4), &txin).is_none());
+
+        let app_hash_parts = AppHashParts {
+            valid_tx_root: [0; 32],
+            account_root: [1; 32],
+            rewards_pool_hash: [2; 32],
+            network_params_hash: [3; 32],
+            node_state_hash: Some([4; 32]),
+            spent_outputs_root: Some(root),
+        };
+        let (_, proof) = get_spent_output_with_proof(&store, BlockHeight::new(3), &txin).unwrap();
+        let status_proof = OutputStatusProof {
+            block_height: BlockHeight::new(3),
+            app_hash_parts,
+            status: OutputStatus::Spent,
+            proof,
+        };
+        let app_hash = app_hash_parts.app_hash();
+        status_proof.verify(&txin, &app_hash).unwrap();
+        assert!(status_proof.verify(&txin, &[0; 32]).is_err());
+        let mut forged = status_proof.clone();
+        forged.status = OutputStatus::Unspent;
+        assert!(forged.verify(&txin, &app_hash).is_err());
+        // the blocks before app version 2 have no spent outputs root
+        let mut forged = status_proof;
+        forged.app_hash_parts.spent_outputs_root = None;
+        assert!(forged
+            .verify(&txin, &forged.app_hash_parts.app_hash())
+            .is_err());
+    }
+
     /// Test encoding of jellyfish nodes
     #[test]
     fn check_nodes() {
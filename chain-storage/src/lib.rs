@@ -1,9 +1,13 @@
 mod api;
 pub mod buffer;
 pub mod jellyfish;
+pub mod migration;
+pub mod pruning;
+pub mod snapshot;
 
 use crate::buffer::{flush_storage, BufferStore, Get, KVBuffer};
 use crate::jellyfish::{put_stakings, Version};
+use crate::pruning::{prune_staking_versions, PruningMode};
 use chain_core::common::H256;
 use chain_core::state::account::StakedState;
 use chain_core::state::tendermint::BlockHeight;
@@ -16,6 +20,8 @@ pub use api::*;
 
 // database columns
 /// Column for UTXOs: TxId => BitVec (where each bit indicates whether the output was spent or not, e.g. b[0] == true if output 0 was spent in a given TX)
+/// also TxId ++ TX_CREATION_KEY_SUFFIX => TxCreation (the block in which the TX was committed)
+/// and TxId ++ TX_SPENT_KEY_SUFFIX ++ output index => BlockHeight (the block in which the output was spent)
 pub const COL_TX_META: u32 = 0;
 /// Column for TX witnesses: TxId => TxWitness
 pub const COL_WITNESS: u32 = 1;
@@ -39,12 +45,23 @@ pub const COL_TRIE_NODE: u32 = 9;
 pub const COL_TRIE_STALED: u32 = 10;
 /// Column to store block height -> staking version
 pub const COL_STAKING_VERSIONS: u32 = 11;
+/// Column for the merkle trie of spent outputs (versioned by block height)
+pub const COL_SPENT_TRIE_NODE: u32 = 12;
+/// Column for staled node key in the merkle trie of spent outputs
+pub const COL_SPENT_TRIE_STALED: u32 = 13;
 /// Number of columns in DB
-pub const NUM_COLUMNS: u32 = 12;
+pub const NUM_COLUMNS: u32 = 14;
 
 pub const CHAIN_ID_KEY: &[u8] = b"chain_id";
+pub const CONSENSUS_PARAMS_KEY: &[u8] = b"init_chain_consensus_params";
 pub const GENESIS_APP_HASH_KEY: &[u8] = b"genesis_app_hash";
 pub const LAST_STATE_KEY: &[u8] = b"last_state";
+pub const PRUNED_HEIGHT_KEY: &[u8] = b"pruned_height";
+pub const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+pub const STAKING_HISTORY_KEY_PREFIX: &[u8] = b"staking_history";
+pub const TX_CREATION_KEY_SUFFIX: &[u8] = b"created";
+pub const TX_SPENT_KEY_SUFFIX: &[u8] = b"spent";
+pub const UPGRADE_HALT_KEY: &[u8] = b"upgrade_halt";
 
 pub enum StorageType {
     Node,
@@ -186,6 +203,34 @@ impl Storage {
         }
     }
 
+    /// opens the storage based on the provided config without taking the write lock
+    /// (as a RocksDB secondary instance, so it can be inspected while the node is running);
+    /// writes to it fail
+    #[cfg(feature = "kvdb-rocksdb")]
+    pub fn new_read_only(config: &StorageConfig<'_>) -> Self {
+        let secondary_path = std::env::temp_dir()
+            .join(format!("cro-storage-secondary-{}", std::process::id()))
+            .to_str()
+            .expect("invalid storage path")
+            .to_string();
+        let db = Arc::new(
+            kvdb_rocksdb::Database::open(
+                &kvdb_rocksdb::DatabaseConfig {
+                    // required by the secondary instances
+                    max_open_files: -1,
+                    secondary: Some(secondary_path),
+                    ..kvdb_rocksdb::DatabaseConfig::with_columns(NUM_COLUMNS)
+                },
+                &config.db_path(),
+            )
+            .expect("failed to open db"),
+        );
+        Storage {
+            db,
+            current_tx: None,
+        }
+    }
+
     fn get_or_create_tx(&mut self) -> &mut DBTransaction {
         match self.current_tx.as_mut() {
             Some(_tx) => {}
@@ -201,11 +246,11 @@ impl Storage {
         self.lookup_item(LookupItem::TxSealed, txid)
     }
 
-    /// currently for potential debugging / diagnostics
+    /// the accepted consensus parameters (returned in the "state" query)
     /// parameters are protobuf-serialized (what was passed in initchain)
     pub fn store_consensus_params(&mut self, cp: &[u8]) {
         let inittx = self.get_or_create_tx();
-        inittx.put(COL_EXTRA, b"init_chain_consensus_params", cp);
+        inittx.put(COL_EXTRA, CONSENSUS_PARAMS_KEY, cp);
     }
 
     pub fn get_genesis_app_hash(&self) -> H256 {
@@ -245,6 +290,11 @@ impl Storage {
             .expect("genesis app hash should be stored");
     }
 
+    /// iterates over all the entries of a column (e.g. for offline inspection)
+    pub fn iter_column(&self, col: u32) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + '_> {
+        self.db.iter(col)
+    }
+
     pub fn persist_write(&mut self) -> std::io::Result<()> {
         if let Some(dbtx) = self.current_tx.take() {
             self.db.write(dbtx)
@@ -253,6 +303,15 @@ impl Storage {
         }
     }
 
+    /// prunes the historical staking versions (and states) not kept by the pruning mode
+    pub fn prune_staking_versions(
+        &mut self,
+        mode: PruningMode,
+        current_version: Version,
+    ) -> std::io::Result<()> {
+        prune_staking_versions(&*self.db, mode, current_version)
+    }
+
     pub fn put_stakings(&mut self, version: Version, stakings: &[StakedState]) -> H256 {
         let mut kv_buffer = KVBuffer::new();
         let root_hash = put_stakings(
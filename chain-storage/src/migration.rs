@@ -0,0 +1,246 @@
+//! # Storage schema versions and migrations
+//!
+//! The version of the storage layout (columns and the encoding of the stored structures)
+//! is recorded under `SCHEMA_VERSION_KEY` in `COL_EXTRA`.
+//! When the layout changes, `CURRENT_SCHEMA_VERSION` is incremented and a step
+//! migrating the data from the previous version is registered,
+//! so that the existing storages are upgraded on startup instead of resyncing.
+//!
+//! The storages created before the version was recorded have an older layout than
+//! `INITIAL_SCHEMA_VERSION` (e.g. the node state, staking states and network parameters
+//! were encoded without the later fields), which can't be migrated, so they are rejected.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+use parity_scale_codec::Encode;
+
+use crate::buffer::{flush_storage, KVBuffer};
+use crate::{get_genesis_app_hash, get_schema_version, Storage, COL_EXTRA, SCHEMA_VERSION_KEY};
+
+/// Version of the storage layout
+pub type SchemaVersion = u32;
+
+/// The storage layout written by the current code
+/// (version 6: the merkle trie of spent outputs and its root in the top level state,
+/// see the steps registered by chain-abci for the previous versions)
+pub const CURRENT_SCHEMA_VERSION: SchemaVersion = 6;
+/// The first recorded storage layout
+pub const INITIAL_SCHEMA_VERSION: SchemaVersion = 1;
+
+/// Reads the data in the previous schema version and writes the changes into the buffer
+/// (flushed together with the new version number)
+pub type MigrationStep = fn(&Storage, &mut KVBuffer) -> Result<(), String>;
+
+/// Errors when upgrading the storage
+#[derive(Debug)]
+pub enum MigrationError {
+    /// the storage was written before the schema version was recorded
+    Unversioned,
+    /// the storage was written by a newer version of the code
+    NewerSchema(SchemaVersion),
+    /// no step registered to migrate from the version
+    MissingStep(SchemaVersion),
+    /// the step from the version failed
+    StepFailed(SchemaVersion, String),
+    /// the migrated data couldn't be written
+    Io(io::Error),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Unversioned => write!(
+                f,
+                "storage was created before the schema version was recorded, its layout can't be migrated (resync is needed)"
+            ),
+            MigrationError::NewerSchema(version) => write!(
+                f,
+                "storage schema version {} is newer than the supported one ({})",
+                version, CURRENT_SCHEMA_VERSION
+            ),
+            MigrationError::MissingStep(version) => write!(
+                f,
+                "no migration from storage schema version {} (resync is needed)",
+                version
+            ),
+            MigrationError::StepFailed(version, reason) => write!(
+                f,
+                "migration from storage schema version {} failed: {}",
+                version, reason
+            ),
+            MigrationError::Io(e) => write!(f, "failed to write the migrated storage: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+/// The registered migration steps up to the target schema version
+pub struct Migrations {
+    target: SchemaVersion,
+    steps: BTreeMap<SchemaVersion, (&'static str, MigrationStep)>,
+}
+
+impl Default for Migrations {
+    fn default() -> Self {
+        Migrations::new(CURRENT_SCHEMA_VERSION)
+    }
+}
+
+impl Migrations {
+    /// no steps registered yet
+    pub fn new(target: SchemaVersion) -> Self {
+        Migrations {
+            target,
+            steps: BTreeMap::new(),
+        }
+    }
+
+    /// registers the step migrating from the version `from` to `from + 1`
+    pub fn register(
+        &mut self,
+        from: SchemaVersion,
+        description: &'static str,
+        step: MigrationStep,
+    ) -> &mut Self {
+        let previous = self.steps.insert(from, (description, step));
+        assert!(
+            previous.is_none(),
+            "migration from schema version {} registered twice",
+            from
+        );
+        self
+    }
+
+    /// Upgrades the storage to the target schema version (each step is written atomically).
+    /// A new storage is marked with the target version, an unversioned one with data is rejected.
+    pub fn run(&self, storage: &mut Storage) -> Result<SchemaVersion, MigrationError> {
+        let mut version = match get_schema_version(storage) {
+            Some(version) => version,
+            None if storage.get_last_app_state().is_none()
+                && get_genesis_app_hash(storage).is_none() =>
+            {
+                write_schema_version(storage, KVBuffer::new(), self.target)?;
+                return Ok(self.target);
+            }
+            None => return Err(MigrationError::Unversioned),
+        };
+        if version > self.target {
+            return Err(MigrationError::NewerSchema(version));
+        }
+        while version < self.target {
+            let (description, step) = self
+                .steps
+                .get(&version)
+                .ok_or(MigrationError::MissingStep(version))?;
+            let mut buffer = KVBuffer::new();
+            step(storage, &mut buffer).map_err(|e| {
+                MigrationError::StepFailed(version, format!("{}: {}", description, e))
+            })?;
+            version += 1;
+            write_schema_version(storage, buffer, version)?;
+        }
+        Ok(version)
+    }
+}
+
+fn write_schema_version(
+    storage: &mut Storage,
+    mut buffer: KVBuffer,
+    version: SchemaVersion,
+) -> Result<(), MigrationError> {
+    buffer.insert(
+        (COL_EXTRA, SCHEMA_VERSION_KEY.to_vec()),
+        Some(version.encode()),
+    );
+    flush_storage(storage, buffer).map_err(MigrationError::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use kvdb_memorydb::create as create_memorydb;
+
+    use super::*;
+    use crate::buffer::Get;
+    use crate::{COL_NODE_INFO, LAST_STATE_KEY, NUM_COLUMNS};
+
+    fn storage_with_state(version: Option<SchemaVersion>) -> Storage {
+        let mut storage = Storage::new_db(Arc::new(create_memorydb(NUM_COLUMNS)));
+        let mut buffer = KVBuffer::new();
+        buffer.insert((COL_NODE_INFO, LAST_STATE_KEY.to_vec()), Some(vec![1]));
+        if let Some(version) = version {
+            buffer.insert(
+                (COL_EXTRA, SCHEMA_VERSION_KEY.to_vec()),
+                Some(version.encode()),
+            );
+        }
+        flush_storage(&mut storage, buffer).unwrap();
+        storage
+    }
+
+    fn append_state_byte(storage: &Storage, buffer: &mut KVBuffer) -> Result<(), String> {
+        let mut state = storage
+            .get(&(COL_NODE_INFO, LAST_STATE_KEY.to_vec()))
+            .ok_or("no state")?;
+        state.push(state.len() as u8 + 1);
+        buffer.insert((COL_NODE_INFO, LAST_STATE_KEY.to_vec()), Some(state));
+        Ok(())
+    }
+
+    #[test]
+    fn check_new_storage_is_marked() {
+        let mut storage = Storage::new_db(Arc::new(create_memorydb(NUM_COLUMNS)));
+        assert_eq!(Migrations::new(3).run(&mut storage).unwrap(), 3);
+        assert_eq!(get_schema_version(&storage), Some(3));
+    }
+
+    #[test]
+    fn check_migration_steps() {
+        let mut storage = storage_with_state(Some(INITIAL_SCHEMA_VERSION));
+        let mut migrations = Migrations::new(INITIAL_SCHEMA_VERSION + 2);
+        migrations
+            .register(INITIAL_SCHEMA_VERSION, "first", append_state_byte)
+            .register(INITIAL_SCHEMA_VERSION + 1, "second", append_state_byte);
+        assert_eq!(
+            migrations.run(&mut storage).unwrap(),
+            INITIAL_SCHEMA_VERSION + 2
+        );
+        assert_eq!(storage.get_last_app_state(), Some(vec![1, 2, 3]));
+        // nothing to do the next time
+        assert_eq!(
+            migrations.run(&mut storage).unwrap(),
+            INITIAL_SCHEMA_VERSION + 2
+        );
+        assert_eq!(storage.get_last_app_state(), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn check_migration_errors() {
+        let mut storage = storage_with_state(None);
+        assert!(matches!(
+            Migrations::new(INITIAL_SCHEMA_VERSION).run(&mut storage),
+            Err(MigrationError::Unversioned)
+        ));
+        assert_eq!(get_schema_version(&storage), None);
+
+        let mut storage = storage_with_state(Some(INITIAL_SCHEMA_VERSION));
+        assert!(matches!(
+            Migrations::new(INITIAL_SCHEMA_VERSION + 1).run(&mut storage),
+            Err(MigrationError::MissingStep(INITIAL_SCHEMA_VERSION))
+        ));
+        assert_eq!(
+            Migrations::new(INITIAL_SCHEMA_VERSION)
+                .run(&mut storage)
+                .unwrap(),
+            INITIAL_SCHEMA_VERSION
+        );
+        assert_eq!(get_schema_version(&storage), Some(INITIAL_SCHEMA_VERSION));
+        assert!(matches!(
+            Migrations::new(INITIAL_SCHEMA_VERSION - 1).run(&mut storage),
+            Err(MigrationError::NewerSchema(INITIAL_SCHEMA_VERSION))
+        ));
+    }
+}
@@ -0,0 +1,329 @@
+//! # Pruning of the staking merkle trie history
+//!
+//! Every staking version creates new Jellyfish nodes and marks the replaced ones as stale
+//! (in `COL_TRIE_STALED`, ordered by the version since which they are stale).
+//! A stale node is only needed by the versions between its creation and the version it became stale,
+//! so it can be deleted once none of these versions are kept.
+//!
+//! Together with the nodes, the historical entries of the pruned versions
+//! (`COL_STAKING_VERSIONS` and `COL_APP_STATES`) are deleted, so the historical queries
+//! of these heights fail instead of returning incomplete data.
+//!
+//! The merkle trie of spent outputs is versioned by the block height, so its stale nodes
+//! (in `COL_SPENT_TRIE_STALED`) are deleted once all the heights below them are pruned
+//! (only when keeping the recent versions, as some heights are kept when keeping every Kth version).
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use kvdb::KeyValueDB;
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::state::tendermint::BlockHeight;
+
+use crate::jellyfish::{decode_stale_node_index, Version};
+use crate::{
+    COL_APP_STATES, COL_EXTRA, COL_SPENT_TRIE_NODE, COL_SPENT_TRIE_STALED, COL_STAKING_VERSIONS,
+    COL_TRIE_NODE, COL_TRIE_STALED, PRUNED_HEIGHT_KEY,
+};
+
+/// How much of the staking merkle trie history is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruningMode {
+    /// keep all the staking versions
+    Archive,
+    /// keep only the last N staking versions
+    KeepRecent(Version),
+    /// keep every Kth staking version (and the latest one)
+    KeepEvery(Version),
+}
+
+impl Default for PruningMode {
+    fn default() -> Self {
+        PruningMode::Archive
+    }
+}
+
+impl PruningMode {
+    /// the versions below the returned one are pruned (unless kept by `keeps_version`)
+    fn prune_below(self, current_version: Version) -> Option<Version> {
+        match self {
+            PruningMode::Archive => None,
+            PruningMode::KeepRecent(n) => Some(current_version.saturating_add(1).saturating_sub(n)),
+            PruningMode::KeepEvery(_) => Some(current_version),
+        }
+    }
+
+    /// if the version below the pruning bound is kept
+    fn keeps_version(self, version: Version) -> bool {
+        match self {
+            PruningMode::KeepEvery(k) => version % k == 0,
+            _ => false,
+        }
+    }
+
+    /// if any version in `from..to` (below the pruning bound) is kept
+    fn keeps_any(self, from: Version, to: Version) -> bool {
+        match self {
+            PruningMode::KeepEvery(k) => {
+                let first_kept = if from % k == 0 {
+                    from
+                } else {
+                    (from / k + 1).saturating_mul(k)
+                };
+                first_kept < to
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for PruningMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PruningMode::Archive => write!(f, "archive"),
+            PruningMode::KeepRecent(n) => write!(f, "keep-recent:{}", n),
+            PruningMode::KeepEvery(k) => write!(f, "keep-every:{}", k),
+        }
+    }
+}
+
+/// parses "archive", "keep-recent:N" or "keep-every:K" (N and K being positive)
+impl FromStr for PruningMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "archive" {
+            return Ok(PruningMode::Archive);
+        }
+        let mut parts = s.splitn(2, ':');
+        let (mode, value) = (parts.next(), parts.next());
+        let value = value
+            .and_then(|v| v.parse::<Version>().ok())
+            .filter(|v| *v > 0)
+            .ok_or_else(|| format!("invalid number of versions in pruning mode: {}", s))?;
+        match mode {
+            Some("keep-recent") => Ok(PruningMode::KeepRecent(value)),
+            Some("keep-every") => Ok(PruningMode::KeepEvery(value)),
+            _ => Err(format!("unknown pruning mode: {}", s)),
+        }
+    }
+}
+
+/// Deletes the stale staking trie nodes and the historical states not kept by the pruning mode.
+/// It only depends on the current staking version, so it needs to run only when it changes.
+pub fn prune_staking_versions<S: KeyValueDB + ?Sized>(
+    db: &S,
+    mode: PruningMode,
+    current_version: Version,
+) -> io::Result<()> {
+    let prune_below = match mode.prune_below(current_version) {
+        Some(version) => version,
+        None => return Ok(()),
+    };
+    let mut tx = db.transaction();
+
+    for (key, _) in db.iter(COL_TRIE_STALED) {
+        let index = decode_stale_node_index(&key).expect("storage corrupted");
+        if index.stale_since_version > prune_below {
+            break;
+        }
+        if !mode.keeps_any(index.node_key.version(), index.stale_since_version) {
+            tx.delete(
+                COL_TRIE_NODE,
+                &index
+                    .node_key
+                    .encode()
+                    .expect("jellyfish node key encoding"),
+            );
+        }
+        tx.delete(COL_TRIE_STALED, &key);
+    }
+
+    // heights below it were already processed
+    let mut height = match db.get(COL_EXTRA, PRUNED_HEIGHT_KEY)? {
+        Some(value) => BlockHeight::decode(&mut value.as_slice()).expect("storage corrupted"),
+        None => BlockHeight::genesis(),
+    };
+    loop {
+        let encoded_height = height.encode();
+        let version = match db.get(COL_STAKING_VERSIONS, &encoded_height)? {
+            Some(value) => Version::decode(&mut value.as_slice()).expect("storage corrupted"),
+            None => break,
+        };
+        if version >= prune_below {
+            break;
+        }
+        if !mode.keeps_version(version) {
+            tx.delete(COL_STAKING_VERSIONS, &encoded_height);
+            tx.delete(COL_APP_STATES, &encoded_height);
+        }
+        height = height.saturating_add(1);
+    }
+    tx.put(COL_EXTRA, PRUNED_HEIGHT_KEY, &height.encode());
+
+    if let PruningMode::KeepRecent(_) = mode {
+        for (key, _) in db.iter(COL_SPENT_TRIE_STALED) {
+            let index = decode_stale_node_index(&key).expect("storage corrupted");
+            if index.stale_since_version > height.value() {
+                break;
+            }
+            tx.delete(
+                COL_SPENT_TRIE_NODE,
+                &index
+                    .node_key
+                    .encode()
+                    .expect("jellyfish node key encoding"),
+            );
+            tx.delete(COL_SPENT_TRIE_STALED, &key);
+        }
+    }
+
+    db.write(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use kvdb_memorydb::create as create_memorydb;
+
+    use super::*;
+    use crate::buffer::{flush_kvdb, BufferStore, Get, KVBuffer, SimpleStore};
+    use crate::jellyfish::{
+        get_spent_output_with_proof, put_spent_outputs, put_stakings, restore_spent_outputs,
+        StakingGetter,
+    };
+    use crate::{get_historical_staking_version, get_pruned_height, NUM_COLUMNS};
+    use chain_core::init::coin::Coin;
+    use chain_core::state::account::{StakedState, StakedStateAddress};
+    use chain_core::tx::data::input::TxoPointer;
+
+    #[test]
+    fn check_parse_pruning_mode() {
+        for mode in [
+            PruningMode::Archive,
+            PruningMode::KeepRecent(100),
+            PruningMode::KeepEvery(1000),
+        ]
+        .iter()
+        {
+            assert_eq!(&mode.to_string().parse::<PruningMode>().unwrap(), mode);
+        }
+        assert!("keep-recent:0".parse::<PruningMode>().is_err());
+        assert!("keep-every".parse::<PruningMode>().is_err());
+        assert!("keep-all:1".parse::<PruningMode>().is_err());
+    }
+
+    /// the output spent at the height
+    fn spent_at(version: u64) -> TxoPointer {
+        TxoPointer::new([version as u8; 32], 0)
+    }
+
+    /// commits a new staking version of the same account (and a spent output) at each height
+    fn prepare_versions(versions: u64) -> (kvdb_memorydb::InMemory, StakedStateAddress) {
+        let db = create_memorydb(NUM_COLUMNS);
+        let address = StakedStateAddress::BasicRedeem([0x01; 20].into());
+        let other = StakedState::default(StakedStateAddress::BasicRedeem([0x02; 20].into()));
+        for version in 0..versions {
+            let staking = StakedState {
+                bonded: Coin::new(version + 1).unwrap(),
+                ..StakedState::default(address)
+            };
+            let mut buffer = KVBuffer::new();
+            let mut store = BufferStore::new(&db, &mut buffer);
+            put_stakings(&mut store, version, vec![&staking, &other].into_iter()).unwrap();
+            if version == 0 {
+                restore_spent_outputs(&mut store, BlockHeight::genesis(), std::iter::empty())
+                    .unwrap();
+            } else {
+                let height = BlockHeight::new(version);
+                put_spent_outputs(&mut store, height, std::iter::once(spent_at(version))).unwrap();
+            }
+            store.set(
+                (COL_STAKING_VERSIONS, BlockHeight::new(version).encode()),
+                version.encode(),
+            );
+            store.set(
+                (COL_APP_STATES, BlockHeight::new(version).encode()),
+                vec![1],
+            );
+            flush_kvdb(&db, buffer).unwrap();
+        }
+        (db, address)
+    }
+
+    fn bonded_at(db: &kvdb_memorydb::InMemory, address: &StakedStateAddress, version: u64) -> Coin {
+        StakingGetter::new(db, version)
+            .get(address)
+            .expect("staking exists")
+            .bonded
+    }
+
+    #[test]
+    fn check_keep_recent() {
+        let (db, address) = prepare_versions(10);
+        prune_staking_versions(&db, PruningMode::KeepRecent(3), 9).unwrap();
+        for version in 7..10 {
+            assert_eq!(
+                bonded_at(&db, &address, version),
+                Coin::new(version + 1).unwrap()
+            );
+            assert!(get_historical_staking_version(&db, BlockHeight::new(version)).is_some());
+        }
+        for version in 0..7 {
+            assert!(get_historical_staking_version(&db, BlockHeight::new(version)).is_none());
+            assert!(
+                KeyValueDB::get(&db, COL_APP_STATES, &BlockHeight::new(version).encode())
+                    .unwrap()
+                    .is_none()
+            );
+        }
+        assert_eq!(get_pruned_height(&db), Some(BlockHeight::new(7)));
+        assert!(db
+            .iter(COL_TRIE_STALED)
+            .all(|(key, _)| { decode_stale_node_index(&key).unwrap().stale_since_version > 7 }));
+
+        // the spent outputs of the kept heights can still be proven
+        for version in 7..10 {
+            let height = BlockHeight::new(version);
+            let spent = |v| {
+                get_spent_output_with_proof(&db, height, &spent_at(v))
+                    .unwrap()
+                    .0
+            };
+            assert!(spent(1));
+            assert!(spent(version));
+            assert!(!spent(version + 1));
+        }
+        assert!(db
+            .iter(COL_SPENT_TRIE_STALED)
+            .all(|(key, _)| { decode_stale_node_index(&key).unwrap().stale_since_version > 7 }));
+    }
+
+    #[test]
+    fn check_keep_every() {
+        let (db, address) = prepare_versions(10);
+        let nodes_before = db.iter(COL_TRIE_NODE).count();
+        prune_staking_versions(&db, PruningMode::KeepEvery(4), 9).unwrap();
+        assert!(db.iter(COL_TRIE_NODE).count() < nodes_before);
+        for version in [0, 4, 8, 9].iter() {
+            assert_eq!(
+                bonded_at(&db, &address, *version),
+                Coin::new(version + 1).unwrap()
+            );
+            assert!(get_historical_staking_version(&db, BlockHeight::new(*version)).is_some());
+        }
+        for version in [1, 2, 3, 5, 6, 7].iter() {
+            assert!(get_historical_staking_version(&db, BlockHeight::new(*version)).is_none());
+        }
+        assert_eq!(db.iter(COL_TRIE_STALED).count(), 0);
+    }
+
+    #[test]
+    fn check_archive() {
+        let (db, _) = prepare_versions(5);
+        let nodes_before = db.iter(COL_TRIE_NODE).count();
+        prune_staking_versions(&db, PruningMode::Archive, 4).unwrap();
+        assert_eq!(db.iter(COL_TRIE_NODE).count(), nodes_before);
+        assert!(get_pruned_height(&db).is_none());
+    }
+}
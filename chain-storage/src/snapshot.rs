@@ -0,0 +1,414 @@
+//! # State snapshots
+//!
+//! A snapshot contains everything a node needs to continue from a committed block
+//! without replaying the chain from genesis:
+//! the staking states at the block's staking version, the encoded node state,
+//! the UTXO bitsets (and creation / spending records) from `COL_TX_META`,
+//! the genesis app hash, chain id and consensus parameters, and the block's historical entries.
+//! The merkle trie of spent outputs is rebuilt from the UTXO bitsets at the block's height.
+//!
+//! Sealed transaction payloads (`COL_ENCLAVE_TX`) are not included,
+//! as they are sealed to the enclave on a particular machine.
+//!
+//! The encoded content is split into chunks; the manifest records the hash of each chunk,
+//! so that chunks from untrusted peers can be checked one by one.
+use std::fmt;
+use std::io;
+
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::common::H256;
+use chain_core::state::account::StakedState;
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::data::input::TxoPointer;
+
+use crate::buffer::{flush_storage, BufferStore, KVBuffer, MemStore, SimpleStore};
+use crate::jellyfish::{
+    compute_staking_root, iter_stakings, restore_spent_outputs, restore_stakings, Version,
+};
+use crate::{
+    spent_outputs_in_bitset, LookupItem, ReadOnlyStorage, Storage, StoredChainState, CHAIN_ID_KEY,
+    COL_APP_HASHS, COL_APP_STATES, COL_EXTRA, COL_NODE_INFO, COL_STAKING_VERSIONS, COL_TX_META,
+    CONSENSUS_PARAMS_KEY, GENESIS_APP_HASH_KEY, LAST_STATE_KEY, PRUNED_HEIGHT_KEY,
+};
+
+/// The current version of the snapshot content encoding
+/// (format 3: the top level state includes the spent outputs root)
+pub const SNAPSHOT_FORMAT: u32 = 3;
+/// The default maximal size of a snapshot chunk (Tendermint accepts up to 16MB)
+pub const SNAPSHOT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Errors when restoring a snapshot
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// the snapshot was created with an unknown format
+    UnsupportedFormat(u32),
+    /// the chunk's index is not in the manifest
+    UnknownChunk(u32),
+    /// the chunk doesn't match the hash in the manifest
+    ChunkHashMismatch(u32),
+    /// the chunks were verified, but the content can't be decoded
+    Corrupted(parity_scale_codec::Error),
+    /// the staking states are empty or don't match the expected root
+    InvalidStakings,
+    /// the spent outputs in the UTXO bitsets can't be put into the merkle trie
+    InvalidSpentOutputs,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::UnsupportedFormat(format) => {
+                write!(f, "unsupported snapshot format: {}", format)
+            }
+            SnapshotError::UnknownChunk(index) => write!(f, "unknown snapshot chunk: {}", index),
+            SnapshotError::ChunkHashMismatch(index) => {
+                write!(f, "snapshot chunk {} doesn't match its hash", index)
+            }
+            SnapshotError::Corrupted(e) => write!(f, "snapshot content corrupted: {}", e),
+            SnapshotError::InvalidStakings => {
+                write!(f, "snapshot staking states are invalid")
+            }
+            SnapshotError::InvalidSpentOutputs => {
+                write!(f, "snapshot spent outputs are invalid")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Describes a snapshot and the hashes of its chunks
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct SnapshotManifest {
+    /// the height of the committed block
+    pub height: BlockHeight,
+    /// the content encoding version
+    pub format: u32,
+    /// the app hash after committing the block
+    pub app_hash: H256,
+    /// blake3 hashes of the chunks
+    pub chunk_hashes: Vec<H256>,
+}
+
+impl SnapshotManifest {
+    /// the number of the chunks
+    pub fn chunks(&self) -> u32 {
+        self.chunk_hashes.len() as u32
+    }
+
+    /// blake3 hash of the encoded manifest
+    pub fn hash(&self) -> H256 {
+        blake3::hash(&self.encode()).into()
+    }
+
+    /// checks the chunk against its hash in the manifest
+    pub fn verify_chunk(&self, index: u32, chunk: &[u8]) -> Result<(), SnapshotError> {
+        let expected = self
+            .chunk_hashes
+            .get(index as usize)
+            .ok_or(SnapshotError::UnknownChunk(index))?;
+        let hash: H256 = blake3::hash(chunk).into();
+        if &hash == expected {
+            Ok(())
+        } else {
+            Err(SnapshotError::ChunkHashMismatch(index))
+        }
+    }
+}
+
+/// The content of a snapshot
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct SnapshotContent {
+    /// the height of the committed block
+    pub height: BlockHeight,
+    /// the staking version of the block
+    pub staking_version: Version,
+    /// the staking states at the staking version
+    pub stakings: Vec<StakedState>,
+    /// the other key-value entries (column, key, value) -- including the encoded node state
+    pub entries: Vec<(u32, Vec<u8>, Vec<u8>)>,
+}
+
+impl SnapshotContent {
+    /// the encoded node state (as stored by `store_chain_state`)
+    pub fn last_state(&self) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|(col, key, _)| *col == COL_NODE_INFO && key.as_slice() == LAST_STATE_KEY)
+            .map(|(_, _, value)| value.as_slice())
+    }
+
+    /// the encoded merkle tree of the block's valid transactions (needed to recompute the app hash)
+    pub fn txs_merkle_tree(&self, app_hash: &H256) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|(col, key, _)| {
+                *col == LookupItem::TxsMerkle as u32 && key.as_slice() == &app_hash[..]
+            })
+            .map(|(_, _, value)| value.as_slice())
+    }
+
+    /// the staking root computed from the staking states
+    pub fn staking_root(&self) -> Result<H256, SnapshotError> {
+        if self.stakings.is_empty() {
+            return Err(SnapshotError::InvalidStakings);
+        }
+        Ok(compute_staking_root(&self.stakings))
+    }
+
+    /// the outputs marked as spent in the UTXO bitsets
+    pub fn spent_outputs(&self) -> impl Iterator<Item = TxoPointer> + '_ {
+        self.entries
+            .iter()
+            .filter(|(col, _, _)| *col == COL_TX_META)
+            .flat_map(|(_, key, value)| spent_outputs_in_bitset(key, value))
+    }
+
+    /// the root of the merkle trie of spent outputs computed from the UTXO bitsets
+    pub fn spent_outputs_root(&self) -> Result<H256, SnapshotError> {
+        restore_spent_outputs(&mut MemStore::new(), self.height, self.spent_outputs())
+            .map_err(|_| SnapshotError::InvalidSpentOutputs)
+    }
+
+    /// encodes the content and splits it into chunks (of at most `chunk_size` bytes)
+    pub fn into_chunks(
+        self,
+        app_hash: H256,
+        chunk_size: usize,
+    ) -> (SnapshotManifest, Vec<Vec<u8>>) {
+        let encoded = self.encode();
+        let chunks = encoded
+            .chunks(chunk_size)
+            .map(<[u8]>::to_vec)
+            .collect::<Vec<_>>();
+        let manifest = SnapshotManifest {
+            height: self.height,
+            format: SNAPSHOT_FORMAT,
+            app_hash,
+            chunk_hashes: chunks
+                .iter()
+                .map(|chunk| blake3::hash(chunk).into())
+                .collect(),
+        };
+        (manifest, chunks)
+    }
+
+    /// verifies the chunks against the manifest and decodes the content
+    pub fn from_chunks(
+        manifest: &SnapshotManifest,
+        chunks: &[Vec<u8>],
+    ) -> Result<Self, SnapshotError> {
+        if manifest.format != SNAPSHOT_FORMAT {
+            return Err(SnapshotError::UnsupportedFormat(manifest.format));
+        }
+        if chunks.len() != manifest.chunk_hashes.len() {
+            return Err(SnapshotError::UnknownChunk(chunks.len() as u32));
+        }
+        for (index, chunk) in chunks.iter().enumerate() {
+            manifest.verify_chunk(index as u32, chunk)?;
+        }
+        let encoded = chunks.concat();
+        let mut input = encoded.as_slice();
+        let content = SnapshotContent::decode(&mut input).map_err(SnapshotError::Corrupted)?;
+        if !input.is_empty() {
+            return Err(SnapshotError::Corrupted(
+                "trailing bytes after snapshot content".into(),
+            ));
+        }
+        Ok(content)
+    }
+}
+
+impl ReadOnlyStorage {
+    /// Collects the snapshot content from the committed storage
+    /// (`state` is the last committed node state of the block at `height`).
+    /// The UTXO bitsets aren't versioned, so nothing may be flushed until it returns.
+    pub fn create_snapshot<T: StoredChainState>(
+        &self,
+        state: &T,
+        height: BlockHeight,
+    ) -> io::Result<SnapshotContent> {
+        let staking_version = state.get_staking_version();
+        let encoded_height = height.encode();
+        let mut entries = vec![(COL_NODE_INFO, LAST_STATE_KEY.to_vec(), state.get_encoded())];
+        let single_keys = [
+            (COL_NODE_INFO, GENESIS_APP_HASH_KEY.to_vec()),
+            (COL_EXTRA, CHAIN_ID_KEY.to_vec()),
+            (COL_EXTRA, CONSENSUS_PARAMS_KEY.to_vec()),
+            (
+                LookupItem::TxsMerkle as u32,
+                state.get_last_app_hash().to_vec(),
+            ),
+            (COL_APP_HASHS, encoded_height.clone()),
+            (COL_APP_STATES, encoded_height.clone()),
+            (COL_STAKING_VERSIONS, encoded_height),
+        ];
+        for (col, key) in single_keys.iter() {
+            if let Some(value) = self.db.get(*col, key)? {
+                entries.push((*col, key.clone(), value));
+            }
+        }
+        entries.extend(
+            self.db
+                .iter(COL_TX_META)
+                .map(|(key, value)| (COL_TX_META, key.into_vec(), value.into_vec())),
+        );
+        Ok(SnapshotContent {
+            height,
+            staking_version,
+            stakings: iter_stakings(self, staking_version).collect(),
+            entries,
+        })
+    }
+}
+
+impl Storage {
+    /// Writes the (verified) snapshot content into an empty storage.
+    /// The history before the snapshot's height is not available, so it's marked as pruned.
+    pub fn restore_snapshot(&mut self, content: &SnapshotContent) -> Result<(), SnapshotError> {
+        let mut kv_buffer = KVBuffer::new();
+        let mut store = BufferStore::new(&*self, &mut kv_buffer);
+        restore_stakings(&mut store, content.staking_version, content.stakings.iter())
+            .map_err(|_| SnapshotError::InvalidStakings)?;
+        restore_spent_outputs(&mut store, content.height, content.spent_outputs())
+            .map_err(|_| SnapshotError::InvalidSpentOutputs)?;
+        for (col, key, value) in content.entries.iter() {
+            store.set((*col, key.clone()), value.clone());
+        }
+        store.set(
+            (COL_EXTRA, PRUNED_HEIGHT_KEY.to_vec()),
+            content.height.encode(),
+        );
+        flush_storage(self, kv_buffer).expect("kv storage io error");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use kvdb_memorydb::create as create_memorydb;
+
+    use super::*;
+    use crate::buffer::Get;
+    use crate::jellyfish::{get_spent_output_with_proof, StakingGetter};
+    use crate::{get_pruned_height, lookup_input, NUM_COLUMNS};
+    use chain_core::init::coin::Coin;
+    use chain_core::state::account::StakedStateAddress;
+    use chain_core::tx::data::input::TxoPointer;
+    use chain_core::TxCreation;
+
+    struct DummyState {
+        app_hash: H256,
+        version: Version,
+    }
+
+    impl StoredChainState for DummyState {
+        fn get_encoded(&self) -> Vec<u8> {
+            (self.app_hash, self.version).encode()
+        }
+
+        fn get_encoded_top_level(&self) -> Vec<u8> {
+            self.app_hash.encode()
+        }
+
+        fn get_last_app_hash(&self) -> H256 {
+            self.app_hash
+        }
+
+        fn get_staking_version(&self) -> Version {
+            self.version
+        }
+    }
+
+    fn staking(byte: u8, bonded: u64) -> StakedState {
+        StakedState {
+            bonded: Coin::new(bonded).unwrap(),
+            ..StakedState::default(StakedStateAddress::BasicRedeem([byte; 20].into()))
+        }
+    }
+
+    #[test]
+    fn check_snapshot_roundtrip() {
+        let mut storage = Storage::new_db(Arc::new(create_memorydb(NUM_COLUMNS)));
+        storage.put_stakings(0, &[staking(1, 1), staking(2, 2)]);
+        let root = storage.put_stakings(1, &[staking(1, 10)]);
+        let txid = [3u8; 32];
+        let mut kv_buffer = KVBuffer::new();
+        let mut store = BufferStore::new(&storage, &mut kv_buffer);
+        crate::create_utxo(&mut store, 2, &txid, &TxCreation::default());
+        crate::spend_utxos(&mut store, &[TxoPointer::new(txid, 0)]);
+        crate::write_genesis_chain_id(&mut store, &[4u8; 32], "test-chain-y3m1e6-AB");
+        let state = DummyState {
+            app_hash: [5u8; 32],
+            version: 1,
+        };
+        crate::store_chain_state(&mut store, &state, BlockHeight::new(7), true);
+        flush_storage(&mut storage, kv_buffer).unwrap();
+
+        let content = storage
+            .get_read_only()
+            .create_snapshot(&state, BlockHeight::new(7))
+            .unwrap();
+        assert_eq!(content.staking_root().unwrap(), root);
+        assert_eq!(
+            content.spent_outputs().collect::<Vec<_>>(),
+            vec![TxoPointer::new(txid, 0)]
+        );
+        let spent_outputs_root = content.spent_outputs_root().unwrap();
+        assert_ne!(spent_outputs_root, H256::default());
+        assert_eq!(content.last_state(), Some(&state.get_encoded()[..]));
+        let (manifest, chunks) = content.clone().into_chunks(state.app_hash, 64);
+        assert!(manifest.chunks() > 1);
+        assert_eq!(manifest.app_hash, state.app_hash);
+
+        let mut corrupted = chunks.clone();
+        corrupted[0][0] ^= 1;
+        assert!(matches!(
+            SnapshotContent::from_chunks(&manifest, &corrupted),
+            Err(SnapshotError::ChunkHashMismatch(0))
+        ));
+        let restored_content = SnapshotContent::from_chunks(&manifest, &chunks).unwrap();
+        assert_eq!(restored_content, content);
+
+        let mut restored = Storage::new_db(Arc::new(create_memorydb(NUM_COLUMNS)));
+        restored.restore_snapshot(&restored_content).unwrap();
+        assert_eq!(restored.get_last_app_state(), Some(state.get_encoded()));
+        assert_eq!(restored.get_genesis_app_hash(), [4u8; 32]);
+        assert_eq!(
+            lookup_input(&restored, &TxoPointer::new(txid, 0)),
+            Some(true)
+        );
+        assert_eq!(
+            lookup_input(&restored, &TxoPointer::new(txid, 1)),
+            Some(false)
+        );
+        assert_eq!(
+            restored.get_historical_staking_version(BlockHeight::new(7)),
+            Some(1)
+        );
+        assert_eq!(get_pruned_height(&restored), Some(BlockHeight::new(7)));
+        // the merkle trie of spent outputs is rebuilt at the snapshot's height
+        for (index, expected) in [(0, true), (1, false)].iter() {
+            let txin = TxoPointer::new(txid, *index);
+            let (spent, proof) =
+                get_spent_output_with_proof(&restored, BlockHeight::new(7), &txin).unwrap();
+            assert_eq!(spent, *expected);
+            proof
+                .verify_spent_output(spent_outputs_root, &txin, spent)
+                .unwrap();
+        }
+        let address = StakedStateAddress::BasicRedeem([1; 20].into());
+        assert_eq!(
+            StakingGetter::new(&restored, 1).get(&address),
+            Some(staking(1, 10))
+        );
+
+        // the next version is built on top of the restored one
+        let next_root = restored.put_stakings(2, &[staking(2, 20)]);
+        let next_expected = storage.put_stakings(2, &[staking(2, 20)]);
+        assert_eq!(next_root, next_expected);
+    }
+}
@@ -11,8 +11,8 @@ use crate::secrets::*;
 use crate::tree::*;
 use crate::tree_math::{LeafSize, NodeSize};
 use crate::utils::{
-    encode_vec_option_u32, encode_vec_u8_u16, encode_vec_u8_u8, read_vec_option_u32,
-    read_vec_u8_u16, read_vec_u8_u8,
+    encode_vec_option_u32, encode_vec_u32, encode_vec_u8_u16, encode_vec_u8_u8,
+    read_vec_option_u32, read_vec_u32, read_vec_u8_u16, read_vec_u8_u8,
 };
 use ra_client::AttestedCertVerifier;
 use rustls::internal::msgs::codec::{self, Codec, Reader};
@@ -137,16 +137,7 @@ impl GroupAux {
     }
 
     fn get_init_confirmed_transcript_hash(&self, sender: Sender, commit: &Commit) -> Vec<u8> {
-        let interim_transcript_hash = b"".to_vec(); // TODO
-        let content_to_commit = MLSPlaintextCommitContent::new(
-            self.context.group_id.clone(),
-            self.context.epoch,
-            sender,
-            commit.clone(),
-        )
-        .get_encoding();
-        let to_hash = [interim_transcript_hash, content_to_commit].concat();
-        self.tree.cs.hash(&to_hash)
+        compute_init_confirmed_transcript_hash(self.tree.cs, &self.context, sender, commit)
     }
 
     fn get_interim_transcript_hash(
@@ -629,6 +620,157 @@ impl GroupAux {
     }
 }
 
+fn compute_init_confirmed_transcript_hash(
+    cs: CipherSuite,
+    context: &GroupContext,
+    sender: Sender,
+    commit: &Commit,
+) -> Vec<u8> {
+    let interim_transcript_hash = b"".to_vec(); // TODO
+    let content_to_commit = MLSPlaintextCommitContent::new(
+        context.group_id.clone(),
+        context.epoch,
+        sender,
+        commit.clone(),
+    )
+    .get_encoding();
+    let to_hash = [interim_transcript_hash, content_to_commit].concat();
+    cs.hash(&to_hash)
+}
+
+/// public view of the group -- group context + tree without any secrets
+///
+/// It can be maintained by non-members (e.g. chain-abci) to validate
+/// the handshake messages: the signatures, epochs and the membership
+/// (the path secrets and confirmation can only be checked by group members).
+#[derive(Clone)]
+pub struct GroupPublicView {
+    pub context: GroupContext,
+    pub tree: Tree,
+}
+
+impl GroupPublicView {
+    /// the group state before the genesis commit
+    pub fn init(creator_kp: KeyPackage) -> Self {
+        let (context, tree) = GroupContext::init(creator_kp);
+        GroupPublicView { context, tree }
+    }
+
+    /// keypackage of the member at the leaf position (if any)
+    pub fn get_member(&self, leaf_index: LeafSize) -> Option<&KeyPackage> {
+        if leaf_index < self.tree.leaf_len() {
+            self.tree.get_package(leaf_index)
+        } else {
+            None
+        }
+    }
+
+    /// leaf position of the member with the keypackage (if any)
+    pub fn find_member(&self, kp: &KeyPackage) -> Option<LeafSize> {
+        (0..self.tree.leaf_len().0)
+            .map(LeafSize)
+            .find(|leaf_index| self.tree.get_package(*leaf_index) == Some(kp))
+    }
+
+    /// Verify the message was sent by a group member in the current epoch
+    pub fn verify_msg_signature(
+        &self,
+        msg: &MLSPlaintext,
+        ra_verifier: &impl AttestedCertVerifier,
+        now: Timespec,
+    ) -> Result<(), ProcessCommitError> {
+        if msg.content.group_id != self.context.group_id || msg.content.epoch != self.context.epoch
+        {
+            return Err(ProcessCommitError::GroupEpochError);
+        }
+        let kp = self
+            .get_member(LeafSize(msg.content.sender.sender))
+            .ok_or(ProcessCommitError::SenderNotFound)?;
+        let pk = IdentityPublicKey::new_unsafe(kp.verify(ra_verifier, now)?.public_key);
+        msg.verify_signature(&self.context, &pk)
+            .map_err(ProcessCommitError::MsgSignatureVerifyFailed)
+    }
+
+    /// Verify the commit and the proposals it refers to,
+    /// and apply them to the group state (moves to the next epoch)
+    pub fn process_commit(
+        &mut self,
+        commit: &MLSPlaintext,
+        proposals: &[MLSPlaintext],
+        ra_verifier: &impl AttestedCertVerifier,
+        now: Timespec,
+    ) -> Result<CommitContent, ProcessCommitError> {
+        self.verify_msg_signature(commit, ra_verifier, now)?;
+        for proposal in proposals.iter() {
+            self.verify_msg_signature(proposal, ra_verifier, now)?;
+        }
+        let commit_content = CommitContent::new(self.tree.cs, commit, proposals)
+            .map_err(|_| ProcessCommitError::CommitError)?;
+
+        for add in commit_content.additions.iter() {
+            if self.find_member(&add.key_package).is_some() {
+                return Err(ProcessCommitError::DuplicateMember);
+            }
+            add.key_package.verify(ra_verifier, now)?;
+        }
+        for (_, update) in commit_content.updates.iter() {
+            update.key_package.verify(ra_verifier, now)?;
+        }
+        for remove in commit_content.removes.iter() {
+            self.get_member(LeafSize(remove.removed))
+                .ok_or(ProcessCommitError::RemovedMemberNotFound)?;
+        }
+        if let Some(path) = &commit_content.commit.path {
+            path.leaf_key_package.verify(ra_verifier, now)?;
+        }
+
+        let mut updated_tree = self.tree.clone();
+        updated_tree.apply_commit_public(&commit_content)?;
+        let confirmed_transcript_hash = compute_init_confirmed_transcript_hash(
+            self.tree.cs,
+            &self.context,
+            commit.content.sender.clone(),
+            &commit_content.commit,
+        );
+        self.context = GroupContext {
+            epoch: self.context.epoch + 1,
+            tree_hash: updated_tree.compute_tree_hash(),
+            confirmed_transcript_hash,
+            ..self.context.clone()
+        };
+        self.tree = updated_tree;
+        Ok(commit_content)
+    }
+}
+
+impl Codec for GroupPublicView {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        self.context.encode(bytes);
+        (self.tree.cs as u16).encode(bytes);
+        encode_vec_u32(bytes, &self.tree.nodes);
+    }
+
+    fn read(r: &mut Reader) -> Option<Self> {
+        let context = GroupContext::read(r)?;
+        let cs = match u16::read(r)? {
+            x if x == (CipherSuite::MLS10_128_DHKEMP256_AES128GCM_SHA256_P256 as u16) => {
+                CipherSuite::MLS10_128_DHKEMP256_AES128GCM_SHA256_P256
+            }
+            _ => return None,
+        };
+        let nodes: Vec<Node> = read_vec_u32(r)?;
+        NodeSize(u32::try_from(nodes.len()).ok()?).leafs_len()?;
+        Some(GroupPublicView {
+            context,
+            tree: Tree {
+                nodes,
+                cs,
+                my_pos: LeafSize(0),
+            },
+        })
+    }
+}
+
 const TDBE_GROUP_ID: &[u8] = b"Crypto.com Chain Council Node Transaction Data Bootstrap Enclave";
 
 /// spec: draft-ietf-mls-protocol.md#group-state
@@ -797,6 +939,10 @@ pub enum ProcessCommitError {
     MsgSignatureVerifyFailed(ring::error::Unspecified),
     #[error("commit path is not populated")]
     CommitPathNotPopulated,
+    #[error("added keypackage is already in the group")]
+    DuplicateMember,
+    #[error("removed member not found")]
+    RemovedMemberNotFound,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -1079,4 +1225,55 @@ mod test {
             None
         );
     }
+
+    #[test]
+    fn test_public_view_process_commit() {
+        let (member1, member1_secret) = get_fake_keypackage();
+        let (member2, _) = get_fake_keypackage();
+        let ra_verifier = MockVerifier {};
+
+        let mut public_view = GroupPublicView::init(member1.clone());
+        let (mut member1_group, proposals, commit, _welcome) =
+            GroupAux::init_group(member1, member1_secret, &[member2.clone()], &ra_verifier, 0)
+                .expect("group init");
+        public_view
+            .process_commit(&commit, &proposals, &ra_verifier, 0)
+            .expect("public commit ok");
+        member1_group
+            .process_commit(commit.clone(), &proposals, &ra_verifier, 0)
+            .expect("commit ok");
+        assert_eq!(&public_view.context, &member1_group.context);
+        assert_eq!(public_view.find_member(&member2), Some(LeafSize(1)));
+
+        // replayed commit is rejected
+        assert!(matches!(
+            public_view.process_commit(&commit, &proposals, &ra_verifier, 0),
+            Err(ProcessCommitError::GroupEpochError)
+        ));
+
+        // the state survives serialization
+        let mut public_view =
+            GroupPublicView::read_bytes(&public_view.get_encoding()).expect("decode public view");
+        assert_eq!(&public_view.context, &member1_group.context);
+
+        // remove member2
+        let proposals = vec![member1_group.get_signed_remove(LeafSize(1))];
+        let (commit, _welcome) = member1_group.commit_proposals(&proposals);
+        public_view
+            .process_commit(&commit, &proposals, &ra_verifier, 0)
+            .expect("public commit ok");
+        member1_group
+            .process_commit(commit, &proposals, &ra_verifier, 0)
+            .expect("commit ok");
+        assert_eq!(&public_view.context, &member1_group.context);
+        assert_eq!(public_view.find_member(&member2), None);
+
+        // unknown sender
+        let mut proposal = member1_group.get_signed_remove(LeafSize(0));
+        proposal.content.sender.sender = 5;
+        assert!(matches!(
+            public_view.verify_msg_signature(&proposal, &ra_verifier, 0),
+            Err(ProcessCommitError::SenderNotFound)
+        ));
+    }
 }
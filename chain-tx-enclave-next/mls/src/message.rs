@@ -375,6 +375,29 @@ pub struct Welcome {
     pub encrypted_group_info: Vec<u8>,
 }
 
+impl Codec for Welcome {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        self.version.encode(bytes);
+        self.cipher_suite.encode(bytes);
+        encode_vec_u32(bytes, &self.secrets);
+        encode_vec_u32(bytes, &self.encrypted_group_info);
+    }
+
+    fn read(r: &mut Reader) -> Option<Self> {
+        let version = ProtocolVersion::read(r)?;
+        let cipher_suite = CipherSuite::read(r)?;
+        let secrets = read_vec_u32(r)?;
+        let encrypted_group_info = read_vec_u32(r)?;
+
+        Some(Welcome {
+            version,
+            cipher_suite,
+            secrets,
+            encrypted_group_info,
+        })
+    }
+}
+
 /// spec: draft-ietf-mls-protocol.md#Welcoming-New-Members
 pub struct PathSecret {
     /// 1..255
@@ -439,6 +462,23 @@ pub struct EncryptedGroupSecrets {
     pub key_package_hash: Vec<u8>,
 }
 
+impl Codec for EncryptedGroupSecrets {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        encode_vec_u8_u8(bytes, &self.key_package_hash);
+        self.encrypted_group_secrets.encode(bytes);
+    }
+
+    fn read(r: &mut Reader) -> Option<Self> {
+        let key_package_hash = read_vec_u8_u8(r)?;
+        let encrypted_group_secrets = HPKECiphertext::read(r)?;
+
+        Some(EncryptedGroupSecrets {
+            encrypted_group_secrets,
+            key_package_hash,
+        })
+    }
+}
+
 /// spec: draft-ietf-mls-protocol.md#Direct-Paths
 #[derive(Debug, Clone)]
 pub struct HPKECiphertext {
@@ -507,6 +507,40 @@ impl Tree {
         Ok(commit_secret)
     }
 
+    /// apply proposals and the public part of the commit path
+    ///
+    /// used by the parties that don't hold any private keys in the tree
+    /// (e.g. chain nodes tracking the group state),
+    /// so the path secrets are not decrypted
+    pub fn apply_commit_public(
+        &mut self,
+        commit: &CommitContent,
+    ) -> Result<(), ProcessCommitError> {
+        self.update(&commit.additions, &commit.updates, &commit.removes);
+        if let Some(path) = &commit.commit.path {
+            let from = NodeSize::from(commit.sender);
+            let direct_path = from.direct_path(self.leaf_len());
+            if direct_path.len() != path.nodes.len() {
+                return Err(ProcessCommitError::CommitError);
+            }
+            if !direct_path.is_empty() {
+                for (node_index, path_node) in direct_path.into_iter().zip(path.nodes.iter()) {
+                    self.nodes[node_index.node_index()].merge_public(path_node.public_key.clone());
+                }
+                let leaf_parent_hash = self.set_parent_hash_path(commit.sender);
+                let ext = path
+                    .leaf_key_package
+                    .payload
+                    .find_extension::<ext::ParentHashExt>()?;
+                if !bool::from(ext.0.ct_eq(&leaf_parent_hash)) {
+                    return Err(ProcessCommitError::LeafParentHashDontMatch);
+                }
+            }
+            self.set_package(commit.sender, path.leaf_key_package.clone());
+        }
+        Ok(())
+    }
+
     fn node_hash(&self, index: NodeSize) -> Vec<u8> {
         node_hash(&self.nodes, self.cs, index, self.leaf_len())
     }
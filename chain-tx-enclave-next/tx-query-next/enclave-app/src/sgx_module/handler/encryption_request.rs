@@ -95,7 +95,7 @@ pub fn handle_encryption_request(
 }
 
 fn construct_request(req: &EncryptionRequest, req_len: usize) -> Option<QueryEncryptRequest> {
-    let (txid, sealed, tx_inputs, tx_size, op_sig) = match req {
+    let (txid, sealed, tx_inputs, no_of_outputs, tx_size, op_sig) = match req {
         // TODO: are the size estimates ok?
         EncryptionRequest::TransferTx(tx, _) => {
             let txid = tx.id();
@@ -105,6 +105,7 @@ fn construct_request(req: &EncryptionRequest, req_len: usize) -> Option<QueryEnc
                 txid,
                 sealed,
                 tx_inputs,
+                tx.outputs.len() as TxoSize,
                 req_len + 34 * tx.inputs.len() + 74,
                 None,
             )
@@ -117,6 +118,7 @@ fn construct_request(req: &EncryptionRequest, req_len: usize) -> Option<QueryEnc
                 txid,
                 sealed,
                 tx_inputs,
+                0,
                 req_len + 34 * tx.inputs.len() + 74,
                 None,
             )
@@ -124,13 +126,21 @@ fn construct_request(req: &EncryptionRequest, req_len: usize) -> Option<QueryEnc
         EncryptionRequest::WithdrawStake(tx, witness) => {
             let txid = tx.id();
             let sealed = SealedData::seal(&req.encode(), txid).ok();
-            (txid, sealed, None, req_len + 73, Some(witness.clone()))
+            (
+                txid,
+                sealed,
+                None,
+                tx.outputs.len() as TxoSize,
+                req_len + 73,
+                Some(witness.clone()),
+            )
         }
     };
     sealed.map(|sealed_enc_request| QueryEncryptRequest {
         txid,
         sealed_enc_request,
         tx_inputs,
+        no_of_outputs,
         // TODO: checks, but this should fit, as all things are bounded more like u16::max
         tx_size: tx_size as u32,
         op_sig,
@@ -131,7 +131,7 @@ mod tests {
         TxEnclaveAux,
     };
     use chain_core::tx::{PlainTxAux, TxToObfuscate};
-    use chain_core::ChainInfo;
+    use chain_core::{ChainInfo, TxCreation};
     use chain_tx_validation::Error;
     use enclave_protocol::{IntraEnclaveRequest, IntraEnclaveResponseOk, VerifyTxRequest};
     use log::debug;
@@ -301,6 +301,8 @@ mod tests {
             block_time: 1,
             block_height: BlockHeight::genesis(),
             max_evidence_age: 0,
+            max_evidence_age_blocks: 0,
+            app_version: chain_core::APP_VERSION,
         };
 
         let request0 = IntraEnclaveRequest::ValidateTx {
@@ -308,6 +310,7 @@ mod tests {
                 tx: withdrawtx,
                 account: Some(account),
                 info,
+                inputs_created: vec![],
             }),
             tx_inputs: None,
         };
@@ -371,6 +374,7 @@ mod tests {
                 tx: transfertx,
                 account: None,
                 info,
+                inputs_created: vec![TxCreation::default()],
             }),
             tx_inputs: Some(vec![sealedtx.clone()]),
         };
@@ -415,6 +419,7 @@ mod tests {
                 tx: transfertx2,
                 account: None,
                 info,
+                inputs_created: vec![TxCreation::default()],
             }),
             tx_inputs: Some(vec![sealedtx]),
         };
@@ -7,11 +7,11 @@ use chain_core::tx::data::TxId;
 use chain_core::tx::TransactionId;
 use chain_core::tx::TxWithOutputs;
 use chain_core::tx::{PlainTxAux, TxObfuscated, TxToObfuscate};
-use chain_tx_validation::Error;
 use chain_tx_validation::{
     verify_bonded_deposit_core, verify_transfer, verify_unbonded_withdraw_core,
     witness::verify_tx_recover_address,
 };
+use chain_tx_validation::{Error, TxCreation};
 use enclave_macro::mock_key;
 use enclave_protocol::{EncryptionRequest, IntraEncryptRequest};
 use enclave_protocol::{IntraEnclaveResponse, IntraEnclaveResponseOk};
@@ -72,14 +72,26 @@ fn unseal_request(request: &IntraEncryptRequest) -> Option<EncryptionRequest> {
     }
 }
 
+/// unseals the transaction inputs and pairs them with the blocks they were created in
 #[inline]
-pub fn check_unseal<I>(txids: I, sealed_logs: Vec<Vec<u8>>) -> Option<Vec<TxWithOutputs>>
+pub fn check_unseal<I>(
+    txids: I,
+    sealed_logs: Vec<Vec<u8>>,
+    inputs_created: &[TxCreation],
+) -> Option<Vec<(TxWithOutputs, TxCreation)>>
 where
     I: IntoIterator<Item = TxId> + ExactSizeIterator,
 {
+    if sealed_logs.len() != inputs_created.len() {
+        return None;
+    }
     let mut return_result = Vec::with_capacity(sealed_logs.len());
 
-    for (txid, sealed_log) in txids.into_iter().zip(sealed_logs.into_iter()) {
+    for ((txid, sealed_log), created) in txids
+        .into_iter()
+        .zip(sealed_logs.into_iter())
+        .zip(inputs_created.iter())
+    {
         let sealed_data = SealedData::try_copy_from(&sealed_log)?;
 
         if sealed_data.aes_data.additional_txt != txid {
@@ -89,7 +101,7 @@ where
         let mut unsealed_data = sealed_data.unseal().ok()?;
         let otx = TxWithOutputs::decode(&mut unsealed_data.as_slice());
         if let Ok(tx) = otx {
-            return_result.push(tx.clone());
+            return_result.push((tx.clone(), *created));
         } else {
             return None;
         }
@@ -103,7 +115,11 @@ where
 pub(crate) fn handle_encrypt_request<I: Write>(request: Box<IntraEncryptRequest>, output: &mut I) {
     match (unseal_request(&request), request.tx_inputs) {
         (Some(EncryptionRequest::TransferTx(tx, witness)), Some(sealed_inputs)) => {
-            let unsealed_inputs = check_unseal(tx.inputs.iter().map(|x| x.id), sealed_inputs);
+            let unsealed_inputs = check_unseal(
+                tx.inputs.iter().map(|x| x.id),
+                sealed_inputs,
+                &request.inputs_created,
+            );
             if let Some(inputs) = unsealed_inputs {
                 let result = verify_transfer(&tx, &witness, &request.info, inputs);
                 let txid = tx.id();
@@ -120,7 +136,11 @@ pub(crate) fn handle_encrypt_request<I: Write>(request: Box<IntraEncryptRequest>
             }
         }
         (Some(EncryptionRequest::DepositStake(tx, witness)), Some(sealed_inputs)) => {
-            let unsealed_inputs = check_unseal(tx.inputs.iter().map(|x| x.id), sealed_inputs);
+            let unsealed_inputs = check_unseal(
+                tx.inputs.iter().map(|x| x.id),
+                sealed_inputs,
+                &request.inputs_created,
+            );
             if let Some(inputs) = unsealed_inputs {
                 let result = verify_bonded_deposit_core(&tx, &witness, &request.info, inputs);
                 let txid = tx.id();
@@ -90,7 +90,11 @@ pub(crate) fn handle_validate_tx<I: Write>(
                 },
             ) => {
                 let plaintx = decrypt(&payload);
-                let unsealed_inputs = check_unseal(inputs.iter().map(|x| x.id), sealed_inputs);
+                let unsealed_inputs = check_unseal(
+                    inputs.iter().map(|x| x.id),
+                    sealed_inputs,
+                    &request.inputs_created,
+                );
                 match (plaintx, unsealed_inputs) {
                     (Ok(PlainTxAux::TransferTx(tx, witness)), Some(inputs)) => {
                         if tx.id() != payload.txid || tx.outputs.len() as TxoSize != no_of_outputs {
@@ -114,7 +118,11 @@ pub(crate) fn handle_validate_tx<I: Write>(
             }
             (Some(sealed_inputs), TxEnclaveAux::DepositStakeTx { tx, payload }) => {
                 let plaintx = decrypt(&payload);
-                let inputs = check_unseal(tx.inputs.iter().map(|x| x.id), sealed_inputs);
+                let inputs = check_unseal(
+                    tx.inputs.iter().map(|x| x.id),
+                    sealed_inputs,
+                    &request.inputs_created,
+                );
                 match (plaintx, inputs) {
                     (Ok(PlainTxAux::DepositStakeTx(witness)), Some(inputs)) => {
                         let result =
@@ -1,4 +1,4 @@
-use crate::validate::write_back_response;
+use crate::validate::{with_creation, write_back_response};
 use aead::{generic_array::GenericArray, Aead, NewAead};
 use aes_gcm_siv::Aes128GcmSiv;
 use chain_core::state::tendermint::BlockHeight;
@@ -167,8 +167,10 @@ pub(crate) fn handle_encrypt_request(
 ) -> sgx_status_t {
     match (unseal_request(&mut request), request.tx_inputs) {
         (Some(EncryptionRequest::TransferTx(tx, witness)), Some(sealed_inputs)) => {
-            let unsealed_inputs =
-                check_unseal(None, false, tx.inputs.iter().map(|x| x.id), sealed_inputs);
+            let unsealed_inputs = with_creation(
+                check_unseal(None, false, tx.inputs.iter().map(|x| x.id), sealed_inputs),
+                &request.inputs_created,
+            );
             if let Some(inputs) = unsealed_inputs {
                 let result = verify_transfer(&tx, &witness, &request.info, inputs);
                 let txid = tx.id();
@@ -183,8 +185,10 @@ pub(crate) fn handle_encrypt_request(
             }
         }
         (Some(EncryptionRequest::DepositStake(tx, witness)), Some(sealed_inputs)) => {
-            let unsealed_inputs =
-                check_unseal(None, false, tx.inputs.iter().map(|x| x.id), sealed_inputs);
+            let unsealed_inputs = with_creation(
+                check_unseal(None, false, tx.inputs.iter().map(|x| x.id), sealed_inputs),
+                &request.inputs_created,
+            );
             if let Some(inputs) = unsealed_inputs {
                 let result = verify_bonded_deposit_core(&tx, &witness, &request.info, inputs);
                 let txid = tx.id();
@@ -6,7 +6,8 @@ use chain_core::tx::{data::input::TxoSize, PlainTxAux, TxEnclaveAux, TxObfuscate
 use chain_tx_filter::BlockFilter;
 use chain_tx_validation::witness::verify_tx_recover_address;
 use chain_tx_validation::{
-    verify_bonded_deposit_core, verify_transfer, verify_unbonded_withdraw_core, TxWithOutputs,
+    verify_bonded_deposit_core, verify_transfer, verify_unbonded_withdraw_core, TxCreation,
+    TxWithOutputs,
 };
 use enclave_protocol::{
     is_basic_valid_tx_request, IntraEnclaveResponse, IntraEnclaveResponseOk, VerifyTxRequest,
@@ -140,6 +141,23 @@ pub(crate) fn write_back_response(
     }
 }
 
+/// pairs the unsealed transaction inputs with the blocks they were created in
+#[inline]
+pub(crate) fn with_creation(
+    inputs: Option<Vec<TxWithOutputs>>,
+    inputs_created: &[TxCreation],
+) -> Option<Vec<(TxWithOutputs, TxCreation)>> {
+    match inputs {
+        Some(inputs) if inputs.len() == inputs_created.len() => Some(
+            inputs
+                .into_iter()
+                .zip(inputs_created.iter().copied())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
 #[inline]
 fn decrypt(payload: &TxObfuscated) -> Result<PlainTxAux, ()> {
     crate::obfuscate::decrypt(payload)
@@ -167,8 +185,10 @@ pub(crate) fn handle_validate_tx(
             },
         ) => {
             let plaintx = decrypt(&payload);
-            let unsealed_inputs =
-                check_unseal(None, false, inputs.iter().map(|x| x.id), sealed_inputs);
+            let unsealed_inputs = with_creation(
+                check_unseal(None, false, inputs.iter().map(|x| x.id), sealed_inputs),
+                &request.inputs_created,
+            );
             match (plaintx, unsealed_inputs) {
                 (Ok(PlainTxAux::TransferTx(tx, witness)), Some(inputs)) => {
                     if tx.id() != payload.txid || tx.outputs.len() as TxoSize != no_of_outputs {
@@ -191,7 +211,10 @@ pub(crate) fn handle_validate_tx(
         }
         (Some(sealed_inputs), TxEnclaveAux::DepositStakeTx { tx, payload }) => {
             let plaintx = decrypt(&payload);
-            let inputs = check_unseal(None, false, tx.inputs.iter().map(|x| x.id), sealed_inputs);
+            let inputs = with_creation(
+                check_unseal(None, false, tx.inputs.iter().map(|x| x.id), sealed_inputs),
+                &request.inputs_created,
+            );
             match (plaintx, inputs) {
                 (Ok(PlainTxAux::DepositStakeTx(witness)), Some(inputs)) => {
                     let result = verify_bonded_deposit_core(&tx, &witness, &request.info, inputs);
@@ -14,22 +14,25 @@ pub mod witness;
 #[macro_use]
 extern crate sgx_tstd as std;
 
+use chain_core::common::Timespec;
 use chain_core::init::coin::Coin;
 use chain_core::state::account::{DepositBondTx, StakedState, WithdrawUnbondedTx};
+use chain_core::tx::data::address::{ExtendedAddr, HashTimeLock};
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
 use chain_core::tx::data::Tx;
 use chain_core::tx::data::TxId;
 use chain_core::tx::fee::Fee;
-use chain_core::tx::witness::TxWitness;
+use chain_core::tx::witness::{TxInWitness, TxWitness};
 use chain_core::tx::TransactionId;
 pub use chain_core::tx::TxWithOutputs;
 pub use chain_core::ChainInfo;
+pub use chain_core::TxCreation;
 use parity_scale_codec::{Decode, Encode};
 use std::collections::BTreeSet;
 use std::fmt;
 use std::prelude::v1::Vec;
-use witness::verify_tx_address;
+use witness::{hash_preimage, verify_tx_address};
 
 /// All possible TX validation errors
 #[derive(Debug, Encode, Decode)]
@@ -82,6 +85,12 @@ pub enum Error {
     AccountIncorrectNonce,
     /// Account is jailed
     AccountJailed,
+    /// hash-timelocked output can't be claimed after its deadline
+    HashLockExpired,
+    /// hash-timelocked output claimed with a wrong preimage
+    InvalidHashPreimage,
+    /// output transaction is in timelock (relative to its creation) that hasn't passed
+    OutputInRelativeTimelock,
 }
 
 impl fmt::Display for Error {
@@ -131,13 +140,21 @@ impl fmt::Display for Error {
             AccountIncorrectNonce => write!(f, "incorrect transaction count for account operation"),
             MismatchAccountAddress => write!(f, "mismatch account address"),
             AccountJailed => write!(f, "account is jailed"),
+            HashLockExpired => write!(f, "hash-timelocked output claimed after its deadline"),
+            InvalidHashPreimage => {
+                write!(f, "hash-timelocked output claimed with a wrong preimage")
+            }
+            OutputInRelativeTimelock => write!(
+                f,
+                "output transaction is in timelock relative to its creation"
+            ),
         }
     }
 }
 
 /// The network byte is checked, as this is fixed at compile-time in enclave code
 /// altogether with ProductId etc. (so that testnet or devnet enclaves don't seal/unseal mainnet transactions, and vice versa).
-/// App version is fixed in a constant in chain-core -- it is expected that code with higher version will be able to "handle"
+/// App version is the one active on the network (at most the constant in chain-core) -- it is expected that code with higher version will be able to "handle"
 /// lower version to some extent (e.g. have some grace period fixes),
 /// but lower version may not be able to "handle" higher version, as even if the schema is the same,
 /// the semantics may have changed and lead to undesirable outcomes.
@@ -150,8 +167,8 @@ fn check_attributes(
     if extra_info.chain_hex_id != tx_chain_hex_id {
         return Err(Error::WrongChainHexId);
     }
-    // check that version number is <= current one
-    if chain_core::APP_VERSION < tx_app_version {
+    // check that version number is <= the one active on the network
+    if extra_info.app_version < tx_app_version {
         return Err(Error::UnsupportedVersion);
     }
     Ok(())
@@ -182,18 +199,43 @@ pub fn check_inputs_basic(inputs: &[TxoPointer], witness: &TxWitness) -> Result<
     Ok(())
 }
 
+/// the recipient can claim a hash-timelocked output with the preimage before the deadline,
+/// the sender can get it refunded only since the deadline
+fn check_hash_time_lock(
+    lock: &HashTimeLock,
+    witness: &TxInWitness,
+    block_time: Timespec,
+) -> Result<(), Error> {
+    match witness {
+        TxInWitness::HashLockClaim(preimage, ..) => {
+            if block_time >= lock.deadline {
+                return Err(Error::HashLockExpired);
+            }
+            if hash_preimage(preimage) != lock.hash {
+                return Err(Error::InvalidHashPreimage);
+            }
+            Ok(())
+        }
+        TxInWitness::TimeLockRefund(..) if block_time < lock.deadline => {
+            Err(Error::OutputInTimelock)
+        }
+        // other witness types are rejected in `verify_tx_address`
+        _ => Ok(()),
+    }
+}
+
 fn check_inputs(
     main_txid: &TxId,
     inputs: &[TxoPointer],
     witness: &TxWitness,
     extra_info: &ChainInfo,
-    transaction_inputs: Vec<TxWithOutputs>,
+    transaction_inputs: Vec<(TxWithOutputs, TxCreation)>,
 ) -> Result<Coin, Error> {
     let mut incoins = Coin::zero();
     // verify that txids of inputs correspond to the owner/signer
     // and it'd check they are not spent
     // TODO: zip3 / itertools?
-    for (txin, (tx, in_witness)) in inputs
+    for (txin, ((tx, created), in_witness)) in inputs
         .iter()
         .zip(transaction_inputs.iter().zip(witness.iter()))
     {
@@ -211,6 +253,14 @@ fn check_inputs(
                 return Err(Error::OutputInTimelock);
             }
         }
+        if let Some(relative_lock) = &txout.relative_lock {
+            if !relative_lock.is_unlocked(created, extra_info.block_height, extra_info.block_time) {
+                return Err(Error::OutputInRelativeTimelock);
+            }
+        }
+        if let ExtendedAddr::HashTimeLock(lock) = &txout.address {
+            check_hash_time_lock(lock, in_witness, extra_info.block_time)?;
+        }
         let wv = verify_tx_address(&in_witness, main_txid, &txout.address);
         if let Err(_e) = wv {
             return Err(Error::EcdsaCrypto); // FIXME: Err(Error::EcdsaCrypto(e));
@@ -244,6 +294,19 @@ pub fn check_outputs_basic(outputs: &[TxOut]) -> Result<(), Error> {
     Ok(())
 }
 
+/// the hash-timelocked addresses and the relative timelocks were added in app version 2,
+/// so the outputs using them aren't accepted before the network upgrades to it
+fn check_outputs_version(outputs: &[TxOut], extra_info: &ChainInfo) -> Result<(), Error> {
+    if extra_info.app_version < 2
+        && outputs.iter().any(|x| {
+            x.relative_lock.is_some() || matches!(x.address, ExtendedAddr::HashTimeLock(_))
+        })
+    {
+        return Err(Error::UnsupportedVersion);
+    }
+    Ok(())
+}
+
 fn check_input_output_sums(
     incoins: Coin,
     outcoins: Coin,
@@ -267,7 +330,7 @@ pub fn verify_transfer(
     maintx: &Tx,
     witness: &TxWitness,
     extra_info: &ChainInfo,
-    transaction_inputs: Vec<TxWithOutputs>,
+    transaction_inputs: Vec<(TxWithOutputs, TxCreation)>,
 ) -> Result<Fee, Error> {
     check_attributes(
         maintx.attributes.chain_hex_id,
@@ -276,6 +339,7 @@ pub fn verify_transfer(
     )?;
     check_inputs_basic(&maintx.inputs, witness)?;
     check_outputs_basic(&maintx.outputs)?;
+    check_outputs_version(&maintx.outputs, extra_info)?;
     let incoins = check_inputs(
         &maintx.id(),
         &maintx.inputs,
@@ -296,7 +360,7 @@ pub fn verify_bonded_deposit_core(
     maintx: &DepositBondTx,
     witness: &TxWitness,
     extra_info: &ChainInfo,
-    transaction_inputs: Vec<TxWithOutputs>,
+    transaction_inputs: Vec<(TxWithOutputs, TxCreation)>,
 ) -> Result<Coin, Error> {
     check_attributes(
         maintx.attributes.chain_hex_id,
@@ -332,6 +396,7 @@ pub fn verify_unbonded_withdraw_core(
         &extra_info,
     )?;
     check_outputs_basic(&maintx.outputs)?;
+    check_outputs_version(&maintx.outputs, extra_info)?;
     // checks that account transaction count matches to the one in transaction
     if maintx.nonce != account.nonce {
         return Err(Error::AccountIncorrectNonce);
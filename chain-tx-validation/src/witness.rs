@@ -1,9 +1,17 @@
+use chain_core::common::{hash256, H256};
 use chain_core::init::address::RedeemAddress;
 use chain_core::state::account::{StakedStateAddress, StakedStateOpWitness};
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::TxId;
 use chain_core::tx::witness::TxInWitness;
 use secp256k1::{key::XOnlyPublicKey, schnorrsig::schnorr_verify, Message, Secp256k1};
+use sha2::Sha256;
+
+/// the hash-timelocked outputs are locked to the SHA-256 hash of the preimage
+/// (so that the same secret can be used on the other chain in a swap)
+pub fn hash_preimage(preimage: &H256) -> H256 {
+    hash256::<Sha256>(preimage)
+}
 
 /// verify a given extended address is associated to the witness
 /// and the signature against the given transaction `Tx`
@@ -19,19 +27,29 @@ pub fn verify_tx_address(
     let secp = Secp256k1::preallocated_verification_only(&mut buf_vfy)?;
     let message = Message::from_slice(&txid[..])?;
 
-    match (witness, address) {
+    // NOTE: the hash preimage and the deadline of the hash-timelocked outputs
+    // depend on the block time, so they are checked in `check_inputs`
+    let (sig, proof, root_hash) = match (witness, address) {
         (TxInWitness::TreeSig(sig, proof), ExtendedAddr::OrTree(root_hash)) => {
-            if !proof.verify(root_hash) {
-                Err(secp256k1::Error::InvalidPublicKey)
-            } else {
-                schnorr_verify(
-                    &secp,
-                    &message,
-                    &sig,
-                    &XOnlyPublicKey::from_slice(proof.value().as_bytes())?,
-                )
-            }
+            (sig, proof, root_hash)
+        }
+        (TxInWitness::HashLockClaim(_, sig, proof), ExtendedAddr::HashTimeLock(lock)) => {
+            (sig, proof, &lock.recipient)
+        }
+        (TxInWitness::TimeLockRefund(sig, proof), ExtendedAddr::HashTimeLock(lock)) => {
+            (sig, proof, &lock.refund)
         }
+        _ => return Err(secp256k1::Error::IncorrectSignature),
+    };
+    if !proof.verify(root_hash) {
+        Err(secp256k1::Error::InvalidPublicKey)
+    } else {
+        schnorr_verify(
+            &secp,
+            &message,
+            &sig,
+            &XOnlyPublicKey::from_slice(proof.value().as_bytes())?,
+        )
     }
 }
 
@@ -63,6 +81,7 @@ pub mod tests {
     use secp256k1::{PublicKey, SecretKey};
 
     use chain_core::common::MerkleTree;
+    use chain_core::tx::data::address::HashTimeLock;
     use chain_core::tx::data::Tx;
     use chain_core::tx::witness::tree::RawXOnlyPubkey;
     use chain_core::tx::TransactionId;
@@ -166,6 +185,81 @@ pub mod tests {
         assert!(verify_tx_address(&witness, &transation.id(), &address).is_err())
     }
 
+    #[test]
+    fn check_hash_time_lock_verify() {
+        let transation = Tx::new();
+
+        let secp = Secp256k1::new();
+
+        let secret_keys = [
+            SecretKey::from_slice(&[0xcd; 32]).expect("Unable to create secret key"),
+            SecretKey::from_slice(&[0xde; 32]).expect("Unable to create secret key"),
+        ];
+        let public_keys = [
+            RawXOnlyPubkey::from(
+                XOnlyPublicKey::from_secret_key(&secp, &secret_keys[0]).serialize(),
+            ),
+            RawXOnlyPubkey::from(
+                XOnlyPublicKey::from_secret_key(&secp, &secret_keys[1]).serialize(),
+            ),
+        ];
+        let recipient_tree = MerkleTree::new(vec![public_keys[0].clone()]);
+        let refund_tree = MerkleTree::new(vec![public_keys[1].clone()]);
+        let preimage = [0x42; 32];
+        let address = ExtendedAddr::HashTimeLock(HashTimeLock {
+            hash: hash_preimage(&preimage),
+            recipient: recipient_tree.root_hash(),
+            refund: refund_tree.root_hash(),
+            deadline: 1_000,
+        });
+        let message = Message::from_slice(&transation.id()).unwrap();
+        let sign =
+            |i: usize| schnorr_sign(&secp, &message, &secret_keys[i], &mut rand::thread_rng());
+
+        let claim = TxInWitness::HashLockClaim(
+            preimage,
+            sign(0),
+            recipient_tree
+                .generate_proof(public_keys[0].clone())
+                .unwrap(),
+        );
+        assert!(verify_tx_address(&claim, &transation.id(), &address).is_ok());
+        let refund = TxInWitness::TimeLockRefund(
+            sign(1),
+            refund_tree.generate_proof(public_keys[1].clone()).unwrap(),
+        );
+        assert!(verify_tx_address(&refund, &transation.id(), &address).is_ok());
+
+        // the refund address can't claim and the recipient can't get a refund
+        let wrong_claim = TxInWitness::HashLockClaim(
+            preimage,
+            sign(1),
+            refund_tree.generate_proof(public_keys[1].clone()).unwrap(),
+        );
+        assert!(verify_tx_address(&wrong_claim, &transation.id(), &address).is_err());
+        let wrong_refund = TxInWitness::TimeLockRefund(
+            sign(0),
+            recipient_tree
+                .generate_proof(public_keys[0].clone())
+                .unwrap(),
+        );
+        assert!(verify_tx_address(&wrong_refund, &transation.id(), &address).is_err());
+        // the tree signature can't be used for the hash-timelocked outputs
+        let tree_sig = TxInWitness::TreeSig(
+            sign(0),
+            recipient_tree
+                .generate_proof(public_keys[0].clone())
+                .unwrap(),
+        );
+        assert!(verify_tx_address(&tree_sig, &transation.id(), &address).is_err());
+        assert!(verify_tx_address(
+            &claim,
+            &transation.id(),
+            &ExtendedAddr::OrTree(recipient_tree.root_hash())
+        )
+        .is_err());
+    }
+
     #[test]
     fn check_staked_verify() {
         let transation = Tx::new();
@@ -38,12 +38,11 @@ use self::multisig_command::MultiSigCommand;
 use self::transaction_command::TransactionCommand;
 use self::wallet_command::WalletCommand;
 use crate::logo::{get_jok, get_logo};
-use crate::{ask_seckey, storage_path, tendermint_url};
-use chain_core::tx::fee::LinearFee;
+use crate::{ask_seckey, hw_signer, storage_path, tendermint_url};
 use client_core::hd_wallet::HardwareKind;
-use client_core::service::HwKeyService;
 #[cfg(feature = "mock-hardware-wallet")]
 use client_core::service::MockHardwareService;
+use client_core::service::{ExternalSignerService, HwKeyService};
 use once_cell::sync::Lazy;
 use std::env;
 
@@ -57,7 +56,8 @@ type AppTransactionCipher = DefaultTransactionObfuscation;
 #[cfg(feature = "mock-enclave")]
 type AppTransactionCipher = MockAbciTransactionObfuscation<WebsocketRpcClient>;
 
-type AppTxBuilder = DefaultWalletTransactionBuilder<SledStorage, LinearFee, AppTransactionCipher>;
+type AppTxBuilder =
+    DefaultWalletTransactionBuilder<SledStorage, WebsocketRpcClient, AppTransactionCipher>;
 type AppWalletClient = DefaultWalletClient<SledStorage, WebsocketRpcClient, AppTxBuilder>;
 
 static VERSION: Lazy<String> = Lazy::new(|| {
@@ -213,6 +213,13 @@ pub enum Command {
             help = "Disable address recovery, which is not necessary, if addresses already exist"
         )]
         disable_address_recovery: bool,
+        #[structopt(
+            name = "address-gap-limit",
+            long,
+            default_value = "20",
+            help = "Number of unused addresses derived ahead of the last used one in address recovery"
+        )]
+        address_gap_limit: u32,
         #[structopt(
             name = "block-height-ensure",
             long,
@@ -262,6 +269,26 @@ fn get_tx_query(
     Ok(MockAbciTransactionObfuscation::new(tendermint_client))
 }
 
+/// Ledger and Trezor are reached through the external signer set in `CRYPTO_CLIENT_HW_SIGNER`
+fn get_hw_key_service(hardware: &Option<HardwareKind>) -> Result<HwKeyService> {
+    match hardware {
+        None => Ok(HwKeyService::default()),
+        #[cfg(feature = "mock-hardware-wallet")]
+        Some(HardwareKind::Mock) => Ok(HwKeyService::Mock(MockHardwareService::new())),
+        Some(HardwareKind::Trezor) | Some(HardwareKind::Ledger) => {
+            let spec = hw_signer().chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    "Hardware wallet requires an external signer: set CRYPTO_CLIENT_HW_SIGNER (e.g. \"exec:<program>\" or \"unix:<socket path>\")",
+                )
+            })?;
+            Ok(HwKeyService::External(ExternalSignerService::from_spec(
+                &spec,
+            )?))
+        }
+    }
+}
+
 impl Command {
     pub fn execute(&self) -> Result<()> {
         match self {
@@ -302,20 +329,14 @@ impl Command {
             } => {
                 let storage = SledStorage::new(storage_path())?;
                 let tendermint_client = WebsocketRpcClient::new(&tendermint_url())?;
-                let hw_key_service = match hardware {
-                    None => HwKeyService::default(),
-                    #[cfg(feature = "mock-hardware-wallet")]
-                    Some(HardwareKind::Mock) => HwKeyService::Mock(MockHardwareService::new()),
-                    Some(HardwareKind::Trezor) => HwKeyService::default(),
-                    Some(HardwareKind::Ledger) => HwKeyService::default(),
-                };
+                let hw_key_service = get_hw_key_service(hardware)?;
                 let signer_manager =
                     WalletSignerManager::new(storage.clone(), hw_key_service.clone());
-                let fee_algorithm = tendermint_client.genesis()?.fee_policy();
+                let fee_algorithm = tendermint_client.clone();
                 let transaction_obfuscation = get_tx_query(tendermint_client.clone())?;
                 let transaction_builder = DefaultWalletTransactionBuilder::new(
                     signer_manager.clone(),
-                    fee_algorithm,
+                    fee_algorithm.clone(),
                     transaction_obfuscation.clone(),
                 );
 
@@ -341,22 +362,16 @@ impl Command {
                 address,
                 hardware,
             } => {
-                let hw_key_service = match hardware {
-                    None => HwKeyService::default(),
-                    #[cfg(feature = "mock-hardware-wallet")]
-                    Some(HardwareKind::Mock) => HwKeyService::Mock(MockHardwareService::new()),
-                    Some(HardwareKind::Trezor) => HwKeyService::default(),
-                    Some(HardwareKind::Ledger) => HwKeyService::default(),
-                };
+                let hw_key_service = get_hw_key_service(hardware)?;
                 let storage = SledStorage::new(storage_path())?;
                 let tendermint_client = WebsocketRpcClient::new(&tendermint_url())?;
                 let signer_manager =
                     WalletSignerManager::new(storage.clone(), hw_key_service.clone());
-                let fee_algorithm = tendermint_client.genesis()?.fee_policy();
+                let fee_algorithm = tendermint_client.clone();
                 let transaction_obfuscation = get_tx_query(tendermint_client.clone())?;
                 let transaction_builder = DefaultWalletTransactionBuilder::new(
                     signer_manager.clone(),
-                    fee_algorithm,
+                    fee_algorithm.clone(),
                     transaction_obfuscation.clone(),
                 );
                 let wallet_client = DefaultWalletClient::new(
@@ -382,6 +397,7 @@ impl Command {
                 force,
                 enable_fast_forward,
                 disable_address_recovery,
+                address_gap_limit,
                 block_height_ensure,
             } => {
                 let enckey = ask_seckey(None)?;
@@ -396,6 +412,7 @@ impl Command {
                     SyncerOptions {
                         enable_fast_forward: *enable_fast_forward,
                         enable_address_recovery: !*disable_address_recovery,
+                        address_gap_limit: *address_gap_limit,
                         batch_size: *batch_size,
                         block_height_ensure: *block_height_ensure,
                     },
@@ -453,7 +470,7 @@ impl Command {
                     staked_state
                         .node_meta
                         .and_then(|val| match val {
-                            NodeState::CommunityNode(_) => unreachable!("FIXME"),
+                            NodeState::CommunityNode(v) => v.jailed_until,
                             NodeState::CouncilNode(v) => v.jailed_until,
                         })
                         .map_or_else(
@@ -700,7 +717,7 @@ fn get_wallet_client(storage: SledStorage) -> Result<AppWalletClient> {
     let hw_key_service = HwKeyService::default();
 
     let signer_manager = WalletSignerManager::new(storage.clone(), hw_key_service.clone());
-    let fee_algorithm = tendermint_client.genesis()?.fee_policy();
+    let fee_algorithm = tendermint_client.clone();
     let transaction_obfuscation = get_tx_query(tendermint_client.clone())?;
     let transaction_builder = DefaultWalletTransactionBuilder::new(
         signer_manager,
@@ -1,6 +1,6 @@
 use ra_client::EnclaveCertVerifier;
 use std::collections::BTreeSet;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
@@ -12,17 +12,18 @@ use chain_core::init::network::get_network_id;
 use chain_core::state::account::{
     ConfidentialInit, CouncilNodeMeta, StakedStateAddress, StakedStateOpAttributes,
 };
-use chain_core::state::tendermint::TendermintValidatorPubKey;
+use chain_core::state::tendermint::{TendermintNodeId, TendermintValidatorPubKey};
 use chain_core::tx::data::access::{TxAccess, TxAccessPolicy};
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
+use chain_core::tx::fee::Milli;
 use chain_core::tx::TxAux;
 use client_common::{Error, ErrorKind, PublicKey, Result, ResultExt, SecKey, Transaction};
 use client_core::transaction_builder::SignedTransferTransaction;
 use client_core::types::{BalanceChange, TransactionPending};
-use client_core::WalletClient;
+use client_core::{InputSelection, InputSelectionStrategy, WalletClient};
 use client_network::NetworkOpsClient;
 use mls::{Codec, KeyPackage};
 
@@ -38,13 +39,22 @@ use crate::{ask_seckey, coin_from_str};
 use client_core::transaction_builder::UnsignedTransferTransaction;
 use mls::extensions::LifeTimeExt;
 
-const TRANSACTION_TYPE_VARIANTS: [&str; 6] = [
+const TRANSACTION_TYPE_VARIANTS: [&str; 7] = [
     "transfer",
     "deposit",
     "unbond",
     "withdraw",
     "unjail",
     "node-join",
+    "node-update",
+];
+
+const INPUT_SELECTION_STRATEGY_VARIANTS: [&str; 5] = [
+    "highest-value-first",
+    "lowest-value-first",
+    "random",
+    "branch-and-bound",
+    "privacy",
 ];
 
 #[derive(Debug, PartialEq)]
@@ -58,6 +68,8 @@ pub enum TransactionType {
     Withdraw,
     Unjail,
     NodeJoin,
+    // update the name, security contact, consensus key, commission rate or p2p node ID of a council node
+    NodeUpdate,
 }
 
 impl FromStr for TransactionType {
@@ -76,6 +88,8 @@ impl FromStr for TransactionType {
             Ok(TransactionType::Unjail)
         } else if eq_ascii(s, "node-join") {
             Ok(TransactionType::NodeJoin)
+        } else if eq_ascii(s, "node-update") {
+            Ok(TransactionType::NodeUpdate)
         } else {
             Err(ErrorKind::DeserializationError.into())
         }
@@ -117,6 +131,35 @@ pub enum TransactionCommand {
             case_insensitive = true
         )]
         keypackage: Option<PathBuf>,
+        #[structopt(
+            name = "input selection strategy",
+            long = "strategy",
+            help = "Strategy to select inputs when transaction type is transfer",
+            possible_values = &INPUT_SELECTION_STRATEGY_VARIANTS
+        )]
+        strategy: Option<InputSelectionStrategy>,
+        #[structopt(
+            name = "dust threshold",
+            long = "consolidate-dust",
+            parse(try_from_str = coin_from_str),
+            conflicts_with = "input selection strategy",
+            help = "Spend all inputs whose value is not more than the threshold when transaction type is transfer"
+        )]
+        dust_threshold: Option<Coin>,
+        #[structopt(
+            name = "include",
+            long = "include",
+            parse(try_from_str = txo_pointer_from_str),
+            help = "Input (`<transaction id>:<index>`) to spend when transaction type is transfer"
+        )]
+        include: Vec<TxoPointer>,
+        #[structopt(
+            name = "exclude",
+            long = "exclude",
+            parse(try_from_str = txo_pointer_from_str),
+            help = "Input (`<transaction id>:<index>`) not to spend when transaction type is transfer"
+        )]
+        exclude: Vec<TxoPointer>,
     },
     #[structopt(name = "show", about = "Display details of a transaction")]
     Show {
@@ -254,14 +297,30 @@ impl TransactionCommand {
                 transaction_type,
                 advanced,
                 keypackage,
-            } => new_transaction(
-                wallet_client,
-                network_ops_client,
-                name,
-                transaction_type,
-                *advanced,
-                keypackage.clone(),
-            ),
+                strategy,
+                dust_threshold,
+                include,
+                exclude,
+            } => {
+                let strategy = match dust_threshold {
+                    Some(threshold) => Some(InputSelectionStrategy::ConsolidateDust(*threshold)),
+                    None => *strategy,
+                };
+                let input_selection = InputSelection {
+                    strategy: strategy.unwrap_or_default(),
+                    include: include.clone(),
+                    exclude: exclude.clone(),
+                };
+                new_transaction(
+                    wallet_client,
+                    network_ops_client,
+                    name,
+                    transaction_type,
+                    *advanced,
+                    keypackage.clone(),
+                    input_selection,
+                )
+            }
             TransactionCommand::Show {
                 name,
                 transaction_id,
@@ -530,6 +589,7 @@ fn new_transaction<T: WalletClient, N: NetworkOpsClient>(
     transaction_type: &TransactionType,
     advanced: bool,
     keypackage: Option<PathBuf>,
+    input_selection: InputSelection,
 ) -> Result<()> {
     let can_use_advanced = vec![TransactionType::Deposit];
     if advanced && !can_use_advanced.contains(transaction_type) {
@@ -539,11 +599,21 @@ fn new_transaction<T: WalletClient, N: NetworkOpsClient>(
         );
         return Err(error);
     }
+    if input_selection != InputSelection::default()
+        && transaction_type != &TransactionType::Transfer
+    {
+        let error = Error::new(
+            ErrorKind::InvalidInput,
+            "input selection is only available when transfer",
+        );
+        return Err(error);
+    }
     let enckey = ask_seckey(None)?;
 
     match transaction_type {
         TransactionType::Transfer => {
-            let (tx_aux, tx_pending) = new_transfer_transaction(wallet_client, name, &enckey)?;
+            let (tx_aux, tx_pending) =
+                new_transfer_transaction(wallet_client, name, &enckey, input_selection)?;
             wallet_client.broadcast_transaction(&tx_aux)?;
             wallet_client.update_tx_pending_state(&name, &enckey, tx_aux.tx_id(), tx_pending)?;
         }
@@ -580,6 +650,10 @@ fn new_transaction<T: WalletClient, N: NetworkOpsClient>(
             let tx_aux = new_node_join_transaction(network_ops_client, name, &enckey, keypackage)?;
             wallet_client.broadcast_transaction(&tx_aux)?;
         }
+        TransactionType::NodeUpdate => {
+            let tx_aux = new_node_metadata_update_transaction(network_ops_client, name, &enckey)?;
+            wallet_client.broadcast_transaction(&tx_aux)?;
+        }
     };
 
     success("Transaction successfully created!");
@@ -791,6 +865,7 @@ fn new_transfer_transaction<T: WalletClient>(
     wallet_client: &T,
     name: &str,
     enckey: &SecKey,
+    input_selection: InputSelection,
 ) -> Result<(TxAux, TransactionPending)> {
     let outputs = ask_outputs()?;
     let mut view_keys = ask_view_keys()?;
@@ -814,7 +889,7 @@ fn new_transfer_transaction<T: WalletClient>(
         &enckey,
         outputs,
         attributes,
-        None,
+        Some(input_selection),
         return_address,
     )?;
     let tx_pending = TransactionPending {
@@ -846,12 +921,83 @@ fn new_node_join_transaction<N: NetworkOpsClient>(
     let staking_account_address = ask_staking_address()?;
     let node_metadata = ask_node_metadata(keypackage)?;
 
+    ask("Enter commission rate (e.g. 0.1 for 10%) (leave blank for no commission): ");
+    let commission_rate =
+        text().chain(|| (ErrorKind::IoError, "Unable to read commission rate"))?;
+    let commission_rate = if commission_rate.is_empty() {
+        Milli::default()
+    } else {
+        commission_rate
+            .parse::<Milli>()
+            .chain(|| (ErrorKind::DeserializationError, "Invalid commission rate"))?
+    };
+
     network_ops_client.create_node_join_transaction(
         name,
         enckey,
         staking_account_address,
         attributes,
         node_metadata,
+        commission_rate,
+        true,
+    )
+}
+
+fn new_node_metadata_update_transaction<N: NetworkOpsClient>(
+    network_ops_client: &N,
+    name: &str,
+    enckey: &SecKey,
+) -> Result<TxAux> {
+    let attributes = StakedStateOpAttributes::new(get_network_id());
+    let staking_account_address = ask_staking_address()?;
+
+    ask("Enter new validator node name (leave blank to keep the current one): ");
+    let node_name = text().chain(|| (ErrorKind::IoError, "Unable to read validator node name"))?;
+
+    ask("Enter new security contact (leave blank to keep the current one): ");
+    let security_contact =
+        text().chain(|| (ErrorKind::IoError, "Unable to read security contact"))?;
+
+    ask("Enter new validator pub-key (base64 encoded) (leave blank to keep the current one): ");
+    let validator_pubkey =
+        text().chain(|| (ErrorKind::IoError, "Unable to read validator pub-key"))?;
+    let consensus_pubkey = if validator_pubkey.is_empty() {
+        None
+    } else {
+        Some(parse_validator_pubkey(&validator_pubkey)?)
+    };
+
+    ask("Enter new commission rate (e.g. 0.1 for 10%) (leave blank to keep the current one): ");
+    let commission_rate =
+        text().chain(|| (ErrorKind::IoError, "Unable to read commission rate"))?;
+    let commission_rate = if commission_rate.is_empty() {
+        None
+    } else {
+        Some(
+            commission_rate
+                .parse::<Milli>()
+                .chain(|| (ErrorKind::DeserializationError, "Invalid commission rate"))?,
+        )
+    };
+
+    ask("Enter new p2p node ID (hex encoded) (leave blank to keep the current one): ");
+    let p2p_node_id = text().chain(|| (ErrorKind::IoError, "Unable to read p2p node ID"))?;
+    let p2p_node_id = if p2p_node_id.is_empty() {
+        None
+    } else {
+        Some(parse_p2p_node_id(&p2p_node_id)?)
+    };
+
+    network_ops_client.create_node_metadata_update_transaction(
+        name,
+        enckey,
+        staking_account_address,
+        attributes,
+        Some(node_name).filter(|x| !x.is_empty()),
+        Some(security_contact).filter(|x| !x.is_empty()).map(Some),
+        consensus_pubkey,
+        commission_rate,
+        p2p_node_id,
         true,
     )
 }
@@ -944,6 +1090,43 @@ fn ask_cro() -> Result<Coin> {
     }
 }
 
+fn txo_pointer_from_str(txo_pointer: &str) -> Result<TxoPointer> {
+    let mut parts = txo_pointer.splitn(2, ':');
+    let (transaction_id, index) = match (parts.next(), parts.next()) {
+        (Some(transaction_id), Some(index)) => (transaction_id, index),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::DeserializationError,
+                "Input should be in the form of <transaction id>:<index>",
+            ))
+        }
+    };
+
+    let transaction_id_decoded = decode(transaction_id).chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            "Unable to deserialize transaction ID from bytes",
+        )
+    })?;
+    if transaction_id_decoded.len() != HASH_SIZE_256 {
+        return Err(Error::new(
+            ErrorKind::DeserializationError,
+            "Transaction ID should be of 32 bytes",
+        ));
+    }
+    let mut id: [u8; HASH_SIZE_256] = [0; HASH_SIZE_256];
+    id.copy_from_slice(&transaction_id_decoded);
+
+    let index = index.parse::<usize>().chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            "Unable to parse input index into integer",
+        )
+    })?;
+
+    Ok(TxoPointer::new(id, index))
+}
+
 fn ask_inputs() -> Result<Vec<TxoPointer>> {
     let mut inputs = Vec::new();
 
@@ -1155,7 +1338,36 @@ fn ask_node_metadata(keypackage: Option<PathBuf>) -> Result<CouncilNodeMeta> {
     let info = keypackage_info(&keypackage)?;
     success(&info);
 
-    let decoded_pubkey = base64::decode(&validator_pubkey).chain(|| {
+    ask("Enter p2p node ID (hex encoded) (leave blank to skip): ");
+    let p2p_node_id = text().chain(|| (ErrorKind::IoError, "Unable to read p2p node ID"))?;
+
+    let mut node_metadata = CouncilNodeMeta::new_with_details(
+        name,
+        None,
+        parse_validator_pubkey(&validator_pubkey)?,
+        ConfidentialInit {
+            keypackage: keypackage_raw,
+        },
+    );
+    if !p2p_node_id.is_empty() {
+        node_metadata.node_info.p2p_node_id = Some(parse_p2p_node_id(&p2p_node_id)?);
+    }
+    Ok(node_metadata)
+}
+
+fn parse_p2p_node_id(p2p_node_id: &str) -> Result<TendermintNodeId> {
+    let decoded_id = decode(p2p_node_id).chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            "Unable to decode hex encoded bytes of p2p node ID",
+        )
+    })?;
+    TendermintNodeId::try_from(decoded_id.as_slice())
+        .chain(|| (ErrorKind::InvalidInput, "Expected p2p node ID of 20 bytes"))
+}
+
+fn parse_validator_pubkey(validator_pubkey: &str) -> Result<TendermintValidatorPubKey> {
+    let decoded_pubkey = base64::decode(validator_pubkey).chain(|| {
         (
             ErrorKind::DeserializationError,
             "Unable to decode base64 encoded bytes of validator pub-key",
@@ -1172,12 +1384,5 @@ fn ask_node_metadata(keypackage: Option<PathBuf>) -> Result<CouncilNodeMeta> {
     let mut pubkey_bytes = [0; 32];
     pubkey_bytes.copy_from_slice(&decoded_pubkey);
 
-    Ok(CouncilNodeMeta::new_with_details(
-        name,
-        None,
-        TendermintValidatorPubKey::Ed25519(pubkey_bytes),
-        ConfidentialInit {
-            keypackage: keypackage_raw,
-        },
-    ))
+    Ok(TendermintValidatorPubKey::Ed25519(pubkey_bytes))
 }
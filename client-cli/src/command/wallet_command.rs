@@ -1,4 +1,4 @@
-use quest::{ask, error, success, text};
+use quest::{ask, error, password, success, text};
 use secstr::SecUtf8;
 use structopt::StructOpt;
 
@@ -7,7 +7,7 @@ use client_core::types::WalletKind;
 use client_core::{Mnemonic, WalletClient};
 
 use crate::{ask_passphrase, ask_seckey};
-use client_core::service::WalletInfo;
+use client_core::service::{AccountPrivKeys, AccountPubKeys, HDAccountType, WalletInfo};
 use client_core::wallet::WalletRequest;
 use std::fs::File;
 use std::io::Write;
@@ -103,6 +103,60 @@ pub enum WalletCommand {
         )]
         name: String,
     },
+    #[structopt(
+        name = "restore-watch-only",
+        about = "Restore watch-only HD Wallet from account public keys"
+    )]
+    RestoreWatchOnly {
+        #[structopt(
+            name = "wallet name",
+            short = "n",
+            long = "name",
+            help = "Name of wallet"
+        )]
+        name: String,
+    },
+    #[structopt(
+        name = "restore-xprv",
+        about = "Restore HD Wallet from account extended private keys"
+    )]
+    RestoreXprv {
+        #[structopt(
+            name = "wallet name",
+            short = "n",
+            long = "name",
+            help = "Name of wallet"
+        )]
+        name: String,
+    },
+    #[structopt(
+        name = "export-xpub",
+        about = "Export account extended public key (or private key) of HD Wallet"
+    )]
+    ExportXpub {
+        #[structopt(
+            name = "wallet name",
+            short = "n",
+            long = "name",
+            help = "Name of wallet"
+        )]
+        name: String,
+        #[structopt(
+            name = "account",
+            short = "a",
+            long = "account",
+            default_value = "transfer",
+            help = "Account of the key: transfer, staking or viewkey"
+        )]
+        account: HDAccountType,
+        #[structopt(
+            name = "private",
+            short = "p",
+            long = "private",
+            help = "Export extended private key (xprv) instead of extended public key (xpub)"
+        )]
+        private: bool,
+    },
     #[structopt(name = "auth-token", about = "Get authentication token")]
     AuthToken {
         #[structopt(
@@ -136,6 +190,15 @@ impl WalletCommand {
             WalletCommand::List => Self::list_wallets(wallet_client),
             WalletCommand::Restore { name } => Self::restore_wallet(wallet_client, name),
             WalletCommand::RestoreBasic { name } => Self::restore_basic_wallet(wallet_client, name),
+            WalletCommand::RestoreWatchOnly { name } => {
+                Self::restore_watch_only_wallet(wallet_client, name)
+            }
+            WalletCommand::RestoreXprv { name } => Self::restore_xprv_wallet(wallet_client, name),
+            WalletCommand::ExportXpub {
+                name,
+                account,
+                private,
+            } => Self::export_xpub(wallet_client, name, *account, *private),
             WalletCommand::AuthToken { name } => Self::auth_token(wallet_client, name),
             WalletCommand::Delete { name } => Self::delete(wallet_client, name),
             WalletCommand::Export {
@@ -343,6 +406,82 @@ impl WalletCommand {
         Ok(())
     }
 
+    fn restore_watch_only_wallet<T: WalletClient>(wallet_client: T, name: &str) -> Result<()> {
+        let passphrase = ask_passphrase(None)?;
+        let confirmed_passphrase = ask_passphrase(Some("Confirm passphrase: "))?;
+
+        if passphrase != confirmed_passphrase {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Passphrases do not match",
+            ));
+        }
+
+        ask("Enter transfer account xpub: ");
+        let transfer = text().chain(|| (ErrorKind::IoError, "Unable to read xpub"))?;
+        ask("Enter staking account xpub (empty to skip staking): ");
+        let staking = text().chain(|| (ErrorKind::IoError, "Unable to read xpub"))?;
+        let staking = Some(staking.trim()).filter(|staking| !staking.is_empty());
+        let account_pubkeys = AccountPubKeys::from_xpub(&transfer, staking)?;
+        let private_view_key = ask_private_view_key()?;
+
+        let enckey = wallet_client.restore_watch_only_wallet(
+            name,
+            &passphrase,
+            &account_pubkeys,
+            &private_view_key,
+        )?;
+
+        success(&format!(
+            "Authentication token: {}",
+            &hex::encode(enckey.unsecure())
+        ));
+        Ok(())
+    }
+
+    fn restore_xprv_wallet<T: WalletClient>(wallet_client: T, name: &str) -> Result<()> {
+        let passphrase = ask_passphrase(None)?;
+        let confirmed_passphrase = ask_passphrase(Some("Confirm passphrase: "))?;
+
+        if passphrase != confirmed_passphrase {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Passphrases do not match",
+            ));
+        }
+
+        let account_privkeys = AccountPrivKeys::from_xprv(
+            &ask_xprv("Enter transfer account xprv: ")?,
+            &ask_xprv("Enter staking account xprv: ")?,
+            &ask_xprv("Enter viewkey account xprv: ")?,
+        )?;
+
+        let enckey = wallet_client.restore_wallet_from_account_privkeys(
+            name,
+            &passphrase,
+            &account_privkeys,
+        )?;
+
+        success(&format!(
+            "Authentication token: {}",
+            &hex::encode(enckey.unsecure())
+        ));
+        Ok(())
+    }
+
+    fn export_xpub<T: WalletClient>(
+        wallet_client: T,
+        name: &str,
+        account: HDAccountType,
+        private: bool,
+    ) -> Result<()> {
+        let enckey = ask_seckey(None)?;
+        let extended_key = wallet_client.export_extended_key(name, &enckey, account, private)?;
+
+        success(extended_key.unsecure());
+        Ok(())
+    }
+
     fn list_wallets<T: WalletClient>(wallet_client: T) -> Result<()> {
         let wallets = wallet_client.wallets()?;
 
@@ -397,3 +536,10 @@ fn ask_private_view_key() -> Result<PrivateKey> {
         Ok(view_key)
     }
 }
+
+fn ask_xprv(message: &str) -> Result<SecUtf8> {
+    ask(message);
+    password()
+        .map(SecUtf8::from)
+        .chain(|| (ErrorKind::IoError, "Unable to read xprv"))
+}
@@ -56,6 +56,11 @@ pub(crate) fn tendermint_url() -> String {
         .unwrap_or_else(|_| "ws://localhost:26657/websocket".to_owned())
 }
 
+#[inline]
+pub(crate) fn hw_signer() -> Option<String> {
+    std::env::var("CRYPTO_CLIENT_HW_SIGNER").ok()
+}
+
 #[inline]
 pub(crate) fn chain_id() -> Option<String> {
     std::env::var("CRYPTO_CHAIN_ID").map(Some).unwrap_or(None)
@@ -33,7 +33,7 @@ use crate::tendermint::WebsocketRpcClient;
 type AppTransactionObfuscation = MockAbciTransactionObfuscation<WebsocketRpcClient>;
 use chain_core::init::coin::CoinError;
 use chain_core::tx::data::TxId;
-use chain_core::tx::fee::{Fee, FeeAlgorithm, LinearFee};
+use chain_core::tx::fee::{BlockFullness, CurrentFeePolicy, Fee, FeeAlgorithm, FeePolicy};
 use chain_core::tx::TxAux;
 use futures_util::sink::SinkExt;
 use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
@@ -49,15 +49,27 @@ pub struct SyncRpcClient {
     /// ASYNC RPC CLIENT
     pub async_rpc_client: Arc<Mutex<Option<AsyncRpcClient>>>,
     url: String,
+    /// the fee policy fetched for one transaction build (see `FeeAlgorithm::for_build`)
+    build_fee_policy: Option<CurrentFeePolicy>,
 }
 
 impl FeeAlgorithm for SyncRpcClient {
     fn calculate_fee(&self, num_bytes: usize) -> std::result::Result<Fee, CoinError> {
-        self.get_fee_policy().calculate_fee(num_bytes)
+        self.build_fee_policy
+            .unwrap_or_else(|| self.get_fee_policy())
+            .calculate_fee(num_bytes)
     }
 
     fn calculate_for_txaux(&self, txaux: &TxAux) -> std::result::Result<Fee, CoinError> {
-        self.get_fee_policy().calculate_for_txaux(txaux)
+        self.build_fee_policy
+            .unwrap_or_else(|| self.get_fee_policy())
+            .calculate_for_txaux(txaux)
+    }
+
+    fn for_build(&self) -> Self {
+        let mut client = self.clone();
+        client.build_fee_policy = Some(self.get_fee_policy());
+        client
     }
 }
 
@@ -91,12 +103,27 @@ impl SyncRpcClient {
             runtime: Arc::new(Mutex::new(runtime)),
             async_rpc_client: Arc::new(Mutex::new(None)),
             url: url.to_string(),
+            build_fee_policy: None,
         })
     }
 
-    /// get the fee policy
-    pub fn get_fee_policy(&self) -> LinearFee {
-        static POLICY: OnceCell<LinearFee> = OnceCell::new();
+    /// get the current fee policy and the last committed block fullness
+    /// (falls back to the genesis fee policy if the latest chain state isn't available)
+    pub fn get_fee_policy(&self) -> CurrentFeePolicy {
+        match self.get_latest_chain_state() {
+            Ok(state) => {
+                CurrentFeePolicy::new(state.network_params.get_fee_policy(), state.block_fullness)
+            }
+            Err(e) => {
+                log::debug!("get latest chain state failed: {:?}", e);
+                CurrentFeePolicy::new(self.get_genesis_fee_policy(), BlockFullness::default())
+            }
+        }
+    }
+
+    /// get the fee policy specified in genesis
+    pub fn get_genesis_fee_policy(&self) -> FeePolicy {
+        static POLICY: OnceCell<FeePolicy> = OnceCell::new();
         let policy = POLICY.get_or_init(|| {
             self.genesis()
                 .map_err(|e| log::error!("get genesis failed: {:?}", e))
@@ -106,6 +133,13 @@ impl SyncRpcClient {
         *policy
     }
 
+    fn get_latest_chain_state(&self) -> Result<ChainState> {
+        let height = self.status()?.sync_info.latest_block_height.value();
+        self.query_state_batch(std::iter::once(height))?
+            .pop()
+            .err_kind(ErrorKind::InvalidInput, || "chain state not found")
+    }
+
     /// get the obfuscation from tx query
     pub fn get_tx_query(
         &self,
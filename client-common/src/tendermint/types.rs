@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use crate::{ErrorKind, Result, ResultExt, Transaction};
 use chain_core::init::config::InitConfig;
 use chain_core::tx::data::TxId;
-use chain_core::tx::fee::LinearFee;
+use chain_core::tx::fee::FeePolicy;
 use chain_core::tx::{TxAux, TxEnclaveAux, TxPublicAux};
 
 pub use self::block_results::BlockResults;
@@ -97,11 +97,11 @@ impl BlockExt for Block {
 /// crypto-chain specific methods.
 pub trait GenesisExt {
     /// get fee policy
-    fn fee_policy(&self) -> LinearFee;
+    fn fee_policy(&self) -> FeePolicy;
 }
 
 impl GenesisExt for Genesis {
-    fn fee_policy(&self) -> LinearFee {
+    fn fee_policy(&self) -> FeePolicy {
         self.app_state
             .as_ref()
             .expect("parsed app state")
@@ -5,7 +5,7 @@ use super::{ErrorKind, Result, ResultExt};
 use chain_core::state::account::{
     DepositBondTx, StakedStateOpWitness, UnbondTx, UnjailTx, WithdrawUnbondedTx,
 };
-use chain_core::state::validator::NodeJoinRequestTx;
+use chain_core::state::validator::{NodeJoinRequestTx, NodeMetadataUpdateTx};
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
 use chain_core::tx::data::{Tx, TxId};
@@ -70,6 +70,8 @@ pub enum Transaction {
     UnjailTransaction(UnjailTx),
     /// Node join transaction
     NodejoinTransaction(NodeJoinRequestTx),
+    /// Node metadata update transaction
+    NodeMetadataUpdateTransaction(NodeMetadataUpdateTx),
 }
 
 impl Transaction {
@@ -81,7 +83,8 @@ impl Transaction {
             Transaction::UnbondStakeTransaction(_)
             | Transaction::WithdrawUnbondedStakeTransaction(_)
             | Transaction::UnjailTransaction(_)
-            | Transaction::NodejoinTransaction(_) => &[],
+            | Transaction::NodejoinTransaction(_)
+            | Transaction::NodeMetadataUpdateTransaction(_) => &[],
         }
     }
 
@@ -93,7 +96,8 @@ impl Transaction {
             Transaction::UnbondStakeTransaction(_)
             | Transaction::DepositStakeTransaction(_)
             | Transaction::UnjailTransaction(_)
-            | Transaction::NodejoinTransaction(_) => &[],
+            | Transaction::NodejoinTransaction(_)
+            | Transaction::NodeMetadataUpdateTransaction(_) => &[],
         }
     }
 }
@@ -107,6 +111,7 @@ impl TransactionId for Transaction {
             Transaction::WithdrawUnbondedStakeTransaction(ref transaction) => transaction.id(),
             Transaction::UnjailTransaction(ref transaction) => transaction.id(),
             Transaction::NodejoinTransaction(ref transaction) => transaction.id(),
+            Transaction::NodeMetadataUpdateTransaction(ref transaction) => transaction.id(),
         }
     }
 }
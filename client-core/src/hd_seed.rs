@@ -5,7 +5,8 @@ use chain_core::init::network::{get_bip44_coin_type_from_network, Network};
 use client_common::{ErrorKind, PrivateKey, PublicKey, Result, ResultExt};
 
 use crate::hd_wallet::{
-    ChainPath, DefaultKeyChain, ExtendedPrivKey, ExtendedPubKey, KeyChain, KeyIndex,
+    Bip32Key, ChainPath, DefaultKeyChain, Derivation, ExtendedPrivKey, ExtendedPubKey, KeyChain,
+    KeyIndex,
 };
 use crate::Mnemonic;
 
@@ -98,6 +99,42 @@ impl HDSeed {
         Ok(parent_pubkey)
     }
 
+    /// get the account-level extended pubkey (`m/44'/coin_type'/account'`) for watch-only wallets
+    pub fn get_account_pubkey(
+        &self,
+        network: Network,
+        account_index: u32,
+    ) -> Result<ExtendedPubKey> {
+        let (account_key, _) = self.derive_account_key(network, account_index)?;
+        Ok(ExtendedPubKey::from_private_key(&account_key))
+    }
+
+    /// get the account-level extended private key (`m/44'/coin_type'/account'`) to serialize
+    pub fn get_account_bip32_key(&self, network: Network, account_index: u32) -> Result<Bip32Key> {
+        let (account_key, derivation) = self.derive_account_key(network, account_index)?;
+        Ok(Bip32Key::from_derivation(network, account_key, &derivation))
+    }
+
+    fn derive_account_key(
+        &self,
+        network: Network,
+        account_index: u32,
+    ) -> Result<(ExtendedPrivKey, Derivation)> {
+        let coin_type = get_bip44_coin_type_from_network(network);
+        let chain_path = ChainPath::from(format!("m/44'/{}'/{}'", coin_type, account_index));
+        let key_chain = DefaultKeyChain::new(
+            ExtendedPrivKey::with_seed(&self.bytes)
+                .chain(|| (ErrorKind::InternalError, "Invalid seed bytes"))?,
+        );
+
+        key_chain.derive_private_key(chain_path).chain(|| {
+            (
+                ErrorKind::InternalError,
+                "Failed to derive HD wallet private key",
+            )
+        })
+    }
+
     /// Get publickey from parent extended publickey on specific index
     pub fn get_pubkey_from_parent_pubkey(
         parent_pubkey: &ExtendedPubKey,
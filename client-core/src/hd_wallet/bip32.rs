@@ -0,0 +1,307 @@
+//! # BIP32 serialization of extended keys
+//!
+//! `Base58Check(version || depth || parent fingerprint || child number || chain code || key)`
+//!
+//! The version prefix is specific to this chain (so that the keys can't be mistaken for
+//! Bitcoin ones) and depends on the network: `cprv`/`cpub` on mainnet and `tcrv`/`tcub`
+//! on testnet and devnet. The rest of the layout is the same as in BIP32.
+
+use base58::{FromBase58, ToBase58};
+use chain_core::init::network::Network;
+use ring::digest;
+use ripemd160::{Digest, Ripemd160};
+use secp256k1::{PublicKey, SecretKey};
+
+use crate::hd_wallet::{error::Error, Derivation, ExtendedPrivKey, ExtendedPubKey};
+
+/// Length of the serialized extended key (without the checksum)
+const SERIALIZED_LEN: usize = 78;
+/// Length of the checksum appended to the serialized extended key
+const CHECKSUM_LEN: usize = 4;
+
+/// Version prefixes (the encoded keys start with `cprv`, `cpub`, `tcrv` and `tcub`)
+const MAINNET_PRIVATE: [u8; 4] = [0x02, 0xe8, 0xda, 0x56];
+const MAINNET_PUBLIC: [u8; 4] = [0x02, 0xe8, 0xde, 0x91];
+const TESTNET_PRIVATE: [u8; 4] = [0x04, 0x31, 0x36, 0x5c];
+const TESTNET_PUBLIC: [u8; 4] = [0x04, 0x31, 0x3a, 0x97];
+
+/// Extended key to serialize
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtendedKey {
+    /// extended private key (`cprv`)
+    Private(ExtendedPrivKey),
+    /// extended public key (`cpub`)
+    Public(ExtendedPubKey),
+}
+
+/// Extended key with its position in the key tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bip32Key {
+    /// network of the version prefix
+    pub network: Network,
+    /// depth, 0 if it is master key
+    pub depth: u8,
+    /// first 4 bytes of the hash160 of the parent public key
+    pub parent_fingerprint: [u8; 4],
+    /// raw index used with the parent key to derive this key
+    pub child_number: u32,
+    /// the extended key
+    pub key: ExtendedKey,
+}
+
+impl Bip32Key {
+    /// Creates the serializable private key from the result of a key chain derivation
+    pub fn from_derivation(
+        network: Network,
+        key: ExtendedPrivKey,
+        derivation: &Derivation,
+    ) -> Self {
+        let parent_fingerprint = derivation
+            .parent_key
+            .as_ref()
+            .map(|parent_key| fingerprint(&ExtendedPubKey::from_private_key(parent_key).public_key))
+            .unwrap_or_default();
+        Bip32Key {
+            network,
+            depth: derivation.depth,
+            parent_fingerprint,
+            child_number: derivation.key_index.map_or(0, |index| index.raw_index()),
+            key: ExtendedKey::Private(key),
+        }
+    }
+
+    /// Returns the public counterpart of the key (`cprv` -> `cpub`)
+    pub fn neuter(&self) -> Self {
+        match self.key {
+            ExtendedKey::Private(ref key) => Bip32Key {
+                key: ExtendedKey::Public(ExtendedPubKey::from_private_key(key)),
+                ..self.clone()
+            },
+            ExtendedKey::Public(_) => self.clone(),
+        }
+    }
+
+    /// Base58Check encoding of the key
+    pub fn to_base58(&self) -> String {
+        self.to_base58_with_version(version(self.network, self.is_private()))
+    }
+
+    fn to_base58_with_version(&self, version: [u8; 4]) -> String {
+        let mut buf = Vec::with_capacity(SERIALIZED_LEN + CHECKSUM_LEN);
+        buf.extend_from_slice(&version);
+        buf.push(self.depth);
+        buf.extend_from_slice(&self.parent_fingerprint);
+        buf.extend_from_slice(&self.child_number.to_be_bytes());
+        match self.key {
+            ExtendedKey::Private(ref key) => {
+                buf.extend_from_slice(&key.chain_code);
+                buf.push(0);
+                buf.extend_from_slice(&key.private_key[..]);
+            }
+            ExtendedKey::Public(ref key) => {
+                buf.extend_from_slice(&key.chain_code);
+                buf.extend_from_slice(&key.public_key.serialize());
+            }
+        }
+        let checksum = checksum(&buf);
+        buf.extend_from_slice(&checksum);
+        buf.to_base58()
+    }
+
+    /// Decodes a Base58Check encoded key, its version prefix has to match the network
+    pub fn from_base58(encoded: &str, network: Network) -> Result<Self, Error> {
+        Self::from_base58_with_versions(
+            encoded,
+            network,
+            version(network, true),
+            version(network, false),
+        )
+    }
+
+    fn from_base58_with_versions(
+        encoded: &str,
+        network: Network,
+        private_version: [u8; 4],
+        public_version: [u8; 4],
+    ) -> Result<Self, Error> {
+        let buf = encoded
+            .trim()
+            .from_base58()
+            .map_err(|_| Error::InvalidBase58)?;
+        if buf.len() != SERIALIZED_LEN + CHECKSUM_LEN {
+            return Err(Error::InvalidSerializedKey);
+        }
+        let (data, expected_checksum) = buf.split_at(SERIALIZED_LEN);
+        if checksum(data)[..] != expected_checksum[..] {
+            return Err(Error::InvalidChecksum);
+        }
+
+        let private = if data[0..4] == private_version {
+            true
+        } else if data[0..4] == public_version {
+            false
+        } else {
+            return Err(Error::InvalidVersion);
+        };
+        let depth = data[4];
+        let mut parent_fingerprint = [0; 4];
+        parent_fingerprint.copy_from_slice(&data[5..9]);
+        let mut child_number = [0; 4];
+        child_number.copy_from_slice(&data[9..13]);
+        let chain_code = data[13..45].to_vec();
+        let key = if private {
+            if data[45] != 0 {
+                return Err(Error::InvalidSerializedKey);
+            }
+            ExtendedKey::Private(ExtendedPrivKey {
+                private_key: SecretKey::from_slice(&data[46..])?,
+                chain_code,
+            })
+        } else {
+            ExtendedKey::Public(ExtendedPubKey {
+                public_key: PublicKey::from_slice(&data[45..])?,
+                chain_code,
+            })
+        };
+
+        Ok(Bip32Key {
+            network,
+            depth,
+            parent_fingerprint,
+            child_number: u32::from_be_bytes(child_number),
+            key,
+        })
+    }
+
+    /// Returns true if it is an extended private key
+    #[inline]
+    pub fn is_private(&self) -> bool {
+        match self.key {
+            ExtendedKey::Private(_) => true,
+            ExtendedKey::Public(_) => false,
+        }
+    }
+}
+
+fn version(network: Network, private: bool) -> [u8; 4] {
+    match (network, private) {
+        (Network::Mainnet, true) => MAINNET_PRIVATE,
+        (Network::Mainnet, false) => MAINNET_PUBLIC,
+        (_, true) => TESTNET_PRIVATE,
+        (_, false) => TESTNET_PUBLIC,
+    }
+}
+
+fn fingerprint(public_key: &PublicKey) -> [u8; 4] {
+    let sha256 = digest::digest(&digest::SHA256, &public_key.serialize());
+    let mut hasher = Ripemd160::new();
+    hasher.update(sha256.as_ref());
+    let mut fingerprint = [0; 4];
+    fingerprint.copy_from_slice(&hasher.finalize()[..4]);
+    fingerprint
+}
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let hash = digest::digest(&digest::SHA256, data);
+    let hash = digest::digest(&digest::SHA256, hash.as_ref());
+    let mut checksum = [0; CHECKSUM_LEN];
+    checksum.copy_from_slice(&hash.as_ref()[..CHECKSUM_LEN]);
+    checksum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hd_wallet::{ChainPath, DefaultKeyChain, KeyChain};
+
+    // test vector 1 of BIP32
+    const SEED: &str = "000102030405060708090a0b0c0d0e0f";
+    // the version prefixes of the test vectors (Bitcoin mainnet)
+    const BIP32_PRIVATE: [u8; 4] = [0x04, 0x88, 0xad, 0xe4];
+    const BIP32_PUBLIC: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+
+    fn derive(chain_path: &str) -> Bip32Key {
+        let key_chain = DefaultKeyChain::new(
+            ExtendedPrivKey::with_seed(&hex::decode(SEED).unwrap()).expect("master key"),
+        );
+        let (key, derivation) = key_chain
+            .derive_private_key(ChainPath::from(chain_path))
+            .expect("derive key");
+        Bip32Key::from_derivation(Network::Mainnet, key, &derivation)
+    }
+
+    #[test]
+    fn check_serialization_layout() {
+        for (chain_path, xprv, xpub) in &[
+            ("m", "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi", "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"),
+            ("m/0H/1/2H", "xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM", "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5"),
+        ] {
+            let key = derive(chain_path);
+            assert_eq!(&key.to_base58_with_version(BIP32_PRIVATE), xprv);
+            assert_eq!(&key.neuter().to_base58_with_version(BIP32_PUBLIC), xpub);
+
+            let decode = |encoded: &str| {
+                Bip32Key::from_base58_with_versions(
+                    encoded,
+                    Network::Mainnet,
+                    BIP32_PRIVATE,
+                    BIP32_PUBLIC,
+                )
+            };
+            assert_eq!(decode(xprv).unwrap(), key);
+            assert_eq!(decode(xpub).unwrap(), key.neuter());
+            // the Bitcoin keys aren't accepted
+            assert_eq!(
+                Bip32Key::from_base58(xprv, Network::Mainnet),
+                Err(Error::InvalidVersion)
+            );
+        }
+    }
+
+    #[test]
+    fn check_version_prefix() {
+        let mut key = derive("m/0H/1");
+        let cprv = key.to_base58();
+        let cpub = key.neuter().to_base58();
+        assert!(cprv.starts_with("cprv"));
+        assert!(cpub.starts_with("cpub"));
+        assert_eq!(Bip32Key::from_base58(&cprv, Network::Mainnet).unwrap(), key);
+        assert_eq!(
+            Bip32Key::from_base58(&cpub, Network::Mainnet).unwrap(),
+            key.neuter()
+        );
+
+        key.network = Network::Testnet;
+        let tcrv = key.to_base58();
+        let tcub = key.neuter().to_base58();
+        assert!(tcrv.starts_with("tcrv"));
+        assert!(tcub.starts_with("tcub"));
+        assert_eq!(
+            Bip32Key::from_base58(&tcrv, Network::Devnet).unwrap().key,
+            key.key
+        );
+        assert_eq!(
+            Bip32Key::from_base58(&tcub, Network::Mainnet),
+            Err(Error::InvalidVersion)
+        );
+    }
+
+    #[test]
+    fn check_invalid_serialization() {
+        let xpub = derive("m").neuter().to_base58();
+        let mut corrupted = xpub.clone().into_bytes();
+        corrupted[20] = if corrupted[20] == b'a' { b'b' } else { b'a' };
+        assert_eq!(
+            Bip32Key::from_base58(&String::from_utf8(corrupted).unwrap(), Network::Mainnet),
+            Err(Error::InvalidChecksum)
+        );
+        assert_eq!(
+            Bip32Key::from_base58(&xpub[..100], Network::Mainnet),
+            Err(Error::InvalidSerializedKey)
+        );
+        assert_eq!(
+            Bip32Key::from_base58("xpub0OIl", Network::Mainnet),
+            Err(Error::InvalidBase58)
+        );
+    }
+}
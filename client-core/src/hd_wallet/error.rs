@@ -16,6 +16,14 @@ pub enum Error {
     ChainPath(ChainPathError),
     /// secp256k1 errors
     Secp(secp256k1::Error),
+    /// serialized extended key is not valid base58
+    InvalidBase58,
+    /// checksum of the serialized extended key doesn't match
+    InvalidChecksum,
+    /// version prefix of the serialized extended key doesn't match the network
+    InvalidVersion,
+    /// serialized extended key has invalid length or key data
+    InvalidSerializedKey,
 }
 
 impl std::error::Error for Error {}
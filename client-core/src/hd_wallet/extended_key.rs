@@ -176,6 +176,9 @@ impl Serialize<Vec<u8>> for ExtendedPrivKey {
 }
 impl Deserialize<&[u8], Error> for ExtendedPrivKey {
     fn deserialize(data: &[u8]) -> Result<Self, Error> {
+        if data.len() != 32 + 32 {
+            return Err(Error::Secp(secp256k1::Error::InvalidSecretKey));
+        }
         let private_key = SecretKey::from_slice(&data[..32])?;
         let chain_code = data[32..].to_vec();
         Ok(ExtendedPrivKey {
@@ -194,6 +197,9 @@ impl Serialize<Vec<u8>> for ExtendedPubKey {
 }
 impl Deserialize<&[u8], Error> for ExtendedPubKey {
     fn deserialize(data: &[u8]) -> Result<Self, Error> {
+        if data.len() != 33 + 32 {
+            return Err(Error::Secp(secp256k1::Error::InvalidPublicKey));
+        }
         let public_key = PublicKey::from_slice(&data[..33])?;
         let chain_code = data[33..].to_vec();
         Ok(ExtendedPubKey {
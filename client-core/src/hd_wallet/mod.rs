@@ -3,6 +3,8 @@
 //! Copyright (c) 2018 - 2020, Jiang Jinyang (licensed under the MIT License)
 //! Modifications Copyright (c) 2018 - 2020, Foris Limited (licensed under the Apache License, Version 2.0)
 //!
+/// BIP32 serialization of extended keys
+pub mod bip32;
 /// error code for hdwallet
 pub mod error;
 /// entended key for hdwallet
@@ -16,6 +18,8 @@ mod wallet_kind;
 
 pub use wallet_kind::HardwareKind;
 
+pub use crate::hd_wallet::bip32::{Bip32Key, ExtendedKey};
+
 pub use crate::hd_wallet::extended_key::{
     key_index::KeyIndex, ExtendedPrivKey, ExtendedPubKey, KeySeed,
 };
@@ -1,8 +1,18 @@
 //! Input selection operations
-use crate::unspent_transactions::{Operation, Sorter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::input::TxoPointer;
+use chain_core::tx::data::output::TxOut;
+use client_common::{Error, ErrorKind, Result};
+
+use crate::unspent_transactions::{Operation, Selection, Selector, Sorter, UnspentTransactions};
 
 /// Different strategies for input selection
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum InputSelectionStrategy {
     /// Selects unspent transactions with highest value first
     HighestValueFirst,
@@ -10,6 +20,15 @@ pub enum InputSelectionStrategy {
     LowestValueFirst,
     /// Selects unspent transactions randomly
     Random,
+    /// Selects unspent transactions matching the amount exactly (without change output), or
+    /// with the least change if there is no exact match
+    BranchAndBound,
+    /// Selects all unspent transactions of the fewest addresses, so that outputs received on
+    /// different addresses are not merged if possible
+    Privacy,
+    /// Spends all unspent transactions whose value is not more than the threshold, and selects the
+    /// rest with highest value first
+    ConsolidateDust(Coin),
 }
 
 impl Default for InputSelectionStrategy {
@@ -22,13 +41,219 @@ impl Default for InputSelectionStrategy {
 impl AsRef<[Operation]> for InputSelectionStrategy {
     fn as_ref(&self) -> &[Operation] {
         match self {
-            InputSelectionStrategy::HighestValueFirst => {
+            InputSelectionStrategy::HighestValueFirst
+            | InputSelectionStrategy::BranchAndBound
+            | InputSelectionStrategy::ConsolidateDust(_) => {
                 &[Operation::Sort(Sorter::HighestValueFirst)]
             }
             InputSelectionStrategy::LowestValueFirst => {
                 &[Operation::Sort(Sorter::LowestValueFirst)]
             }
-            InputSelectionStrategy::Random => &[],
+            InputSelectionStrategy::Random | InputSelectionStrategy::Privacy => &[],
+        }
+    }
+}
+
+impl FromStr for InputSelectionStrategy {
+    type Err = Error;
+
+    /// Parses the strategies without parameter
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "highest-value-first" => Ok(InputSelectionStrategy::HighestValueFirst),
+            "lowest-value-first" => Ok(InputSelectionStrategy::LowestValueFirst),
+            "random" => Ok(InputSelectionStrategy::Random),
+            "branch-and-bound" => Ok(InputSelectionStrategy::BranchAndBound),
+            "privacy" => Ok(InputSelectionStrategy::Privacy),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown input selection strategy: {}", s),
+            )),
+        }
+    }
+}
+
+impl InputSelectionStrategy {
+    fn selector(self) -> Selector {
+        match self {
+            InputSelectionStrategy::BranchAndBound => Selector::BranchAndBound,
+            InputSelectionStrategy::Privacy => Selector::SameAddress,
+            _ => Selector::Prefix,
+        }
+    }
+}
+
+/// Input selection of a transaction: strategy and coin control
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSelection {
+    /// Strategy to select the unspent transactions
+    pub strategy: InputSelectionStrategy,
+    /// Unspent transactions which are always spent
+    pub include: Vec<TxoPointer>,
+    /// Unspent transactions which are never spent
+    pub exclude: Vec<TxoPointer>,
+}
+
+impl From<InputSelectionStrategy> for InputSelection {
+    #[inline]
+    fn from(strategy: InputSelectionStrategy) -> Self {
+        InputSelection {
+            strategy,
+            ..Default::default()
+        }
+    }
+}
+
+impl InputSelection {
+    /// Removes excluded unspent transactions, orders the rest with the strategy and moves the
+    /// ones which are always spent to the beginning. Returns the rules to select them.
+    pub fn apply(&self, unspent_transactions: &mut UnspentTransactions) -> Result<Selection> {
+        if let Some(input) = self
+            .include
+            .iter()
+            .find(|input| self.exclude.contains(input))
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Input is both included and excluded: {}", input),
+            ));
+        }
+        if let Some(input) = self.include.iter().find(|input| {
+            !unspent_transactions
+                .iter()
+                .any(|(pointer, _)| pointer == *input)
+        }) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Included input is not an unspent transaction: {}", input),
+            ));
         }
+
+        unspent_transactions.retain(|(pointer, _)| !self.exclude.contains(pointer));
+        unspent_transactions.apply_all(self.strategy.as_ref());
+
+        let is_required = |(pointer, output): &(TxoPointer, TxOut)| match self.strategy {
+            InputSelectionStrategy::ConsolidateDust(threshold) if output.value <= threshold => true,
+            _ => self.include.contains(pointer),
+        };
+        let (mut required, rest): (Vec<_>, Vec<_>) =
+            unspent_transactions.drain(..).partition(is_required);
+        let required_len = required.len();
+        required.extend(rest);
+        **unspent_transactions = required;
+
+        Ok(Selection {
+            required: required_len,
+            selector: self.strategy.selector(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod input_selection_tests {
+    use super::*;
+
+    use chain_core::tx::data::address::ExtendedAddr;
+
+    fn unspent(id: u8, address: u8, value: u64) -> (TxoPointer, TxOut) {
+        (
+            TxoPointer::new([id; 32], 0),
+            TxOut::new(
+                ExtendedAddr::OrTree([address; 32]),
+                Coin::new(value).unwrap(),
+            ),
+        )
+    }
+
+    fn sample() -> UnspentTransactions {
+        UnspentTransactions::new(vec![
+            unspent(1, 1, 500),
+            unspent(2, 2, 300),
+            unspent(3, 1, 200),
+            unspent(4, 3, 120),
+            unspent(5, 2, 5),
+            unspent(6, 3, 3),
+        ])
+    }
+
+    fn select(input_selection: &InputSelection, amount: u64) -> Result<(Vec<TxoPointer>, Coin)> {
+        let mut unspent_transactions = sample();
+        let selection = input_selection.apply(&mut unspent_transactions)?;
+        let (selected, change) =
+            unspent_transactions.select_with(Coin::new(amount).unwrap(), selection)?;
+        let mut pointers = selected
+            .iter()
+            .map(|(pointer, _)| pointer.clone())
+            .collect::<Vec<_>>();
+        pointers.sort();
+        Ok((pointers, change))
+    }
+
+    fn pointers(ids: &[u8]) -> Vec<TxoPointer> {
+        ids.iter().map(|id| TxoPointer::new([*id; 32], 0)).collect()
+    }
+
+    #[test]
+    fn check_coin_control() {
+        let input_selection = InputSelection {
+            strategy: InputSelectionStrategy::HighestValueFirst,
+            include: pointers(&[6]),
+            exclude: pointers(&[1]),
+        };
+        let (selected, change) = select(&input_selection, 400).unwrap();
+        assert_eq!(selected, pointers(&[2, 3, 6]));
+        assert_eq!(change, Coin::new(103).unwrap());
+
+        let input_selection = InputSelection {
+            exclude: pointers(&[1, 2, 3]),
+            ..Default::default()
+        };
+        assert!(select(&input_selection, 400).is_err());
+
+        let input_selection = InputSelection {
+            include: pointers(&[7]),
+            ..Default::default()
+        };
+        assert!(select(&input_selection, 100).is_err());
+    }
+
+    #[test]
+    fn check_branch_and_bound() {
+        let input_selection = InputSelection::from(InputSelectionStrategy::BranchAndBound);
+        // exact match
+        let (selected, change) = select(&input_selection, 325).unwrap();
+        assert_eq!(selected, pointers(&[3, 4, 5]));
+        assert_eq!(change, Coin::zero());
+        let (selected, change) = select(&input_selection, 623).unwrap();
+        assert_eq!(selected, pointers(&[1, 4, 6]));
+        assert_eq!(change, Coin::zero());
+        // least change
+        let (selected, change) = select(&input_selection, 190).unwrap();
+        assert_eq!(selected, pointers(&[3]));
+        assert_eq!(change, Coin::new(10).unwrap());
+    }
+
+    #[test]
+    fn check_privacy() {
+        let input_selection = InputSelection::from(InputSelectionStrategy::Privacy);
+        // all outputs of the address with the least change
+        let (selected, _) = select(&input_selection, 250).unwrap();
+        assert_eq!(selected, pointers(&[2, 5]));
+        let (selected, _) = select(&input_selection, 100).unwrap();
+        assert_eq!(selected, pointers(&[4, 6]));
+        // fewest addresses
+        let (selected, _) = select(&input_selection, 1000).unwrap();
+        assert_eq!(selected, pointers(&[1, 2, 3, 5]));
+    }
+
+    #[test]
+    fn check_consolidate_dust() {
+        let input_selection = InputSelection::from(InputSelectionStrategy::ConsolidateDust(
+            Coin::new(10).unwrap(),
+        ));
+        let (selected, change) = select(&input_selection, 100).unwrap();
+        assert_eq!(selected, pointers(&[1, 5, 6]));
+        assert_eq!(change, Coin::new(408).unwrap());
     }
 }
@@ -27,7 +27,7 @@ pub mod wallet;
 #[doc(inline)]
 pub use crate::hd_seed::HDSeed;
 #[doc(inline)]
-pub use crate::input_selection::InputSelectionStrategy;
+pub use crate::input_selection::{InputSelection, InputSelectionStrategy};
 #[doc(inline)]
 pub use crate::mnemonic::Mnemonic;
 #[doc(inline)]
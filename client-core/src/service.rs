@@ -1,4 +1,5 @@
 //! Management services
+mod external_signer;
 mod hd_key_service;
 mod hw_key_service;
 mod key_service;
@@ -14,8 +15,18 @@ mod wallet_state_service;
 #[doc(hidden)]
 pub use self::wallet_state_service::WalletStateMemento;
 
-pub use self::hd_key_service::{HDAccountType, HdKey, HdKeyService};
-pub use self::hw_key_service::{HwKeyService, UnauthorizedHwKeyService};
+#[cfg(unix)]
+pub use self::external_signer::UnixSocketTransport;
+pub use self::external_signer::{
+    decode_message, encode_message, hid_frame, hid_unframe, DisplayData, ExternalSignerKey,
+    ExternalSignerService, HidDevice, HidTransport, ProcessTransport, SignatureScheme,
+    SignerEmulator, SignerRequest, SignerResponse, SignerTransport, HID_REPORT_SIZE,
+    PROTOCOL_VERSION,
+};
+pub use self::hd_key_service::{
+    parse_account_key, AccountPrivKeys, AccountPubKeys, HDAccountType, HdKey, HdKeyService,
+};
+pub use self::hw_key_service::{HardwareWalletAction, HwKeyService, UnauthorizedHwKeyService};
 pub use self::key_service::KeyService;
 #[cfg(feature = "mock-hardware-wallet")]
 pub use self::mock_hw_key_service::{MockHardwareKey, MockHardwareService, MockHardwareWallet};
@@ -0,0 +1,531 @@
+//! Hardware wallet backend delegating the key derivation and signing to an external signer
+//! (a device adapter, a separate program or the reference emulator).
+//!
+//! # Protocol
+//!
+//! The wallet sends one request and waits for one response, each is a line of JSON:
+//!
+//! ```text
+//! > {"method":"get_info"}
+//! < {"result":"info","version":1,"device":"signer emulator"}
+//! > {"method":"get_public_key","params":{"path":"m/44'/394'/0'/0/0","display":false}}
+//! < {"result":"public_key","public_key":"<hex compressed public key>"}
+//! > {"method":"sign","params":{"path":"m/44'/394'/0'/0/0","scheme":"ecdsa",
+//!     "digest":"<hex transaction ID>","transaction":"<hex SCALE-encoded transaction>",
+//!     "display":{"tx_type":"unbond","fields":[["from","0x..."],["amount","100"]]}}}
+//! < {"result":"signature","signature":"<hex signature>"}
+//! < {"result":"rejected","reason":"declined by the user"}
+//! < {"result":"error","message":"..."}
+//! ```
+//!
+//! The signed digest is the transaction ID; the signer should only sign it
+//! after checking it against the transaction and showing the display data to the user.
+//! "ecdsa" signatures are 65 bytes (compact signature followed by the recovery id),
+//! "schnorr" signatures are 64 bytes. The wallet checks the returned signatures
+//! against the public key of the path before using them.
+//!
+//! # Transports
+//!
+//! - `exec:<program> [args]`: the program is started for each request,
+//!   which is written to its stdin; the response is read from its stdout
+//! - `unix:<path>`: a connection to the Unix socket for each request
+//! - `HidTransport`: reports framed for HID devices (over a `HidDevice` binding)
+mod emulator;
+mod protocol;
+mod transport;
+
+pub use self::emulator::SignerEmulator;
+pub use self::protocol::{
+    decode_message, encode_message, DisplayData, SignatureScheme, SignerRequest, SignerResponse,
+    PROTOCOL_VERSION,
+};
+#[cfg(unix)]
+pub use self::transport::UnixSocketTransport;
+pub use self::transport::{
+    hid_frame, hid_unframe, HidDevice, HidTransport, ProcessTransport, SignerTransport,
+    HID_REPORT_SIZE,
+};
+
+use std::fmt;
+use std::sync::Arc;
+
+use secp256k1::key::XOnlyPublicKey;
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use secp256k1::schnorrsig::{schnorr_verify, SchnorrSignature};
+use secp256k1::{Message, PublicKey as SecpPublicKey};
+
+use crate::hd_wallet::ChainPath;
+use crate::service::hw_key_service::HardwareWalletAction;
+use client_common::{
+    Error, ErrorKind, PrivateKeyAction, PublicKey, Result, ResultExt, Transaction, SECP,
+};
+
+/// Hardware wallet service talking to an external signer
+#[derive(Clone)]
+pub struct ExternalSignerService {
+    transport: Arc<dyn SignerTransport>,
+}
+
+impl fmt::Debug for ExternalSignerService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ExternalSignerService")
+    }
+}
+
+impl ExternalSignerService {
+    /// creates a service using the transport
+    pub fn new<T: SignerTransport + 'static>(transport: T) -> Self {
+        Self {
+            transport: Arc::new(transport),
+        }
+    }
+
+    /// creates a service from the transport specification
+    /// (`exec:<program> [args]` or `unix:<path>`)
+    pub fn from_spec(spec: &str) -> Result<Self> {
+        if let Some(command) = spec.strip_prefix("exec:") {
+            let mut words = command.split_whitespace().map(ToOwned::to_owned);
+            let program = words
+                .next()
+                .chain(|| (ErrorKind::InvalidInput, "Missing external signer program"))?;
+            return Ok(Self::new(ProcessTransport::new(program, words.collect())));
+        }
+        #[cfg(unix)]
+        {
+            if let Some(path) = spec.strip_prefix("unix:") {
+                return Ok(Self::new(UnixSocketTransport::new(path.into())));
+            }
+        }
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unsupported external signer transport: {}", spec),
+        ))
+    }
+
+    /// returns the device description (if it supports this protocol version)
+    pub fn info(&self) -> Result<String> {
+        match self.request(&SignerRequest::GetInfo)? {
+            SignerResponse::Info { version, device } if version == PROTOCOL_VERSION => Ok(device),
+            SignerResponse::Info { version, .. } => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "External signer protocol version {} is not supported (expected {})",
+                    version, PROTOCOL_VERSION
+                ),
+            )),
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    /// returns the public key after showing it on the device (for verifying addresses)
+    pub fn display_public_key(&self, chain_path: ChainPath) -> Result<PublicKey> {
+        self.public_key(chain_path.into_string(), true)
+    }
+
+    fn public_key(&self, path: String, display: bool) -> Result<PublicKey> {
+        match self.request(&SignerRequest::GetPublicKey { path, display })? {
+            SignerResponse::PublicKey { public_key } => {
+                let public_key = hex::decode(public_key)
+                    .chain(|| (ErrorKind::DeserializationError, "Invalid public key"))?;
+                PublicKey::deserialize_from(&public_key)
+            }
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    fn signature(&self, path: &str, scheme: SignatureScheme, tx: &Transaction) -> Result<Vec<u8>> {
+        match self.request(&SignerRequest::sign(path.to_owned(), scheme, tx))? {
+            SignerResponse::Signature { signature } => hex::decode(signature)
+                .chain(|| (ErrorKind::DeserializationError, "Invalid signature")),
+            _ => Err(unexpected_response()),
+        }
+    }
+
+    fn request(&self, request: &SignerRequest) -> Result<SignerResponse> {
+        let response = self.transport.exchange(&encode_message(request)?)?;
+        match decode_message(&response)? {
+            SignerResponse::Rejected { reason } => Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Rejected by the external signer: {}", reason),
+            )),
+            SignerResponse::Error { message } => Err(Error::new(
+                ErrorKind::InternalError,
+                format!("External signer error: {}", message),
+            )),
+            response => Ok(response),
+        }
+    }
+}
+
+fn tx_message(tx: &Transaction) -> Result<Message> {
+    Message::from_slice(&tx.id()).chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            "Unable to deserialize message to sign",
+        )
+    })
+}
+
+/// the signature returned by the signer is checked as it may not be made with the key at the path
+fn wrong_signature() -> Error {
+    Error::new(
+        ErrorKind::VerifyError,
+        "External signer returned a signature not made with the key of the path",
+    )
+}
+
+fn unexpected_response() -> Error {
+    Error::new(
+        ErrorKind::DeserializationError,
+        "Unexpected external signer response",
+    )
+}
+
+impl HardwareWalletAction for ExternalSignerService {
+    fn get_public_key(&self, chain_path: ChainPath) -> Result<PublicKey> {
+        self.public_key(chain_path.into_string(), false)
+    }
+
+    fn get_sign_key(&self, hd_path: &ChainPath) -> Result<Box<dyn PrivateKeyAction>> {
+        Ok(Box::new(ExternalSignerKey {
+            service: self.clone(),
+            path: hd_path.clone().into_string(),
+        }))
+    }
+}
+
+/// Key held by the external signer
+pub struct ExternalSignerKey {
+    service: ExternalSignerService,
+    path: String,
+}
+
+impl PrivateKeyAction for ExternalSignerKey {
+    fn sign(&self, tx: &Transaction) -> Result<RecoverableSignature> {
+        let signature = self
+            .service
+            .signature(&self.path, SignatureScheme::Ecdsa, tx)?;
+        if signature.len() != 65 {
+            return Err(Error::new(
+                ErrorKind::DeserializationError,
+                "Recoverable signature should be 65 bytes",
+            ));
+        }
+        let recovery_id = RecoveryId::from_i32(i32::from(signature[64]))
+            .chain(|| (ErrorKind::DeserializationError, "Invalid recovery id"))?;
+        let signature = RecoverableSignature::from_compact(&signature[..64], recovery_id)
+            .chain(|| (ErrorKind::DeserializationError, "Invalid signature"))?;
+
+        let message = tx_message(tx)?;
+        let public_key = SecpPublicKey::from(self.public_key()?);
+        let valid = SECP.with(|secp| {
+            secp.recover(&message, &signature)
+                .map(|recovered| recovered == public_key)
+                .unwrap_or(false)
+        });
+        if valid {
+            Ok(signature)
+        } else {
+            Err(wrong_signature())
+        }
+    }
+
+    fn schnorr_sign(&self, tx: &Transaction) -> Result<SchnorrSignature> {
+        let signature = self
+            .service
+            .signature(&self.path, SignatureScheme::Schnorr, tx)?;
+        let signature = SchnorrSignature::from_default(&signature)
+            .chain(|| (ErrorKind::DeserializationError, "Invalid signature"))?;
+
+        let message = tx_message(tx)?;
+        let public_key = XOnlyPublicKey::from_pubkey(&SecpPublicKey::from(self.public_key()?)).0;
+        SECP.with(|secp| schnorr_verify(secp, &message, &signature, &public_key))
+            .map_err(|_| wrong_signature())?;
+        Ok(signature)
+    }
+
+    fn public_key(&self) -> Result<PublicKey> {
+        self.service.public_key(self.path.clone(), false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    use crate::hd_wallet::{DefaultKeyChain, ExtendedPrivKey, KeyChain};
+    use crate::{HDSeed, Mnemonic};
+    use chain_core::init::{address::RedeemAddress, coin::Coin};
+    use chain_core::state::account::{StakedStateAddress, StakedStateOpAttributes, UnbondTx};
+    use chain_core::tx::data::address::ExtendedAddr;
+    use chain_core::tx::data::attribute::TxAttributes;
+    use chain_core::tx::data::output::TxOut;
+    use chain_core::tx::data::Tx;
+    use chain_core::tx::TransactionId;
+    use client_common::{PrivateKey, SECP};
+
+    const PATH: &str = "m/44'/394'/0'/0/0";
+
+    fn mnemonic() -> Mnemonic {
+        let words = "ordinary mandate edit father snack mesh history identify print borrow skate unhappy cattle tiny first".into();
+        Mnemonic::from_secstr(&words).unwrap()
+    }
+
+    fn private_key(path: &str) -> PrivateKey {
+        let key_chain = DefaultKeyChain::new(
+            ExtendedPrivKey::with_seed(&HDSeed::from(&mnemonic()).bytes).unwrap(),
+        );
+        let (extended_private_key, _) =
+            key_chain.derive_private_key(ChainPath::from(path)).unwrap();
+        PrivateKey::from(extended_private_key.private_key)
+    }
+
+    fn transfer_transaction() -> Transaction {
+        Transaction::TransferTransaction(Tx::new_with(
+            Vec::new(),
+            vec![TxOut::new(ExtendedAddr::OrTree([0; 32]), Coin::new(100).unwrap()); 3],
+            TxAttributes::default(),
+        ))
+    }
+
+    fn unbond_transaction() -> Transaction {
+        Transaction::UnbondStakeTransaction(UnbondTx::new(
+            StakedStateAddress::from(
+                RedeemAddress::from_str("0x0e7c045110b8dbf29765047380898919c5cb56f4").unwrap(),
+            ),
+            0,
+            Coin::new(100).unwrap(),
+            StakedStateOpAttributes::new(0),
+        ))
+    }
+
+    /// HID device forwarding the reports to the emulator
+    struct LoopbackHid {
+        emulator: SignerEmulator,
+        channel: u16,
+        written: Vec<Vec<u8>>,
+        pending: VecDeque<Vec<u8>>,
+    }
+
+    impl HidDevice for LoopbackHid {
+        fn write_report(&mut self, report: &[u8]) -> Result<()> {
+            assert_eq!(HID_REPORT_SIZE, report.len());
+            self.written.push(report.to_vec());
+            let mut reports = self.written.iter().cloned();
+            let incomplete = || Error::new(ErrorKind::IoError, "incomplete");
+            if let Ok(request) = hid_unframe(self.channel, || reports.next().ok_or_else(incomplete))
+            {
+                self.written.clear();
+                let response = self.emulator.exchange(&request)?;
+                self.pending
+                    .extend(hid_frame(self.channel, &response)?.into_iter());
+            }
+            Ok(())
+        }
+
+        fn read_report(&mut self) -> Result<Vec<u8>> {
+            self.pending
+                .pop_front()
+                .chain(|| (ErrorKind::IoError, "no pending report"))
+        }
+    }
+
+    fn services() -> Vec<ExternalSignerService> {
+        let hid = LoopbackHid {
+            emulator: SignerEmulator::new(&mnemonic()),
+            channel: 0x0101,
+            written: Vec::new(),
+            pending: VecDeque::new(),
+        };
+        vec![
+            ExternalSignerService::new(SignerEmulator::new(&mnemonic())),
+            ExternalSignerService::new(HidTransport::new(hid, 0x0101)),
+        ]
+    }
+
+    #[test]
+    fn check_get_public_key() {
+        let expected = PublicKey::from(&private_key(PATH));
+        for service in services() {
+            assert_eq!("signer emulator", service.info().unwrap());
+            assert_eq!(
+                expected,
+                service.get_public_key(ChainPath::from(PATH)).unwrap()
+            );
+            assert_eq!(
+                expected,
+                service.display_public_key(ChainPath::from(PATH)).unwrap()
+            );
+            let key = service.get_sign_key(&ChainPath::from(PATH)).unwrap();
+            assert_eq!(expected, key.public_key().unwrap());
+        }
+    }
+
+    #[test]
+    fn check_sign() {
+        let private_key = private_key(PATH);
+        for tx in vec![transfer_transaction(), unbond_transaction()] {
+            let message = Message::from_slice(&tx.id()).unwrap();
+            for service in services() {
+                let key = service.get_sign_key(&ChainPath::from(PATH)).unwrap();
+
+                // deterministic nonce
+                assert_eq!(private_key.sign(&tx).unwrap(), key.sign(&tx).unwrap());
+
+                let signature = key.schnorr_sign(&tx).unwrap();
+                SECP.with(|secp| {
+                    schnorr_verify(
+                        &secp,
+                        &message,
+                        &signature,
+                        &PublicKey::from(&private_key).into(),
+                    )
+                    .expect("Invalid signature");
+                });
+            }
+        }
+    }
+
+    /// signer signing with the key of another path
+    struct WrongKeySigner(SignerEmulator);
+
+    impl SignerTransport for WrongKeySigner {
+        fn exchange(&self, request: &[u8]) -> Result<Vec<u8>> {
+            let request = match decode_message(request)? {
+                SignerRequest::Sign {
+                    scheme,
+                    digest,
+                    transaction,
+                    display,
+                    ..
+                } => SignerRequest::Sign {
+                    path: "m/44'/394'/0'/0/1".to_owned(),
+                    scheme,
+                    digest,
+                    transaction,
+                    display,
+                },
+                request => request,
+            };
+            encode_message(&self.0.handle(request))
+        }
+    }
+
+    #[test]
+    fn check_signature_of_wrong_key() {
+        let service = ExternalSignerService::new(WrongKeySigner(SignerEmulator::new(&mnemonic())));
+        let key = service.get_sign_key(&ChainPath::from(PATH)).unwrap();
+        let tx = transfer_transaction();
+        assert_eq!(ErrorKind::VerifyError, key.sign(&tx).unwrap_err().kind());
+        assert_eq!(
+            ErrorKind::VerifyError,
+            key.schnorr_sign(&tx).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn check_rejected_request() {
+        let mut emulator = SignerEmulator::new(&mnemonic());
+        emulator.set_approve(false);
+        let service = ExternalSignerService::new(emulator);
+        let key = service.get_sign_key(&ChainPath::from(PATH)).unwrap();
+        let error = key.sign(&transfer_transaction()).unwrap_err();
+        assert_eq!(ErrorKind::PermissionDenied, error.kind());
+    }
+
+    #[test]
+    fn check_emulator_checks_request() {
+        let emulator = SignerEmulator::new(&mnemonic());
+        let tx = unbond_transaction();
+
+        let mut request = SignerRequest::sign(PATH.to_owned(), SignatureScheme::Ecdsa, &tx);
+        if let SignerRequest::Sign { display, .. } = &mut request {
+            display.fields[1].1 = "1".to_owned();
+        }
+        match emulator.handle(request) {
+            SignerResponse::Error { .. } => {}
+            response => panic!("unexpected response: {:?}", response),
+        }
+
+        let mut request = SignerRequest::sign(PATH.to_owned(), SignatureScheme::Ecdsa, &tx);
+        if let SignerRequest::Sign { digest, .. } = &mut request {
+            *digest = hex::encode(transfer_transaction().id());
+        }
+        match emulator.handle(request) {
+            SignerResponse::Error { .. } => {}
+            response => panic!("unexpected response: {:?}", response),
+        }
+    }
+
+    #[test]
+    fn check_emulator_serve() {
+        let emulator = SignerEmulator::new(&mnemonic());
+        let mut input = encode_message(&SignerRequest::GetInfo).unwrap();
+        input.extend(
+            encode_message(&SignerRequest::GetPublicKey {
+                path: PATH.to_owned(),
+                display: false,
+            })
+            .unwrap(),
+        );
+        input.extend(b"not json\n");
+        let mut output = Vec::new();
+        emulator.serve(Cursor::new(input), &mut output).unwrap();
+
+        let responses = output
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| decode_message::<SignerResponse>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(3, responses.len());
+        assert_eq!(
+            SignerResponse::Info {
+                version: PROTOCOL_VERSION,
+                device: "signer emulator".to_owned(),
+            },
+            responses[0]
+        );
+        assert_eq!(
+            SignerResponse::PublicKey {
+                public_key: hex::encode(PublicKey::from(&private_key(PATH)).serialize_compressed()),
+            },
+            responses[1]
+        );
+        match &responses[2] {
+            SignerResponse::Error { .. } => {}
+            response => panic!("unexpected response: {:?}", response),
+        }
+    }
+
+    #[test]
+    fn check_hid_framing() {
+        for len in &[0, 1, 55, 56, 57, 59, 60, 200] {
+            let message = (0..*len).map(|i| i as u8).collect::<Vec<u8>>();
+            let reports = hid_frame(7, &message).unwrap();
+            assert!(reports.iter().all(|report| report.len() == HID_REPORT_SIZE));
+            let mut reports = reports.into_iter();
+            let decoded = hid_unframe(7, || {
+                reports
+                    .next()
+                    .chain(|| (ErrorKind::IoError, "missing report"))
+            })
+            .unwrap();
+            assert_eq!(message, decoded);
+            assert!(reports.next().is_none());
+        }
+
+        let mut reports = hid_frame(7, b"message").unwrap().into_iter();
+        assert!(hid_unframe(8, || Ok(reports.next().unwrap())).is_err());
+    }
+
+    #[test]
+    fn check_transport_spec() {
+        assert!(ExternalSignerService::from_spec("exec:signer --device 1").is_ok());
+        assert!(ExternalSignerService::from_spec("exec:").is_err());
+        assert!(ExternalSignerService::from_spec("tcp:127.0.0.1:1").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! Reference implementation of the external signer (keys derived from a mnemonic)
+use std::io::{BufRead, Write};
+
+use parity_scale_codec::Decode;
+use secp256k1::recovery::RecoverableSignature;
+
+use super::protocol::{
+    decode_message, encode_message, DisplayData, SignatureScheme, SignerRequest, SignerResponse,
+    PROTOCOL_VERSION,
+};
+use super::transport::SignerTransport;
+use crate::hd_wallet::{ChainPath, DefaultKeyChain, ExtendedPrivKey, KeyChain};
+use crate::{HDSeed, Mnemonic};
+use chain_core::tx::TransactionId;
+use client_common::{
+    Error, ErrorKind, PrivateKey, PrivateKeyAction, Result, ResultExt, Transaction,
+};
+
+/// Emulates a signing device: keys are derived from the mnemonic like in a HD wallet,
+/// and the requests are checked like a device would do before asking the user
+#[derive(Debug, Clone)]
+pub struct SignerEmulator {
+    hd_seed: HDSeed,
+    approve: bool,
+}
+
+impl SignerEmulator {
+    /// creates an emulator which approves all the valid requests
+    pub fn new(mnemonic: &Mnemonic) -> Self {
+        Self {
+            hd_seed: HDSeed::from(mnemonic),
+            approve: true,
+        }
+    }
+
+    /// sets whether the (emulated) user approves the signing requests
+    pub fn set_approve(&mut self, approve: bool) {
+        self.approve = approve;
+    }
+
+    /// processes the request
+    pub fn handle(&self, request: SignerRequest) -> SignerResponse {
+        let response = match request {
+            SignerRequest::GetInfo => Ok(SignerResponse::Info {
+                version: PROTOCOL_VERSION,
+                device: "signer emulator".to_owned(),
+            }),
+            SignerRequest::GetPublicKey { path, .. } => {
+                self.get_private_key(&path).and_then(|private_key| {
+                    Ok(SignerResponse::PublicKey {
+                        public_key: hex::encode(private_key.public_key()?.serialize_compressed()),
+                    })
+                })
+            }
+            SignerRequest::Sign {
+                path,
+                scheme,
+                digest,
+                transaction,
+                display,
+            } => self.sign(&path, scheme, &digest, &transaction, &display),
+        };
+        response.unwrap_or_else(|e| SignerResponse::Error {
+            message: e.to_string(),
+        })
+    }
+
+    /// serves the requests read from the lines of the reader (e.g. stdin or a socket)
+    pub fn serve<R: BufRead, W: Write>(&self, reader: R, mut writer: W) -> Result<()> {
+        for line in reader.lines() {
+            let line = line.chain(|| (ErrorKind::IoError, "Unable to read request"))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            writer
+                .write_all(&self.exchange(line.as_bytes())?)
+                .and_then(|_| writer.flush())
+                .chain(|| (ErrorKind::IoError, "Unable to write response"))?;
+        }
+        Ok(())
+    }
+
+    fn get_private_key(&self, path: &str) -> Result<PrivateKey> {
+        let key_chain = DefaultKeyChain::new(
+            ExtendedPrivKey::with_seed(&self.hd_seed.bytes)
+                .chain(|| (ErrorKind::InternalError, "Invalid seed bytes"))?,
+        );
+        let chain_path = ChainPath::from(path);
+        let (extended_private_key, _) = key_chain.derive_private_key(chain_path).chain(|| {
+            (
+                ErrorKind::InternalError,
+                "Failed to derive HD wallet private key",
+            )
+        })?;
+        Ok(PrivateKey::from(extended_private_key.private_key))
+    }
+
+    fn sign(
+        &self,
+        path: &str,
+        scheme: SignatureScheme,
+        digest: &str,
+        transaction: &str,
+        display: &DisplayData,
+    ) -> Result<SignerResponse> {
+        let digest =
+            hex::decode(digest).chain(|| (ErrorKind::DeserializationError, "Invalid digest"))?;
+        if digest.len() != 32 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Digest should be 32 bytes",
+            ));
+        }
+        let encoded = hex::decode(transaction)
+            .chain(|| (ErrorKind::DeserializationError, "Invalid transaction"))?;
+        let tx = Transaction::decode(&mut encoded.as_slice())
+            .chain(|| (ErrorKind::DeserializationError, "Invalid transaction"))?;
+        // a device only signs what it shows
+        if tx.id()[..] != digest[..] {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Digest doesn't match the transaction",
+            ));
+        }
+        if DisplayData::from(&tx) != *display {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Display data doesn't match the transaction",
+            ));
+        }
+        if !self.approve {
+            return Ok(SignerResponse::Rejected {
+                reason: "declined by the user".to_owned(),
+            });
+        }
+
+        let private_key = self.get_private_key(path)?;
+        let signature = match scheme {
+            SignatureScheme::Ecdsa => serialize_recoverable(&private_key.sign(&tx)?),
+            SignatureScheme::Schnorr => private_key.schnorr_sign(&tx)?.serialize_default().to_vec(),
+        };
+        Ok(SignerResponse::Signature {
+            signature: hex::encode(signature),
+        })
+    }
+}
+
+/// compact signature followed by the recovery id
+fn serialize_recoverable(signature: &RecoverableSignature) -> Vec<u8> {
+    let (recovery_id, compact) = signature.serialize_compact();
+    let mut serialized = compact.to_vec();
+    serialized.push(recovery_id.to_i32() as u8);
+    serialized
+}
+
+impl SignerTransport for SignerEmulator {
+    fn exchange(&self, request: &[u8]) -> Result<Vec<u8>> {
+        let response = match decode_message(request) {
+            Ok(request) => self.handle(request),
+            Err(e) => SignerResponse::Error {
+                message: e.to_string(),
+            },
+        };
+        encode_message(&response)
+    }
+}
@@ -0,0 +1,167 @@
+//! Messages exchanged with an external signer (see the module documentation)
+use chain_core::tx::TransactionId;
+use client_common::{Error, ErrorKind, Result, ResultExt, Transaction};
+use serde::{Deserialize, Serialize};
+
+/// Version of the request / response format
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Signature algorithm requested from the signer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureScheme {
+    /// recoverable ECDSA (65 bytes: compact signature + recovery id)
+    Ecdsa,
+    /// BIP-340 style Schnorr (64 bytes)
+    Schnorr,
+}
+
+/// What the device shows to the user before signing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayData {
+    /// transaction type (e.g. "transfer", "unbond")
+    pub tx_type: String,
+    /// (label, value) lines
+    pub fields: Vec<(String, String)>,
+}
+
+impl From<&Transaction> for DisplayData {
+    fn from(tx: &Transaction) -> Self {
+        let mut fields = Vec::new();
+        let tx_type = match tx {
+            Transaction::TransferTransaction(_) => "transfer",
+            Transaction::DepositStakeTransaction(tx) => {
+                fields.push(("to".to_owned(), tx.to_staked_account.to_string()));
+                "deposit"
+            }
+            Transaction::UnbondStakeTransaction(tx) => {
+                fields.push(("from".to_owned(), tx.from_staked_account.to_string()));
+                fields.push(("amount".to_owned(), tx.value.to_string()));
+                "unbond"
+            }
+            Transaction::WithdrawUnbondedStakeTransaction(_) => "withdraw",
+            Transaction::UnjailTransaction(tx) => {
+                fields.push(("address".to_owned(), tx.address.to_string()));
+                "unjail"
+            }
+            Transaction::NodejoinTransaction(tx) => {
+                fields.push(("address".to_owned(), tx.address.to_string()));
+                "node join"
+            }
+            Transaction::NodeMetadataUpdateTransaction(tx) => {
+                fields.push(("address".to_owned(), tx.address.to_string()));
+                fields.push(("name".to_owned(), tx.name.clone()));
+                "node update"
+            }
+        };
+        if !tx.inputs().is_empty() {
+            fields.push(("inputs".to_owned(), tx.inputs().len().to_string()));
+        }
+        for (i, output) in tx.outputs().iter().enumerate() {
+            fields.push((
+                format!("output {}", i),
+                format!("{} {}", output.address, output.value),
+            ));
+        }
+        DisplayData {
+            tx_type: tx_type.to_owned(),
+            fields,
+        }
+    }
+}
+
+/// Request to the external signer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum SignerRequest {
+    /// protocol version and device description
+    GetInfo,
+    /// public key at the derivation path (optionally shown on the device for verification)
+    GetPublicKey {
+        /// BIP-32 derivation path (e.g. "m/44'/394'/0'/0/0")
+        path: String,
+        /// show the key on the device
+        display: bool,
+    },
+    /// signature of the transaction digest (the transaction ID)
+    Sign {
+        /// BIP-32 derivation path of the signing key
+        path: String,
+        /// requested signature algorithm
+        scheme: SignatureScheme,
+        /// hex-encoded 32-byte transaction ID
+        digest: String,
+        /// hex-encoded SCALE-encoded transaction (for devices which check the digest)
+        transaction: String,
+        /// what to show to the user
+        display: DisplayData,
+    },
+}
+
+impl SignerRequest {
+    /// signing request for the transaction
+    pub fn sign(path: String, scheme: SignatureScheme, tx: &Transaction) -> Self {
+        SignerRequest::Sign {
+            path,
+            scheme,
+            digest: hex::encode(tx.id()),
+            transaction: hex::encode(parity_scale_codec::Encode::encode(tx)),
+            display: DisplayData::from(tx),
+        }
+    }
+}
+
+/// Response of the external signer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum SignerResponse {
+    /// response to `GetInfo`
+    Info {
+        /// the supported protocol version
+        version: u32,
+        /// device description (e.g. model and firmware version)
+        device: String,
+    },
+    /// response to `GetPublicKey`: hex-encoded compressed public key
+    PublicKey {
+        /// hex-encoded compressed public key
+        public_key: String,
+    },
+    /// response to `Sign`: hex-encoded signature
+    Signature {
+        /// hex-encoded signature (in the requested scheme's format)
+        signature: String,
+    },
+    /// the user declined the request on the device
+    Rejected {
+        /// why
+        reason: String,
+    },
+    /// the request couldn't be processed
+    Error {
+        /// what went wrong
+        message: String,
+    },
+}
+
+/// encodes the message as a line of JSON
+pub fn encode_message<T: Serialize>(message: &T) -> Result<Vec<u8>> {
+    let mut encoded = serde_json::to_vec(message).chain(|| {
+        (
+            ErrorKind::SerializationError,
+            "Unable to serialize external signer message",
+        )
+    })?;
+    encoded.push(b'\n');
+    Ok(encoded)
+}
+
+/// decodes the message from a line of JSON
+pub fn decode_message<T: for<'de> Deserialize<'de>>(encoded: &[u8]) -> Result<T> {
+    serde_json::from_slice(encoded).map_err(|e| {
+        Error::new(
+            ErrorKind::DeserializationError,
+            format!("Unable to deserialize external signer message: {}", e),
+        )
+    })
+}
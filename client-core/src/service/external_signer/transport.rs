@@ -0,0 +1,250 @@
+//! Transports carrying the external signer messages
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use client_common::{Error, ErrorKind, Result, ResultExt};
+
+/// Delivers a request to the external signer and waits for its response
+/// (the messages are lines of JSON, see `protocol`)
+pub trait SignerTransport: Send + Sync {
+    /// sends the encoded request and returns the encoded response
+    fn exchange(&self, request: &[u8]) -> Result<Vec<u8>>;
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Vec<u8>> {
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line).chain(|| {
+        (
+            ErrorKind::IoError,
+            "Unable to read external signer response",
+        )
+    })?;
+    if line.is_empty() {
+        return Err(Error::new(
+            ErrorKind::IoError,
+            "External signer closed the connection without a response",
+        ));
+    }
+    Ok(line)
+}
+
+/// Runs the signer program for each request:
+/// the request is written to its stdin, the response is the first line of its stdout
+#[derive(Debug, Clone)]
+pub struct ProcessTransport {
+    program: String,
+    args: Vec<String>,
+}
+
+impl ProcessTransport {
+    /// creates a transport running the program with the arguments
+    pub fn new(program: String, args: Vec<String>) -> Self {
+        Self { program, args }
+    }
+}
+
+impl SignerTransport for ProcessTransport {
+    fn exchange(&self, request: &[u8]) -> Result<Vec<u8>> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .chain(|| {
+                (
+                    ErrorKind::IoError,
+                    format!("Unable to start external signer: {}", self.program),
+                )
+            })?;
+        // stdin is closed when dropped, so that the signer sees the end of the request
+        child
+            .stdin
+            .take()
+            .chain(|| (ErrorKind::IoError, "External signer stdin is not available"))?
+            .write_all(request)
+            .chain(|| {
+                (
+                    ErrorKind::IoError,
+                    "Unable to send request to external signer",
+                )
+            })?;
+        let output = child
+            .wait_with_output()
+            .chain(|| (ErrorKind::IoError, "Unable to wait for external signer"))?;
+        if !output.status.success() {
+            return Err(Error::new(
+                ErrorKind::IoError,
+                format!("External signer exited with {}", output.status),
+            ));
+        }
+        read_line(&mut output.stdout.as_slice())
+    }
+}
+
+/// Connects to the signer listening on a Unix socket (one request per connection)
+#[cfg(unix)]
+#[derive(Debug, Clone)]
+pub struct UnixSocketTransport {
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixSocketTransport {
+    /// creates a transport connecting to the socket at the path
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+#[cfg(unix)]
+impl SignerTransport for UnixSocketTransport {
+    fn exchange(&self, request: &[u8]) -> Result<Vec<u8>> {
+        let mut stream = UnixStream::connect(&self.path).chain(|| {
+            (
+                ErrorKind::ConnectionError,
+                format!(
+                    "Unable to connect to external signer at {}",
+                    self.path.display()
+                ),
+            )
+        })?;
+        stream.write_all(request).chain(|| {
+            (
+                ErrorKind::IoError,
+                "Unable to send request to external signer",
+            )
+        })?;
+        read_line(&mut BufReader::new(stream))
+    }
+}
+
+/// Size of the HID reports
+pub const HID_REPORT_SIZE: usize = 64;
+/// Tag of the message reports
+const HID_TAG: u8 = 0x05;
+/// channel (2 bytes) + tag (1 byte) + sequence number (2 bytes)
+const HID_HEADER_SIZE: usize = 5;
+/// the first report also carries the message length (4 bytes)
+const HID_LENGTH_SIZE: usize = 4;
+
+/// Raw access to a HID device (e.g. provided by a hidapi binding)
+pub trait HidDevice: Send {
+    /// writes a report of `HID_REPORT_SIZE` bytes
+    fn write_report(&mut self, report: &[u8]) -> Result<()>;
+    /// reads a report of `HID_REPORT_SIZE` bytes
+    fn read_report(&mut self) -> Result<Vec<u8>>;
+}
+
+/// Splits the messages into HID reports:
+/// each report starts with the channel, the tag and the sequence number (big-endian),
+/// the first report of a message continues with the message length (big-endian u32)
+pub struct HidTransport<D: HidDevice> {
+    device: Mutex<D>,
+    channel: u16,
+}
+
+impl<D: HidDevice> HidTransport<D> {
+    /// creates a transport over the device using the channel ID
+    pub fn new(device: D, channel: u16) -> Self {
+        Self {
+            device: Mutex::new(device),
+            channel,
+        }
+    }
+}
+
+fn hid_header(channel: u16, sequence: u16) -> Vec<u8> {
+    let mut header = Vec::with_capacity(HID_REPORT_SIZE);
+    header.extend_from_slice(&channel.to_be_bytes());
+    header.push(HID_TAG);
+    header.extend_from_slice(&sequence.to_be_bytes());
+    header
+}
+
+/// splits the message into the HID reports
+pub fn hid_frame(channel: u16, message: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let length = u32::try_from(message.len()).ok().chain(|| {
+        (
+            ErrorKind::InvalidInput,
+            "Message too long for HID transport",
+        )
+    })?;
+    let mut reports = Vec::new();
+    let mut remaining = message;
+    let mut sequence = 0u16;
+    loop {
+        let mut report = hid_header(channel, sequence);
+        if sequence == 0 {
+            report.extend_from_slice(&length.to_be_bytes());
+        }
+        let take = remaining.len().min(HID_REPORT_SIZE - report.len());
+        report.extend_from_slice(&remaining[..take]);
+        remaining = &remaining[take..];
+        report.resize(HID_REPORT_SIZE, 0);
+        reports.push(report);
+        if remaining.is_empty() {
+            return Ok(reports);
+        }
+        sequence = sequence.checked_add(1).chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                "Message too long for HID transport",
+            )
+        })?;
+    }
+}
+
+/// reassembles the message from the HID reports
+pub fn hid_unframe(
+    channel: u16,
+    mut next_report: impl FnMut() -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    let invalid = |message: &str| Error::new(ErrorKind::DeserializationError, message);
+    let mut message = Vec::new();
+    let mut length = None;
+    let mut sequence = 0u16;
+    loop {
+        let report = next_report()?;
+        if report.len() < HID_HEADER_SIZE + HID_LENGTH_SIZE
+            || report[..HID_HEADER_SIZE] != hid_header(channel, sequence)[..]
+        {
+            return Err(invalid("Unexpected HID report"));
+        }
+        let mut data = &report[HID_HEADER_SIZE..];
+        if sequence == 0 {
+            let mut len = [0u8; HID_LENGTH_SIZE];
+            len.copy_from_slice(&data[..HID_LENGTH_SIZE]);
+            length = Some(u32::from_be_bytes(len) as usize);
+            data = &data[HID_LENGTH_SIZE..];
+        }
+        // no panic: set in the first report
+        let length = length.unwrap();
+        let take = (length - message.len()).min(data.len());
+        message.extend_from_slice(&data[..take]);
+        if message.len() == length {
+            return Ok(message);
+        }
+        sequence = sequence
+            .checked_add(1)
+            .ok_or_else(|| invalid("HID message too long"))?;
+    }
+}
+
+impl<D: HidDevice> SignerTransport for HidTransport<D> {
+    fn exchange(&self, request: &[u8]) -> Result<Vec<u8>> {
+        let mut device = self
+            .device
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::InternalError, "HID device lock poisoned"))?;
+        for report in hid_frame(self.channel, request)? {
+            device.write_report(&report)?;
+        }
+        hid_unframe(self.channel, || device.read_report())
+    }
+}
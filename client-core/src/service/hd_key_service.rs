@@ -1,4 +1,6 @@
-use parity_scale_codec::{Decode, Encode};
+use parity_scale_codec::{Decode, Encode, Input, Output};
+use secstr::SecUtf8;
+use unicase::eq_ascii;
 
 use chain_core::init::network::get_network;
 use client_common::storage::decrypt_bytes;
@@ -9,10 +11,19 @@ use client_common::{
 use crate::types::AddressType;
 use crate::{HDSeed, Mnemonic};
 
-use crate::hd_wallet::ChainPath;
+use crate::hd_wallet::traits::{Deserialize, Serialize};
+use crate::hd_wallet::{
+    Bip32Key, ChainPath, ExtendedKey, ExtendedPrivKey, ExtendedPubKey, KeyIndex,
+};
 use std::convert::From;
+use std::ops::Range;
+use std::str::FromStr;
 
 const KEYSPACE: &str = "core_hd_key";
+const ACCOUNT_PUBKEY_KEYSPACE: &str = "core_hd_account_pubkey";
+const ACCOUNT_PRIVKEY_KEYSPACE: &str = "core_hd_account_privkey";
+/// Depth of the account-level keys (`m / 44' / coin_type' / account'`)
+const ACCOUNT_KEY_DEPTH: u8 = 3;
 
 /// HD key
 #[derive(Debug, Clone, PartialEq, Default, Encode, Decode)]
@@ -47,6 +58,229 @@ impl HDAccountType {
     }
 }
 
+impl FromStr for HDAccountType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if eq_ascii(s, "transfer") {
+            Ok(HDAccountType::Transfer)
+        } else if eq_ascii(s, "staking") {
+            Ok(HDAccountType::Staking)
+        } else if eq_ascii(s, "viewkey") {
+            Ok(HDAccountType::Viewkey)
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Account type can either be `transfer` or `staking` or `viewkey`",
+            ))
+        }
+    }
+}
+
+/// Decodes a serialized (BIP32) account-level extended key, and checks it's the key of the account
+pub fn parse_account_key(encoded: &str, account_type: HDAccountType) -> Result<ExtendedKey> {
+    let key = Bip32Key::from_base58(encoded, get_network())
+        .chain(|| (ErrorKind::InvalidInput, "Invalid extended key"))?;
+    let child_number = KeyIndex::hardened_from_normalize_index(account_type.index())
+        .chain(|| (ErrorKind::InternalError, "Invalid account index"))?
+        .raw_index();
+    if key.depth != ACCOUNT_KEY_DEPTH || key.child_number != child_number {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Extended key is not the key of {:?} account", account_type),
+        ));
+    }
+    Ok(key.key)
+}
+
+/// Account-level extended public keys (`m / 44' / coin_type' / account'`)
+/// from which a watch-only HD wallet derives its addresses
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountPubKeys {
+    /// extended public key of the transfer account
+    pub transfer: ExtendedPubKey,
+    /// extended public key of the staking account (no staking addresses without it)
+    pub staking: Option<ExtendedPubKey>,
+}
+
+impl Encode for AccountPubKeys {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.transfer.serialize().encode_to(dest);
+        self.staking
+            .as_ref()
+            .map(|key| key.serialize())
+            .encode_to(dest);
+    }
+}
+
+impl Decode for AccountPubKeys {
+    fn decode<I: Input>(input: &mut I) -> std::result::Result<Self, parity_scale_codec::Error> {
+        let decode_key = |bytes: Vec<u8>| {
+            ExtendedPubKey::deserialize(bytes.as_slice())
+                .map_err(|_| parity_scale_codec::Error::from("Invalid extended public key"))
+        };
+        let transfer = decode_key(Vec::decode(input)?)?;
+        let staking = match Option::<Vec<u8>>::decode(input)? {
+            Some(bytes) => Some(decode_key(bytes)?),
+            None => None,
+        };
+        Ok(AccountPubKeys { transfer, staking })
+    }
+}
+
+impl AccountPubKeys {
+    /// Decodes the account public keys from the serialized (BIP32) `xpub`s
+    pub fn from_xpub(transfer: &str, staking: Option<&str>) -> Result<Self> {
+        let parse_xpub = |encoded: &str, account_type: HDAccountType| match parse_account_key(
+            encoded,
+            account_type,
+        )? {
+            ExtendedKey::Public(key) => Ok(key),
+            ExtendedKey::Private(_) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Expected an extended public key, not an extended private key",
+            )),
+        };
+        Ok(AccountPubKeys {
+            transfer: parse_xpub(transfer, HDAccountType::Transfer)?,
+            staking: staking
+                .map(|staking| parse_xpub(staking, HDAccountType::Staking))
+                .transpose()?,
+        })
+    }
+
+    fn account_pubkey(&self, account_type: HDAccountType) -> Result<&ExtendedPubKey> {
+        match account_type {
+            HDAccountType::Transfer => Ok(&self.transfer),
+            HDAccountType::Staking => self.staking.as_ref().chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    "Staking account public key is not in the watch-only wallet",
+                )
+            }),
+            HDAccountType::Viewkey => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "View key is not derived in watch-only wallet",
+            )),
+        }
+    }
+
+    /// Derives the public key at `account / 0 / index`
+    pub fn derive_pubkey(&self, account_type: HDAccountType, index: u32) -> Result<PublicKey> {
+        let parent_pubkey = self.parent_pubkey(account_type)?;
+        HDSeed::get_pubkey_from_parent_pubkey(&parent_pubkey, index)
+    }
+
+    fn parent_pubkey(&self, account_type: HDAccountType) -> Result<ExtendedPubKey> {
+        self.account_pubkey(account_type)?
+            .derive_public_key(KeyIndex::Normal(0))
+            .chain(|| (ErrorKind::InternalError, "Invalid extended pubkey"))
+    }
+}
+
+/// Account-level extended private keys (`m / 44' / coin_type' / account'`)
+/// from which a HD wallet restored without the seed derives its keys
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountPrivKeys {
+    /// extended private key of the transfer account
+    pub transfer: ExtendedPrivKey,
+    /// extended private key of the staking account
+    pub staking: ExtendedPrivKey,
+    /// extended private key of the viewkey account
+    pub viewkey: ExtendedPrivKey,
+}
+
+impl Encode for AccountPrivKeys {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.transfer.serialize().encode_to(dest);
+        self.staking.serialize().encode_to(dest);
+        self.viewkey.serialize().encode_to(dest);
+    }
+}
+
+impl Decode for AccountPrivKeys {
+    fn decode<I: Input>(input: &mut I) -> std::result::Result<Self, parity_scale_codec::Error> {
+        Ok(AccountPrivKeys {
+            transfer: decode_privkey(input)?,
+            staking: decode_privkey(input)?,
+            viewkey: decode_privkey(input)?,
+        })
+    }
+}
+
+fn decode_privkey<I: Input>(
+    input: &mut I,
+) -> std::result::Result<ExtendedPrivKey, parity_scale_codec::Error> {
+    let bytes = Vec::<u8>::decode(input)?;
+    ExtendedPrivKey::deserialize(bytes.as_slice())
+        .map_err(|_| parity_scale_codec::Error::from("Invalid extended private key"))
+}
+
+impl AccountPrivKeys {
+    /// Decodes the account private keys from the serialized (BIP32) `xprv`s
+    pub fn from_xprv(transfer: &SecUtf8, staking: &SecUtf8, viewkey: &SecUtf8) -> Result<Self> {
+        let parse_xprv = |encoded: &SecUtf8, account_type: HDAccountType| match parse_account_key(
+            encoded.unsecure(),
+            account_type,
+        )? {
+            ExtendedKey::Private(key) => Ok(key),
+            ExtendedKey::Public(_) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Expected an extended private key, not an extended public key",
+            )),
+        };
+        Ok(AccountPrivKeys {
+            transfer: parse_xprv(transfer, HDAccountType::Transfer)?,
+            staking: parse_xprv(staking, HDAccountType::Staking)?,
+            viewkey: parse_xprv(viewkey, HDAccountType::Viewkey)?,
+        })
+    }
+
+    fn account_privkey(&self, account_type: HDAccountType) -> &ExtendedPrivKey {
+        match account_type {
+            HDAccountType::Transfer => &self.transfer,
+            HDAccountType::Staking => &self.staking,
+            HDAccountType::Viewkey => &self.viewkey,
+        }
+    }
+
+    /// Derives the key pair at `account / 0 / index`
+    pub fn derive_key_pair(
+        &self,
+        account_type: HDAccountType,
+        index: u32,
+    ) -> Result<(PublicKey, PrivateKey)> {
+        let extended_private_key = self
+            .account_privkey(account_type)
+            .derive_private_key(KeyIndex::Normal(0))
+            .and_then(|key| key.derive_private_key(KeyIndex::Normal(index)))
+            .chain(|| {
+                (
+                    ErrorKind::InternalError,
+                    "Failed to derive HD wallet private key",
+                )
+            })?;
+        let private_key = PrivateKey::from(extended_private_key.private_key);
+        let public_key = PublicKey::from(&private_key);
+        Ok((public_key, private_key))
+    }
+
+    fn parent_pubkey(&self, account_type: HDAccountType) -> Result<ExtendedPubKey> {
+        self.account_privkey(account_type)
+            .derive_private_key(KeyIndex::Normal(0))
+            .map(|key| ExtendedPubKey::from_private_key(&key))
+            .chain(|| (ErrorKind::InternalError, "Invalid extended private key"))
+    }
+
+    /// Returns the account public keys to create the watch-only counterpart of the wallet
+    pub fn to_pubkeys(&self) -> AccountPubKeys {
+        AccountPubKeys {
+            transfer: ExtendedPubKey::from_private_key(&self.transfer),
+            staking: Some(ExtendedPubKey::from_private_key(&self.staking)),
+        }
+    }
+}
+
 // AddressType is subset of HDAccountType
 impl From<AddressType> for HDAccountType {
     fn from(addr_type: AddressType) -> HDAccountType {
@@ -75,22 +309,22 @@ where
 
     /// automatically recover address in syncing
     pub fn get_latest_transfer_index(&mut self, name: &str, enckey: &SecKey) -> Result<u32> {
-        let bytes: Vec<u8> = self.storage.get_secure(KEYSPACE, name, enckey)?.chain(|| {
-            (
-                ErrorKind::InvalidInput,
-                format!("HD Key with name ({}) not found", name),
-            )
-        })?;
-
-        //  let hd_key_bytes = decrypt_bytes(name, enckey, &bytes)?;
-        let hd_key = HdKey::decode(&mut bytes.as_slice()).chain(|| {
-            (
-                ErrorKind::DeserializationError,
-                "Unable to decode HD key bytes",
-            )
-        })?;
+        self.get_latest_index(name, enckey, HDAccountType::Transfer)
+    }
 
-        let index = hd_key.transfer_index;
+    /// Returns the index of the latest derived key of the account
+    pub fn get_latest_index(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_type: HDAccountType,
+    ) -> Result<u32> {
+        let hd_key = self.load_hd_key(name, enckey)?;
+        let index = match account_type {
+            HDAccountType::Transfer => hd_key.transfer_index,
+            HDAccountType::Staking => hd_key.staking_index,
+            HDAccountType::Viewkey => hd_key.viewkey_index,
+        };
         Ok(index)
     }
 
@@ -107,6 +341,8 @@ where
                 format!("Wallet with name {} not found in hd key service", name)
             })?;
         self.storage.delete(KEYSPACE, name)?;
+        self.storage.delete(ACCOUNT_PUBKEY_KEYSPACE, name)?;
+        self.storage.delete(ACCOUNT_PRIVKEY_KEYSPACE, name)?;
         Ok(())
     }
 
@@ -158,6 +394,61 @@ where
 
     /// peek key pair by index
     pub fn peek_pubkey(&self, name: &str, enckey: &SecKey, index: u32) -> Result<PublicKey> {
+        let parent_pubkey = self.parent_pubkey(name, enckey, HDAccountType::Transfer)?;
+        HDSeed::get_pubkey_from_parent_pubkey(&parent_pubkey, index)
+    }
+
+    /// peek the public keys of the account at the indexes, without updating the HD key
+    pub fn peek_pubkeys(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_type: HDAccountType,
+        indexes: Range<u32>,
+    ) -> Result<Vec<PublicKey>> {
+        let parent_pubkey = self.parent_pubkey(name, enckey, account_type)?;
+        indexes
+            .map(|index| HDSeed::get_pubkey_from_parent_pubkey(&parent_pubkey, index))
+            .collect()
+    }
+
+    /// Returns true if the public keys of the account can be derived without a hardware wallet
+    pub fn can_derive_pubkey(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_type: HDAccountType,
+    ) -> Result<bool> {
+        let hd_key = self.load_hd_key(name, enckey)?;
+        if let Some(account_pubkeys) = self.get_account_pubkeys(name, enckey)? {
+            return Ok(account_pubkeys.account_pubkey(account_type).is_ok());
+        }
+        Ok(!hd_key.seed.bytes.is_empty() || self.get_account_privkeys(name, enckey)?.is_some())
+    }
+
+    /// extended public key at `account / 0` from which the keys of the account are derived
+    fn parent_pubkey(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_type: HDAccountType,
+    ) -> Result<ExtendedPubKey> {
+        let hd_key = self.load_hd_key(name, enckey)?;
+
+        if let Some(account_pubkeys) = self.get_account_pubkeys(name, enckey)? {
+            return account_pubkeys.parent_pubkey(account_type);
+        }
+        if hd_key.seed.bytes.is_empty() {
+            if let Some(account_privkeys) = self.get_account_privkeys(name, enckey)? {
+                return account_privkeys.parent_pubkey(account_type);
+            }
+        }
+        hd_key
+            .seed
+            .get_parent_pubkey(get_network(), account_type.index())
+    }
+
+    fn load_hd_key(&self, name: &str, enckey: &SecKey) -> Result<HdKey> {
         let bytes: Vec<u8> = self.storage.get_secure(KEYSPACE, name, enckey)?.chain(|| {
             (
                 ErrorKind::InvalidInput,
@@ -165,17 +456,179 @@ where
             )
         })?;
 
-        //  let hd_key_bytes = decrypt_bytes(name, enckey, &bytes)?;
-        let hd_key = HdKey::decode(&mut bytes.as_slice()).chain(|| {
+        HdKey::decode(&mut bytes.as_slice()).chain(|| {
             (
                 ErrorKind::DeserializationError,
                 "Unable to decode HD key bytes",
             )
+        })
+    }
+
+    /// Adds the account public keys of a watch-only wallet in storage
+    #[inline]
+    pub fn add_account_pubkeys(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_pubkeys: &AccountPubKeys,
+    ) -> Result<()> {
+        self.storage
+            .set_secure(
+                ACCOUNT_PUBKEY_KEYSPACE,
+                name,
+                account_pubkeys.encode(),
+                enckey,
+            )
+            .map(|_| ())
+    }
+
+    /// Get the account public keys of a watch-only wallet from storage
+    pub fn get_account_pubkeys(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+    ) -> Result<Option<AccountPubKeys>> {
+        let raw = self
+            .storage
+            .get_secure(ACCOUNT_PUBKEY_KEYSPACE, name, enckey)?;
+        match raw {
+            None => Ok(None),
+            Some(r) => {
+                let account_pubkeys = AccountPubKeys::decode(&mut r.as_slice()).chain(|| {
+                    (
+                        ErrorKind::DeserializationError,
+                        "Unable to decode account public keys",
+                    )
+                })?;
+                Ok(Some(account_pubkeys))
+            }
+        }
+    }
+
+    /// Adds the account private keys of a HD wallet restored without the seed in storage
+    #[inline]
+    pub fn add_account_privkeys(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_privkeys: &AccountPrivKeys,
+    ) -> Result<()> {
+        self.storage
+            .set_secure(
+                ACCOUNT_PRIVKEY_KEYSPACE,
+                name,
+                account_privkeys.encode(),
+                enckey,
+            )
+            .map(|_| ())
+    }
+
+    /// Get the account private keys of a HD wallet restored without the seed from storage
+    pub fn get_account_privkeys(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+    ) -> Result<Option<AccountPrivKeys>> {
+        let raw = self
+            .storage
+            .get_secure(ACCOUNT_PRIVKEY_KEYSPACE, name, enckey)?;
+        match raw {
+            None => Ok(None),
+            Some(r) => {
+                let account_privkeys = AccountPrivKeys::decode(&mut r.as_slice()).chain(|| {
+                    (
+                        ErrorKind::DeserializationError,
+                        "Unable to decode account private keys",
+                    )
+                })?;
+                Ok(Some(account_privkeys))
+            }
+        }
+    }
+
+    /// Serializes (BIP32) the account-level extended key of a HD or watch-only wallet
+    ///
+    /// # Note
+    ///
+    /// Only the wallets with a seed know the parent of the account keys, the parent fingerprint
+    /// is zero in the keys exported from the other wallets.
+    pub fn export_extended_key(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_type: HDAccountType,
+        private: bool,
+    ) -> Result<Bip32Key> {
+        let hd_key = self.get_hdkey(name, enckey)?.chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                format!("HD Key with name ({}) not found", name),
+            )
         })?;
+        let network = get_network();
+        let key = if !hd_key.seed.bytes.is_empty() {
+            hd_key
+                .seed
+                .get_account_bip32_key(network, account_type.index())?
+        } else if let Some(account_privkeys) = self.get_account_privkeys(name, enckey)? {
+            account_bip32_key(
+                account_type,
+                ExtendedKey::Private(account_privkeys.account_privkey(account_type).clone()),
+            )?
+        } else if let Some(account_pubkeys) = self.get_account_pubkeys(name, enckey)? {
+            if private {
+                return Err(Error::new(
+                    ErrorKind::PermissionDenied,
+                    "Watch-only wallet has no extended private key",
+                ));
+            }
+            account_bip32_key(
+                account_type,
+                ExtendedKey::Public(account_pubkeys.account_pubkey(account_type)?.clone()),
+            )?
+        } else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Extended keys can only be exported from HD or watch-only wallet",
+            ));
+        };
+        if private {
+            Ok(key)
+        } else {
+            Ok(key.neuter())
+        }
+    }
 
-        hd_key
-            .seed
-            .get_pubkey(get_network(), HDAccountType::Transfer.index(), index)
+    /// Derives the account public keys of a HD wallet (to create its watch-only counterpart)
+    pub fn export_account_pubkeys(&self, name: &str, enckey: &SecKey) -> Result<AccountPubKeys> {
+        let hd_key = self.get_hdkey(name, enckey)?.chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                format!("HD Key with name ({}) not found", name),
+            )
+        })?;
+        if hd_key.seed.bytes.is_empty() {
+            return self
+                .get_account_privkeys(name, enckey)?
+                .map(|account_privkeys| account_privkeys.to_pubkeys())
+                .chain(|| {
+                    (
+                        ErrorKind::InvalidInput,
+                        "Account public keys can only be exported from HD wallet",
+                    )
+                });
+        }
+        let network = get_network();
+        Ok(AccountPubKeys {
+            transfer: hd_key
+                .seed
+                .get_account_pubkey(network, HDAccountType::Transfer.index())?,
+            staking: Some(
+                hd_key
+                    .seed
+                    .get_account_pubkey(network, HDAccountType::Staking.index())?,
+            ),
+        })
     }
 
     /// update the stored HDKey, return the updated one
@@ -245,6 +698,7 @@ where
         enckey: &SecKey,
         account_type: HDAccountType,
     ) -> Result<(PublicKey, PrivateKey)> {
+        let account_privkeys = self.get_account_privkeys(name, enckey)?;
         let hd_key = self.update_hd_key(name, enckey, account_type)?;
         let index = match account_type {
             HDAccountType::Transfer => hd_key.transfer_index,
@@ -252,9 +706,14 @@ where
             HDAccountType::Viewkey => hd_key.viewkey_index,
         };
 
-        hd_key
-            .seed
-            .derive_key_pair(get_network(), account_type.index(), index)
+        match account_privkeys {
+            Some(account_privkeys) if hd_key.seed.bytes.is_empty() => {
+                account_privkeys.derive_key_pair(account_type, index)
+            }
+            _ => hd_key
+                .seed
+                .derive_key_pair(get_network(), account_type.index(), index),
+        }
     }
 
     /// Generate ChainPath for given wallet and address type
@@ -276,17 +735,63 @@ where
         Ok(chain_path)
     }
 
+    /// Generates public key for given watch-only wallet and address type
+    /// 1. update the HdKey
+    /// 2. derive the public key at the updated index from the account public key
+    pub fn generate_public_key(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_type: HDAccountType,
+    ) -> Result<(PublicKey, ChainPath)> {
+        let account_pubkeys = self.get_account_pubkeys(name, enckey)?.chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                format!("Account public keys of wallet ({}) not found", name),
+            )
+        })?;
+        // fail before the index is updated
+        account_pubkeys.account_pubkey(account_type)?;
+
+        let hd_key = self.update_hd_key(name, enckey, account_type)?;
+        let index = match account_type {
+            HDAccountType::Transfer => hd_key.transfer_index,
+            HDAccountType::Staking => hd_key.staking_index,
+            HDAccountType::Viewkey => hd_key.viewkey_index,
+        };
+        let public_key = account_pubkeys.derive_pubkey(account_type, index)?;
+        let chain_path = ChainPath::create_bip44(get_network(), account_type.index(), index);
+        Ok((public_key, chain_path))
+    }
+
     /// Clears all storage
     #[inline]
     pub fn clear(&self) -> Result<()> {
-        self.storage.clear(KEYSPACE)
+        self.storage.clear(KEYSPACE)?;
+        self.storage.clear(ACCOUNT_PUBKEY_KEYSPACE)?;
+        self.storage.clear(ACCOUNT_PRIVKEY_KEYSPACE)
     }
 }
 
+/// Account-level key of which the parent is unknown
+fn account_bip32_key(account_type: HDAccountType, key: ExtendedKey) -> Result<Bip32Key> {
+    let child_number = KeyIndex::hardened_from_normalize_index(account_type.index())
+        .chain(|| (ErrorKind::InternalError, "Invalid account index"))?
+        .raw_index();
+    Ok(Bip32Key {
+        network: get_network(),
+        depth: ACCOUNT_KEY_DEPTH,
+        parent_fingerprint: [0; 4],
+        child_number,
+        key,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::wallet::{DefaultWalletClient, WalletClient};
+    use chain_core::init::network::Network;
     use client_common::storage::MemoryStorage;
     use secstr::SecUtf8;
 
@@ -320,6 +825,73 @@ mod tests {
         assert_eq!(hd_key, decoded_hd_key);
     }
 
+    #[test]
+    fn check_account_pubkeys_encode_decode() {
+        let mnemonic =
+            Mnemonic::from_secstr(&SecUtf8::from("speed tortoise kiwi forward extend baby acoustic foil coach castle ship purchase unlock base hip erode tag keen present vibrant oyster cotton write fetch")).unwrap();
+        let seed = HDSeed::from(&mnemonic);
+        let transfer = seed
+            .get_account_pubkey(Network::Devnet, HDAccountType::Transfer.index())
+            .unwrap();
+        let account_pubkeys = AccountPubKeys {
+            transfer,
+            staking: None,
+        };
+
+        let encoded = account_pubkeys.encode();
+        let decoded = AccountPubKeys::decode(&mut encoded.as_slice()).unwrap();
+        assert_eq!(account_pubkeys, decoded);
+        assert_eq!(
+            decoded.derive_pubkey(HDAccountType::Transfer, 1).unwrap(),
+            seed.get_pubkey(Network::Devnet, HDAccountType::Transfer.index(), 1)
+                .unwrap()
+        );
+        assert!(decoded.derive_pubkey(HDAccountType::Staking, 1).is_err());
+    }
+
+    #[test]
+    fn check_export_extended_key() {
+        let storage = MemoryStorage::default();
+        let name = "testhdwallet";
+        let passphrase = SecUtf8::from("passphrase");
+        let service = HdKeyService::new(storage.clone());
+        let mnemonic =
+            Mnemonic::from_secstr(&SecUtf8::from("speed tortoise kiwi forward extend baby acoustic foil coach castle ship purchase unlock base hip erode tag keen present vibrant oyster cotton write fetch")).unwrap();
+
+        let wallet = DefaultWalletClient::new_read_only(storage.clone());
+        let enckey = wallet
+            .restore_wallet(&name, &passphrase, &mnemonic)
+            .expect("restore wallet");
+
+        let export = |account_type, private| {
+            service
+                .export_extended_key(name, &enckey, account_type, private)
+                .unwrap()
+                .to_base58()
+        };
+        let transfer_xpub = export(HDAccountType::Transfer, false);
+        let staking_xpub = export(HDAccountType::Staking, false);
+        assert!(transfer_xpub.starts_with("tcub"));
+        assert_eq!(
+            AccountPubKeys::from_xpub(&transfer_xpub, Some(&staking_xpub)).unwrap(),
+            service.export_account_pubkeys(name, &enckey).unwrap()
+        );
+        // the key of another account is rejected
+        assert!(AccountPubKeys::from_xpub(&staking_xpub, None).is_err());
+
+        let account_privkeys = AccountPrivKeys::from_xprv(
+            &SecUtf8::from(export(HDAccountType::Transfer, true)),
+            &SecUtf8::from(export(HDAccountType::Staking, true)),
+            &SecUtf8::from(export(HDAccountType::Viewkey, true)),
+        )
+        .unwrap();
+        assert_eq!(
+            account_privkeys.to_pubkeys(),
+            service.export_account_pubkeys(name, &enckey).unwrap()
+        );
+        assert!(AccountPubKeys::from_xpub(&export(HDAccountType::Transfer, true), None).is_err());
+    }
+
     #[test]
     fn check_deterministic_hdkey_staking() {
         let storage = MemoryStorage::default();
@@ -1,4 +1,5 @@
 use crate::hd_wallet::ChainPath;
+use crate::service::ExternalSignerService;
 #[cfg(feature = "mock-hardware-wallet")]
 use crate::service::MockHardwareService;
 use client_common::{ErrorKind, PrivateKeyAction, PublicKey, Result};
@@ -21,11 +22,12 @@ pub struct UnauthorizedHwKeyService;
 impl HardwareWalletAction for UnauthorizedHwKeyService {}
 
 /// Hardware Key Service collections
-/// TODO: add Ledger, Trezor Service
 #[derive(Clone, Debug)]
 pub enum HwKeyService {
     /// unauthorized hardware key service
     Unauthorized(UnauthorizedHwKeyService),
+    /// external signer (e.g. Ledger, Trezor adapters)
+    External(ExternalSignerService),
     /// mock key service
     #[cfg(feature = "mock-hardware-wallet")]
     Mock(MockHardwareService),
@@ -42,6 +44,7 @@ impl HwKeyService {
     pub fn get_sign_key(&self, hd_path: &ChainPath) -> Result<Box<dyn PrivateKeyAction>> {
         match self {
             HwKeyService::Unauthorized(hw_key_service) => hw_key_service.get_sign_key(hd_path),
+            HwKeyService::External(hw_key_service) => hw_key_service.get_sign_key(hd_path),
             #[cfg(feature = "mock-hardware-wallet")]
             HwKeyService::Mock(hw_key_service) => hw_key_service.get_sign_key(hd_path),
         }
@@ -51,6 +54,7 @@ impl HwKeyService {
     pub fn get_public_key(&self, chain_path: ChainPath) -> Result<PublicKey> {
         match self {
             HwKeyService::Unauthorized(hw_key_service) => hw_key_service.get_public_key(chain_path),
+            HwKeyService::External(hw_key_service) => hw_key_service.get_public_key(chain_path),
             #[cfg(feature = "mock-hardware-wallet")]
             HwKeyService::Mock(hw_key_service) => hw_key_service.get_public_key(chain_path),
         }
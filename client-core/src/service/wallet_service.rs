@@ -2,12 +2,12 @@ use indexmap::IndexSet;
 use parity_scale_codec::{Decode, Encode, Input, Output};
 
 use crate::hd_wallet::ChainPath;
-use crate::service::{load_wallet_state, HdKey, WalletState};
+use crate::service::{load_wallet_state, AccountPrivKeys, AccountPubKeys, HdKey, WalletState};
 use crate::types::WalletKind;
 use chain_core::common::H256;
 use chain_core::init::address::RedeemAddress;
 use chain_core::state::account::StakedStateAddress;
-use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::address::{ExtendedAddr, HashTimeLock};
 use client_common::{
     Error, ErrorKind, MultiSigAddress, PrivateKey, PublicKey, Result, ResultExt, SecKey,
     SecureStorage, Storage,
@@ -123,6 +123,20 @@ pub struct WalletInfo {
     /// hdkey for hd wallet and hw wallet
     #[serde(deserialize_with = "deserde_from_str", serialize_with = "serde_to_str")]
     pub hdkey: Option<HdKey>,
+    /// account public keys for watch-only wallet
+    #[serde(
+        default,
+        deserialize_with = "deserde_from_str",
+        serialize_with = "serde_to_str"
+    )]
+    pub account_pubkeys: Option<AccountPubKeys>,
+    /// account private keys for hd wallet restored without the seed
+    #[serde(
+        default,
+        deserialize_with = "deserde_from_str",
+        serialize_with = "serde_to_str"
+    )]
+    pub account_privkeys: Option<AccountPrivKeys>,
     /// hex encoded root_hash -> parity_scale_codec encoded multisig_address pairs
     #[serde(deserialize_with = "deserde_from_str", serialize_with = "serde_to_str")]
     pub multisig_address_pair: BTreeMap<String, MultiSigAddress>,
@@ -210,6 +224,12 @@ impl Wallet {
             .map(ExtendedAddr::OrTree)
             .collect()
     }
+
+    /// Returns `true` if the wallet can claim or refund the hash-timelocked output
+    /// (i.e. its recipient or refund address belongs to the wallet)
+    pub fn is_hash_time_lock_party(&self, lock: &HashTimeLock) -> bool {
+        self.root_hashes.contains(&lock.recipient) || self.root_hashes.contains(&lock.refund)
+    }
 }
 
 fn read_pubkey<S: SecureStorage>(storage: &S, keyspace: &str, key: &str) -> Result<PublicKey> {
@@ -536,6 +556,8 @@ where
                     return Ok(Some(roothash_found));
                 }
             }
+            // spent with the dedicated witnesses (see `WalletClient::claim_hash_time_lock`)
+            ExtendedAddr::HashTimeLock(_) => {}
         }
 
         Err(Error::new(ErrorKind::InvalidInput, "private_key not found"))
@@ -1002,6 +1024,8 @@ mod test {
             key_pairs,
             key_chainpath,
             hdkey: Some(HdKey::default()),
+            account_pubkeys: None,
+            account_privkeys: None,
             multisig_address_pair,
         };
         let s = serde_json::to_string(&info);
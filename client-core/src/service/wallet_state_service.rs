@@ -1,4 +1,4 @@
-use parity_scale_codec::{Decode, Encode};
+use parity_scale_codec::{Decode, Encode, Error as CodecError, Input};
 use std::collections::BTreeMap;
 
 use chain_core::{
@@ -87,6 +87,19 @@ where
             .collect())
     }
 
+    /// Returns currently stored unspent hash-timelocked outputs which can be claimed or refunded
+    /// by given wallet
+    #[inline]
+    pub fn get_hash_time_locked_outputs(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+    ) -> Result<BTreeMap<TxoPointer, TxOut>> {
+        Ok(self
+            .get_wallet_state(name, enckey)?
+            .hash_time_locked_outputs)
+    }
+
     /// Returns currently stored transaction history for given wallet
     #[inline]
     pub fn get_transaction_history(
@@ -241,7 +254,7 @@ pub fn delete_wallet_state<S: Storage>(storage: &S, name: &str) -> Result<()> {
 }
 
 /// Wallet state
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode)]
 pub struct WalletState {
     /// UTxO
     pub unspent_transactions: BTreeMap<TxoPointer, TxOut>,
@@ -251,6 +264,30 @@ pub struct WalletState {
     pub transaction_history: BTreeMap<TxId, TransactionChange>,
     /// Transaction ids ordered by insert order.
     pub transaction_log: Vec<TxId>,
+    /// Unspent hash-timelocked outputs whose recipient or refund address belongs to the wallet
+    /// (they aren't in the balance, as they are spent with the dedicated witnesses)
+    pub hash_time_locked_outputs: BTreeMap<TxoPointer, TxOut>,
+}
+
+impl Decode for WalletState {
+    fn decode<I: Input>(input: &mut I) -> std::result::Result<Self, CodecError> {
+        let unspent_transactions = BTreeMap::decode(input)?;
+        let pending_transactions = BTreeMap::decode(input)?;
+        let transaction_history = BTreeMap::decode(input)?;
+        let transaction_log = Vec::decode(input)?;
+        // the states stored before the hash-timelocked outputs were tracked end here
+        let hash_time_locked_outputs = match input.remaining_len()? {
+            Some(0) => BTreeMap::new(),
+            _ => BTreeMap::decode(input)?,
+        };
+        Ok(WalletState {
+            unspent_transactions,
+            pending_transactions,
+            transaction_history,
+            transaction_log,
+            hash_time_locked_outputs,
+        })
+    }
 }
 
 impl Default for WalletState {
@@ -261,6 +298,7 @@ impl Default for WalletState {
             pending_transactions: Default::default(),
             transaction_history: Default::default(),
             transaction_log: vec![],
+            hash_time_locked_outputs: Default::default(),
         }
     }
 }
@@ -361,6 +399,13 @@ impl WalletState {
             MementoOperation::RemoveUnspentTransaction(ref input) => {
                 self.unspent_transactions.remove(input);
             }
+            MementoOperation::AddHashTimeLockedOutput(ref input, ref output) => {
+                self.hash_time_locked_outputs
+                    .insert(input.clone(), output.clone());
+            }
+            MementoOperation::RemoveHashTimeLockedOutput(ref input) => {
+                self.hash_time_locked_outputs.remove(input);
+            }
             MementoOperation::AddPendingTransaction(ref transaction_id, ref pending_info) => {
                 if !self.pending_transactions.contains_key(transaction_id) {
                     let _ = self
@@ -408,6 +453,8 @@ enum MementoOperation {
     AddPendingTransaction(TxId, TransactionPending),
     RemovePendingTransaction(TxId),
     RemoveUnspentTransaction(TxoPointer),
+    AddHashTimeLockedOutput(TxoPointer, TxOut),
+    RemoveHashTimeLockedOutput(TxoPointer),
 }
 
 impl WalletStateMemento {
@@ -447,6 +494,20 @@ impl WalletStateMemento {
         self.0
             .push(MementoOperation::RemovePendingTransaction(tx_id))
     }
+
+    /// Adds unspent hash-timelocked output to memento
+    #[inline]
+    pub fn add_hash_time_locked_output(&mut self, input: TxoPointer, output: TxOut) {
+        self.0
+            .push(MementoOperation::AddHashTimeLockedOutput(input, output))
+    }
+
+    /// Removes unspent hash-timelocked output from memento
+    #[inline]
+    pub fn remove_hash_time_locked_output(&mut self, input: TxoPointer) {
+        self.0
+            .push(MementoOperation::RemoveHashTimeLockedOutput(input))
+    }
 }
 
 #[cfg(test)]
@@ -749,4 +810,35 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn check_wallet_state_decoding() {
+        let mut state = WalletState::default();
+        let pointer = TxoPointer::new([1; 32], 0);
+        let output = TxOut::new(ExtendedAddr::OrTree([2; 32]), Coin::unit());
+        state
+            .unspent_transactions
+            .insert(pointer.clone(), output.clone());
+        state
+            .hash_time_locked_outputs
+            .insert(pointer.clone(), output.clone());
+        let decoded = WalletState::decode(&mut state.encode().as_slice()).unwrap();
+        assert_eq!(decoded.unspent_transactions, state.unspent_transactions);
+        assert_eq!(
+            decoded.hash_time_locked_outputs,
+            state.hash_time_locked_outputs
+        );
+
+        // the state stored without the hash-timelocked outputs
+        let old_state = (
+            &state.unspent_transactions,
+            &state.pending_transactions,
+            &state.transaction_history,
+            &state.transaction_log,
+        )
+            .encode();
+        let decoded = WalletState::decode(&mut old_state.as_slice()).unwrap();
+        assert_eq!(decoded.unspent_transactions, state.unspent_transactions);
+        assert!(decoded.hash_time_locked_outputs.is_empty());
+    }
 }
@@ -1,9 +1,11 @@
 //! Wallet signer responsible for signing as wallet
-use chain_core::common::H256;
-use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::common::{Proof, H256};
+use chain_core::tx::data::address::{ExtendedAddr, HashTimeLock};
+use chain_core::tx::witness::tree::RawXOnlyPubkey;
 use chain_core::tx::witness::{TxInWitness, TxWitness};
 use client_common::Transaction;
 use client_common::{Error, ErrorKind, Result, ResultExt, SecKey, Storage};
+use secp256k1::schnorrsig::SchnorrSignature;
 
 use crate::service::{HwKeyService, KeyService, RootHashService, WalletService};
 use crate::types::WalletKind;
@@ -128,7 +130,8 @@ where
                 )
             })?;
 
-        self.schnorr_sign_with_root_hash(tx, &root_hash)
+        let (signature, proof) = self.schnorr_sign_with_root_hash(tx, &root_hash)?;
+        Ok(TxInWitness::TreeSig(signature, proof))
     }
 }
 
@@ -136,12 +139,44 @@ impl<'a, S> WalletSigner<'a, S>
 where
     S: Storage,
 {
+    /// Schnorr signs transaction spending a hash-timelocked output: claims it with the hash `preimage`
+    /// (as its recipient) or gets it refunded if no preimage is provided
+    pub fn schnorr_sign_hash_time_lock(
+        &self,
+        tx: &Transaction,
+        lock: &HashTimeLock,
+        preimage: Option<H256>,
+    ) -> Result<TxInWitness> {
+        let signing_addr = match preimage {
+            Some(_) => ExtendedAddr::OrTree(lock.recipient),
+            None => ExtendedAddr::OrTree(lock.refund),
+        };
+        let root_hash = self
+            .wallet_service
+            .find_root_hash(self.name, self.enckey, &signing_addr)?
+            .chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Hash-timelocked output's address ({}) does not belong to wallet with name: {}",
+                        signing_addr, self.name
+                    ),
+                )
+            })?;
+
+        let (signature, proof) = self.schnorr_sign_with_root_hash(tx, &root_hash)?;
+        match preimage {
+            Some(preimage) => Ok(TxInWitness::HashLockClaim(preimage, signature, proof)),
+            None => Ok(TxInWitness::TimeLockRefund(signature, proof)),
+        }
+    }
+
     /// Schnorr signs message with private key corresponding to `self_public_key` in given 1-of-n root hash
     fn schnorr_sign_with_root_hash(
         &self,
         tx: &Transaction,
         root_hash: &H256,
-    ) -> Result<TxInWitness> {
+    ) -> Result<(SchnorrSignature, Proof<RawXOnlyPubkey>)> {
         if self
             .root_hash_service
             .required_signers(self.name, &root_hash, self.enckey)?
@@ -167,6 +202,12 @@ where
                     .chain(|| (ErrorKind::PermissionDenied, "can not find chain path"))?;
                 self.hw_key_service.get_sign_key(&chain_path)?
             }
+            WalletKind::WatchOnly => {
+                return Err(Error::new(
+                    ErrorKind::PermissionDenied,
+                    "Watch-only wallet can not sign, build a raw transaction and sign it offline",
+                ));
+            }
             WalletKind::Basic | WalletKind::HD => {
                 let private_key = self
                     .wallet_service
@@ -191,7 +232,7 @@ where
             self.enckey,
         )?;
 
-        Ok(TxInWitness::TreeSig(sign_key.schnorr_sign(tx)?, proof))
+        Ok((sign_key.schnorr_sign(tx)?, proof))
     }
 }
 
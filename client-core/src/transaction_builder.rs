@@ -10,6 +10,7 @@ pub use raw_transfer_transaction_builder::{
 };
 pub use unauthorized_wallet_transaction_builder::UnauthorizedWalletTransactionBuilder;
 
+use chain_core::common::H256;
 use chain_core::init::coin::Coin;
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
@@ -18,6 +19,7 @@ use chain_core::tx::data::output::TxOut;
 use chain_core::tx::TxAux;
 use client_common::{PrivateKey, Result, SecKey, SignedTransaction, Transaction};
 
+use crate::unspent_transactions::Selection;
 use crate::UnspentTransactions;
 use chain_core::tx::data::TxId;
 
@@ -31,6 +33,7 @@ pub trait WalletTransactionBuilder: Send + Sync + Clone {
     /// - `name`: Name of wallet
     /// - `enckey`: Encryption key of wallet
     /// - `unspent_transactions`: Unspent transactions
+    /// - `selection`: Rules to select the unspent transactions
     /// - `outputs`: Transaction outputs
     /// - `return_address`: Address to which change amount will get returned
     /// - `attributes`: Transaction attributes,
@@ -44,11 +47,32 @@ pub trait WalletTransactionBuilder: Send + Sync + Clone {
         name: &str,
         enckey: &SecKey,
         unspent_transactions: UnspentTransactions,
+        selection: Selection,
         outputs: Vec<TxOut>,
         return_address: ExtendedAddr,
         attributes: TxAttributes,
     ) -> Result<(TxAux, Vec<TxoPointer>, Coin)>;
 
+    /// Builds a transfer transaction spending a hash-timelocked output
+    ///
+    /// # Attributes
+    ///
+    /// - `name`: Name of wallet
+    /// - `enckey`: Encryption key of wallet
+    /// - `input`: The hash-timelocked output (and its pointer)
+    /// - `preimage`: The hash preimage if the output is claimed, `None` if it is refunded
+    /// - `to_address`: Address to which the output value (minus fee) will be transferred
+    /// - `attributes`: Transaction attributes,
+    fn build_hash_time_lock_tx(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        input: (TxoPointer, TxOut),
+        preimage: Option<H256>,
+        to_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<TxAux>;
+
     /// Obfuscates given signed transaction
     fn obfuscate(&self, signed_transaction: SignedTransaction) -> Result<TxAux>;
 
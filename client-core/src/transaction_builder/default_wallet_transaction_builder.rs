@@ -1,3 +1,4 @@
+use chain_core::common::H256;
 use chain_core::init::coin::{sum_coins, Coin};
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
@@ -6,12 +7,13 @@ use chain_core::tx::data::output::TxOut;
 use chain_core::tx::fee::FeeAlgorithm;
 use chain_core::tx::TxAux;
 use client_common::{
-    ErrorKind, PrivateKey, Result, ResultExt, SecKey, SignedTransaction, Storage, Transaction,
-    TransactionObfuscation,
+    Error, ErrorKind, PrivateKey, Result, ResultExt, SecKey, SignedTransaction, Storage,
+    Transaction, TransactionObfuscation,
 };
 
 use crate::signer::WalletSignerManager;
 use crate::transaction_builder::RawTransferTransactionBuilder;
+use crate::unspent_transactions::Selection;
 use crate::{SelectedUnspentTransactions, UnspentTransactions, WalletTransactionBuilder};
 use chain_core::tx::{data::TxId, TransactionId};
 
@@ -53,6 +55,7 @@ where
         name: &str,
         enckey: &SecKey,
         unspent_transactions: UnspentTransactions,
+        selection: Selection,
         outputs: Vec<TxOut>,
         return_address: ExtendedAddr,
         attributes: TxAttributes,
@@ -61,6 +64,7 @@ where
     ) -> Result<(TxAux, Vec<TxoPointer>, Coin)> {
         let mut raw_builder = self.select_and_build(
             &unspent_transactions,
+            selection,
             outputs,
             return_address.clone(),
             attributes,
@@ -100,6 +104,7 @@ where
         name: &str,
         enckey: &SecKey,
         unspent_transactions: UnspentTransactions,
+        selection: Selection,
         outputs: Vec<TxOut>,
         return_address: ExtendedAddr,
         attributes: TxAttributes,
@@ -108,6 +113,7 @@ where
             name,
             enckey,
             unspent_transactions,
+            selection,
             outputs,
             return_address,
             attributes,
@@ -115,6 +121,69 @@ where
         )
     }
 
+    fn build_hash_time_lock_tx(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        input: (TxoPointer, TxOut),
+        preimage: Option<H256>,
+        to_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<TxAux> {
+        let lock = match input.1.address {
+            ExtendedAddr::HashTimeLock(ref lock) => lock.clone(),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Input is not a hash-timelocked output",
+                ))
+            }
+        };
+        let input_value = input.1.value;
+        let signer =
+            self.signer_manager
+                .create_signer(name, enckey, &self.signer_manager.hw_key_service);
+
+        // the fee is paid from the output value, so it's computed on the signed transaction
+        // (the claim witness is larger than the dummy one)
+        let fee_algorithm = self.fee_algorithm.for_build();
+        let mut fees = Coin::zero();
+        loop {
+            let output_value = (input_value - fees).chain(|| {
+                (
+                    ErrorKind::IllegalInput,
+                    "Hash-timelocked output value is not enough for the fee",
+                )
+            })?;
+            let mut raw_builder =
+                RawTransferTransactionBuilder::new(attributes.clone(), fee_algorithm.clone());
+            raw_builder.add_input(input.clone(), 1);
+            raw_builder.add_output(TxOut::new(to_address.clone(), output_value));
+            let witness = signer.schnorr_sign_hash_time_lock(
+                &raw_builder.to_transaction(),
+                &lock,
+                preimage,
+            )?;
+            raw_builder.add_witness(0, witness)?;
+
+            let tx_aux = raw_builder.to_tx_aux(self.transaction_obfuscation.clone())?;
+            let new_fees = fee_algorithm
+                .calculate_for_txaux(&tx_aux)
+                .chain(|| {
+                    (
+                        ErrorKind::IllegalInput,
+                        "Fee exceeds maximum allowed amount",
+                    )
+                })?
+                .to_coin();
+            if new_fees > fees {
+                fees = new_fees;
+            } else {
+                break Ok(tx_aux);
+            }
+        }
+    }
+
     #[inline]
     fn obfuscate(&self, signed_transaction: SignedTransaction) -> Result<TxAux> {
         self.transaction_obfuscation.encrypt(signed_transaction)
@@ -157,6 +226,7 @@ where
     pub fn select_and_build<'a>(
         &self,
         unspent_transactions: &'a UnspentTransactions,
+        selection: Selection,
         outputs: Vec<TxOut>,
         return_address: ExtendedAddr,
         attributes: TxAttributes,
@@ -169,16 +239,20 @@ where
                 "Sum of output values exceeds maximum allowed amount",
             )
         })?;
+        let fee_algorithm = self.fee_algorithm.for_build();
         let mut fees = Coin::zero();
         let raw_tx_builder = loop {
-            let (selected_unspent_txs, change_amount) =
-                unspent_transactions.select((output_value + fees).chain(|| {
+            let (selected_unspent_txs, change_amount) = unspent_transactions.select_with(
+                (output_value + fees).chain(|| {
                     (
                         ErrorKind::IllegalInput,
                         "Sum of output values and fee exceeds maximum allowed amount",
                     )
-                })?)?;
+                })?,
+                selection,
+            )?;
             let raw_tx_builder = self.build_raw_transaction(
+                fee_algorithm.clone(),
                 &selected_unspent_txs,
                 &outputs,
                 return_address.clone(),
@@ -198,8 +272,10 @@ where
         Ok(raw_tx_builder)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn build_raw_transaction(
         &self,
+        fee_algorithm: F,
         selected_unspent_transactions: &SelectedUnspentTransactions<'_>,
         outputs: &[TxOut],
         return_address: ExtendedAddr,
@@ -208,8 +284,7 @@ where
         // FIXME: this should be per SelectedUnspentTransactions
         threshold: u16,
     ) -> RawTransferTransactionBuilder<F> {
-        let mut raw_tx_builder =
-            RawTransferTransactionBuilder::new(attributes, self.fee_algorithm.clone());
+        let mut raw_tx_builder = RawTransferTransactionBuilder::new(attributes, fee_algorithm);
         for input in selected_unspent_transactions.iter() {
             raw_tx_builder.add_input(input.clone(), threshold);
         }
@@ -234,6 +309,7 @@ mod default_wallet_transaction_builder_tests {
     use chain_core::tx::data::input::{TxoPointer, TxoSize};
     use chain_core::tx::data::TxId;
     use chain_core::tx::fee::{LinearFee, Milli};
+    use chain_core::tx::witness::TxInWitness;
     use chain_core::tx::{PlainTxAux, TransactionId, TxAux, TxEnclaveAux, TxObfuscated};
     use chain_tx_validation::witness::verify_tx_address;
     use client_common::storage::MemoryStorage;
@@ -353,6 +429,7 @@ mod default_wallet_transaction_builder_tests {
                 name,
                 &enckey,
                 unspent_transactions.clone(),
+                Selection::default(),
                 outputs,
                 return_address,
                 attributes,
@@ -479,6 +556,7 @@ mod default_wallet_transaction_builder_tests {
                     name,
                     &enckey,
                     unspent_transactions.clone(),
+                    Selection::default(),
                     outputs,
                     return_address,
                     attributes,
@@ -487,4 +565,95 @@ mod default_wallet_transaction_builder_tests {
                 .kind()
         );
     }
+
+    #[test]
+    fn check_hash_time_lock_flow() {
+        let name = "name";
+        let passphrase = SecUtf8::from("passphrase");
+
+        let storage = MemoryStorage::default();
+        let wallet_client = DefaultWalletClient::new_read_only(storage.clone());
+
+        let (enckey, _) = wallet_client
+            .new_wallet(name, &passphrase, WalletKind::Basic, None)
+            .unwrap();
+
+        let recipient = wallet_client.new_transfer_address(name, &enckey).unwrap();
+        let preimage = [0x42; 32];
+        let address = wallet_client
+            .new_hash_time_lock_address(
+                name,
+                &enckey,
+                &recipient,
+                chain_tx_validation::witness::hash_preimage(&preimage),
+                1_000,
+            )
+            .unwrap();
+        let input = (
+            TxoPointer::new([0; 32], 0),
+            TxOut::new(address.clone(), Coin::new(1000).unwrap()),
+        );
+
+        let signer_manager = WalletSignerManager::new(storage, HwKeyService::default());
+        let fee_algorithm =
+            LinearFee::new(Milli::try_new(1, 1).unwrap(), Milli::try_new(1, 1).unwrap());
+        let transaction_builder = DefaultWalletTransactionBuilder::new(
+            signer_manager,
+            fee_algorithm,
+            MockTransactionCipher,
+        );
+
+        for preimage in [Some(preimage), None].iter() {
+            let tx_aux = transaction_builder
+                .build_hash_time_lock_tx(
+                    name,
+                    &enckey,
+                    input.clone(),
+                    *preimage,
+                    recipient.clone(),
+                    TxAttributes::new(171),
+                )
+                .unwrap();
+            let fee = fee_algorithm
+                .calculate_for_txaux(&tx_aux)
+                .unwrap()
+                .to_coin();
+
+            match tx_aux {
+                TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+                    payload: TxObfuscated { txpayload, .. },
+                    ..
+                }) => {
+                    let (transaction, witness) =
+                        match PlainTxAux::decode(&mut txpayload.as_slice()).unwrap() {
+                            PlainTxAux::TransferTx(transaction, witness) => (transaction, witness),
+                            _ => unreachable!(),
+                        };
+                    assert_eq!((transaction.outputs[0].value + fee).unwrap(), input.1.value);
+                    match (&witness[0], preimage) {
+                        (TxInWitness::HashLockClaim(..), Some(_))
+                        | (TxInWitness::TimeLockRefund(..), None) => {}
+                        _ => panic!("unexpected witness: {}", witness[0]),
+                    }
+                    assert!(verify_tx_address(&witness[0], &transaction.id(), &address).is_ok());
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        // only the hash-timelocked outputs can be claimed
+        assert!(transaction_builder
+            .build_hash_time_lock_tx(
+                name,
+                &enckey,
+                (
+                    input.0,
+                    TxOut::new(recipient.clone(), Coin::new(1000).unwrap())
+                ),
+                Some(preimage),
+                recipient,
+                TxAttributes::new(171),
+            )
+            .is_err());
+    }
 }
@@ -1,3 +1,4 @@
+use chain_core::common::H256;
 use chain_core::init::coin::Coin;
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
@@ -6,6 +7,7 @@ use chain_core::tx::data::output::TxOut;
 use chain_core::tx::TxAux;
 use client_common::{ErrorKind, PrivateKey, Result, SecKey, SignedTransaction, Transaction};
 
+use crate::unspent_transactions::Selection;
 use crate::{UnspentTransactions, WalletTransactionBuilder};
 use chain_core::tx::data::TxId;
 
@@ -20,6 +22,7 @@ impl WalletTransactionBuilder for UnauthorizedWalletTransactionBuilder {
         _: &str,
         _: &SecKey,
         _: UnspentTransactions,
+        _: Selection,
         _: Vec<TxOut>,
         _: ExtendedAddr,
         _: TxAttributes,
@@ -27,6 +30,18 @@ impl WalletTransactionBuilder for UnauthorizedWalletTransactionBuilder {
         Err(ErrorKind::PermissionDenied.into())
     }
 
+    fn build_hash_time_lock_tx(
+        &self,
+        _: &str,
+        _: &SecKey,
+        _: (TxoPointer, TxOut),
+        _: Option<H256>,
+        _: ExtendedAddr,
+        _: TxAttributes,
+    ) -> Result<TxAux> {
+        Err(ErrorKind::PermissionDenied.into())
+    }
+
     fn obfuscate(&self, _: SignedTransaction) -> Result<TxAux> {
         Err(ErrorKind::PermissionDenied.into())
     }
@@ -114,6 +114,8 @@ pub enum TransactionType {
     Unjail,
     /// Nodejoin transaction
     Nodejoin,
+    /// Node metadata update transaction
+    NodeMetadataUpdate,
 }
 
 impl fmt::Display for TransactionType {
@@ -125,6 +127,7 @@ impl fmt::Display for TransactionType {
             TransactionType::Deposit => write!(f, "Deposit"),
             TransactionType::Unjail => write!(f, "Unfail"),
             TransactionType::Nodejoin => write!(f, "Nodejoin"),
+            TransactionType::NodeMetadataUpdate => write!(f, "NodeMetadataUpdate"),
         }
     }
 }
@@ -260,6 +263,7 @@ impl From<&Transaction> for TransactionType {
             Transaction::DepositStakeTransaction(_) => TransactionType::Deposit,
             Transaction::UnjailTransaction(_) => TransactionType::Unjail,
             Transaction::NodejoinTransaction(_) => TransactionType::Nodejoin,
+            Transaction::NodeMetadataUpdateTransaction(_) => TransactionType::NodeMetadataUpdate,
         }
     }
 }
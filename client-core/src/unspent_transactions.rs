@@ -1,6 +1,8 @@
 //! Operations on unspent transactions
 use parity_scale_codec::{Decode, Encode};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 
 use chain_core::init::coin::Coin;
@@ -30,7 +32,7 @@ pub struct UnspentTransactions(Vec<(TxoPointer, TxOut)>);
 /// An iterator over selected unspent transactions
 #[derive(Debug)]
 pub struct SelectedUnspentTransactions<'a> {
-    inner: &'a [(TxoPointer, TxOut)],
+    inner: Cow<'a, [(TxoPointer, TxOut)]>,
 }
 
 impl Deref for UnspentTransactions {
@@ -102,7 +104,7 @@ impl UnspentTransactions {
             if selected_amount >= amount {
                 return Ok((
                     SelectedUnspentTransactions {
-                        inner: &self.0[..=i],
+                        inner: Cow::Borrowed(&self.0[..=i]),
                     },
                     (selected_amount - amount).chain(|| {
                         (
@@ -117,10 +119,217 @@ impl UnspentTransactions {
         Err(Error::new(ErrorKind::InvalidInput, "Insufficient balance"))
     }
 
+    /// Selects unspent transactions for given amount with the rules of `selection` and returns
+    /// difference amount
+    pub fn select_with(
+        &self,
+        amount: Coin,
+        selection: Selection,
+    ) -> Result<(SelectedUnspentTransactions<'_>, Coin)> {
+        let required = selection.required.min(self.0.len());
+        if required == 0 {
+            if let Selector::Prefix = selection.selector {
+                return self.select(amount);
+            }
+        }
+
+        let (required_transactions, candidates) = self.0.split_at(required);
+        let required_amount = total_value(required_transactions);
+        let target = u128::from(u64::from(amount));
+        let indexes = if required_amount >= target {
+            Vec::new()
+        } else {
+            let target = target - required_amount;
+            match selection.selector {
+                Selector::Prefix => select_prefix(candidates, target),
+                Selector::BranchAndBound => select_branch_and_bound(candidates, target),
+                Selector::SameAddress => select_same_address(candidates, target),
+            }
+            .chain(|| (ErrorKind::InvalidInput, "Insufficient balance"))?
+        };
+
+        let selected = required_transactions
+            .iter()
+            .cloned()
+            .chain(indexes.into_iter().map(|i| candidates[i].clone()))
+            .collect::<Vec<_>>();
+        let selected_amount = selected
+            .iter()
+            .try_fold(Coin::zero(), |acc, (_, output)| acc + output.value)
+            .chain(|| {
+                (
+                    ErrorKind::IllegalInput,
+                    "Total amount of selected UTXOs exceeds maximum allowed value",
+                )
+            })?;
+
+        Ok((
+            SelectedUnspentTransactions {
+                inner: Cow::Owned(selected),
+            },
+            (selected_amount - amount).chain(|| {
+                (
+                    ErrorKind::IllegalInput,
+                    "Amount of selected UTXOs is negative",
+                )
+            })?,
+        ))
+    }
+
     /// Selects all unspent transactions
     pub fn select_all(&self) -> SelectedUnspentTransactions<'_> {
-        SelectedUnspentTransactions { inner: &self.0 }
+        SelectedUnspentTransactions {
+            inner: Cow::Borrowed(&self.0),
+        }
+    }
+}
+
+/// Rules to select unspent transactions for an amount
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Selection {
+    /// Number of unspent transactions (at the beginning) which are always selected
+    pub required: usize,
+    /// Selector for the rest of unspent transactions
+    pub selector: Selector,
+}
+
+/// Selectors of unspent transactions
+#[derive(Debug, Clone, Copy)]
+pub enum Selector {
+    /// Selects unspent transactions in order until the amount is reached
+    Prefix,
+    /// Searches for the unspent transactions with the least change (no change output if there
+    /// is an exact match)
+    BranchAndBound,
+    /// Selects all unspent transactions of the fewest addresses, so that outputs received on
+    /// different addresses are not merged if possible
+    SameAddress,
+}
+
+impl Default for Selector {
+    #[inline]
+    fn default() -> Self {
+        Selector::Prefix
+    }
+}
+
+/// Maximum number of steps of branch and bound search
+const BRANCH_AND_BOUND_MAX_TRIES: usize = 100_000;
+
+fn total_value(unspent_transactions: &[(TxoPointer, TxOut)]) -> u128 {
+    unspent_transactions
+        .iter()
+        .map(|(_, output)| u128::from(u64::from(output.value)))
+        .sum()
+}
+
+fn select_prefix(candidates: &[(TxoPointer, TxOut)], target: u128) -> Option<Vec<usize>> {
+    let mut selected_amount = 0;
+    for (i, (_, output)) in candidates.iter().enumerate() {
+        selected_amount += u128::from(u64::from(output.value));
+        if selected_amount >= target {
+            return Some((0..=i).collect());
+        }
+    }
+    None
+}
+
+/// Depth first search over the candidates in decreasing order of value, a branch is cut when
+/// its amount reaches the target or can't reach it anymore
+fn select_branch_and_bound(candidates: &[(TxoPointer, TxOut)], target: u128) -> Option<Vec<usize>> {
+    let mut order = (0..candidates.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| candidates[b].1.value.cmp(&candidates[a].1.value));
+    let values = order
+        .iter()
+        .map(|&i| u128::from(u64::from(candidates[i].1.value)))
+        .collect::<Vec<_>>();
+    // remaining[i]: sum of the values from i
+    let mut remaining = vec![0; values.len() + 1];
+    for i in (0..values.len()).rev() {
+        remaining[i] = remaining[i + 1] + values[i];
+    }
+    if remaining[0] < target {
+        return None;
+    }
+
+    let mut best: Option<(u128, Vec<usize>)> = None;
+    let mut current = Vec::new();
+    let mut current_amount = 0;
+    let mut index = 0;
+    let mut tries = 0;
+    loop {
+        tries += 1;
+        let backtrack = if current_amount >= target {
+            let change = current_amount - target;
+            if best
+                .as_ref()
+                .map_or(true, |(best_change, _)| change < *best_change)
+            {
+                best = Some((change, current.clone()));
+            }
+            true
+        } else {
+            index >= values.len() || current_amount + remaining[index] < target
+        };
+
+        if backtrack {
+            if best.as_ref().map_or(false, |(change, _)| *change == 0)
+                || tries >= BRANCH_AND_BOUND_MAX_TRIES
+            {
+                break;
+            }
+            // exclude the last included candidate and continue with the next one
+            match current.pop() {
+                Some(last) => {
+                    current_amount -= values[last];
+                    index = last + 1;
+                }
+                None => break,
+            }
+        } else {
+            current.push(index);
+            current_amount += values[index];
+            index += 1;
+        }
+    }
+
+    best.map(|(_, selected)| selected.into_iter().map(|i| order[i]).collect())
+}
+
+fn select_same_address(candidates: &[(TxoPointer, TxOut)], target: u128) -> Option<Vec<usize>> {
+    // indexes and amount of the unspent transactions of each address
+    let mut groups: Vec<(Vec<usize>, u128)> = Vec::new();
+    let mut group_index = HashMap::new();
+    for (i, (_, output)) in candidates.iter().enumerate() {
+        let group = *group_index.entry(&output.address).or_insert_with(|| {
+            groups.push((Vec::new(), 0));
+            groups.len() - 1
+        });
+        groups[group].0.push(i);
+        groups[group].1 += u128::from(u64::from(output.value));
+    }
+
+    // a single address with the least change
+    if let Some((indexes, _)) = groups
+        .iter()
+        .filter(|(_, amount)| *amount >= target)
+        .min_by_key(|(_, amount)| *amount)
+    {
+        return Some(indexes.clone());
+    }
+
+    // otherwise the fewest addresses
+    groups.sort_by(|a, b| b.1.cmp(&a.1));
+    let mut selected = Vec::new();
+    let mut selected_amount = 0;
+    for (indexes, amount) in groups {
+        selected.extend(indexes);
+        selected_amount += amount;
+        if selected_amount >= target {
+            return Some(selected);
+        }
     }
+    None
 }
 
 /// Builder for unspent transactions
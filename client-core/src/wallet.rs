@@ -12,7 +12,7 @@ use secp256k1::schnorrsig::SchnorrSignature;
 use secstr::SecUtf8;
 use std::collections::BTreeSet;
 
-use chain_core::common::{Proof, H256};
+use chain_core::common::{Proof, Timespec, H256};
 use chain_core::init::address::RedeemAddress;
 use chain_core::init::coin::Coin;
 use chain_core::state::account::StakedStateAddress;
@@ -33,10 +33,10 @@ use client_common::{
 use serde::{Deserialize, Serialize};
 
 use crate::hd_wallet::HardwareKind;
-use crate::service::{SyncState, WalletInfo};
+use crate::service::{AccountPrivKeys, AccountPubKeys, HDAccountType, SyncState, WalletInfo};
 use crate::transaction_builder::{SignedTransferTransaction, UnsignedTransferTransaction};
 use crate::types::{AddressType, TransactionChange, TransactionPending, WalletBalance, WalletKind};
-use crate::{InputSelectionStrategy, Mnemonic, UnspentTransactions};
+use crate::{InputSelection, Mnemonic, UnspentTransactions};
 
 /// information needed when create/delete a wallet
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -77,6 +77,7 @@ pub trait WalletClient: Send + Sync {
         address: ExtendedAddr,
         view_keys: &mut BTreeSet<PublicKey>,
         network_id: u8,
+        input_selection: Option<InputSelection>,
     ) -> Result<TxId>;
 
     /// send balance to a transfer address, waiting it transaction confirmed then return transaction id
@@ -132,6 +133,35 @@ pub trait WalletClient: Send + Sync {
         view_key: &PrivateKey,
     ) -> Result<SecKey>;
 
+    /// Restores a watch-only HD wallet from the account public keys and the private view key
+    fn restore_watch_only_wallet(
+        &self,
+        name: &str,
+        passphrase: &SecUtf8,
+        account_pubkeys: &AccountPubKeys,
+        view_key: &PrivateKey,
+    ) -> Result<SecKey>;
+
+    /// Exports the account public keys of a HD wallet (to restore its watch-only counterpart)
+    fn export_account_pubkeys(&self, name: &str, enckey: &SecKey) -> Result<AccountPubKeys>;
+
+    /// Restores a HD wallet from the account private keys (when the mnemonic is not available)
+    fn restore_wallet_from_account_privkeys(
+        &self,
+        name: &str,
+        passphrase: &SecUtf8,
+        account_privkeys: &AccountPrivKeys,
+    ) -> Result<SecKey>;
+
+    /// Exports the serialized (BIP32) account-level extended key (`xprv` if `private`, else `xpub`)
+    fn export_extended_key(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_type: HDAccountType,
+        private: bool,
+    ) -> Result<SecUtf8>;
+
     /// Remove a wallet
     fn delete_wallet(&self, name: &str, passphrase: &SecUtf8) -> Result<()>;
 
@@ -318,7 +348,7 @@ pub trait WalletClient: Send + Sync {
     /// - `enckey`: Passphrase of wallet
     /// - `outputs`: Transaction outputs
     /// - `attributes`: Transaction attributes,
-    /// - `input_selection_strategy`: Strategy to use while selecting unspent transactions
+    /// - `input_selection`: Strategy and coin control to use while selecting unspent transactions
     /// - `return_address`: Address to which change amount will get returned
     fn create_transaction(
         &self,
@@ -326,10 +356,68 @@ pub trait WalletClient: Send + Sync {
         enckey: &SecKey,
         outputs: Vec<TxOut>,
         attributes: TxAttributes,
-        input_selection_strategy: Option<InputSelectionStrategy>,
+        input_selection: Option<InputSelection>,
         return_address: ExtendedAddr,
     ) -> Result<(TxAux, Vec<TxoPointer>, Coin)>;
 
+    /// Creates a hash-timelocked address: the output can be claimed by `recipient` with the preimage
+    /// of `hash` before `deadline`, or refunded to a new transfer address of this wallet after it.
+    /// The address can be used in the transaction outputs as any other transfer address.
+    fn new_hash_time_lock_address(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        recipient: &ExtendedAddr,
+        hash: H256,
+        deadline: Timespec,
+    ) -> Result<ExtendedAddr>;
+
+    /// Returns the unspent hash-timelocked outputs (tracked by the wallet sync)
+    /// which can be claimed or refunded by this wallet
+    fn hash_time_locked_outputs(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+    ) -> Result<Vec<(TxoPointer, TxOut)>>;
+
+    /// Builds a transaction claiming a hash-timelocked output (locked to this wallet's address)
+    ///
+    /// # Attributes
+    ///
+    /// - `name`: Name of wallet
+    /// - `enckey`: Passphrase of wallet
+    /// - `input`: Pointer to the hash-timelocked output (see `hash_time_locked_outputs`)
+    /// - `preimage`: The preimage of the output's hash
+    /// - `to_address`: Address to which the output value (minus fee) will be transferred
+    /// - `attributes`: Transaction attributes,
+    fn claim_hash_time_lock(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        input: &TxoPointer,
+        preimage: H256,
+        to_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<TxAux>;
+
+    /// Builds a transaction refunding an expired hash-timelocked output (created by this wallet)
+    ///
+    /// # Attributes
+    ///
+    /// - `name`: Name of wallet
+    /// - `enckey`: Passphrase of wallet
+    /// - `input`: Pointer to the hash-timelocked output (see `hash_time_locked_outputs`)
+    /// - `to_address`: Address to which the output value (minus fee) will be transferred
+    /// - `attributes`: Transaction attributes,
+    fn refund_hash_time_lock(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        input: &TxoPointer,
+        to_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<TxAux>;
+
     /// Broadcasts a transaction to Crypto.com Chain
     fn broadcast_transaction(&self, tx_aux: &TxAux) -> Result<BroadcastTxResponse>;
 
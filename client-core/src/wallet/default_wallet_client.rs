@@ -5,20 +5,21 @@ use crate::transaction_builder::{SignedTransferTransaction, UnsignedTransferTran
 use crate::types::{
     AddressType, BalanceChange, TransactionChange, TransactionPending, WalletBalance, WalletKind,
 };
+use crate::unspent_transactions::Selection;
 use crate::wallet::syncer::{get_genesis_sync_state, AddressRecovery};
 use crate::wallet::syncer_logic::create_transaction_change;
 #[cfg(feature = "experimental")]
 use crate::MultiSigWalletClient;
 use crate::{
-    InputSelectionStrategy, Mnemonic, UnspentTransactions, WalletClient, WalletTransactionBuilder,
+    InputSelection, Mnemonic, UnspentTransactions, WalletClient, WalletTransactionBuilder,
 };
 use bit_vec::BitVec;
-use chain_core::common::{Proof, H256};
+use chain_core::common::{Proof, Timespec, H256};
 use chain_core::init::address::RedeemAddress;
 use chain_core::init::coin::Coin;
 use chain_core::state::account::StakedStateAddress;
 use chain_core::tx::data::access::{TxAccess, TxAccessPolicy};
-use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::address::{ExtendedAddr, HashTimeLock};
 use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::{str2txid, TxoPointer};
 use chain_core::tx::data::output::TxOut;
@@ -142,7 +143,23 @@ where
     C: Client,
     T: WalletTransactionBuilder,
 {
-    // new_address: transfer address in TxOut, it will check whether it belongs with 20 window, then it will create 20 new addresses
+    fn prepare_lookahead(
+        &mut self,
+        name: &str,
+        enckey: &SecKey,
+        wallet: &mut Wallet,
+        gap_limit: u32,
+    ) -> Result<bool> {
+        let transfer =
+            self.derive_addresses_until(name, enckey, wallet, HDAccountType::Transfer, gap_limit)?;
+        let staking =
+            self.derive_addresses_until(name, enckey, wallet, HDAccountType::Staking, gap_limit)?;
+        Ok(transfer || staking)
+    }
+
+    // new_address: transfer address in TxOut, it will check whether it belongs to the last
+    // `gap_limit` derived addresses or the next `gap_limit` ones, then it will derive new
+    // addresses until there are `gap_limit` addresses after it
     // return: true means new addresses are generated, so need to refresh current wallet state to bring new addresses
     // return: false mean no new addresses, don't need to refresh wallet state
     fn recover_addresses(
@@ -151,54 +168,175 @@ where
         name: &str,
         enckey: &SecKey,
         wallet: &mut Wallet,
+        gap_limit: u32,
     ) -> Result<bool> {
-        let is_exist = self
-            .wallet_service
-            .find_root_hash(name, enckey, &extended_addr)
-            .is_ok();
-        if is_exist {
-            // no need
+        let found = self.find_in_window(
+            name,
+            enckey,
+            HDAccountType::Transfer,
+            gap_limit,
+            |public_key| {
+                let (h256, _multisigaddr) = RootHashService::<S>::peek_new_root_hash(
+                    vec![public_key.clone()],
+                    public_key.clone(),
+                    1,
+                )?;
+                Ok(extended_addr == &ExtendedAddr::OrTree(h256))
+            },
+        )?;
+
+        match found {
+            Some(index) => self.derive_addresses_until(
+                name,
+                enckey,
+                wallet,
+                HDAccountType::Transfer,
+                index.saturating_add(gap_limit),
+            ),
+            None => Ok(false),
+        }
+    }
+
+    fn recover_staking_addresses(
+        &mut self,
+        staking_address: &StakedStateAddress,
+        name: &str,
+        enckey: &SecKey,
+        wallet: &mut Wallet,
+        gap_limit: u32,
+    ) -> Result<bool> {
+        if !self
+            .hd_key_service
+            .can_derive_pubkey(name, enckey, HDAccountType::Staking)?
+        {
             return Ok(false);
         }
 
-        let index = self
+        let found = self.find_in_window(
+            name,
+            enckey,
+            HDAccountType::Staking,
+            gap_limit,
+            |public_key| {
+                Ok(staking_address
+                    == &StakedStateAddress::BasicRedeem(RedeemAddress::from(public_key)))
+            },
+        )?;
+
+        match found {
+            Some(index) => self.derive_addresses_until(
+                name,
+                enckey,
+                wallet,
+                HDAccountType::Staking,
+                index.saturating_add(gap_limit),
+            ),
+            None => Ok(false),
+        }
+    }
+}
+
+impl<S, C, T> DefaultWalletClient<S, C, T>
+where
+    S: Storage,
+    C: Client,
+    T: WalletTransactionBuilder,
+{
+    /// Returns the index of the first key matching `is_match` among the last `gap_limit` derived
+    /// keys of the account and the next `gap_limit` ones
+    fn find_in_window<F>(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_type: HDAccountType,
+        gap_limit: u32,
+        is_match: F,
+    ) -> Result<Option<u32>>
+    where
+        F: Fn(&PublicKey) -> Result<bool>,
+    {
+        let latest_index = self
             .hd_key_service
-            .get_latest_transfer_index(name, enckey)?;
-        let mut found = false;
-        let count = 20;
-        for i in index..(index + count) {
-            let publickey = self.hd_key_service.peek_pubkey(name, enckey, i)?;
-            let (h256, _multisigaddr) = RootHashService::<S>::peek_new_root_hash(
-                vec![publickey.clone()],
-                publickey.clone(),
-                1,
-            )?;
-            assert!(32 == h256.len());
-            let extended_addr_from_hash = ExtendedAddr::OrTree(h256);
-            if extended_addr == &extended_addr_from_hash {
-                found = true;
-                break;
+            .get_latest_index(name, enckey, account_type)?;
+        // keys are derived from index 1
+        let start = latest_index.saturating_sub(gap_limit).max(1);
+        let end = latest_index.saturating_add(gap_limit).saturating_add(1);
+        let public_keys =
+            self.hd_key_service
+                .peek_pubkeys(name, enckey, account_type, start..end)?;
+        for (index, public_key) in (start..end).zip(public_keys.iter()) {
+            if is_match(public_key)? {
+                return Ok(Some(index));
             }
         }
+        Ok(None)
+    }
 
-        if !found {
+    /// Derives new addresses of the account until the latest index reaches `index`, and adds them
+    /// to the cached `wallet`
+    fn derive_addresses_until(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        wallet: &mut Wallet,
+        account_type: HDAccountType,
+        index: u32,
+    ) -> Result<bool> {
+        if !self
+            .hd_key_service
+            .can_derive_pubkey(name, enckey, account_type)?
+        {
+            return Ok(false);
+        }
+
+        let latest_index = self
+            .hd_key_service
+            .get_latest_index(name, enckey, account_type)?;
+        if latest_index >= index {
             return Ok(false);
         }
 
-        let count = count;
-        for _i in 0..count {
-            let newaddress: ExtendedAddr = self
-                .new_transfer_address(name, enckey)
-                .expect("get new transfer address");
-            match newaddress {
-                ExtendedAddr::OrTree(ref root_hash) => {
-                    wallet.root_hashes.insert(*root_hash);
+        for _ in latest_index..index {
+            match account_type {
+                HDAccountType::Staking => {
+                    self.new_staking_address(name, enckey)?;
+                }
+                _ => {
+                    if let ExtendedAddr::OrTree(root_hash) =
+                        self.new_transfer_address(name, enckey)?
+                    {
+                        wallet.root_hashes.insert(root_hash);
+                    }
                 }
             }
         }
+        if let HDAccountType::Staking = account_type {
+            wallet
+                .staking_keys
+                .extend(self.wallet_service.staking_keys(name, enckey)?);
+        }
 
         Ok(true)
     }
+
+    /// Returns the unspent hash-timelocked output (and its pointer) tracked by the wallet sync
+    fn hash_time_locked_output(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        input: &TxoPointer,
+    ) -> Result<(TxoPointer, TxOut)> {
+        self.wallet_state_service
+            .get_hash_time_locked_outputs(name, enckey)?
+            .remove(input)
+            .map(|output| (input.clone(), output))
+            .chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    "Unspent hash-timelocked output not found in wallet (sync the wallet first)",
+                )
+            })
+    }
 }
 
 impl<S, C, T> WalletClient for DefaultWalletClient<S, C, T>
@@ -242,6 +380,7 @@ where
         address: ExtendedAddr,
         view_keys: &mut BTreeSet<PublicKey>,
         network_id: u8,
+        input_selection: Option<InputSelection>,
     ) -> Result<TxId> {
         let current_block_height = self.get_current_block_height()?;
         let tx_out = TxOut::new(address, amount);
@@ -262,8 +401,14 @@ where
             TxAttributes::new_with_access(network_id, access_policies.into_iter().collect());
 
         let return_address = self.new_transfer_address(name, enckey)?;
-        let (transaction, selected_inputs, return_amount) =
-            self.create_transaction(name, enckey, vec![tx_out], attributes, None, return_address)?;
+        let (transaction, selected_inputs, return_amount) = self.create_transaction(
+            name,
+            enckey,
+            vec![tx_out],
+            attributes,
+            input_selection,
+            return_address,
+        )?;
 
         self.broadcast_transaction(&transaction)?;
         //update the wallet state
@@ -299,7 +444,8 @@ where
         view_keys: &mut BTreeSet<PublicKey>,
         network_id: u8,
     ) -> Result<TxId> {
-        let tx_id = self.send_to_address(name, enckey, amount, address, view_keys, network_id)?;
+        let tx_id =
+            self.send_to_address(name, enckey, amount, address, view_keys, network_id, None)?;
         let block_height = self.get_current_block_height()?;
         loop {
             // query tx_id from tendermint
@@ -371,6 +517,8 @@ where
 
         // get hdkey
         let hdkey = self.hd_key_service.get_hdkey(name, enckey)?;
+        let account_pubkeys = self.hd_key_service.get_account_pubkeys(name, enckey)?;
+        let account_privkeys = self.hd_key_service.get_account_privkeys(name, enckey)?;
 
         let wallet_info = WalletInfo {
             name: name.into(),
@@ -380,6 +528,8 @@ where
             key_pairs,
             key_chainpath,
             hdkey,
+            account_pubkeys,
+            account_privkeys,
             multisig_address_pair,
         };
         Ok(wallet_info)
@@ -433,6 +583,14 @@ where
         if let Some(hdkey) = wallet_info.hdkey {
             self.hd_key_service.add_hdkey(name, &enckey, hdkey)?;
         }
+        if let Some(account_pubkeys) = wallet_info.account_pubkeys {
+            self.hd_key_service
+                .add_account_pubkeys(name, &enckey, &account_pubkeys)?;
+        }
+        if let Some(account_privkeys) = wallet_info.account_privkeys {
+            self.hd_key_service
+                .add_account_privkeys(name, &enckey, &account_privkeys)?;
+        }
 
         // store multisig address
         for (root_hash_str, multisig_addr) in wallet_info.multisig_address_pair.iter() {
@@ -514,6 +672,10 @@ where
 
                 Ok((enckey, None))
             }
+            WalletKind::WatchOnly => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Watch-only wallet can only be restored from account public keys",
+            )),
         }
     }
 
@@ -564,6 +726,97 @@ where
         Ok(enckey)
     }
 
+    fn restore_watch_only_wallet(
+        &self,
+        name: &str,
+        passphrase: &SecUtf8,
+        account_pubkeys: &AccountPubKeys,
+        view_key_priv: &PrivateKey,
+    ) -> Result<SecKey> {
+        check_passphrase_strength(name, passphrase)?;
+
+        let enckey = derive_enckey(passphrase, name).err_kind(ErrorKind::InvalidInput, || {
+            "unable to derive encryption key from passphrase"
+        })?;
+
+        // the HD key without seed keeps the address indexes
+        self.hd_key_service.add_mnemonic(name, None, &enckey)?;
+        self.hd_key_service
+            .add_account_pubkeys(name, &enckey, account_pubkeys)?;
+
+        let view_key = PublicKey::from(view_key_priv);
+        self.key_service
+            .add_wallet_private_key(name, &view_key_priv, &enckey)?;
+        self.wallet_service
+            .create(name, &enckey, view_key, WalletKind::WatchOnly)?;
+        Ok(enckey)
+    }
+
+    fn export_account_pubkeys(&self, name: &str, enckey: &SecKey) -> Result<AccountPubKeys> {
+        let wallet = self.wallet_service.get_wallet_info(name, enckey)?;
+        match wallet.wallet_kind {
+            WalletKind::HD => self.hd_key_service.export_account_pubkeys(name, enckey),
+            WalletKind::WatchOnly => self
+                .hd_key_service
+                .get_account_pubkeys(name, enckey)?
+                .chain(|| (ErrorKind::InvalidInput, "Account public keys not found")),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Account public keys can only be exported from HD wallet",
+            )),
+        }
+    }
+
+    fn restore_wallet_from_account_privkeys(
+        &self,
+        name: &str,
+        passphrase: &SecUtf8,
+        account_privkeys: &AccountPrivKeys,
+    ) -> Result<SecKey> {
+        check_passphrase_strength(name, passphrase)?;
+
+        let enckey = derive_enckey(passphrase, name).err_kind(ErrorKind::InvalidInput, || {
+            "unable to derive encryption key from passphrase"
+        })?;
+
+        // the keys are derived from the account private keys instead of the (empty) seed
+        self.hd_key_service.add_mnemonic(name, None, &enckey)?;
+        self.hd_key_service
+            .add_account_privkeys(name, &enckey, account_privkeys)?;
+
+        let (public_key, private_key) =
+            self.hd_key_service
+                .generate_keypair(name, &enckey, HDAccountType::Viewkey)?;
+
+        self.key_service
+            .add_wallet_private_key(name, &private_key, &enckey)?;
+
+        self.wallet_service
+            .create(name, &enckey, public_key, WalletKind::HD)?;
+        Ok(enckey)
+    }
+
+    fn export_extended_key(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_type: HDAccountType,
+        private: bool,
+    ) -> Result<SecUtf8> {
+        let wallet = self.wallet_service.get_wallet_info(name, enckey)?;
+        match wallet.wallet_kind {
+            WalletKind::HD | WalletKind::WatchOnly => Ok(SecUtf8::from(
+                self.hd_key_service
+                    .export_extended_key(name, enckey, account_type, private)?
+                    .to_base58(),
+            )),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Extended keys can only be exported from HD or watch-only wallet",
+            )),
+        }
+    }
+
     fn delete_wallet(&self, name: &str, passphrase: &SecUtf8) -> Result<()> {
         // remove from wallet/sync_state/wallet_state/key_service
 
@@ -691,6 +944,7 @@ where
                     })?;
                 self.hw_key_service.get_sign_key(&chain_path)
             }
+            WalletKind::WatchOnly => Err(watch_only_sign_error()),
             _ => {
                 let private_key = self
                     .wallet_service
@@ -774,6 +1028,24 @@ where
                     .add_key_path(name, enckey, &public_key, &hd_path)?;
                 Ok(public_key)
             }
+            WalletKind::WatchOnly => {
+                let account_type = address_type
+                    .chain(|| {
+                        (
+                            ErrorKind::InvalidInput,
+                            "Address type is needed when creating address for watch-only wallet",
+                        )
+                    })?
+                    .into();
+                let (public_key, hd_path) =
+                    self.hd_key_service
+                        .generate_public_key(name, enckey, account_type)?;
+                self.wallet_service
+                    .add_public_key(name, enckey, &public_key)?;
+                self.wallet_service
+                    .add_key_path(name, enckey, &public_key, &hd_path)?;
+                Ok(public_key)
+            }
         }
     }
 
@@ -806,6 +1078,16 @@ where
                     .add_key_path(name, enckey, &public_key, &hd_path)?;
                 public_key
             }
+            WalletKind::WatchOnly => {
+                let (public_key, hd_path) = self.hd_key_service.generate_public_key(
+                    name,
+                    enckey,
+                    HDAccountType::Staking,
+                )?;
+                self.wallet_service
+                    .add_key_path(name, enckey, &public_key, &hd_path)?;
+                public_key
+            }
         };
 
         self.wallet_service
@@ -845,6 +1127,16 @@ where
                     .add_key_path(name, enckey, &public_key, &hd_path)?;
                 public_key
             }
+            WalletKind::WatchOnly => {
+                let (public_key, hd_path) = self.hd_key_service.generate_public_key(
+                    name,
+                    enckey,
+                    HDAccountType::Transfer,
+                )?;
+                self.wallet_service
+                    .add_key_path(name, enckey, &public_key, &hd_path)?;
+                public_key
+            }
         };
         self.wallet_service
             .add_public_key(name, enckey, &public_key)?;
@@ -931,6 +1223,10 @@ where
                 self.root_hash_service
                     .generate_proof(name, address, public_keys, enckey)
             }
+            ExtendedAddr::HashTimeLock(_) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Proofs are generated for the recipient or refund addresses of hash-timelocked outputs",
+            )),
         }
     }
 
@@ -1042,22 +1338,108 @@ where
         enckey: &SecKey,
         outputs: Vec<TxOut>,
         attributes: TxAttributes,
-        input_selection_strategy: Option<InputSelectionStrategy>,
+        input_selection: Option<InputSelection>,
         return_address: ExtendedAddr,
     ) -> Result<(TxAux, Vec<TxoPointer>, Coin)> {
         let mut unspent_transactions = self.unspent_transactions(name, enckey)?;
-        unspent_transactions.apply_all(input_selection_strategy.unwrap_or_default().as_ref());
+        let selection = input_selection
+            .unwrap_or_default()
+            .apply(&mut unspent_transactions)?;
 
         self.transaction_builder.build_transfer_tx(
             name,
             enckey,
             unspent_transactions,
+            selection,
             outputs,
             return_address,
             attributes,
         )
     }
 
+    fn new_hash_time_lock_address(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        recipient: &ExtendedAddr,
+        hash: H256,
+        deadline: Timespec,
+    ) -> Result<ExtendedAddr> {
+        let recipient = match recipient {
+            ExtendedAddr::OrTree(root_hash) => *root_hash,
+            ExtendedAddr::HashTimeLock(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Recipient of hash-timelocked output should be a transfer address",
+                ))
+            }
+        };
+        let refund = match self.new_transfer_address(name, enckey)? {
+            ExtendedAddr::OrTree(root_hash) => root_hash,
+            ExtendedAddr::HashTimeLock(_) => unreachable!("new transfer address is a tree address"),
+        };
+        Ok(ExtendedAddr::HashTimeLock(HashTimeLock {
+            hash,
+            recipient,
+            refund,
+            deadline,
+        }))
+    }
+
+    fn hash_time_locked_outputs(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+    ) -> Result<Vec<(TxoPointer, TxOut)>> {
+        // Check if wallet exists
+        self.wallet_service.view_key(name, enckey)?;
+
+        Ok(self
+            .wallet_state_service
+            .get_hash_time_locked_outputs(name, enckey)?
+            .into_iter()
+            .collect())
+    }
+
+    fn claim_hash_time_lock(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        input: &TxoPointer,
+        preimage: H256,
+        to_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<TxAux> {
+        // To verify if the enckey is correct or not
+        self.wallet_service.view_key(name, enckey)?;
+
+        let input = self.hash_time_locked_output(name, enckey, input)?;
+        self.transaction_builder.build_hash_time_lock_tx(
+            name,
+            enckey,
+            input,
+            Some(preimage),
+            to_address,
+            attributes,
+        )
+    }
+
+    fn refund_hash_time_lock(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        input: &TxoPointer,
+        to_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<TxAux> {
+        // To verify if the enckey is correct or not
+        self.wallet_service.view_key(name, enckey)?;
+
+        let input = self.hash_time_locked_output(name, enckey, input)?;
+        self.transaction_builder
+            .build_hash_time_lock_tx(name, enckey, input, None, to_address, attributes)
+    }
+
     #[inline]
     fn broadcast_transaction(&self, tx_aux: &TxAux) -> Result<BroadcastTxResponse> {
         self.tendermint_client
@@ -1219,6 +1601,7 @@ where
                 name,
                 enckey,
                 unsigned_tx.unspent_transactions,
+                Selection::default(),
                 vec![tx_out],
                 return_address,
                 attributes,
@@ -1302,6 +1685,7 @@ where
                     .chain(|| (ErrorKind::InvalidInput, "can not find chain path"))?;
                 self.hw_key_service.get_sign_key(&chain_path)?
             }
+            WalletKind::WatchOnly => return Err(watch_only_sign_error()),
             _ => {
                 let private_key = self
                     .wallet_service
@@ -1446,6 +1830,13 @@ where
     }
 }
 
+fn watch_only_sign_error() -> Error {
+    Error::new(
+        ErrorKind::PermissionDenied,
+        "Watch-only wallet can not sign, build a raw transaction and sign it offline",
+    )
+}
+
 fn check_passphrase_strength(name: &str, passphrase: &SecUtf8) -> Result<()> {
     // `estimate_password_strength` returns a score between `0-4`. Any score less than 3 should be considered too
     // weak.
@@ -1623,6 +2014,7 @@ mod tests {
                     &name1,
                     &enckey1,
                     &mut dummy_wallet,
+                    20,
                 )
                 .unwrap(),
             true
@@ -1637,12 +2029,63 @@ mod tests {
                     &name1,
                     &enckey1,
                     &mut dummy_wallet,
+                    20,
                 )
                 .unwrap(),
             false
         );
     }
 
+    #[test]
+    fn check_address_lookahead() {
+        let words = Mnemonic::from_secstr(&SecUtf8::from("pony thank pluck sweet bless tuna couple eight stove fluid essay debate cinnamon elite only")).unwrap();
+        let passphrase = SecUtf8::from("123456");
+        let mut client = DefaultWalletClient::new_read_only(MemoryStorage::default());
+        let enckey = client
+            .restore_wallet("restored", &passphrase, &words)
+            .expect("restore wallet");
+        let used_enckey = client
+            .restore_wallet("used", &passphrase, &words)
+            .expect("restore wallet");
+        let view_key = client.view_key("restored", &enckey).unwrap();
+        let mut wallet = Wallet::new(view_key, WalletKind::HD);
+
+        assert!(client
+            .prepare_lookahead("restored", &enckey, &mut wallet, 5)
+            .unwrap());
+        assert!(!client
+            .prepare_lookahead("restored", &enckey, &mut wallet, 5)
+            .unwrap());
+        assert_eq!(wallet.root_hashes.len(), 5);
+        assert_eq!(wallet.staking_keys.len(), 5);
+        assert_eq!(
+            client.staking_addresses("restored", &enckey).unwrap().len(),
+            5
+        );
+
+        // staking address at index 8 is in the window, the window is extended after it
+        let used_addresses = (0..20)
+            .map(|_| client.new_staking_address("used", &used_enckey).unwrap())
+            .collect::<Vec<_>>();
+        assert!(client
+            .recover_staking_addresses(&used_addresses[7], "restored", &enckey, &mut wallet, 5)
+            .unwrap());
+        assert_eq!(wallet.staking_keys.len(), 13);
+        assert!(wallet.staking_addresses().contains(&used_addresses[12]));
+        assert!(!client
+            .recover_staking_addresses(&used_addresses[7], "restored", &enckey, &mut wallet, 5)
+            .unwrap());
+
+        // staking address at index 20 is beyond the window
+        assert!(!client
+            .recover_staking_addresses(&used_addresses[19], "restored", &enckey, &mut wallet, 5)
+            .unwrap());
+        assert_eq!(
+            client.staking_addresses("restored", &enckey).unwrap().len(),
+            13
+        );
+    }
+
     #[test]
     fn check_restore_basic_wallet() {
         let private_key =
@@ -1660,4 +2103,85 @@ mod tests {
                 .unwrap()
         );
     }
+
+    #[test]
+    fn check_watch_only_wallet() {
+        let words = Mnemonic::from_secstr(&SecUtf8::from("pony thank pluck sweet bless tuna couple eight stove fluid essay debate cinnamon elite only")).unwrap();
+        let passphrase = SecUtf8::from("123456");
+        let client = DefaultWalletClient::new_read_only(MemoryStorage::default());
+        let hd_enckey = client
+            .restore_wallet("hd", &passphrase, &words)
+            .expect("restore wallet");
+        let account_pubkeys = client
+            .export_account_pubkeys("hd", &hd_enckey)
+            .expect("export account public keys");
+        let view_key = client.view_key_private("hd", &hd_enckey).unwrap();
+
+        assert!(client
+            .new_wallet("watch", &passphrase, WalletKind::WatchOnly, None)
+            .is_err());
+        let enckey = client
+            .restore_watch_only_wallet("watch", &passphrase, &account_pubkeys, &view_key)
+            .expect("restore watch-only wallet");
+        assert_eq!(
+            client.export_account_pubkeys("watch", &enckey).unwrap(),
+            account_pubkeys
+        );
+
+        for _ in 0..2 {
+            assert_eq!(
+                client.new_transfer_address("watch", &enckey).unwrap(),
+                client.new_transfer_address("hd", &hd_enckey).unwrap()
+            );
+            assert_eq!(
+                client.new_staking_address("watch", &enckey).unwrap(),
+                client.new_staking_address("hd", &hd_enckey).unwrap()
+            );
+        }
+
+        let public_key = client
+            .new_public_key("watch", &enckey, Some(AddressType::Transfer))
+            .unwrap();
+        assert_eq!(
+            client
+                .sign_key("watch", &enckey, &public_key)
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert!(client
+            .private_key("watch", &enckey, &public_key)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn check_watch_only_wallet_without_staking_key() {
+        let words = Mnemonic::from_secstr(&SecUtf8::from("pony thank pluck sweet bless tuna couple eight stove fluid essay debate cinnamon elite only")).unwrap();
+        let passphrase = SecUtf8::from("123456");
+        let mut client = DefaultWalletClient::new_read_only(MemoryStorage::default());
+        let hd_enckey = client
+            .restore_wallet("hd", &passphrase, &words)
+            .expect("restore wallet");
+        let mut account_pubkeys = client.export_account_pubkeys("hd", &hd_enckey).unwrap();
+        account_pubkeys.staking = None;
+        let view_key = client.view_key_private("hd", &hd_enckey).unwrap();
+        let enckey = client
+            .restore_watch_only_wallet("watch", &passphrase, &account_pubkeys, &view_key)
+            .expect("restore watch-only wallet");
+
+        assert!(client.new_staking_address("watch", &enckey).is_err());
+
+        // addresses used by the hd wallet are recovered during syncing
+        let mut dummy_wallet = Wallet::new(PublicKey::from(&view_key), WalletKind::WatchOnly);
+        let address = client.new_transfer_address("hd", &hd_enckey).unwrap();
+        assert!(client
+            .recover_addresses(&address, "watch", &enckey, &mut dummy_wallet, 20)
+            .unwrap());
+        assert!(client
+            .transfer_addresses("watch", &enckey)
+            .unwrap()
+            .contains(&address));
+    }
 }
@@ -28,6 +28,16 @@ use crate::service;
 use crate::service::{KeyService, SyncState, Wallet, WalletState, WalletStateMemento};
 
 pub trait AddressRecovery: Clone + Send + Sync {
+    // derive transfer and staking addresses until at least `gap_limit` of them exist
+    // return: true, new addresses are generated
+    fn prepare_lookahead(
+        &mut self,
+        name: &str,
+        enckey: &SecKey,
+        wallet: &mut Wallet,
+        gap_limit: u32,
+    ) -> Result<bool>;
+
     // new_address: transfer address in TxOut
     // return: true, new addresses are generated
     fn recover_addresses(
@@ -36,6 +46,18 @@ pub trait AddressRecovery: Clone + Send + Sync {
         name: &str,
         enckey: &SecKey,
         wallet: &mut Wallet,
+        gap_limit: u32,
+    ) -> Result<bool>;
+
+    // staking_address: staking address in deposit or unbond transaction
+    // return: true, new addresses are generated
+    fn recover_staking_addresses(
+        &mut self,
+        staking_address: &StakedStateAddress,
+        name: &str,
+        enckey: &SecKey,
+        wallet: &mut Wallet,
+        gap_limit: u32,
     ) -> Result<bool>;
 }
 
@@ -82,6 +104,8 @@ impl<O: TransactionObfuscation> TxDecryptor for TxObfuscationDecryptor<O> {
 pub struct SyncerOptions {
     pub enable_fast_forward: bool,
     pub enable_address_recovery: bool,
+    /// number of unused addresses derived ahead of the last used one
+    pub address_gap_limit: u32,
     pub batch_size: usize,
     pub block_height_ensure: u64,
 }
@@ -321,6 +345,7 @@ impl<
                 &self.env.name,
                 &self.env.enckey,
                 &mut self.wallet,
+                self.env.options.address_gap_limit,
             )?;
 
             if tmp_refetch {
@@ -356,6 +381,32 @@ impl<
         Ok(())
     }
 
+    /// only wallets with derivable addresses
+    fn address_recovery_enabled(&self) -> bool {
+        self.env.options.enable_address_recovery
+            && (crate::types::WalletKind::HD == self.wallet.wallet_kind
+                || crate::types::WalletKind::WatchOnly == self.wallet.wallet_kind)
+    }
+
+    /// Extends the staking address window when a staking transaction uses one of its addresses
+    fn handle_recover_staking_addresses(&mut self, block: &FilteredBlock) -> Result<()> {
+        for transaction in block.staking_transactions.iter() {
+            let staking_address = match transaction {
+                Transaction::DepositStakeTransaction(tx) => &tx.to_staked_account,
+                Transaction::UnbondStakeTransaction(tx) => &tx.from_staked_account,
+                _ => continue,
+            };
+            self.env.recover_address.recover_staking_addresses(
+                staking_address,
+                &self.env.name,
+                &self.env.enckey,
+                &mut self.wallet,
+                self.env.options.address_gap_limit,
+            )?;
+        }
+        Ok(())
+    }
+
     fn handle_batch(&mut self, blocks: NonEmpty<FilteredBlock>) -> Result<()> {
         let enclave_txids = blocks
             .iter()
@@ -363,10 +414,7 @@ impl<
             .collect::<Vec<_>>();
         let enclave_txs = self.env.decryptor.decrypt_tx(&enclave_txids)?;
 
-        if self.env.options.enable_address_recovery
-            && crate::types::WalletKind::HD == self.wallet.wallet_kind
-        {
-            // only hdwallet
+        if self.address_recovery_enabled() {
             self.handle_recover_addresses(&blocks)?;
         }
 
@@ -399,6 +447,15 @@ impl<
             return Err(Error::new(ErrorKind::InvalidInput, "Cancelled by user"));
         }
 
+        if self.address_recovery_enabled() {
+            self.env.recover_address.prepare_lookahead(
+                &self.env.name,
+                &self.env.enckey,
+                &mut self.wallet,
+                self.env.options.address_gap_limit,
+            )?;
+        }
+
         // Send batch RPC requests to tendermint in chunks of `batch_size` requests per batch call
         for chunk in ((self.sync_state.last_block_height + 1)..=current_block_height)
             .chunks(self.env.options.batch_size)
@@ -466,6 +523,10 @@ impl<
                     &block_result,
                     &state,
                 )?;
+                if self.address_recovery_enabled() {
+                    // later blocks are filtered with the extended staking addresses
+                    self.handle_recover_staking_addresses(&block)?;
+                }
                 self.update_progress(block.block_height);
                 batch.push(block);
             }
@@ -803,6 +864,7 @@ mod tests {
                 options: SyncerOptions {
                     enable_fast_forward,
                     enable_address_recovery: false,
+                    address_gap_limit: 20,
                     batch_size: 20,
                     block_height_ensure: 50,
                 },
@@ -937,6 +999,7 @@ mod tests {
                 options: SyncerOptions {
                     enable_fast_forward,
                     enable_address_recovery: false,
+                    address_gap_limit: 20,
                     batch_size: 20,
                     block_height_ensure: 50,
                 },
@@ -987,6 +1050,7 @@ mod tests {
                 options: SyncerOptions {
                     enable_fast_forward: false,
                     enable_address_recovery: true,
+                    address_gap_limit: 20,
                     batch_size: 20,
                     block_height_ensure: 50,
                 },
@@ -1005,6 +1069,7 @@ mod tests {
             address: ExtendedAddr::OrTree([0; 32]),
             value: Coin::new(10).unwrap(),
             valid_from: None,
+            relative_lock: None,
         };
         tx_core.outputs.push(output);
         let tx = Transaction::TransferTransaction(tx_core);
@@ -1044,6 +1109,7 @@ mod tests {
                 options: SyncerOptions {
                     enable_fast_forward: false,
                     enable_address_recovery: true,
+                    address_gap_limit: 20,
                     batch_size: 20,
                     block_height_ensure: 50,
                 },
@@ -1067,6 +1133,7 @@ mod tests {
 
             value: Coin::new(10).unwrap(),
             valid_from: None,
+            relative_lock: None,
         };
         tx_core.outputs.push(output);
         let tx = Transaction::TransferTransaction(tx_core);
@@ -1095,7 +1162,8 @@ mod tests {
                     .unwrap(),
                     &name,
                     &enckey,
-                    &mut dummy_wallet
+                    &mut dummy_wallet,
+                    20,
                 )
                 .unwrap()
         );
@@ -5,6 +5,7 @@ use thiserror::Error;
 use chain_core::init::coin::{sum_coins, Coin, CoinError};
 use chain_core::tx::{
     data::{
+        address::ExtendedAddr,
         input::{TxoPointer, TxoSize},
         output::TxOut,
         TxId,
@@ -131,17 +132,26 @@ pub(crate) fn handle_transaction(
     }
     for input in transaction_change.inputs.iter() {
         memento.remove_unspent_transaction(input.pointer.clone());
+        memento.remove_hash_time_locked_output(input.pointer.clone());
     }
 
     let transfer_addresses = wallet.transfer_addresses();
 
     for (i, output) in transaction_change.outputs.iter().enumerate() {
-        // Only add unspent transaction if output address belongs to current wallet
-        if transfer_addresses.contains(&output.address) {
-            memento.add_unspent_transaction(
-                TxoPointer::new(transaction_change.transaction_id, i),
-                output.clone(),
-            );
+        let pointer = TxoPointer::new(transaction_change.transaction_id, i);
+        match &output.address {
+            // Only add unspent transaction if output address belongs to current wallet
+            address @ ExtendedAddr::OrTree(_) => {
+                if transfer_addresses.contains(address) {
+                    memento.add_unspent_transaction(pointer, output.clone());
+                }
+            }
+            // Kept apart, as it's only spent with the claim or refund witness
+            ExtendedAddr::HashTimeLock(lock) => {
+                if wallet.is_hash_time_lock_party(lock) {
+                    memento.add_hash_time_locked_output(pointer, output.clone());
+                }
+            }
         }
     }
 
@@ -251,7 +261,12 @@ mod tests {
     use chain_core::state::account::{
         DepositBondTx, StakedStateAddress, StakedStateOpAttributes, UnbondTx,
     };
-    use chain_core::tx::data::{address::ExtendedAddr, attribute::TxAttributes, output::TxOut, Tx};
+    use chain_core::tx::data::{
+        address::{ExtendedAddr, HashTimeLock},
+        attribute::TxAttributes,
+        output::TxOut,
+        Tx,
+    };
     use chain_core::tx::fee::Fee;
     use chain_core::tx::TransactionId;
     use chain_tx_filter::BlockFilter;
@@ -462,4 +477,73 @@ mod tests {
         assert_eq!(states[1].transaction_history.len(), 1);
         assert_eq!(states[1].unspent_transactions.len(), 1);
     }
+
+    #[test]
+    fn check_syncer_logic_hash_time_lock() {
+        let wallets = create_test_wallet(2).unwrap();
+        let view_keys = wallets
+            .iter()
+            .map(|wallet| wallet.view_key.clone())
+            .collect::<Vec<_>>();
+        let refund = *wallets[0].root_hashes.iter().next().unwrap();
+        let recipient = *wallets[1].root_hashes.iter().next().unwrap();
+        let lock = ExtendedAddr::HashTimeLock(HashTimeLock {
+            hash: [1; 32],
+            recipient,
+            refund,
+            deadline: 1_000,
+        });
+        let locking_tx = Transaction::TransferTransaction(Tx::new_with(
+            Vec::new(),
+            vec![TxOut::new(lock, Coin::new(100).unwrap())],
+            TxAttributes::default(),
+        ));
+        let claiming_tx = Transaction::TransferTransaction(Tx::new_with(
+            vec![TxoPointer::new(locking_tx.id(), 0)],
+            vec![TxOut::new(
+                ExtendedAddr::OrTree(recipient),
+                Coin::new(99).unwrap(),
+            )],
+            TxAttributes::default(),
+        ));
+        let mut states = wallets
+            .iter()
+            .map(|_| WalletState::default())
+            .collect::<Vec<_>>();
+
+        // both the recipient and the refund wallet track the output, it's not in the balance
+        let txs = [locking_tx.clone()];
+        let blocks = [block_header(&view_keys, &txs, &[], [0u8; 32])];
+        for (wallet, state) in wallets.iter().zip(states.iter_mut()) {
+            let memento = handle_blocks(wallet, state, &blocks, &txs).expect("handle block");
+            state.apply_memento(&memento).expect("apply memento");
+            assert_eq!(state.hash_time_locked_outputs.len(), 1);
+            assert!(state
+                .hash_time_locked_outputs
+                .contains_key(&TxoPointer::new(locking_tx.id(), 0)));
+            assert!(state.unspent_transactions.is_empty());
+            assert_eq!(state.get_balance().unwrap().total, Coin::zero());
+        }
+
+        // a third wallet doesn't track it
+        let other = create_test_wallet(1).unwrap().remove(0);
+        let mut other_state = WalletState::default();
+        let memento = handle_blocks(&other, &mut other_state, &blocks, &txs).unwrap();
+        other_state.apply_memento(&memento).unwrap();
+        assert!(other_state.hash_time_locked_outputs.is_empty());
+
+        // claimed: no longer tracked
+        let txs = [claiming_tx];
+        let blocks = [block_header(&view_keys, &txs, &[], [0u8; 32])];
+        for (wallet, state) in wallets.iter().zip(states.iter_mut()) {
+            let memento = handle_blocks(wallet, state, &blocks, &txs).expect("handle block");
+            state.apply_memento(&memento).expect("apply memento");
+            assert!(state.hash_time_locked_outputs.is_empty());
+        }
+        assert_eq!(
+            states[1].get_balance().unwrap().total,
+            Coin::new(99).unwrap()
+        );
+        assert_eq!(states[0].get_balance().unwrap().total, Coin::zero());
+    }
 }
@@ -4,12 +4,15 @@ mod default_network_ops_client;
 pub use self::default_network_ops_client::DefaultNetworkOpsClient;
 use chain_core::init::coin::Coin;
 use chain_core::state::account::{
-    CouncilNodeMeta, StakedState, StakedStateAddress, StakedStateOpAttributes,
+    CouncilNodeMeta, NodeName, NodeSecurityContact, StakedState, StakedStateAddress,
+    StakedStateOpAttributes, StakingHistoryPage,
 };
+use chain_core::state::tendermint::{TendermintNodeId, TendermintValidatorPubKey};
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
+use chain_core::tx::fee::Milli;
 use chain_core::tx::TxAux;
 use client_common::tendermint::types::{Genesis, StatusResponse};
 use client_common::{ErrorKind, Result, ResultExt, SecKey};
@@ -82,6 +85,24 @@ pub trait NetworkOpsClient: Send + Sync {
         staking_account_address: StakedStateAddress,
         attributes: StakedStateOpAttributes,
         node_metadata: CouncilNodeMeta,
+        commission_rate: Milli,
+        verify_staking: bool,
+    ) -> Result<TxAux>;
+
+    /// Creates a new transaction for updating the metadata of a council node
+    /// (the current values are kept for the `None` arguments)
+    #[allow(clippy::too_many_arguments)]
+    fn create_node_metadata_update_transaction(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        staking_account_address: StakedStateAddress,
+        attributes: StakedStateOpAttributes,
+        node_name: Option<NodeName>,
+        security_contact: Option<NodeSecurityContact>,
+        consensus_pubkey: Option<TendermintValidatorPubKey>,
+        commission_rate: Option<Milli>,
+        p2p_node_id: Option<TendermintNodeId>,
         verify_staking: bool,
     ) -> Result<TxAux>;
 
@@ -106,6 +127,16 @@ pub trait NetworkOpsClient: Send + Sync {
         verify: bool,
     ) -> Result<Option<StakedState>>;
 
+    /// Returns at most `limit` punishment and reward events of the staking address
+    /// from the `start` index (oldest first).
+    /// The history is indexed by the queried node (it's not committed in the app hash).
+    fn get_staking_history(
+        &self,
+        address: &StakedStateAddress,
+        start: u64,
+        limit: u16,
+    ) -> Result<StakingHistoryPage>;
+
     /// Return genesis of tendermint
     fn get_genesis(&self) -> Result<Genesis>;
 
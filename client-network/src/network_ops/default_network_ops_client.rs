@@ -1,19 +1,22 @@
-use parity_scale_codec::Decode;
+use parity_scale_codec::{Decode, Encode};
 
 use crate::NetworkOpsClient;
-use chain_core::common::Timespec;
+use chain_core::common::{Timespec, HASH_SIZE_256};
 use chain_core::init::coin::{sum_coins, Coin};
 use chain_core::state::account::{
-    CouncilNodeMeta, DepositBondTx, NodeMetadata, StakedState, StakedStateAddress,
-    StakedStateOpAttributes, StakedStateOpWitness, UnbondTx, UnjailTx, WithdrawUnbondedTx,
+    CouncilNodeMeta, DepositBondTx, NodeMetadata, NodeName, NodeSecurityContact, NodeState,
+    StakedState, StakedStateAddress, StakedStateOpAttributes, StakedStateOpWitness,
+    StakingHistoryPage, UnbondTx, UnjailTx, WithdrawUnbondedTx,
 };
-use chain_core::state::validator::NodeJoinRequestTx;
+use chain_core::state::tendermint::{TendermintNodeId, TendermintValidatorPubKey};
+use chain_core::state::validator::{NodeJoinRequestTx, NodeMetadataUpdateTx};
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
-use chain_core::tx::fee::FeeAlgorithm;
+use chain_core::tx::fee::{FeeAlgorithm, Milli};
 use chain_core::tx::{TxAux, TxPublicAux};
+use chain_core::AppHashParts;
 use chain_storage::jellyfish::SparseMerkleProof;
 use chain_tx_validation::{check_inputs_basic, check_outputs_basic, verify_unjailed};
 use client_common::tendermint::types::{AbciQueryExt, Genesis, StatusResponse};
@@ -28,6 +31,27 @@ use client_core::types::TransactionPending;
 use client_core::{UnspentTransactions, WalletClient};
 use tendermint::{block::Height, Time};
 
+/// Staking state queried with its proof
+struct ProvenStaking {
+    staking: Option<StakedState>,
+    /// membership / non-membership proof in the staking trie
+    proof: SparseMerkleProof,
+    /// parts of the app hash after the queried block
+    /// (only provided if the node has the state at that height)
+    app_hash_parts: Option<AppHashParts>,
+}
+
+/// Checks the app hash parts in a staking proof against the app hash in a verified header
+fn verify_app_hash_parts(parts: &AppHashParts, app_hash: &[u8]) -> Result<()> {
+    if parts.app_hash()[..] != app_hash[..] {
+        return Err(Error::new(
+            ErrorKind::VerifyError,
+            "Staking state proof doesn't match the app hash in the verified header",
+        ));
+    }
+    Ok(())
+}
+
 /// Default implementation of `NetworkOpsClient`
 #[derive(Clone)]
 pub struct DefaultNetworkOpsClient<W, S, C, F, E>
@@ -104,6 +128,60 @@ where
             },
         ))
     }
+
+    /// Queries the staking state at the height with its proof
+    fn query_proven_staking(
+        &self,
+        address: &StakedStateAddress,
+        height: u64,
+    ) -> Result<ProvenStaking> {
+        let rsp = self
+            .client
+            .query("staking", address.as_ref(), Some(height.into()), true)?;
+        let staking = <Option<StakedState>>::decode(&mut rsp.bytes().as_slice())
+            .err_kind(ErrorKind::DeserializationError, || {
+                format!("Cannot deserialize staked state for address: {}", address)
+            })?;
+
+        let ops = rsp
+            .proof
+            .as_ref()
+            .map(|proof| proof.ops.as_slice())
+            .unwrap_or_default();
+        let proof_data = |field_type: &str| {
+            ops.iter()
+                .find(|op| op.field_type == field_type)
+                .map(|op| op.data.as_slice())
+        };
+        let mut proof_bytes = proof_data("staking")
+            .err_kind(ErrorKind::TendermintRpcError, || {
+                format!("There is no proof for address: {}", address)
+            })?;
+        // for a non-existent staking state, it's a non-membership proof
+        let proof = SparseMerkleProof::decode(&mut proof_bytes).err_kind(
+            ErrorKind::DeserializationError,
+            || {
+                format!(
+                    "Cannot deserialize staked state proof for address: {}",
+                    address
+                )
+            },
+        )?;
+        let app_hash_parts = match proof_data("app_hash") {
+            Some(mut parts_bytes) => Some(
+                AppHashParts::decode(&mut parts_bytes)
+                    .err_kind(ErrorKind::DeserializationError, || {
+                        "Cannot deserialize app hash proof"
+                    })?,
+            ),
+            None => None,
+        };
+        Ok(ProvenStaking {
+            staking,
+            proof,
+            app_hash_parts,
+        })
+    }
 }
 
 impl<W, S, C, F, E> NetworkOpsClient for DefaultNetworkOpsClient<W, S, C, F, E>
@@ -423,6 +501,64 @@ where
         staking_account_address: StakedStateAddress,
         attributes: StakedStateOpAttributes,
         node_metadata: CouncilNodeMeta,
+        commission_rate: Milli,
+        verify_staking: bool,
+    ) -> Result<TxAux> {
+        let staked_state = self.get_staked_state(name, &staking_account_address, verify_staking)?;
+
+        verify_unjailed(&staked_state).map_err(|e| {
+            Error::new(
+                ErrorKind::ValidationError,
+                format!("Failed to validate staking account: {}", e),
+            )
+        })?;
+
+        let transaction = NodeJoinRequestTx::new(
+            staked_state.nonce,
+            staking_account_address,
+            attributes,
+            NodeMetadata::CouncilNode(node_metadata),
+            commission_rate,
+        );
+        if !transaction.is_valid_commission_rate() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Commission rate can not be over 1.0",
+            ));
+        }
+        let tx = Transaction::NodejoinTransaction(transaction.clone());
+
+        let public_key = match staking_account_address {
+            StakedStateAddress::BasicRedeem(ref redeem_address) => self
+                .wallet_client
+                .find_staking_key(name, enckey, redeem_address)?
+                .chain(|| {
+                    (
+                        ErrorKind::InvalidInput,
+                        "Address not found in current wallet",
+                    )
+                })?,
+        };
+        let sign_key = self.wallet_client.sign_key(name, enckey, &public_key)?;
+        let signature = sign_key.sign(&tx).map(StakedStateOpWitness::new)?;
+
+        Ok(TxAux::PublicTx(TxPublicAux::NodeJoinTx(
+            transaction,
+            signature,
+        )))
+    }
+
+    fn create_node_metadata_update_transaction(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        staking_account_address: StakedStateAddress,
+        attributes: StakedStateOpAttributes,
+        node_name: Option<NodeName>,
+        security_contact: Option<NodeSecurityContact>,
+        consensus_pubkey: Option<TendermintValidatorPubKey>,
+        commission_rate: Option<Milli>,
+        p2p_node_id: Option<TendermintNodeId>,
         verify_staking: bool,
     ) -> Result<TxAux> {
         let staked_state = self.get_staked_state(name, &staking_account_address, verify_staking)?;
@@ -433,14 +569,33 @@ where
                 format!("Failed to validate staking account: {}", e),
             )
         })?;
+        let (council_node, current_commission_rate) = match staked_state.node_meta {
+            Some(NodeState::CouncilNode(val)) => (val.council_node, val.commission_rate),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::IllegalInput,
+                    "You can only update the metadata of a council node (synchronizing your wallet may help)",
+                ))
+            }
+        };
 
-        let transaction = NodeJoinRequestTx {
+        let transaction = NodeMetadataUpdateTx {
             nonce: staked_state.nonce,
             address: staking_account_address,
             attributes,
-            node_meta: NodeMetadata::CouncilNode(node_metadata),
+            name: node_name.unwrap_or(council_node.node_info.name),
+            security_contact: security_contact.unwrap_or(council_node.node_info.security_contact),
+            consensus_pubkey: consensus_pubkey.unwrap_or(council_node.consensus_pubkey),
+            commission_rate: commission_rate.unwrap_or(current_commission_rate),
+            p2p_node_id: p2p_node_id.or(council_node.node_info.p2p_node_id),
         };
-        let tx = Transaction::NodejoinTransaction(transaction.clone());
+        if !transaction.is_valid_commission_rate() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Commission rate can not be over 1.0",
+            ));
+        }
+        let tx = Transaction::NodeMetadataUpdateTransaction(transaction.clone());
 
         let public_key = match staking_account_address {
             StakedStateAddress::BasicRedeem(ref redeem_address) => self
@@ -456,7 +611,7 @@ where
         let sign_key = self.wallet_client.sign_key(name, enckey, &public_key)?;
         let signature = sign_key.sign(&tx).map(StakedStateOpWitness::new)?;
 
-        Ok(TxAux::PublicTx(TxPublicAux::NodeJoinTx(
+        Ok(TxAux::PublicTx(TxPublicAux::NodeMetadataUpdateTx(
             transaction,
             signature,
         )))
@@ -470,44 +625,51 @@ where
     ) -> Result<Option<StakedState>> {
         let mstaking = if verify {
             let sync_state = self.wallet_client.get_sync_state(name)?;
-            let rsp = self.client.query(
-                "staking",
-                address.as_ref(),
-                Some(sync_state.last_block_height.into()),
-                true,
-            )?;
-            let mstaking = <Option<StakedState>>::decode(&mut rsp.bytes().as_slice())
-                .err_kind(ErrorKind::DeserializationError, || {
-                    format!("Cannot deserialize staked state for address: {}", address)
-                })?;
-
-            let mbytes = if let Some(proof) = rsp.proof.as_ref() {
-                if let Some(evidence) = proof.ops.first() {
-                    Some(evidence.data.as_slice())
-                } else {
-                    None
-                }
+            // the app hash in the last synced (verified) header commits to the state
+            // after the previous block; until two blocks are synced,
+            // the staking root in the sync state is trusted instead
+            let trusted_app_hash = if sync_state.last_block_height > 1 {
+                let app_hash = hex::decode(&sync_state.last_app_hash)
+                    .ok()
+                    .filter(|app_hash| app_hash.len() == HASH_SIZE_256)
+                    .err_kind(ErrorKind::VerifyError, || {
+                        "Invalid app hash in the sync state"
+                    })?;
+                Some(app_hash)
             } else {
                 None
             };
-            let mut proof_bytes = mbytes.err_kind(ErrorKind::TendermintRpcError, || {
-                format!("There is no proof for address: {}", address)
-            })?;
-            let proof = SparseMerkleProof::decode(&mut proof_bytes).err_kind(
-                ErrorKind::DeserializationError,
-                || {
-                    format!(
-                        "Cannot deserialize staked state proof for address: {}",
-                        address
-                    )
-                },
-            )?;
 
-            proof
-                .verify(sync_state.staking_root, address, mstaking.as_ref())
+            let proven_staking = match trusted_app_hash {
+                Some(app_hash) => {
+                    let proven_staking =
+                        self.query_proven_staking(address, sync_state.last_block_height - 1)?;
+                    match &proven_staking.app_hash_parts {
+                        Some(parts) => {
+                            verify_app_hash_parts(parts, &app_hash)?;
+                            let staking_root = parts.account_root;
+                            Some((proven_staking, staking_root))
+                        }
+                        // the node doesn't keep the historical states
+                        // (tx query address not set), so it can't prove the previous state
+                        None => None,
+                    }
+                }
+                None => None,
+            };
+            let (proven_staking, staking_root) = match proven_staking {
+                Some(proven) => proven,
+                None => (
+                    self.query_proven_staking(address, sync_state.last_block_height)?,
+                    sync_state.staking_root,
+                ),
+            };
+            proven_staking
+                .proof
+                .verify(staking_root, address, proven_staking.staking.as_ref())
                 .err_kind(ErrorKind::VerifyError, || "Verify staking state failed")?;
 
-            mstaking
+            proven_staking.staking
         } else {
             let bytes = self
                 .client
@@ -521,6 +683,29 @@ where
         Ok(mstaking)
     }
 
+    fn get_staking_history(
+        &self,
+        address: &StakedStateAddress,
+        start: u64,
+        limit: u16,
+    ) -> Result<StakingHistoryPage> {
+        let mut data = address.as_ref().to_vec();
+        data.extend_from_slice(&(start, limit).encode());
+        let bytes = self
+            .client
+            .query("staking-history", &data, None, false)?
+            .bytes();
+        StakingHistoryPage::decode(&mut bytes.as_slice()).err_kind(
+            ErrorKind::DeserializationError,
+            || {
+                format!(
+                    "Cannot deserialize staking history for address: {}",
+                    address
+                )
+            },
+        )
+    }
+
     fn get_genesis(&self) -> Result<Genesis> {
         self.client.genesis()
     }
@@ -552,6 +737,8 @@ mod tests {
     use chain_core::tx::fee::Fee;
     use chain_core::tx::TransactionId;
     use chain_core::tx::{PlainTxAux, TxEnclaveAux, TxObfuscated};
+    use chain_storage::buffer::MemStore;
+    use chain_storage::jellyfish::{get_with_proof, put_stakings};
     use chain_tx_validation::witness::verify_tx_recover_address;
     use client_common::storage::MemoryStorage;
     use client_common::tendermint::lite;
@@ -686,6 +873,7 @@ mod tests {
                     inactive_time: Some(0),
                     inactive_block: Some(BlockHeight::genesis()),
                     used_validator_addresses: vec![],
+                    commission_rate: Milli::default(),
                 }),
             );
 
@@ -800,6 +988,7 @@ mod tests {
             address: ExtendedAddr::OrTree([0; 32]),
             value: Coin::new(10).unwrap(),
             valid_from: None,
+            relative_lock: None,
         };
         let transactions = vec![(input, output)];
 
@@ -1188,6 +1377,7 @@ mod tests {
                 staking_account_address,
                 StakedStateOpAttributes::new(171),
                 node_metadata,
+                Milli::default(),
                 false,
             )
             .unwrap();
@@ -1202,4 +1392,98 @@ mod tests {
             _ => unreachable!("`create_node_join_tx()` created invalid transaction"),
         }
     }
+
+    #[test]
+    fn check_node_metadata_update_transaction_requires_council_node() {
+        let name = "name";
+        let passphrase = SecUtf8::from("passphrase");
+
+        let storage = MemoryStorage::default();
+        let signer_manager = WalletSignerManager::new(storage.clone(), HwKeyService::default());
+
+        let fee_algorithm = UnitFeeAlgorithm::default();
+
+        let wallet_client = DefaultWalletClient::new_read_only(storage.clone());
+
+        let tendermint_client = MockClient::default();
+        let network_ops_client = DefaultNetworkOpsClient::new(
+            wallet_client,
+            signer_manager,
+            tendermint_client,
+            fee_algorithm,
+            MockTransactionCipher,
+        );
+
+        let (enckey, _) = network_ops_client
+            .get_wallet_client()
+            .new_wallet(name, &passphrase, WalletKind::Basic, None)
+            .unwrap();
+
+        let staking_account_address = network_ops_client
+            .get_wallet_client()
+            .new_staking_address(name, &enckey)
+            .unwrap();
+
+        assert_eq!(
+            ErrorKind::IllegalInput,
+            network_ops_client
+                .create_node_metadata_update_transaction(
+                    name,
+                    &enckey,
+                    staking_account_address,
+                    StakedStateOpAttributes::new(171),
+                    Some("renamed".to_owned()),
+                    None,
+                    None,
+                    None,
+                    None,
+                    false,
+                )
+                .expect_err("Only council nodes can update their metadata")
+                .kind()
+        );
+    }
+
+    #[test]
+    fn check_staking_proof_verification() {
+        let staking = StakedState::default(StakedStateAddress::BasicRedeem([1; 20].into()));
+        let absent = StakedStateAddress::BasicRedeem([2; 20].into());
+        let mut store = MemStore::new();
+        let account_root = put_stakings(&mut store, 0, std::iter::once(&staking)).unwrap();
+        let parts = AppHashParts {
+            valid_tx_root: [0; 32],
+            account_root,
+            rewards_pool_hash: [1; 32],
+            network_params_hash: [2; 32],
+            node_state_hash: Some([3; 32]),
+            spent_outputs_root: Some([5; 32]),
+        };
+
+        // membership proof against the staking root in the app hash parts
+        verify_app_hash_parts(&parts, &parts.app_hash()).unwrap();
+        let (mstaking, proof) = get_with_proof(&store, 0, &staking.address);
+        assert_eq!(mstaking.as_ref(), Some(&staking));
+        proof
+            .verify(parts.account_root, &staking.address, mstaking.as_ref())
+            .unwrap();
+
+        // non-membership proof
+        let (mstaking, proof) = get_with_proof(&store, 0, &absent);
+        assert!(mstaking.is_none());
+        proof.verify(account_root, &absent, None).unwrap();
+        assert!(proof.verify(account_root, &absent, Some(&staking)).is_err());
+
+        // the parts don't match the app hash in the verified header
+        assert_eq!(
+            ErrorKind::VerifyError,
+            verify_app_hash_parts(&parts, &[0; HASH_SIZE_256])
+                .unwrap_err()
+                .kind()
+        );
+        let forged_parts = AppHashParts {
+            account_root: [4; 32],
+            ..parts
+        };
+        assert!(verify_app_hash_parts(&forged_parts, &parts.app_hash()).is_err());
+    }
 }
@@ -63,6 +63,13 @@ pub struct Options {
         help = "Disable address recovery when syncing wallet, which is not necessary, when addresses already exist"
     )]
     pub disable_address_recovery: bool,
+    #[structopt(
+        name = "address-gap-limit",
+        long,
+        default_value = "20",
+        help = "Number of unused addresses derived ahead of the last used one when syncing wallet"
+    )]
+    pub address_gap_limit: u32,
     #[structopt(
         name = "batch-size",
         short,
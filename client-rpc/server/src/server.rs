@@ -33,6 +33,7 @@ impl Server {
             sync_options: SyncerOptions {
                 enable_fast_forward: options.enable_fast_forward,
                 enable_address_recovery: !options.disable_address_recovery,
+                address_gap_limit: options.address_gap_limit,
                 batch_size: options.batch_size,
                 block_height_ensure: options.block_height_ensure,
             },
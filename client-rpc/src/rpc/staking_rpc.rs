@@ -1,4 +1,5 @@
 use std::collections::BTreeSet;
+use std::convert::TryFrom;
 use std::str::FromStr;
 
 use jsonrpc_core::Result;
@@ -8,18 +9,23 @@ use crate::{rpc_error_from_string, to_rpc_error};
 use chain_core::init::coin::Coin;
 use chain_core::state::account::{
     ConfidentialInit, CouncilNodeMeta, StakedState, StakedStateAddress, StakedStateOpAttributes,
+    StakingHistoryPage,
 };
-use chain_core::state::tendermint::TendermintValidatorPubKey;
+use chain_core::state::tendermint::{TendermintNodeId, TendermintValidatorPubKey};
 use chain_core::tx::data::access::{TxAccess, TxAccessPolicy};
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
+use chain_core::tx::fee::Milli;
 use client_common::{Error, ErrorKind, PublicKey, Result as CommonResult, ResultExt, Transaction};
 use client_core::wallet::WalletRequest;
 use client_core::WalletClient;
 use client_network::NetworkOpsClient;
 
+/// the number of staking history events returned if the limit isn't specified
+const DEFAULT_STAKING_HISTORY_LIMIT: u16 = 100;
+
 #[rpc(server)]
 pub trait StakingRpc: Send + Sync {
     #[rpc(name = "staking_depositStake")]
@@ -41,6 +47,14 @@ pub trait StakingRpc: Send + Sync {
     #[rpc(name = "staking_state")]
     fn state(&self, name: String, address: StakedStateAddress) -> Result<StakedState>;
 
+    #[rpc(name = "staking_history")]
+    fn history(
+        &self,
+        address: StakedStateAddress,
+        start: Option<u64>,
+        limit: Option<u16>,
+    ) -> Result<StakingHistoryPage>;
+
     #[rpc(name = "staking_unbondStake")]
     fn unbond_stake(
         &self,
@@ -69,6 +83,19 @@ pub trait StakingRpc: Send + Sync {
         validator_pubkey: String,
         staking_address: String,
         keypackage: String,
+        commission_rate: Option<String>,
+    ) -> Result<String>;
+
+    #[rpc(name = "staking_validatorNodeMetadataUpdate")]
+    fn node_metadata_update(
+        &self,
+        request: WalletRequest,
+        staking_address: String,
+        validator_node_name: Option<String>,
+        security_contact: Option<String>,
+        validator_pubkey: Option<String>,
+        commission_rate: Option<String>,
+        p2p_node_id: Option<String>,
     ) -> Result<String>;
 }
 
@@ -258,6 +285,21 @@ where
             .map_err(to_rpc_error)
     }
 
+    fn history(
+        &self,
+        address: StakedStateAddress,
+        start: Option<u64>,
+        limit: Option<u16>,
+    ) -> Result<StakingHistoryPage> {
+        self.ops_client
+            .get_staking_history(
+                &address,
+                start.unwrap_or(0),
+                limit.unwrap_or(DEFAULT_STAKING_HISTORY_LIMIT),
+            )
+            .map_err(to_rpc_error)
+    }
+
     fn unbond_stake(
         &self,
         request: WalletRequest,
@@ -407,6 +449,7 @@ where
         validator_pubkey: String,
         staking_addr: String,
         keypackage: String,
+        commission_rate: Option<String>,
     ) -> Result<String> {
         let attributes = StakedStateOpAttributes::new(self.network_id);
         let staking_account_address = staking_addr
@@ -420,6 +463,18 @@ where
             .map_err(to_rpc_error)?;
         let node_metadata =
             get_node_metadata(&validator_node_name, &validator_pubkey, &keypackage)?;
+        let commission_rate = match commission_rate {
+            Some(rate) => rate
+                .parse::<Milli>()
+                .chain(|| {
+                    (
+                        ErrorKind::DeserializationError,
+                        "Unable to deserialize commission rate",
+                    )
+                })
+                .map_err(to_rpc_error)?,
+            None => Milli::default(),
+        };
         let transaction = self
             .ops_client
             .create_node_join_transaction(
@@ -428,6 +483,78 @@ where
                 staking_account_address,
                 attributes,
                 node_metadata,
+                commission_rate,
+                true,
+            )
+            .map_err(to_rpc_error)?;
+        self.client
+            .broadcast_transaction(&transaction)
+            .map_err(to_rpc_error)?;
+
+        Ok(hex::encode(transaction.tx_id()))
+    }
+
+    fn node_metadata_update(
+        &self,
+        request: WalletRequest,
+        staking_addr: String,
+        validator_node_name: Option<String>,
+        security_contact: Option<String>,
+        validator_pubkey: Option<String>,
+        commission_rate: Option<String>,
+        p2p_node_id: Option<String>,
+    ) -> Result<String> {
+        let attributes = StakedStateOpAttributes::new(self.network_id);
+        let staking_account_address = staking_addr
+            .parse::<StakedStateAddress>()
+            .chain(|| {
+                (
+                    ErrorKind::DeserializationError,
+                    "Unable to deserialize staking address",
+                )
+            })
+            .map_err(to_rpc_error)?;
+        let consensus_pubkey = match validator_pubkey {
+            Some(pubkey) => Some(get_validator_pubkey(&pubkey)?),
+            None => None,
+        };
+        let commission_rate = match commission_rate {
+            Some(rate) => Some(
+                rate.parse::<Milli>()
+                    .chain(|| {
+                        (
+                            ErrorKind::DeserializationError,
+                            "Unable to deserialize commission rate",
+                        )
+                    })
+                    .map_err(to_rpc_error)?,
+            ),
+            None => None,
+        };
+        let p2p_node_id = match p2p_node_id {
+            Some(node_id) => Some(
+                hex::decode(&node_id)
+                    .ok()
+                    .and_then(|node_id| TendermintNodeId::try_from(node_id.as_slice()).ok())
+                    .err_kind(ErrorKind::DeserializationError, || {
+                        "Unable to deserialize p2p node ID"
+                    })
+                    .map_err(to_rpc_error)?,
+            ),
+            None => None,
+        };
+        let transaction = self
+            .ops_client
+            .create_node_metadata_update_transaction(
+                &request.name,
+                &request.enckey,
+                staking_account_address,
+                attributes,
+                validator_node_name,
+                security_contact.map(Some),
+                consensus_pubkey,
+                commission_rate,
+                p2p_node_id,
                 true,
             )
             .map_err(to_rpc_error)?;
@@ -439,11 +566,7 @@ where
     }
 }
 
-fn get_node_metadata(
-    validator_name: &str,
-    validator_pubkey: &str,
-    keypackage: &str,
-) -> Result<CouncilNodeMeta> {
+fn get_validator_pubkey(validator_pubkey: &str) -> Result<TendermintValidatorPubKey> {
     let decoded_pubkey = base64::decode(validator_pubkey)
         .chain(|| {
             (
@@ -464,6 +587,16 @@ fn get_node_metadata(
     let mut pubkey_bytes = [0; 32];
     pubkey_bytes.copy_from_slice(&decoded_pubkey);
 
+    Ok(TendermintValidatorPubKey::Ed25519(pubkey_bytes))
+}
+
+fn get_node_metadata(
+    validator_name: &str,
+    validator_pubkey: &str,
+    keypackage: &str,
+) -> Result<CouncilNodeMeta> {
+    let consensus_pubkey = get_validator_pubkey(validator_pubkey)?;
+
     let keypackage = base64::decode(keypackage)
         .err_kind(ErrorKind::InvalidInput, || "invalid base64")
         .map_err(to_rpc_error)?;
@@ -471,7 +604,7 @@ fn get_node_metadata(
     Ok(CouncilNodeMeta::new_with_details(
         validator_name.to_string(),
         None,
-        TendermintValidatorPubKey::Ed25519(pubkey_bytes),
+        consensus_pubkey,
         ConfidentialInit { keypackage },
     ))
 }
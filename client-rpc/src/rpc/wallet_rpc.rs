@@ -8,13 +8,13 @@ use secstr::SecUtf8;
 use chain_core::init::coin::Coin;
 use chain_core::tx::data::address::ExtendedAddr;
 use client_common::{PrivateKey, PublicKey, Result as CommonResult, SecKey};
-use client_core::service::WalletInfo;
+use client_core::service::{AccountPrivKeys, AccountPubKeys, HDAccountType, WalletInfo};
 use client_core::transaction_builder::SignedTransferTransaction;
 use client_core::types::{TransactionChange, WalletBalance, WalletKind};
 use client_core::wallet::{CreateWalletRequest, WalletRequest};
 #[cfg(feature = "experimental")]
 use client_core::MultiSigWalletClient;
-use client_core::{Mnemonic, UnspentTransactions, WalletClient};
+use client_core::{InputSelection, Mnemonic, UnspentTransactions, WalletClient};
 use parity_scale_codec::{Decode, Encode};
 
 use crate::{rpc_error_from_string, to_rpc_error};
@@ -38,6 +38,28 @@ pub trait WalletRpc: Send + Sync {
     #[rpc(name = "wallet_restoreBasic")]
     fn restore_basic(&self, request: CreateWalletRequest, view_key: SecUtf8) -> Result<SecKey>;
 
+    #[rpc(name = "wallet_restoreWatchOnly")]
+    fn restore_watch_only(
+        &self,
+        request: CreateWalletRequest,
+        transfer_xpub: String,
+        staking_xpub: Option<String>,
+        view_key: SecUtf8,
+    ) -> Result<SecKey>;
+
+    #[rpc(name = "wallet_restoreFromXprv")]
+    fn restore_from_xprv(
+        &self,
+        request: CreateWalletRequest,
+        transfer_xprv: SecUtf8,
+        staking_xprv: SecUtf8,
+        viewkey_xprv: SecUtf8,
+    ) -> Result<SecKey>;
+
+    #[rpc(name = "wallet_exportXpub")]
+    fn export_xpub(&self, request: WalletRequest, account: String, private: bool)
+        -> Result<String>;
+
     #[rpc(name = "wallet_delete")]
     fn delete(&self, request: CreateWalletRequest) -> Result<()>;
 
@@ -92,6 +114,7 @@ pub trait WalletRpc: Send + Sync {
         to_address: String,
         amount: Coin,
         view_keys: Vec<String>,
+        input_selection: Option<InputSelection>,
     ) -> Result<String>;
 
     #[rpc(name = "wallet_buildRawTransferTx")]
@@ -226,6 +249,60 @@ where
         Ok(enckey)
     }
 
+    fn restore_watch_only(
+        &self,
+        request: CreateWalletRequest,
+        transfer_xpub: String,
+        staking_xpub: Option<String>,
+        view_key: SecUtf8,
+    ) -> Result<SecKey> {
+        let account_pubkeys = AccountPubKeys::from_xpub(&transfer_xpub, staking_xpub.as_deref())
+            .map_err(to_rpc_error)?;
+        let view_key =
+            PrivateKey::deserialize_from(&hex::decode(view_key.unsecure()).map_err(to_rpc_error)?)
+                .map_err(to_rpc_error)?;
+        self.client
+            .restore_watch_only_wallet(
+                &request.name,
+                &request.passphrase,
+                &account_pubkeys,
+                &view_key,
+            )
+            .map_err(to_rpc_error)
+    }
+
+    fn restore_from_xprv(
+        &self,
+        request: CreateWalletRequest,
+        transfer_xprv: SecUtf8,
+        staking_xprv: SecUtf8,
+        viewkey_xprv: SecUtf8,
+    ) -> Result<SecKey> {
+        let account_privkeys =
+            AccountPrivKeys::from_xprv(&transfer_xprv, &staking_xprv, &viewkey_xprv)
+                .map_err(to_rpc_error)?;
+        self.client
+            .restore_wallet_from_account_privkeys(
+                &request.name,
+                &request.passphrase,
+                &account_privkeys,
+            )
+            .map_err(to_rpc_error)
+    }
+
+    fn export_xpub(
+        &self,
+        request: WalletRequest,
+        account: String,
+        private: bool,
+    ) -> Result<String> {
+        let account_type = HDAccountType::from_str(&account).map_err(to_rpc_error)?;
+        self.client
+            .export_extended_key(&request.name, &request.enckey, account_type, private)
+            .map(|extended_key| extended_key.unsecure().to_owned())
+            .map_err(to_rpc_error)
+    }
+
     fn delete(&self, request: CreateWalletRequest) -> Result<()> {
         self.client
             .delete_wallet(&request.name, &request.passphrase)
@@ -345,6 +422,7 @@ where
         to_address: String,
         amount: Coin,
         view_keys: Vec<String>,
+        input_selection: Option<InputSelection>,
     ) -> Result<String> {
         let address = to_address
             .parse::<ExtendedAddr>()
@@ -363,6 +441,7 @@ where
                 address,
                 &mut view_keys,
                 self.network_id,
+                input_selection,
             )
             .map_err(to_rpc_error)?;
         Ok(hex::encode(tx_id))
@@ -942,6 +1021,68 @@ pub mod tests {
         )
     }
 
+    #[test]
+    fn hdwallet_should_restore_from_extended_keys() {
+        let wallet_rpc = setup_wallet_rpc();
+        let (create_request, wallet_request) = create_wallet_request("Default", "123456");
+        wallet_rpc
+            .create(create_request, WalletKind::HD, Some(24))
+            .unwrap();
+        let transfer_address = wallet_rpc
+            .list_transfer_addresses(wallet_request.clone())
+            .unwrap()[0]
+            .clone();
+        let export = |account: &str, private: bool| {
+            wallet_rpc
+                .export_xpub(wallet_request.clone(), account.to_owned(), private)
+                .unwrap()
+        };
+        let view_key = wallet_rpc
+            .get_view_key(wallet_request.clone(), true)
+            .unwrap();
+
+        let (watch_request, watch_wallet_request) = create_wallet_request("Watch", "123456");
+        wallet_rpc
+            .restore_watch_only(
+                watch_request,
+                export("transfer", false),
+                Some(export("staking", false)),
+                SecUtf8::from(view_key.clone()),
+            )
+            .unwrap();
+        assert_eq!(
+            wallet_rpc
+                .create_transfer_address(watch_wallet_request.clone())
+                .unwrap(),
+            transfer_address
+        );
+        assert!(wallet_rpc
+            .export_xpub(watch_wallet_request, "transfer".to_owned(), true)
+            .is_err());
+
+        let (restore_request, restore_wallet_request) = create_wallet_request("Restored", "123456");
+        wallet_rpc
+            .restore_from_xprv(
+                restore_request,
+                SecUtf8::from(export("transfer", true)),
+                SecUtf8::from(export("staking", true)),
+                SecUtf8::from(export("viewkey", true)),
+            )
+            .unwrap();
+        assert_eq!(
+            wallet_rpc
+                .create_transfer_address(restore_wallet_request.clone())
+                .unwrap(),
+            transfer_address
+        );
+        assert_eq!(
+            wallet_rpc
+                .get_view_key(restore_wallet_request, true)
+                .unwrap(),
+            view_key
+        );
+    }
+
     #[test]
     fn hdwallet_should_create_hd_wallet() {
         let wallet_rpc = setup_wallet_rpc();
@@ -1007,6 +1148,7 @@ pub mod tests {
             addrs[0].clone(),
             Coin::from(1_0000u32),
             vec![viewkey],
+            None,
         );
         assert!(send_result.is_err());
     }
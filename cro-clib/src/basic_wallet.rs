@@ -74,6 +74,7 @@ fn do_cro_basic_create_transfer_address(private: PrivateKey) -> Box<CroAddress>
             };
             Box::new(ret)
         }
+        ExtendedAddr::HashTimeLock(_) => unreachable!("multi-sig address is a tree address"),
     }
 }
 
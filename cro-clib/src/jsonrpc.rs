@@ -217,6 +217,7 @@ unsafe fn create_rpc(
     let options = SyncerOptions {
         enable_fast_forward: false,
         enable_address_recovery: true,
+        address_gap_limit: 20,
         batch_size: 50,
         block_height_ensure: 50,
     };
@@ -7,6 +7,7 @@ use chain_core::state::account::{
 };
 use chain_core::state::tendermint::TendermintValidatorPubKey;
 use chain_core::state::validator::NodeJoinRequestTx;
+use chain_core::tx::fee::Milli;
 use chain_core::tx::{TxAux, TxPublicAux};
 use client_common::{ErrorKind, PrivateKeyAction, Result, ResultExt, Transaction};
 use parity_scale_codec::Encode;
@@ -116,12 +117,13 @@ fn create_encoded_signed_join(
         pubkey,
         ConfidentialInit { keypackage },
     );
-    let transaction: NodeJoinRequestTx = NodeJoinRequestTx {
+    let transaction: NodeJoinRequestTx = NodeJoinRequestTx::new(
         nonce,
-        address: to_address,
+        to_address,
         attributes,
-        node_meta: node_metadata,
-    };
+        node_metadata,
+        Milli::default(),
+    );
     let tx = Transaction::NodejoinTransaction(transaction.clone());
     let from_private = &from_address.privatekey;
     let signature: StakedStateOpWitness = from_private.sign(&tx).map(StakedStateOpWitness::new)?;
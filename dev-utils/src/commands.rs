@@ -4,6 +4,7 @@ mod init_command;
 mod keypackage_command;
 mod run_command;
 mod stop_command;
+mod storage_command;
 mod test_vector_command;
 
 pub use self::genesis_command::GenesisCommand;
@@ -12,4 +13,5 @@ pub use self::init_command::InitCommand;
 pub use self::keypackage_command::KeypackageCommand;
 pub use self::run_command::RunCommand;
 pub use self::stop_command::StopCommand;
+pub use self::storage_command::StorageCommand;
 pub use self::test_vector_command::TestVectorCommand;
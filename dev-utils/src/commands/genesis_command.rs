@@ -17,11 +17,11 @@ use chain_core::state::account::StakedStateDestination;
 use chain_core::state::tendermint::{
     TendermintValidator, TendermintValidatorAddress, TendermintVotePower,
 };
-use chain_core::tx::fee::{LinearFee, Milli};
+use chain_core::tx::fee::{CongestionFee, FeePolicy, LinearFee, Milli, WeightedFee};
 use client_common::tendermint::types::{Genesis, Time};
-use client_common::{ErrorKind, Result, ResultExt};
+use client_common::{Error, ErrorKind, Result, ResultExt};
 
-use crate::commands::genesis_dev_config::GenesisDevConfig;
+use crate::commands::genesis_dev_config::{GenesisDevConfig, InitialFeePolicy};
 use client_core::wallet::syncer::compute_genesis_fingerprint;
 
 #[derive(Debug, StructOpt)]
@@ -330,14 +330,13 @@ pub fn generate_genesis(
 
         dist.insert(*address, (dest, *amount));
     }
-    let constant_fee = Milli::from_str(&genesis_dev_config.initial_fee_policy.base_fee)
-        .chain(|| (ErrorKind::InvalidInput, "Invalid constant fee"))?;
-    let coefficient_fee = Milli::from_str(&genesis_dev_config.initial_fee_policy.per_byte_fee)
-        .chain(|| (ErrorKind::InvalidInput, "Invalid per byte fee"))?;
-    let fee_policy = LinearFee::new(constant_fee, coefficient_fee);
+    let fee_policy = parse_fee_policy(&genesis_dev_config.initial_fee_policy)?;
     let network_params = InitNetworkParameters {
         initial_fee_policy: fee_policy,
         required_council_node_stake: genesis_dev_config.required_council_node_stake,
+        required_community_node_stake: genesis_dev_config
+            .required_community_node_stake
+            .unwrap_or(genesis_dev_config.required_council_node_stake),
         jailing_config: genesis_dev_config.jailing_config,
         slashing_config: genesis_dev_config.slashing_config,
         rewards_config: genesis_dev_config.rewards_config,
@@ -356,6 +355,63 @@ pub fn generate_genesis(
     Ok((encode_upper(genesis_app_hash), config, validators))
 }
 
+fn parse_milli(value: &str, name: &'static str) -> Result<Milli> {
+    Milli::from_str(value).chain(|| (ErrorKind::InvalidInput, format!("Invalid {}", name)))
+}
+
+/// linear fee policy, unless the per input/output fees or the congestion parameters are specified
+fn parse_fee_policy(config: &InitialFeePolicy) -> Result<FeePolicy> {
+    let constant = parse_milli(&config.base_fee, "constant fee")?;
+    let coefficient = parse_milli(&config.per_byte_fee, "per byte fee")?;
+    let weighted = config.per_input_fee.is_some() || config.per_output_fee.is_some();
+    let congestion = config.minimal_fee.is_some()
+        || config.target_block_size.is_some()
+        || config.max_congestion_multiplier.is_some();
+    match (weighted, congestion) {
+        (false, false) => Ok(FeePolicy::Linear(LinearFee::new(constant, coefficient))),
+        (true, false) => {
+            let parse_optional = |value: &Option<String>, name| match value {
+                Some(value) => parse_milli(value, name),
+                None => Ok(Milli::default()),
+            };
+            Ok(FeePolicy::Weighted(WeightedFee {
+                constant,
+                coefficient,
+                per_input: parse_optional(&config.per_input_fee, "per input fee")?,
+                per_output: parse_optional(&config.per_output_fee, "per output fee")?,
+            }))
+        }
+        (false, true) => {
+            let minimal_fee = match &config.minimal_fee {
+                Some(value) => parse_milli(value, "minimal fee")?,
+                None => constant,
+            };
+            let target_block_size = config.target_block_size.chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    "Target block size is required by the congestion fee policy",
+                )
+            })?;
+            let max_multiplier = config.max_congestion_multiplier.as_ref().chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    "Max congestion multiplier is required by the congestion fee policy",
+                )
+            })?;
+            Ok(FeePolicy::Congestion(CongestionFee {
+                base: LinearFee::new(constant, coefficient),
+                minimal_fee,
+                target_block_size,
+                max_multiplier: parse_milli(max_multiplier, "max congestion multiplier")?,
+            }))
+        }
+        (true, true) => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Weighted and congestion fee parameters can't be combined",
+        )),
+    }
+}
+
 fn generate_validators(genesis_dev_config: &GenesisDevConfig) -> Result<Vec<TendermintValidator>> {
     let mut validators: Vec<TendermintValidator> = Vec::new();
     for (redeem_addr, (validator_name, _, validator_pubkey, _confidential_init)) in
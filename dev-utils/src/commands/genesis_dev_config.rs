@@ -9,11 +9,15 @@ use chain_core::init::{
 };
 use chain_core::state::account::{ConfidentialInit, NodeName, NodeSecurityContact};
 use chain_core::state::tendermint::TendermintValidatorPubKey;
+use chain_core::tx::fee::Milli;
 
 #[derive(Deserialize, Debug)]
 pub struct GenesisDevConfig {
     pub distribution: BTreeMap<RedeemAddress, Coin>,
     pub required_council_node_stake: Coin,
+    /// the council node stake if not specified
+    #[serde(default)]
+    pub required_community_node_stake: Option<Coin>,
     pub jailing_config: JailingParameters,
     pub slashing_config: SlashingParameters,
     pub rewards_config: RewardsParameters,
@@ -35,6 +39,7 @@ impl GenesisDevConfig {
         GenesisDevConfig {
             distribution: BTreeMap::new(),
             required_council_node_stake: Coin::new(1_250_000_000_000_000_000).unwrap(),
+            required_community_node_stake: None,
             jailing_config: JailingParameters {
                 block_signing_window: 100,
                 missed_block_threshold: 50,
@@ -49,10 +54,18 @@ impl GenesisDevConfig {
                 monetary_expansion_r0: "0.45".parse().unwrap(),
                 monetary_expansion_tau: 1_4500_0000_0000_0000,
                 monetary_expansion_decay: 999_860,
+                fee_burn_ratio: Milli::default(),
+                fee_treasury_ratio: Milli::default(),
+                treasury_address: None,
             },
             initial_fee_policy: InitialFeePolicy {
                 base_fee: "1.1".to_string(),
                 per_byte_fee: "1.25".to_string(),
+                per_input_fee: None,
+                per_output_fee: None,
+                minimal_fee: None,
+                target_block_size: None,
+                max_congestion_multiplier: None,
             },
             evidence: Evidence {
                 max_age_duration: "5400000000000".into(),
@@ -67,6 +80,21 @@ impl GenesisDevConfig {
 pub struct InitialFeePolicy {
     pub base_fee: String,
     pub per_byte_fee: String,
+    /// weighted fee policy: fee for each transaction input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_input_fee: Option<String>,
+    /// weighted fee policy: fee for each transaction output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_output_fee: Option<String>,
+    /// congestion fee policy: the fee floor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimal_fee: Option<String>,
+    /// congestion fee policy: the average block size (in bytes) above which the fee goes up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_block_size: Option<u64>,
+    /// congestion fee policy: the upper bound of the fee multiplier
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_congestion_multiplier: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -0,0 +1,579 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bit_vec::BitVec;
+use parity_scale_codec::Decode;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer as _, Serialize};
+use serde_json::{json, Value};
+use structopt::StructOpt;
+
+use chain_abci::app::ChainNodeState;
+use chain_core::common::{MerkleTree, H256, HASH_SIZE_256};
+use chain_core::compute_app_hash;
+use chain_core::state::account::StakedState;
+use chain_core::state::governance::UpgradePlan;
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::state::ChainState;
+use chain_core::TxCreation;
+use chain_storage::buffer::{flush_storage, KVBuffer};
+use chain_storage::jellyfish::{compute_staking_root, has_staking_version, iter_stakings, Version};
+use chain_storage::{
+    LookupItem, Storage, StorageConfig, StorageType, CHAIN_ID_KEY, COL_APP_HASHS, COL_APP_STATES,
+    COL_BODIES, COL_ENCLAVE_TX, COL_EXTRA, COL_MERKLE_PROOFS, COL_NODE_INFO, COL_SPENT_TRIE_NODE,
+    COL_SPENT_TRIE_STALED, COL_STAKING_VERSIONS, COL_TRIE_NODE, COL_TRIE_STALED, COL_TX_META,
+    COL_WITNESS, LAST_STATE_KEY, TX_SPENT_KEY_SUFFIX, UPGRADE_HALT_KEY,
+};
+use client_common::{Error, ErrorKind, Result, ResultExt};
+
+/// column names used in the exported records
+const COLUMNS: [(&str, u32); 14] = [
+    ("tx_meta", COL_TX_META),
+    ("witness", COL_WITNESS),
+    ("bodies", COL_BODIES),
+    ("extra", COL_EXTRA),
+    ("node_info", COL_NODE_INFO),
+    ("merkle_proofs", COL_MERKLE_PROOFS),
+    ("app_hashes", COL_APP_HASHS),
+    ("app_states", COL_APP_STATES),
+    ("enclave_tx", COL_ENCLAVE_TX),
+    ("trie_node", COL_TRIE_NODE),
+    ("trie_staled", COL_TRIE_STALED),
+    ("staking_versions", COL_STAKING_VERSIONS),
+    ("spent_trie_node", COL_SPENT_TRIE_NODE),
+    ("spent_trie_staled", COL_SPENT_TRIE_STALED),
+];
+
+/// the number of records written to the database at once during the import
+const IMPORT_BATCH_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ExportFormat::Json),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(format!("unknown format: {} (json or csv)", s)),
+        }
+    }
+}
+
+/// Commands for inspecting the chain-abci storage (opened read-only) and importing it offline
+#[derive(Debug, StructOpt)]
+pub enum StorageCommand {
+    #[structopt(
+        name = "export",
+        about = "Dump a column (or all columns) of the chain-abci storage"
+    )]
+    Export {
+        #[structopt(
+            short = "d",
+            long = "data",
+            default_value = ".cro-storage/",
+            help = "chain-abci data storage directory"
+        )]
+        data: String,
+        #[structopt(
+            short = "c",
+            long = "column",
+            default_value = "all",
+            help = "Column name (tx_meta, witness, bodies, extra, node_info, merkle_proofs, app_hashes, app_states, enclave_tx, trie_node, trie_staled, staking_versions) or all"
+        )]
+        column: String,
+        #[structopt(
+            short = "f",
+            long = "format",
+            default_value = "json",
+            help = "Output format: json or csv"
+        )]
+        format: ExportFormat,
+        #[structopt(short = "o", long = "output", help = "Output file (stdout if not set)")]
+        output: Option<PathBuf>,
+    },
+    #[structopt(
+        name = "export-stakings",
+        about = "Dump the staking states in the merkle trie at a version (or block height)"
+    )]
+    ExportStakings {
+        #[structopt(
+            short = "d",
+            long = "data",
+            default_value = ".cro-storage/",
+            help = "chain-abci data storage directory"
+        )]
+        data: String,
+        #[structopt(
+            long = "staking-version",
+            help = "Staking trie version (the last one if neither version nor height is set)"
+        )]
+        version: Option<Version>,
+        #[structopt(long = "height", help = "Block height", conflicts_with = "version")]
+        height: Option<u64>,
+        #[structopt(
+            short = "f",
+            long = "format",
+            default_value = "json",
+            help = "Output format: json or csv"
+        )]
+        format: ExportFormat,
+        #[structopt(short = "o", long = "output", help = "Output file (stdout if not set)")]
+        output: Option<PathBuf>,
+    },
+    #[structopt(
+        name = "verify",
+        about = "Verify the staking trie root and the app hash against the stored ones"
+    )]
+    Verify {
+        #[structopt(
+            short = "d",
+            long = "data",
+            default_value = ".cro-storage/",
+            help = "chain-abci data storage directory"
+        )]
+        data: String,
+        #[structopt(
+            long = "height",
+            help = "Block height (the last committed one if not set; historical heights need the app states stored)"
+        )]
+        height: Option<u64>,
+    },
+    #[structopt(
+        name = "import",
+        about = "Import the records exported (in json) from all columns into a fresh storage"
+    )]
+    Import {
+        #[structopt(
+            short = "d",
+            long = "data",
+            help = "New chain-abci data storage directory"
+        )]
+        data: String,
+        #[structopt(short = "i", long = "input", help = "Exported json file")]
+        input: PathBuf,
+    },
+}
+
+/// An exported key-value entry (key and value are hex-encoded)
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    column: String,
+    key: String,
+    value: String,
+    /// the decoded value for the known entries (only informational, not used in the import)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    decoded: Option<Value>,
+}
+
+/// The result of the integrity verification
+#[derive(Debug, Serialize)]
+struct VerifyReport {
+    height: BlockHeight,
+    staking_version: Version,
+    stakings: usize,
+    stored_app_hash: String,
+    stored_account_root: String,
+    computed_account_root: String,
+    computed_app_hash: String,
+    valid: bool,
+}
+
+impl StorageCommand {
+    pub fn execute(&self) -> Result<()> {
+        match self {
+            StorageCommand::Export {
+                data,
+                column,
+                format,
+                output,
+            } => {
+                let storage = open_read_only_storage(data);
+                let columns = if column == "all" {
+                    COLUMNS.to_vec()
+                } else {
+                    vec![COLUMNS
+                        .iter()
+                        .find(|(name, _)| *name == column.as_str())
+                        .cloned()
+                        .chain(|| (ErrorKind::InvalidInput, "Unknown column"))?]
+                };
+                let records = columns.into_iter().flat_map(|(name, col)| {
+                    storage.iter_column(col).map(move |(key, value)| Record {
+                        column: name.to_owned(),
+                        key: hex::encode(&key),
+                        value: hex::encode(&value),
+                        decoded: decode_entry(col, &key, &value),
+                    })
+                });
+                let mut writer = open_output(output)?;
+                match format {
+                    ExportFormat::Json => write_json_array(&mut writer, records)?,
+                    ExportFormat::Csv => {
+                        let rows =
+                            records.map(|record| vec![record.column, record.key, record.value]);
+                        write_csv(&mut writer, &["column", "key", "value"], rows)?
+                    }
+                }
+                Ok(())
+            }
+            StorageCommand::ExportStakings {
+                data,
+                version,
+                height,
+                format,
+                output,
+            } => {
+                let storage = open_read_only_storage(data);
+                let version = match (version, height) {
+                    (Some(version), _) => *version,
+                    (None, Some(height)) => storage
+                        .get_historical_staking_version(BlockHeight::new(*height))
+                        .chain(|| {
+                            (
+                                ErrorKind::InvalidInput,
+                                "Staking version of the height not found (or pruned)",
+                            )
+                        })?,
+                    (None, None) => last_state(&storage)?.staking_version,
+                };
+                let stakings = stakings_at(&storage, version)?;
+                let mut writer = open_output(output)?;
+                match format {
+                    ExportFormat::Json => write_json_array(&mut writer, stakings)?,
+                    ExportFormat::Csv => {
+                        let rows = stakings.map(|staking| {
+                            vec![
+                                staking.address.to_string(),
+                                staking.nonce.to_string(),
+                                u64::from(staking.bonded).to_string(),
+                                u64::from(staking.unbonded).to_string(),
+                                staking.unbonded_from.to_string(),
+                                u64::from(staking.delegated()).to_string(),
+                                staking.is_jailed().to_string(),
+                            ]
+                        });
+                        write_csv(
+                            &mut writer,
+                            &[
+                                "address",
+                                "nonce",
+                                "bonded",
+                                "unbonded",
+                                "unbonded_from",
+                                "delegated",
+                                "jailed",
+                            ],
+                            rows,
+                        )?
+                    }
+                }
+                Ok(())
+            }
+            StorageCommand::Verify { data, height } => {
+                let storage = open_read_only_storage(data);
+                let report = verify(&storage, height.map(BlockHeight::new))?;
+                write_json(&mut io::stdout(), &report)?;
+                if report.valid {
+                    Ok(())
+                } else {
+                    Err(Error::new(
+                        ErrorKind::VerifyError,
+                        "Stored app hash doesn't match the storage content",
+                    ))
+                }
+            }
+            StorageCommand::Import { data, input } => {
+                let file =
+                    File::open(input).chain(|| (ErrorKind::IoError, "Unable to open input"))?;
+                let mut storage = open_storage(data);
+                if COLUMNS
+                    .iter()
+                    .any(|(_, col)| storage.iter_column(*col).next().is_some())
+                {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "Target storage isn't empty",
+                    ));
+                }
+                let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(file));
+                let imported = deserializer
+                    .deserialize_seq(RecordImporter {
+                        storage: &mut storage,
+                    })
+                    .and_then(|imported| deserializer.end().map(|_| imported))
+                    .chain(|| (ErrorKind::DeserializationError, "Unable to import records"))?;
+                println!("imported {} records", imported);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Writes the exported records (a json array) into the storage as they are read, in batches
+struct RecordImporter<'a> {
+    storage: &'a mut Storage,
+}
+
+impl<'a, 'de> Visitor<'de> for RecordImporter<'a> {
+    type Value = usize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("an array of exported records")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<usize, A::Error> {
+        let mut imported = 0;
+        let mut buffer = KVBuffer::new();
+        while let Some(record) = seq.next_element::<Record>()? {
+            let (_, col) = COLUMNS
+                .iter()
+                .find(|(name, _)| *name == record.column)
+                .ok_or_else(|| de::Error::custom("unknown column in the record"))?;
+            let key = hex::decode(&record.key).map_err(de::Error::custom)?;
+            let value = hex::decode(&record.value).map_err(de::Error::custom)?;
+            buffer.insert((*col, key), Some(value));
+            imported += 1;
+            if buffer.len() >= IMPORT_BATCH_SIZE {
+                flush_storage(self.storage, std::mem::take(&mut buffer))
+                    .map_err(de::Error::custom)?;
+            }
+        }
+        flush_storage(self.storage, buffer).map_err(de::Error::custom)?;
+        Ok(imported)
+    }
+}
+
+fn open_storage(data: &str) -> Storage {
+    Storage::new(&StorageConfig::new(data, StorageType::Node))
+}
+
+fn open_read_only_storage(data: &str) -> Storage {
+    Storage::new_read_only(&StorageConfig::new(data, StorageType::Node))
+}
+
+fn open_output(output: &Option<PathBuf>) -> Result<Box<dyn Write>> {
+    match output {
+        Some(path) => {
+            let file =
+                File::create(path).chain(|| (ErrorKind::IoError, "Unable to create output"))?;
+            Ok(Box::new(BufWriter::new(file)))
+        }
+        None => Ok(Box::new(io::stdout())),
+    }
+}
+
+fn write_json<T: Serialize>(writer: &mut impl Write, value: &T) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *writer, value)
+        .chain(|| (ErrorKind::SerializationError, "Unable to serialize records"))?;
+    writeln!(writer).chain(|| (ErrorKind::IoError, "Unable to write output"))
+}
+
+/// writes the items as a json array, one item per line (without collecting them)
+fn write_json_array<T: Serialize>(
+    writer: &mut impl Write,
+    items: impl Iterator<Item = T>,
+) -> Result<()> {
+    writer
+        .write_all(b"[")
+        .chain(|| (ErrorKind::IoError, "Unable to write output"))?;
+    for (i, item) in items.enumerate() {
+        let separator: &[u8] = if i == 0 { b"\n" } else { b",\n" };
+        writer
+            .write_all(separator)
+            .chain(|| (ErrorKind::IoError, "Unable to write output"))?;
+        serde_json::to_writer(&mut *writer, &item)
+            .chain(|| (ErrorKind::SerializationError, "Unable to serialize records"))?;
+    }
+    writeln!(writer, "\n]").chain(|| (ErrorKind::IoError, "Unable to write output"))
+}
+
+fn write_csv(
+    writer: &mut impl Write,
+    header: &[&str],
+    rows: impl Iterator<Item = Vec<String>>,
+) -> Result<()> {
+    writeln!(writer, "{}", header.join(","))
+        .chain(|| (ErrorKind::IoError, "Unable to write output"))?;
+    for row in rows {
+        writeln!(writer, "{}", row.join(","))
+            .chain(|| (ErrorKind::IoError, "Unable to write output"))?;
+    }
+    Ok(())
+}
+
+fn last_state(storage: &Storage) -> Result<ChainNodeState> {
+    let raw = storage
+        .get_last_app_state()
+        .chain(|| (ErrorKind::InvalidInput, "No committed state in the storage"))?;
+    ChainNodeState::decode_stored(&raw)
+        .chain(|| (ErrorKind::DeserializationError, "Invalid stored node state"))
+}
+
+fn stakings_at(
+    storage: &Storage,
+    version: Version,
+) -> Result<impl Iterator<Item = StakedState> + '_> {
+    if !has_staking_version(storage, version) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Staking version not found in the merkle trie (or pruned)",
+        ));
+    }
+    Ok(iter_stakings(storage, version))
+}
+
+/// recomputes the staking trie root and the app hash of the block at the height
+fn verify(storage: &Storage, height: Option<BlockHeight>) -> Result<VerifyReport> {
+    let state = last_state(storage)?;
+    let height = height.unwrap_or(state.last_block_height);
+    let stored_app_hash = storage
+        .get_historical_app_hash(height)
+        .chain(|| (ErrorKind::InvalidInput, "App hash of the height not found"))?;
+    let (top_level, staking_version) = if height == state.last_block_height {
+        if state.last_apphash != stored_app_hash {
+            return Err(Error::new(
+                ErrorKind::VerifyError,
+                "Last node state doesn't match the stored app hash",
+            ));
+        }
+        (state.top_level, state.staking_version)
+    } else {
+        let raw = storage.get_historical_state(height).chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                "Historical state not stored (no tx query or pruned)",
+            )
+        })?;
+        let top_level = ChainState::decode(&mut raw.as_slice())
+            .chain(|| (ErrorKind::DeserializationError, "Invalid historical state"))?;
+        let version = storage.get_historical_staking_version(height).chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                "Staking version of the height not found",
+            )
+        })?;
+        (top_level, version)
+    };
+
+    let stakings = stakings_at(storage, staking_version)?.collect::<Vec<_>>();
+    let computed_account_root = compute_staking_root(&stakings);
+    let raw_tree = storage
+        .lookup_item(LookupItem::TxsMerkle, &stored_app_hash)
+        .chain(|| (ErrorKind::InvalidInput, "Transaction merkle tree not found"))?;
+    let tree = MerkleTree::<H256>::decode(&mut raw_tree.as_slice()).chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            "Invalid transaction merkle tree",
+        )
+    })?;
+    let computed_app_hash = compute_app_hash(
+        &tree,
+        &ChainState {
+            account_root: computed_account_root,
+            ..top_level.clone()
+        },
+    );
+    Ok(VerifyReport {
+        height,
+        staking_version,
+        stakings: stakings.len(),
+        stored_app_hash: hex::encode(stored_app_hash),
+        stored_account_root: hex::encode(top_level.account_root),
+        computed_account_root: hex::encode(computed_account_root),
+        computed_app_hash: hex::encode(computed_app_hash),
+        valid: computed_account_root == top_level.account_root
+            && computed_app_hash == stored_app_hash,
+    })
+}
+
+/// human-readable form of the known entries
+fn decode_entry(col: u32, key: &[u8], value: &[u8]) -> Option<Value> {
+    match col {
+        COL_TX_META if key.len() == HASH_SIZE_256 => {
+            let spent = BitVec::from_bytes(value).iter().collect::<Vec<_>>();
+            Some(json!({ "spent": spent }))
+        }
+        COL_TX_META
+            if key.len() > HASH_SIZE_256
+                && key[HASH_SIZE_256..].starts_with(TX_SPENT_KEY_SUFFIX) =>
+        {
+            let height = BlockHeight::decode(&mut &value[..]).ok()?;
+            Some(json!({ "spent_height": height }))
+        }
+        COL_TX_META => {
+            let created = TxCreation::decode(&mut &value[..]).ok()?;
+            Some(json!({
+                "block_height": created.block_height,
+                "block_time": created.block_time,
+            }))
+        }
+        COL_APP_HASHS => {
+            let height = BlockHeight::decode(&mut &key[..]).ok()?;
+            Some(json!({ "height": height }))
+        }
+        COL_STAKING_VERSIONS => {
+            let height = BlockHeight::decode(&mut &key[..]).ok()?;
+            let version = Version::decode(&mut &value[..]).ok()?;
+            Some(json!({ "height": height, "version": version }))
+        }
+        COL_APP_STATES => {
+            let state = ChainState::decode(&mut &value[..]).ok()?;
+            serde_json::to_value(&state).ok()
+        }
+        COL_NODE_INFO if key == LAST_STATE_KEY => {
+            let state = ChainNodeState::decode_stored(value).ok()?;
+            serde_json::to_value(&state).ok()
+        }
+        COL_EXTRA if key == CHAIN_ID_KEY => {
+            Some(Value::String(String::from_utf8_lossy(value).into_owned()))
+        }
+        COL_EXTRA if key == UPGRADE_HALT_KEY => {
+            let plan = UpgradePlan::decode(&mut &value[..]).ok()?;
+            serde_json::to_value(&plan).ok()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parity_scale_codec::Encode;
+
+    #[test]
+    fn check_decode_entry() {
+        let mut spent = BitVec::from_elem(3, false);
+        spent.set(1, true);
+        assert_eq!(
+            decode_entry(COL_TX_META, &[0u8; 32], &spent.to_bytes()),
+            Some(json!({ "spent": [false, true, false, false, false, false, false, false] }))
+        );
+        assert_eq!(
+            decode_entry(
+                COL_STAKING_VERSIONS,
+                &BlockHeight::new(10).encode(),
+                &3u64.encode()
+            ),
+            Some(json!({ "height": 10, "version": 3 }))
+        );
+        let mut spent_key = vec![0u8; 32];
+        spent_key.extend_from_slice(TX_SPENT_KEY_SUFFIX);
+        spent_key.extend_from_slice(&1u16.encode());
+        assert_eq!(
+            decode_entry(COL_TX_META, &spent_key, &BlockHeight::new(7).encode()),
+            Some(json!({ "spent_height": 7 }))
+        );
+        assert_eq!(decode_entry(COL_BODIES, &[0u8; 32], &[1, 2, 3]), None);
+        assert_eq!("csv".parse::<ExportFormat>(), Ok(ExportFormat::Csv));
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+}
@@ -17,6 +17,7 @@ use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
 use chain_core::tx::data::{Tx, TxId};
+use chain_core::tx::fee::Milli;
 use chain_core::tx::witness::tree::RawXOnlyPubkey;
 use chain_core::tx::witness::{TxInWitness, TxWitness};
 use chain_core::tx::TransactionId;
@@ -327,6 +328,7 @@ impl VectorFactory {
                 tendermint_validator_pubkey.clone(),
                 mock_confidential_init(),
             )),
+            Milli::default(),
         );
         let txid = tx.id();
         let witness = sign_key
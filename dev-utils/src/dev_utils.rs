@@ -3,7 +3,8 @@ use structopt::StructOpt;
 use client_common::Result;
 
 use crate::commands::{
-    GenesisCommand, InitCommand, KeypackageCommand, RunCommand, StopCommand, TestVectorCommand,
+    GenesisCommand, InitCommand, KeypackageCommand, RunCommand, StopCommand, StorageCommand,
+    TestVectorCommand,
 };
 
 const NETWORKS: [&str; 3] = ["devnet", "testnet", "mainnet"];
@@ -69,6 +70,16 @@ pub enum DevUtils {
         #[structopt(subcommand)]
         keypackage_command: KeypackageCommand,
     },
+
+    /// Used for inspecting the chain-abci storage offline
+    #[structopt(
+        name = "storage",
+        about = "Commands for exporting, verifying and importing the chain-abci storage"
+    )]
+    Storage {
+        #[structopt(subcommand)]
+        storage_command: StorageCommand,
+    },
 }
 
 impl DevUtils {
@@ -92,6 +103,7 @@ impl DevUtils {
                 test_vectors_command.execute()
             }
             DevUtils::Keypackage { keypackage_command } => keypackage_command.execute(),
+            DevUtils::Storage { storage_command } => storage_command.execute(),
         }
     }
 }
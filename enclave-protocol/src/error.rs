@@ -4,6 +4,7 @@ pub enum Error {
     HexIdMisMatch,
     EmptySealedLog,
     EmptyRequestAccount,
+    InputsCreationMisMatch,
 }
 
 impl fmt::Display for Error {
@@ -13,6 +14,7 @@ impl fmt::Display for Error {
             HexIdMisMatch => write!(f, "hex id mismatch"),
             EmptySealedLog => write!(f, "sealed log is empty"),
             EmptyRequestAccount => write!(f, "request account is empty"),
+            InputsCreationMisMatch => write!(f, "inputs creation info doesn't match inputs"),
         }
     }
 }
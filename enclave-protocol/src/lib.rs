@@ -21,12 +21,12 @@ use chain_core::state::account::DepositBondTx;
 use chain_core::state::account::StakedState;
 use chain_core::state::account::StakedStateOpWitness;
 use chain_core::state::account::WithdrawUnbondedTx;
-use chain_core::tx::data::input::TxoPointer;
+use chain_core::tx::data::input::{TxoPointer, TxoSize};
 use chain_core::tx::data::{txid_hash, Tx, TxId};
 use chain_core::tx::witness::TxWitness;
 use chain_core::tx::TxObfuscated;
 use chain_core::tx::{fee::Fee, TxEnclaveAux};
-use chain_core::ChainInfo;
+use chain_core::{ChainInfo, TxCreation};
 use chain_tx_validation::TxWithOutputs;
 use secp256k1::{
     key::{PublicKey, SecretKey},
@@ -50,6 +50,8 @@ pub struct IntraEncryptRequest {
     pub sealed_enc_request: SealedLog,
     /// transaction inputs (if any)
     pub tx_inputs: Option<Vec<SealedLog>>,
+    /// the blocks in which the transaction inputs were created (for relative timelocks)
+    pub inputs_created: Vec<TxCreation>,
     /// related account if any
     pub account: Option<StakedState>,
     /// last chain info
@@ -72,6 +74,7 @@ impl IntraEnclaveRequest {
         tx: TxEnclaveAux,
         info: ChainInfo,
         tx_inputs: Vec<SealedLog>,
+        inputs_created: Vec<TxCreation>,
     ) -> Self {
         Self::ValidateTx {
             tx_inputs: Some(tx_inputs),
@@ -79,6 +82,7 @@ impl IntraEnclaveRequest {
                 tx,
                 account: None,
                 info,
+                inputs_created,
             }),
         }
     }
@@ -88,10 +92,16 @@ impl IntraEnclaveRequest {
         info: ChainInfo,
         account: Option<StakedState>,
         tx_inputs: Vec<SealedLog>,
+        inputs_created: Vec<TxCreation>,
     ) -> Self {
         Self::ValidateTx {
             tx_inputs: Some(tx_inputs),
-            request: Box::new(VerifyTxRequest { tx, account, info }),
+            request: Box::new(VerifyTxRequest {
+                tx,
+                account,
+                info,
+                inputs_created,
+            }),
         }
     }
 
@@ -102,6 +112,7 @@ impl IntraEnclaveRequest {
                 tx,
                 account: Some(account),
                 info,
+                inputs_created: Vec::new(),
             }),
         }
     }
@@ -117,11 +128,10 @@ pub fn is_basic_valid_tx_request(
         return Err(PError::HexIdMisMatch);
     }
     match request.tx {
-        TxEnclaveAux::DepositStakeTx { .. } => match tx_inputs {
-            Some(ref i) if !i.is_empty() => Ok(()),
-            _ => Err(PError::EmptySealedLog),
-        },
-        TxEnclaveAux::TransferTx { .. } => match tx_inputs {
+        TxEnclaveAux::DepositStakeTx { .. } | TxEnclaveAux::TransferTx { .. } => match tx_inputs {
+            Some(ref i) if i.len() != request.inputs_created.len() => {
+                Err(PError::InputsCreationMisMatch)
+            }
             Some(ref i) if !i.is_empty() => Ok(()),
             _ => Err(PError::EmptySealedLog),
         },
@@ -158,6 +168,8 @@ pub struct VerifyTxRequest {
     pub tx: TxEnclaveAux,
     pub account: Option<StakedState>,
     pub info: ChainInfo,
+    /// the blocks in which the transaction inputs were created (in the same order as the inputs)
+    pub inputs_created: Vec<TxCreation>,
 }
 
 /// TQE's encryption request
@@ -171,6 +183,8 @@ pub struct QueryEncryptRequest {
     pub tx_size: u32,
     /// transaction inputs (if any; for deposits/transfers)
     pub tx_inputs: Option<Vec<TxoPointer>>,
+    /// number of the created outputs (for transfers/withdraws; used in the fee calculation)
+    pub no_of_outputs: TxoSize,
     /// account sig (if any; for withdraws)
     pub op_sig: Option<StakedStateOpWitness>,
 }
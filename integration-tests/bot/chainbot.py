@@ -180,6 +180,7 @@ def app_state_cfg(cfg):
     return {
         "distribution": gen_distribution(cfg),
         "required_council_node_stake": "100000000",  # 10 coins
+        "required_community_node_stake": "100000000",  # 10 coins
         "jailing_config": {
             "block_signing_window": 20,
             "missed_block_threshold": 5
@@ -240,6 +240,9 @@ class Staking:
     def state(self, address, name=DEFAULT_WALLET):
         return self.client.call('staking_state', name, fix_address(address))
 
+    def history(self, address, start=None, limit=None):
+        return self.client.call('staking_history', fix_address(address), start, limit)
+
     def unbond(self, address, amount, name=DEFAULT_WALLET, enckey=None):
         return self.client.call('staking_unbondStake', [name, enckey or get_enckey()], fix_address(address), str(amount))
 
@@ -253,14 +256,27 @@ class Staking:
     def unjail(self, address, name=DEFAULT_WALLET, enckey=None):
         return self.client.call('staking_unjail', [name, enckey or get_enckey()], fix_address(address))
 
-    def join(self, node_name, node_pubkey, node_staking_address, keypackage, name=DEFAULT_WALLET, enckey=None):
+    def join(self, node_name, node_pubkey, node_staking_address, keypackage, commission_rate=None, name=DEFAULT_WALLET, enckey=None):
         return self.client.call(
             'staking_validatorNodeJoin',
             [name, enckey or get_enckey()],
             node_name,
             node_pubkey,
             fix_address(node_staking_address),
-            keypackage
+            keypackage,
+            commission_rate
+        )
+
+    def update_node(self, node_staking_address, node_name=None, security_contact=None, node_pubkey=None, commission_rate=None, p2p_node_id=None, name=DEFAULT_WALLET, enckey=None):
+        return self.client.call(
+            'staking_validatorNodeMetadataUpdate',
+            [name, enckey or get_enckey()],
+            fix_address(node_staking_address),
+            node_name,
+            security_contact,
+            node_pubkey,
+            commission_rate,
+            p2p_node_id
         )
 
     def build_raw_transfer_tx(self, to_address, amount, name=DEFAULT_WALLET,  enckey=None, viewkeys=[]):
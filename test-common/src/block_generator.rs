@@ -32,7 +32,7 @@ use chain_core::state::tendermint::{
     TendermintValidatorAddress, TendermintValidatorPubKey, TendermintVotePower,
 };
 use chain_core::state::ChainState;
-use chain_core::tx::fee::{LinearFee, Milli};
+use chain_core::tx::fee::{FeePolicy, LinearFee, Milli};
 use chain_core::tx::TxAux;
 use chain_storage::buffer::MemStore;
 use chain_storage::jellyfish::{put_stakings, StakingGetter};
@@ -272,6 +272,8 @@ impl TestnetSpec {
                 self.expansion_cap,
             ),
             council_nodes,
+            upgrade_plan: None,
+            app_version: chain_core::APP_VERSION,
         }
     }
 
@@ -288,13 +290,13 @@ impl TestnetSpec {
             .validate_config_get_genesis(genesis_seconds)
             .expect("distribution validation error");
         let account_root = put_stakings(&mut store, 0, genesis_state.accounts.iter()).unwrap();
-        let network_params = NetworkParameters::Genesis(config.network_params.clone());
-        let app_hash = compute_app_hash(
-            &MerkleTree::empty(),
-            &account_root,
-            &genesis_state.rewards_pool,
-            &network_params,
+        let top_level = ChainState::genesis(
+            account_root,
+            genesis_state.rewards_pool,
+            NetworkParameters::Genesis(config.network_params.clone()),
+            genesis_state.app_version,
         );
+        let app_hash = compute_app_hash(&MerkleTree::empty(), &top_level);
 
         let share = self.share();
         let validators = self
@@ -331,8 +333,9 @@ impl TestnetSpec {
 
         let staking_table = StakingTable::from_genesis(
             &StakingGetter::new(&store, 0),
-            network_params.get_required_council_node_stake(),
-            network_params.get_max_validators(),
+            top_level.network_params.get_required_council_node_stake(),
+            top_level.network_params.get_required_community_node_stake(),
+            top_level.network_params.get_max_validators(),
             &genesis_state
                 .validators
                 .iter()
@@ -344,11 +347,10 @@ impl TestnetSpec {
             app_hash,
             genesis_seconds,
             self.max_evidence_age,
-            account_root,
-            genesis_state.rewards_pool,
-            network_params,
+            top_level,
             staking_table,
             genesis_state.isv_svn,
+            genesis_state.upgrade_plan,
         );
 
         (genesis, state)
@@ -614,11 +616,12 @@ fn gen_network_params(
     expansion_cap: Coin,
 ) -> params::InitNetworkParameters {
     params::InitNetworkParameters {
-        initial_fee_policy: LinearFee {
+        initial_fee_policy: FeePolicy::Linear(LinearFee {
             constant: base_fee,
             coefficient: per_byte_fee,
-        },
+        }),
         required_council_node_stake: Coin::unit(),
+        required_community_node_stake: Coin::unit(),
         jailing_config: params::JailingParameters {
             block_signing_window: 100,
             missed_block_threshold: 50,
@@ -633,6 +636,9 @@ fn gen_network_params(
             monetary_expansion_r0: "0.5".parse().unwrap(),
             monetary_expansion_tau: 166_666_600,
             monetary_expansion_decay: 999_860,
+            fee_burn_ratio: Milli::default(),
+            fee_treasury_ratio: Milli::default(),
+            treasury_address: None,
         },
         max_validators: 50,
     }
@@ -22,15 +22,16 @@ use chain_core::init::config::{
     SlashRatio, SlashingParameters,
 };
 use chain_core::state::account::{
-    ConfidentialInit, CouncilNodeMeta, NodeMetadata, NodeName, NodeSecurityContact, NodeState,
-    StakedState, StakedStateAddress, StakedStateDestination, StakedStateOpAttributes,
-    StakedStateOpWitness, UnbondTx, Validator as ChainValidator,
+    ConfidentialInit, CouncilNodeMeta, NodeCommonInfo, NodeMetadata, NodeName, NodeSecurityContact,
+    NodeState, StakedState, StakedStateAddress, StakedStateDestination, StakedStateOpAttributes,
+    StakedStateOpWitness, UnbondTx, UnjailTx, Validator as ChainValidator,
 };
 use chain_core::state::tendermint::{
     TendermintValidatorAddress, TendermintValidatorPubKey, TendermintVotePower,
 };
 use chain_core::state::validator::NodeJoinRequestTx;
-use chain_core::tx::fee::{LinearFee, Milli};
+use chain_core::state::ChainState;
+use chain_core::tx::fee::{FeePolicy, LinearFee, Milli};
 use chain_core::tx::witness::EcdsaSignature;
 use chain_core::tx::{data::TxId, TransactionId, TxAux, TxPublicAux};
 use chain_storage::buffer::Get;
@@ -92,11 +93,12 @@ pub fn create_storage() -> Storage {
 
 pub fn get_init_network_params(expansion_cap: Coin) -> InitNetworkParameters {
     InitNetworkParameters {
-        initial_fee_policy: LinearFee::new(
+        initial_fee_policy: FeePolicy::Linear(LinearFee::new(
             Milli::try_new(0, 0).unwrap(),
             Milli::try_new(0, 0).unwrap(),
-        ),
+        )),
         required_council_node_stake: Coin::unit(),
+        required_community_node_stake: Coin::unit(),
         jailing_config: JailingParameters {
             block_signing_window: 5,
             missed_block_threshold: 1,
@@ -111,6 +113,9 @@ pub fn get_init_network_params(expansion_cap: Coin) -> InitNetworkParameters {
             monetary_expansion_r0: "0.5".parse().unwrap(),
             monetary_expansion_tau: 1_4500_0000_0000_0000,
             monetary_expansion_decay: 999_860,
+            fee_burn_ratio: Milli::default(),
+            fee_treasury_ratio: Milli::default(),
+            treasury_address: None,
         },
         max_validators: 50,
     }
@@ -134,6 +139,15 @@ pub fn mock_council_node_meta(consensus_pubkey: TendermintValidatorPubKey) -> Co
     )
 }
 
+pub fn mock_community_node() -> NodeMetadata {
+    NodeMetadata::CommunityNode(NodeCommonInfo {
+        name: "no-name".to_string(),
+        security_contact: None,
+        confidential_init: mock_confidential_init(),
+        p2p_node_id: None,
+    })
+}
+
 pub fn mock_confidential_init() -> ConfidentialInit {
     ConfidentialInit {
         keypackage: KEYPACKAGE_VECTOR.to_vec(),
@@ -267,9 +281,12 @@ impl ChainEnv {
         let new_account_root = storage.put_stakings(0, &genesis_state.accounts);
         let genesis_app_hash = compute_app_hash(
             &MerkleTree::empty(),
-            &new_account_root,
-            &genesis_state.rewards_pool,
-            &NetworkParameters::Genesis(init_network_params),
+            &ChainState::genesis(
+                new_account_root,
+                genesis_state.rewards_pool,
+                NetworkParameters::Genesis(init_network_params),
+                genesis_state.app_version,
+            ),
         );
         (
             ChainEnv {
@@ -307,6 +324,7 @@ impl ChainEnv {
             self.accounts[account_index].staking_address(),
             StakedStateOpAttributes::new(0),
             NodeMetadata::CouncilNode(self.council_nodes[account_index].1.clone()),
+            Milli::default(),
         );
         let secp = Secp256k1::new();
         let witness = StakedStateOpWitness::new(get_ecdsa_witness(
@@ -333,6 +351,21 @@ impl ChainEnv {
         TxAux::PublicTx(TxPublicAux::UnbondStakeTx(tx, witness))
     }
 
+    pub fn unjail_tx(&self, nonce: u64, account_index: usize) -> TxAux {
+        let tx = UnjailTx {
+            nonce,
+            address: self.accounts[account_index].staking_address(),
+            attributes: StakedStateOpAttributes::new(0),
+        };
+        let secp = Secp256k1::new();
+        let witness = StakedStateOpWitness::new(get_ecdsa_witness(
+            &secp,
+            &tx.id(),
+            &self.accounts[account_index].secret_key,
+        ));
+        TxAux::PublicTx(TxPublicAux::UnjailTx(tx, witness))
+    }
+
     pub fn req_init_chain(&self) -> RequestInitChain {
         let share = Coin::new(u64::from(self.dist_coin) / self.accounts.len() as u64).unwrap();
         let validators = self